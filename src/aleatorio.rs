@@ -0,0 +1,85 @@
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+thread_local! {
+    /// Generador de `RANDOM()`, uno por hilo para que los hilos que evalúan un lote en
+    /// paralelo no compitan por él.
+    static GENERADOR: RefCell<GeneradorAleatorio> =
+        RefCell::new(GeneradorAleatorio::desde_entropia());
+}
+
+/// Devuelve un número decimal uniforme en el intervalo `[0, 1)`, distinto en cada llamada y en
+/// cada ejecución, como el de `RANDOM()`.
+pub fn decimal_aleatorio() -> f64 {
+    GENERADOR.with(|generador| generador.borrow_mut().decimal())
+}
+
+/// Generador de números pseudoaleatorios (SplitMix64), para las muestras de `TABLESAMPLE` y
+/// la función `RANDOM()`.
+///
+/// No es apto para usos criptográficos: solo se busca que sea rápido y que, con la misma
+/// semilla, genere siempre la misma secuencia.
+///
+/// - `estado`: El estado interno, que avanza con cada número generado.
+#[derive(Debug, Clone)]
+pub struct GeneradorAleatorio {
+    estado: u64,
+}
+
+impl GeneradorAleatorio {
+    /// Crea un generador que produce siempre la misma secuencia para la misma semilla.
+    pub fn new(semilla: u64) -> GeneradorAleatorio {
+        GeneradorAleatorio { estado: semilla }
+    }
+
+    /// Crea un generador con una semilla distinta en cada ejecución, tomada de la hora actual
+    /// y de las claves aleatorias que la biblioteca estándar usa para los `HashMap`.
+    pub fn desde_entropia() -> GeneradorAleatorio {
+        let mut hasher = RandomState::new().build_hasher();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duracion| duracion.as_nanos());
+        hasher.write_u128(nanos);
+        GeneradorAleatorio::new(hasher.finish())
+    }
+
+    /// Devuelve el siguiente número de la secuencia.
+    pub fn siguiente(&mut self) -> u64 {
+        self.estado = self.estado.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut numero = self.estado;
+        numero = (numero ^ (numero >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        numero = (numero ^ (numero >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        numero ^ (numero >> 31)
+    }
+
+    /// Devuelve un número decimal uniforme en el intervalo `[0, 1)`.
+    pub fn decimal(&mut self) -> f64 {
+        // Los 53 bits más altos alcanzan para la mantisa de un f64.
+        (self.siguiente() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_misma_semilla_misma_secuencia() {
+        let mut uno = GeneradorAleatorio::new(42);
+        let mut otro = GeneradorAleatorio::new(42);
+        let secuencia: Vec<u64> = (0..5).map(|_| uno.siguiente()).collect();
+        assert_eq!(
+            secuencia,
+            (0..5).map(|_| otro.siguiente()).collect::<Vec<_>>()
+        );
+        assert_ne!(secuencia[0], GeneradorAleatorio::new(43).siguiente());
+
+        let mut generador = GeneradorAleatorio::desde_entropia();
+        let decimales: Vec<f64> = (0..1000).map(|_| generador.decimal()).collect();
+        assert!(decimales.iter().all(|decimal| (0.0..1.0).contains(decimal)));
+        let promedio = decimales.iter().sum::<f64>() / decimales.len() as f64;
+        assert!((0.4..0.6).contains(&promedio));
+    }
+}
@@ -0,0 +1,918 @@
+use crate::archivo::{leer_archivo, leer_texto, FinDeLinea, OpcionesArchivo};
+use crate::bloqueo::{self, Bloqueo};
+use crate::diario::{self, Entrada};
+use crate::errores;
+use crate::esquema::Esquema;
+use crate::estadisticas::{Estadisticas, EXTENSION_ESTADISTICAS};
+use crate::fecha;
+use crate::gzip;
+use crate::json;
+use crate::resolvedor::ResolvedorTablas;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Lugar donde se guarda el contenido de las tablas.
+///
+/// Las consultas leen y modifican las tablas a través de un almacenamiento, a partir de la
+/// ruta que devuelve el `ResolvedorTablas`. El contenido de una tabla es siempre un CSV cuya
+/// primera línea es el encabezado, aunque el almacenamiento lo guarde comprimido, en otra
+/// codificación o como JSON (ver `json`).
+pub trait Almacenamiento: fmt::Debug + Send + Sync {
+    /// Abre la tabla para leer su contenido, empezando por el encabezado.
+    ///
+    /// # Parámetros
+    /// - `ruta`: La ruta de la tabla.
+    /// - `opciones`: Las opciones con las que se lee la tabla.
+    ///
+    /// # Retorno
+    /// Un lector del contenido de la tabla como texto UTF-8, o el error de E/S si la tabla no
+    /// existe o no se puede leer.
+    fn leer(&self, ruta: &str, opciones: &OpcionesArchivo) -> io::Result<Box<dyn BufRead>>;
+
+    /// Agrega registros al final de la tabla.
+    ///
+    /// # Parámetros
+    /// - `ruta`: La ruta de la tabla.
+    /// - `opciones`: Las opciones con las que se escribe la tabla.
+    /// - `lineas`: Los registros ya formateados, cada uno terminado en un salto de línea.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error que impidió escribir los registros.
+    fn agregar(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        lineas: &str,
+    ) -> Result<(), errores::Errores>;
+
+    /// Reemplaza el contenido completo de la tabla, como al repararla con `REPAIR TABLE`.
+    ///
+    /// # Parámetros
+    /// - `ruta`: La ruta de la tabla.
+    /// - `opciones`: Las opciones con las que se escribe la tabla.
+    /// - `contenido`: El nuevo contenido CSV, empezando por el encabezado si la tabla lo tiene.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error que impidió escribir la tabla; en ese caso la tabla queda
+    /// como estaba.
+    fn reescribir(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores>;
+
+    /// Crea una tabla nueva con el contenido indicado.
+    ///
+    /// # Parámetros
+    /// - `ruta`: La ruta de la tabla.
+    /// - `opciones`: Las opciones con las que se escribe la tabla.
+    /// - `contenido`: El contenido CSV, empezando por el encabezado.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())`, o el error que impidió crear la tabla, como `Errores::Io` con
+    /// `AlreadyExists` si ya existe; en ese caso la tabla no se modifica.
+    fn crear(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores>;
+
+    /// Carga el esquema de la tabla (ver `Esquema`).
+    ///
+    /// # Retorno
+    /// El `Esquema` de la tabla, vacío si no tiene, o el error si el esquema no es válido.
+    fn esquema(&self, ruta: &str) -> Result<Esquema, errores::Errores>;
+
+    /// Carga las estadísticas de la tabla calculadas con `ANALYZE` (ver `Estadisticas`).
+    ///
+    /// # Retorno
+    /// Las `Estadisticas`, `None` si la tabla nunca se analizó, o el error si no son válidas.
+    fn estadisticas(&self, ruta: &str) -> Result<Option<Estadisticas>, errores::Errores>;
+
+    /// Guarda las estadísticas de la tabla, reemplazando las anteriores.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error que impidió guardarlas.
+    fn guardar_estadisticas(
+        &self,
+        ruta: &str,
+        estadisticas: &Estadisticas,
+    ) -> Result<(), errores::Errores>;
+
+    /// Crea una copia de trabajo de la tabla, en la que se preparan los cambios de una
+    /// transacción sin modificar la tabla original.
+    ///
+    /// # Retorno
+    /// La ruta de la copia, que se lee y modifica como cualquier otra tabla de este
+    /// almacenamiento, o el error si no se pudo crear.
+    fn copiar(&self, ruta: &str) -> Result<String, errores::Errores>;
+
+    /// Reemplaza cada tabla por su copia creada con `copiar`, que deja de existir. Las tablas
+    /// se reemplazan todas o ninguna, aunque el proceso se interrumpa en el medio.
+    ///
+    /// # Parámetros
+    /// - `copias`: La ruta de cada tabla junto con la ruta de su copia.
+    fn reemplazar(&self, copias: &[(String, String)]) -> Result<(), errores::Errores>;
+
+    /// Elimina una copia creada con `copiar` sin aplicar sus cambios.
+    ///
+    /// # Parámetros
+    /// - `ruta`: La ruta de la tabla.
+    /// - `ruta_copia`: La ruta de la copia.
+    fn descartar(&self, ruta: &str, ruta_copia: &str) -> Result<(), errores::Errores>;
+
+    /// Guarda una copia de respaldo de la tabla tal como está, antes de reemplazarla, en
+    /// `<ruta>.bak-YYYYMMDDHHMMSS` (con un sufijo `-1`, `-2`, ... si ya existe).
+    ///
+    /// # Retorno
+    /// La ruta del respaldo, `None` si el almacenamiento no reemplaza la tabla (como una
+    /// transacción, que solo modifica sus copias), o el error si no se pudo crear.
+    fn respaldar(&self, ruta: &str) -> Result<Option<String>, errores::Errores>;
+}
+
+/// Devuelve la primera ruta de respaldo de la tabla para la que `existe` es `false` (ver
+/// `Almacenamiento::respaldar`).
+fn ruta_respaldo(ruta: &str, existe: impl Fn(&str) -> bool) -> String {
+    let base = format!("{}.bak-{}", ruta, fecha::marca_de_tiempo_actual());
+    let mut ruta_respaldo = base.to_string();
+    let mut sufijo = 0;
+    while existe(&ruta_respaldo) {
+        sufijo += 1;
+        ruta_respaldo = format!("{}-{}", base, sufijo);
+    }
+    ruta_respaldo
+}
+
+/// Almacenamiento de las tablas en archivos del sistema de archivos.
+///
+/// Las modificaciones se registran antes en el diario de la tabla (ver `diario`), de modo que
+/// si el proceso se interrumpe en el medio, la próxima vez que se use la tabla se terminan o se
+/// deshacen. Mientras se modifica una tabla se tiene su bloqueo (ver `bloqueo`), para que otro
+/// proceso no la modifique a la vez: al agregar registros, y desde que se copia para una
+/// transacción hasta que la copia la reemplaza o se descarta.
+#[derive(Debug, Default)]
+pub struct AlmacenamientoArchivos {
+    bloqueos: Mutex<HashMap<String, Bloqueo>>,
+}
+
+impl AlmacenamientoArchivos {
+    pub fn new() -> AlmacenamientoArchivos {
+        AlmacenamientoArchivos::default()
+    }
+
+    /// Libera el bloqueo que se tomó al copiar la tabla, y elimina el de su copia.
+    fn liberar(&self, ruta: &str, ruta_copia: &str) -> io::Result<()> {
+        self.bloqueos
+            .lock()
+            .unwrap_or_else(|envenenado| envenenado.into_inner())
+            .remove(ruta);
+        bloqueo::eliminar(ruta_copia)
+    }
+}
+
+impl Almacenamiento for AlmacenamientoArchivos {
+    /// Si quedó un diario, la tabla se recupera solo si ningún proceso la tiene bloqueada: si
+    /// no, el diario es de una modificación que todavía está en curso.
+    fn leer(&self, ruta: &str, opciones: &OpcionesArchivo) -> io::Result<Box<dyn BufRead>> {
+        if Path::new(&diario::ruta_diario(ruta)).exists() {
+            if let Some(_bloqueo) = bloqueo::intentar_bloquear(ruta)? {
+                diario::recuperar(ruta)?;
+            }
+        }
+        leer_archivo(ruta, opciones)
+    }
+
+    /// Abre el archivo en modo append y escribe los registros al final, en la codificación y
+    /// con el fin de línea de la tabla, empezando en una línea nueva aunque el último registro
+    /// no tenga salto de línea; si la tabla está comprimida, se agregan como un nuevo miembro
+    /// gzip. Las tablas JSON no admiten agregar al final, así que se escriben completas en un
+    /// archivo nuevo que reemplaza a la tabla con un `rename`.
+    fn agregar(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        lineas: &str,
+    ) -> Result<(), errores::Errores> {
+        let _bloqueo = bloqueo::bloquear(ruta, bloqueo::ESPERA_MAXIMA)?;
+        diario::recuperar(ruta)?;
+        if opciones.es_json(ruta) {
+            let texto = json::agregar(
+                &leer_texto(ruta, opciones)?,
+                lineas,
+                opciones.delimitador(),
+                opciones.nulo(),
+            )?;
+            return reescribir_archivo(ruta, opciones, &texto);
+        }
+        let (fin_de_linea, ultimo) = fin_de_linea_de(ruta, opciones)?;
+        let lineas = format!(
+            "{}{}",
+            fin_de_linea.faltante(ultimo),
+            fin_de_linea.aplicar(lineas)
+        );
+        let mut registros = opciones.codificacion.codificar(&lineas)?;
+        if opciones.es_comprimido(ruta) {
+            registros = gzip::comprimir(&registros);
+        }
+        let mut archivo = OpenOptions::new()
+            .append(true)
+            .open(ruta)
+            .map_err(|error| errores::Errores::de_archivo(error, ruta))?;
+        diario::registrar(ruta, &Entrada::Agregar(archivo.metadata()?.len()))?;
+        archivo.write_all(&registros)?;
+        archivo.sync_all()?;
+        diario::cerrar(ruta)?;
+        Ok(())
+    }
+
+    /// El contenido se escribe en un archivo nuevo que reemplaza a la tabla con un `rename`,
+    /// con el mismo fin de línea que tenía la tabla; las tablas JSON se convierten antes a su
+    /// documento (ver `json::desde_csv`).
+    fn reescribir(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores> {
+        let _bloqueo = bloqueo::bloquear(ruta, bloqueo::ESPERA_MAXIMA)?;
+        diario::recuperar(ruta)?;
+        if opciones.es_json(ruta) {
+            let texto = json::desde_csv(contenido, opciones.delimitador(), opciones.nulo())?;
+            return reescribir_archivo(ruta, opciones, &texto);
+        }
+        let (fin_de_linea, _) = fin_de_linea_de(ruta, opciones)?;
+        reescribir_archivo(ruta, opciones, &fin_de_linea.aplicar(contenido))
+    }
+
+    /// El contenido se escribe en una copia que luego se deja en la ruta de la tabla con
+    /// `publicar_copia`, lo que falla si la tabla ya existe, de modo que nunca queda creada a
+    /// medias ni se reemplaza otra tabla.
+    fn crear(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores> {
+        let texto = if opciones.es_json(ruta) {
+            json::desde_csv(contenido, opciones.delimitador(), opciones.nulo())?
+        } else {
+            contenido.to_string()
+        };
+        let mut contenido = opciones.codificacion.codificar(&texto)?;
+        if opciones.es_comprimido(ruta) {
+            contenido = gzip::comprimir(&contenido);
+        }
+        let ruta_copia = ruta_copia(ruta);
+        let escrito = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&ruta_copia)
+            .and_then(|mut copia| {
+                copia.write_all(&contenido)?;
+                copia.sync_all()
+            })
+            .and_then(|_| publicar_copia(&ruta_copia, ruta));
+        let _ = fs::remove_file(&ruta_copia);
+        escrito.map_err(|error| errores::Errores::de_archivo(error, ruta))
+    }
+
+    fn esquema(&self, ruta: &str) -> Result<Esquema, errores::Errores> {
+        Esquema::cargar(ruta)
+    }
+
+    fn estadisticas(&self, ruta: &str) -> Result<Option<Estadisticas>, errores::Errores> {
+        Estadisticas::cargar(ruta)
+    }
+
+    fn guardar_estadisticas(
+        &self,
+        ruta: &str,
+        estadisticas: &Estadisticas,
+    ) -> Result<(), errores::Errores> {
+        estadisticas.guardar(ruta)
+    }
+
+    /// La copia es un archivo oculto en el mismo directorio que la tabla, con la misma
+    /// extensión, de modo que el reemplazo es un `rename` dentro del mismo sistema de archivos.
+    /// Si no se puede terminar de copiar, se elimina la copia a medias.
+    fn copiar(&self, ruta: &str) -> Result<String, errores::Errores> {
+        let bloqueo = bloqueo::bloquear(ruta, bloqueo::ESPERA_MAXIMA)?;
+        diario::recuperar(ruta)?;
+        let ruta_copia = ruta_copia(ruta);
+        diario::registrar(ruta, &Entrada::Copia(ruta_copia.to_string()))?;
+        if let Err(error) = copiar_archivo(ruta, &ruta_copia) {
+            let _ = fs::remove_file(&ruta_copia);
+            diario::cerrar(ruta)?;
+            return Err(error.into());
+        }
+        self.bloqueos
+            .lock()
+            .unwrap_or_else(|envenenado| envenenado.into_inner())
+            .insert(ruta.to_string(), bloqueo);
+        Ok(ruta_copia)
+    }
+
+    /// Primero se indica en el diario de cada tabla dónde va a estar el registro de la
+    /// transacción, después se escribe ese único registro con todas las copias (ver
+    /// `diario::confirmar`) y recién entonces se renombran las copias. Si el proceso se
+    /// interrumpe antes de escribir el registro, ninguna tabla se reemplaza al recuperarlas; si
+    /// se interrumpe después, se terminan de reemplazar todas.
+    fn reemplazar(&self, copias: &[(String, String)]) -> Result<(), errores::Errores> {
+        let Some((primera, _)) = copias.first() else {
+            return Ok(());
+        };
+        let ruta_registro = ruta_copia(&format!("{}.transaccion", primera));
+        for (ruta, _) in copias {
+            diario::registrar(ruta, &Entrada::Transaccion(ruta_registro.to_string()))?;
+        }
+        diario::confirmar(&ruta_registro, copias)?;
+        for (ruta, ruta_copia) in copias {
+            fs::rename(ruta_copia, ruta)?;
+            diario::cerrar(ruta)?;
+            self.liberar(ruta, ruta_copia)?;
+        }
+        diario::cerrar_transaccion(&ruta_registro)?;
+        Ok(())
+    }
+
+    fn descartar(&self, ruta: &str, ruta_copia: &str) -> Result<(), errores::Errores> {
+        fs::remove_file(ruta_copia)?;
+        diario::cerrar(ruta)?;
+        self.liberar(ruta, ruta_copia)?;
+        Ok(())
+    }
+
+    /// El respaldo se crea con `create_new`, de modo que nunca se pisa un respaldo anterior,
+    /// y se espera a que llegue al disco antes de reemplazar la tabla.
+    fn respaldar(&self, ruta: &str) -> Result<Option<String>, errores::Errores> {
+        loop {
+            let ruta_respaldo = ruta_respaldo(ruta, |candidata| Path::new(candidata).exists());
+            let mut respaldo = match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&ruta_respaldo)
+            {
+                Ok(respaldo) => respaldo,
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(error.into()),
+            };
+            let copiado = fs::File::open(ruta)
+                .and_then(|mut tabla| io::copy(&mut tabla, &mut respaldo))
+                .and_then(|_| respaldo.sync_all());
+            if let Err(error) = copiado {
+                let _ = fs::remove_file(&ruta_respaldo);
+                return Err(error.into());
+            }
+            return Ok(Some(ruta_respaldo));
+        }
+    }
+}
+
+/// Cantidad de copias de trabajo creadas por el proceso, para que cada una tenga su nombre.
+static COPIAS: AtomicU64 = AtomicU64::new(0);
+
+/// Arma la ruta de una nueva copia de trabajo de una tabla: `directorio/.<pid>-<n>-<archivo>`,
+/// donde `n` distingue las copias del mismo proceso. Se saltean los nombres que ya existen, como
+/// los que dejó un proceso interrumpido con el mismo identificador.
+fn ruta_copia(ruta: &str) -> String {
+    let ruta = Path::new(ruta);
+    let archivo = ruta
+        .file_name()
+        .map(|archivo| archivo.to_string_lossy().to_string())
+        .unwrap_or_default();
+    loop {
+        let numero = COPIAS.fetch_add(1, Ordering::Relaxed);
+        let ruta_copia = ruta.with_file_name(format!(".{}-{}-{}", process::id(), numero, archivo));
+        if !ruta_copia.exists() {
+            return ruta_copia.to_string_lossy().to_string();
+        }
+    }
+}
+
+/// Deja una copia ya escrita en la ruta de una tabla nueva, sin reemplazar la tabla si ya
+/// existe.
+///
+/// Se enlaza la copia con la ruta, que queda creada con todo su contenido de una vez. Si el
+/// sistema de archivos no admite enlaces duros, como FAT o algunos montajes de red, se recurre a
+/// `reservar_y_renombrar`, con el que la tabla puede verse vacía por un instante.
+///
+/// # Retorno
+/// Retorna `Ok(())`, o un error con `AlreadyExists` si la tabla ya existe.
+fn publicar_copia(ruta_copia: &str, ruta: &str) -> io::Result<()> {
+    match fs::hard_link(ruta_copia, ruta) {
+        Err(error) if error.kind() != io::ErrorKind::AlreadyExists => {
+            reservar_y_renombrar(ruta_copia, ruta)
+        }
+        enlazado => enlazado,
+    }
+}
+
+/// Crea la tabla vacía, lo que falla si ya existe, y la reemplaza con la copia. Si el reemplazo
+/// falla, se elimina la tabla vacía.
+fn reservar_y_renombrar(ruta_copia: &str, ruta: &str) -> io::Result<()> {
+    OpenOptions::new().write(true).create_new(true).open(ruta)?;
+    fs::rename(ruta_copia, ruta).inspect_err(|_| {
+        let _ = fs::remove_file(ruta);
+    })
+}
+
+/// Copia la tabla en un archivo nuevo, que no debe existir, y espera a que llegue al disco.
+fn copiar_archivo(ruta: &str, ruta_copia: &str) -> io::Result<()> {
+    let mut copia = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(ruta_copia)?;
+    io::copy(&mut fs::File::open(ruta)?, &mut copia)?;
+    copia.set_permissions(fs::metadata(ruta)?.permissions())?;
+    copia.sync_all()
+}
+
+/// Lee el fin de línea de una tabla y el último byte de su contenido (ver
+/// `FinDeLinea::faltante`), sin leer el archivo completo salvo que esté comprimido.
+fn fin_de_linea_de(ruta: &str, opciones: &OpcionesArchivo) -> io::Result<(FinDeLinea, Option<u8>)> {
+    if opciones.es_comprimido(ruta) {
+        let contenido = leer_texto(ruta, opciones)?;
+        let contenido = contenido.as_bytes();
+        return Ok((FinDeLinea::detectar(contenido), contenido.last().copied()));
+    }
+    let mut archivo = fs::File::open(ruta)?;
+    let mut primera_linea = Vec::new();
+    BufReader::new(&archivo).read_until(b'\n', &mut primera_linea)?;
+    if archivo.metadata()?.len() == 0 {
+        return Ok((FinDeLinea::Lf, None));
+    }
+    let mut ultimo = [0];
+    archivo.seek(SeekFrom::End(-1))?;
+    archivo.read_exact(&mut ultimo)?;
+    Ok((FinDeLinea::detectar(&primera_linea), Some(ultimo[0])))
+}
+
+/// Reemplaza el contenido de la tabla por el texto indicado, en la codificación de la tabla y
+/// comprimido si corresponde. El texto se escribe en una copia nueva que luego reemplaza a la
+/// tabla, de modo que nunca queda escrita a medias.
+fn reescribir_archivo(
+    ruta: &str,
+    opciones: &OpcionesArchivo,
+    texto: &str,
+) -> Result<(), errores::Errores> {
+    let mut contenido = opciones.codificacion.codificar(texto)?;
+    if opciones.es_comprimido(ruta) {
+        contenido = gzip::comprimir(&contenido);
+    }
+    let ruta_copia = ruta_copia(ruta);
+    let escrito = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&ruta_copia)
+        .and_then(|mut copia| {
+            copia.write_all(&contenido)?;
+            copia.set_permissions(fs::metadata(ruta)?.permissions())?;
+            copia.sync_all()
+        })
+        .and_then(|_| fs::rename(&ruta_copia, ruta));
+    if let Err(error) = escrito {
+        let _ = fs::remove_file(&ruta_copia);
+        return Err(errores::Errores::de_archivo(error, ruta));
+    }
+    Ok(())
+}
+
+/// Tablas guardadas en memoria, sin archivos, para pruebas y para usar la base de datos
+/// embebida en otros programas.
+///
+/// Funciona a la vez como resolvedor y como almacenamiento: las tablas se registran con
+/// `agregar_tabla` y las consultas creadas con este resolvedor las leen y modifican en
+/// memoria. Las copias creadas con `clone` comparten las mismas tablas.
+///
+/// ```
+/// # use base_de_datos::almacenamiento::TablasEnMemoria;
+/// # use base_de_datos::archivo::OpcionesArchivo;
+/// # use base_de_datos::select::ConsultaSelect;
+/// let tablas = TablasEnMemoria::new();
+/// tablas.agregar_tabla("personas", "nombre,edad\nAna,30\nLuis,45\n");
+/// let mut consulta = ConsultaSelect::crear(
+///     "SELECT nombre FROM personas WHERE edad > 40",
+///     &tablas,
+///     &OpcionesArchivo::default(),
+/// )
+/// .unwrap();
+/// let filas: Vec<Vec<String>> = consulta.filas().unwrap().map(Result::unwrap).collect();
+/// assert_eq!(filas, vec![vec!["Luis"]]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TablasEnMemoria {
+    tablas: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl TablasEnMemoria {
+    pub fn new() -> TablasEnMemoria {
+        TablasEnMemoria::default()
+    }
+
+    /// Registra una tabla, o reemplaza su contenido si ya existía.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre de la tabla; se usa en minúsculas, como en las consultas.
+    /// - `contenido`: El contenido CSV de la tabla, con el encabezado en la primera línea.
+    pub fn agregar_tabla(&self, nombre: &str, contenido: &str) {
+        self.bloquear()
+            .insert(nombre.to_lowercase(), contenido.to_string());
+    }
+
+    /// Devuelve el contenido CSV actual de una tabla.
+    ///
+    /// # Retorno
+    /// El contenido de la tabla, o `None` si no está registrada.
+    pub fn contenido(&self, nombre: &str) -> Option<String> {
+        self.bloquear().get(&nombre.to_lowercase()).cloned()
+    }
+
+    fn bloquear(&self) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+        // Las tablas siguen siendo válidas aunque otro hilo haya fallado con el bloqueo tomado.
+        self.tablas
+            .lock()
+            .unwrap_or_else(|envenenado| envenenado.into_inner())
+    }
+}
+
+impl ResolvedorTablas for TablasEnMemoria {
+    /// La ruta de una tabla en memoria es su nombre en minúsculas.
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        let nombre = tabla.to_lowercase();
+        if !self.bloquear().contains_key(&nombre) {
+            return Err(errores::Errores::InvalidTable(tabla.to_string()));
+        }
+        Ok(nombre)
+    }
+
+    fn almacenamiento(&self) -> Arc<dyn Almacenamiento> {
+        Arc::new(self.clone())
+    }
+
+    /// Las estadísticas se guardan como tablas (ver `estadisticas`), pero no se listan.
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        let mut tablas: Vec<String> = self
+            .bloquear()
+            .keys()
+            .filter(|tabla| !tabla.ends_with(EXTENSION_ESTADISTICAS))
+            .cloned()
+            .collect();
+        tablas.sort();
+        Ok(tablas)
+    }
+}
+
+impl Almacenamiento for TablasEnMemoria {
+    /// Devuelve una copia del contenido de la tabla, de modo que las modificaciones
+    /// posteriores no afectan a una lectura en curso.
+    fn leer(&self, ruta: &str, _opciones: &OpcionesArchivo) -> io::Result<Box<dyn BufRead>> {
+        match self.contenido(ruta) {
+            Some(contenido) => Ok(Box::new(Cursor::new(contenido.into_bytes()))),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn agregar(
+        &self,
+        ruta: &str,
+        _opciones: &OpcionesArchivo,
+        lineas: &str,
+    ) -> Result<(), errores::Errores> {
+        match self.bloquear().get_mut(ruta) {
+            Some(contenido) => {
+                let fin_de_linea = FinDeLinea::detectar(contenido.as_bytes());
+                contenido.push_str(fin_de_linea.faltante(contenido.bytes().last()));
+                contenido.push_str(&fin_de_linea.aplicar(lineas));
+                Ok(())
+            }
+            None => Err(errores::Errores::InvalidTable(ruta.to_string())),
+        }
+    }
+
+    fn reescribir(
+        &self,
+        ruta: &str,
+        _opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores> {
+        match self.bloquear().get_mut(ruta) {
+            Some(tabla) => {
+                *tabla = FinDeLinea::detectar(tabla.as_bytes())
+                    .aplicar(contenido)
+                    .into_owned();
+                Ok(())
+            }
+            None => Err(errores::Errores::InvalidTable(ruta.to_string())),
+        }
+    }
+
+    fn crear(
+        &self,
+        ruta: &str,
+        _opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores> {
+        let mut tablas = self.bloquear();
+        if tablas.contains_key(ruta) {
+            return Err(io::Error::from(io::ErrorKind::AlreadyExists).into());
+        }
+        tablas.insert(ruta.to_string(), contenido.to_string());
+        Ok(())
+    }
+
+    /// Las tablas en memoria no tienen esquema.
+    fn esquema(&self, _ruta: &str) -> Result<Esquema, errores::Errores> {
+        Ok(Esquema::default())
+    }
+
+    /// Las estadísticas se guardan como otra tabla, con el nombre de la tabla seguido de
+    /// `.estadisticas`.
+    fn estadisticas(&self, ruta: &str) -> Result<Option<Estadisticas>, errores::Errores> {
+        let ruta = format!("{}{}", ruta, EXTENSION_ESTADISTICAS);
+        self.contenido(&ruta)
+            .map(|contenido| Estadisticas::desde_csv(&contenido))
+            .transpose()
+    }
+
+    fn guardar_estadisticas(
+        &self,
+        ruta: &str,
+        estadisticas: &Estadisticas,
+    ) -> Result<(), errores::Errores> {
+        let ruta = format!("{}{}", ruta, EXTENSION_ESTADISTICAS);
+        self.bloquear().insert(ruta, estadisticas.a_csv());
+        Ok(())
+    }
+
+    /// La copia se guarda con el nombre de la tabla seguido de `#copia`, que no puede aparecer
+    /// en el nombre de una tabla de una consulta.
+    fn copiar(&self, ruta: &str) -> Result<String, errores::Errores> {
+        let mut tablas = self.bloquear();
+        let contenido = tablas
+            .get(ruta)
+            .cloned()
+            .ok_or_else(|| errores::Errores::InvalidTable(ruta.to_string()))?;
+        let ruta_copia = format!("{}#copia", ruta);
+        tablas.insert(ruta_copia.to_string(), contenido);
+        Ok(ruta_copia)
+    }
+
+    /// Las tablas se reemplazan con el bloqueo tomado, todas a la vez.
+    fn reemplazar(&self, copias: &[(String, String)]) -> Result<(), errores::Errores> {
+        let mut tablas = self.bloquear();
+        if let Some((_, faltante)) = copias
+            .iter()
+            .find(|(_, ruta_copia)| !tablas.contains_key(ruta_copia))
+        {
+            return Err(errores::Errores::InvalidTable(faltante.to_string()));
+        }
+        for (ruta, ruta_copia) in copias {
+            if let Some(contenido) = tablas.remove(ruta_copia) {
+                tablas.insert(ruta.to_string(), contenido);
+            }
+        }
+        Ok(())
+    }
+
+    fn descartar(&self, _ruta: &str, ruta_copia: &str) -> Result<(), errores::Errores> {
+        self.bloquear().remove(ruta_copia);
+        Ok(())
+    }
+
+    fn respaldar(&self, ruta: &str) -> Result<Option<String>, errores::Errores> {
+        let mut tablas = self.bloquear();
+        let contenido = tablas
+            .get(ruta)
+            .cloned()
+            .ok_or_else(|| errores::Errores::InvalidTable(ruta.to_string()))?;
+        let ruta_respaldo = ruta_respaldo(ruta, |candidata| tablas.contains_key(candidata));
+        tablas.insert(ruta_respaldo.to_string(), contenido);
+        Ok(Some(ruta_respaldo))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consulta::SQLConsulta;
+    use crate::resolvedor::ResolvedorDirectorio;
+    use crate::salida::OpcionesSalida;
+
+    fn ejecutar(tablas: &TablasEnMemoria, consulta: &str) -> String {
+        let mut destino = Vec::new();
+        {
+            let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+            SQLConsulta::crear_consulta(consulta, tablas, &OpcionesArchivo::default())
+                .unwrap()
+                .procesar_consulta(escritor.as_mut())
+                .unwrap();
+        }
+        String::from_utf8(destino).unwrap()
+    }
+
+    #[test]
+    fn test_copia_de_trabajo_bloquea_la_tabla() {
+        let directorio = std::env::temp_dir().join("test_copia_de_trabajo_bloquea_la_tabla");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("personas").to_string_lossy().to_string();
+        fs::write(&ruta, "nombre\nAna\n").unwrap();
+
+        let almacenamiento = AlmacenamientoArchivos::new();
+        let ruta_copia = almacenamiento.copiar(&ruta).unwrap();
+        assert!(bloqueo::intentar_bloquear(&ruta).unwrap().is_none());
+        almacenamiento.descartar(&ruta, &ruta_copia).unwrap();
+        assert!(bloqueo::intentar_bloquear(&ruta).unwrap().is_some());
+        assert!(!Path::new(&ruta_copia).exists());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_copias_de_trabajo_con_nombres_distintos() {
+        let directorio = std::env::temp_dir().join("test_copias_de_trabajo_con_nombres_distintos");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio
+            .join("personas.csv")
+            .to_string_lossy()
+            .to_string();
+
+        let primera = ruta_copia(&ruta);
+        fs::write(&primera, "").unwrap();
+        let segunda = ruta_copia(&ruta);
+        assert_ne!(primera, segunda);
+        assert_eq!(Path::new(&segunda).parent(), Some(directorio.as_path()));
+        assert!(segunda.ends_with("-personas.csv"));
+
+        // Si la tabla no se puede copiar, no queda ni la copia ni el diario.
+        let almacenamiento = AlmacenamientoArchivos::new();
+        assert!(almacenamiento.copiar(&ruta).is_err());
+        let archivos: Vec<_> = fs::read_dir(&directorio)
+            .unwrap()
+            .map(|entrada| entrada.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|archivo| !archivo.ends_with(".bloqueo"))
+            .collect();
+        assert_eq!(archivos.len(), 1);
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_crear_sin_enlaces_duros() {
+        let directorio = std::env::temp_dir().join("test_crear_sin_enlaces_duros");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("ventas").to_string_lossy().to_string();
+        let _ = fs::remove_file(&ruta);
+
+        let copia = ruta_copia(&ruta);
+        fs::write(&copia, "producto\nmesa\n").unwrap();
+        reservar_y_renombrar(&copia, &ruta).unwrap();
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "producto\nmesa\n");
+        assert!(!Path::new(&copia).exists());
+
+        // Si la tabla ya existe, no se toca y la copia queda para descartarla.
+        let otra = ruta_copia(&ruta);
+        fs::write(&otra, "producto\n").unwrap();
+        assert_eq!(
+            reservar_y_renombrar(&otra, &ruta).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            publicar_copia(&otra, &ruta).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "producto\nmesa\n");
+        assert!(Path::new(&otra).exists());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_tabla_con_fin_de_linea_crlf() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre\r\nAna");
+        ejecutar(&tablas, "INSERT INTO personas VALUES ('Luis')");
+        assert_eq!(
+            tablas.contenido("personas").unwrap(),
+            "nombre\r\nAna\r\nLuis\r\n"
+        );
+
+        let directorio = std::env::temp_dir().join("test_tabla_con_fin_de_linea_crlf");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("personas.csv");
+        fs::write(&ruta, "nombre,ciudad\r\nAna,Rosario\r\nLuis,Salta\r").unwrap();
+
+        let resolvedor = ResolvedorDirectorio::new(&directorio.to_string_lossy());
+        let ejecutar = |consulta: &str| {
+            let mut destino = Vec::new();
+            {
+                let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+                SQLConsulta::crear_consulta(consulta, &resolvedor, &OpcionesArchivo::default())
+                    .unwrap()
+                    .procesar_consulta(escritor.as_mut())
+                    .unwrap();
+            }
+            String::from_utf8(destino).unwrap()
+        };
+        assert_eq!(
+            ejecutar("SELECT nombre FROM personas WHERE ciudad = 'Salta'"),
+            "nombre\nLuis\n"
+        );
+        ejecutar("INSERT INTO personas VALUES ('Eva', 'Tandil'), ('Juan', 'Salta')");
+        assert_eq!(
+            fs::read_to_string(&ruta).unwrap(),
+            "nombre,ciudad\r\nAna,Rosario\r\nLuis,Salta\r\nEva,Tandil\r\nJuan,Salta\r\n"
+        );
+        assert_eq!(
+            ejecutar("SELECT nombre FROM personas WHERE ciudad = 'Salta'"),
+            "nombre\nLuis\nJuan\n"
+        );
+
+        AlmacenamientoArchivos::new()
+            .reescribir(
+                &ruta.to_string_lossy(),
+                &OpcionesArchivo::default(),
+                "nombre,ciudad\nAna,Rosario\n",
+            )
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(&ruta).unwrap(),
+            "nombre,ciudad\r\nAna,Rosario\r\n"
+        );
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_insertar_y_consultar_tabla_json() {
+        let directorio = std::env::temp_dir().join("test_insertar_y_consultar_tabla_json");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("personas.json");
+        fs::write(&ruta, "[{\"nombre\": \"Ana\", \"edad\": 30}]").unwrap();
+
+        let resolvedor = ResolvedorDirectorio::new(&directorio.to_string_lossy());
+        let ejecutar = |consulta: &str| {
+            let mut destino = Vec::new();
+            {
+                let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+                SQLConsulta::crear_consulta(consulta, &resolvedor, &OpcionesArchivo::default())
+                    .unwrap()
+                    .procesar_consulta(escritor.as_mut())
+                    .unwrap();
+            }
+            String::from_utf8(destino).unwrap()
+        };
+        ejecutar("INSERT INTO personas (nombre, edad) VALUES ('Luis', 45)");
+        assert_eq!(
+            ejecutar("SELECT nombre FROM personas WHERE edad > 40"),
+            "nombre\nLuis\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&ruta).unwrap(),
+            "[\n  {\"nombre\": \"Ana\", \"edad\": 30},\n  {\"nombre\": \"Luis\", \"edad\": 45}\n]\n"
+        );
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_insertar_y_consultar_en_memoria() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("Personas", "nombre,edad\nAna,30");
+
+        ejecutar(
+            &tablas,
+            "INSERT INTO personas (nombre, edad) VALUES ('Luis', 45), ('Eva', NULL)",
+        );
+
+        assert_eq!(
+            tablas.contenido("personas").unwrap(),
+            "nombre,edad\nAna,30\nLuis,45\nEva,\n"
+        );
+        assert_eq!(
+            ejecutar(&tablas, "SELECT nombre FROM personas ORDER BY edad"),
+            "nombre\nAna\nLuis\nEva\n"
+        );
+    }
+
+    #[test]
+    fn test_tabla_en_memoria_inexistente() {
+        let tablas = TablasEnMemoria::new();
+        assert_eq!(
+            SQLConsulta::crear_consulta(
+                "SELECT * FROM ventas",
+                &tablas,
+                &OpcionesArchivo::default()
+            )
+            .unwrap_err(),
+            errores::Errores::InvalidTable("ventas".to_string())
+        );
+    }
+}
@@ -1,58 +1,1467 @@
-use std::fs::File;
-use std::io::{self, BufReader};
+use crate::errores;
+use crate::gzip::{self, EXTENSION_GZIP};
+use crate::json::{self, EXTENSION_JSON};
+use crate::registro;
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Delimitador de campos por defecto, usado también en la salida de las consultas.
+pub const DELIMITADOR: char = ',';
+const COMILLA: char = '"';
+/// Delimitadores que se reconocen al detectar automáticamente el formato de una tabla.
+const DELIMITADORES_DETECTABLES: [char; 4] = [',', ';', '\t', '|'];
+/// Ruta con la que se lee una tabla de la entrada estándar, como en `FROM -`.
+pub const RUTA_ENTRADA: &str = "-";
+/// Contenido de la entrada estándar, que se lee una única vez aunque se consulte varias veces.
+static ENTRADA: OnceLock<Vec<u8>> = OnceLock::new();
+/// Marca de orden de bytes con la que algunos programas, como Excel, inician los archivos UTF-8.
+const BOM_UTF8: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Codificaciones de caracteres admitidas para los archivos de las tablas.
+///
+/// - `Utf8`: UTF-8, la codificación por defecto.
+/// - `Latin1`: ISO-8859-1, donde cada byte es un carácter.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Codificacion {
+    #[default]
+    Utf8,
+    Latin1,
+}
+
+impl Codificacion {
+    /// Devuelve la codificación con el nombre indicado por línea de comandos.
+    ///
+    /// # Argumentos
+    /// - `nombre`: El nombre de la codificación, sin distinguir mayúsculas.
+    ///
+    /// # Retorno
+    /// La `Codificacion`, o `None` si el nombre no corresponde a ninguna admitida.
+    pub fn desde_nombre(nombre: &str) -> Option<Codificacion> {
+        match nombre.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Codificacion::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Codificacion::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Convierte el contenido de un archivo en esta codificación a UTF-8.
+    ///
+    /// # Argumentos
+    /// - `contenido`: Los bytes leídos del archivo.
+    ///
+    /// # Retorno
+    /// El contenido codificado en UTF-8.
+    fn decodificar(&self, contenido: Vec<u8>) -> Vec<u8> {
+        match self {
+            Codificacion::Utf8 => contenido,
+            Codificacion::Latin1 => contenido
+                .into_iter()
+                .map(char::from)
+                .collect::<String>()
+                .into_bytes(),
+        }
+    }
+
+    /// Convierte un texto a los bytes que lo representan en esta codificación.
+    ///
+    /// # Argumentos
+    /// - `texto`: El texto a escribir en el archivo.
+    ///
+    /// # Retorno
+    /// Los bytes codificados, o `Errores::ValorInvalido` si el texto tiene caracteres que la
+    /// codificación no puede representar.
+    pub fn codificar(&self, texto: &str) -> Result<Vec<u8>, errores::Errores> {
+        match self {
+            Codificacion::Utf8 => Ok(texto.as_bytes().to_vec()),
+            Codificacion::Latin1 => texto
+                .chars()
+                .map(|caracter| {
+                    u8::try_from(caracter).map_err(|_| {
+                        errores::Errores::ValorInvalido(format!(
+                            "el carácter '{}' no se puede escribir en latin1",
+                            caracter
+                        ))
+                    })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Qué se hace con los registros de una tabla cuya cantidad de campos no coincide con la de
+/// sus columnas.
+///
+/// - `Libre`: Los campos que faltan se leen como `NULL` y los que sobran se ignoran, sin
+///   avisar. Es el modo por defecto.
+/// - `Estricto`: La consulta termina con `Errores::RegistroMalformado`, indicando la línea
+///   del registro.
+/// - `Tolerante`: El registro se completa con `NULL` o se recortan sus campos sobrantes, y
+///   se avisa en la salida de errores con la línea del registro.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ModoCampos {
+    #[default]
+    Libre,
+    Estricto,
+    Tolerante,
+}
+
+/// Opciones que indican cómo leer y escribir los archivos de las tablas.
+///
+/// # Campos
+///
+/// - `delimitador`: El carácter que separa los campos. Si es `None`, se detecta a partir del
+///   encabezado de la tabla.
+/// - `comprimido`: Indica que las tablas están comprimidas con gzip aunque su ruta no termine
+///   en `.gz`.
+/// - `columnas`: Los nombres de las columnas de tablas sin fila de encabezado. Si es `Some`, la
+///   primera línea del archivo se trata como un registro más.
+/// - `codificacion`: La `Codificacion` de caracteres de los archivos.
+/// - `nulo`: El texto que representa `NULL` en los archivos, como `\N` o `NA`, además del
+///   campo vacío sin comillas.
+/// - `mapear_memoria`: Indica que las tablas se lean proyectándolas en memoria en lugar de
+///   con lecturas sucesivas.
+/// - `respaldo`: Indica que antes de que un `COMMIT` reemplace una tabla se guarde una copia
+///   de respaldo (ver `Almacenamiento::respaldar`).
+/// - `campos`: El `ModoCampos` con el que se tratan los registros con una cantidad de campos
+///   distinta de la de las columnas.
+/// - `progreso`: Indica que mientras se lee una tabla se informe en la salida de errores cuánto
+///   se leyó (ver `LectorConProgreso`).
+/// - `mezclar`: Indica que el resultado de cada `SELECT` se ordene al azar, como con un
+///   `ORDER BY RANDOM()` agregado después de sus criterios.
+/// - `solo_tablas`: Indica que el `FROM` solo admita las tablas que resuelve el resolvedor,
+///   sin rutas entre comillas ni la entrada estándar, como en los servidores.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpcionesArchivo {
+    pub delimitador: Option<char>,
+    pub comprimido: bool,
+    pub columnas: Option<Vec<String>>,
+    pub codificacion: Codificacion,
+    pub nulo: Option<String>,
+    pub mapear_memoria: bool,
+    pub respaldo: bool,
+    pub campos: ModoCampos,
+    pub progreso: bool,
+    pub mezclar: bool,
+    pub solo_tablas: bool,
+}
+
+impl OpcionesArchivo {
+    /// Devuelve el delimitador de campos a usar, o la coma si todavía no se conoce.
+    pub fn delimitador(&self) -> char {
+        self.delimitador.unwrap_or(DELIMITADOR)
+    }
+
+    /// Devuelve el texto que representa `NULL` en los archivos, si se indicó alguno.
+    pub fn nulo(&self) -> Option<&str> {
+        self.nulo.as_deref()
+    }
+
+    /// Indica si el archivo de la tabla está comprimido con gzip, ya sea porque así se indicó o
+    /// porque su ruta termina en `.gz`.
+    ///
+    /// # Argumentos
+    /// - `ruta_archivo`: La ruta del archivo de la tabla.
+    pub fn es_comprimido(&self, ruta_archivo: &str) -> bool {
+        self.comprimido || ruta_archivo.ends_with(EXTENSION_GZIP)
+    }
+
+    /// Indica si la tabla está guardada como JSON (ver `json`), porque su ruta termina en
+    /// `.json`, o en `.json.gz` si está comprimida.
+    ///
+    /// # Argumentos
+    /// - `ruta_archivo`: La ruta del archivo de la tabla.
+    pub fn es_json(&self, ruta_archivo: &str) -> bool {
+        ruta_archivo
+            .strip_suffix(EXTENSION_GZIP)
+            .unwrap_or(ruta_archivo)
+            .ends_with(EXTENSION_JSON)
+    }
+
+    /// Fija el delimitador a partir del encabezado de la tabla si no fue indicado explícitamente.
+    ///
+    /// # Argumentos
+    /// - `encabezado`: La primera línea del archivo de la tabla.
+    pub fn detectar_delimitador(&mut self, encabezado: &str) {
+        if self.delimitador.is_none() {
+            self.delimitador = Some(detectar_delimitador(encabezado));
+        }
+    }
+}
+
+/// Interpreta el valor de la opción de delimitador recibida por línea de comandos.
+///
+/// Acepta un único carácter o los nombres `tab` y `\t` para el tabulador.
+///
+/// # Argumentos
+/// - `valor`: El valor indicado por el usuario.
+///
+/// # Retorno
+/// El carácter delimitador, o `None` si el valor no es válido.
+pub fn parsear_delimitador(valor: &str) -> Option<char> {
+    if valor.eq_ignore_ascii_case("tab") || valor == "\\t" {
+        return Some('\t');
+    }
+    let mut caracteres = valor.chars();
+    match (caracteres.next(), caracteres.next()) {
+        (Some(caracter), None) if caracter != COMILLA && caracter != '\n' => Some(caracter),
+        _ => None,
+    }
+}
+
+/// Detecta el delimitador de una tabla a partir de su encabezado.
+///
+/// Elige, entre coma, punto y coma, tabulador y barra vertical, el que más veces aparece fuera
+/// de comillas. Si ninguno aparece, se asume la coma.
+///
+/// # Argumentos
+/// - `encabezado`: La primera línea del archivo de la tabla.
+///
+/// # Retorno
+/// El carácter delimitador detectado.
+pub fn detectar_delimitador(encabezado: &str) -> char {
+    let mut apariciones = [0; DELIMITADORES_DETECTABLES.len()];
+    let mut entre_comillas = false;
+    for caracter in encabezado.chars() {
+        if caracter == COMILLA {
+            entre_comillas = !entre_comillas;
+        } else if !entre_comillas {
+            if let Some(posicion) = DELIMITADORES_DETECTABLES
+                .iter()
+                .position(|delimitador| *delimitador == caracter)
+            {
+                apariciones[posicion] += 1;
+            }
+        }
+    }
+    let mut elegido = DELIMITADOR;
+    let mut maximo = 0;
+    for (delimitador, cantidad) in DELIMITADORES_DETECTABLES.iter().zip(apariciones) {
+        if cantidad > maximo {
+            elegido = *delimitador;
+            maximo = cantidad;
+        }
+    }
+    elegido
+}
+
+/// Extensiones con las que se buscan por defecto los archivos de las tablas (ver
+/// `procesar_ruta`).
+pub const EXTENSIONES_TABLA: &[&str] = &["csv", "json"];
+
 /// Procesa la ruta para acceder a una tabla específica, agregando el nombre de la tabla a la ruta.
 ///
 /// Este método modifica la ruta original añadiendo una barra y el nombre de la tabla en minúsculas.
+/// Si no existe un archivo con ese nombre, se prueba con cada una de las extensiones, en orden.
 ///
 /// # Argumentos
 /// - `ruta`: La ruta base donde se encuentran las tablas.
 /// - `tabla`: El nombre de la tabla que se desea agregar a la ruta.
+/// - `extensiones`: Las extensiones, sin el punto, con las que se busca el archivo.
 ///
 /// # Retorno
-/// Devuelve la ruta completa como un `String` que combina la ruta base y la tabla.
-
-pub fn procesar_ruta(ruta: &str, tabla: &str) -> String {
+/// Devuelve la ruta completa como un `String` que combina la ruta base y la tabla, con la
+/// primera extensión para la que existe el archivo. Si no existe ninguno, la ruta no lleva
+/// extensión.
+pub fn procesar_ruta(ruta: &str, tabla: &str, extensiones: &[String]) -> String {
     let mut ruta_modificada = String::new(); // Crear un nuevo String
     ruta_modificada.push_str(ruta); // Agregar la ruta original (sin clonar)
-    ruta_modificada.push_str("/"); // Modificar
-    ruta_modificada.push_str(&tabla.to_ascii_lowercase()); // Modificar
-    return ruta_modificada;
+    ruta_modificada.push('/'); // Modificar
+    ruta_modificada.push_str(&tabla.to_lowercase()); // Modificar
+    if Path::new(&ruta_modificada).is_file() {
+        return ruta_modificada;
+    }
+    extensiones
+        .iter()
+        .map(|extension| format!("{}.{}", ruta_modificada, extension))
+        .find(|candidata| Path::new(candidata).is_file())
+        .unwrap_or(ruta_modificada)
 }
 
-/// Lee el archivo en la ruta especificada y devuelve un `BufReader` para procesarlo.
+/// Lee el archivo en la ruta especificada y devuelve un lector con buffer para procesarlo.
 ///
 /// Abre el archivo indicado y crea un `BufReader` que permite la lectura eficiente del archivo.
+/// Si el archivo está comprimido con gzip, se descomprime a medida que se lee (ver
+/// `gzip::Descompresor`). Si no está codificado en UTF-8, se carga completo en memoria y se
+/// convierte a UTF-8. Si la tabla está guardada como JSON, se carga completa y se convierte a
+/// CSV con el delimitador de las opciones. Si se pidió mapear el archivo en memoria, se lee
+/// directamente de la proyección, sin copias ni llamadas al sistema por cada bloque.
+/// Si el contenido empieza con la marca de orden de bytes de UTF-8, se descarta. La ruta
+/// `RUTA_ENTRADA` lee la tabla de la entrada estándar. Con `OpcionesArchivo::progreso`, el
+/// lector informa cuánto se leyó de la tabla (ver `LectorConProgreso`); en las tablas
+/// comprimidas, cuánto se leyó del archivo comprimido.
+///
+/// # Argumentos
+/// - `ruta_archivo`: La ruta del archivo que se desea leer.
+/// - `opciones`: Las opciones que indican si el archivo está comprimido, su codificación y si
+///   se mapea en memoria.
+///
+/// # Retorno
+/// Retorna `Result<Box<dyn BufRead>, io::Error>` que contiene el lector en caso de éxito, o un error de E/S en caso de fallo.
+pub fn leer_archivo(
+    ruta_archivo: &str,
+    opciones: &OpcionesArchivo,
+) -> Result<Box<dyn BufRead>, io::Error> {
+    if opciones.es_json(ruta_archivo) && ruta_archivo != RUTA_ENTRADA {
+        let texto = leer_texto(ruta_archivo, opciones)?;
+        let csv = json::a_csv(&texto, opciones.delimitador(), opciones.nulo())?;
+        let total = csv.len() as u64;
+        let lector = Box::new(Cursor::new(csv.into_bytes()));
+        return descartar_bom(con_progreso(lector, ruta_archivo, total, opciones));
+    }
+    let (lector, total): (Box<dyn BufRead>, u64) = if ruta_archivo == RUTA_ENTRADA {
+        let contenido = leer_entrada()?;
+        (Box::new(contenido), contenido.len() as u64)
+    } else if opciones.mapear_memoria {
+        (
+            mapear_archivo(ruta_archivo)?,
+            fs::metadata(ruta_archivo)?.len(),
+        )
+    } else {
+        let file = File::open(ruta_archivo)?;
+        let total = file.metadata()?.len();
+        (Box::new(BufReader::new(file)), total)
+    };
+    let mut lector = con_progreso(lector, ruta_archivo, total, opciones);
+    if opciones.es_comprimido(ruta_archivo) {
+        lector = Box::new(BufReader::new(gzip::Descompresor::new(lector)));
+    }
+    if opciones.codificacion != Codificacion::Utf8 {
+        let mut contenido = Vec::new();
+        lector.read_to_end(&mut contenido)?;
+        lector = Box::new(Cursor::new(opciones.codificacion.decodificar(contenido)));
+    }
+    descartar_bom(lector)
+}
+
+/// Envuelve el lector en un `LectorConProgreso` si las opciones lo piden.
+fn con_progreso(
+    lector: Box<dyn BufRead>,
+    ruta_archivo: &str,
+    total: u64,
+    opciones: &OpcionesArchivo,
+) -> Box<dyn BufRead> {
+    if opciones.progreso {
+        Box::new(LectorConProgreso::new(lector, ruta_archivo, total))
+    } else {
+        lector
+    }
+}
+
+/// Descarta la marca de orden de bytes de UTF-8 si el contenido empieza con ella.
+fn descartar_bom(mut lector: Box<dyn BufRead>) -> Result<Box<dyn BufRead>, io::Error> {
+    if lector.fill_buf()?.starts_with(BOM_UTF8) {
+        lector.consume(BOM_UTF8.len());
+    }
+    Ok(lector)
+}
+
+/// Cantidad de bytes que se leen entre dos consultas del reloj en `LectorConProgreso`.
+const BYTES_ENTRE_CONSULTAS: u64 = 1 << 20;
+
+/// Tiempo mínimo entre dos informes de `LectorConProgreso`.
+const INTERVALO_PROGRESO: Duration = Duration::from_secs(1);
+
+/// Lector de una tabla que informa periódicamente en la salida de errores cuántos bytes se
+/// leyeron y qué porcentaje del total representan, para las lecturas de tablas grandes.
+///
+/// El primer informe se escribe recién después de `INTERVALO_PROGRESO`, de modo que las
+/// tablas que se leen rápido no informan nada; si se informó algo, al terminar de leer se
+/// informa también el total.
+///
+/// # Campos
+///
+/// - `lector`: El lector de la tabla.
+/// - `ruta`: La ruta de la tabla, con la que empieza cada informe.
+/// - `total`: El tamaño de la tabla, en bytes.
+/// - `leidos`: Los bytes leídos hasta ahora.
+/// - `consultado`: Los bytes leídos la última vez que se consultó el reloj.
+/// - `ultimo_informe`: El momento del último informe, o del inicio de la lectura.
+/// - `informados`: Los bytes leídos en el último informe, o `None` si todavía no se informó.
+pub struct LectorConProgreso {
+    lector: Box<dyn BufRead>,
+    ruta: String,
+    total: u64,
+    leidos: u64,
+    consultado: u64,
+    ultimo_informe: Instant,
+    informados: Option<u64>,
+}
+
+impl LectorConProgreso {
+    pub fn new(lector: Box<dyn BufRead>, ruta: &str, total: u64) -> LectorConProgreso {
+        LectorConProgreso {
+            lector,
+            ruta: ruta.to_string(),
+            total,
+            leidos: 0,
+            consultado: 0,
+            ultimo_informe: Instant::now(),
+            informados: None,
+        }
+    }
+
+    fn informar(&mut self) {
+        registro::progreso(format!(
+            "{}: {}",
+            self.ruta,
+            describir_progreso(self.leidos, self.total)
+        ));
+        self.informados = Some(self.leidos);
+        self.ultimo_informe = Instant::now();
+    }
+}
+
+impl Read for LectorConProgreso {
+    fn read(&mut self, destino: &mut [u8]) -> io::Result<usize> {
+        let disponible = self.fill_buf()?;
+        let cantidad = disponible.len().min(destino.len());
+        destino[..cantidad].copy_from_slice(&disponible[..cantidad]);
+        self.consume(cantidad);
+        Ok(cantidad)
+    }
+}
+
+impl BufRead for LectorConProgreso {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let fin = self.lector.fill_buf()?.is_empty();
+        if fin
+            && self
+                .informados
+                .is_some_and(|informados| informados != self.leidos)
+        {
+            self.informar();
+        }
+        self.lector.fill_buf()
+    }
+
+    fn consume(&mut self, cantidad: usize) {
+        self.lector.consume(cantidad);
+        self.leidos += cantidad as u64;
+        if self.leidos - self.consultado >= BYTES_ENTRE_CONSULTAS {
+            self.consultado = self.leidos;
+            if self.ultimo_informe.elapsed() >= INTERVALO_PROGRESO {
+                self.informar();
+            }
+        }
+    }
+}
+
+/// Describe el avance de una lectura, como `512.0 MiB de 2.0 GiB (25%)`.
+///
+/// # Parámetros
+/// - `leidos`: Los bytes leídos.
+/// - `total`: El tamaño total, en bytes.
+fn describir_progreso(leidos: u64, total: u64) -> String {
+    let porcentaje = (leidos.min(total) * 100).checked_div(total).unwrap_or(100);
+    format!(
+        "{} de {} ({}%)",
+        describir_bytes(leidos),
+        describir_bytes(total),
+        porcentaje
+    )
+}
+
+/// Describe una cantidad de bytes con la unidad binaria más grande en la que es al menos 1.
+fn describir_bytes(bytes: u64) -> String {
+    const UNIDADES: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut valor = bytes as f64 / 1024.0;
+    let mut unidad = 0;
+    while valor >= 1024.0 && unidad + 1 < UNIDADES.len() {
+        valor /= 1024.0;
+        unidad += 1;
+    }
+    format!("{:.1} {}", valor, UNIDADES[unidad])
+}
+
+/// Devuelve el contenido completo de la entrada estándar, que se lee la primera vez que se
+/// pide. Si las sentencias se leyeron de la entrada estándar, el contenido queda vacío.
+fn leer_entrada() -> Result<&'static [u8], io::Error> {
+    if let Some(contenido) = ENTRADA.get() {
+        return Ok(contenido);
+    }
+    let mut contenido = Vec::new();
+    io::stdin().lock().read_to_end(&mut contenido)?;
+    Ok(ENTRADA.get_or_init(|| contenido))
+}
+
+/// Lee el archivo completo como texto UTF-8, descomprimiéndolo y convirtiéndolo desde su
+/// codificación si hace falta.
 ///
 /// # Argumentos
 /// - `ruta_archivo`: La ruta del archivo que se desea leer.
+/// - `opciones`: Las opciones que indican si el archivo está comprimido y su codificación.
+///
+/// # Retorno
+/// El contenido del archivo, o un error de E/S si no se pudo leer o no es UTF-8 válido.
+pub fn leer_texto(ruta_archivo: &str, opciones: &OpcionesArchivo) -> Result<String, io::Error> {
+    let mut contenido = fs::read(ruta_archivo)?;
+    if opciones.es_comprimido(ruta_archivo) {
+        contenido = gzip::descomprimir(&contenido)?;
+    }
+    String::from_utf8(opciones.codificacion.decodificar(contenido))
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// Proyecta en memoria el archivo indicado y devuelve un lector sobre su contenido.
+///
+/// Modificar o truncar el archivo mientras está proyectado puede terminar el programa, por lo
+/// que esta lectura solo debe usarse sobre tablas que no se modifican durante la consulta.
+#[cfg(all(unix, target_pointer_width = "64"))]
+fn mapear_archivo(ruta_archivo: &str) -> Result<Box<dyn BufRead>, io::Error> {
+    let archivo = File::open(ruta_archivo)?;
+    Ok(Box::new(Cursor::new(mapeo::ArchivoMapeado::abrir(
+        &archivo,
+    )?)))
+}
+
+/// En las plataformas sin `mmap` el archivo se carga completo en memoria.
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+fn mapear_archivo(ruta_archivo: &str) -> Result<Box<dyn BufRead>, io::Error> {
+    Ok(Box::new(Cursor::new(fs::read(ruta_archivo)?)))
+}
+
+/// Proyección de archivos en memoria mediante `mmap`, sin depender de bibliotecas externas.
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod mapeo {
+    use std::ffi::c_void;
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::ptr;
+
+    const PROT_READ: i32 = 1;
+    const MAP_PRIVATE: i32 = 2;
+
+    extern "C" {
+        fn mmap(
+            direccion: *mut c_void,
+            longitud: usize,
+            proteccion: i32,
+            banderas: i32,
+            descriptor: i32,
+            desplazamiento: i64,
+        ) -> *mut c_void;
+        fn munmap(direccion: *mut c_void, longitud: usize) -> i32;
+    }
+
+    /// Archivo proyectado en memoria en modo de solo lectura, que se libera al descartarse.
+    pub struct ArchivoMapeado {
+        direccion: *mut c_void,
+        longitud: usize,
+    }
+
+    impl ArchivoMapeado {
+        /// Proyecta el archivo completo en memoria.
+        ///
+        /// # Argumentos
+        /// - `archivo`: El archivo abierto para lectura.
+        ///
+        /// # Retorno
+        /// La proyección, o el error del sistema si no se pudo crear.
+        pub fn abrir(archivo: &File) -> Result<ArchivoMapeado, io::Error> {
+            let longitud = usize::try_from(archivo.metadata()?.len())
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))?;
+            if longitud == 0 {
+                // `mmap` no admite proyecciones vacías.
+                return Ok(ArchivoMapeado {
+                    direccion: ptr::null_mut(),
+                    longitud,
+                });
+            }
+            // SAFETY: se pide una proyección nueva de solo lectura de un descriptor válido, y
+            // el resultado se controla contra `MAP_FAILED` antes de usarse.
+            let direccion = unsafe {
+                mmap(
+                    ptr::null_mut(),
+                    longitud,
+                    PROT_READ,
+                    MAP_PRIVATE,
+                    archivo.as_raw_fd(),
+                    0,
+                )
+            };
+            if direccion as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(ArchivoMapeado {
+                direccion,
+                longitud,
+            })
+        }
+    }
+
+    impl AsRef<[u8]> for ArchivoMapeado {
+        fn as_ref(&self) -> &[u8] {
+            if self.longitud == 0 {
+                return &[];
+            }
+            // SAFETY: la proyección abarca `longitud` bytes legibles y vive tanto como `self`.
+            unsafe { std::slice::from_raw_parts(self.direccion as *const u8, self.longitud) }
+        }
+    }
+
+    impl Drop for ArchivoMapeado {
+        fn drop(&mut self) {
+            if self.longitud > 0 {
+                // SAFETY: la dirección y la longitud son las de una proyección creada por
+                // `abrir` que no se liberó antes.
+                unsafe {
+                    munmap(self.direccion, self.longitud);
+                }
+            }
+        }
+    }
+}
+
+/// Obtiene los nombres de las columnas de una tabla, en minúsculas.
+///
+/// Si las opciones indican las columnas externamente, se usan esas y no se lee nada del
+/// lector. Si no, se consume la primera línea como encabezado y, si no fue indicado, se detecta
+/// el delimitador a partir de ella.
+///
+/// # Argumentos
+/// - `lector`: El lector posicionado al inicio del archivo de la tabla.
+/// - `opciones`: Las opciones de la tabla, donde se guarda el delimitador detectado.
+///
+/// # Retorno
+/// Retorna los nombres de las columnas o un error de E/S si no se pudo leer el encabezado.
+pub fn leer_encabezado(
+    lector: &mut dyn BufRead,
+    opciones: &mut OpcionesArchivo,
+) -> Result<Vec<String>, io::Error> {
+    if let Some(columnas) = &opciones.columnas {
+        return Ok(columnas
+            .iter()
+            .map(|columna| columna.to_lowercase())
+            .collect());
+    }
+    let encabezado = leer_registro(lector)?.unwrap_or_default();
+    let encabezado = encabezado.trim_end();
+    opciones.detectar_delimitador(encabezado);
+    let (_, campos) = parsear_linea_archivo(encabezado, opciones.delimitador());
+    Ok(campos)
+}
+
+/// Lee el texto completo del siguiente registro de una tabla, sin el salto de línea final.
+///
+/// Un registro ocupa normalmente una línea, pero si un campo entre comillas contiene saltos de
+/// línea, se siguen leyendo líneas hasta que se cierran las comillas.
+///
+/// # Argumentos
+/// - `lector`: El lector del archivo de la tabla.
+///
+/// # Retorno
+/// Retorna el registro, `None` si no quedan registros o un error de E/S si falla la lectura.
+pub fn leer_registro(lector: &mut dyn BufRead) -> Result<Option<String>, io::Error> {
+    let mut registro = String::new();
+    if leer_registro_en(lector, &mut registro)? {
+        Ok(Some(registro))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Lee el siguiente registro de una tabla como `leer_registro`, pero sobre una cadena que se
+/// reutiliza entre registros para no reservar memoria en cada lectura.
+///
+/// # Argumentos
+/// - `lector`: El lector del archivo de la tabla.
+/// - `registro`: La cadena donde se deja el registro; se vacía antes de leer.
 ///
 /// # Retorno
-/// Retorna `Result<BufReader<File>, io::Error>` que contiene el `BufReader` en caso de éxito, o un error de E/S en caso de fallo.
+/// Retorna `true` si se leyó un registro, `false` si no quedan registros o un error de E/S si
+/// falla la lectura.
+pub fn leer_registro_en(
+    lector: &mut dyn BufRead,
+    registro: &mut String,
+) -> Result<bool, io::Error> {
+    registro.clear();
+    let mut entre_comillas = false;
+    loop {
+        let inicio = registro.len();
+        if lector.read_line(registro)? == 0 {
+            break;
+        }
+        // Cada comilla abre o cierra un campo citado; las comillas escapadas (`""`) se anulan.
+        let comillas = registro[inicio..].matches(COMILLA).count();
+        entre_comillas ^= comillas % 2 == 1;
+        if !entre_comillas {
+            break;
+        }
+    }
+    if registro.is_empty() {
+        return Ok(false);
+    }
+    if registro.ends_with('\n') {
+        registro.pop();
+    }
+    // El `\r` de un fin de línea CRLF se quita aunque falte el `\n`, como en la última línea
+    // de algunos archivos exportados en Windows; si no, quedaría pegado al último campo.
+    if registro.ends_with('\r') && !entre_comillas {
+        registro.pop();
+    }
+    Ok(true)
+}
+
+/// Convención de fin de línea de una tabla, que se respeta al agregarle registros o al
+/// reescribirla.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FinDeLinea {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl FinDeLinea {
+    /// Detecta la convención de un contenido por el final de su primera línea.
+    ///
+    /// # Argumentos
+    /// - `contenido`: El contenido de la tabla, o al menos su primera línea.
+    ///
+    /// # Retorno
+    /// `FinDeLinea::CrLf` si la primera línea termina en `\r\n`, y `FinDeLinea::Lf` si no o si
+    /// el contenido no tiene saltos de línea.
+    pub fn detectar(contenido: &[u8]) -> FinDeLinea {
+        match contenido.iter().position(|&byte| byte == b'\n') {
+            Some(fin) if fin > 0 && contenido[fin - 1] == b'\r' => FinDeLinea::CrLf,
+            _ => FinDeLinea::Lf,
+        }
+    }
 
-pub fn leer_archivo(ruta_archivo: &str) -> Result<BufReader<File>, io::Error> {
-    let file = File::open(ruta_archivo)?;
-    let reader = BufReader::new(file);
-    Ok(reader)
+    /// Devuelve el salto de línea de la convención.
+    pub fn como_texto(self) -> &'static str {
+        match self {
+            FinDeLinea::Lf => "\n",
+            FinDeLinea::CrLf => "\r\n",
+        }
+    }
+
+    /// Convierte los saltos de línea `\n` de un texto a la convención. Los que ya están
+    /// precedidos por `\r` no cambian.
+    pub fn aplicar(self, texto: &str) -> Cow<'_, str> {
+        if self == FinDeLinea::Lf || !texto.contains('\n') {
+            return Cow::Borrowed(texto);
+        }
+        let mut convertido = String::with_capacity(texto.len() + texto.len() / 16);
+        let mut anterior = None;
+        for caracter in texto.chars() {
+            if caracter == '\n' && anterior != Some('\r') {
+                convertido.push('\r');
+            }
+            convertido.push(caracter);
+            anterior = Some(caracter);
+        }
+        Cow::Owned(convertido)
+    }
+
+    /// Devuelve lo que le falta a un contenido para terminar con un salto de línea, de modo que
+    /// lo que se le agregue empiece en una línea nueva.
+    ///
+    /// # Argumentos
+    /// - `ultimo`: El último byte del contenido, o `None` si está vacío.
+    ///
+    /// # Retorno
+    /// Nada si el contenido está vacío o ya termina en `\n`, `\n` si termina en el `\r` de un
+    /// CRLF incompleto, o el salto de línea completo en otro caso.
+    pub fn faltante(self, ultimo: Option<u8>) -> &'static str {
+        match ultimo {
+            None | Some(b'\n') => "",
+            Some(b'\r') => "\n",
+            Some(_) => self.como_texto(),
+        }
+    }
+}
+
+/// Controla, mientras se recorre una tabla, que cada registro tenga un campo por columna según
+/// el `ModoCampos` de las opciones, llevando la cuenta de la línea del archivo en la que
+/// empieza cada registro.
+#[derive(Debug)]
+pub struct VerificadorCampos {
+    modo: ModoCampos,
+    columnas: usize,
+    delimitador: char,
+    linea: usize,
+}
+
+impl VerificadorCampos {
+    /// Crea el verificador de una tabla cuyo encabezado ya se leyó.
+    ///
+    /// # Argumentos
+    /// - `opciones`: Las opciones de la tabla, con el delimitador ya detectado.
+    /// - `columnas`: La cantidad de columnas de la tabla.
+    pub fn new(opciones: &OpcionesArchivo, columnas: usize) -> VerificadorCampos {
+        VerificadorCampos {
+            modo: opciones.campos,
+            columnas,
+            delimitador: opciones.delimitador(),
+            // Si las columnas no se indicaron, la primera línea fue el encabezado.
+            linea: usize::from(opciones.columnas.is_none()),
+        }
+    }
+
+    /// Verifica el siguiente registro de la tabla, leído con `leer_registro_en`.
+    ///
+    /// # Argumentos
+    /// - `registro`: El texto del registro, que en el modo `Tolerante` se corrige agregando
+    ///   campos vacíos o recortando los sobrantes.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())`, o `Errores::RegistroMalformado` en el modo `Estricto` si el registro
+    /// no tiene un campo por columna.
+    pub fn verificar(&mut self, registro: &mut String) -> Result<(), errores::Errores> {
+        if self.modo == ModoCampos::Libre {
+            return Ok(());
+        }
+        let linea = self.linea + 1;
+        self.linea = linea + registro.matches('\n').count();
+        let separadores = separadores(registro, self.delimitador);
+        let campos = separadores.len() + 1;
+        if campos == self.columnas {
+            return Ok(());
+        }
+        if self.modo == ModoCampos::Estricto {
+            return Err(errores::Errores::RegistroMalformado {
+                linea,
+                campos,
+                columnas: self.columnas,
+            });
+        }
+        if campos < self.columnas {
+            let faltantes = self.columnas - campos;
+            registro.extend(std::iter::repeat_n(self.delimitador, faltantes));
+            registro::advertir(format!(
+                "el registro de la línea {} tiene {} campos y la tabla {} columnas, se completa \
+                 con NULL",
+                linea, campos, self.columnas
+            ));
+        } else {
+            registro.truncate(separadores[self.columnas.saturating_sub(1)]);
+            registro::advertir(format!(
+                "el registro de la línea {} tiene {} campos y la tabla {} columnas, se descartan \
+                 los campos sobrantes",
+                linea, campos, self.columnas
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Devuelve las posiciones, en bytes, de los delimitadores de un registro que separan campos,
+/// es decir, los que no están dentro de un campo entre comillas.
+fn separadores(linea: &str, delimitador: char) -> Vec<usize> {
+    let mut posiciones = Vec::new();
+    let mut desde = 0;
+    let mut entre_comillas = false;
+    while let Some(posicion) = buscar_separador(linea, desde, delimitador) {
+        if linea.as_bytes()[posicion] == COMILLA as u8 {
+            entre_comillas = !entre_comillas;
+            desde = posicion + 1;
+            continue;
+        }
+        desde = posicion + delimitador.len_utf8();
+        if !entre_comillas {
+            posiciones.push(posicion);
+        }
+    }
+    posiciones
 }
 
 /// Parsea una línea del archivo CSV y devuelve dos vectores con los campos originales y en minúsculas.
 ///
-/// Esta función divide la línea en campos usando comas como delimitador y devuelve dos vectores:
-/// uno con los campos tal como están y otro con los campos en minúsculas.
+/// Esta función divide la línea en campos usando el delimitador indicado, respetando los campos
+/// entre comillas dobles según RFC 4180 (`"Pérez, Juan"` es un único campo y `""` dentro de un
+/// campo entre comillas representa una comilla), y devuelve dos vectores: uno con los campos
+/// tal como están y otro con los campos en minúsculas.
 ///
 /// # Argumentos
 /// - `linea`: La línea que se desea procesar.
+/// - `delimitador`: El carácter que separa los campos.
 ///
 /// # Retorno
 /// Devuelve una tupla con dos vectores `Vec<String>`: el primero con los campos originales y el segundo con los campos en minúsculas.
+pub fn parsear_linea_archivo(linea: &str, delimitador: char) -> (Vec<String>, Vec<String>) {
+    let campos: Vec<String> = parsear_registro(linea, delimitador, None)
+        .into_iter()
+        .map(|campo| campo.unwrap_or_default())
+        .collect();
+    let campos_minuscula = campos.iter().map(|campo| campo.to_lowercase()).collect();
+    (campos, campos_minuscula)
+}
+
+/// Parsea un registro de una tabla distinguiendo los valores nulos.
+///
+/// Un campo vacío sin comillas representa `NULL` (`None`), mientras que un campo vacío entre
+/// comillas (`""`) representa una cadena vacía. Si se indica un texto para `NULL`, ese texto
+/// sin comillas también representa `NULL`.
+///
+/// # Argumentos
+/// - `linea`: La línea que se desea procesar.
+/// - `delimitador`: El carácter que separa los campos.
+/// - `nulo`: El texto que representa `NULL`, si hay alguno además del campo vacío.
+///
+/// # Retorno
+/// Devuelve un `Vec<Option<String>>` con el valor de cada campo.
+pub fn parsear_registro(linea: &str, delimitador: char, nulo: Option<&str>) -> Vec<Option<String>> {
+    dividir_registro(linea, delimitador, nulo)
+        .into_iter()
+        .map(|campo| campo.map(Cow::into_owned))
+        .collect()
+}
+
+/// Divide un registro en sus campos sin copiar el texto de la línea siempre que sea posible.
+///
+/// Es equivalente a `parsear_registro`, pero cada campo es un fragmento de `linea`; solo se
+/// arma una cadena nueva para los campos citados que contienen comillas escapadas (`""`) o que
+/// no están completamente entre comillas. Se usa al recorrer las tablas, donde la mayoría de
+/// los campos de cada registro no llegan a usarse.
+///
+/// # Argumentos
+/// - `linea`: La línea que se desea dividir.
+/// - `delimitador`: El carácter que separa los campos.
+/// - `nulo`: El texto que, sin comillas, representa `NULL`.
+///
+/// # Retorno
+/// Devuelve un `Vec<Option<Cow<str>>>` con el valor de cada campo, que es `None` si el campo
+/// no tiene comillas y está vacío o es el texto de `NULL`.
+pub fn dividir_registro<'a>(
+    linea: &'a str,
+    delimitador: char,
+    nulo: Option<&str>,
+) -> Vec<Option<Cow<'a, str>>> {
+    let mut campos = Vec::new();
+    dividir_registro_en(linea, delimitador, nulo, &mut campos);
+    campos
+}
+
+/// Divide un registro como `dividir_registro`, dejando los campos en un vector que se
+/// reutiliza entre registros.
+///
+/// # Argumentos
+/// - `linea`: La línea que se desea dividir.
+/// - `delimitador`: El carácter que separa los campos.
+/// - `nulo`: El texto que, sin comillas, representa `NULL`.
+/// - `campos`: El vector donde se dejan los campos; se vacía antes de dividir.
+pub fn dividir_registro_en<'a>(
+    linea: &'a str,
+    delimitador: char,
+    nulo: Option<&str>,
+    campos: &mut Vec<Option<Cow<'a, str>>>,
+) {
+    campos.clear();
+    let mut inicio = 0;
+    let mut desde = 0;
+    let mut entre_comillas = false;
+    while let Some(posicion) = buscar_separador(linea, desde, delimitador) {
+        if linea.as_bytes()[posicion] == COMILLA as u8 {
+            entre_comillas = !entre_comillas;
+            desde = posicion + 1;
+            continue;
+        }
+        desde = posicion + delimitador.len_utf8();
+        if !entre_comillas {
+            campos.push(valor_de_campo(&linea[inicio..posicion], nulo));
+            inicio = desde;
+        }
+    }
+    campos.push(valor_de_campo(&linea[inicio..], nulo));
+}
+
+/// Busca la siguiente aparición del delimitador o de una comilla en una línea.
+///
+/// Con un delimitador ASCII, que es el caso habitual, la línea se recorre de a ocho bytes por
+/// vez: como los bytes de un carácter UTF-8 de varios bytes nunca son ASCII, cada coincidencia
+/// es siempre el comienzo de un carácter.
+///
+/// # Argumentos
+/// - `linea`: La línea en la que se busca.
+/// - `desde`: La posición, en bytes, desde la que se busca.
+/// - `delimitador`: El carácter que separa los campos.
+///
+/// # Retorno
+/// La posición en bytes del delimitador o la comilla encontrados, o `None` si no hay ninguno.
+fn buscar_separador(linea: &str, desde: usize, delimitador: char) -> Option<usize> {
+    if !delimitador.is_ascii() {
+        return linea[desde..]
+            .find([delimitador, COMILLA])
+            .map(|posicion| desde + posicion);
+    }
+    const UNOS: u64 = u64::from_ne_bytes([0x01; 8]);
+    const ALTOS: u64 = u64::from_ne_bytes([0x80; 8]);
+    // Marca el bit alto de los bytes nulos de `palabra`; el byte marcado más bajo es exacto.
+    let bytes_nulos = |palabra: u64| palabra.wrapping_sub(UNOS) & !palabra & ALTOS;
+    let bytes = &linea.as_bytes()[desde..];
+    let delimitadores = UNOS * delimitador as u64;
+    let comillas = UNOS * COMILLA as u64;
+    let mut posicion = 0;
+    while let Some(bloque) = bytes.get(posicion..posicion + 8) {
+        let palabra = u64::from_le_bytes(bloque.try_into().unwrap_or_default());
+        let coincidencias = bytes_nulos(palabra ^ delimitadores) | bytes_nulos(palabra ^ comillas);
+        if coincidencias != 0 {
+            return Some(desde + posicion + coincidencias.trailing_zeros() as usize / 8);
+        }
+        posicion += 8;
+    }
+    bytes[posicion..]
+        .iter()
+        .position(|&byte| byte == delimitador as u8 || byte == COMILLA as u8)
+        .map(|resto| desde + posicion + resto)
+}
+
+/// Interpreta el texto de un campo tal como aparece en la línea, quitando sus comillas.
+///
+/// # Argumentos
+/// - `campo`: El texto del campo, sin el delimitador.
+/// - `nulo`: El texto que, sin comillas, representa `NULL`.
+///
+/// # Retorno
+/// El valor del campo, prestado de `campo` si no hace falta quitar comillas escapadas, o `None`
+/// si el campo no tiene comillas y está vacío o es el texto de `NULL`.
+fn valor_de_campo<'a>(campo: &'a str, nulo: Option<&str>) -> Option<Cow<'a, str>> {
+    if !campo.contains(COMILLA) {
+        if campo.is_empty() || Some(campo) == nulo {
+            return None;
+        }
+        return Some(Cow::Borrowed(campo));
+    }
+    if let Some(interior) = campo
+        .strip_prefix(COMILLA)
+        .and_then(|resto| resto.strip_suffix(COMILLA))
+    {
+        if !interior.contains(COMILLA) {
+            return Some(Cow::Borrowed(interior));
+        }
+    }
+
+    let mut valor = String::with_capacity(campo.len());
+    let mut entre_comillas = false;
+    let mut caracteres = campo.chars().peekable();
+    while let Some(caracter) = caracteres.next() {
+        if caracter != COMILLA {
+            valor.push(caracter);
+        } else if !entre_comillas {
+            entre_comillas = true;
+        } else if caracteres.peek() == Some(&COMILLA) {
+            valor.push(COMILLA);
+            caracteres.next();
+        } else {
+            entre_comillas = false;
+        }
+    }
+    Some(Cow::Owned(valor))
+}
+
+/// Arma una línea CSV a partir de los campos de un registro, distinguiendo los valores nulos.
+///
+/// Los campos que contienen el delimitador, comillas dobles o saltos de línea se escriben
+/// entre comillas dobles, duplicando las comillas que contengan. Los valores `None` se escriben como un campo vacío sin comillas y las cadenas vacías como
+/// `""`, de modo que `parsear_registro` recupera exactamente los mismos valores. Si se indica
+/// un texto para `NULL`, los valores `None` se escriben con ese texto y los valores iguales a
+/// él, entre comillas.
+///
+/// # Argumentos
+/// - `campos`: Los valores de cada campo.
+/// - `delimitador`: El carácter que separa los campos.
+/// - `nulo`: El texto que representa `NULL`, o `None` para usar el campo vacío.
+///
+/// # Retorno
+/// Devuelve la línea como un `String`, sin salto de línea final.
+pub fn formatear_registro(
+    campos: &[Option<String>],
+    delimitador: char,
+    nulo: Option<&str>,
+) -> String {
+    let campos: Vec<String> = campos
+        .iter()
+        .map(|campo| match campo {
+            None => nulo
+                .map(|nulo| formatear_campo(nulo, delimitador))
+                .unwrap_or_default(),
+            Some(campo) if campo.is_empty() || Some(campo.as_str()) == nulo => {
+                format!("{}{}{}", COMILLA, campo.replace(COMILLA, "\"\""), COMILLA)
+            }
+            Some(campo) => formatear_campo(campo, delimitador),
+        })
+        .collect();
+    campos.join(&delimitador.to_string())
+}
+
+fn formatear_campo(campo: &str, delimitador: char) -> String {
+    let requiere_comillas = campo
+        .chars()
+        .any(|c| c == delimitador || c == COMILLA || c == '\n' || c == '\r');
+    if !requiere_comillas {
+        return campo.to_string();
+    }
+    let escapado = campo.replace(COMILLA, "\"\"");
+    format!("{}{}{}", COMILLA, escapado, COMILLA)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registro(campos: &[&str]) -> Vec<Option<String>> {
+        campos.iter().map(|campo| Some(campo.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parsear_linea_con_campos_entre_comillas() {
+        let (campos, campos_minuscula) =
+            parsear_linea_archivo("1,\"Pérez, Juan\",\"dijo \"\"hola\"\"\",", DELIMITADOR);
+
+        assert_eq!(campos, vec!["1", "Pérez, Juan", "dijo \"hola\"", ""]);
+        assert_eq!(campos_minuscula[1], "pérez, juan");
+    }
+
+    #[test]
+    fn test_parsear_linea_simple() {
+        let (campos, _) = parsear_linea_archivo("Luis,34,Madrid", DELIMITADOR);
+        assert_eq!(campos, vec!["Luis", "34", "Madrid"]);
+    }
+
+    #[test]
+    fn test_formatear_linea_cita_solo_lo_necesario() {
+        let linea = formatear_registro(
+            &registro(&["Pérez, Juan", "34", "dijo \"hola\""]),
+            DELIMITADOR,
+            None,
+        );
+        assert_eq!(linea, "\"Pérez, Juan\",34,\"dijo \"\"hola\"\"\"");
+    }
+
+    #[test]
+    fn test_formatear_y_parsear_es_ida_y_vuelta() {
+        let campos = registro(&["a,b", "\"", "c"]);
+        let parseados = parsear_registro(
+            &formatear_registro(&campos, DELIMITADOR, None),
+            DELIMITADOR,
+            None,
+        );
+        assert_eq!(parseados, campos);
+    }
+
+    #[test]
+    fn test_parsear_y_formatear_con_otro_delimitador() {
+        let (campos, _) = parsear_linea_archivo("Luis;\"34;5\";Madrid", ';');
+        assert_eq!(campos, vec!["Luis", "34;5", "Madrid"]);
+        assert_eq!(
+            formatear_registro(&registro(&["a,b", "c\td"]), '\t', None),
+            "a,b\t\"c\td\""
+        );
+    }
+
+    #[test]
+    fn test_registro_distingue_nulo_de_cadena_vacia() {
+        let registro = parsear_registro("Ana,,\"\",\"x\"", DELIMITADOR, None);
+        assert_eq!(
+            registro,
+            vec![
+                Some("Ana".to_string()),
+                None,
+                Some(String::new()),
+                Some("x".to_string())
+            ]
+        );
+        assert_eq!(
+            formatear_registro(&registro, DELIMITADOR, None),
+            "Ana,,\"\",x"
+        );
+        assert_eq!(
+            parsear_registro(&formatear_registro(&registro, ';', None), ';', None),
+            registro
+        );
+    }
+
+    #[test]
+    fn test_registro_con_texto_para_nulo() {
+        let registro = parsear_registro("\\N,,\"\\N\",NA", DELIMITADOR, Some("\\N"));
+        assert_eq!(
+            registro,
+            vec![None, None, Some("\\N".to_string()), Some("NA".to_string())]
+        );
+        let linea = formatear_registro(&registro, DELIMITADOR, Some("\\N"));
+        assert_eq!(linea, "\\N,\\N,\"\\N\",NA");
+        assert_eq!(parsear_registro(&linea, DELIMITADOR, Some("\\N")), registro);
+    }
+
+    #[test]
+    fn test_dividir_registro_presta_los_campos_sin_comillas_escapadas() {
+        let linea = "Ana,\"Pérez, Juan\",\"dijo \"\"hola\"\"\",,\"\"";
+        let campos = dividir_registro(linea, DELIMITADOR, None);
+        assert!(matches!(campos[0], Some(Cow::Borrowed("Ana"))));
+        assert!(matches!(campos[1], Some(Cow::Borrowed("Pérez, Juan"))));
+        assert!(matches!(&campos[2], Some(Cow::Owned(campo)) if campo == "dijo \"hola\""));
+        assert_eq!(campos[3], None);
+        assert!(matches!(campos[4], Some(Cow::Borrowed(""))));
+        assert_eq!(
+            campos
+                .into_iter()
+                .map(|campo| campo.map(Cow::into_owned))
+                .collect::<Vec<_>>(),
+            parsear_registro(linea, DELIMITADOR, None)
+        );
+    }
+
+    #[test]
+    fn test_dividir_registro_con_lineas_largas_y_delimitadores_no_ascii() {
+        let linea = "identificador_largo,\"Pérez, Juan Ñandú\",,ciudad con espacios,\"\"";
+        let esperado = vec![
+            Some("identificador_largo".to_string()),
+            Some("Pérez, Juan Ñandú".to_string()),
+            None,
+            Some("ciudad con espacios".to_string()),
+            Some(String::new()),
+        ];
+        assert_eq!(parsear_registro(linea, DELIMITADOR, None), esperado);
+        assert_eq!(
+            parsear_registro(&linea.replace(',', "¦"), '¦', None),
+            esperado
+                .iter()
+                .map(|campo| campo.as_ref().map(|campo| campo.replace(',', "¦")))
+                .collect::<Vec<_>>()
+        );
+        for largo in 0..20 {
+            let campo = "x".repeat(largo);
+            assert_eq!(
+                parsear_registro(&format!("{campo}\t{campo}"), '\t', None),
+                vec![Some(campo.clone()).filter(|_| largo > 0); 2]
+            );
+        }
+    }
+
+    #[test]
+    fn test_leer_tabla_comprimida() {
+        let mut lector =
+            leer_archivo("tablas/personas_comprimida.gz", &OpcionesArchivo::default()).unwrap();
+        let mut encabezado = String::new();
+        lector.read_line(&mut encabezado).unwrap();
+        assert_eq!(encabezado.trim_end(), "nombre,edad,ciudad");
+        assert!(OpcionesArchivo {
+            comprimido: true,
+            ..Default::default()
+        }
+        .es_comprimido("tablas/personas"));
+    }
+
+    #[test]
+    fn test_leer_encabezado_externo() {
+        let mut opciones = OpcionesArchivo {
+            columnas: Some(vec!["Nombre".to_string(), "edad".to_string()]),
+            ..Default::default()
+        };
+        let mut lector = Cursor::new("Luis;34\n");
+        assert_eq!(
+            leer_encabezado(&mut lector, &mut opciones).unwrap(),
+            vec!["nombre", "edad"]
+        );
+        let mut primera_linea = String::new();
+        lector.read_line(&mut primera_linea).unwrap();
+        assert_eq!(primera_linea, "Luis;34\n");
+
+        let mut opciones = OpcionesArchivo::default();
+        let mut lector = Cursor::new("Nombre;Edad\nLuis;34\n");
+        assert_eq!(
+            leer_encabezado(&mut lector, &mut opciones).unwrap(),
+            vec!["nombre", "edad"]
+        );
+        assert_eq!(opciones.delimitador, Some(';'));
+    }
+
+    #[test]
+    fn test_leer_archivo_con_bom_y_latin1() {
+        let directorio = std::env::temp_dir().join("test_leer_archivo_con_bom_y_latin1");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta_bom = directorio.join("bom");
+        fs::write(&ruta_bom, b"\xEF\xBB\xBFnombre,edad\n").unwrap();
+        let ruta_latin1 = directorio.join("latin1");
+        fs::write(&ruta_latin1, b"a\xF1o,ciudad\n").unwrap();
+
+        let mut opciones = OpcionesArchivo::default();
+        let mut lector = leer_archivo(ruta_bom.to_str().unwrap(), &opciones).unwrap();
+        assert_eq!(
+            leer_encabezado(lector.as_mut(), &mut opciones).unwrap(),
+            vec!["nombre", "edad"]
+        );
+
+        let mut opciones = OpcionesArchivo {
+            codificacion: Codificacion::Latin1,
+            ..Default::default()
+        };
+        let mut lector = leer_archivo(ruta_latin1.to_str().unwrap(), &opciones).unwrap();
+        assert_eq!(
+            leer_encabezado(lector.as_mut(), &mut opciones).unwrap(),
+            vec!["año", "ciudad"]
+        );
+        fs::remove_dir_all(&directorio).unwrap();
+
+        assert_eq!(Codificacion::Latin1.codificar("año").unwrap(), b"a\xF1o");
+        assert!(Codificacion::Latin1.codificar("€").is_err());
+    }
+
+    #[test]
+    fn test_registros_con_fin_de_linea_crlf() {
+        let mut lector = Cursor::new("nombre,edad\r\nAna,30\r\nLuis,45\r");
+        let mut registros = Vec::new();
+        while let Some(registro) = leer_registro(&mut lector).unwrap() {
+            registros.push(registro);
+        }
+        assert_eq!(registros, vec!["nombre,edad", "Ana,30", "Luis,45"]);
+        let mut lector = Cursor::new("1,\"abierto\r");
+        assert_eq!(
+            leer_registro(&mut lector).unwrap().as_deref(),
+            Some("1,\"abierto\r")
+        );
+
+        assert_eq!(FinDeLinea::detectar(b"a,b\r\nc,d\n"), FinDeLinea::CrLf);
+        assert_eq!(FinDeLinea::detectar(b"a,b\nc,d\r\n"), FinDeLinea::Lf);
+        assert_eq!(FinDeLinea::detectar(b"a,b"), FinDeLinea::Lf);
+        assert_eq!(FinDeLinea::CrLf.aplicar("a\nb\r\nc\n"), "a\r\nb\r\nc\r\n");
+        assert_eq!(FinDeLinea::Lf.aplicar("a\nb\r\n"), "a\nb\r\n");
+        assert_eq!(FinDeLinea::CrLf.faltante(None), "");
+        assert_eq!(FinDeLinea::CrLf.faltante(Some(b'\n')), "");
+        assert_eq!(FinDeLinea::CrLf.faltante(Some(b'\r')), "\n");
+        assert_eq!(FinDeLinea::CrLf.faltante(Some(b'x')), "\r\n");
+        assert_eq!(FinDeLinea::Lf.faltante(Some(b'x')), "\n");
+    }
+
+    #[test]
+    fn test_leer_registros_con_saltos_de_linea() {
+        let contenido = "1,\"calle 1\r\npiso 2\",x\r\n2,\"dijo \"\"hola\"\"\",y\n3,\"a\n\n\"";
+        let mut lector = Cursor::new(contenido);
+        let mut registro_leido = String::new();
+        let mut registros = Vec::new();
+        while leer_registro_en(&mut lector, &mut registro_leido).unwrap() {
+            registros.push(registro_leido.clone());
+        }
+        assert_eq!(
+            registros,
+            vec![
+                "1,\"calle 1\r\npiso 2\",x",
+                "2,\"dijo \"\"hola\"\"\",y",
+                "3,\"a\n\n\""
+            ]
+        );
+        assert_eq!(
+            parsear_registro(&registros[0], DELIMITADOR, None),
+            registro(&["1", "calle 1\r\npiso 2", "x"])
+        );
+    }
+
+    #[test]
+    fn test_verificar_cantidad_de_campos() {
+        let mut opciones = OpcionesArchivo {
+            campos: ModoCampos::Estricto,
+            ..OpcionesArchivo::default()
+        };
+        let mut verificador = VerificadorCampos::new(&opciones, 3);
+        let mut correcto = "1,\"a,\nb\",x".to_string();
+        verificador.verificar(&mut correcto).unwrap();
+        let mut corto = "2,y".to_string();
+        assert_eq!(
+            verificador.verificar(&mut corto).unwrap_err(),
+            errores::Errores::RegistroMalformado {
+                linea: 4,
+                campos: 2,
+                columnas: 3
+            }
+        );
+
+        opciones.campos = ModoCampos::Tolerante;
+        let mut verificador = VerificadorCampos::new(&opciones, 3);
+        verificador.verificar(&mut corto).unwrap();
+        assert_eq!(corto, "2,y,");
+        let mut largo = "3,\"z,w\",v,sobra,otra".to_string();
+        verificador.verificar(&mut largo).unwrap();
+        assert_eq!(largo, "3,\"z,w\",v");
+
+        opciones.campos = ModoCampos::Libre;
+        let mut corto = "4".to_string();
+        VerificadorCampos::new(&opciones, 3)
+            .verificar(&mut corto)
+            .unwrap();
+        assert_eq!(corto, "4");
+    }
+
+    #[test]
+    fn test_leer_archivo_mapeado_en_memoria() {
+        let opciones = OpcionesArchivo {
+            mapear_memoria: true,
+            ..Default::default()
+        };
+        let mapeadas: Vec<String> = leer_archivo("tablas/personas", &opciones)
+            .unwrap()
+            .lines()
+            .map(|linea| linea.unwrap())
+            .collect();
+        let leidas: Vec<String> = leer_archivo("tablas/personas", &OpcionesArchivo::default())
+            .unwrap()
+            .lines()
+            .map(|linea| linea.unwrap())
+            .collect();
+        assert_eq!(mapeadas, leidas);
+
+        let ruta_vacia = std::env::temp_dir().join("test_leer_archivo_mapeado_vacio");
+        fs::write(&ruta_vacia, b"").unwrap();
+        let mut lector = leer_archivo(ruta_vacia.to_str().unwrap(), &opciones).unwrap();
+        assert_eq!(leer_registro(lector.as_mut()).unwrap(), None);
+        fs::remove_file(&ruta_vacia).unwrap();
+    }
+
+    #[test]
+    fn test_lector_con_progreso() {
+        assert_eq!(describir_progreso(512, 2048), "512 B de 2.0 KiB (25%)");
+        assert_eq!(
+            describir_progreso(3 << 29, 3 << 30),
+            "1.5 GiB de 3.0 GiB (50%)"
+        );
+        assert_eq!(describir_progreso(0, 0), "0 B de 0 B (100%)");
+
+        let opciones = OpcionesArchivo {
+            progreso: true,
+            ..OpcionesArchivo::default()
+        };
+        let mut lector = leer_archivo("tablas/personas", &opciones).unwrap();
+        let mut contenido = String::new();
+        lector.read_to_string(&mut contenido).unwrap();
+        assert_eq!(contenido, fs::read_to_string("tablas/personas").unwrap());
+    }
+
+    #[test]
+    fn test_detectar_delimitador() {
+        assert_eq!(detectar_delimitador("nombre,edad,ciudad"), ',');
+        assert_eq!(detectar_delimitador("nombre;edad;ciudad"), ';');
+        assert_eq!(detectar_delimitador("nombre\tedad\tciudad"), '\t');
+        assert_eq!(detectar_delimitador("\"a;b\",c"), ',');
+        assert_eq!(detectar_delimitador("nombre"), ',');
+    }
+
+    #[test]
+    fn test_parsear_delimitador() {
+        assert_eq!(parsear_delimitador(";"), Some(';'));
+        assert_eq!(parsear_delimitador("tab"), Some('\t'));
+        assert_eq!(parsear_delimitador("\\t"), Some('\t'));
+        assert_eq!(parsear_delimitador("ab"), None);
+        assert_eq!(parsear_delimitador("\""), None);
+    }
+
+    #[test]
+    fn test_opciones_respetan_delimitador_explicito() {
+        let mut opciones = OpcionesArchivo {
+            delimitador: Some('|'),
+            ..Default::default()
+        };
+        opciones.detectar_delimitador("a;b;c");
+        assert_eq!(opciones.delimitador(), '|');
 
-pub fn parsear_linea_archivo(linea: &String) -> (Vec<String>, Vec<String>) {
-    return (
-        linea.split(",").map(|s| s.to_string()).collect(),
-        linea
-            .to_lowercase()
-            .split(",")
-            .map(|s| s.to_string())
-            .collect(),
-    );
+        let mut opciones = OpcionesArchivo::default();
+        opciones.detectar_delimitador("a;b;c");
+        assert_eq!(opciones.delimitador(), ';');
+    }
 }
@@ -0,0 +1,957 @@
+use crate::archivo::{parsear_delimitador, Codificacion, ModoCampos, OpcionesArchivo};
+use crate::errores;
+use crate::salida::{FormatoSalida, OpcionesSalida, PoliticaComillas};
+use std::fs;
+use std::io::{self, Read};
+
+/// Texto de ayuda que se muestra con `--help`.
+pub const AYUDA: &str = "\
+Uso: base_de_datos [query] [opciones] <ruta_tablas> <consulta>
+     base_de_datos [query] [opciones] <ruta_tablas> --file <ruta>
+     base_de_datos [query] [opciones] <ruta_tablas> --serve-http <direccion>
+     base_de_datos [query] [opciones] <ruta_tablas> --serve-pg <direccion>
+     base_de_datos import [opciones] <ruta_tablas> <tabla> <archivo>
+     base_de_datos export [opciones] <ruta_tablas> <tabla>
+     base_de_datos schema [opciones] <ruta_tablas> [<tabla>...]
+     base_de_datos check [--repair] [opciones] <ruta_tablas> [<tabla>...]
+
+Ejecuta sentencias SQL, separadas por ';', sobre tablas CSV. Si la consulta es '-', las
+sentencias se leen de la entrada estándar. La ruta de las tablas puede ser un directorio, un
+manifiesto o un único archivo .csv; con FROM '<ruta>' se consulta cualquier archivo, y con
+FROM - (o FROM stdin) se consulta el CSV recibido por la entrada estándar. CHECK TABLE <tabla>
+informa los registros con problemas y REPAIR TABLE <tabla> además los corrige. ANALYZE <tabla>
+guarda las estadísticas de la tabla en <tabla>.estadisticas, que se usan para planificar.
+COPY (SELECT ...) TO '<archivo>' [FORMAT <f>] [HEADER] escribe el resultado en el archivo.
+COPY <tabla> [(<columnas>)] FROM '<archivo>' [HEADER] [DELIMITER '<c>'] agrega a la tabla los
+registros del CSV, que se verifican como los de un INSERT.
+Con --serve-http se atienden consultas por HTTP: POST /query ejecuta la sentencia del cuerpo
+y responde sus filas en JSON, y GET /tables responde los nombres de las tablas. Con
+--serve-pg se atienden conexiones con el protocolo de PostgreSQL, como las de psql. Los
+servidores solo admiten consultas de lectura sobre las tablas, salvo con --allow-writes, y
+nunca COPY.
+
+Subcomandos:
+  query                       Ejecuta las sentencias (es el subcomando por defecto)
+  import                      Agrega a la tabla los registros del archivo CSV, o JSON (una
+                              lista de objetos o NDJSON), por el nombre de sus columnas; si
+                              la tabla no existe, la crea con esas columnas
+  export                      Escribe todos los registros de la tabla en el formato indicado
+  schema                      Muestra las columnas de las tablas (o de todas), con su tipo
+  check                       Revisa las tablas (o todas) como CHECK TABLE; con --repair las
+                              corrige como REPAIR TABLE
+Las opciones de los resultados no se admiten con import, y --file, --serve-http, --serve-pg,
+--allow-writes, --backup, --dry-run y --parallel solo se admiten con query.
+
+Opciones:
+  -t, --tablas <ruta>         Directorio de tablas o manifiesto (en lugar de <ruta_tablas>);
+                              repetida, o con rutas separadas por ':', se busca en cada una
+  -f, --file <ruta>           Archivo .sql con las sentencias a ejecutar
+  -o, --output <ruta>         Archivo donde se escriben los resultados
+      --serve-http <dir>      Atiende consultas por HTTP en la dirección (ej. 127.0.0.1:8080)
+      --serve-pg <dir>        Atiende conexiones de PostgreSQL en la dirección (ej. 127.0.0.1:5432)
+      --allow-writes          Con --serve-http o --serve-pg, admite las sentencias que
+                              modifican las tablas
+      --backup                Guarda tabla.bak-AAAAMMDDHHMMSS antes de que un COMMIT la reemplace
+      --dry-run               Muestra los registros que se agregarían, sin modificar las tablas
+      --repair                Con check, corrige las tablas como REPAIR TABLE
+      --formato <f>           Formato de los resultados: csv, tabla, markdown, html, xml, yaml,
+                              json
+      --nulo-salida <t>       Texto con el que se muestra NULL en los resultados
+      --comillas <p>          Cuándo se citan los campos csv: necesario, siempre, nunca
+      --comilla <c>           Carácter con el que se citan los campos csv (por defecto: \")
+      --no-header             Omite la fila con los nombres de las columnas en los resultados
+      --resumen               Muestra las filas de cada sentencia y lo que tardó, en stderr
+      --parallel              Ejecuta al mismo tiempo los SELECT consecutivos, marcando cada
+                              línea de sus resultados con el número de la sentencia
+  -d, --delimitador <c>       Delimitador de campos de las tablas: un carácter o 'tab'
+      --extensiones <e1,...>  Extensiones de los archivos de las tablas (por defecto: csv,json)
+      --columnas <c1,c2,...>  Columnas de las tablas que no tienen fila de encabezado
+      --codificacion <cod>    Codificación de las tablas: utf-8, latin1
+      --nulo <t>              Texto que representa NULL en las tablas
+      --gzip                  Las tablas están comprimidas con gzip
+      --mmap                  Lee las tablas proyectándolas en memoria
+      --estricto              Falla si un registro no tiene un campo por columna, con su línea
+      --tolerante             Completa o recorta esos registros, avisando en stderr
+      --progreso              Muestra en stderr cuánto se leyó de las tablas grandes
+      --shuffle               Ordena al azar el resultado de cada SELECT (o sus empates)
+  -v, --verbose               Registra detalles en la salida de errores (-vv: más detalles)
+      --errores-json          Escribe los errores como JSON en la salida de errores
+  -h, --help                  Muestra esta ayuda
+";
+
+/// Argumentos con los que se ejecuta el programa.
+///
+/// La forma de invocación es `[query] [opciones] <ruta_tablas> <consulta>`, o
+/// `[query] [opciones] <ruta_tablas> --file <ruta>`, donde las opciones pueden aparecer en
+/// cualquier posición (ver `AYUDA`). En lugar de `query`, el primer argumento puede ser otro
+/// subcomando, con sus propios argumentos (ver `Comando`). Las opciones que no tienen sentido
+/// para el subcomando no se admiten (ver `admite_opcion`):
+///
+/// - `--tablas <ruta>` (o `-t`): La ruta de las tablas, en lugar del primer argumento. Si se
+///   repite, las tablas se buscan en cada ruta, en orden (ver `resolvedor::crear_resolvedor`).
+/// - `--file <ruta>` (o `-f`): El archivo `.sql` con las sentencias a ejecutar.
+/// - `--delimitador <c>` (o `-d`): El delimitador de campos de las tablas.
+/// - `--extensiones <e1,e2,...>` (o `--extensions`): Las extensiones con las que se buscan los
+///   archivos de las tablas que no existen con el nombre exacto (por defecto, `csv` y `json`).
+/// - `--gzip`: Las tablas están comprimidas aunque su ruta no termine en `.gz`.
+/// - `--mmap`: Las tablas se leen proyectándolas en memoria.
+/// - `--estricto` (o `--strict`) y `--tolerante` (o `--lenient`): Cómo se tratan los registros
+///   que no tienen un campo por columna (ver `ModoCampos`).
+/// - `--progreso` (o `--progress`): Mientras se lee una tabla, se informa periódicamente en la
+///   salida de errores cuánto se leyó (ver `LectorConProgreso`).
+/// - `--shuffle` (o `--mezclar`): El resultado de cada `SELECT` se ordena al azar; si la
+///   consulta tiene `ORDER BY`, solo se mezclan las filas que empatan.
+/// - `--columnas <c1,c2,...>`: Las tablas no tienen fila de encabezado y sus columnas son las
+///   indicadas.
+/// - `--codificacion <cod>` (o `--encoding`): La codificación de caracteres de las tablas.
+/// - `--nulo <t>` (o `--null`): El texto que representa `NULL` en las tablas.
+/// - `--formato <f>` (o `--format`): El formato en que se muestran los resultados.
+/// - `--nulo-salida <t>` (o `--null-output`): El texto con el que se muestra `NULL` en los
+///   resultados.
+/// - `--comillas <p>` (o `--quoting`): Cuándo se encierran entre comillas los campos de los
+///   resultados en formato `csv` (ver `PoliticaComillas`).
+/// - `--comilla <c>` (o `--quote-char`): El carácter con el que se encierran los campos de los
+///   resultados en formato `csv`.
+/// - `--no-header` (o `--sin-encabezado`): Los resultados se muestran sin la fila con los
+///   nombres de las columnas (ver `OpcionesSalida`).
+/// - `--resumen` (o `--summary`): Al terminar cada sentencia se muestra en la salida de errores
+///   la cantidad de filas o de registros agregados y el tiempo que tardó.
+/// - `--parallel` (o `--paralelo`): Las consultas de selección consecutivas se ejecutan al
+///   mismo tiempo y sus resultados se intercalan, con cada línea marcada con el número de la
+///   sentencia (ver `transaccion::ejecutar_sentencias`).
+/// - `--output <ruta>` (o `-o`): El archivo donde se escriben los resultados.
+/// - `--serve-http <direccion>` (o `--servir-http`): En lugar de ejecutar una consulta, se
+///   atienden consultas por HTTP en la dirección indicada (ver `servidor::servir`).
+/// - `--serve-pg <direccion>` (o `--servir-pg`): En lugar de ejecutar una consulta, se
+///   atienden conexiones con el protocolo de PostgreSQL en la dirección indicada (ver
+///   `postgres::servir`).
+/// - `--allow-writes` (o `--permitir-escritura`): Con `--serve-http` o `--serve-pg`, se
+///   admiten las sentencias que modifican las tablas, como `INSERT` (ver
+///   `servidor::admitir_sentencia`).
+/// - `--backup` (o `--respaldo`): Antes de que un `COMMIT` reemplace una tabla, se guarda una
+///   copia de respaldo con la fecha y hora (ver `Almacenamiento::respaldar`).
+/// - `--dry-run` (o `--simular`): Las sentencias se ejecutan sin modificar las tablas, y se
+///   informan los registros que se les agregarían (ver `transaccion::simular_sentencias`).
+/// - `-v` (o `--verbose`): Registra en la salida de errores la ruta de cada tabla y la cantidad
+///   de filas de cada sentencia; `-vv` (o `-v` dos veces) registra además las sentencias
+///   parseadas y el árbol de cada `WHERE` (ver `registro`).
+/// - `--errores-json` (o `--json-errors`): Los errores se escriben como JSON en la salida de
+///   errores (ver `Errores::escribir_json`).
+/// - `--help` (o `-h`): Se muestra la ayuda en lugar de ejecutar sentencias.
+/// - `--repair` (o `--reparar`): Con `check`, las tablas además se corrigen.
+///
+/// # Campos
+///
+/// - `ruta_tablas`: La ruta del directorio de tablas o del manifiesto, o una lista de rutas
+///   separadas por `:` (`;` en Windows).
+/// - `comando`: El subcomando, con sus argumentos.
+/// - `consulta`: Las sentencias SQL sin parsear, separadas por `;`, o `-` para leerlas de la
+///   entrada estándar; vacío si se indicó `archivo_consulta`.
+/// - `archivo_consulta`: El archivo del que se leen las sentencias, si se indicó con `--file`.
+/// - `extensiones`: Las extensiones indicadas con `--extensiones`, sin el punto, o `None` para
+///   usar las de por defecto.
+/// - `opciones_archivo`: Las `OpcionesArchivo` con las que se leen las tablas.
+/// - `opciones_salida`: Las `OpcionesSalida` con las que se muestran los resultados.
+/// - `ruta_salida`: El archivo donde se escriben los resultados, o `None` para la salida
+///   estándar.
+/// - `servir_http`: La dirección en la que se atienden consultas por HTTP, si se indicó
+///   `--serve-http`; en ese caso no hay consulta.
+/// - `servir_pg`: La dirección en la que se atienden conexiones con el protocolo de
+///   PostgreSQL, si se indicó `--serve-pg`; en ese caso tampoco hay consulta.
+/// - `permitir_escritura`: Si se indicó `--allow-writes`.
+/// - `simular`: Si se indicó `--dry-run`.
+/// - `errores_json`: Si los errores se escriben como JSON.
+/// - `verbosidad`: El nivel de verbosidad, `0` si no se indicó `-v`.
+/// - `ayuda`: Si se pidió la ayuda con `--help`; en ese caso el resto de los campos no se
+///   completan.
+#[derive(Debug, Default, PartialEq)]
+pub struct Argumentos {
+    pub ruta_tablas: String,
+    pub comando: Comando,
+    pub consulta: String,
+    pub archivo_consulta: Option<String>,
+    pub extensiones: Option<Vec<String>>,
+    pub opciones_archivo: OpcionesArchivo,
+    pub opciones_salida: OpcionesSalida,
+    pub ruta_salida: Option<String>,
+    pub servir_http: Option<String>,
+    pub servir_pg: Option<String>,
+    pub permitir_escritura: bool,
+    pub simular: bool,
+    pub errores_json: bool,
+    pub verbosidad: u8,
+    pub ayuda: bool,
+}
+
+/// Subcomando con el que se ejecuta el programa, que es el primer argumento; si no se indica,
+/// es `Consulta`.
+///
+/// - `Consulta`: `query` (o `consulta`), que ejecuta las sentencias indicadas en
+///   `Argumentos::consulta` o en `Argumentos::archivo_consulta`.
+/// - `Importar`: `import <tabla> <archivo>` (o `importar`), que agrega a la tabla los registros
+///   del archivo CSV o JSON, asignando sus campos a las columnas con el mismo nombre en su
+///   encabezado, o crea la tabla si no existe (ver `comandos::importar`).
+/// - `Exportar`: `export <tabla>` (o `exportar`), que escribe todos los registros de la tabla,
+///   como `SELECT * FROM <tabla>`.
+/// - `Esquema`: `schema [<tabla>...]` (o `esquema`), que escribe las columnas de las tablas,
+///   o de todas si no se indica ninguna (ver `comandos::escribir_esquema`).
+/// - `Revisar`: `check [<tabla>...]` (o `revisar`), que revisa las tablas, o todas si no se
+///   indica ninguna, como `CHECK TABLE`; con `--repair`, como `REPAIR TABLE`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Comando {
+    #[default]
+    Consulta,
+    Importar {
+        tabla: String,
+        archivo: String,
+    },
+    Exportar {
+        tabla: String,
+    },
+    Esquema {
+        tablas: Vec<String>,
+    },
+    Revisar {
+        tablas: Vec<String>,
+        reparar: bool,
+    },
+}
+
+/// Nombres de los subcomandos, junto con su nombre en castellano.
+const SUBCOMANDOS: [(&str, &str); 5] = [
+    ("query", "consulta"),
+    ("import", "importar"),
+    ("export", "exportar"),
+    ("schema", "esquema"),
+    ("check", "revisar"),
+];
+
+/// Indica si la opción se puede usar con el subcomando: las de los resultados no se admiten al
+/// importar, y las que ejecutan o sirven sentencias solo al consultar.
+///
+/// # Parámetros
+/// - `subcomando`: El nombre en inglés del subcomando (ver `SUBCOMANDOS`).
+/// - `opcion`: La opción, tal como se escribió.
+fn admite_opcion(subcomando: &str, opcion: &str) -> bool {
+    match opcion {
+        "--file"
+        | "-f"
+        | "--serve-http"
+        | "--servir-http"
+        | "--serve-pg"
+        | "--servir-pg"
+        | "--allow-writes"
+        | "--permitir-escritura"
+        | "--backup"
+        | "--respaldo"
+        | "--dry-run"
+        | "--simular"
+        | "--parallel"
+        | "--paralelo" => subcomando == "query",
+        "--shuffle" | "--mezclar" => matches!(subcomando, "query" | "export"),
+        "--repair" | "--reparar" => subcomando == "check",
+        "--formato" | "--format" | "--nulo-salida" | "--null-output" | "--comillas"
+        | "--quoting" | "--comilla" | "--quote-char" | "--no-header" | "--sin-encabezado"
+        | "--resumen" | "--summary" | "--output" | "-o" => subcomando != "import",
+        _ => true,
+    }
+}
+
+/// Indica si el argumento es la opción `--errores-json`.
+pub fn es_opcion_errores_json(argumento: &str) -> bool {
+    argumento == "--errores-json" || argumento == "--json-errors"
+}
+
+fn argumento_invalido(motivo: String) -> errores::Errores {
+    errores::Errores::ArgumentoInvalido(motivo)
+}
+
+/// Obtiene el valor de la opción en la posición `index`, que es el argumento siguiente.
+fn valor_de(args: &[String], index: usize) -> Result<&str, errores::Errores> {
+    args.get(index + 1)
+        .map(String::as_str)
+        .ok_or_else(|| argumento_invalido(format!("falta el valor de la opción '{}'", args[index])))
+}
+
+impl Argumentos {
+    /// Interpreta los argumentos recibidos por línea de comandos.
+    ///
+    /// # Parámetros
+    /// - `args`: Los argumentos del programa, sin incluir el nombre del ejecutable.
+    ///
+    /// # Retorno
+    /// Retorna los `Argumentos` o `Errores::ArgumentoInvalido` con el motivo si una opción no
+    /// existe o le falta su valor, el valor no es válido, o falta o sobra algún argumento.
+    pub fn parsear(args: &[String]) -> Result<Argumentos, errores::Errores> {
+        if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+            return Ok(Argumentos {
+                ayuda: true,
+                ..Argumentos::default()
+            });
+        }
+        let subcomando = args.first().and_then(|primero| {
+            SUBCOMANDOS
+                .iter()
+                .find(|(nombre, nombre_castellano)| {
+                    primero == nombre || primero == nombre_castellano
+                })
+                .map(|(nombre, _)| *nombre)
+        });
+        let args = if subcomando.is_some() {
+            &args[1..]
+        } else {
+            args
+        };
+        let subcomando = subcomando.unwrap_or("query");
+        let mut argumentos = Argumentos::default();
+        let mut rutas_tablas = Vec::new();
+        let mut posicionales = Vec::new();
+        let mut reparar = false;
+        let mut index = 0;
+        while index < args.len() {
+            if !admite_opcion(subcomando, &args[index]) {
+                return Err(argumento_invalido(format!(
+                    "la opción '{}' no se admite con el subcomando {}",
+                    args[index], subcomando
+                )));
+            }
+            let opciones_archivo = &mut argumentos.opciones_archivo;
+            match args[index].as_str() {
+                "--tablas" | "-t" => {
+                    rutas_tablas.push(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--delimitador" | "-d" => {
+                    let valor = valor_de(args, index)?;
+                    let delimitador = parsear_delimitador(valor).ok_or_else(|| {
+                        argumento_invalido(format!(
+                            "el delimitador '{}' no es válido, debe ser un único carácter o 'tab'",
+                            valor
+                        ))
+                    })?;
+                    opciones_archivo.delimitador = Some(delimitador);
+                    index += 1;
+                }
+                "--extensiones" | "--extensions" => {
+                    let extensiones: Vec<String> = valor_de(args, index)?
+                        .split(',')
+                        .map(|extension| extension.trim().trim_start_matches('.').to_string())
+                        .filter(|extension| !extension.is_empty())
+                        .collect();
+                    argumentos.extensiones = Some(extensiones);
+                    index += 1;
+                }
+                "--gzip" => opciones_archivo.comprimido = true,
+                "--mmap" => opciones_archivo.mapear_memoria = true,
+                "--estricto" | "--strict" => opciones_archivo.campos = ModoCampos::Estricto,
+                "--tolerante" | "--lenient" => opciones_archivo.campos = ModoCampos::Tolerante,
+                "--backup" | "--respaldo" => opciones_archivo.respaldo = true,
+                "--progreso" | "--progress" => opciones_archivo.progreso = true,
+                "--shuffle" | "--mezclar" => opciones_archivo.mezclar = true,
+                "--columnas" | "--columns" => {
+                    let valor = valor_de(args, index)?;
+                    let columnas: Vec<String> = valor
+                        .split(',')
+                        .map(|columna| columna.trim().to_string())
+                        .collect();
+                    if columnas.iter().any(|columna| columna.is_empty()) {
+                        return Err(argumento_invalido(format!(
+                            "la lista de columnas '{}' tiene columnas sin nombre",
+                            valor
+                        )));
+                    }
+                    opciones_archivo.columnas = Some(columnas);
+                    index += 1;
+                }
+                "--codificacion" | "--encoding" => {
+                    let valor = valor_de(args, index)?;
+                    opciones_archivo.codificacion =
+                        Codificacion::desde_nombre(valor).ok_or_else(|| {
+                            argumento_invalido(format!(
+                                "la codificación '{}' no es válida, debe ser utf-8 o latin1",
+                                valor
+                            ))
+                        })?;
+                    index += 1;
+                }
+                "--formato" | "--format" => {
+                    let valor = valor_de(args, index)?;
+                    argumentos.opciones_salida.formato = FormatoSalida::desde_nombre(valor)
+                        .ok_or_else(|| {
+                            argumento_invalido(format!(
+                                "el formato '{}' no es válido, debe ser csv, tabla, markdown, \
+                                 html, xml, yaml o json",
+                                valor
+                            ))
+                        })?;
+                    index += 1;
+                }
+                "--comillas" | "--quoting" => {
+                    let valor = valor_de(args, index)?;
+                    argumentos.opciones_salida.comillas = PoliticaComillas::desde_nombre(valor)
+                        .ok_or_else(|| {
+                            argumento_invalido(format!(
+                                "la política de comillas '{}' no es válida, debe ser necesario, \
+                                 siempre o nunca",
+                                valor
+                            ))
+                        })?;
+                    index += 1;
+                }
+                "--comilla" | "--quote-char" => {
+                    let valor = valor_de(args, index)?;
+                    let mut caracteres = valor.chars();
+                    let comilla = match (caracteres.next(), caracteres.next()) {
+                        (Some(caracter), None) if !matches!(caracter, ',' | '\n' | '\r') => {
+                            caracter
+                        }
+                        _ => {
+                            return Err(argumento_invalido(format!(
+                                "la comilla '{}' no es válida, debe ser un único carácter \
+                                 distinto de la coma",
+                                valor
+                            )))
+                        }
+                    };
+                    argumentos.opciones_salida.comilla = Some(comilla);
+                    index += 1;
+                }
+                "--nulo" | "--null" => {
+                    opciones_archivo.nulo = Some(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--nulo-salida" | "--null-output" => {
+                    argumentos.opciones_salida.nulo = Some(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--file" | "-f" => {
+                    argumentos.archivo_consulta = Some(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--resumen" | "--summary" => argumentos.opciones_salida.resumen = true,
+                "--parallel" | "--paralelo" => argumentos.opciones_salida.paralelo = true,
+                "--no-header" | "--sin-encabezado" => {
+                    argumentos.opciones_salida.sin_encabezado = true
+                }
+                "--output" | "-o" => {
+                    argumentos.ruta_salida = Some(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--serve-http" | "--servir-http" => {
+                    argumentos.servir_http = Some(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--serve-pg" | "--servir-pg" => {
+                    argumentos.servir_pg = Some(valor_de(args, index)?.to_string());
+                    index += 1;
+                }
+                "--allow-writes" | "--permitir-escritura" => argumentos.permitir_escritura = true,
+                "--dry-run" | "--simular" => argumentos.simular = true,
+                "--repair" | "--reparar" => reparar = true,
+                "-v" | "--verbose" => {
+                    argumentos.verbosidad = argumentos.verbosidad.saturating_add(1)
+                }
+                "-vv" => argumentos.verbosidad = argumentos.verbosidad.saturating_add(2),
+                argumento if es_opcion_errores_json(argumento) => argumentos.errores_json = true,
+                // Una consulta puede empezar con `-` (un comentario, o `-` para leerla de la
+                // entrada estándar), pero no es una única palabra como una opción.
+                argumento
+                    if argumento.len() > 1
+                        && argumento.starts_with('-')
+                        && !argumento.contains(char::is_whitespace) =>
+                {
+                    return Err(argumento_invalido(format!(
+                        "la opción '{}' no existe",
+                        argumento
+                    )));
+                }
+                argumento => posicionales.push(argumento.to_string()),
+            }
+            index += 1;
+        }
+
+        let mut posicionales = posicionales.into_iter();
+        argumentos.ruta_tablas = match rutas_tablas.len() {
+            0 => match posicionales.next() {
+                Some(ruta_tablas) => ruta_tablas,
+                None => {
+                    return Err(argumento_invalido(
+                        "falta la ruta de las tablas".to_string(),
+                    ))
+                }
+            },
+            1 => rutas_tablas.remove(0),
+            _ => std::env::join_paths(&rutas_tablas)
+                .map_err(|_| {
+                    argumento_invalido(
+                        "las rutas de las tablas no pueden contener el separador de rutas"
+                            .to_string(),
+                    )
+                })?
+                .to_string_lossy()
+                .to_string(),
+        };
+        argumentos.comando = match subcomando {
+            "import" => match (posicionales.next(), posicionales.next()) {
+                (Some(tabla), Some(archivo)) => Comando::Importar { tabla, archivo },
+                _ => {
+                    return Err(argumento_invalido(
+                        "falta la tabla o el archivo a importar".to_string(),
+                    ))
+                }
+            },
+            "export" => match posicionales.next() {
+                Some(tabla) => Comando::Exportar { tabla },
+                None => return Err(argumento_invalido("falta la tabla a exportar".to_string())),
+            },
+            "schema" => Comando::Esquema {
+                tablas: posicionales.by_ref().collect(),
+            },
+            "check" => Comando::Revisar {
+                tablas: posicionales.by_ref().collect(),
+                reparar,
+            },
+            _ => Comando::Consulta,
+        };
+        if argumentos.comando != Comando::Consulta {
+            if let Some(sobrante) = posicionales.next() {
+                return Err(argumento_invalido(format!(
+                    "sobra el argumento '{}'",
+                    sobrante
+                )));
+            }
+            return Ok(argumentos);
+        }
+        let consulta = posicionales.next();
+        let servidor = match (&argumentos.servir_http, &argumentos.servir_pg) {
+            (Some(_), Some(_)) => {
+                return Err(argumento_invalido(
+                    "no se puede indicar --serve-http junto con --serve-pg".to_string(),
+                ))
+            }
+            (Some(_), None) => Some("--serve-http"),
+            (None, Some(_)) => Some("--serve-pg"),
+            (None, None) => None,
+        };
+        if argumentos.permitir_escritura && servidor.is_none() {
+            return Err(argumento_invalido(
+                "--allow-writes solo se admite junto con --serve-http o --serve-pg".to_string(),
+            ));
+        }
+        if let Some(servidor) = servidor {
+            if consulta.is_some() || argumentos.archivo_consulta.is_some() {
+                return Err(argumento_invalido(format!(
+                    "no se puede indicar una consulta junto con {}",
+                    servidor
+                )));
+            }
+            return Ok(argumentos);
+        }
+        match (consulta, &argumentos.archivo_consulta) {
+            (Some(_), Some(_)) => {
+                return Err(argumento_invalido(
+                    "no se puede indicar una consulta junto con --file".to_string(),
+                ))
+            }
+            (Some(consulta), None) => argumentos.consulta = consulta,
+            (None, Some(_)) => {}
+            (None, None) => {
+                return Err(argumento_invalido(
+                    "falta la consulta a ejecutar, o --file con el archivo de sentencias"
+                        .to_string(),
+                ))
+            }
+        }
+        if let Some(sobrante) = posicionales.next() {
+            return Err(argumento_invalido(format!(
+                "sobra el argumento '{}'",
+                sobrante
+            )));
+        }
+        Ok(argumentos)
+    }
+
+    /// Obtiene el texto de las sentencias a ejecutar: el contenido del archivo indicado con
+    /// `--file`, la entrada estándar si la consulta es `-`, o la consulta tal como se recibió.
+    ///
+    /// # Retorno
+    /// El texto de las sentencias, `Errores::PermisoDenegado` si no hay permiso para leer el
+    /// archivo, o `Errores::Io` si no se pudo leer.
+    pub fn leer_consulta(&self) -> Result<String, errores::Errores> {
+        if let Some(archivo_consulta) = &self.archivo_consulta {
+            return fs::read_to_string(archivo_consulta)
+                .map_err(|error| errores::Errores::de_archivo(error, archivo_consulta));
+        }
+        if self.consulta == "-" {
+            let mut consulta = String::new();
+            io::stdin().read_to_string(&mut consulta)?;
+            return Ok(consulta);
+        }
+        Ok(self.consulta.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(valores: &[&str]) -> Vec<String> {
+        valores.iter().map(|valor| valor.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parsear_sin_opciones() {
+        let argumentos = Argumentos::parsear(&args(&["tablas", "SELECT * FROM personas"])).unwrap();
+
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+        assert_eq!(argumentos.consulta, "SELECT * FROM personas");
+        assert_eq!(argumentos.opciones_archivo.delimitador, None);
+        assert_eq!(argumentos.opciones_salida, OpcionesSalida::default());
+        assert_eq!(argumentos.ruta_salida, None);
+        assert!(!argumentos.errores_json);
+
+        let argumentos =
+            Argumentos::parsear(&args(&["--errores-json", "tablas", "SELECT"])).unwrap();
+        assert!(argumentos.errores_json);
+
+        let argumentos = Argumentos::parsear(&args(&["-vv", "tablas", "SELECT", "-v"])).unwrap();
+        assert_eq!(argumentos.verbosidad, 3);
+    }
+
+    #[test]
+    fn test_parsear_con_archivo_de_salida() {
+        let argumentos =
+            Argumentos::parsear(&args(&["tablas", "SELECT", "--output", "resultado.csv"])).unwrap();
+        assert_eq!(argumentos.ruta_salida, Some("resultado.csv".to_string()));
+        assert!(!argumentos.simular);
+        assert!(
+            Argumentos::parsear(&args(&["tablas", "INSERT", "--dry-run"]))
+                .unwrap()
+                .simular
+        );
+        assert!(Argumentos::parsear(&args(&["tablas", "SELECT", "-o"])).is_err());
+    }
+
+    #[test]
+    fn test_parsear_con_archivo_de_consulta() {
+        let argumentos = Argumentos::parsear(&args(&["tablas", "-f", "migracion.sql"])).unwrap();
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+        assert_eq!(
+            argumentos.archivo_consulta,
+            Some("migracion.sql".to_string())
+        );
+        assert!(Argumentos::parsear(&args(&["tablas", "SELECT", "--file", "a.sql"])).is_err());
+        assert!(Argumentos::parsear(&args(&["tablas", "--file"])).is_err());
+    }
+
+    #[test]
+    fn test_parsear_con_columnas() {
+        let argumentos = Argumentos::parsear(&args(&[
+            "tablas",
+            "SELECT",
+            "--columnas",
+            "nombre, edad,ciudad",
+        ]))
+        .unwrap();
+        assert_eq!(
+            argumentos.opciones_archivo.columnas,
+            Some(vec![
+                "nombre".to_string(),
+                "edad".to_string(),
+                "ciudad".to_string()
+            ])
+        );
+        assert!(Argumentos::parsear(&args(&["--columnas", "a,,b", "tablas", "SELECT"])).is_err());
+    }
+
+    #[test]
+    fn test_parsear_con_textos_para_nulo() {
+        let argumentos = Argumentos::parsear(&args(&[
+            "--nulo",
+            "\\N",
+            "--null-output",
+            "NULL",
+            "tablas",
+            "SELECT",
+        ]))
+        .unwrap();
+        assert_eq!(argumentos.opciones_archivo.nulo, Some("\\N".to_string()));
+        assert_eq!(argumentos.opciones_salida.nulo, Some("NULL".to_string()));
+        assert!(Argumentos::parsear(&args(&["tablas", "SELECT", "--nulo"])).is_err());
+    }
+
+    #[test]
+    fn test_parsear_con_formato() {
+        let argumentos =
+            Argumentos::parsear(&args(&["--formato", "tabla", "tablas", "SELECT"])).unwrap();
+        assert_eq!(argumentos.opciones_salida.formato, FormatoSalida::Tabla);
+        assert!(!argumentos.opciones_salida.resumen);
+        let argumentos = Argumentos::parsear(&args(&["--summary", "tablas", "SELECT"])).unwrap();
+        assert!(argumentos.opciones_salida.resumen);
+        assert!(!argumentos.opciones_salida.paralelo);
+        let argumentos = Argumentos::parsear(&args(&["tablas", "SELECT", "--parallel"])).unwrap();
+        assert!(argumentos.opciones_salida.paralelo);
+        assert!(!argumentos.opciones_salida.sin_encabezado);
+        let argumentos = Argumentos::parsear(&args(&["tablas", "SELECT", "--no-header"])).unwrap();
+        assert!(argumentos.opciones_salida.sin_encabezado);
+        let argumentos = Argumentos::parsear(&args(&[
+            "--quoting",
+            "always",
+            "--comilla",
+            "'",
+            "tablas",
+            "SELECT",
+        ]))
+        .unwrap();
+        assert_eq!(
+            argumentos.opciones_salida.comillas,
+            PoliticaComillas::Siempre
+        );
+        assert_eq!(argumentos.opciones_salida.comilla, Some('\''));
+        assert!(Argumentos::parsear(&args(&["--comilla", "''", "tablas", "SELECT"])).is_err());
+        assert!(Argumentos::parsear(&args(&["--comillas", "x", "tablas", "SELECT"])).is_err());
+        assert!(Argumentos::parsear(&args(&["--format", "xls", "tablas", "SELECT"])).is_err());
+    }
+
+    #[test]
+    fn test_parsear_con_delimitador() {
+        let argumentos =
+            Argumentos::parsear(&args(&["-d", "tab", "tablas", "SELECT * FROM personas"])).unwrap();
+        assert_eq!(argumentos.opciones_archivo.delimitador, Some('\t'));
+
+        let argumentos = Argumentos::parsear(&args(&[
+            "tablas",
+            "SELECT * FROM personas",
+            "--delimitador",
+            ";",
+        ]))
+        .unwrap();
+        assert_eq!(argumentos.opciones_archivo.delimitador, Some(';'));
+
+        let argumentos =
+            Argumentos::parsear(&args(&["--extensiones", ".tsv, csv", "tablas", "SELECT"]))
+                .unwrap();
+        assert_eq!(
+            argumentos.extensiones,
+            Some(vec!["tsv".to_string(), "csv".to_string()])
+        );
+
+        let argumentos = Argumentos::parsear(&args(&["--gzip", "tablas", "SELECT"])).unwrap();
+        assert!(argumentos.opciones_archivo.comprimido);
+
+        let argumentos = Argumentos::parsear(&args(&["--mmap", "tablas", "SELECT"])).unwrap();
+        assert!(argumentos.opciones_archivo.mapear_memoria);
+        assert_eq!(argumentos.opciones_archivo.campos, ModoCampos::Libre);
+
+        let argumentos = Argumentos::parsear(&args(&["--strict", "tablas", "SELECT"])).unwrap();
+        assert_eq!(argumentos.opciones_archivo.campos, ModoCampos::Estricto);
+
+        let argumentos = Argumentos::parsear(&args(&["tablas", "SELECT", "--backup"])).unwrap();
+        assert!(argumentos.opciones_archivo.respaldo);
+        assert!(!argumentos.opciones_archivo.progreso);
+
+        let argumentos = Argumentos::parsear(&args(&["--progress", "tablas", "SELECT"])).unwrap();
+        assert!(argumentos.opciones_archivo.progreso);
+        assert!(!argumentos.opciones_archivo.mezclar);
+
+        let argumentos = Argumentos::parsear(&args(&["tablas", "SELECT", "--shuffle"])).unwrap();
+        assert!(argumentos.opciones_archivo.mezclar);
+
+        let argumentos =
+            Argumentos::parsear(&args(&["tablas", "SELECT", "--encoding", "Latin1"])).unwrap();
+        assert_eq!(
+            argumentos.opciones_archivo.codificacion,
+            Codificacion::Latin1
+        );
+        assert!(Argumentos::parsear(&args(&["--encoding", "ebcdic", "tablas", "SELECT"])).is_err());
+    }
+
+    #[test]
+    fn test_parsear_argumentos_invalidos() {
+        let motivo = |valores: &[&str]| match Argumentos::parsear(&args(valores)) {
+            Err(errores::Errores::ArgumentoInvalido(motivo)) => motivo,
+            resultado => panic!("{:?}", resultado),
+        };
+        assert_eq!(
+            motivo(&["tablas"]),
+            "falta la consulta a ejecutar, o --file con el archivo de sentencias"
+        );
+        assert_eq!(motivo(&[]), "falta la ruta de las tablas");
+        assert_eq!(
+            motivo(&["tablas", "SELECT", "extra"]),
+            "sobra el argumento 'extra'"
+        );
+        assert_eq!(
+            motivo(&["tablas", "SELECT", "-d"]),
+            "falta el valor de la opción '-d'"
+        );
+        assert_eq!(
+            motivo(&["-d", ";;", "tablas", "SELECT"]),
+            "el delimitador ';;' no es válido, debe ser un único carácter o 'tab'"
+        );
+        assert_eq!(
+            motivo(&["--formatos", "tabla", "tablas", "SELECT"]),
+            "la opción '--formatos' no existe"
+        );
+        assert_eq!(
+            motivo(&["tablas", "SELECT", "--serve-http", "127.0.0.1:8080"]),
+            "no se puede indicar una consulta junto con --serve-http"
+        );
+        assert_eq!(
+            motivo(&["tablas", "--file", "a.sql", "--serve-pg", "127.0.0.1:5432"]),
+            "no se puede indicar una consulta junto con --serve-pg"
+        );
+        assert_eq!(
+            motivo(&["tablas", "--serve-pg", ":5432", "--serve-http", ":8080"]),
+            "no se puede indicar --serve-http junto con --serve-pg"
+        );
+        assert_eq!(
+            motivo(&["tablas", "SELECT 1", "--allow-writes"]),
+            "--allow-writes solo se admite junto con --serve-http o --serve-pg"
+        );
+    }
+
+    #[test]
+    fn test_parsear_servidor_http() {
+        let argumentos =
+            Argumentos::parsear(&args(&["--serve-http", "127.0.0.1:8080", "tablas"])).unwrap();
+        assert_eq!(argumentos.servir_http.as_deref(), Some("127.0.0.1:8080"));
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+        assert_eq!(argumentos.consulta, "");
+        assert!(!argumentos.permitir_escritura);
+
+        let argumentos = Argumentos::parsear(&args(&[
+            "tablas",
+            "--serve-http",
+            "127.0.0.1:8080",
+            "--allow-writes",
+        ]))
+        .unwrap();
+        assert!(argumentos.permitir_escritura);
+    }
+
+    #[test]
+    fn test_parsear_servidor_postgres() {
+        let argumentos =
+            Argumentos::parsear(&args(&["tablas", "--serve-pg", "127.0.0.1:5432"])).unwrap();
+        assert_eq!(argumentos.servir_pg.as_deref(), Some("127.0.0.1:5432"));
+        assert_eq!(argumentos.servir_http, None);
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+        assert_eq!(argumentos.consulta, "");
+    }
+
+    #[test]
+    fn test_parsear_subcomandos() {
+        let argumentos =
+            Argumentos::parsear(&args(&["query", "tablas", "SELECT * FROM personas"])).unwrap();
+        assert_eq!(argumentos.comando, Comando::Consulta);
+        assert_eq!(argumentos.consulta, "SELECT * FROM personas");
+
+        let argumentos = Argumentos::parsear(&args(&[
+            "import",
+            "-d",
+            ";",
+            "tablas",
+            "personas",
+            "nuevas.csv",
+        ]))
+        .unwrap();
+        assert_eq!(
+            argumentos.comando,
+            Comando::Importar {
+                tabla: "personas".to_string(),
+                archivo: "nuevas.csv".to_string()
+            }
+        );
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+        assert_eq!(argumentos.opciones_archivo.delimitador, Some(';'));
+
+        let argumentos = Argumentos::parsear(&args(&[
+            "exportar",
+            "tablas",
+            "personas",
+            "--formato",
+            "json",
+        ]))
+        .unwrap();
+        assert_eq!(
+            argumentos.comando,
+            Comando::Exportar {
+                tabla: "personas".to_string()
+            }
+        );
+        assert_eq!(argumentos.opciones_salida.formato, FormatoSalida::Json);
+
+        let argumentos = Argumentos::parsear(&args(&["schema", "-t", "tablas"])).unwrap();
+        assert_eq!(argumentos.comando, Comando::Esquema { tablas: vec![] });
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+
+        let argumentos = Argumentos::parsear(&args(&[
+            "check", "tablas", "personas", "ventas", "--repair",
+        ]))
+        .unwrap();
+        assert_eq!(
+            argumentos.comando,
+            Comando::Revisar {
+                tablas: vec!["personas".to_string(), "ventas".to_string()],
+                reparar: true
+            }
+        );
+
+        let motivo = |valores: &[&str]| match Argumentos::parsear(&args(valores)) {
+            Err(errores::Errores::ArgumentoInvalido(motivo)) => motivo,
+            otro => panic!("se esperaba un argumento inválido: {:?}", otro),
+        };
+        assert_eq!(
+            motivo(&["import", "tablas", "personas"]),
+            "falta la tabla o el archivo a importar"
+        );
+        assert_eq!(
+            motivo(&["export", "tablas", "personas", "ventas"]),
+            "sobra el argumento 'ventas'"
+        );
+        assert_eq!(
+            motivo(&["import", "tablas", "personas", "a.csv", "--formato", "json"]),
+            "la opción '--formato' no se admite con el subcomando import"
+        );
+        assert_eq!(
+            motivo(&["export", "tablas", "personas", "--dry-run"]),
+            "la opción '--dry-run' no se admite con el subcomando export"
+        );
+        assert_eq!(
+            motivo(&["tablas", "SELECT", "--repair"]),
+            "la opción '--repair' no se admite con el subcomando query"
+        );
+    }
+
+    #[test]
+    fn test_parsear_ayuda_y_ruta_de_tablas_con_nombre() {
+        let argumentos = Argumentos::parsear(&args(&["tablas", "--help"])).unwrap();
+        assert!(argumentos.ayuda);
+        assert!(Argumentos::parsear(&args(&["-h"])).unwrap().ayuda);
+
+        let argumentos =
+            Argumentos::parsear(&args(&["SELECT * FROM personas", "--tablas", "tablas"])).unwrap();
+        assert_eq!(argumentos.ruta_tablas, "tablas");
+        assert_eq!(argumentos.consulta, "SELECT * FROM personas");
+        assert!(!argumentos.ayuda);
+
+        let argumentos =
+            Argumentos::parsear(&args(&["-t", "base", "SELECT", "-t", "temporales"])).unwrap();
+        assert_eq!(
+            argumentos.ruta_tablas,
+            std::env::join_paths(["base", "temporales"])
+                .unwrap()
+                .to_string_lossy()
+        );
+
+        let argumentos = Argumentos::parsear(&args(&["tablas", "-- todas\nSELECT 1"])).unwrap();
+        assert_eq!(argumentos.consulta, "-- todas\nSELECT 1");
+        assert_eq!(
+            Argumentos::parsear(&args(&["tablas", "-"]))
+                .unwrap()
+                .consulta,
+            "-"
+        );
+    }
+}
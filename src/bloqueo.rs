@@ -0,0 +1,112 @@
+use crate::errores;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Tiempo máximo que se espera a que otro proceso libere una tabla.
+pub const ESPERA_MAXIMA: Duration = Duration::from_secs(10);
+
+/// Intervalo entre cada intento de tomar el bloqueo.
+const INTERVALO: Duration = Duration::from_millis(20);
+
+/// Bloqueo exclusivo de una tabla entre procesos, tomado sobre el archivo
+/// `directorio/.<archivo>.bloqueo` (ver `ruta_bloqueo`).
+///
+/// Mientras un proceso tiene el bloqueo, ningún otro puede agregar registros a la tabla ni
+/// reemplazarla; las lecturas no se bloquean, porque la tabla se reemplaza con un `rename`.
+/// El bloqueo se libera al descartarse. El archivo del bloqueo se conserva, ya que eliminarlo
+/// mientras otro proceso espera para bloquearlo permitiría que dos procesos lo tomen a la vez.
+#[derive(Debug)]
+pub struct Bloqueo {
+    _archivo: File,
+}
+
+/// Arma la ruta del archivo de bloqueo de una tabla: `directorio/.<archivo>.bloqueo`.
+pub fn ruta_bloqueo(ruta: &str) -> String {
+    let ruta = Path::new(ruta);
+    let archivo = ruta
+        .file_name()
+        .map(|archivo| archivo.to_string_lossy().to_string())
+        .unwrap_or_default();
+    ruta.with_file_name(format!(".{}.bloqueo", archivo))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Toma el bloqueo de la tabla, esperando a que otro proceso lo libere.
+///
+/// # Parámetros
+/// - `ruta`: La ruta de la tabla.
+/// - `espera`: El tiempo máximo que se espera.
+///
+/// # Retorno
+/// El `Bloqueo`, `Errores::TablaBloqueada` si otro proceso no lo liberó a tiempo,
+/// `Errores::PermisoDenegado` si no hay permiso para crear el archivo del bloqueo en el
+/// directorio de la tabla, o el error de E/S si no se pudo crear.
+pub fn bloquear(ruta: &str, espera: Duration) -> Result<Bloqueo, errores::Errores> {
+    let inicio = Instant::now();
+    loop {
+        let intento = intentar_bloquear(ruta)
+            .map_err(|error| errores::Errores::de_archivo(error, &ruta_bloqueo(ruta)))?;
+        if let Some(bloqueo) = intento {
+            return Ok(bloqueo);
+        }
+        if inicio.elapsed() >= espera {
+            return Err(errores::Errores::TablaBloqueada(ruta.to_string()));
+        }
+        thread::sleep(INTERVALO);
+    }
+}
+
+/// Toma el bloqueo de la tabla solo si ningún otro proceso lo tiene.
+///
+/// # Retorno
+/// El `Bloqueo`, `None` si otro proceso lo tiene, o el error de E/S.
+pub fn intentar_bloquear(ruta: &str) -> io::Result<Option<Bloqueo>> {
+    let archivo = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(ruta_bloqueo(ruta))?;
+    match archivo.try_lock() {
+        Ok(()) => Ok(Some(Bloqueo { _archivo: archivo })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(error)) => Err(error),
+    }
+}
+
+/// Elimina el archivo de bloqueo de una copia de trabajo, que solo usa el proceso que la creó,
+/// una vez que la copia dejó de existir.
+pub fn eliminar(ruta_copia: &str) -> io::Result<()> {
+    match fs::remove_file(ruta_bloqueo(ruta_copia)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloqueo_exclusivo() {
+        let directorio = std::env::temp_dir().join("test_bloqueo_exclusivo");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("personas").to_string_lossy().to_string();
+
+        let bloqueo = bloquear(&ruta, ESPERA_MAXIMA).unwrap();
+        assert!(Path::new(&ruta_bloqueo(&ruta)).exists());
+        // Otro archivo abierto sobre el bloqueo, como el de otro proceso, no puede tomarlo.
+        assert!(intentar_bloquear(&ruta).unwrap().is_none());
+        assert_eq!(
+            bloquear(&ruta, Duration::ZERO).unwrap_err(),
+            errores::Errores::TablaBloqueada(ruta.to_string())
+        );
+        drop(bloqueo);
+        assert!(intentar_bloquear(&ruta).unwrap().is_some());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use crate::errores;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Versión de un archivo: su fecha de modificación y su tamaño, o `None` si no existe.
+type Version = Option<(SystemTime, u64)>;
+
+/// Caché de valores que se cargan a partir de un archivo, como el esquema de una tabla.
+///
+/// Cada valor se guarda junto con la versión del archivo del que se cargó y se vuelve a cargar
+/// cuando el archivo cambia de fecha de modificación o de tamaño, o cuando aparece o se elimina.
+///
+/// - `entradas`: La versión del archivo y el valor cargado, por ruta.
+#[derive(Debug)]
+pub struct CacheArchivos<T> {
+    entradas: Mutex<HashMap<String, (Version, T)>>,
+}
+
+impl<T: Clone> CacheArchivos<T> {
+    pub fn new() -> CacheArchivos<T> {
+        CacheArchivos {
+            entradas: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Devuelve el valor guardado para el archivo o, si no hay uno de su versión actual, lo
+    /// carga y lo guarda.
+    ///
+    /// # Parámetros
+    /// - `ruta`: La ruta del archivo del que depende el valor, que puede no existir.
+    /// - `cargar`: Carga el valor a partir del archivo.
+    ///
+    /// # Retorno
+    /// El valor, o el error de `cargar`, que no se guarda.
+    pub fn obtener(
+        &self,
+        ruta: &str,
+        cargar: impl FnOnce() -> Result<T, errores::Errores>,
+    ) -> Result<T, errores::Errores> {
+        let version = version(ruta);
+        let mut entradas = self.entradas.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((guardada, valor)) = entradas.get(ruta) {
+            if *guardada == version {
+                return Ok(valor.clone());
+            }
+        }
+        let valor = cargar()?;
+        entradas.insert(ruta.to_string(), (version, valor.clone()));
+        Ok(valor)
+    }
+}
+
+impl<T: Clone> Default for CacheArchivos<T> {
+    fn default() -> Self {
+        CacheArchivos::new()
+    }
+}
+
+fn version(ruta: &str) -> Version {
+    let metadatos = fs::metadata(ruta).ok()?;
+    Some((metadatos.modified().ok()?, metadatos.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_cache_se_invalida_al_cambiar_el_archivo() {
+        let directorio = std::env::temp_dir().join(format!("cache_{}", std::process::id()));
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("tabla.esquema");
+        let ruta = ruta.to_str().unwrap();
+        let _ = fs::remove_file(ruta);
+
+        let cache = CacheArchivos::new();
+        let cargas = Cell::new(0);
+        let leer = || {
+            cargas.set(cargas.get() + 1);
+            Ok(fs::read_to_string(ruta).unwrap_or_default())
+        };
+
+        assert_eq!(cache.obtener(ruta, leer).unwrap(), "");
+        assert_eq!(cache.obtener(ruta, leer).unwrap(), "");
+        assert_eq!(cargas.get(), 1);
+
+        fs::write(ruta, "id INT\n").unwrap();
+        assert_eq!(cache.obtener(ruta, leer).unwrap(), "id INT\n");
+        assert_eq!(cache.obtener(ruta, leer).unwrap(), "id INT\n");
+        assert_eq!(cargas.get(), 2);
+
+        fs::write(ruta, "id INT\nnombre TEXT\n").unwrap();
+        assert_eq!(cache.obtener(ruta, leer).unwrap(), "id INT\nnombre TEXT\n");
+        assert_eq!(cargas.get(), 3);
+
+        assert!(cache
+            .obtener("otro", || Err(errores::Errores::Error))
+            .is_err());
+        assert_eq!(cache.obtener("otro", || Ok(String::new())).unwrap(), "");
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
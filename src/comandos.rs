@@ -0,0 +1,403 @@
+use crate::archivo::{
+    formatear_registro, leer_archivo, leer_encabezado, leer_registro_en, parsear_registro,
+    ModoCampos, OpcionesArchivo, VerificadorCampos,
+};
+use crate::consulta::{Resumen, SQLConsulta};
+use crate::errores;
+use crate::esquema::ValorPorDefecto;
+use crate::expresion::Valor;
+use crate::json;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::OpcionesSalida;
+use crate::sintaxis::{self, Sentencia, SentenciaInsert};
+use std::io::{self, BufRead, Cursor, Read, Write};
+
+/// Columnas del resultado de `escribir_esquema`.
+const COLUMNAS_ESQUEMA: [&str; 6] = [
+    "tabla",
+    "columna",
+    "tipo",
+    "nulable",
+    "clave_primaria",
+    "valor_por_defecto",
+];
+
+/// Agrega a una tabla los registros de un archivo CSV o JSON, como el subcomando `import`.
+///
+/// El archivo es JSON si empieza con `[` o `{`: una lista de objetos, o un objeto por línea
+/// como en NDJSON, que se convierte a CSV con una columna por clave (ver `json::a_csv`). El
+/// archivo se lee con las mismas opciones que las tablas, y con `-` se lee de la entrada
+/// estándar.
+///
+/// Si la tabla existe, los campos de cada registro se asignan a sus columnas con el mismo
+/// nombre en el encabezado del archivo, que no tiene que tener todas las columnas ni en el
+/// mismo orden. Los registros se agregan con un único `INSERT`, así que se verifican como los
+/// de cualquier `INSERT` (ver `ConsultaInsert`) y, si alguno no es válido, no se agrega
+/// ninguno. Si la tabla no existe, se crea con el encabezado del archivo (ver
+/// `Almacenamiento::crear`); salvo con `--tolerante`, cada registro debe tener un campo por
+/// columna.
+///
+/// # Parámetros
+/// - `ruta_archivo`: La ruta del archivo con los registros.
+/// - `tabla`: El nombre de la tabla a la que se agregan.
+/// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla.
+/// - `opciones`: Las opciones con las que se leen el archivo y la tabla.
+///
+/// # Retorno
+/// La cantidad de registros agregados, o el error que impidió leer el archivo o agregarlos.
+pub fn importar(
+    ruta_archivo: &str,
+    tabla: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+) -> Result<usize, errores::Errores> {
+    let ruta_tabla = resolvedor.resolver(tabla)?;
+    let almacenamiento = resolvedor.almacenamiento();
+    let existe = match almacenamiento.leer(&ruta_tabla, opciones) {
+        Ok(_) => true,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => false,
+        Err(error) => return Err(error.into()),
+    };
+
+    // El delimitador del archivo se detecta aparte del de la tabla.
+    let mut opciones_archivo = opciones.clone();
+    if !existe && opciones_archivo.campos == ModoCampos::Libre {
+        opciones_archivo.campos = ModoCampos::Estricto;
+    }
+    let mut lector = leer_archivo(ruta_archivo, &opciones_archivo)
+        .map_err(|error| errores::Errores::de_archivo(error, ruta_archivo))?;
+    if es_json(lector.as_mut())? {
+        let mut texto = String::new();
+        lector.read_to_string(&mut texto)?;
+        let csv = json::a_csv(
+            &texto,
+            opciones_archivo.delimitador(),
+            opciones_archivo.nulo(),
+        )?;
+        lector = Box::new(Cursor::new(csv.into_bytes()));
+    }
+    let columnas = leer_encabezado(lector.as_mut(), &mut opciones_archivo)?;
+    let mut verificador = VerificadorCampos::new(&opciones_archivo, columnas.len());
+    let mut filas = Vec::new();
+    let mut registro = String::new();
+    while leer_registro_en(lector.as_mut(), &mut registro)? {
+        if registro.is_empty() {
+            continue;
+        }
+        verificador.verificar(&mut registro)?;
+        filas.push(parsear_registro(
+            &registro,
+            opciones_archivo.delimitador(),
+            opciones_archivo.nulo(),
+        ));
+    }
+
+    if !existe {
+        let mut contenido = String::new();
+        let encabezado: Vec<Option<String>> = columnas.into_iter().map(Some).collect();
+        for registro in std::iter::once(&encabezado).chain(&filas) {
+            contenido.push_str(&formatear_registro(
+                registro,
+                opciones.delimitador(),
+                opciones.nulo(),
+            ));
+            contenido.push('\n');
+        }
+        almacenamiento.crear(&ruta_tabla, opciones, &contenido)?;
+        return Ok(filas.len());
+    }
+    if filas.is_empty() {
+        return Ok(0);
+    }
+    let sentencia = Sentencia::Insert(SentenciaInsert {
+        tabla: tabla.to_string(),
+        columnas,
+        filas,
+    });
+    let mut escritor = OpcionesSalida::default().crear_escritor(io::sink());
+    match SQLConsulta::desde_sentencia(sentencia, resolvedor, opciones)?
+        .procesar_consulta(escritor.as_mut())?
+    {
+        Resumen::Agregados(registros) => Ok(registros),
+        Resumen::Filas(_) | Resumen::Copiados(_) => Ok(0),
+    }
+}
+
+/// Indica si el contenido por leer es JSON, porque su primer carácter que no es un espacio es
+/// `[` o `{`.
+fn es_json(lector: &mut dyn BufRead) -> io::Result<bool> {
+    loop {
+        let disponible = lector.fill_buf()?;
+        let Some(&byte) = disponible.first() else {
+            return Ok(false);
+        };
+        if !byte.is_ascii_whitespace() {
+            return Ok(byte == b'[' || byte == b'{');
+        }
+        lector.consume(1);
+    }
+}
+
+/// Escribe las columnas de las tablas, como el subcomando `schema`: una fila por columna con la
+/// tabla, el nombre de la columna y, según el esquema de la tabla (ver `Esquema`), su tipo, si
+/// es nulable, si es parte de la clave primaria y su valor por defecto. Las columnas que el
+/// esquema no declara no tienen tipo, son nulables y no son parte de la clave.
+///
+/// # Parámetros
+/// - `tablas`: Los nombres de las tablas, o vacío para escribir todas las que se pueden
+///   enumerar (ver `ResolvedorTablas::tablas`).
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen las tablas.
+/// - `opciones_salida`: Las opciones con las que se escribe el resultado.
+/// - `destino`: Donde se escribe el resultado.
+///
+/// # Retorno
+/// Retorna `Ok(())`, `Errores::InvalidTable` si una tabla no existe, o el error que impidió
+/// leerla o escribir el resultado.
+pub fn escribir_esquema<W: Write>(
+    tablas: &[String],
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    opciones_salida: &OpcionesSalida,
+    destino: &mut W,
+) -> Result<(), errores::Errores> {
+    let tablas = if tablas.is_empty() {
+        resolvedor.tablas()?
+    } else {
+        tablas.to_vec()
+    };
+    let almacenamiento = resolvedor.almacenamiento();
+    let mut escritor = opciones_salida.crear_escritor(&mut *destino);
+    let encabezado: Vec<String> = COLUMNAS_ESQUEMA.iter().map(|c| c.to_string()).collect();
+    escritor.escribir_encabezado(&encabezado)?;
+    for tabla in tablas {
+        let ruta = resolvedor.resolver(&tabla)?;
+        let mut opciones_tabla = opciones.clone();
+        let mut lector = almacenamiento
+            .leer(&ruta, &opciones_tabla)
+            .map_err(|error| errores::Errores::tabla_ilegible(error, &tabla))?;
+        let columnas = leer_encabezado(lector.as_mut(), &mut opciones_tabla)?;
+        let esquema = almacenamiento.esquema(&ruta)?;
+        for columna in columnas {
+            let definicion = esquema.columna(&columna);
+            let tipo = definicion
+                .and_then(|definicion| definicion.tipo)
+                .map_or(Valor::Nulo, |tipo| Valor::Texto(tipo.to_string()));
+            let valor_por_defecto = match definicion.and_then(|d| d.valor_por_defecto.as_ref()) {
+                Some(ValorPorDefecto::Constante(valor)) => Valor::Texto(valor.to_string()),
+                Some(ValorPorDefecto::FechaActual) => Valor::Texto("CURRENT_DATE".to_string()),
+                None => Valor::Nulo,
+            };
+            escritor.escribir_fila(&[
+                Valor::Texto(tabla.to_string()),
+                Valor::Texto(columna),
+                tipo,
+                Valor::Booleano(definicion.is_none_or(|definicion| definicion.nulable)),
+                Valor::Booleano(definicion.is_some_and(|definicion| definicion.clave_primaria)),
+                valor_por_defecto,
+            ])?;
+        }
+    }
+    escritor.finalizar()
+}
+
+/// Devuelve la sentencia que escribe todos los registros de la tabla, como el subcomando
+/// `export`.
+///
+/// # Retorno
+/// El `SELECT * FROM` de la tabla, o `Errores::ArgumentoInvalido` si el nombre de la tabla no
+/// es un único nombre (ver `sintaxis::es_nombre`).
+pub fn sentencia_de_exportacion(tabla: &str) -> Result<String, errores::Errores> {
+    verificar_nombre_de_tabla(tabla)?;
+    Ok(format!("SELECT * FROM {}", tabla))
+}
+
+/// Devuelve las sentencias que revisan las tablas, como el subcomando `check`.
+///
+/// # Parámetros
+/// - `tablas`: Los nombres de las tablas, o vacío para revisar todas las que se pueden
+///   enumerar (ver `ResolvedorTablas::tablas`).
+/// - `reparar`: Si las tablas además se corrigen, con `REPAIR TABLE` en lugar de `CHECK TABLE`.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+///
+/// # Retorno
+/// Las sentencias separadas por `;`, el error si no se pudieron enumerar las tablas, o
+/// `Errores::ArgumentoInvalido` si alguna no es un único nombre (ver `sintaxis::es_nombre`).
+pub fn sentencias_de_revision(
+    tablas: &[String],
+    reparar: bool,
+    resolvedor: &dyn ResolvedorTablas,
+) -> Result<String, errores::Errores> {
+    let tablas = if tablas.is_empty() {
+        resolvedor.tablas()?
+    } else {
+        tablas.to_vec()
+    };
+    let sentencia = if reparar {
+        "REPAIR TABLE"
+    } else {
+        "CHECK TABLE"
+    };
+    let mut sentencias = String::new();
+    for tabla in &tablas {
+        verificar_nombre_de_tabla(tabla)?;
+        sentencias.push_str(&format!("{} {};", sentencia, tabla));
+    }
+    Ok(sentencias)
+}
+
+/// Verifica que el nombre de una tabla de los argumentos se pueda escribir en una sentencia
+/// sin que se interprete como algo más que la tabla.
+fn verificar_nombre_de_tabla(tabla: &str) -> Result<(), errores::Errores> {
+    if sintaxis::es_nombre(tabla) {
+        return Ok(());
+    }
+    Err(errores::Errores::ArgumentoInvalido(format!(
+        "'{}' no es un nombre de tabla",
+        tabla
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use crate::resolvedor::ResolvedorDirectorio;
+    use std::fs;
+
+    #[test]
+    fn test_importar_por_nombre_de_columna() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad,ciudad\nAna,40,Rosario\n");
+        let ruta = std::env::temp_dir().join(format!("importar_{}.csv", std::process::id()));
+        fs::write(&ruta, "ciudad;nombre\nSalta;Luis\n\nTandil;\"Eva; hija\"\n").unwrap();
+        let ruta = ruta.to_str().unwrap();
+
+        let importados = importar(ruta, "personas", &tablas, &OpcionesArchivo::default()).unwrap();
+        assert_eq!(importados, 2);
+        assert_eq!(
+            tablas.contenido("personas").unwrap(),
+            "nombre,edad,ciudad\nAna,40,Rosario\nLuis,,Salta\nEva; hija,,Tandil\n"
+        );
+
+        fs::write(ruta, "nombre,telefono\nJuan,123\n").unwrap();
+        assert!(importar(ruta, "personas", &tablas, &OpcionesArchivo::default()).is_err());
+        assert!(importar(ruta, "clientes", &tablas, &OpcionesArchivo::default()).is_err());
+        fs::remove_file(ruta).unwrap();
+    }
+
+    #[test]
+    fn test_importar_json_en_una_tabla_nueva() {
+        let directorio = std::env::temp_dir().join(format!("importar_json_{}", std::process::id()));
+        fs::create_dir_all(&directorio).unwrap();
+        let resolvedor = ResolvedorDirectorio::new(directorio.to_str().unwrap());
+        let origen = directorio.join("origen.ndjson");
+        let origen = origen.to_str().unwrap();
+        fs::write(
+            origen,
+            "{\"nombre\": \"Ana\", \"edad\": 40}\n{\"nombre\": \"Luis, hijo\", \"ciudad\": null}\n",
+        )
+        .unwrap();
+
+        let opciones = OpcionesArchivo::default();
+        assert_eq!(
+            importar(origen, "personas", &resolvedor, &opciones).unwrap(),
+            2
+        );
+        assert_eq!(
+            fs::read_to_string(directorio.join("personas")).unwrap(),
+            "nombre,edad,ciudad\nAna,40,\n\"Luis, hijo\",,\n"
+        );
+
+        fs::write(origen, "  [{\"ciudad\": \"Salta\", \"nombre\": \"Eva\"}]").unwrap();
+        assert_eq!(
+            importar(origen, "personas", &resolvedor, &opciones).unwrap(),
+            1
+        );
+        assert_eq!(
+            fs::read_to_string(directorio.join("personas")).unwrap(),
+            "nombre,edad,ciudad\nAna,40,\n\"Luis, hijo\",,\nEva,,Salta\n"
+        );
+
+        fs::write(origen, "nombre,edad\nJuan,30,sobra\n").unwrap();
+        assert!(matches!(
+            importar(origen, "clientes", &resolvedor, &opciones),
+            Err(errores::Errores::RegistroMalformado { .. })
+        ));
+        assert!(!directorio.join("clientes").exists());
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_escribir_esquema_y_revisiones() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\n");
+        tablas.agregar_tabla("ventas", "id,monto\n1,10\n");
+        let mut destino = Vec::new();
+        escribir_esquema(
+            &[],
+            &tablas,
+            &OpcionesArchivo::default(),
+            &OpcionesSalida::default(),
+            &mut destino,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(destino).unwrap(),
+            "tabla,columna,tipo,nulable,clave_primaria,valor_por_defecto\n\
+             personas,nombre,,true,false,\npersonas,edad,,true,false,\n\
+             ventas,id,,true,false,\nventas,monto,,true,false,\n"
+        );
+
+        let mut destino = Vec::new();
+        let resultado = escribir_esquema(
+            &["clientes".to_string()],
+            &tablas,
+            &OpcionesArchivo::default(),
+            &OpcionesSalida::default(),
+            &mut destino,
+        );
+        assert!(matches!(resultado, Err(errores::Errores::InvalidTable(_))));
+
+        assert_eq!(
+            sentencias_de_revision(&[], false, &tablas).unwrap(),
+            "CHECK TABLE personas;CHECK TABLE ventas;"
+        );
+        assert_eq!(
+            sentencias_de_revision(&["ventas".to_string()], true, &tablas).unwrap(),
+            "REPAIR TABLE ventas;"
+        );
+        assert!(matches!(
+            sentencias_de_revision(
+                &["ventas; INSERT INTO x VALUES (1)".to_string()],
+                true,
+                &tablas
+            ),
+            Err(errores::Errores::ArgumentoInvalido(_))
+        ));
+    }
+
+    #[test]
+    fn test_exportar_solo_un_nombre_de_tabla() {
+        assert_eq!(
+            sentencia_de_exportacion("ventas-2024").unwrap(),
+            "SELECT * FROM ventas-2024"
+        );
+        for tabla in [
+            "personas; DELETE FROM personas",
+            "personas WHERE 1 = 0",
+            "'/etc/passwd'",
+            "personas -- x",
+            "from",
+            "",
+        ] {
+            assert_eq!(
+                sentencia_de_exportacion(tabla),
+                Err(errores::Errores::ArgumentoInvalido(format!(
+                    "'{}' no es un nombre de tabla",
+                    tabla
+                ))),
+            );
+        }
+    }
+}
@@ -1,21 +1,16 @@
+use crate::archivo::OpcionesArchivo;
+use crate::copia::{ConsultaCarga, ConsultaCopia};
 use crate::errores;
+use crate::estadisticas::ConsultaAnalisis;
 use crate::insert::ConsultaInsert;
+use crate::lexico;
+use crate::resolvedor::ResolvedorTablas;
+use crate::revision::ConsultaRevision;
+use crate::salida::EscritorResultados;
 use crate::select::ConsultaSelect;
+use crate::sintaxis::{self, Sentencia};
 use std::collections::HashMap;
-
-pub trait Parseables {
-    fn parsear_campos(consulta: &Vec<String>, index: &mut usize) -> Vec<String>;
-    fn parsear_tabla(consulta: &Vec<String>, index: &mut usize) -> String;
-    fn parsear_restricciones(_consulta: &Vec<String>, _index: &mut usize) -> Vec<String> {
-        Vec::new()
-    }
-    fn parsear_ordenamiento(_consulta: &Vec<String>, _index: &mut usize) -> Vec<String> {
-        Vec::new()
-    }
-    fn parsear_valores(_consulta: &Vec<String>, _index: &mut usize) -> Vec<Vec<String>> {
-        Vec::new()
-    }
-}
+use std::fmt;
 
 // Trait para definir metodos comunes de las consultas posibles
 pub trait MetodosConsulta {
@@ -27,50 +22,137 @@ pub trait MetodosConsulta {
     /// # Retorno
     /// - `Ok(())`: Si la consulta es válida.
     /// - `Err(errores::Errores::InvalidSyntax)`: Si faltan campos en la consulta.
-    /// - `Err(errores::Errores::InvalidColumn)`: Si la consulta contiene columnas inválidas.
-
+    /// - `Err(errores::Errores::ColumnaDesconocida)`: Si la consulta contiene columnas que no
+    ///   existen.
     fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores>;
 
     /// Procesa la consulta
     ///
+    /// # Parámetros
+    /// - `escritor`: El escritor que recibe los resultados de la consulta, si los tiene.
+    ///
     /// # Retorno
-    /// Retorna `Ok(())` si la consulta fue exitosa o un error si hubo algún problema al procesarla.
+    /// Retorna el `Resumen` de la consulta si fue exitosa o un error si hubo algún problema al
+    /// procesarla.
+    fn procesar(
+        &mut self,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores>;
+}
+
+/// Lo que produjo una consulta al procesarse, que se muestra al terminarla con `--resumen`.
+///
+/// - `Filas`: La cantidad de filas del resultado.
+/// - `Agregados`: La cantidad de registros agregados a la tabla.
+/// - `Copiados`: La cantidad de filas escritas en un archivo con `COPY ... TO`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resumen {
+    Filas(usize),
+    Agregados(usize),
+    Copiados(usize),
+}
 
-    fn procesar(&mut self) -> Result<(), errores::Errores>;
+impl fmt::Display for Resumen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Resumen::Filas(1) => write!(f, "1 fila"),
+            Resumen::Filas(filas) => write!(f, "{} filas", filas),
+            Resumen::Agregados(1) => write!(f, "1 registro agregado"),
+            Resumen::Agregados(registros) => write!(f, "{} registros agregados", registros),
+            Resumen::Copiados(1) => write!(f, "1 fila copiada"),
+            Resumen::Copiados(filas) => write!(f, "{} filas copiadas", filas),
+        }
+    }
 }
+
 #[derive(Debug)]
 pub enum SQLConsulta {
     Select(ConsultaSelect),
     Insert(ConsultaInsert),
+    Explicar(ConsultaSelect),
+    Revisar(ConsultaRevision),
+    Analizar(ConsultaAnalisis),
+    CopiarA(ConsultaCopia),
+    CopiarDesde(ConsultaCarga),
     //Delete(ConsultaDelete),
     //Update(ConsultaUpdate),
 }
 
 impl SQLConsulta {
-    //Documentar cuando la tenga terminada
+    /// Crea una consulta a partir de su texto SQL.
+    ///
+    /// # Parámetros
+    /// - `consulta`: La consulta SQL.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+    /// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+    ///
+    /// # Retorno
+    /// La `SQLConsulta` lista para procesar, `Errores::InvalidSyntax` si la consulta no es
+    /// válida o no está soportada, o `Errores::InvalidTable` si la tabla no se puede resolver.
     pub fn crear_consulta(
-        consulta: &String,
-        ruta_tablas: &String,
+        consulta: &str,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
     ) -> Result<SQLConsulta, errores::Errores> {
-        // Primero eliminamos los espacios al inicio y convertimos la consulta a minúsculas
-        let consulta_limpia = &consulta.trim_start().to_lowercase();
+        SQLConsulta::desde_sentencia(sintaxis::parsear(consulta)?, resolvedor, opciones)
+    }
 
-        // Usamos match para decidir el tipo de consulta
-        match consulta_limpia.as_str() {
-            _ if consulta_limpia.starts_with("select") => Ok(SQLConsulta::Select(
-                ConsultaSelect::crear(consulta_limpia, ruta_tablas),
+    /// Crea una consulta a partir de una sentencia ya parseada (ver `crear_consulta`).
+    ///
+    /// # Retorno
+    /// La `SQLConsulta` lista para procesar, o `Errores::TransaccionInvalida` si la sentencia
+    /// controla una transacción, que solo se puede usar en un lote (ver
+    /// `transaccion::ejecutar_sentencias`).
+    pub fn desde_sentencia(
+        sentencia: Sentencia,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<SQLConsulta, errores::Errores> {
+        match sentencia {
+            Sentencia::Select(select) => Ok(SQLConsulta::Select(ConsultaSelect::desde_sentencia(
+                select, resolvedor, opciones,
+            )?)),
+            Sentencia::Insert(insert) => Ok(SQLConsulta::Insert(ConsultaInsert::desde_sentencia(
+                insert, resolvedor, opciones,
+            )?)),
+            // Por ahora solo se pueden explicar las consultas de selección
+            Sentencia::Explicar(explicada) => match *explicada {
+                Sentencia::Select(select) => Ok(SQLConsulta::Explicar(
+                    ConsultaSelect::desde_sentencia(select, resolvedor, opciones)?,
+                )),
+                _ => Err(errores::Errores::InvalidSyntax),
+            },
+            Sentencia::Revisar(revisar) => Ok(SQLConsulta::Revisar(
+                ConsultaRevision::desde_sentencia(revisar, resolvedor, opciones)?,
+            )),
+            Sentencia::Analizar(tabla) => Ok(SQLConsulta::Analizar(
+                ConsultaAnalisis::desde_sentencia(tabla, resolvedor, opciones)?,
             )),
-            _ if consulta_limpia.starts_with("insert into") => Ok(SQLConsulta::Insert(
-                ConsultaInsert::crear(consulta_limpia, ruta_tablas),
+            Sentencia::CopiarA(copia) => Ok(SQLConsulta::CopiarA(ConsultaCopia::desde_sentencia(
+                copia, resolvedor, opciones,
+            )?)),
+            Sentencia::CopiarDesde(carga) => Ok(SQLConsulta::CopiarDesde(
+                ConsultaCarga::desde_sentencia(carga, resolvedor, opciones)?,
             )),
-            _ => {
-                // En caso de que no coincida con ninguna consulta soportada, retornamos un error
-                return Err(errores::Errores::InvalidSyntax);
+            Sentencia::Comenzar | Sentencia::Confirmar | Sentencia::Deshacer => {
+                Err(errores::Errores::TransaccionInvalida(
+                    "solo se puede usar en un lote de sentencias".to_string(),
+                ))
             }
         }
     }
 
-    pub fn procesar_consulta(&mut self) -> Result<(), errores::Errores> {
+    /// Verifica la consulta, la procesa y termina de escribir sus resultados.
+    ///
+    /// # Parámetros
+    /// - `escritor`: El escritor que recibe los resultados de la consulta.
+    ///
+    /// # Retorno
+    /// Retorna el `Resumen` de la consulta si fue exitosa o el error que impidió procesarla.
+    pub fn procesar_consulta(
+        &mut self,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
         match self.verificar_validez_consulta() {
             Ok(_) => {}
             Err(consulta_no_valida) => {
@@ -78,30 +160,58 @@ impl SQLConsulta {
             }
         }
 
-        match self {
-            SQLConsulta::Select(consulta_select) => consulta_select.procesar(),
-            SQLConsulta::Insert(consulta_insert) => consulta_insert.procesar(),
-        }
+        let resumen = match self {
+            SQLConsulta::Select(consulta_select) => consulta_select.procesar(escritor)?,
+            SQLConsulta::Insert(consulta_insert) => consulta_insert.procesar(escritor)?,
+            SQLConsulta::Explicar(consulta_select) => consulta_select.explicar(escritor)?,
+            SQLConsulta::Revisar(consulta_revision) => consulta_revision.procesar(escritor)?,
+            SQLConsulta::Analizar(consulta_analisis) => consulta_analisis.procesar(escritor)?,
+            SQLConsulta::CopiarA(consulta_copia) => consulta_copia.procesar(escritor)?,
+            SQLConsulta::CopiarDesde(consulta_carga) => consulta_carga.procesar(escritor)?,
+        };
+        escritor.finalizar()?;
+        Ok(resumen)
     }
 
     fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
         match self {
             SQLConsulta::Select(consulta_select) => consulta_select.verificar_validez_consulta(),
             SQLConsulta::Insert(consulta_insert) => consulta_insert.verificar_validez_consulta(),
+            SQLConsulta::Explicar(consulta_select) => consulta_select.verificar_validez_consulta(),
+            SQLConsulta::Revisar(consulta_revision) => {
+                consulta_revision.verificar_validez_consulta()
+            }
+            SQLConsulta::Analizar(consulta_analisis) => {
+                consulta_analisis.verificar_validez_consulta()
+            }
+            SQLConsulta::CopiarA(consulta_copia) => consulta_copia.verificar_validez_consulta(),
+            SQLConsulta::CopiarDesde(consulta_carga) => consulta_carga.verificar_validez_consulta(),
         }
     }
 }
 
-pub fn mapear_campos(campos: &Vec<String>) -> HashMap<String, usize> {
+pub fn mapear_campos(campos: &[String]) -> HashMap<String, usize> {
     let mut campos_mapeados: HashMap<String, usize> = HashMap::new();
-    let mut indice: usize = 0;
-    for campo in campos {
-        let indice_i: usize = indice;
-        campos_mapeados.insert(campo.to_string(), indice_i);
-        indice += 1;
+    for (indice, campo) in campos.iter().enumerate() {
+        campos_mapeados.insert(campo.to_string(), indice);
     }
-    return campos_mapeados;
+    campos_mapeados
 }
+
+/// Divide una consulta SQL en el texto de sus tokens (ver `lexico::analizar`).
+///
+/// # Parámetros
+/// - `consulta`: La consulta SQL a dividir.
+///
+/// # Retorno
+/// Un `Vec<String>` con el texto de los tokens en el orden en que aparecen.
+pub fn tokenizar(consulta: &str) -> Vec<String> {
+    lexico::analizar(consulta)
+        .into_iter()
+        .map(|token| token.texto)
+        .collect()
+}
+
 pub trait Verificaciones {
     fn verificar_campos_validos(
         campos_validos: &HashMap<String, usize>,
@@ -109,6 +219,25 @@ pub trait Verificaciones {
     ) -> bool;
 }
 
+/// Arma el error de una lista de campos que no pasó la verificación de `Verificaciones`.
+///
+/// # Parámetros
+/// - `campos_validos`: Los campos de la tabla.
+/// - `campos_consulta`: Los campos de la consulta.
+///
+/// # Retorno
+/// `Errores::ColumnaDesconocida` con el primer campo de la consulta que no pertenece a la tabla.
+pub fn columna_invalida(
+    campos_validos: &HashMap<String, usize>,
+    campos_consulta: &[String],
+) -> errores::Errores {
+    let campo = campos_consulta
+        .iter()
+        .find(|campo| !campos_validos.contains_key(*campo))
+        .map_or_else(String::new, |campo| campo.to_string());
+    errores::Errores::columna_desconocida(&campo, campos_validos.keys())
+}
+
 pub fn obtener_campos_consulta_orden_por_defecto(campos: &HashMap<String, usize>) -> Vec<String> {
     // Convertimos el HashMap en un vector de pares (clave, valor)
     let mut vec: Vec<(&String, &usize)> = campos.iter().collect();
@@ -127,6 +256,9 @@ pub fn obtener_campos_consulta_orden_por_defecto(campos: &HashMap<String, usize>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use crate::resolvedor::ResolvedorDirectorio;
+    use crate::salida::OpcionesSalida;
     use std::collections::HashMap;
 
     #[test]
@@ -155,42 +287,126 @@ mod tests {
         assert_eq!(resultado, esperado);
     }
 
+    #[test]
+    fn test_tokenizar_respeta_literales_y_simbolos() {
+        let tokens = tokenizar("SELECT IIF(Edad>30,'Sí, Mayor',nombre) FROM Personas");
+        assert_eq!(
+            tokens,
+            vec![
+                "select",
+                "iif",
+                "(",
+                "edad",
+                ">",
+                "30",
+                ",",
+                "'Sí, Mayor'",
+                ",",
+                "nombre",
+                ")",
+                "from",
+                "personas"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenizar_literales_con_comillas_escapadas() {
+        let tokens = tokenizar(r"WHERE nombre = 'O''Brien' OR nombre = 'D\'Amico'");
+        assert_eq!(
+            tokens,
+            vec![
+                "where",
+                "nombre",
+                "=",
+                "'O''Brien'",
+                "or",
+                "nombre",
+                "=",
+                r"'D\'Amico'"
+            ]
+        );
+    }
+
     #[test]
     fn test_crear_consulta_select() {
         let consulta = "SELECT * FROM tabla".to_string();
-        let ruta_tablas = "ruta/a/tablas".to_string();
-        let resultado = SQLConsulta::crear_consulta(&consulta, &ruta_tablas);
+        let resolvedor = ResolvedorDirectorio::new("ruta/a/tablas");
+        let resultado =
+            SQLConsulta::crear_consulta(&consulta, &resolvedor, &OpcionesArchivo::default());
 
-        assert!(resultado.is_ok());
-        match resultado.unwrap() {
-            SQLConsulta::Select(_) => assert!(true),
-            _ => assert!(false, "Se esperaba una consulta de tipo SELECT"),
-        }
+        assert!(
+            matches!(resultado, Ok(SQLConsulta::Select(_))),
+            "Se esperaba una consulta de tipo SELECT"
+        );
     }
 
     #[test]
     fn test_crear_consulta_insert() {
         let consulta = "INSERT INTO tabla (id, nombre ) VALUES (1, 'John')".to_string();
-        let ruta_tablas = "ruta/a/tablas".to_string();
-        let resultado = SQLConsulta::crear_consulta(&consulta, &ruta_tablas);
+        let resolvedor = ResolvedorDirectorio::new("ruta/a/tablas");
+        let resultado =
+            SQLConsulta::crear_consulta(&consulta, &resolvedor, &OpcionesArchivo::default());
 
-        assert!(resultado.is_ok());
-        match resultado.unwrap() {
-            SQLConsulta::Insert(_) => assert!(true),
-            _ => assert!(false, "Se esperaba una consulta de tipo INSERT"),
-        }
+        assert!(
+            matches!(resultado, Ok(SQLConsulta::Insert(_))),
+            "Se esperaba una consulta de tipo INSERT"
+        );
+    }
+
+    #[test]
+    fn test_procesar_consulta_devuelve_resumen() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre\nAna\n");
+        let procesar = |consulta: &str| {
+            let mut destino = Vec::new();
+            let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+            SQLConsulta::crear_consulta(consulta, &tablas, &OpcionesArchivo::default())
+                .unwrap()
+                .procesar_consulta(escritor.as_mut())
+                .unwrap()
+        };
+
+        let resumen = procesar("INSERT INTO personas VALUES ('Luis'), ('Eva')");
+        assert_eq!(resumen, Resumen::Agregados(2));
+        assert_eq!(resumen.to_string(), "2 registros agregados");
+        let resumen = procesar("SELECT * FROM personas WHERE nombre <> 'Ana'");
+        assert_eq!(resumen, Resumen::Filas(2));
+        let resumen = procesar("SELECT * FROM personas LIMIT 1");
+        assert_eq!(resumen.to_string(), "1 fila");
+    }
+
+    #[test]
+    fn test_crear_consulta_explain() {
+        let resolvedor = ResolvedorDirectorio::new("ruta/a/tablas");
+        let resultado = SQLConsulta::crear_consulta(
+            "EXPLAIN SELECT * FROM tabla",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        );
+        assert!(matches!(resultado, Ok(SQLConsulta::Explicar(_))));
+
+        let resultado = SQLConsulta::crear_consulta(
+            "EXPLAIN INSERT INTO tabla (id) VALUES (1)",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        );
+        assert_eq!(resultado.unwrap_err(), errores::Errores::InvalidSyntax);
     }
 
     #[test]
     fn test_crear_consulta_invalida() {
         let consulta = " * FROM tabla".to_string();
-        let ruta_tablas = "ruta/a/tablas".to_string();
-        let resultado = SQLConsulta::crear_consulta(&consulta, &ruta_tablas);
+        let resolvedor = ResolvedorDirectorio::new("ruta/a/tablas");
+        let resultado =
+            SQLConsulta::crear_consulta(&consulta, &resolvedor, &OpcionesArchivo::default());
 
-        assert!(resultado.is_err());
-        match resultado.unwrap() {
-            SQLConsulta::Select(_) => assert!(true),
-            _ => assert!(false, "Se esperaba una consulta válida"),
-        }
+        assert_eq!(
+            resultado.unwrap_err(),
+            errores::Errores::TokenInesperado {
+                token: "*".to_string(),
+                posicion: 1
+            }
+        );
     }
 }
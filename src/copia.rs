@@ -0,0 +1,426 @@
+use crate::almacenamiento::Almacenamiento;
+use crate::archivo::{
+    formatear_registro, leer_archivo, leer_encabezado, leer_registro_en, parsear_registro,
+    OpcionesArchivo,
+};
+use crate::consulta::{
+    columna_invalida, mapear_campos, obtener_campos_consulta_orden_por_defecto, MetodosConsulta,
+    Resumen,
+};
+use crate::errores;
+use crate::esquema::Esquema;
+use crate::expresion::Valor;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::{EscritorResultados, FormatoSalida, OpcionesSalida};
+use crate::select::ConsultaSelect;
+use crate::sintaxis::{SentenciaCopiarA, SentenciaCopiarDesde};
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::Arc;
+
+/// Representa una sentencia `COPY ... TO`, que ejecuta una consulta de selección y escribe su
+/// resultado en un archivo, en cualquiera de los formatos de los resultados (ver
+/// `FormatoSalida`), en lugar de mostrarlo.
+///
+/// El archivo se crea, o se reemplaza si ya existe, con las opciones por defecto de cada
+/// formato. Con `HEADER`, el formato `csv` escribe primero los nombres de las columnas, de modo
+/// que el archivo se puede consultar como una tabla; sin `HEADER`, escribe solo los registros.
+///
+/// # Campos
+///
+/// - `consulta`: La `ConsultaSelect` cuyo resultado se copia.
+/// - `archivo`: La ruta del archivo en el que se escribe.
+/// - `formato`: El `FormatoSalida` del archivo.
+/// - `encabezado`: Si el formato `csv` escribe la fila con los nombres de las columnas.
+#[derive(Debug)]
+pub struct ConsultaCopia {
+    pub consulta: ConsultaSelect,
+    pub archivo: String,
+    pub formato: FormatoSalida,
+    pub encabezado: bool,
+}
+
+impl ConsultaCopia {
+    /// Crea la copia a partir de la sentencia ya parseada.
+    ///
+    /// # Parámetros
+    /// - `sentencia`: La sentencia `COPY ... TO`.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+    /// - `opciones`: Las opciones con las que se leen los archivos de las tablas.
+    ///
+    /// # Retorno
+    /// La `ConsultaCopia`, o el error de la consulta copiada (ver
+    /// `ConsultaSelect::desde_sentencia`).
+    pub fn desde_sentencia(
+        sentencia: SentenciaCopiarA,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaCopia, errores::Errores> {
+        Ok(ConsultaCopia {
+            consulta: ConsultaSelect::desde_sentencia(sentencia.consulta, resolvedor, opciones)?,
+            archivo: sentencia.archivo,
+            formato: sentencia.formato,
+            encabezado: sentencia.encabezado,
+        })
+    }
+}
+
+impl MetodosConsulta for ConsultaCopia {
+    /// Verifica la consulta copiada.
+    fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
+        self.consulta.verificar_validez_consulta()
+    }
+
+    /// Ejecuta la consulta y escribe su resultado en el archivo; no escribe nada en el
+    /// escritor de la sentencia.
+    ///
+    /// # Retorno
+    /// El `Resumen` con la cantidad de filas copiadas, o el error que impidió ejecutar la
+    /// consulta o escribir el archivo.
+    fn procesar(
+        &mut self,
+        _escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let archivo = File::create(&self.archivo)
+            .map_err(|error| errores::Errores::de_archivo(error, &self.archivo))?;
+        let mut destino = BufWriter::new(archivo);
+        let opciones_salida = OpcionesSalida {
+            formato: self.formato,
+            sin_encabezado: self.formato == FormatoSalida::Csv && !self.encabezado,
+            ..OpcionesSalida::default()
+        };
+        let mut escritor = opciones_salida.crear_escritor(&mut destino);
+        let filas = match self.consulta.procesar(escritor.as_mut())? {
+            Resumen::Filas(filas) | Resumen::Agregados(filas) | Resumen::Copiados(filas) => filas,
+        };
+        escritor.finalizar()?;
+        registro::registrar(registro::DETALLE, || {
+            format!("{} filas copiadas en '{}'", filas, self.archivo)
+        });
+        Ok(Resumen::Copiados(filas))
+    }
+}
+
+/// Representa una sentencia `COPY tabla [(columnas)] FROM 'archivo'`, que agrega a una tabla
+/// todos los registros de un archivo CSV sin armar un `INSERT` por cada uno.
+///
+/// Los campos de cada registro del archivo corresponden, en orden, a las columnas listadas en
+/// la sentencia o, si no lista ninguna, a todas las columnas de la tabla; las columnas omitidas
+/// toman su valor por defecto del esquema o quedan nulas. Con `HEADER` la primera línea del
+/// archivo se descarta. El archivo se lee con la codificación y el texto de `NULL` de la tabla,
+/// y con el delimitador de `DELIMITER`, el indicado para las tablas o, si no hay ninguno, el
+/// detectado en el encabezado del archivo o la coma.
+///
+/// Cada registro se verifica como los valores de un `INSERT` (ver `ConsultaInsert`) y todos se
+/// agregan juntos al final de la tabla, de modo que si alguno no es válido no se agrega
+/// ninguno.
+///
+/// # Campos
+///
+/// - `ruta_tabla`: La ruta del archivo de la tabla.
+/// - `columnas`: Las columnas que reciben los campos de cada registro, en orden.
+/// - `archivo`: La ruta del archivo CSV, o `-` para la entrada estándar.
+/// - `encabezado`: Si la primera línea del archivo es un encabezado que se descarta.
+/// - `opciones_archivo`: Las `OpcionesArchivo` con las que se lee el archivo CSV.
+/// - `campos_tabla`: Las columnas de la tabla, en el orden del archivo de la tabla.
+/// - `almacenamiento`: El `Almacenamiento` en el que está guardada la tabla.
+/// - `esquema`: El `Esquema` de la tabla.
+/// - `opciones`: Las `OpcionesArchivo` con las que se lee y escribe el archivo de la tabla.
+#[derive(Debug)]
+pub struct ConsultaCarga {
+    pub ruta_tabla: String,
+    pub columnas: Vec<String>,
+    pub archivo: String,
+    pub encabezado: bool,
+    pub opciones_archivo: OpcionesArchivo,
+    pub campos_tabla: Vec<String>,
+    pub almacenamiento: Arc<dyn Almacenamiento>,
+    pub esquema: Esquema,
+    pub opciones: OpcionesArchivo,
+}
+
+impl ConsultaCarga {
+    /// Crea la carga a partir de la sentencia ya parseada.
+    ///
+    /// # Parámetros
+    /// - `sentencia`: La sentencia `COPY ... FROM`.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla.
+    /// - `opciones`: Las opciones con las que se leen los archivos de las tablas.
+    ///
+    /// # Retorno
+    /// La `ConsultaCarga`, o `Errores::InvalidTable` si la tabla no se puede resolver.
+    pub fn desde_sentencia(
+        sentencia: SentenciaCopiarDesde,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaCarga, errores::Errores> {
+        let ruta_tabla = resolvedor.resolver(&sentencia.tabla)?;
+        let opciones_archivo = OpcionesArchivo {
+            delimitador: sentencia.delimitador.or(opciones.delimitador),
+            comprimido: false,
+            columnas: None,
+            mapear_memoria: false,
+            ..opciones.clone()
+        };
+        Ok(ConsultaCarga {
+            ruta_tabla,
+            columnas: sentencia.columnas,
+            archivo: sentencia.archivo,
+            encabezado: sentencia.encabezado,
+            opciones_archivo,
+            campos_tabla: Vec::new(),
+            almacenamiento: resolvedor.almacenamiento(),
+            esquema: Esquema::default(),
+            opciones: opciones.clone(),
+        })
+    }
+
+    /// Arma el registro completo a escribir en la tabla a partir de los campos de un registro
+    /// del archivo, verificando que cada valor sea del tipo de su columna y que no falten
+    /// valores en las columnas `NOT NULL`.
+    ///
+    /// # Parámetros
+    /// - `campos`: Los campos del registro, en el orden de `columnas`.
+    /// - `linea`: La línea del archivo en la que empieza el registro, para los errores.
+    ///
+    /// # Retorno
+    /// Un valor por cada columna de la tabla, `Errores::TipoInvalido` si un valor no es del
+    /// tipo de su columna o `Errores::InvalidColumn` si una columna `NOT NULL` queda nula.
+    fn armar_registro(
+        &self,
+        mut campos: Vec<Option<String>>,
+        linea: usize,
+    ) -> Result<Vec<Option<String>>, errores::Errores> {
+        let mut registro = Vec::with_capacity(self.campos_tabla.len());
+        for campo in &self.campos_tabla {
+            let columna = self.esquema.columna(campo);
+            let valor = match self.columnas.iter().position(|c| c == campo) {
+                Some(posicion) => campos[posicion].take(),
+                None => columna
+                    .and_then(|columna| columna.valor_por_defecto.as_ref())
+                    .map(|valor_por_defecto| valor_por_defecto.evaluar()),
+            };
+            if let Some(columna) = columna {
+                if !columna.nulable && valor.is_none() {
+                    return Err(errores::Errores::InvalidColumn(campo.to_string()));
+                }
+                if let (Some(tipo), Some(texto)) = (columna.tipo, &valor) {
+                    if Valor::desde_campo_tipado(Some(texto), tipo).is_err() {
+                        return Err(errores::Errores::TipoInvalido(format!(
+                            "'{}' no es un valor de tipo {} para la columna '{}' en la línea {}",
+                            texto, tipo, campo, linea
+                        )));
+                    }
+                }
+            }
+            registro.push(valor);
+        }
+        Ok(registro)
+    }
+}
+
+impl MetodosConsulta for ConsultaCarga {
+    /// Verifica que la tabla exista, que las columnas listadas sean columnas de la tabla y que
+    /// las columnas omitidas tengan valor por defecto o admitan valores nulos.
+    ///
+    /// # Retorno
+    /// - `Ok(())`: Si la carga es válida.
+    /// - `Err(errores::Errores::InvalidTable)`: Si no se puede leer la tabla.
+    /// - `Err(errores::Errores::ColumnaDesconocida)`: Si se lista una columna que no existe.
+    /// - `Err(errores::Errores::InvalidColumn)`: Si se omite una columna `NOT NULL` sin valor
+    ///   por defecto.
+    fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
+        let campos_posibles = match self.almacenamiento.leer(&self.ruta_tabla, &self.opciones) {
+            Ok(mut lector) => mapear_campos(&leer_encabezado(lector.as_mut(), &mut self.opciones)?),
+            Err(error) => return Err(errores::Errores::tabla_ilegible(error, &self.ruta_tabla)),
+        };
+        self.esquema = self.almacenamiento.esquema(&self.ruta_tabla)?;
+        self.campos_tabla = obtener_campos_consulta_orden_por_defecto(&campos_posibles);
+
+        if self.columnas.is_empty() {
+            self.columnas = self.campos_tabla.clone();
+        }
+        if self
+            .columnas
+            .iter()
+            .any(|columna| !campos_posibles.contains_key(columna))
+        {
+            return Err(columna_invalida(&campos_posibles, &self.columnas));
+        }
+        for campo in &self.campos_tabla {
+            if self.columnas.contains(campo) {
+                continue;
+            }
+            if let Some(columna) = self.esquema.columna(campo) {
+                if !columna.nulable && columna.valor_por_defecto.is_none() {
+                    return Err(errores::Errores::InvalidColumn(campo.to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lee los registros del archivo, los verifica y los agrega al final de la tabla a través
+    /// del almacenamiento (ver `Almacenamiento::agregar`).
+    ///
+    /// # Retorno
+    /// El `Resumen` con la cantidad de registros agregados, `Errores::RegistroMalformado` si
+    /// un registro no tiene un campo por columna, o el error del primer registro que no es
+    /// válido (ver `armar_registro`).
+    fn procesar(
+        &mut self,
+        _escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let mut lector = leer_archivo(&self.archivo, &self.opciones_archivo)
+            .map_err(|error| errores::Errores::de_archivo(error, &self.archivo))?;
+        if self.encabezado {
+            leer_encabezado(lector.as_mut(), &mut self.opciones_archivo)?;
+        }
+        let delimitador = self.opciones_archivo.delimitador();
+        let mut linea = usize::from(self.encabezado);
+        let mut registros = 0;
+        let mut lineas = String::new();
+        let mut texto = String::new();
+        while leer_registro_en(lector.as_mut(), &mut texto)? {
+            let inicio = linea + 1;
+            linea = inicio + texto.matches('\n').count();
+            if texto.is_empty() {
+                continue;
+            }
+            let campos = parsear_registro(&texto, delimitador, self.opciones_archivo.nulo());
+            if campos.len() != self.columnas.len() {
+                return Err(errores::Errores::RegistroMalformado {
+                    linea: inicio,
+                    campos: campos.len(),
+                    columnas: self.columnas.len(),
+                });
+            }
+            lineas.push_str(&formatear_registro(
+                &self.armar_registro(campos, inicio)?,
+                self.opciones.delimitador(),
+                self.opciones.nulo(),
+            ));
+            lineas.push('\n');
+            registros += 1;
+        }
+        if registros > 0 {
+            self.almacenamiento
+                .agregar(&self.ruta_tabla, &self.opciones, &lineas)?;
+        }
+        registro::registrar(registro::DETALLE, || {
+            format!("{} registros cargados desde '{}'", registros, self.archivo)
+        });
+        Ok(Resumen::Agregados(registros))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use crate::consulta::SQLConsulta;
+    use std::fs;
+
+    #[test]
+    fn test_copiar_resultado_a_un_archivo() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\n\"Luis, hijo\",25\nEva,\n");
+        let ruta = std::env::temp_dir().join(format!("copia_{}", std::process::id()));
+        let ruta = ruta.to_str().unwrap();
+        let copiar = |consulta: &str| {
+            let mut salida = Vec::new();
+            let resumen =
+                SQLConsulta::crear_consulta(consulta, &tablas, &OpcionesArchivo::default())
+                    .unwrap()
+                    .procesar_consulta(
+                        OpcionesSalida::default()
+                            .crear_escritor(&mut salida)
+                            .as_mut(),
+                    )
+                    .unwrap();
+            assert!(salida.is_empty());
+            (resumen, fs::read_to_string(ruta).unwrap())
+        };
+
+        assert_eq!(
+            copiar(&format!(
+                "COPY (SELECT nombre, edad FROM personas WHERE edad > 30) TO '{}' FORMAT JSON",
+                ruta
+            )),
+            (
+                Resumen::Copiados(1),
+                "[\n  {\"nombre\": \"Ana\", \"edad\": 40}\n]\n".to_string()
+            )
+        );
+        assert_eq!(
+            copiar(&format!("COPY personas TO '{}' (FORMAT csv, HEADER)", ruta)),
+            (
+                Resumen::Copiados(3),
+                "nombre,edad\nAna,40\n\"Luis, hijo\",25\nEva,\n".to_string()
+            )
+        );
+        assert_eq!(
+            copiar(&format!("COPY personas TO '{}'", ruta)),
+            (
+                Resumen::Copiados(3),
+                "Ana,40\n\"Luis, hijo\",25\nEva,\n".to_string()
+            )
+        );
+        fs::remove_file(ruta).unwrap();
+
+        let resultado = SQLConsulta::crear_consulta(
+            "COPY clientes TO 'x.csv'",
+            &tablas,
+            &OpcionesArchivo::default(),
+        );
+        assert!(matches!(resultado, Err(errores::Errores::InvalidTable(_))));
+    }
+
+    #[test]
+    fn test_cargar_registros_desde_un_archivo() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\n");
+        let ruta = std::env::temp_dir().join(format!("carga_{}.csv", std::process::id()));
+        let ruta = ruta.to_str().unwrap();
+        let cargar = |consulta: &str| {
+            SQLConsulta::crear_consulta(consulta, &tablas, &OpcionesArchivo::default())?
+                .procesar_consulta(
+                    OpcionesSalida::default()
+                        .crear_escritor(std::io::sink())
+                        .as_mut(),
+                )
+        };
+
+        fs::write(ruta, "edad;nombre\n25;\"Luis; hijo\"\n;Eva\n").unwrap();
+        assert_eq!(
+            cargar(&format!(
+                "COPY personas (edad, nombre) FROM '{}' WITH (FORMAT csv, HEADER, DELIMITER ';')",
+                ruta
+            )),
+            Ok(Resumen::Agregados(2))
+        );
+        assert_eq!(
+            tablas.contenido("personas").unwrap(),
+            "nombre,edad\nAna,40\nLuis; hijo,25\nEva,\n"
+        );
+
+        // Un registro con otra cantidad de campos impide agregar todos los demás.
+        fs::write(ruta, "Juan,30\nMaria\n").unwrap();
+        assert_eq!(
+            cargar(&format!("COPY personas FROM '{}'", ruta)),
+            Err(errores::Errores::RegistroMalformado {
+                linea: 2,
+                campos: 1,
+                columnas: 2
+            })
+        );
+        assert_eq!(tablas.contenido("personas").unwrap().lines().count(), 4);
+        fs::remove_file(ruta).unwrap();
+
+        assert!(matches!(
+            cargar(&format!("COPY personas (altura) FROM '{}'", ruta)),
+            Err(errores::Errores::ColumnaDesconocida { .. })
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Operación sobre una tabla registrada en su diario antes de modificarla.
+///
+/// - `Agregar`: Se van a agregar registros al final de la tabla, que tenía la longitud indicada,
+///   en bytes.
+/// - `Copia`: Se creó una copia de trabajo de la tabla en la ruta indicada, para una
+///   transacción.
+/// - `Transaccion`: La transacción se va a confirmar con el registro de la ruta indicada (ver
+///   `confirmar`); las copias de trabajo que el registro lista deben reemplazar a la tabla.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entrada {
+    Agregar(u64),
+    Copia(String),
+    Transaccion(String),
+}
+
+impl Entrada {
+    fn parsear(linea: &str) -> Option<Entrada> {
+        match linea.split_once(' ') {
+            Some(("agregar", longitud)) => longitud.parse().ok().map(Entrada::Agregar),
+            Some(("copia", ruta_copia)) => Some(Entrada::Copia(ruta_copia.to_string())),
+            Some(("transaccion", ruta_registro)) => {
+                Some(Entrada::Transaccion(ruta_registro.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Arma la ruta del diario de una tabla: `directorio/.<archivo>.diario`.
+pub fn ruta_diario(ruta: &str) -> String {
+    let ruta = Path::new(ruta);
+    let archivo = ruta
+        .file_name()
+        .map(|archivo| archivo.to_string_lossy().to_string())
+        .unwrap_or_default();
+    ruta.with_file_name(format!(".{}.diario", archivo))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Agrega una entrada al diario de la tabla y espera a que llegue al disco, de modo que
+/// sobreviva a una interrupción del proceso.
+///
+/// # Parámetros
+/// - `ruta`: La ruta de la tabla.
+/// - `entrada`: La operación que se va a realizar.
+pub fn registrar(ruta: &str, entrada: &Entrada) -> io::Result<()> {
+    let linea = match entrada {
+        Entrada::Agregar(longitud) => format!("agregar {}\n", longitud),
+        Entrada::Copia(ruta_copia) => format!("copia {}\n", ruta_copia),
+        Entrada::Transaccion(ruta_registro) => format!("transaccion {}\n", ruta_registro),
+    };
+    let mut diario = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ruta_diario(ruta))?;
+    diario.write_all(linea.as_bytes())?;
+    diario.sync_all()
+}
+
+/// Elimina el diario de la tabla una vez que las operaciones registradas terminaron.
+pub fn cerrar(ruta: &str) -> io::Result<()> {
+    eliminar_si_existe(&ruta_diario(ruta))
+}
+
+/// Confirma una transacción escribiendo un único registro con todas sus copias de trabajo,
+/// una por línea junto con su tabla. El registro se escribe completo en un archivo temporal
+/// que luego se renombra, de modo que existe entero o no existe: desde ese momento las copias
+/// deben reemplazar a sus tablas, aunque el proceso se interrumpa antes de renombrarlas.
+///
+/// # Parámetros
+/// - `ruta_registro`: La ruta del registro, que el diario de cada tabla ya indica con
+///   `Entrada::Transaccion`.
+/// - `copias`: La ruta de cada tabla junto con la ruta de su copia.
+pub fn confirmar(ruta_registro: &str, copias: &[(String, String)]) -> io::Result<()> {
+    let contenido: String = copias
+        .iter()
+        .map(|(ruta, ruta_copia)| format!("{}\t{}\n", ruta, ruta_copia))
+        .collect();
+    let ruta_temporal = format!("{}.tmp", ruta_registro);
+    let mut registro = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&ruta_temporal)?;
+    registro.write_all(contenido.as_bytes())?;
+    registro.sync_all()?;
+    fs::rename(&ruta_temporal, ruta_registro)
+}
+
+/// Elimina el registro de una transacción confirmada una vez que todas sus copias
+/// reemplazaron a sus tablas.
+pub fn cerrar_transaccion(ruta_registro: &str) -> io::Result<()> {
+    eliminar_si_existe(ruta_registro)
+}
+
+/// Lee las tablas y las copias de trabajo del registro de una transacción confirmada.
+///
+/// # Retorno
+/// Las tablas junto con sus copias, `None` si la transacción no llegó a confirmarse o ya
+/// terminó, o el error de E/S al leer el registro.
+fn leer_transaccion(ruta_registro: &str) -> io::Result<Option<Vec<(String, String)>>> {
+    match fs::read_to_string(ruta_registro) {
+        Ok(contenido) => Ok(Some(
+            contenido
+                .lines()
+                .filter_map(|linea| linea.split_once('\t'))
+                .map(|(ruta, ruta_copia)| (ruta.to_string(), ruta_copia.to_string()))
+                .collect(),
+        )),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+/// Termina o deshace las operaciones que quedaron registradas en el diario de la tabla porque el
+/// proceso se interrumpió antes de cerrarlo.
+///
+/// Los registros agregados a medias se quitan, devolviendo la tabla a su longitud anterior. Las
+/// copias de trabajo que lista el registro de una transacción confirmada reemplazan a la tabla
+/// (si no lo hicieron ya); las de una transacción sin confirmar se eliminan. El registro de la
+/// transacción se elimina cuando ya no queda ninguna de sus tablas por recuperar.
+///
+/// # Parámetros
+/// - `ruta`: La ruta de la tabla.
+///
+/// # Retorno
+/// `true` si había un diario y se recuperó la tabla, o el error de E/S al recuperarla; en ese
+/// caso el diario se conserva para volver a intentarlo.
+pub fn recuperar(ruta: &str) -> io::Result<bool> {
+    let contenido = match fs::read_to_string(ruta_diario(ruta)) {
+        Ok(contenido) => contenido,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(error) => return Err(error),
+    };
+    // Una última línea incompleta se descarta: el proceso se interrumpió al escribirla.
+    let entradas: Vec<Entrada> = contenido
+        .split_inclusive('\n')
+        .filter_map(|linea| linea.strip_suffix('\n'))
+        .filter_map(Entrada::parsear)
+        .collect();
+    let ruta_registro = entradas.iter().find_map(|entrada| match entrada {
+        Entrada::Transaccion(ruta_registro) => Some(ruta_registro.as_str()),
+        _ => None,
+    });
+    let transaccion = match ruta_registro {
+        Some(ruta_registro) => leer_transaccion(ruta_registro)?,
+        None => None,
+    };
+    let confirmada = |ruta_copia: &str| {
+        transaccion
+            .iter()
+            .flatten()
+            .any(|(tabla, copia)| tabla == ruta && copia == ruta_copia)
+    };
+
+    for entrada in &entradas {
+        match entrada {
+            Entrada::Agregar(longitud) => {
+                let tabla = OpenOptions::new().write(true).open(ruta)?;
+                if tabla.metadata()?.len() > *longitud {
+                    tabla.set_len(*longitud)?;
+                    tabla.sync_all()?;
+                }
+            }
+            Entrada::Copia(ruta_copia) => {
+                if confirmada(ruta_copia) && Path::new(ruta_copia).exists() {
+                    fs::rename(ruta_copia, ruta)?;
+                } else {
+                    eliminar_si_existe(ruta_copia)?;
+                }
+                // Los registros agregados a la copia también pueden haber dejado su diario.
+                cerrar(ruta_copia)?;
+            }
+            Entrada::Transaccion(_) => {}
+        }
+    }
+    cerrar(ruta)?;
+    if let (Some(ruta_registro), Some(transaccion)) = (ruta_registro, &transaccion) {
+        let pendiente = transaccion
+            .iter()
+            .any(|(tabla, _)| Path::new(&ruta_diario(tabla)).exists());
+        if !pendiente {
+            cerrar_transaccion(ruta_registro)?;
+        }
+    }
+    Ok(true)
+}
+
+fn eliminar_si_existe(ruta: &str) -> io::Result<()> {
+    match fs::remove_file(ruta) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recuperar_operaciones_interrumpidas() {
+        let directorio = std::env::temp_dir().join("test_recuperar_operaciones_interrumpidas");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = directorio.join("personas").to_string_lossy().to_string();
+        let ruta_copia = directorio.join(".1-personas").to_string_lossy().to_string();
+
+        // Un INSERT interrumpido a mitad de un registro.
+        fs::write(&ruta, "nombre\nAna\n").unwrap();
+        registrar(&ruta, &Entrada::Agregar(11)).unwrap();
+        fs::write(&ruta, "nombre\nAna\nLu").unwrap();
+        assert!(recuperar(&ruta).unwrap());
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "nombre\nAna\n");
+        assert!(!Path::new(&ruta_diario(&ruta)).exists());
+        assert!(!recuperar(&ruta).unwrap());
+
+        // Una transacción que no llegó a confirmarse.
+        registrar(&ruta, &Entrada::Copia(ruta_copia.to_string())).unwrap();
+        fs::write(&ruta_copia, "nombre\nAna\nLuis\n").unwrap();
+        assert!(recuperar(&ruta).unwrap());
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "nombre\nAna\n");
+        assert!(!Path::new(&ruta_copia).exists());
+
+        // Una transacción que indicó su registro pero no llegó a escribirlo.
+        let ruta_registro = directorio
+            .join(".1-transaccion")
+            .to_string_lossy()
+            .to_string();
+        registrar(&ruta, &Entrada::Copia(ruta_copia.to_string())).unwrap();
+        fs::write(&ruta_copia, "nombre\nAna\nLuis\n").unwrap();
+        registrar(&ruta, &Entrada::Transaccion(ruta_registro.to_string())).unwrap();
+        assert!(recuperar(&ruta).unwrap());
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "nombre\nAna\n");
+        assert!(!Path::new(&ruta_copia).exists());
+
+        // Una transacción confirmada, interrumpida antes de reemplazar la tabla.
+        registrar(&ruta, &Entrada::Copia(ruta_copia.to_string())).unwrap();
+        fs::write(&ruta_copia, "nombre\nAna\nLuis\n").unwrap();
+        registrar(&ruta, &Entrada::Transaccion(ruta_registro.to_string())).unwrap();
+        confirmar(
+            &ruta_registro,
+            &[(ruta.to_string(), ruta_copia.to_string())],
+        )
+        .unwrap();
+        assert!(recuperar(&ruta).unwrap());
+        assert_eq!(fs::read_to_string(&ruta).unwrap(), "nombre\nAna\nLuis\n");
+        assert!(!Path::new(&ruta_copia).exists());
+        assert!(!Path::new(&ruta_diario(&ruta)).exists());
+        assert!(!Path::new(&ruta_registro).exists());
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_recuperar_transaccion_confirmada_sobre_varias_tablas() {
+        let directorio =
+            std::env::temp_dir().join("test_recuperar_transaccion_confirmada_sobre_varias_tablas");
+        fs::create_dir_all(&directorio).unwrap();
+        let ruta = |nombre: &str| directorio.join(nombre).to_string_lossy().to_string();
+        let copias = vec![
+            (ruta("personas"), ruta(".1-personas")),
+            (ruta("ciudades"), ruta(".2-ciudades")),
+        ];
+        let ruta_registro = ruta(".3-transaccion");
+        for (tabla, copia) in &copias {
+            fs::write(tabla, "nombre\nAna\n").unwrap();
+            registrar(tabla, &Entrada::Copia(copia.to_string())).unwrap();
+            fs::write(copia, "nombre\nLuis\n").unwrap();
+            registrar(tabla, &Entrada::Transaccion(ruta_registro.to_string())).unwrap();
+        }
+        confirmar(&ruta_registro, &copias).unwrap();
+
+        // El proceso se interrumpe antes de renombrar las copias: recuperar cualquiera de las
+        // tablas la reemplaza, y el registro se conserva hasta recuperar la última.
+        assert!(recuperar(&copias[1].0).unwrap());
+        assert_eq!(fs::read_to_string(&copias[1].0).unwrap(), "nombre\nLuis\n");
+        assert!(Path::new(&ruta_registro).exists());
+        assert!(recuperar(&copias[0].0).unwrap());
+        assert_eq!(fs::read_to_string(&copias[0].0).unwrap(), "nombre\nLuis\n");
+        assert!(!Path::new(&ruta_registro).exists());
+        for (tabla, copia) in &copias {
+            assert!(!Path::new(copia).exists());
+            assert!(!Path::new(&ruta_diario(tabla)).exists());
+        }
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
@@ -1,42 +1,640 @@
-#[derive(Debug, PartialEq)]
+use crate::terminal;
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
 
 /// Enumeración de posibles errores que pueden ocurrir durante la ejecución de las consultas SQL.
 ///
-/// - `InvalidSyntax`: Error de sintaxis en la consulta.
+/// - `InvalidSyntax`: Error de sintaxis en la consulta, por ejemplo porque termina antes de
+///   completar una cláusula.
+/// - `TokenInesperado`: Error de sintaxis en un token concreto de la consulta, con su texto y
+///   la posición, en bytes, en la que empieza.
+/// - `FinInesperado`: La consulta termina antes de completarse; la posición, en bytes, es
+///   la del final de la consulta.
 /// - `InvalidTable`: La tabla especificada no es válida o no existe.
 /// - `InvalidColumn`: La columna especificada no es válida.
-/// - `Error`: Error genérico.
+/// - `ColumnaDesconocida`: La columna no existe en la tabla, con los nombres parecidos de las
+///   columnas que sí existen (ver `Errores::columna_desconocida`).
+/// - `ColumnaNoAgrupada`: Una columna proyectada no está en `GROUP BY` ni dentro de una función
+///   de agregación.
+/// - `PatronInvalido`: Un patrón de búsqueda no es válido o excede los límites de evaluación.
+/// - `TransaccionInvalida`: `BEGIN`, `COMMIT` o `ROLLBACK` no corresponden al estado de la
+///   transacción, con el motivo.
+/// - `TipoInvalido`: Un valor o una comparación no respetan el tipo declarado de una columna, o
+///   una función de fechas recibe un valor que no es una fecha, con el motivo.
+/// - `ArgumentoInvalido`: Los argumentos del programa o los valores de los parámetros de una
+///   sentencia preparada no son válidos, con el motivo.
+/// - `TablaBloqueada`: Otro proceso está modificando la tabla y no la liberó a tiempo (ver
+///   `bloqueo`), con la ruta de la tabla.
+/// - `RegistroMalformado`: Un registro de la tabla no tiene un campo por columna y se lee en
+///   modo estricto (ver `archivo::ModoCampos`), con la línea en la que empieza.
+/// - `ValorInvalido`: Un valor no es válido donde se usa, como una línea del esquema o del
+///   manifiesto que no respeta su sintaxis, un carácter que la codificación de la tabla no
+///   puede representar o un resultado que excede el rango de los enteros o de las fechas, con
+///   el motivo.
+/// - `PermisoDenegado`: No hay permiso para leer o escribir un archivo, con su ruta.
+/// - `HiloFallido`: Un hilo que evaluaba parte de la consulta terminó en pánico, con su
+///   mensaje (ver `Errores::de_panico`).
+/// - `Io`: Falló una operación de entrada/salida, como escribir el resultado, con el
+///   `io::Error` que la produjo.
+/// - `Error`: Error inesperado que no corresponde a ninguno de los anteriores.
+#[derive(Debug)]
 pub enum Errores {
     InvalidSyntax,
-    InvalidTable,
-    InvalidColumn,
+    TokenInesperado {
+        token: String,
+        posicion: usize,
+    },
+    FinInesperado {
+        posicion: usize,
+    },
+    InvalidTable(String),
+    InvalidColumn(String),
+    ColumnaDesconocida {
+        columna: String,
+        sugerencias: Vec<String>,
+    },
+    ColumnaNoAgrupada(String),
+    PatronInvalido(String),
+    TransaccionInvalida(String),
+    TipoInvalido(String),
+    ArgumentoInvalido(String),
+    TablaBloqueada(String),
+    RegistroMalformado {
+        linea: usize,
+        campos: usize,
+        columnas: usize,
+    },
+    ValorInvalido(String),
+    PermisoDenegado(String),
+    HiloFallido(String),
+    Io(io::Error),
     Error,
 }
 
+/// Cantidad máxima de columnas que se sugieren para una columna desconocida.
+const SUGERENCIAS_MAXIMAS: usize = 3;
+
 impl Errores {
-    /// Imprime una descripción del error específico.
+    /// Arma el error de una columna que no existe, sugiriendo las columnas existentes cuyo
+    /// nombre se parece (a una distancia de edición de hasta 2, o un tercio del nombre si es
+    /// largo), de la más parecida a la menos.
+    ///
+    /// # Parámetros
+    /// - `columna`: El nombre de la columna desconocida.
+    /// - `existentes`: Los nombres de las columnas de la tabla.
+    ///
+    /// # Retorno
+    /// `Errores::ColumnaDesconocida` con hasta tres sugerencias.
+    pub fn columna_desconocida<'a>(
+        columna: &str,
+        existentes: impl IntoIterator<Item = &'a String>,
+    ) -> Errores {
+        let largo = columna.chars().count();
+        let maxima = 2.max(largo / 3).min(largo.saturating_sub(1));
+        let mut candidatas: Vec<(usize, &String)> = existentes
+            .into_iter()
+            .map(|existente| (distancia_edicion(columna, existente), existente))
+            .filter(|(distancia, _)| *distancia <= maxima)
+            .collect();
+        candidatas.sort();
+        Errores::ColumnaDesconocida {
+            columna: columna.to_string(),
+            sugerencias: candidatas
+                .into_iter()
+                .take(SUGERENCIAS_MAXIMAS)
+                .map(|(_, existente)| existente.to_string())
+                .collect(),
+        }
+    }
+
+    /// Arma el error de una operación de entrada/salida sobre un archivo, que es
+    /// `Errores::PermisoDenegado` con la ruta si falló por falta de permisos.
     ///
-    /// Esta función proporciona un mensaje descriptivo para cada tipo de error.
+    /// # Parámetros
+    /// - `fuente`: El error de la operación.
+    /// - `ruta`: La ruta del archivo que se quiso leer o escribir.
+    pub fn de_archivo(fuente: io::Error, ruta: &str) -> Errores {
+        match fuente.kind() {
+            io::ErrorKind::PermissionDenied => Errores::PermisoDenegado(ruta.to_string()),
+            _ => Errores::Io(fuente),
+        }
+    }
+
+    /// Arma el error de un hilo que terminó en pánico, con el mensaje del pánico si es un
+    /// texto, como el de `panic!`.
+    ///
+    /// # Parámetros
+    /// - `panico`: El contenido del pánico, como lo devuelve `JoinHandle::join`.
+    pub fn de_panico(panico: Box<dyn Any + Send>) -> Errores {
+        let mensaje = match panico.downcast::<String>() {
+            Ok(mensaje) => *mensaje,
+            Err(panico) => match panico.downcast_ref::<&str>() {
+                Some(mensaje) => mensaje.to_string(),
+                None => "sin mensaje".to_string(),
+            },
+        };
+        Errores::HiloFallido(mensaje)
+    }
+
+    /// Arma el error de una tabla que no se pudo leer: `Errores::PermisoDenegado` si falló por
+    /// falta de permisos, o `Errores::InvalidTable` si no existe o no se puede leer como tabla.
+    ///
+    /// # Parámetros
+    /// - `fuente`: El error de la lectura.
+    /// - `tabla`: El nombre o la ruta de la tabla, que se informa en el error.
+    pub fn tabla_ilegible(fuente: io::Error, tabla: &str) -> Errores {
+        match fuente.kind() {
+            io::ErrorKind::PermissionDenied => Errores::PermisoDenegado(tabla.to_string()),
+            _ => Errores::InvalidTable(tabla.to_string()),
+        }
+    }
+
+    /// Imprime una descripción del error específico en la salida estándar.
+    ///
+    /// Esta función proporciona un mensaje descriptivo para cada tipo de error. Si la salida
+    /// estándar es una terminal, el mensaje se resalta en rojo (ver `terminal::usar_color`).
     ///
     /// # Ejemplo
     /// ```
+    /// # use base_de_datos::errores::Errores;
     /// Errores::InvalidSyntax.imprimir_desc();  // "[INVALID_SYNTAX] : [sintaxis invalida, por favor ingresa correctamente la consulta]"
     /// ```
-
     pub fn imprimir_desc(self) {
+        let mut salida = io::stdout().lock();
+        // Si no se puede escribir en la salida estándar, no hay otro lugar donde informarlo.
+        let _ = if terminal::usar_color(&salida) {
+            writeln!(
+                salida,
+                "{}",
+                terminal::resaltar(&self.to_string(), terminal::ERROR)
+            )
+        } else {
+            self.escribir_desc(&mut salida)
+        };
+    }
+
+    /// Indica si el error es un error de sintaxis de la consulta, con o sin el token que lo
+    /// produjo.
+    pub fn es_de_sintaxis(&self) -> bool {
+        matches!(
+            self,
+            Errores::InvalidSyntax
+                | Errores::TokenInesperado { .. }
+                | Errores::FinInesperado { .. }
+        )
+    }
+
+    /// Código de salida del proceso para el error, distinto de cero y según su tipo:
+    ///
+    /// - `1`: Error en los argumentos (`ArgumentoInvalido`), error interno (`HiloFallido`) o
+    ///   error genérico (`Error`).
+    /// - `2`: Error de sintaxis de la consulta (`InvalidSyntax`, `TokenInesperado`,
+    ///   `FinInesperado`, `PatronInvalido`, `TransaccionInvalida`).
+    /// - `3`: Tabla inválida (`InvalidTable`, `RegistroMalformado`).
+    /// - `4`: Columna inválida (`InvalidColumn`, `ColumnaDesconocida`, `ColumnaNoAgrupada`).
+    /// - `5`: Error de entrada/salida (`Io`).
+    /// - `6`: La tabla está bloqueada por otro proceso (`TablaBloqueada`); se puede reintentar.
+    /// - `7`: Falta permiso para leer o escribir un archivo (`PermisoDenegado`).
+    /// - `8`: Un valor no es del tipo que se espera (`TipoInvalido`).
+    /// - `9`: Un valor no es válido donde se usa, como un resultado fuera de rango
+    ///   (`ValorInvalido`).
+    pub fn codigo_salida(&self) -> u8 {
+        match self {
+            Errores::Error | Errores::ArgumentoInvalido(_) | Errores::HiloFallido(_) => 1,
+            Errores::InvalidSyntax
+            | Errores::TokenInesperado { .. }
+            | Errores::FinInesperado { .. }
+            | Errores::PatronInvalido(_)
+            | Errores::TransaccionInvalida(_) => 2,
+            Errores::InvalidTable(_) | Errores::RegistroMalformado { .. } => 3,
+            Errores::InvalidColumn(_)
+            | Errores::ColumnaDesconocida { .. }
+            | Errores::ColumnaNoAgrupada(_) => 4,
+            Errores::Io(_) => 5,
+            Errores::TablaBloqueada(_) => 6,
+            Errores::PermisoDenegado(_) => 7,
+            Errores::TipoInvalido(_) => 8,
+            Errores::ValorInvalido(_) => 9,
+        }
+    }
+
+    /// Código del tipo de error, como `INVALID_SYNTAX`, con el que comienza su descripción.
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            Errores::InvalidSyntax
+            | Errores::TokenInesperado { .. }
+            | Errores::FinInesperado { .. }
+            | Errores::PatronInvalido(_)
+            | Errores::TransaccionInvalida(_) => "INVALID_SYNTAX",
+            Errores::InvalidTable(_) => "INVALID_TABLE",
+            Errores::TablaBloqueada(_) => "TABLE_LOCKED",
+            Errores::RegistroMalformado { .. } => "MALFORMED_ROW",
+            Errores::ArgumentoInvalido(_) => "INVALID_ARGUMENT",
+            Errores::InvalidColumn(_)
+            | Errores::ColumnaDesconocida { .. }
+            | Errores::ColumnaNoAgrupada(_) => "INVALID_COLUMN",
+            Errores::TipoInvalido(_) => "TYPE_MISMATCH",
+            Errores::ValorInvalido(_) => "INVALID_VALUE",
+            Errores::PermisoDenegado(_) => "PERMISSION_DENIED",
+            Errores::HiloFallido(_) => "WORKER_FAILED",
+            Errores::Io(_) => "IO_ERROR",
+            Errores::Error => "ERROR",
+        }
+    }
+
+    /// Mensaje que explica el error, sin su código.
+    pub fn mensaje(&self) -> String {
         match self {
             Errores::InvalidSyntax => {
-                println!("[INVALID_SYNTAX] : [sintaxis invalida, por favor ingresa correctamente la consulta]")
+                "sintaxis invalida, por favor ingresa correctamente la consulta".to_string()
+            }
+            Errores::TokenInesperado { token, posicion } => format!(
+                "sintaxis invalida, token inesperado '{}' en la posición {}",
+                token, posicion
+            ),
+            Errores::FinInesperado { posicion } => format!(
+                "sintaxis invalida, la consulta termina antes de completarse en la posición {}",
+                posicion
+            ),
+            Errores::InvalidTable(tabla) => {
+                format!("la tabla '{}' es invalida o no existe", tabla)
             }
-            Errores::InvalidTable => {
-                println!("[INVALID_TABLE] : [tabla invalida o no existe]")
+            Errores::InvalidColumn(columna) => format!(
+                "la columna '{}' es invalida, por favor ingrese un campo válido",
+                columna
+            ),
+            Errores::ColumnaDesconocida {
+                columna,
+                sugerencias,
+            } => {
+                let citadas: Vec<String> = sugerencias
+                    .iter()
+                    .map(|sugerencia| format!("'{}'", sugerencia))
+                    .collect();
+                let sugerencia = match citadas.split_last() {
+                    Some((ultima, [])) => format!(", ¿quisiste decir {}?", ultima),
+                    Some((ultima, anteriores)) => {
+                        format!(", ¿quisiste decir {} o {}?", anteriores.join(", "), ultima)
+                    }
+                    None => String::new(),
+                };
+                format!("la columna '{}' no existe{}", columna, sugerencia)
             }
-            Errores::InvalidColumn => {
-                println!("[INVALID_COLUMN] : [columna invalida, por favor ingrese un campo válido]")
+            Errores::ColumnaNoAgrupada(columna) => format!(
+                "la columna '{}' debe aparecer en GROUP BY o dentro de una función de agregación",
+                columna
+            ),
+            Errores::PatronInvalido(motivo) => format!("patrón inválido, {}", motivo),
+            Errores::TransaccionInvalida(motivo) => format!("transacción inválida, {}", motivo),
+            Errores::TipoInvalido(motivo) => format!("tipo inválido, {}", motivo),
+            Errores::ArgumentoInvalido(motivo) => format!("argumento inválido, {}", motivo),
+            Errores::TablaBloqueada(tabla) => format!(
+                "la tabla '{}' está bloqueada por otro proceso que la está modificando",
+                tabla
+            ),
+            Errores::RegistroMalformado {
+                linea,
+                campos,
+                columnas,
+            } => format!(
+                "el registro de la línea {} tiene {} campos y la tabla tiene {} columnas",
+                linea, campos, columnas
+            ),
+            Errores::ValorInvalido(motivo) => format!("valor inválido, {}", motivo),
+            Errores::PermisoDenegado(ruta) => {
+                format!("no hay permiso para leer o escribir '{}'", ruta)
             }
-            Errores::Error => {
-                println!("[ERROR] : [Error, se produjo un error al procesar la consulta]")
+            Errores::HiloFallido(mensaje) => {
+                format!("un hilo de evaluación terminó en pánico, {}", mensaje)
             }
+            Errores::Io(fuente) => format!("error de entrada/salida, {}", fuente),
+            Errores::Error => "Error, se produjo un error al procesar la consulta".to_string(),
         }
     }
+
+    /// Posición, en bytes dentro de la consulta, del token que produjo el error, si se conoce.
+    pub fn posicion(&self) -> Option<usize> {
+        match self {
+            Errores::TokenInesperado { posicion, .. } | Errores::FinInesperado { posicion } => {
+                Some(*posicion)
+            }
+            _ => None,
+        }
+    }
+
+    /// Escribe el error como un objeto JSON de una línea, para que lo interpreten otros
+    /// programas: `{"code": ..., "message": ..., "position": ..., "query": ...}`.
+    ///
+    /// # Parámetros
+    /// - `consulta`: El texto de la consulta que produjo el error, si se llegó a leer.
+    /// - `destino`: Donde se escribe el objeto, seguido de un salto de línea.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error de E/S si no se pudo escribir.
+    pub fn escribir_json(&self, consulta: Option<&str>, destino: &mut dyn Write) -> io::Result<()> {
+        let posicion = match self.posicion() {
+            Some(posicion) => posicion.to_string(),
+            None => "null".to_string(),
+        };
+        let consulta = match consulta {
+            Some(consulta) => texto_json(consulta),
+            None => "null".to_string(),
+        };
+        writeln!(
+            destino,
+            "{{\"code\": {}, \"message\": {}, \"position\": {}, \"query\": {}}}",
+            texto_json(self.codigo()),
+            texto_json(&self.mensaje()),
+            posicion,
+            consulta
+        )
+    }
+
+    /// Escribe una descripción del error específico en el destino indicado.
+    ///
+    /// # Parámetros
+    /// - `destino`: Donde se escribe la descripción, seguida de un salto de línea.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error de E/S si no se pudo escribir.
+    pub fn escribir_desc(&self, destino: &mut dyn Write) -> io::Result<()> {
+        writeln!(destino, "{}", self)
+    }
+}
+
+impl fmt::Display for Errores {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] : [{}]", self.codigo(), self.mensaje())
+    }
+}
+
+/// Arma un string de JSON entre comillas, escapando las comillas, las barras y los caracteres
+/// de control.
+fn texto_json(texto: &str) -> String {
+    let mut json = String::from('"');
+    for caracter in texto.chars() {
+        match caracter {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            control if control.is_control() => {
+                json.push_str(&format!("\\u{:04x}", control as u32));
+            }
+            _ => json.push(caracter),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Calcula la distancia de edición (de Levenshtein) entre dos textos: la cantidad mínima de
+/// caracteres a insertar, borrar o reemplazar para transformar uno en el otro.
+fn distancia_edicion(texto: &str, otro: &str) -> usize {
+    let otro: Vec<char> = otro.chars().collect();
+    let mut anterior: Vec<usize> = (0..=otro.len()).collect();
+    for (i, caracter) in texto.chars().enumerate() {
+        let mut actual = vec![i + 1];
+        for (j, otro_caracter) in otro.iter().enumerate() {
+            let reemplazo = anterior[j] + usize::from(caracter != *otro_caracter);
+            actual.push(reemplazo.min(anterior[j + 1] + 1).min(actual[j] + 1));
+        }
+        anterior = actual;
+    }
+    anterior[otro.len()]
+}
+
+impl Error for Errores {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Errores::Io(fuente) => Some(fuente),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Errores {
+    fn from(fuente: io::Error) -> Errores {
+        Errores::Io(fuente)
+    }
+}
+
+/// Dos errores de E/S se consideran iguales si son del mismo tipo (`io::ErrorKind`), ya que
+/// `io::Error` no se puede comparar.
+impl PartialEq for Errores {
+    fn eq(&self, otro: &Errores) -> bool {
+        match (self, otro) {
+            (Errores::InvalidSyntax, Errores::InvalidSyntax) => true,
+            (
+                Errores::TokenInesperado { token, posicion },
+                Errores::TokenInesperado {
+                    token: otro_token,
+                    posicion: otra_posicion,
+                },
+            ) => token == otro_token && posicion == otra_posicion,
+            (
+                Errores::FinInesperado { posicion },
+                Errores::FinInesperado {
+                    posicion: otra_posicion,
+                },
+            ) => posicion == otra_posicion,
+            (Errores::InvalidTable(tabla), Errores::InvalidTable(otra)) => tabla == otra,
+            (Errores::InvalidColumn(columna), Errores::InvalidColumn(otra)) => columna == otra,
+            (
+                Errores::ColumnaDesconocida {
+                    columna,
+                    sugerencias,
+                },
+                Errores::ColumnaDesconocida {
+                    columna: otra,
+                    sugerencias: otras,
+                },
+            ) => columna == otra && sugerencias == otras,
+            (Errores::ColumnaNoAgrupada(columna), Errores::ColumnaNoAgrupada(otra)) => {
+                columna == otra
+            }
+            (Errores::PatronInvalido(motivo), Errores::PatronInvalido(otro)) => motivo == otro,
+            (Errores::TransaccionInvalida(motivo), Errores::TransaccionInvalida(otro)) => {
+                motivo == otro
+            }
+            (Errores::TipoInvalido(motivo), Errores::TipoInvalido(otro)) => motivo == otro,
+            (Errores::ArgumentoInvalido(motivo), Errores::ArgumentoInvalido(otro)) => {
+                motivo == otro
+            }
+            (Errores::TablaBloqueada(tabla), Errores::TablaBloqueada(otra)) => tabla == otra,
+            (Errores::ValorInvalido(motivo), Errores::ValorInvalido(otro)) => motivo == otro,
+            (Errores::PermisoDenegado(ruta), Errores::PermisoDenegado(otra)) => ruta == otra,
+            (Errores::HiloFallido(mensaje), Errores::HiloFallido(otro)) => mensaje == otro,
+            (
+                Errores::RegistroMalformado {
+                    linea,
+                    campos,
+                    columnas,
+                },
+                Errores::RegistroMalformado {
+                    linea: otra_linea,
+                    campos: otros_campos,
+                    columnas: otras_columnas,
+                },
+            ) => linea == otra_linea && campos == otros_campos && columnas == otras_columnas,
+            (Errores::Io(fuente), Errores::Io(otra)) => fuente.kind() == otra.kind(),
+            (Errores::Error, Errores::Error) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escribir_desc() {
+        let mut destino = Vec::new();
+        Errores::InvalidTable("ventas".to_string())
+            .escribir_desc(&mut destino)
+            .unwrap();
+        Errores::PatronInvalido("termina con \\".to_string())
+            .escribir_desc(&mut destino)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(destino).unwrap(),
+            "[INVALID_TABLE] : [la tabla 'ventas' es invalida o no existe]\n\
+             [INVALID_SYNTAX] : [patrón inválido, termina con \\]\n"
+        );
+    }
+
+    #[test]
+    fn test_error_de_io_conserva_su_origen() {
+        fn escribir() -> Result<(), Errores> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "tubería cerrada"))?;
+            Ok(())
+        }
+        let error = escribir().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "[IO_ERROR] : [error de entrada/salida, tubería cerrada]"
+        );
+        assert_eq!(error.source().unwrap().to_string(), "tubería cerrada");
+        assert_eq!(error, Errores::Io(io::ErrorKind::BrokenPipe.into()));
+    }
+
+    #[test]
+    fn test_escribir_json() {
+        let mut destino = Vec::new();
+        Errores::TokenInesperado {
+            token: "'x".to_string(),
+            posicion: 7,
+        }
+        .escribir_json(Some("SELECT 'x\n\"a\""), &mut destino)
+        .unwrap();
+        Errores::InvalidTable("ventas".to_string())
+            .escribir_json(None, &mut destino)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(destino).unwrap(),
+            "{\"code\": \"INVALID_SYNTAX\", \"message\": \"sintaxis invalida, token inesperado \
+             ''x' en la posición 7\", \"position\": 7, \"query\": \"SELECT 'x\\n\\\"a\\\"\"}\n\
+             {\"code\": \"INVALID_TABLE\", \"message\": \"la tabla 'ventas' es invalida o no \
+             existe\", \"position\": null, \"query\": null}\n"
+        );
+    }
+
+    #[test]
+    fn test_columna_desconocida_sugiere_columnas_parecidas() {
+        let columnas: Vec<String> = ["nombre", "nombres", "edad", "ciudad", "id"]
+            .iter()
+            .map(|columna| columna.to_string())
+            .collect();
+
+        let error = Errores::columna_desconocida("nombr", &columnas);
+        assert_eq!(
+            error.to_string(),
+            "[INVALID_COLUMN] : [la columna 'nombr' no existe, ¿quisiste decir 'nombre' o \
+             'nombres'?]"
+        );
+        assert_eq!(
+            Errores::columna_desconocida("edda", &columnas).mensaje(),
+            "la columna 'edda' no existe, ¿quisiste decir 'edad'?"
+        );
+        assert_eq!(
+            Errores::columna_desconocida("sueldo", &columnas).mensaje(),
+            "la columna 'sueldo' no existe"
+        );
+        assert_eq!(
+            Errores::columna_desconocida("x", &columnas).mensaje(),
+            "la columna 'x' no existe"
+        );
+        assert_eq!(distancia_edicion("ciudad", "cuidad"), 2);
+        assert_eq!(distancia_edicion("", "id"), 2);
+    }
+
+    #[test]
+    fn test_codigo_salida_por_tipo_de_error() {
+        let codigos: Vec<u8> = [
+            Errores::Error,
+            Errores::TokenInesperado {
+                token: "x".to_string(),
+                posicion: 0,
+            },
+            Errores::InvalidTable("ventas".to_string()),
+            Errores::ColumnaNoAgrupada("nombre".to_string()),
+            Errores::TipoInvalido("fecha".to_string()),
+            Errores::Io(io::ErrorKind::NotFound.into()),
+            Errores::TablaBloqueada("ventas".to_string()),
+            Errores::ValorInvalido("fuera de rango".to_string()),
+            Errores::PermisoDenegado("ventas".to_string()),
+            Errores::HiloFallido("sin mensaje".to_string()),
+        ]
+        .iter()
+        .map(Errores::codigo_salida)
+        .collect();
+        assert_eq!(codigos, vec![1, 2, 3, 4, 8, 5, 6, 9, 7, 1]);
+        assert_eq!(
+            Errores::TipoInvalido("'hoy' no es un valor de tipo date".to_string()).to_string(),
+            "[TYPE_MISMATCH] : [tipo inválido, 'hoy' no es un valor de tipo date]"
+        );
+    }
+
+    #[test]
+    fn test_error_de_un_hilo_en_panico() {
+        let fijo = std::thread::spawn(|| panic!("fila corrupta")).join();
+        assert_eq!(
+            Errores::de_panico(fijo.unwrap_err()),
+            Errores::HiloFallido("fila corrupta".to_string())
+        );
+        let armado = std::thread::spawn(|| panic!("fila {}", 3)).join();
+        assert_eq!(
+            Errores::de_panico(armado.unwrap_err()).to_string(),
+            "[WORKER_FAILED] : [un hilo de evaluación terminó en pánico, fila 3]"
+        );
+        assert_eq!(
+            Errores::de_panico(Box::new(7)),
+            Errores::HiloFallido("sin mensaje".to_string())
+        );
+    }
+
+    #[test]
+    fn test_errores_de_archivos_por_falta_de_permisos() {
+        let denegado = || io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            Errores::de_archivo(denegado(), "salida.csv"),
+            Errores::PermisoDenegado("salida.csv".to_string())
+        );
+        assert_eq!(
+            Errores::de_archivo(io::ErrorKind::NotFound.into(), "salida.csv"),
+            Errores::Io(io::ErrorKind::NotFound.into())
+        );
+        assert_eq!(
+            Errores::tabla_ilegible(denegado(), "tablas/ventas").to_string(),
+            "[PERMISSION_DENIED] : [no hay permiso para leer o escribir 'tablas/ventas']"
+        );
+        assert_eq!(
+            Errores::tabla_ilegible(io::ErrorKind::NotFound.into(), "tablas/ventas"),
+            Errores::InvalidTable("tablas/ventas".to_string())
+        );
+    }
 }
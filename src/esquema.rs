@@ -0,0 +1,368 @@
+use crate::cache::CacheArchivos;
+use crate::errores;
+use crate::fecha::fecha_actual;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Extensión del archivo que acompaña a una tabla con la definición de sus columnas.
+const EXTENSION_ESQUEMA: &str = ".esquema";
+
+/// Esquemas ya cargados, por la ruta de su archivo (ver `Esquema::cargar`).
+static ESQUEMAS: OnceLock<CacheArchivos<Esquema>> = OnceLock::new();
+
+/// Valor que se asigna a una columna cuando un `INSERT` la omite.
+///
+/// - `Constante`: Un valor literal fijo.
+/// - `FechaActual`: La fecha del momento de la inserción (`CURRENT_DATE`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValorPorDefecto {
+    Constante(String),
+    FechaActual,
+}
+
+impl ValorPorDefecto {
+    /// Calcula el valor concreto que se debe escribir en la tabla.
+    ///
+    /// # Retorno
+    /// Una cadena de texto (`String`) con el valor a insertar.
+    pub fn evaluar(&self) -> String {
+        match self {
+            ValorPorDefecto::Constante(valor) => valor.to_string(),
+            ValorPorDefecto::FechaActual => fecha_actual(),
+        }
+    }
+}
+
+/// Tipo declarado de una columna, que indica cómo se comparan sus valores.
+///
+/// - `Texto`: Los valores se comparan como texto, aunque parezcan números (`TEXT`).
+/// - `Entero`: Los valores son números enteros y se comparan numéricamente (`INTEGER`).
+/// - `Fecha`: Los valores son fechas `YYYY-MM-DD` y se comparan cronológicamente (`DATE`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TipoColumna {
+    Texto,
+    Entero,
+    Fecha,
+}
+
+impl TipoColumna {
+    /// Interpreta el nombre de un tipo en el archivo de esquema, sin distinguir mayúsculas.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre del tipo, como `INTEGER`.
+    ///
+    /// # Retorno
+    /// El `TipoColumna`, o `None` si el nombre no es un tipo conocido.
+    pub fn desde_nombre(nombre: &str) -> Option<TipoColumna> {
+        match nombre.to_lowercase().as_str() {
+            "text" | "varchar" | "texto" => Some(TipoColumna::Texto),
+            "integer" | "int" | "entero" => Some(TipoColumna::Entero),
+            "date" | "fecha" => Some(TipoColumna::Fecha),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TipoColumna {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TipoColumna::Texto => write!(f, "TEXT"),
+            TipoColumna::Entero => write!(f, "INTEGER"),
+            TipoColumna::Fecha => write!(f, "DATE"),
+        }
+    }
+}
+
+/// Definición de una columna declarada en el archivo de esquema.
+///
+/// # Campos
+///
+/// - `nombre`: El nombre de la columna, en minúsculas.
+/// - `tipo`: El tipo declarado de la columna, si se declaró.
+/// - `valor_por_defecto`: El valor a usar cuando la columna se omite en un `INSERT`, si existe.
+/// - `nulable`: Indica si la columna admite quedar vacía cuando un `INSERT` la omite.
+/// - `clave_primaria`: Indica si la columna forma parte de la clave primaria de la tabla.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DefinicionColumna {
+    pub nombre: String,
+    pub tipo: Option<TipoColumna>,
+    pub valor_por_defecto: Option<ValorPorDefecto>,
+    pub nulable: bool,
+    pub clave_primaria: bool,
+}
+
+/// Esquema opcional de una tabla, leído del archivo `<tabla>.esquema`.
+///
+/// Cada línea del archivo declara una columna con la sintaxis
+/// `columna [tipo] [NULL | NOT NULL] [PRIMARY KEY] [DEFAULT valor]`, donde `tipo` es `TEXT`,
+/// `INTEGER` o `DATE` (ver `TipoColumna`) y `valor` puede ser un literal (entre comillas
+/// simples o no) o `CURRENT_DATE`. Las columnas sin tipo se comparan según su contenido y son
+/// nulables salvo que se declaren `NOT NULL` o `PRIMARY KEY`. Si varias columnas se declaran
+/// `PRIMARY KEY`, la clave primaria es la combinación de todas; sus repeticiones se detectan
+/// con `CHECK TABLE` (ver `revision`). Las líneas vacías y las que comienzan con `--` se
+/// ignoran.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Esquema {
+    pub columnas: Vec<DefinicionColumna>,
+}
+
+impl Esquema {
+    /// Carga el esquema asociado a la tabla ubicada en `ruta_tabla`.
+    ///
+    /// Si la tabla no tiene archivo de esquema se devuelve un esquema vacío. Los esquemas se
+    /// guardan en una caché y el archivo solo se vuelve a leer cuando cambia (ver
+    /// `CacheArchivos`).
+    ///
+    /// # Parámetros
+    /// - `ruta_tabla`: La ruta del archivo de la tabla.
+    ///
+    /// # Retorno
+    /// Retorna el `Esquema` leído o `Errores::ValorInvalido` si el archivo existe pero no es
+    /// válido.
+    pub fn cargar(ruta_tabla: &str) -> Result<Esquema, errores::Errores> {
+        let ruta_esquema = format!("{}{}", ruta_tabla, EXTENSION_ESQUEMA);
+        let cache = ESQUEMAS.get_or_init(CacheArchivos::new);
+        cache.obtener(&ruta_esquema, || match fs::read_to_string(&ruta_esquema) {
+            Ok(contenido) => Esquema::parsear(&contenido),
+            Err(_) => Ok(Esquema::default()),
+        })
+    }
+
+    /// Parsea el contenido de un archivo de esquema.
+    ///
+    /// # Parámetros
+    /// - `contenido`: El texto completo del archivo de esquema.
+    ///
+    /// # Retorno
+    /// Retorna el `Esquema` o `Errores::ValorInvalido` si alguna línea no respeta la sintaxis.
+    pub fn parsear(contenido: &str) -> Result<Esquema, errores::Errores> {
+        let mut columnas = Vec::new();
+        for linea in contenido.lines() {
+            let linea = linea.trim();
+            if linea.is_empty() || linea.starts_with("--") {
+                continue;
+            }
+            columnas.push(Self::parsear_columna(linea)?);
+        }
+        Ok(Esquema { columnas })
+    }
+
+    /// Busca la definición de una columna por su nombre.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre de la columna en minúsculas.
+    ///
+    /// # Retorno
+    /// La definición de la columna si fue declarada en el esquema.
+    pub fn columna(&self, nombre: &str) -> Option<&DefinicionColumna> {
+        self.columnas
+            .iter()
+            .find(|columna| columna.nombre == nombre)
+    }
+
+    /// Reúne los tipos declarados de las columnas.
+    ///
+    /// # Retorno
+    /// Un mapa del nombre de cada columna con tipo declarado a su `TipoColumna`.
+    pub fn tipos(&self) -> HashMap<String, TipoColumna> {
+        self.columnas
+            .iter()
+            .filter_map(|columna| Some((columna.nombre.to_string(), columna.tipo?)))
+            .collect()
+    }
+
+    /// Reúne las columnas de la clave primaria, en el orden en que se declararon.
+    ///
+    /// # Retorno
+    /// Los nombres de las columnas declaradas `PRIMARY KEY`; vacío si la tabla no tiene clave
+    /// primaria.
+    pub fn clave_primaria(&self) -> Vec<String> {
+        self.columnas
+            .iter()
+            .filter(|columna| columna.clave_primaria)
+            .map(|columna| columna.nombre.to_string())
+            .collect()
+    }
+
+    fn parsear_columna(linea: &str) -> Result<DefinicionColumna, errores::Errores> {
+        let linea_invalida = || {
+            errores::Errores::ValorInvalido(format!(
+                "la línea '{}' del esquema no es una definición de columna válida",
+                linea
+            ))
+        };
+        let tokens = Self::dividir_tokens(linea);
+        let mut valor_por_defecto = None;
+        let mut nulable = true;
+        let mut clave_primaria = false;
+        let tipo = tokens
+            .get(1)
+            .and_then(|token| TipoColumna::desde_nombre(token));
+        let mut index = if tipo.is_some() { 2 } else { 1 };
+        while index < tokens.len() {
+            let token = tokens[index].to_lowercase();
+            match token.as_str() {
+                "null" => nulable = true,
+                "not" if tokens.get(index + 1).map(|t| t.to_lowercase()) == Some("null".into()) => {
+                    nulable = false;
+                    index += 1;
+                }
+                "primary"
+                    if tokens.get(index + 1).map(|t| t.to_lowercase()) == Some("key".into()) =>
+                {
+                    clave_primaria = true;
+                    nulable = false;
+                    index += 1;
+                }
+                "default" => match tokens.get(index + 1) {
+                    Some(valor) => {
+                        valor_por_defecto = Some(Self::parsear_valor_por_defecto(valor));
+                        index += 1;
+                    }
+                    None => return Err(linea_invalida()),
+                },
+                _ => return Err(linea_invalida()),
+            }
+            index += 1;
+        }
+        Ok(DefinicionColumna {
+            nombre: tokens[0].to_lowercase(),
+            tipo,
+            valor_por_defecto,
+            nulable,
+            clave_primaria,
+        })
+    }
+
+    /// Divide una línea del esquema en palabras, manteniendo juntos los literales entre
+    /// comillas simples aunque contengan espacios.
+    fn dividir_tokens(linea: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut token = String::new();
+        let mut entre_comillas = false;
+        for caracter in linea.chars() {
+            if caracter == '\'' {
+                entre_comillas = !entre_comillas;
+                token.push(caracter);
+            } else if caracter.is_whitespace() && !entre_comillas {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            } else {
+                token.push(caracter);
+            }
+        }
+        if !token.is_empty() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    fn parsear_valor_por_defecto(valor: &str) -> ValorPorDefecto {
+        if valor.eq_ignore_ascii_case("current_date") {
+            return ValorPorDefecto::FechaActual;
+        }
+        let sin_comillas = valor
+            .strip_prefix('\'')
+            .and_then(|valor| valor.strip_suffix('\''))
+            .unwrap_or(valor);
+        ValorPorDefecto::Constante(sin_comillas.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsear_esquema_con_valores_por_defecto() {
+        let contenido = "-- columnas de personas\nnombre\nEdad DEFAULT 18\n\nciudad default 'Buenos Aires'\nalta DEFAULT CURRENT_DATE\n";
+        let esquema = Esquema::parsear(contenido).unwrap();
+
+        assert_eq!(esquema.columnas.len(), 4);
+        assert_eq!(esquema.columna("nombre").unwrap().valor_por_defecto, None);
+        assert_eq!(
+            esquema.columna("edad").unwrap().valor_por_defecto,
+            Some(ValorPorDefecto::Constante("18".to_string()))
+        );
+        assert_eq!(
+            esquema.columna("ciudad").unwrap().valor_por_defecto,
+            Some(ValorPorDefecto::Constante("Buenos Aires".to_string()))
+        );
+        assert_eq!(
+            esquema.columna("alta").unwrap().valor_por_defecto,
+            Some(ValorPorDefecto::FechaActual)
+        );
+    }
+
+    #[test]
+    fn test_parsear_esquema_con_nulabilidad() {
+        let contenido = "id NOT NULL\nnombre null\nedad NOT NULL DEFAULT 18\nciudad";
+        let esquema = Esquema::parsear(contenido).unwrap();
+
+        assert!(!esquema.columna("id").unwrap().nulable);
+        assert!(esquema.columna("nombre").unwrap().nulable);
+        assert!(!esquema.columna("edad").unwrap().nulable);
+        assert!(esquema.columna("ciudad").unwrap().nulable);
+    }
+
+    #[test]
+    fn test_parsear_esquema_con_tipos() {
+        let contenido = "id INTEGER NOT NULL\nnombre text\nalta DATE DEFAULT CURRENT_DATE\nciudad";
+        let esquema = Esquema::parsear(contenido).unwrap();
+
+        assert_eq!(
+            esquema.columna("id").unwrap().tipo,
+            Some(TipoColumna::Entero)
+        );
+        assert!(!esquema.columna("id").unwrap().nulable);
+        assert_eq!(
+            esquema.columna("alta").unwrap().valor_por_defecto,
+            Some(ValorPorDefecto::FechaActual)
+        );
+        assert_eq!(esquema.columna("ciudad").unwrap().tipo, None);
+        assert_eq!(
+            esquema.tipos(),
+            HashMap::from([
+                ("id".to_string(), TipoColumna::Entero),
+                ("nombre".to_string(), TipoColumna::Texto),
+                ("alta".to_string(), TipoColumna::Fecha),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parsear_esquema_con_clave_primaria() {
+        let contenido = "pais TEXT PRIMARY KEY\nid INTEGER primary key DEFAULT 1\nnombre";
+        let esquema = Esquema::parsear(contenido).unwrap();
+
+        assert!(esquema.columna("pais").unwrap().clave_primaria);
+        assert!(!esquema.columna("pais").unwrap().nulable);
+        assert!(esquema.columna("id").unwrap().clave_primaria);
+        assert!(!esquema.columna("nombre").unwrap().clave_primaria);
+        assert_eq!(esquema.clave_primaria(), vec!["pais", "id"]);
+        assert!(Esquema::default().clave_primaria().is_empty());
+    }
+
+    #[test]
+    fn test_parsear_esquema_invalido() {
+        let invalida = |linea: &str| {
+            Err(errores::Errores::ValorInvalido(format!(
+                "la línea '{}' del esquema no es una definición de columna válida",
+                linea
+            )))
+        };
+        assert_eq!(Esquema::parsear("edad UNIQUE"), invalida("edad UNIQUE"));
+        assert_eq!(Esquema::parsear("edad PRIMARY"), invalida("edad PRIMARY"));
+        assert_eq!(Esquema::parsear("edad DEFAULT"), invalida("edad DEFAULT"));
+        assert_eq!(Esquema::parsear("edad NOT"), invalida("edad NOT"));
+    }
+
+    #[test]
+    fn test_cargar_tabla_sin_esquema() {
+        let esquema = Esquema::cargar("tablas/no_existe").unwrap();
+        assert!(esquema.columnas.is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+use crate::almacenamiento::Almacenamiento;
+use crate::archivo::{
+    formatear_registro, leer_encabezado, leer_registro, leer_registro_en, parsear_registro,
+    OpcionesArchivo,
+};
+use crate::cache::CacheArchivos;
+use crate::consulta::{MetodosConsulta, Resumen};
+use crate::errores;
+use crate::expresion::Valor;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorResultados;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Cursor;
+use std::sync::{Arc, OnceLock};
+
+/// Extensión del archivo que acompaña a una tabla con las estadísticas calculadas por
+/// `ANALYZE`.
+pub const EXTENSION_ESTADISTICAS: &str = ".estadisticas";
+
+/// Columnas del archivo de estadísticas y del resultado de `ANALYZE`.
+const COLUMNAS_ESTADISTICAS: [&str; 6] =
+    ["columna", "filas", "distintos", "nulos", "minimo", "maximo"];
+
+/// Estadísticas ya cargadas, por la ruta de su archivo (ver `Estadisticas::cargar`).
+static ESTADISTICAS: OnceLock<CacheArchivos<Option<Estadisticas>>> = OnceLock::new();
+
+/// Estadísticas de una columna de la tabla.
+///
+/// - `nombre`: El nombre de la columna, en minúsculas.
+/// - `distintos`: La cantidad de valores distintos, sin contar `NULL`.
+/// - `nulos`: La cantidad de registros en los que la columna es `NULL`.
+/// - `minimo` y `maximo`: El menor y el mayor valor, comparados como en `ORDER BY`, o `None`
+///   si todos los valores son `NULL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstadisticasColumna {
+    pub nombre: String,
+    pub distintos: usize,
+    pub nulos: usize,
+    pub minimo: Option<String>,
+    pub maximo: Option<String>,
+}
+
+/// Estadísticas de una tabla, calculadas por `ANALYZE` y guardadas junto a la tabla en un
+/// archivo CSV con la extensión `EXTENSION_ESTADISTICAS`, que tiene una fila por columna con
+/// las columnas `COLUMNAS_ESTADISTICAS` y se puede consultar como cualquier otra tabla.
+///
+/// Las estadísticas no se actualizan al modificar la tabla, así que solo se usan para
+/// estimaciones que no cambian el resultado de las consultas, como el orden en que se evalúan
+/// las condiciones del `WHERE` (ver `planificador::planificar`).
+///
+/// - `filas`: La cantidad de registros de la tabla.
+/// - `columnas`: Las estadísticas de cada columna, en el orden de la tabla.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Estadisticas {
+    pub filas: usize,
+    pub columnas: Vec<EstadisticasColumna>,
+}
+
+impl Estadisticas {
+    /// Calcula las estadísticas de una tabla a partir de sus registros.
+    ///
+    /// # Parámetros
+    /// - `columnas`: Los nombres de las columnas de la tabla.
+    /// - `registros`: Los campos de cada registro; un campo que falta cuenta como `NULL`.
+    ///
+    /// # Retorno
+    /// Las `Estadisticas` de la tabla.
+    pub fn calcular(
+        columnas: &[String],
+        registros: impl IntoIterator<Item = Vec<Option<String>>>,
+    ) -> Estadisticas {
+        let mut filas = 0;
+        let mut distintos: Vec<HashSet<String>> = vec![HashSet::new(); columnas.len()];
+        let mut nulos = vec![0; columnas.len()];
+        let mut extremos: Vec<Option<(Valor, Valor)>> = vec![None; columnas.len()];
+        for registro in registros {
+            filas += 1;
+            for (indice, extremo) in extremos.iter_mut().enumerate() {
+                let Some(Some(campo)) = registro.get(indice) else {
+                    nulos[indice] += 1;
+                    continue;
+                };
+                if !distintos[indice].insert(campo.to_string()) {
+                    continue;
+                }
+                let valor = Valor::desde_campo(Some(campo));
+                match extremo {
+                    Some((minimo, maximo)) => {
+                        if valor.comparar_para_ordenar(minimo) == Ordering::Less {
+                            *minimo = valor;
+                        } else if valor.comparar_para_ordenar(maximo) == Ordering::Greater {
+                            *maximo = valor;
+                        }
+                    }
+                    None => *extremo = Some((valor.clone(), valor)),
+                }
+            }
+        }
+        let columnas = columnas
+            .iter()
+            .zip(distintos.iter().zip(nulos).zip(extremos))
+            .map(
+                |(nombre, ((distintos, nulos), extremos))| EstadisticasColumna {
+                    nombre: nombre.to_string(),
+                    distintos: distintos.len(),
+                    nulos,
+                    minimo: extremos.as_ref().and_then(|(minimo, _)| minimo.a_campo()),
+                    maximo: extremos.as_ref().and_then(|(_, maximo)| maximo.a_campo()),
+                },
+            )
+            .collect();
+        Estadisticas { filas, columnas }
+    }
+
+    /// Devuelve las estadísticas de una columna.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre de la columna, en minúsculas.
+    pub fn columna(&self, nombre: &str) -> Option<&EstadisticasColumna> {
+        self.columnas
+            .iter()
+            .find(|columna| columna.nombre == nombre)
+    }
+
+    /// Devuelve las filas de las estadísticas, una por columna, con los valores de
+    /// `COLUMNAS_ESTADISTICAS`.
+    pub fn filas(&self) -> Vec<Vec<Valor>> {
+        self.columnas
+            .iter()
+            .map(|columna| {
+                let texto = |valor: &Option<String>| {
+                    valor
+                        .as_ref()
+                        .map_or(Valor::Nulo, |valor| Valor::Texto(valor.to_string()))
+                };
+                vec![
+                    Valor::Texto(columna.nombre.to_string()),
+                    Valor::Entero(self.filas as i128),
+                    Valor::Entero(columna.distintos as i128),
+                    Valor::Entero(columna.nulos as i128),
+                    texto(&columna.minimo),
+                    texto(&columna.maximo),
+                ]
+            })
+            .collect()
+    }
+
+    /// Convierte las estadísticas en el contenido CSV de su archivo.
+    pub fn a_csv(&self) -> String {
+        let mut contenido = COLUMNAS_ESTADISTICAS.join(",");
+        contenido.push('\n');
+        for fila in self.filas() {
+            let campos: Vec<Option<String>> = fila.iter().map(Valor::a_campo).collect();
+            contenido.push_str(&formatear_registro(&campos, ',', None));
+            contenido.push('\n');
+        }
+        contenido
+    }
+
+    /// Interpreta el contenido CSV de un archivo de estadísticas (ver `a_csv`).
+    ///
+    /// # Retorno
+    /// Las `Estadisticas`, o `Errores::ValorInvalido` si el contenido no tiene el formato
+    /// esperado.
+    pub fn desde_csv(contenido: &str) -> Result<Estadisticas, errores::Errores> {
+        let invalido = |motivo: &str| {
+            errores::Errores::ValorInvalido(format!("el archivo de estadísticas {}", motivo))
+        };
+        let mut lector = Cursor::new(contenido.as_bytes());
+        let encabezado = leer_registro(&mut lector)?.unwrap_or_default();
+        if encabezado.trim_end() != COLUMNAS_ESTADISTICAS.join(",") {
+            return Err(invalido("no tiene el encabezado esperado"));
+        }
+        let mut estadisticas = Estadisticas::default();
+        let mut texto = String::new();
+        while leer_registro_en(&mut lector, &mut texto)? {
+            let campos = parsear_registro(&texto, ',', None);
+            let [Some(nombre), Some(filas), Some(distintos), Some(nulos), minimo, maximo] =
+                <[Option<String>; 6]>::try_from(campos)
+                    .map_err(|_| invalido(&format!("tiene un registro mal formado: {}", texto)))?
+            else {
+                return Err(invalido(&format!(
+                    "tiene un registro incompleto: {}",
+                    texto
+                )));
+            };
+            let numero = |numero: &str| {
+                numero
+                    .parse::<usize>()
+                    .map_err(|_| invalido(&format!("tiene una cantidad inválida: {}", numero)))
+            };
+            estadisticas.filas = numero(&filas)?;
+            estadisticas.columnas.push(EstadisticasColumna {
+                nombre,
+                distintos: numero(&distintos)?,
+                nulos: numero(&nulos)?,
+                minimo,
+                maximo,
+            });
+        }
+        Ok(estadisticas)
+    }
+
+    /// Carga las estadísticas de la tabla ubicada en `ruta_tabla`. Las estadísticas se
+    /// guardan en una caché y el archivo solo se vuelve a leer cuando cambia (ver
+    /// `CacheArchivos`).
+    ///
+    /// # Retorno
+    /// Las `Estadisticas`, `None` si la tabla nunca se analizó, o `Errores::ValorInvalido` si
+    /// el archivo existe pero no es válido.
+    pub fn cargar(ruta_tabla: &str) -> Result<Option<Estadisticas>, errores::Errores> {
+        let ruta = format!("{}{}", ruta_tabla, EXTENSION_ESTADISTICAS);
+        let cache = ESTADISTICAS.get_or_init(CacheArchivos::new);
+        cache.obtener(&ruta, || match fs::read_to_string(&ruta) {
+            Ok(contenido) => Estadisticas::desde_csv(&contenido).map(Some),
+            Err(_) => Ok(None),
+        })
+    }
+
+    /// Guarda las estadísticas de la tabla ubicada en `ruta_tabla`, reemplazando las
+    /// anteriores.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error de E/S si no se pudo escribir el archivo.
+    pub fn guardar(&self, ruta_tabla: &str) -> Result<(), errores::Errores> {
+        let ruta = format!("{}{}", ruta_tabla, EXTENSION_ESTADISTICAS);
+        fs::write(ruta, self.a_csv())?;
+        Ok(())
+    }
+}
+
+/// Representa una sentencia `ANALYZE`, que recorre la tabla, calcula sus `Estadisticas` y las
+/// guarda (ver `Almacenamiento::guardar_estadisticas`). El resultado tiene una fila por
+/// columna con sus estadísticas.
+///
+/// # Campos
+///
+/// - `tabla`: El nombre de la tabla.
+/// - `ruta_tabla`: La ruta de la tabla en el almacenamiento.
+/// - `almacenamiento`: El `Almacenamiento` en el que está guardada la tabla.
+/// - `opciones`: Las `OpcionesArchivo` con las que se lee el archivo de la tabla.
+#[derive(Debug)]
+pub struct ConsultaAnalisis {
+    pub tabla: String,
+    pub ruta_tabla: String,
+    pub almacenamiento: Arc<dyn Almacenamiento>,
+    pub opciones: OpcionesArchivo,
+}
+
+impl ConsultaAnalisis {
+    /// Crea el análisis a partir del nombre de la tabla de la sentencia ya parseada.
+    ///
+    /// # Parámetros
+    /// - `tabla`: El nombre de la tabla.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla.
+    /// - `opciones`: Las opciones con las que se lee el archivo de la tabla.
+    ///
+    /// # Retorno
+    /// La `ConsultaAnalisis`, o `Errores::InvalidTable` si la tabla no se puede resolver.
+    pub fn desde_sentencia(
+        tabla: String,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaAnalisis, errores::Errores> {
+        let ruta_tabla = resolvedor.resolver(&tabla)?;
+        registro::registrar(registro::DETALLE, || {
+            format!("tabla '{}' en '{}'", tabla, ruta_tabla)
+        });
+        Ok(ConsultaAnalisis {
+            tabla,
+            ruta_tabla,
+            almacenamiento: resolvedor.almacenamiento(),
+            opciones: opciones.clone(),
+        })
+    }
+}
+
+impl MetodosConsulta for ConsultaAnalisis {
+    /// Verifica que la tabla exista.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o `Errores::InvalidTable` si la tabla no se puede leer.
+    fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
+        if let Err(error) = self.almacenamiento.leer(&self.ruta_tabla, &self.opciones) {
+            return Err(errores::Errores::tabla_ilegible(error, &self.ruta_tabla));
+        }
+        Ok(())
+    }
+
+    /// Calcula las estadísticas de la tabla, las guarda y escribe una fila por columna.
+    ///
+    /// # Retorno
+    /// El `Resumen` con la cantidad de columnas, o el error que impidió leer la tabla o guardar
+    /// sus estadísticas.
+    fn procesar(
+        &mut self,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let mut lector = self.almacenamiento.leer(&self.ruta_tabla, &self.opciones)?;
+        let columnas = leer_encabezado(lector.as_mut(), &mut self.opciones)?;
+        let delimitador = self.opciones.delimitador();
+        let mut registros = Vec::new();
+        let mut texto = String::new();
+        while leer_registro_en(lector.as_mut(), &mut texto)? {
+            registros.push(parsear_registro(&texto, delimitador, self.opciones.nulo()));
+        }
+        let estadisticas = Estadisticas::calcular(&columnas, registros);
+        self.almacenamiento
+            .guardar_estadisticas(&self.ruta_tabla, &estadisticas)?;
+        registro::registrar(registro::DETALLE, || {
+            format!(
+                "tabla '{}' analizada: {} filas",
+                self.tabla, estadisticas.filas
+            )
+        });
+
+        escritor.escribir_encabezado(&COLUMNAS_ESTADISTICAS.map(String::from))?;
+        let filas = estadisticas.filas();
+        for fila in &filas {
+            escritor.escribir_fila(fila)?;
+        }
+        Ok(Resumen::Filas(filas.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use crate::consulta::SQLConsulta;
+    use crate::resolvedor::ResolvedorDirectorio;
+    use crate::salida::OpcionesSalida;
+
+    fn ejecutar(resolvedor: &dyn ResolvedorTablas, consulta: &str) -> String {
+        let mut destino = Vec::new();
+        let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+        SQLConsulta::crear_consulta(consulta, resolvedor, &OpcionesArchivo::default())
+            .unwrap()
+            .procesar_consulta(escritor.as_mut())
+            .unwrap();
+        drop(escritor);
+        String::from_utf8(destino).unwrap()
+    }
+
+    #[test]
+    fn test_analizar_tabla() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla(
+            "personas",
+            "nombre,edad,ciudad\nAna,40,Lugo\nLuis,9,Vigo\nEva,33,Lugo\nJuan,,\"Vigo, centro\"\n",
+        );
+        let resultado = "columna,filas,distintos,nulos,minimo,maximo\n\
+                         nombre,4,4,0,Ana,Luis\n\
+                         edad,4,3,1,9,40\n\
+                         ciudad,4,3,0,Lugo,\"Vigo, centro\"\n";
+        assert_eq!(ejecutar(&tablas, "ANALYZE personas"), resultado);
+
+        let estadisticas = tablas.estadisticas("personas").unwrap().unwrap();
+        assert_eq!(estadisticas.filas, 4);
+        assert_eq!(estadisticas.columna("edad").unwrap().nulos, 1);
+        assert_eq!(
+            Estadisticas::desde_csv(&estadisticas.a_csv()).unwrap(),
+            estadisticas
+        );
+        assert_eq!(
+            ejecutar(
+                &tablas,
+                "SELECT * FROM personas.estadisticas WHERE columna = 'edad'"
+            ),
+            "columna,filas,distintos,nulos,minimo,maximo\nedad,4,3,1,9,40\n"
+        );
+        assert!(Estadisticas::desde_csv("columna,filas\nedad,4\n").is_err());
+    }
+
+    #[test]
+    fn test_analizar_tabla_en_archivo() {
+        let directorio = std::env::temp_dir().join("test_analizar_tabla_en_archivo");
+        fs::create_dir_all(&directorio).unwrap();
+        let tabla = directorio.join("ventas.csv");
+        fs::write(&tabla, "id,monto\n1,10\n2,\n3,7\n").unwrap();
+        let resolvedor = ResolvedorDirectorio::new(&directorio.to_string_lossy());
+        let ruta = tabla.to_string_lossy();
+        let _ = fs::remove_file(directorio.join("ventas.csv.estadisticas"));
+
+        assert_eq!(Estadisticas::cargar(&ruta).unwrap(), None);
+        ejecutar(&resolvedor, "ANALYZE TABLE ventas");
+        assert_eq!(
+            fs::read_to_string(directorio.join("ventas.csv.estadisticas")).unwrap(),
+            "columna,filas,distintos,nulos,minimo,maximo\nid,3,3,0,1,3\nmonto,3,2,1,7,10\n"
+        );
+        let estadisticas = Estadisticas::cargar(&ruta).unwrap().unwrap();
+        assert_eq!(estadisticas.columna("monto").unwrap().distintos, 2);
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
@@ -0,0 +1,2790 @@
+use crate::aleatorio;
+use crate::errores;
+use crate::esquema::TipoColumna;
+use crate::fecha::{
+    civil_desde_dias, dias_desde_civil, es_fecha_valida, fecha_actual, fecha_hora_actual,
+    parsear_fecha,
+};
+use crate::lexico::{error_de_sintaxis, es_numero, TipoToken, Token};
+use crate::patron::{plegar_mayusculas, Patron};
+use crate::sintaxis::{self, CriterioOrden, SentenciaSelect};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Valor que resulta de evaluar una expresión sobre un registro.
+///
+/// - `Texto`: Una cadena de texto.
+/// - `Entero`: Un número entero, de hasta 38 dígitos.
+/// - `Decimal`: Un número con parte decimal, como el literal `3.14`.
+/// - `Booleano`: El resultado de una comparación o de un operador lógico.
+/// - `Nulo`: La ausencia de valor (`NULL`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Valor {
+    Texto(String),
+    Entero(i128),
+    Decimal(f64),
+    Booleano(bool),
+    Nulo,
+}
+
+impl Valor {
+    /// Interpreta el contenido de un campo de la tabla, que es entero si se puede leer como tal.
+    ///
+    /// # Parámetros
+    /// - `campo`: El valor del campo tal como está en el archivo, o `None` si es nulo.
+    ///
+    /// # Retorno
+    /// El `Valor` correspondiente.
+    pub fn desde_campo(campo: Option<&str>) -> Valor {
+        let campo = match campo {
+            Some(campo) => campo,
+            None => return Valor::Nulo,
+        };
+        match campo.parse::<i128>() {
+            Ok(numero) => Valor::Entero(numero),
+            Err(_) => Valor::Texto(campo.to_string()),
+        }
+    }
+
+    /// Interpreta el contenido de un campo de una columna con tipo declarado (ver
+    /// `TipoColumna`): las fechas válidas se conservan como texto, que en formato `YYYY-MM-DD`
+    /// se ordena cronológicamente.
+    ///
+    /// # Parámetros
+    /// - `campo`: El valor del campo tal como está en el archivo, o `None` si es nulo.
+    /// - `tipo`: El tipo declarado de la columna.
+    ///
+    /// # Retorno
+    /// El `Valor` correspondiente, o `Errores::TipoInvalido` si el campo no es del tipo.
+    pub fn desde_campo_tipado(
+        campo: Option<&str>,
+        tipo: TipoColumna,
+    ) -> Result<Valor, errores::Errores> {
+        let campo = match campo {
+            Some(campo) => campo,
+            None => return Ok(Valor::Nulo),
+        };
+        match tipo {
+            TipoColumna::Texto => return Ok(Valor::Texto(campo.to_string())),
+            TipoColumna::Entero => {
+                if let Ok(numero) = campo.parse::<i128>() {
+                    return Ok(Valor::Entero(numero));
+                }
+            }
+            TipoColumna::Fecha => {
+                if es_fecha_valida(campo) {
+                    return Ok(Valor::Texto(campo.to_string()));
+                }
+            }
+        }
+        Err(errores::Errores::TipoInvalido(format!(
+            "'{}' no es un valor de tipo {}",
+            campo, tipo
+        )))
+    }
+
+    /// Convierte el valor en el texto que se muestra en los resultados.
+    ///
+    /// # Retorno
+    /// Una cadena de texto (`String`) con el valor; vacía si el valor es nulo.
+    pub fn a_texto(&self) -> String {
+        match self {
+            Valor::Texto(texto) => texto.to_string(),
+            Valor::Entero(numero) => numero.to_string(),
+            Valor::Decimal(numero) => numero.to_string(),
+            Valor::Booleano(valor) => valor.to_string(),
+            Valor::Nulo => String::new(),
+        }
+    }
+
+    /// Convierte el valor en el campo que se escribe en un archivo.
+    ///
+    /// # Retorno
+    /// El texto del valor, o `None` si el valor es nulo.
+    pub fn a_campo(&self) -> Option<String> {
+        match self {
+            Valor::Nulo => None,
+            valor => Some(valor.a_texto()),
+        }
+    }
+
+    /// Compara dos valores para ordenar resultados: como `comparar`, pero los nulos van
+    /// después de cualquier otro valor.
+    ///
+    /// # Parámetros
+    /// - `otro`: El valor con el que se compara.
+    ///
+    /// # Retorno
+    /// El `Ordering` de `self` respecto de `otro`.
+    pub fn comparar_para_ordenar(&self, otro: &Valor) -> Ordering {
+        match (self, otro) {
+            (Valor::Nulo, Valor::Nulo) => Ordering::Equal,
+            (Valor::Nulo, _) => Ordering::Greater,
+            (_, Valor::Nulo) => Ordering::Less,
+            _ => self.comparar(otro),
+        }
+    }
+
+    /// Compara dos valores como `comparar_para_ordenar`, pero compara los textos en orden
+    /// natural: las secuencias de dígitos se comparan por su valor numérico, de modo que
+    /// `item2` queda antes que `item10`.
+    ///
+    /// # Parámetros
+    /// - `otro`: El valor con el que se compara.
+    ///
+    /// # Retorno
+    /// El `Ordering` de `self` respecto de `otro`.
+    pub fn comparar_natural(&self, otro: &Valor) -> Ordering {
+        match (self, otro) {
+            (Valor::Texto(a), Valor::Texto(b)) => comparar_texto_natural(a, b),
+            _ => self.comparar_para_ordenar(otro),
+        }
+    }
+
+    /// Compara dos valores: numéricamente si ambos son números, o si uno es un número y el otro
+    /// un texto con forma de número (como el campo `10.5` comparado con `3`), y como texto en
+    /// otro caso. Los enteros se comparan en forma exacta aunque no entren en 64 bits.
+    fn comparar(&self, otro: &Valor) -> Ordering {
+        match (self, otro) {
+            (Valor::Entero(a), Valor::Entero(b)) => a.cmp(b),
+            (Valor::Texto(_), Valor::Texto(_)) => self.a_texto().cmp(&otro.a_texto()),
+            _ => match (self.a_entero(), otro.a_entero()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => match (self.a_numero(), otro.a_numero()) {
+                    (Some(a), Some(b)) => a.total_cmp(&b),
+                    _ => self.a_texto().cmp(&otro.a_texto()),
+                },
+            },
+        }
+    }
+
+    /// Interpreta el valor como entero, si es un entero o un texto con forma de entero de
+    /// hasta 38 dígitos.
+    fn a_entero(&self) -> Option<i128> {
+        match self {
+            Valor::Entero(numero) => Some(*numero),
+            Valor::Texto(texto) => texto.parse().ok(),
+            _ => None,
+        }
+    }
+
+    /// Interpreta el valor como número, si es un número o un texto con forma de número (ver
+    /// `lexico::es_numero`).
+    fn a_numero(&self) -> Option<f64> {
+        match self {
+            Valor::Entero(numero) => Some(*numero as f64),
+            Valor::Decimal(numero) => Some(*numero),
+            Valor::Texto(texto) if es_numero(texto) => texto.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Operadores binarios que pueden aparecer en una expresión.
+///
+/// `Like` compara un texto con un patrón de `LIKE` (ver `Patron::like`), `ILike` lo hace sin
+/// distinguir mayúsculas de minúsculas (ver `Patron::ilike`) y `Regexp` (`REGEXP` o
+/// `~`) con una expresión regular (ver `Patron::regexp`). `Es` compara sin
+/// propagar los nulos y representa `IS NULL`. `Suma`, `Resta`, `Producto` y `Division` son
+/// los operadores aritméticos (ver `Expresion::calcular`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operador {
+    Igual,
+    Distinto,
+    Menor,
+    Mayor,
+    MenorIgual,
+    MayorIgual,
+    Like,
+    ILike,
+    Regexp,
+    Es,
+    And,
+    Or,
+    Suma,
+    Resta,
+    Producto,
+    Division,
+}
+
+impl Operador {
+    /// Indica si el operador compara el orden o la igualdad de dos valores (`=`, `!=`, `<`,
+    /// `>`, `<=`, `>=`).
+    pub fn es_comparacion(&self) -> bool {
+        matches!(
+            self,
+            Operador::Igual
+                | Operador::Distinto
+                | Operador::Menor
+                | Operador::Mayor
+                | Operador::MenorIgual
+                | Operador::MayorIgual
+        )
+    }
+
+    /// Indica si el operador es aritmético (`+`, `-`, `*`, `/`).
+    pub fn es_aritmetico(&self) -> bool {
+        matches!(
+            self,
+            Operador::Suma | Operador::Resta | Operador::Producto | Operador::Division
+        )
+    }
+}
+
+/// Funciones de agregación, que combinan los valores de todos los registros de un grupo.
+///
+/// `Variance` y `Stddev` son la varianza y el desvío estándar muestrales, que dividen por la
+/// cantidad de valores menos uno.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Agregacion {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    Variance,
+    Stddev,
+}
+
+impl Agregacion {
+    /// Devuelve la función de agregación con el nombre indicado, si existe.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre de la función en minúsculas.
+    fn desde_nombre(nombre: &str) -> Option<Agregacion> {
+        match nombre {
+            "count" => Some(Agregacion::Count),
+            "sum" => Some(Agregacion::Sum),
+            "min" => Some(Agregacion::Min),
+            "max" => Some(Agregacion::Max),
+            "avg" => Some(Agregacion::Avg),
+            "variance" => Some(Agregacion::Variance),
+            "stddev" => Some(Agregacion::Stddev),
+            _ => None,
+        }
+    }
+
+    /// Devuelve el nombre de la función en SQL, en mayúsculas.
+    fn nombre(&self) -> &'static str {
+        match self {
+            Agregacion::Count => "COUNT",
+            Agregacion::Sum => "SUM",
+            Agregacion::Min => "MIN",
+            Agregacion::Max => "MAX",
+            Agregacion::Avg => "AVG",
+            Agregacion::Variance => "VARIANCE",
+            Agregacion::Stddev => "STDDEV",
+        }
+    }
+
+    /// Arma el error de un valor no numérico recibido por `SUM`, `AVG`, `VARIANCE` o
+    /// `STDDEV`.
+    fn valor_no_numerico(&self, valor: &Valor) -> errores::Errores {
+        errores::Errores::TipoInvalido(format!(
+            "{} solo admite números y recibió '{}'",
+            self.nombre(),
+            valor.a_texto()
+        ))
+    }
+
+    /// Combina los valores de un grupo. Los valores nulos no se tienen en cuenta.
+    ///
+    /// # Parámetros
+    /// - `valores`: Los valores del argumento en cada registro del grupo.
+    ///
+    /// # Retorno
+    /// Retorna el `Valor` resultante (nulo si no hay valores para `SUM`, `MIN`, `MAX` y `AVG`,
+    /// o si hay menos de dos para `VARIANCE` y `STDDEV`), `Errores::TipoInvalido` si `SUM`,
+    /// `AVG`, `VARIANCE` o `STDDEV` reciben valores no numéricos, o `Errores::ValorInvalido`
+    /// si el resultado entero de `SUM` o `AVG` excede el rango de los enteros.
+    fn aplicar(&self, valores: Vec<Valor>) -> Result<Valor, errores::Errores> {
+        let valores: Vec<Valor> = valores
+            .into_iter()
+            .filter(|valor| *valor != Valor::Nulo)
+            .collect();
+        if *self == Agregacion::Count {
+            return Ok(Valor::Entero(valores.len() as i128));
+        }
+        if valores.is_empty() {
+            return Ok(Valor::Nulo);
+        }
+        match self {
+            Agregacion::Min => Ok(valores
+                .into_iter()
+                .min_by(|a, b| a.comparar(b))
+                .unwrap_or(Valor::Nulo)),
+            Agregacion::Max => Ok(valores
+                .into_iter()
+                .max_by(|a, b| a.comparar(b))
+                .unwrap_or(Valor::Nulo)),
+            Agregacion::Variance | Agregacion::Stddev => {
+                let mut numeros = Vec::with_capacity(valores.len());
+                for valor in &valores {
+                    match valor {
+                        Valor::Entero(_) | Valor::Decimal(_) => {
+                            numeros.push(valor.a_numero().unwrap_or_default())
+                        }
+                        _ => return Err(self.valor_no_numerico(valor)),
+                    }
+                }
+                if numeros.len() < 2 {
+                    return Ok(Valor::Nulo);
+                }
+                let cantidad = numeros.len() as f64;
+                let media = numeros.iter().sum::<f64>() / cantidad;
+                let varianza = numeros
+                    .iter()
+                    .map(|numero| (numero - media) * (numero - media))
+                    .sum::<f64>()
+                    / (cantidad - 1.0);
+                if *self == Agregacion::Stddev {
+                    Ok(Valor::Decimal(varianza.sqrt()))
+                } else {
+                    Ok(Valor::Decimal(varianza))
+                }
+            }
+            _ if valores
+                .iter()
+                .any(|valor| matches!(valor, Valor::Decimal(_))) =>
+            {
+                let mut suma = 0.0;
+                for valor in &valores {
+                    match valor {
+                        Valor::Entero(_) | Valor::Decimal(_) => {
+                            suma += valor.a_numero().unwrap_or_default()
+                        }
+                        _ => return Err(self.valor_no_numerico(valor)),
+                    }
+                }
+                if *self == Agregacion::Sum {
+                    Ok(Valor::Decimal(suma))
+                } else {
+                    Ok(Valor::Decimal(suma / valores.len() as f64))
+                }
+            }
+            _ => {
+                let mut suma: i128 = 0;
+                for valor in &valores {
+                    let sumado = match valor {
+                        Valor::Entero(numero) => suma.checked_add(*numero),
+                        _ => return Err(self.valor_no_numerico(valor)),
+                    };
+                    suma = sumado.ok_or_else(|| {
+                        errores::Errores::ValorInvalido(format!(
+                            "el resultado de {} excede el rango de los enteros",
+                            self.nombre()
+                        ))
+                    })?;
+                }
+                let cantidad = valores.len() as i128;
+                if *self == Agregacion::Sum {
+                    Ok(Valor::Entero(suma))
+                } else if suma % cantidad == 0 {
+                    Ok(Valor::Entero(suma / cantidad))
+                } else {
+                    Ok(Valor::Decimal(suma as f64 / cantidad as f64))
+                }
+            }
+        }
+    }
+}
+
+/// Funciones de ventana, que dan a cada fila del resultado un valor según su posición entre
+/// las demás filas, ordenadas con el `ORDER BY` de la ventana.
+///
+/// - `RowNumber`: El número de la fila, empezando en 1.
+/// - `Rank`: La posición de la fila; las filas empatadas comparten la posición, y la siguiente
+///   deja un hueco (1, 1, 3).
+/// - `DenseRank`: Como `Rank`, pero sin huecos después de un empate (1, 1, 2).
+#[derive(Debug, Clone, PartialEq)]
+pub enum FuncionVentana {
+    RowNumber,
+    Rank,
+    DenseRank,
+}
+
+impl FuncionVentana {
+    /// Devuelve la función de ventana con el nombre indicado, si existe.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre de la función en minúsculas.
+    fn desde_nombre(nombre: &str) -> Option<FuncionVentana> {
+        match nombre {
+            "row_number" => Some(FuncionVentana::RowNumber),
+            "rank" => Some(FuncionVentana::Rank),
+            "dense_rank" => Some(FuncionVentana::DenseRank),
+            _ => None,
+        }
+    }
+}
+
+/// Llamada a una función de ventana: `RANK() OVER (PARTITION BY ciudad ORDER BY monto DESC)`.
+///
+/// La función se calcula sobre todas las filas del resultado, una vez evaluadas las demás
+/// proyecciones (ver `select`), así que solo puede ser una proyección completa o un criterio
+/// del `ORDER BY` de la consulta. Con `PARTITION BY`, las filas se separan según el valor de
+/// sus expresiones y la función vuelve a empezar en cada partición. Sin `ORDER BY` en la
+/// ventana, todas las filas de una partición empatan.
+///
+/// - `funcion`: La `FuncionVentana` que se calcula.
+/// - `particion`: Las expresiones del `PARTITION BY`, vacío si la ventana no lo tiene.
+/// - `orden`: Los criterios del `ORDER BY` de la ventana.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ventana {
+    pub funcion: FuncionVentana,
+    pub particion: Vec<Expresion>,
+    pub orden: Vec<CriterioOrden>,
+}
+
+impl Ventana {
+    /// Devuelve las expresiones que se evalúan sobre cada fila para calcular la ventana: las
+    /// de su `PARTITION BY` seguidas de las de su `ORDER BY`, en orden.
+    pub fn expresiones(&self) -> impl Iterator<Item = &Expresion> {
+        self.particion
+            .iter()
+            .chain(self.orden.iter().map(|criterio| &criterio.expresion))
+    }
+}
+
+impl fmt::Display for Ventana {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nombre = match self.funcion {
+            FuncionVentana::RowNumber => "ROW_NUMBER",
+            FuncionVentana::Rank => "RANK",
+            FuncionVentana::DenseRank => "DENSE_RANK",
+        };
+        let mut especificacion = Vec::new();
+        if !self.particion.is_empty() {
+            let particion: Vec<String> = self
+                .particion
+                .iter()
+                .map(|expresion| expresion.to_string())
+                .collect();
+            especificacion.push(format!("PARTITION BY {}", particion.join(", ")));
+        }
+        if !self.orden.is_empty() {
+            let orden: Vec<String> = self.orden.iter().map(ToString::to_string).collect();
+            especificacion.push(format!("ORDER BY {}", orden.join(", ")));
+        }
+        write!(f, "{nombre}() OVER ({})", especificacion.join(" "))
+    }
+}
+
+/// Cuantificador de una comparación con una subconsulta.
+///
+/// - `Alguno`: `ANY` o `SOME`, que se cumple si la comparación se cumple con algún valor.
+/// - `Todos`: `ALL`, que se cumple si la comparación se cumple con todos los valores.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cuantificador {
+    Alguno,
+    Todos,
+}
+
+/// Subconsulta de una comparación cuantificada, como `(SELECT precio FROM competencia)`.
+///
+/// La subconsulta no depende del registro de la consulta que la contiene, así que se ejecuta
+/// una única vez antes de recorrer la tabla (ver `Expresion::materializar_subconsultas`).
+///
+/// # Campos
+///
+/// - `sentencia`: La sentencia `SELECT`, que debe tener una única columna.
+/// - `texto`: El texto de la subconsulta, formado por sus tokens separados por espacios.
+/// - `valores`: Los valores de su resultado, una vez ejecutada.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subconsulta {
+    pub sentencia: SentenciaSelect,
+    pub texto: String,
+    pub valores: Option<Vec<Valor>>,
+}
+
+impl Subconsulta {
+    /// Devuelve los valores del resultado de la subconsulta.
+    ///
+    /// # Retorno
+    /// Los valores, o `Errores::InvalidSyntax` si la subconsulta no se ejecutó, como las que
+    /// aparecen fuera de las cláusulas que se materializan.
+    fn valores(&self) -> Result<&[Valor], errores::Errores> {
+        self.valores
+            .as_deref()
+            .ok_or(errores::Errores::InvalidSyntax)
+    }
+}
+
+/// Nombre de la función interna que pliega las mayúsculas de un texto para `COLLATE NOCASE`
+/// (ver `plegar_mayusculas`). No se puede llamar desde una consulta.
+const FUNCION_SIN_MAYUSCULAS: &str = "collate nocase";
+
+/// Expresión evaluable sobre un registro de una tabla.
+///
+/// - `Literal`: Un valor constante escrito en la consulta.
+/// - `Columna`: El valor de una columna del registro.
+/// - `Binaria`: Una comparación, un operador lógico o uno aritmético entre dos expresiones.
+/// - `Not`: La negación de una expresión lógica.
+/// - `Funcion`: La llamada a una función escalar, como `IIF(condicion, valor_si, valor_no)`.
+///   `expresion COLLATE NOCASE` se representa como la función interna `FUNCION_SIN_MAYUSCULAS`
+///   (ver `Expresion::sin_mayusculas`).
+/// - `Agregada`: La llamada a una función de agregación; el argumento es `None` en `COUNT(*)`.
+/// - `Ventana`: La llamada a una función de ventana (ver `Ventana`).
+/// - `Cuantificada`: Una comparación con cada valor de una subconsulta, como
+///   `precio > ALL (SELECT precio FROM competencia)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expresion {
+    Literal(Valor),
+    Columna(String),
+    Binaria(Box<Expresion>, Operador, Box<Expresion>),
+    Not(Box<Expresion>),
+    Funcion(String, Vec<Expresion>),
+    Agregada(Agregacion, Option<Box<Expresion>>),
+    Ventana(Box<Ventana>),
+    Cuantificada(Box<Expresion>, Operador, Cuantificador, Box<Subconsulta>),
+}
+
+impl Expresion {
+    /// Parsea una expresión completa a partir de sus tokens.
+    ///
+    /// # Parámetros
+    /// - `tokens`: Los tokens de la expresión, tal como los devuelve `lexico::analizar`.
+    ///
+    /// # Retorno
+    /// Retorna la `Expresion` o `Errores::InvalidSyntax` si los tokens no forman una expresión
+    /// válida o sobran tokens al final.
+    pub fn parsear(tokens: &[Token]) -> Result<Expresion, errores::Errores> {
+        let mut index = 0;
+        let expresion = Self::parsear_or(tokens, &mut index)?;
+        if index != tokens.len() {
+            return Err(error_de_sintaxis(tokens, index));
+        }
+        Ok(expresion)
+    }
+
+    fn parsear_or(tokens: &[Token], index: &mut usize) -> Result<Expresion, errores::Errores> {
+        let mut expresion = Self::parsear_and(tokens, index)?;
+        while Self::simbolo(tokens, *index) == Some("or") {
+            *index += 1;
+            let derecha = Self::parsear_and(tokens, index)?;
+            expresion = Expresion::Binaria(Box::new(expresion), Operador::Or, Box::new(derecha));
+        }
+        Ok(expresion)
+    }
+
+    fn parsear_and(tokens: &[Token], index: &mut usize) -> Result<Expresion, errores::Errores> {
+        let mut expresion = Self::parsear_not(tokens, index)?;
+        while Self::simbolo(tokens, *index) == Some("and") {
+            *index += 1;
+            let derecha = Self::parsear_not(tokens, index)?;
+            expresion = Expresion::Binaria(Box::new(expresion), Operador::And, Box::new(derecha));
+        }
+        Ok(expresion)
+    }
+
+    fn parsear_not(tokens: &[Token], index: &mut usize) -> Result<Expresion, errores::Errores> {
+        if Self::simbolo(tokens, *index) == Some("not") {
+            *index += 1;
+            let expresion = Self::parsear_not(tokens, index)?;
+            return Ok(Expresion::Not(Box::new(expresion)));
+        }
+        Self::parsear_comparacion(tokens, index)
+    }
+
+    /// Parsea una comparación, o una expresión primaria sola.
+    ///
+    /// Si alguno de los operandos de una comparación o de un patrón lleva `COLLATE NOCASE`, los
+    /// dos se comparan sin distinguir mayúsculas de minúsculas, como `nombre COLLATE NOCASE =
+    /// 'JOSÉ'`, que se cumple con `José` (ver `Expresion::sin_mayusculas`).
+    fn parsear_comparacion(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<Expresion, errores::Errores> {
+        let (izquierda, sin_mayusculas) = Self::parsear_suma(tokens, index)?;
+        if Self::simbolo(tokens, *index) == Some("is") {
+            *index += 1;
+            let negada = Self::simbolo(tokens, *index) == Some("not");
+            if negada {
+                *index += 1;
+            }
+            Self::consumir(tokens, index, "null")?;
+            let es_nulo = Expresion::Binaria(
+                Box::new(izquierda),
+                Operador::Es,
+                Box::new(Expresion::Literal(Valor::Nulo)),
+            );
+            return Ok(if negada {
+                Expresion::Not(Box::new(es_nulo))
+            } else {
+                es_nulo
+            });
+        }
+        let operador = match Self::simbolo(tokens, *index) {
+            Some("=") => Operador::Igual,
+            Some("!=") | Some("<>") => Operador::Distinto,
+            Some("<") => Operador::Menor,
+            Some(">") => Operador::Mayor,
+            Some("<=") => Operador::MenorIgual,
+            Some(">=") => Operador::MayorIgual,
+            Some("like") => Operador::Like,
+            Some("ilike") => Operador::ILike,
+            Some("regexp") | Some("~") => Operador::Regexp,
+            _ if sin_mayusculas => return Ok(Expresion::sin_mayusculas(izquierda)),
+            _ => return Ok(izquierda),
+        };
+        *index += 1;
+        let cuantificador = match Self::simbolo(tokens, *index) {
+            Some("any") | Some("some") => Some(Cuantificador::Alguno),
+            Some("all") => Some(Cuantificador::Todos),
+            _ => None,
+        };
+        if let Some(cuantificador) = cuantificador.filter(|_| operador.es_comparacion()) {
+            if Self::simbolo(tokens, *index + 1) == Some("(")
+                && Self::simbolo(tokens, *index + 2) == Some("select")
+            {
+                *index += 1;
+                let subconsulta = Self::parsear_subconsulta(tokens, index)?;
+                let izquierda = if sin_mayusculas {
+                    Expresion::sin_mayusculas(izquierda)
+                } else {
+                    izquierda
+                };
+                return Ok(Expresion::Cuantificada(
+                    Box::new(izquierda),
+                    operador,
+                    cuantificador,
+                    Box::new(subconsulta),
+                ));
+            }
+        }
+        let (derecha, derecha_sin_mayusculas) = Self::parsear_suma(tokens, index)?;
+        if sin_mayusculas || derecha_sin_mayusculas {
+            return Ok(Expresion::Binaria(
+                Box::new(Expresion::sin_mayusculas(izquierda)),
+                operador,
+                Box::new(Expresion::sin_mayusculas(derecha)),
+            ));
+        }
+        Ok(Expresion::Binaria(
+            Box::new(izquierda),
+            operador,
+            Box::new(derecha),
+        ))
+    }
+
+    /// Parsea sumas y restas de productos, que se agrupan de izquierda a derecha, como
+    /// `precio * cantidad - descuento`.
+    ///
+    /// `+`, `-` y `/` tienen que ir separados por espacios, porque `ventas-2024` es un nombre
+    /// válido y `-5` es un número; `*` no lo necesita.
+    ///
+    /// # Retorno
+    /// La expresión y si lleva `COLLATE NOCASE`, como `parsear_intercalada`; una operación
+    /// aritmética nunca lo lleva.
+    fn parsear_suma(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<(Expresion, bool), errores::Errores> {
+        let mut izquierda = Self::parsear_producto(tokens, index)?;
+        loop {
+            let operador = match Self::simbolo(tokens, *index) {
+                Some("+") => Operador::Suma,
+                Some("-") => Operador::Resta,
+                _ => return Ok(izquierda),
+            };
+            *index += 1;
+            let derecha = Self::parsear_producto(tokens, index)?;
+            izquierda = (Self::aritmetica(izquierda, operador, derecha), false);
+        }
+    }
+
+    /// Parsea productos y divisiones de expresiones primarias, como `precio * cantidad`.
+    fn parsear_producto(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<(Expresion, bool), errores::Errores> {
+        let mut izquierda = Self::parsear_intercalada(tokens, index)?;
+        loop {
+            let operador = match Self::simbolo(tokens, *index) {
+                Some("*") => Operador::Producto,
+                Some("/") => Operador::Division,
+                _ => return Ok(izquierda),
+            };
+            *index += 1;
+            let derecha = Self::parsear_intercalada(tokens, index)?;
+            izquierda = (Self::aritmetica(izquierda, operador, derecha), false);
+        }
+    }
+
+    /// Arma una operación aritmética entre dos operandos, plegando las mayúsculas de los que
+    /// llevan `COLLATE NOCASE`.
+    fn aritmetica(
+        (izquierda, izquierda_sin_mayusculas): (Expresion, bool),
+        operador: Operador,
+        (derecha, derecha_sin_mayusculas): (Expresion, bool),
+    ) -> Expresion {
+        let plegar = |expresion, sin_mayusculas| {
+            if sin_mayusculas {
+                Expresion::sin_mayusculas(expresion)
+            } else {
+                expresion
+            }
+        };
+        Expresion::Binaria(
+            Box::new(plegar(izquierda, izquierda_sin_mayusculas)),
+            operador,
+            Box::new(plegar(derecha, derecha_sin_mayusculas)),
+        )
+    }
+
+    /// Parsea una expresión primaria seguida opcionalmente de `COLLATE NOCASE` o
+    /// `COLLATE BINARY` (que distingue mayúsculas de minúsculas, como sin `COLLATE`).
+    ///
+    /// # Retorno
+    /// La expresión, sin plegar, y si lleva `COLLATE NOCASE`; o el error de sintaxis si la
+    /// intercalación no es ninguna de las dos.
+    fn parsear_intercalada(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<(Expresion, bool), errores::Errores> {
+        let expresion = Self::parsear_primaria(tokens, index)?;
+        if Self::simbolo(tokens, *index) != Some("collate") {
+            return Ok((expresion, false));
+        }
+        *index += 1;
+        let sin_mayusculas = match Self::simbolo(tokens, *index) {
+            Some("nocase") => true,
+            Some("binary") => false,
+            _ => return Err(error_de_sintaxis(tokens, *index)),
+        };
+        *index += 1;
+        Ok((expresion, sin_mayusculas))
+    }
+
+    /// Envuelve la expresión para que su valor, si es un texto, se pliegue con
+    /// `plegar_mayusculas`. Los literales de texto se pliegan directamente, de modo que un
+    /// patrón de `LIKE` sigue compilándose una sola vez.
+    pub fn sin_mayusculas(expresion: Expresion) -> Expresion {
+        match expresion {
+            Expresion::Literal(Valor::Texto(texto)) => {
+                Expresion::Literal(Valor::Texto(plegar_mayusculas(&texto)))
+            }
+            Expresion::Funcion(nombre, argumentos) if nombre == FUNCION_SIN_MAYUSCULAS => {
+                Expresion::Funcion(nombre, argumentos)
+            }
+            expresion => Expresion::Funcion(FUNCION_SIN_MAYUSCULAS.to_string(), vec![expresion]),
+        }
+    }
+
+    fn parsear_primaria(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<Expresion, errores::Errores> {
+        let token = match tokens.get(*index) {
+            Some(token) => token,
+            None => return Err(error_de_sintaxis(tokens, *index)),
+        };
+        *index += 1;
+
+        match token.tipo {
+            TipoToken::Texto | TipoToken::Parametro => {
+                return match token.valor_texto() {
+                    Some(texto) => Ok(Expresion::Literal(Valor::Texto(texto))),
+                    None => Err(error_de_sintaxis(tokens, *index - 1)),
+                }
+            }
+            TipoToken::Numero => {
+                if let Ok(numero) = token.texto.parse::<i128>() {
+                    return Ok(Expresion::Literal(Valor::Entero(numero)));
+                }
+                return match token.texto.parse::<f64>() {
+                    Ok(numero) if token.texto.contains('.') => {
+                        Ok(Expresion::Literal(Valor::Decimal(numero)))
+                    }
+                    _ => Err(error_de_sintaxis(tokens, *index - 1)),
+                };
+            }
+            TipoToken::Puntuacion if token.es("(") => {
+                let expresion = Self::parsear_or(tokens, index)?;
+                Self::consumir(tokens, index, ")")?;
+                return Ok(expresion);
+            }
+            TipoToken::PalabraClave if token.es("null") => {
+                return Ok(Expresion::Literal(Valor::Nulo));
+            }
+            TipoToken::Identificador => {}
+            _ => return Err(error_de_sintaxis(tokens, *index - 1)),
+        }
+        let token = token.texto.as_str();
+        if token == "current_date" && Self::simbolo(tokens, *index) != Some("(") {
+            return Ok(Expresion::Funcion(token.to_string(), Vec::new()));
+        }
+        if Self::simbolo(tokens, *index) == Some("(") {
+            *index += 1;
+            if let Some(agregacion) = Agregacion::desde_nombre(token) {
+                return Self::parsear_agregada(agregacion, tokens, index);
+            }
+            if let Some(funcion) = FuncionVentana::desde_nombre(token) {
+                return Self::parsear_ventana(funcion, tokens, index);
+            }
+            return Self::parsear_funcion(token, tokens, index);
+        }
+        Ok(Expresion::Columna(token.to_string()))
+    }
+
+    /// Parsea una subconsulta entre paréntesis, desde el `(` hasta el `)` que lo cierra.
+    fn parsear_subconsulta(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<Subconsulta, errores::Errores> {
+        Self::consumir(tokens, index, "(")?;
+        let inicio = *index;
+        let mut profundidad = 0;
+        while let Some(token) = tokens.get(*index) {
+            if token.es("(") {
+                profundidad += 1;
+            } else if token.es(")") {
+                if profundidad == 0 {
+                    break;
+                }
+                profundidad -= 1;
+            }
+            *index += 1;
+        }
+        let contenido = &tokens[inicio..*index];
+        let sentencia = sintaxis::parsear_subconsulta(contenido)?;
+        Self::consumir(tokens, index, ")")?;
+        let texto: Vec<&str> = contenido.iter().map(|token| token.texto.as_str()).collect();
+        Ok(Subconsulta {
+            sentencia,
+            texto: format!("({})", texto.join(" ")),
+            valores: None,
+        })
+    }
+
+    fn parsear_funcion(
+        nombre: &str,
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<Expresion, errores::Errores> {
+        let mut argumentos = Vec::new();
+        if Self::simbolo(tokens, *index) != Some(")") {
+            argumentos.push(Self::parsear_or(tokens, index)?);
+            while Self::simbolo(tokens, *index) == Some(",") {
+                *index += 1;
+                argumentos.push(Self::parsear_or(tokens, index)?);
+            }
+        }
+        Self::consumir(tokens, index, ")")?;
+        if !aridad_funcion(nombre).is_some_and(|aridad| aridad.contains(&argumentos.len())) {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+        Ok(Expresion::Funcion(nombre.to_string(), argumentos))
+    }
+
+    fn parsear_agregada(
+        agregacion: Agregacion,
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<Expresion, errores::Errores> {
+        if agregacion == Agregacion::Count && Self::simbolo(tokens, *index) == Some("*") {
+            *index += 1;
+            Self::consumir(tokens, index, ")")?;
+            return Ok(Expresion::Agregada(agregacion, None));
+        }
+        let argumento = Self::parsear_or(tokens, index)?;
+        Self::consumir(tokens, index, ")")?;
+        if argumento.contiene_agregacion() || argumento.contiene_ventana() {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+        Ok(Expresion::Agregada(agregacion, Some(Box::new(argumento))))
+    }
+
+    /// Parsea el resto de una función de ventana:
+    /// `) OVER ([PARTITION BY expresiones] [ORDER BY criterios])`.
+    fn parsear_ventana(
+        funcion: FuncionVentana,
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<Expresion, errores::Errores> {
+        Self::consumir(tokens, index, ")")?;
+        Self::consumir(tokens, index, "over")?;
+        Self::consumir(tokens, index, "(")?;
+        let inicio = *index;
+        let mut profundidad = 0;
+        while let Some(token) = tokens.get(*index) {
+            if token.es("(") {
+                profundidad += 1;
+            } else if token.es(")") {
+                if profundidad == 0 {
+                    break;
+                }
+                profundidad -= 1;
+            }
+            *index += 1;
+        }
+        let mut especificacion = &tokens[inicio..*index];
+        Self::consumir(tokens, index, ")")?;
+        // Si la especificación termina antes de completarse, el error indica el `)` que la cierra.
+        let al_cerrar = |error| match error {
+            errores::Errores::FinInesperado { .. } => error_de_sintaxis(tokens, *index - 1),
+            error => error,
+        };
+
+        let mut particion = Vec::new();
+        if let Some(resto) = Self::despues_de(especificacion, "partition") {
+            let fin = resto
+                .iter()
+                .position(|token| token.es("order"))
+                .unwrap_or(resto.len());
+            for expresion in sintaxis::separar_por_comas(&resto[..fin]) {
+                particion.push(Expresion::parsear(expresion).map_err(al_cerrar)?);
+            }
+            if particion.is_empty() {
+                return Err(al_cerrar(error_de_sintaxis(resto, fin)));
+            }
+            especificacion = &resto[fin..];
+        }
+        let mut orden = Vec::new();
+        if let Some(resto) = Self::despues_de(especificacion, "order") {
+            orden = sintaxis::parsear_criterios_orden(resto).map_err(al_cerrar)?;
+        } else if !especificacion.is_empty() {
+            return Err(error_de_sintaxis(especificacion, 0));
+        }
+        let ventana = Ventana {
+            funcion,
+            particion,
+            orden,
+        };
+        if ventana
+            .expresiones()
+            .any(|expresion| expresion.contiene_ventana())
+        {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+        Ok(Expresion::Ventana(Box::new(ventana)))
+    }
+
+    /// Si los tokens empiezan con la palabra clave indicada seguida de `BY`, devuelve los
+    /// tokens que siguen.
+    fn despues_de<'a>(tokens: &'a [Token], palabra: &str) -> Option<&'a [Token]> {
+        match tokens {
+            [primero, segundo, resto @ ..] if primero.es(palabra) && segundo.es("by") => {
+                Some(resto)
+            }
+            _ => None,
+        }
+    }
+
+    fn consumir(
+        tokens: &[Token],
+        index: &mut usize,
+        esperado: &str,
+    ) -> Result<(), errores::Errores> {
+        if Self::simbolo(tokens, *index) != Some(esperado) {
+            return Err(error_de_sintaxis(tokens, *index));
+        }
+        *index += 1;
+        Ok(())
+    }
+
+    /// Devuelve el texto del token en la posición indicada, si es una palabra clave, un
+    /// identificador o un símbolo; los literales de texto nunca coinciden con un símbolo.
+    fn simbolo(tokens: &[Token], index: usize) -> Option<&str> {
+        tokens
+            .get(index)
+            .filter(|token| !token.es_literal())
+            .map(|token| token.texto.as_str())
+    }
+
+    /// Devuelve los nombres de las columnas que la expresión utiliza.
+    ///
+    /// # Retorno
+    /// Un `Vec<String>` con los nombres de columna, en el orden en que aparecen.
+    pub fn columnas(&self) -> Vec<String> {
+        let mut columnas = Vec::new();
+        self.acumular_columnas(&mut columnas);
+        columnas
+    }
+
+    fn acumular_columnas(&self, columnas: &mut Vec<String>) {
+        match self {
+            Expresion::Literal(_) => {}
+            Expresion::Columna(nombre) => columnas.push(nombre.to_string()),
+            Expresion::Binaria(izquierda, _, derecha) => {
+                izquierda.acumular_columnas(columnas);
+                derecha.acumular_columnas(columnas);
+            }
+            Expresion::Not(expresion) => expresion.acumular_columnas(columnas),
+            Expresion::Funcion(_, argumentos) => {
+                for argumento in argumentos {
+                    argumento.acumular_columnas(columnas);
+                }
+            }
+            Expresion::Agregada(_, argumento) => {
+                if let Some(argumento) = argumento {
+                    argumento.acumular_columnas(columnas);
+                }
+            }
+            Expresion::Ventana(ventana) => {
+                for expresion in ventana.expresiones() {
+                    expresion.acumular_columnas(columnas);
+                }
+            }
+            // Las columnas de la subconsulta son de su propia tabla.
+            Expresion::Cuantificada(izquierda, _, _, _) => izquierda.acumular_columnas(columnas),
+        }
+    }
+
+    /// Devuelve los nombres de las columnas que la expresión utiliza fuera de las funciones
+    /// de agregación, que son las que deben formar parte del `GROUP BY`.
+    ///
+    /// # Retorno
+    /// Un `Vec<String>` con los nombres de columna, en el orden en que aparecen.
+    pub fn columnas_no_agregadas(&self) -> Vec<String> {
+        match self {
+            Expresion::Agregada(_, _) => Vec::new(),
+            Expresion::Binaria(izquierda, _, derecha) => {
+                let mut columnas = izquierda.columnas_no_agregadas();
+                columnas.extend(derecha.columnas_no_agregadas());
+                columnas
+            }
+            Expresion::Not(expresion) => expresion.columnas_no_agregadas(),
+            Expresion::Funcion(_, argumentos) => argumentos
+                .iter()
+                .flat_map(|argumento| argumento.columnas_no_agregadas())
+                .collect(),
+            Expresion::Ventana(ventana) => ventana
+                .expresiones()
+                .flat_map(|expresion| expresion.columnas_no_agregadas())
+                .collect(),
+            Expresion::Cuantificada(izquierda, _, _, _) => izquierda.columnas_no_agregadas(),
+            _ => self.columnas(),
+        }
+    }
+
+    /// Indica si la expresión contiene alguna función de agregación, incluidas las que
+    /// aparecen en el `ORDER BY` de una función de ventana.
+    pub fn contiene_agregacion(&self) -> bool {
+        match self {
+            Expresion::Literal(_) | Expresion::Columna(_) => false,
+            Expresion::Agregada(_, _) => true,
+            Expresion::Binaria(izquierda, _, derecha) => {
+                izquierda.contiene_agregacion() || derecha.contiene_agregacion()
+            }
+            Expresion::Not(expresion) => expresion.contiene_agregacion(),
+            Expresion::Funcion(_, argumentos) => argumentos
+                .iter()
+                .any(|argumento| argumento.contiene_agregacion()),
+            Expresion::Ventana(ventana) => ventana
+                .expresiones()
+                .any(|expresion| expresion.contiene_agregacion()),
+            Expresion::Cuantificada(izquierda, _, _, _) => izquierda.contiene_agregacion(),
+        }
+    }
+
+    /// Indica si la expresión contiene alguna función de ventana.
+    pub fn contiene_ventana(&self) -> bool {
+        match self {
+            Expresion::Literal(_) | Expresion::Columna(_) | Expresion::Agregada(_, _) => false,
+            Expresion::Ventana(_) => true,
+            Expresion::Binaria(izquierda, _, derecha) => {
+                izquierda.contiene_ventana() || derecha.contiene_ventana()
+            }
+            Expresion::Not(expresion) => expresion.contiene_ventana(),
+            Expresion::Funcion(_, argumentos) => argumentos
+                .iter()
+                .any(|argumento| argumento.contiene_ventana()),
+            Expresion::Cuantificada(izquierda, _, _, _) => izquierda.contiene_ventana(),
+        }
+    }
+
+    /// Indica si la expresión usa `RANDOM()`, de modo que su valor cambia en cada evaluación
+    /// aunque no dependa de ninguna columna.
+    pub fn contiene_aleatoriedad(&self) -> bool {
+        match self {
+            Expresion::Literal(_) | Expresion::Columna(_) => false,
+            Expresion::Funcion(nombre, argumentos) => {
+                nombre == "random"
+                    || argumentos
+                        .iter()
+                        .any(|argumento| argumento.contiene_aleatoriedad())
+            }
+            Expresion::Binaria(izquierda, _, derecha) => {
+                izquierda.contiene_aleatoriedad() || derecha.contiene_aleatoriedad()
+            }
+            Expresion::Not(expresion) => expresion.contiene_aleatoriedad(),
+            Expresion::Agregada(_, argumento) => argumento
+                .as_ref()
+                .is_some_and(|argumento| argumento.contiene_aleatoriedad()),
+            Expresion::Ventana(ventana) => ventana
+                .expresiones()
+                .any(|expresion| expresion.contiene_aleatoriedad()),
+            Expresion::Cuantificada(izquierda, _, _, _) => izquierda.contiene_aleatoriedad(),
+        }
+    }
+
+    /// Reemplaza cada columna de la expresión que tiene una entrada en `reemplazos` por una
+    /// copia de la expresión correspondiente. Las columnas de los argumentos de las funciones de
+    /// agregación y de ventana no se reemplazan, porque se evalúan sobre cada registro.
+    ///
+    /// # Parámetros
+    /// - `reemplazos`: El mapa de nombres de columna a la expresión que los reemplaza.
+    pub fn reemplazar_columnas(&mut self, reemplazos: &HashMap<String, Expresion>) {
+        match self {
+            Expresion::Columna(columna) => {
+                if let Some(reemplazo) = reemplazos.get(columna.as_str()) {
+                    *self = reemplazo.clone();
+                }
+            }
+            Expresion::Binaria(izquierda, _, derecha) => {
+                izquierda.reemplazar_columnas(reemplazos);
+                derecha.reemplazar_columnas(reemplazos);
+            }
+            Expresion::Not(expresion) => expresion.reemplazar_columnas(reemplazos),
+            Expresion::Funcion(_, argumentos) => argumentos
+                .iter_mut()
+                .for_each(|argumento| argumento.reemplazar_columnas(reemplazos)),
+            Expresion::Cuantificada(izquierda, _, _, _) => {
+                izquierda.reemplazar_columnas(reemplazos)
+            }
+            Expresion::Literal(_) | Expresion::Agregada(_, _) | Expresion::Ventana(_) => {}
+        }
+    }
+
+    /// Ejecuta cada subconsulta de la expresión que todavía no se ejecutó y guarda los
+    /// valores de su resultado (ver `Subconsulta`).
+    ///
+    /// # Parámetros
+    /// - `ejecutar`: Ejecuta la sentencia de una subconsulta y devuelve los valores de su
+    ///   única columna.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error de la primera subconsulta que no se pudo ejecutar.
+    pub fn materializar_subconsultas(
+        &mut self,
+        ejecutar: &mut dyn FnMut(&SentenciaSelect) -> Result<Vec<Valor>, errores::Errores>,
+    ) -> Result<(), errores::Errores> {
+        match self {
+            Expresion::Literal(_) | Expresion::Columna(_) | Expresion::Ventana(_) => Ok(()),
+            Expresion::Binaria(izquierda, _, derecha) => {
+                izquierda.materializar_subconsultas(ejecutar)?;
+                derecha.materializar_subconsultas(ejecutar)
+            }
+            Expresion::Not(expresion) => expresion.materializar_subconsultas(ejecutar),
+            Expresion::Funcion(_, argumentos) => argumentos
+                .iter_mut()
+                .try_for_each(|argumento| argumento.materializar_subconsultas(ejecutar)),
+            Expresion::Agregada(_, argumento) => match argumento {
+                Some(argumento) => argumento.materializar_subconsultas(ejecutar),
+                None => Ok(()),
+            },
+            Expresion::Cuantificada(izquierda, _, _, subconsulta) => {
+                izquierda.materializar_subconsultas(ejecutar)?;
+                if subconsulta.valores.is_none() {
+                    let mut valores = ejecutar(&subconsulta.sentencia)?;
+                    if matches!(&**izquierda, Expresion::Funcion(nombre, _) if nombre == FUNCION_SIN_MAYUSCULAS)
+                    {
+                        valores = valores.into_iter().map(plegar_valor).collect();
+                    }
+                    subconsulta.valores = Some(valores);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Compila la expresión para evaluarla sobre muchos registros, resolviendo cada columna a
+    /// su índice y los patrones literales de `LIKE`, `ILIKE` y `REGEXP` una única vez.
+    ///
+    /// # Parámetros
+    /// - `campos`: El mapa de nombres de columna a su índice en el registro.
+    ///
+    /// # Retorno
+    /// Retorna la `ExpresionCompilada`, `Errores::ColumnaDesconocida` si se usa una columna que no
+    /// existe, `Errores::PatronInvalido` si un patrón literal no es válido o
+    /// `Errores::InvalidSyntax` si la expresión contiene una función de agregación. Una función
+    /// de ventana se compila como un valor nulo, que se reemplaza al calcularla sobre todas las
+    /// filas del resultado.
+    pub fn compilar(
+        &self,
+        campos: &HashMap<String, usize>,
+    ) -> Result<ExpresionCompilada, errores::Errores> {
+        self.compilar_con_tipos(campos, &HashMap::new())
+    }
+
+    /// Compila la expresión como `compilar`, interpretando los valores de las columnas con
+    /// tipo declarado según su tipo (ver `Valor::desde_campo_tipado`).
+    ///
+    /// Las comparaciones entre dos columnas con tipos distintos, o entre una columna y un
+    /// literal que no es de su tipo, se rechazan en lugar de compararse como texto.
+    ///
+    /// # Parámetros
+    /// - `campos`: El mapa de nombres de columna a su índice en el registro.
+    /// - `tipos`: Los tipos declarados de las columnas (ver `Esquema::tipos`).
+    ///
+    /// # Retorno
+    /// Retorna la `ExpresionCompilada`, `Errores::TipoInvalido` si una comparación mezcla
+    /// tipos, o los mismos errores que `compilar`.
+    pub fn compilar_con_tipos(
+        &self,
+        campos: &HashMap<String, usize>,
+        tipos: &HashMap<String, TipoColumna>,
+    ) -> Result<ExpresionCompilada, errores::Errores> {
+        let compilar = |expresion: &Expresion| expresion.compilar_con_tipos(campos, tipos);
+        Ok(match self {
+            Expresion::Literal(valor) => ExpresionCompilada::Literal(valor.clone()),
+            Expresion::Columna(nombre) => match (campos.get(nombre), tipos.get(nombre)) {
+                (Some(indice), Some(tipo)) => ExpresionCompilada::CampoTipado(*indice, *tipo),
+                (Some(indice), None) => ExpresionCompilada::Campo(*indice),
+                (None, _) => {
+                    return Err(errores::Errores::columna_desconocida(nombre, campos.keys()))
+                }
+            },
+            Expresion::Not(expresion) => ExpresionCompilada::Not(Box::new(compilar(expresion)?)),
+            Expresion::Binaria(
+                izquierda,
+                operador @ (Operador::Like | Operador::ILike | Operador::Regexp),
+                derecha,
+            ) => match &**derecha {
+                Expresion::Literal(Valor::Texto(patron)) => ExpresionCompilada::Patron(
+                    Box::new(compilar(izquierda)?),
+                    compilar_patron(operador, patron)?,
+                ),
+                _ => ExpresionCompilada::Binaria(
+                    Box::new(compilar(izquierda)?),
+                    operador.clone(),
+                    Box::new(compilar(derecha)?),
+                ),
+            },
+            Expresion::Binaria(izquierda, operador, derecha) => {
+                if operador.es_comparacion() {
+                    Self::verificar_tipos(izquierda, derecha, tipos)?;
+                }
+                ExpresionCompilada::Binaria(
+                    Box::new(compilar(izquierda)?),
+                    operador.clone(),
+                    Box::new(compilar(derecha)?),
+                )
+            }
+            Expresion::Funcion(nombre, argumentos) => {
+                let mut compilados = Vec::new();
+                for argumento in argumentos {
+                    compilados.push(compilar(argumento)?);
+                }
+                ExpresionCompilada::Funcion(nombre.to_string(), compilados)
+            }
+            Expresion::Agregada(_, _) => return Err(errores::Errores::InvalidSyntax),
+            Expresion::Ventana(_) => ExpresionCompilada::Literal(Valor::Nulo),
+            Expresion::Cuantificada(izquierda, operador, cuantificador, subconsulta) => {
+                ExpresionCompilada::Cuantificada(
+                    Box::new(compilar(izquierda)?),
+                    operador.clone(),
+                    *cuantificador,
+                    subconsulta.valores()?.to_vec(),
+                )
+            }
+        })
+    }
+
+    /// Verifica que los operandos de una comparación sean del mismo tipo: dos columnas con
+    /// tipo declarado deben tener el mismo, y un literal comparado con una columna con tipo
+    /// debe ser un valor de ese tipo (una columna `TEXT` solo se compara con textos y una
+    /// `INTEGER` con números o textos numéricos).
+    fn verificar_tipos(
+        izquierda: &Expresion,
+        derecha: &Expresion,
+        tipos: &HashMap<String, TipoColumna>,
+    ) -> Result<(), errores::Errores> {
+        match (izquierda, derecha) {
+            (Expresion::Columna(columna), Expresion::Columna(otra)) => {
+                if let (Some(tipo), Some(otro_tipo)) = (tipos.get(columna), tipos.get(otra)) {
+                    if tipo != otro_tipo {
+                        return Err(errores::Errores::TipoInvalido(format!(
+                            "no se puede comparar '{}' ({}) con '{}' ({})",
+                            columna, tipo, otra, otro_tipo
+                        )));
+                    }
+                }
+            }
+            (Expresion::Columna(columna), Expresion::Literal(valor))
+            | (Expresion::Literal(valor), Expresion::Columna(columna)) => {
+                let Some(tipo) = tipos.get(columna) else {
+                    return Ok(());
+                };
+                let compatible = match (tipo, valor) {
+                    (_, Valor::Nulo) => true,
+                    (TipoColumna::Texto, valor) => matches!(valor, Valor::Texto(_)),
+                    (TipoColumna::Entero, Valor::Entero(_) | Valor::Decimal(_)) => true,
+                    (tipo, valor) => {
+                        Valor::desde_campo_tipado(valor.a_campo().as_deref(), *tipo).is_ok()
+                    }
+                };
+                if !compatible {
+                    return Err(errores::Errores::TipoInvalido(format!(
+                        "no se puede comparar '{}' ({}) con {}",
+                        columna,
+                        tipo,
+                        Expresion::Literal(valor.clone())
+                    )));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Evalúa la expresión sobre un registro de la tabla.
+    ///
+    /// # Parámetros
+    /// - `campos`: El mapa de nombres de columna a su índice en el registro.
+    /// - `registro`: Los valores del registro, tal como están en el archivo (`None` si son nulos).
+    ///
+    /// # Retorno
+    /// Retorna el `Valor` resultante, `Errores::ColumnaDesconocida` si se usa una columna que no
+    /// existe o `Errores::InvalidSyntax` si un operador lógico recibe valores no booleanos o la
+    /// expresión contiene una función de agregación.
+    pub fn evaluar(
+        &self,
+        campos: &HashMap<String, usize>,
+        registro: &[Option<String>],
+    ) -> Result<Valor, errores::Errores> {
+        match self {
+            Expresion::Literal(valor) => Ok(valor.clone()),
+            Expresion::Columna(nombre) => match campos.get(nombre) {
+                Some(indice) => Ok(Valor::desde_campo(
+                    registro.get(*indice).and_then(|campo| campo.as_deref()),
+                )),
+                None => Err(errores::Errores::columna_desconocida(nombre, campos.keys())),
+            },
+            Expresion::Not(expresion) => {
+                let valor = expresion.evaluar(campos, registro)?;
+                Self::negar(&valor)
+            }
+            Expresion::Binaria(izquierda, operador, derecha) => {
+                let izquierda = izquierda.evaluar(campos, registro)?;
+                let derecha = derecha.evaluar(campos, registro)?;
+                Self::aplicar_operador(&izquierda, operador, &derecha)
+            }
+            Expresion::Funcion(nombre, argumentos) => {
+                let mut valores = Vec::new();
+                for argumento in argumentos {
+                    valores.push(argumento.evaluar(campos, registro)?);
+                }
+                evaluar_funcion(nombre, valores)
+            }
+            Expresion::Agregada(_, _) | Expresion::Ventana(_) => {
+                Err(errores::Errores::InvalidSyntax)
+            }
+            Expresion::Cuantificada(izquierda, operador, cuantificador, subconsulta) => {
+                let izquierda = izquierda.evaluar(campos, registro)?;
+                Self::cuantificar(&izquierda, operador, *cuantificador, subconsulta.valores()?)
+            }
+        }
+    }
+
+    /// Evalúa la expresión sobre un grupo de registros.
+    ///
+    /// Las funciones de agregación combinan los valores de todos los registros del grupo; el
+    /// resto de la expresión se evalúa sobre el primer registro, ya que las columnas fuera de
+    /// una agregación tienen el mismo valor en todo el grupo. Las funciones de ventana dan un
+    /// valor nulo, como al compilarlas.
+    ///
+    /// # Parámetros
+    /// - `campos`: El mapa de nombres de columna a su índice en el registro.
+    /// - `registros`: Los registros del grupo, tal como están en el archivo.
+    ///
+    /// # Retorno
+    /// Retorna el `Valor` resultante o el error de evaluación correspondiente.
+    pub fn evaluar_grupo(
+        &self,
+        campos: &HashMap<String, usize>,
+        registros: &[Vec<Option<String>>],
+    ) -> Result<Valor, errores::Errores> {
+        match self {
+            Expresion::Agregada(agregacion, argumento) => {
+                let mut valores = Vec::new();
+                for registro in registros {
+                    valores.push(match argumento {
+                        Some(argumento) => argumento.evaluar(campos, registro)?,
+                        None => Valor::Booleano(true),
+                    });
+                }
+                agregacion.aplicar(valores)
+            }
+            Expresion::Not(expresion) => {
+                let valor = expresion.evaluar_grupo(campos, registros)?;
+                Self::negar(&valor)
+            }
+            Expresion::Binaria(izquierda, operador, derecha) => {
+                let izquierda = izquierda.evaluar_grupo(campos, registros)?;
+                let derecha = derecha.evaluar_grupo(campos, registros)?;
+                Self::aplicar_operador(&izquierda, operador, &derecha)
+            }
+            Expresion::Funcion(nombre, argumentos) => {
+                let mut valores = Vec::new();
+                for argumento in argumentos {
+                    valores.push(argumento.evaluar_grupo(campos, registros)?);
+                }
+                evaluar_funcion(nombre, valores)
+            }
+            Expresion::Ventana(_) => Ok(Valor::Nulo),
+            Expresion::Cuantificada(izquierda, operador, cuantificador, subconsulta) => {
+                let izquierda = izquierda.evaluar_grupo(campos, registros)?;
+                Self::cuantificar(&izquierda, operador, *cuantificador, subconsulta.valores()?)
+            }
+            _ => match registros.first() {
+                Some(registro) => self.evaluar(campos, registro),
+                None => self.evaluar(campos, &[]),
+            },
+        }
+    }
+
+    /// Aplica un operador binario siguiendo la lógica de tres valores de SQL: las comparaciones
+    /// con un valor nulo son nulas, `AND` es falso si algún operando es falso y `OR` es
+    /// verdadero si alguno es verdadero. Los operadores aritméticos se aplican con `calcular`.
+    fn aplicar_operador(
+        izquierda: &Valor,
+        operador: &Operador,
+        derecha: &Valor,
+    ) -> Result<Valor, errores::Errores> {
+        if operador.es_aritmetico() {
+            return Self::calcular(izquierda, operador, derecha);
+        }
+        let resultado = match operador {
+            Operador::Es => Some(izquierda == derecha),
+            Operador::And => match (Self::a_booleano(izquierda)?, Self::a_booleano(derecha)?) {
+                (Some(false), _) | (_, Some(false)) => Some(false),
+                (Some(true), Some(true)) => Some(true),
+                _ => None,
+            },
+            Operador::Or => match (Self::a_booleano(izquierda)?, Self::a_booleano(derecha)?) {
+                (Some(true), _) | (_, Some(true)) => Some(true),
+                (Some(false), Some(false)) => Some(false),
+                _ => None,
+            },
+            _ if *izquierda == Valor::Nulo || *derecha == Valor::Nulo => None,
+            Operador::Igual => Some(izquierda.comparar(derecha) == Ordering::Equal),
+            Operador::Distinto => Some(izquierda.comparar(derecha) != Ordering::Equal),
+            Operador::Menor => Some(izquierda.comparar(derecha) == Ordering::Less),
+            Operador::Mayor => Some(izquierda.comparar(derecha) == Ordering::Greater),
+            Operador::MenorIgual => Some(izquierda.comparar(derecha) != Ordering::Greater),
+            Operador::MayorIgual => Some(izquierda.comparar(derecha) != Ordering::Less),
+            Operador::Like | Operador::ILike | Operador::Regexp => Some(
+                compilar_patron(operador, &derecha.a_texto())?.coincide(&izquierda.a_texto())?,
+            ),
+            Operador::Suma | Operador::Resta | Operador::Producto | Operador::Division => None,
+        };
+        Ok(resultado.map_or(Valor::Nulo, Valor::Booleano))
+    }
+
+    /// Aplica un operador aritmético a dos números, o a textos numéricos como los campos con
+    /// decimales. Entre enteros el resultado es entero y la división descarta los decimales,
+    /// como en SQL; si algún operando es decimal, el resultado es decimal. Con un operando nulo,
+    /// o al dividir por cero, el resultado es nulo, como en `MOD`.
+    ///
+    /// # Retorno
+    /// El resultado, `Errores::TipoInvalido` si algún operando no es un número o
+    /// `Errores::ValorInvalido` si el resultado excede el rango de los enteros.
+    fn calcular(
+        izquierda: &Valor,
+        operador: &Operador,
+        derecha: &Valor,
+    ) -> Result<Valor, errores::Errores> {
+        let numero = |valor: &Valor| {
+            numero_de(valor.clone()).map_err(|_| {
+                errores::Errores::TipoInvalido(format!(
+                    "no se puede aplicar '{}' a {}, que no es un número",
+                    operador,
+                    Expresion::Literal(valor.clone())
+                ))
+            })
+        };
+        match (numero(izquierda)?, numero(derecha)?) {
+            (Valor::Nulo, _) | (_, Valor::Nulo) => Ok(Valor::Nulo),
+            (Valor::Entero(_), Valor::Entero(0)) if *operador == Operador::Division => {
+                Ok(Valor::Nulo)
+            }
+            (Valor::Entero(a), Valor::Entero(b)) => {
+                let resultado = match operador {
+                    Operador::Suma => a.checked_add(b),
+                    Operador::Resta => a.checked_sub(b),
+                    Operador::Producto => a.checked_mul(b),
+                    _ => a.checked_div(b),
+                };
+                resultado.map(Valor::Entero).ok_or_else(|| {
+                    errores::Errores::ValorInvalido(format!(
+                        "el resultado de {} {} {} excede el rango de los enteros",
+                        a, operador, b
+                    ))
+                })
+            }
+            (a, b) => {
+                let (a, b) = (
+                    a.a_numero().unwrap_or_default(),
+                    b.a_numero().unwrap_or_default(),
+                );
+                Ok(match operador {
+                    Operador::Suma => Valor::Decimal(a + b),
+                    Operador::Resta => Valor::Decimal(a - b),
+                    Operador::Producto => Valor::Decimal(a * b),
+                    _ if b == 0.0 => Valor::Nulo,
+                    _ => Valor::Decimal(a / b),
+                })
+            }
+        }
+    }
+
+    /// Compara un valor con cada valor de una subconsulta, siguiendo la lógica de tres valores
+    /// de SQL: `ALL` es falso si alguna comparación es falsa y `ANY` es verdadero si alguna es
+    /// verdadera; si no, el resultado es nulo cuando alguna comparación es nula. Con una
+    /// subconsulta sin filas, `ALL` es verdadero y `ANY` es falso.
+    fn cuantificar(
+        izquierda: &Valor,
+        operador: &Operador,
+        cuantificador: Cuantificador,
+        valores: &[Valor],
+    ) -> Result<Valor, errores::Errores> {
+        let decisivo = cuantificador == Cuantificador::Alguno;
+        let mut resultado = Valor::Booleano(!decisivo);
+        for valor in valores {
+            match Self::aplicar_operador(izquierda, operador, valor)? {
+                Valor::Booleano(cumple) if cumple == decisivo => {
+                    return Ok(Valor::Booleano(cumple))
+                }
+                Valor::Nulo => resultado = Valor::Nulo,
+                _ => {}
+            }
+        }
+        Ok(resultado)
+    }
+
+    fn negar(valor: &Valor) -> Result<Valor, errores::Errores> {
+        Ok(Self::a_booleano(valor)?.map_or(Valor::Nulo, |valor| Valor::Booleano(!valor)))
+    }
+
+    /// Evalúa la expresión como condición sobre un registro.
+    ///
+    /// # Retorno
+    /// Retorna `true` si el registro cumple la condición (una condición nula no se cumple), o
+    /// `Errores::InvalidSyntax` si la expresión no produce un valor booleano.
+    #[cfg(test)]
+    pub fn cumple(
+        &self,
+        campos: &HashMap<String, usize>,
+        registro: &[Option<String>],
+    ) -> Result<bool, errores::Errores> {
+        Ok(Self::a_booleano(&self.evaluar(campos, registro)?)?.unwrap_or(false))
+    }
+
+    /// Evalúa la expresión como condición sobre un grupo de registros (ver `evaluar_grupo`).
+    ///
+    /// # Retorno
+    /// Retorna `true` si el grupo cumple la condición (una condición nula no se cumple), o
+    /// `Errores::InvalidSyntax` si la expresión no produce un valor booleano.
+    pub fn cumple_grupo(
+        &self,
+        campos: &HashMap<String, usize>,
+        registros: &[Vec<Option<String>>],
+    ) -> Result<bool, errores::Errores> {
+        Ok(Self::a_booleano(&self.evaluar_grupo(campos, registros)?)?.unwrap_or(false))
+    }
+
+    /// Interpreta un valor en un contexto lógico; `None` representa un valor nulo.
+    fn a_booleano(valor: &Valor) -> Result<Option<bool>, errores::Errores> {
+        match valor {
+            Valor::Booleano(valor) => Ok(Some(*valor)),
+            Valor::Nulo => Ok(None),
+            _ => Err(errores::Errores::InvalidSyntax),
+        }
+    }
+}
+
+impl fmt::Display for Operador {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let simbolo = match self {
+            Operador::Igual => "=",
+            Operador::Distinto => "!=",
+            Operador::Menor => "<",
+            Operador::Mayor => ">",
+            Operador::MenorIgual => "<=",
+            Operador::MayorIgual => ">=",
+            Operador::Like => "LIKE",
+            Operador::ILike => "ILIKE",
+            Operador::Regexp => "REGEXP",
+            Operador::Es => "IS",
+            Operador::And => "AND",
+            Operador::Or => "OR",
+            Operador::Suma => "+",
+            Operador::Resta => "-",
+            Operador::Producto => "*",
+            Operador::Division => "/",
+        };
+        write!(f, "{simbolo}")
+    }
+}
+
+/// Muestra la expresión con cada operación entre paréntesis, de modo que se vea la forma del
+/// árbol que resultó de parsearla (por ejemplo, `((edad > 30) OR (NOT (ciudad = 'Madrid')))`).
+impl fmt::Display for Expresion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expresion::Literal(Valor::Texto(texto)) => {
+                write!(f, "'{}'", texto.replace('\'', "''"))
+            }
+            Expresion::Literal(Valor::Entero(numero)) => write!(f, "{numero}"),
+            Expresion::Literal(Valor::Decimal(numero)) => write!(f, "{numero:?}"),
+            Expresion::Literal(Valor::Booleano(valor)) => {
+                write!(f, "{}", if *valor { "TRUE" } else { "FALSE" })
+            }
+            Expresion::Literal(Valor::Nulo) => write!(f, "NULL"),
+            Expresion::Columna(nombre) => write!(f, "{nombre}"),
+            Expresion::Binaria(izquierda, operador, derecha) => {
+                write!(f, "({izquierda} {operador} {derecha})")
+            }
+            Expresion::Not(expresion) => write!(f, "(NOT {expresion})"),
+            Expresion::Funcion(nombre, _) if nombre == "current_date" => {
+                write!(f, "CURRENT_DATE")
+            }
+            Expresion::Funcion(nombre, argumentos) if nombre == FUNCION_SIN_MAYUSCULAS => {
+                write!(f, "{} COLLATE NOCASE", argumentos[0])
+            }
+            Expresion::Funcion(nombre, argumentos) => {
+                let argumentos: Vec<String> = argumentos
+                    .iter()
+                    .map(|argumento| argumento.to_string())
+                    .collect();
+                write!(f, "{}({})", nombre.to_uppercase(), argumentos.join(", "))
+            }
+            Expresion::Agregada(agregacion, argumento) => {
+                let nombre = agregacion.nombre();
+                match argumento {
+                    Some(argumento) => write!(f, "{nombre}({argumento})"),
+                    None => write!(f, "{nombre}(*)"),
+                }
+            }
+            Expresion::Ventana(ventana) => write!(f, "{ventana}"),
+            Expresion::Cuantificada(izquierda, operador, cuantificador, subconsulta) => {
+                let cuantificador = match cuantificador {
+                    Cuantificador::Alguno => "ANY",
+                    Cuantificador::Todos => "ALL",
+                };
+                write!(
+                    f,
+                    "({izquierda} {operador} {cuantificador} {})",
+                    subconsulta.texto
+                )
+            }
+        }
+    }
+}
+
+/// Expresión lista para evaluarse registro por registro durante el recorrido de una tabla.
+///
+/// Se obtiene con `Expresion::compilar`: las columnas ya están resueltas a su índice en el
+/// registro y los patrones de `LIKE`, `ILIKE` y `REGEXP` escritos como literales ya están compilados, de
+/// modo que
+/// evaluar un registro no requiere búsquedas por nombre ni recompilar patrones.
+///
+/// - `Literal`: Un valor constante.
+/// - `Campo`: El valor del campo con el índice indicado.
+/// - `CampoTipado`: El valor del campo con el índice indicado, de una columna con tipo
+///   declarado.
+/// - `Binaria`: Un operador binario entre dos expresiones.
+/// - `Patron`: Un `LIKE`, `ILIKE` o `REGEXP` contra un patrón ya compilado.
+/// - `Not`: La negación de una expresión lógica.
+/// - `Funcion`: La llamada a una función escalar.
+/// - `Cuantificada`: Una comparación con cada uno de los valores de una subconsulta ya
+///   ejecutada.
+#[derive(Debug, Clone)]
+pub enum ExpresionCompilada {
+    Literal(Valor),
+    Campo(usize),
+    CampoTipado(usize, TipoColumna),
+    Binaria(Box<ExpresionCompilada>, Operador, Box<ExpresionCompilada>),
+    Patron(Box<ExpresionCompilada>, Patron),
+    Not(Box<ExpresionCompilada>),
+    Funcion(String, Vec<ExpresionCompilada>),
+    Cuantificada(Box<ExpresionCompilada>, Operador, Cuantificador, Vec<Valor>),
+}
+
+impl ExpresionCompilada {
+    /// Evalúa la expresión sobre un registro de la tabla.
+    ///
+    /// # Parámetros
+    /// - `registro`: Los valores del registro, tal como están en el archivo (`None` si son nulos).
+    ///
+    /// # Retorno
+    /// Retorna el `Valor` resultante, `Errores::InvalidSyntax` si un operador lógico recibe
+    /// valores no booleanos o `Errores::TipoInvalido` si un campo no es del tipo declarado de
+    /// su columna.
+    pub fn evaluar<C: AsRef<str>>(
+        &self,
+        registro: &[Option<C>],
+    ) -> Result<Valor, errores::Errores> {
+        match self {
+            ExpresionCompilada::Literal(valor) => Ok(valor.clone()),
+            ExpresionCompilada::Campo(indice) => Ok(Valor::desde_campo(
+                registro
+                    .get(*indice)
+                    .and_then(|campo| campo.as_ref().map(AsRef::as_ref)),
+            )),
+            ExpresionCompilada::CampoTipado(indice, tipo) => Valor::desde_campo_tipado(
+                registro
+                    .get(*indice)
+                    .and_then(|campo| campo.as_ref().map(AsRef::as_ref)),
+                *tipo,
+            ),
+            ExpresionCompilada::Not(expresion) => Expresion::negar(&expresion.evaluar(registro)?),
+            ExpresionCompilada::Binaria(izquierda, operador, derecha) => {
+                let izquierda = izquierda.evaluar(registro)?;
+                // `AND` y `OR` no evalúan el lado derecho si el izquierdo ya decide el resultado.
+                let decisivo = match operador {
+                    Operador::And => Some(false),
+                    Operador::Or => Some(true),
+                    _ => None,
+                };
+                if decisivo.is_some() && Expresion::a_booleano(&izquierda)? == decisivo {
+                    return Ok(izquierda);
+                }
+                let derecha = derecha.evaluar(registro)?;
+                Expresion::aplicar_operador(&izquierda, operador, &derecha)
+            }
+            ExpresionCompilada::Patron(texto, patron) => match texto.evaluar(registro)? {
+                Valor::Nulo => Ok(Valor::Nulo),
+                texto => Ok(Valor::Booleano(patron.coincide(&texto.a_texto())?)),
+            },
+            ExpresionCompilada::Funcion(nombre, argumentos) => {
+                let mut valores = Vec::new();
+                for argumento in argumentos {
+                    valores.push(argumento.evaluar(registro)?);
+                }
+                evaluar_funcion(nombre, valores)
+            }
+            ExpresionCompilada::Cuantificada(izquierda, operador, cuantificador, valores) => {
+                Expresion::cuantificar(
+                    &izquierda.evaluar(registro)?,
+                    operador,
+                    *cuantificador,
+                    valores,
+                )
+            }
+        }
+    }
+
+    /// Evalúa la expresión como condición sobre un registro.
+    ///
+    /// # Retorno
+    /// Retorna `true` si el registro cumple la condición (una condición nula no se cumple), o
+    /// `Errores::InvalidSyntax` si la expresión no produce un valor booleano.
+    pub fn cumple<C: AsRef<str>>(&self, registro: &[Option<C>]) -> Result<bool, errores::Errores> {
+        Ok(Expresion::a_booleano(&self.evaluar(registro)?)?.unwrap_or(false))
+    }
+}
+
+/// Devuelve la cantidad de argumentos que recibe una función escalar conocida.
+///
+/// # Parámetros
+/// - `nombre`: El nombre de la función en minúsculas.
+///
+/// # Retorno
+/// La cantidad mínima y máxima de argumentos, o `None` si la función no existe.
+fn aridad_funcion(nombre: &str) -> Option<RangeInclusive<usize>> {
+    match nombre {
+        "iif" | "if" | "replace" => Some(3..=3),
+        "length" | "year" | "month" | "day" | "abs" => Some(1..=1),
+        "date_add" | "date_sub" | "mod" => Some(2..=2),
+        "round" => Some(1..=2),
+        "current_date" | "now" | "random" => Some(0..=0),
+        _ => None,
+    }
+}
+
+/// Pliega las mayúsculas de un valor de texto (ver `plegar_mayusculas`); el resto de los
+/// valores no cambian.
+fn plegar_valor(valor: Valor) -> Valor {
+    match valor {
+        Valor::Texto(texto) => Valor::Texto(plegar_mayusculas(&texto)),
+        valor => valor,
+    }
+}
+
+/// Compara dos textos en orden natural: las secuencias de dígitos se comparan por su valor
+/// numérico, sin importar los ceros a la izquierda, y el resto de los caracteres uno a uno.
+/// Ante el mismo valor, la secuencia con menos ceros a la izquierda va primero.
+fn comparar_texto_natural(a: &str, b: &str) -> Ordering {
+    let mut caracteres_a = a.chars().peekable();
+    let mut caracteres_b = b.chars().peekable();
+    loop {
+        let (Some(&ca), Some(&cb)) = (caracteres_a.peek(), caracteres_b.peek()) else {
+            return caracteres_a
+                .peek()
+                .is_some()
+                .cmp(&caracteres_b.peek().is_some());
+        };
+        if ca.is_ascii_digit() && cb.is_ascii_digit() {
+            let digitos_a = tomar_digitos(&mut caracteres_a);
+            let digitos_b = tomar_digitos(&mut caracteres_b);
+            let valor_a = digitos_a.trim_start_matches('0');
+            let valor_b = digitos_b.trim_start_matches('0');
+            let orden = valor_a
+                .len()
+                .cmp(&valor_b.len())
+                .then_with(|| valor_a.cmp(valor_b))
+                .then_with(|| digitos_a.len().cmp(&digitos_b.len()));
+            if orden != Ordering::Equal {
+                return orden;
+            }
+        } else {
+            if ca != cb {
+                return ca.cmp(&cb);
+            }
+            caracteres_a.next();
+            caracteres_b.next();
+        }
+    }
+}
+
+/// Consume la secuencia de dígitos ASCII con la que empiezan los caracteres.
+fn tomar_digitos(caracteres: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digitos = String::new();
+    while let Some(digito) = caracteres.next_if(char::is_ascii_digit) {
+        digitos.push(digito);
+    }
+    digitos
+}
+
+/// Compila el patrón de un `LIKE`, `ILIKE` o `REGEXP`, según el operador.
+fn compilar_patron(operador: &Operador, patron: &str) -> Result<Patron, errores::Errores> {
+    match operador {
+        Operador::ILike => Patron::ilike(patron),
+        Operador::Regexp => Patron::regexp(patron),
+        _ => Patron::like(patron),
+    }
+}
+
+/// Aplica una función escalar a sus argumentos ya evaluados.
+///
+/// # Parámetros
+/// - `nombre`: El nombre de la función en minúsculas.
+/// - `argumentos`: Los valores de los argumentos.
+///
+/// # Retorno
+/// Retorna el `Valor` resultante o `Errores::InvalidSyntax` si los argumentos no son válidos.
+fn evaluar_funcion(nombre: &str, mut argumentos: Vec<Valor>) -> Result<Valor, errores::Errores> {
+    match nombre {
+        "iif" | "if" => {
+            let valor_no = argumentos.pop();
+            let valor_si = argumentos.pop();
+            match (argumentos.pop(), valor_si, valor_no) {
+                (Some(Valor::Booleano(condicion)), Some(valor_si), Some(valor_no)) => {
+                    Ok(if condicion { valor_si } else { valor_no })
+                }
+                (Some(Valor::Nulo), Some(_), Some(valor_no)) => Ok(valor_no),
+                _ => Err(errores::Errores::InvalidSyntax),
+            }
+        }
+        // REPLACE(texto, buscado, reemplazo): reemplaza todas las apariciones de `buscado`.
+        // Los números se tratan como su texto; si algún argumento es nulo, el resultado también.
+        "replace" => {
+            if argumentos.contains(&Valor::Nulo) {
+                return Ok(Valor::Nulo);
+            }
+            let textos: Vec<String> = argumentos.iter().map(Valor::a_texto).collect();
+            match textos.as_slice() {
+                [texto, buscado, _] if buscado.is_empty() => Ok(Valor::Texto(texto.to_string())),
+                [texto, buscado, reemplazo] => Ok(Valor::Texto(texto.replace(buscado, reemplazo))),
+                _ => Err(errores::Errores::InvalidSyntax),
+            }
+        }
+        // CURRENT_DATE (con o sin paréntesis) y NOW(): la fecha, o la fecha y hora, actuales en
+        // UTC, como texto que se compara cronológicamente con las fechas `YYYY-MM-DD`.
+        "current_date" => Ok(Valor::Texto(fecha_actual())),
+        "now" => Ok(Valor::Texto(fecha_hora_actual())),
+        FUNCION_SIN_MAYUSCULAS => Ok(argumentos.pop().map_or(Valor::Nulo, plegar_valor)),
+        // RANDOM(): un decimal al azar en [0, 1), distinto para cada registro.
+        "random" => Ok(Valor::Decimal(aleatorio::decimal_aleatorio())),
+        // YEAR, MONTH y DAY(fecha): la parte de la fecha como número.
+        "year" | "month" | "day" => match argumentos.pop() {
+            Some(Valor::Nulo) => Ok(Valor::Nulo),
+            Some(valor) => {
+                let (anio, mes, dia) = fecha_de(&valor)?;
+                Ok(Valor::Entero(match nombre {
+                    "year" => anio as i128,
+                    "month" => mes as i128,
+                    _ => dia as i128,
+                }))
+            }
+            None => Err(errores::Errores::InvalidSyntax),
+        },
+        // DATE_ADD y DATE_SUB(fecha, dias): la fecha que resulta de sumar o restar los días,
+        // que debe caer entre los años 0000 y 9999 para poder escribirse como `YYYY-MM-DD`.
+        "date_add" | "date_sub" => {
+            if argumentos.contains(&Valor::Nulo) {
+                return Ok(Valor::Nulo);
+            }
+            let dias = match argumentos.pop() {
+                Some(Valor::Entero(dias)) => dias,
+                Some(Valor::Texto(texto)) => texto
+                    .parse::<i128>()
+                    .map_err(|_| errores::Errores::InvalidSyntax)?,
+                _ => return Err(errores::Errores::InvalidSyntax),
+            };
+            let fecha = argumentos.pop().unwrap_or(Valor::Nulo);
+            let (anio, mes, dia) = fecha_de(&fecha)?;
+            let resultado = if nombre == "date_add" {
+                (dias_desde_civil(anio, mes, dia) as i128).checked_add(dias)
+            } else {
+                (dias_desde_civil(anio, mes, dia) as i128).checked_sub(dias)
+            };
+            let rango = dias_desde_civil(0, 1, 1) as i128..=dias_desde_civil(9999, 12, 31) as i128;
+            match resultado {
+                Some(resultado) if rango.contains(&resultado) => {
+                    let (anio, mes, dia) = civil_desde_dias(resultado as i64);
+                    Ok(Valor::Texto(format!("{:04}-{:02}-{:02}", anio, mes, dia)))
+                }
+                _ => Err(errores::Errores::ValorInvalido(format!(
+                    "el resultado de {}('{}', {}) queda fuera de los años 0000 a 9999",
+                    nombre.to_uppercase(),
+                    fecha.a_texto(),
+                    dias
+                ))),
+            }
+        }
+        // ABS(numero), ROUND(numero[, decimales]) y MOD(dividendo, divisor): los enteros dan
+        // enteros y el resto de los números, decimales. ROUND redondea alejándose del cero y
+        // acepta decimales negativos (ROUND(1234, -2) es 1200); MOD por cero es nulo.
+        "abs" | "round" | "mod" => {
+            let argumentos = argumentos
+                .into_iter()
+                .map(numero_de)
+                .collect::<Result<Vec<Valor>, errores::Errores>>()?;
+            if argumentos.contains(&Valor::Nulo) {
+                return Ok(Valor::Nulo);
+            }
+            match (nombre, argumentos.as_slice()) {
+                ("abs", [Valor::Entero(numero)]) => {
+                    numero.checked_abs().map(Valor::Entero).ok_or_else(|| {
+                        errores::Errores::ValorInvalido(format!(
+                            "el resultado de ABS({}) excede el rango de los enteros",
+                            numero
+                        ))
+                    })
+                }
+                ("round", [Valor::Entero(numero)]) => Ok(Valor::Entero(*numero)),
+                ("round", [Valor::Entero(numero), Valor::Entero(decimales)]) if *decimales >= 0 => {
+                    Ok(Valor::Entero(*numero))
+                }
+                ("round", [Valor::Entero(numero), Valor::Entero(decimales)]) => {
+                    let escala = 10f64.powi(decimales.unsigned_abs().min(18) as i32);
+                    Ok(Valor::Entero(
+                        ((*numero as f64 / escala).round() * escala) as i128,
+                    ))
+                }
+                ("round", [numero, Valor::Entero(decimales)]) => {
+                    let escala = 10f64.powi((*decimales).clamp(-18, 18) as i32);
+                    let numero = numero.a_numero().unwrap_or_default();
+                    Ok(Valor::Decimal((numero * escala).round() / escala))
+                }
+                ("mod", [_, Valor::Entero(0)]) => Ok(Valor::Nulo),
+                ("mod", [Valor::Entero(dividendo), Valor::Entero(divisor)]) => {
+                    Ok(Valor::Entero(dividendo.checked_rem(*divisor).unwrap_or(0)))
+                }
+                ("abs", [numero]) => {
+                    Ok(Valor::Decimal(numero.a_numero().unwrap_or_default().abs()))
+                }
+                ("round", [numero]) => Ok(Valor::Decimal(
+                    numero.a_numero().unwrap_or_default().round(),
+                )),
+                ("mod", [dividendo, divisor]) => {
+                    let divisor = divisor.a_numero().unwrap_or_default();
+                    if divisor == 0.0 {
+                        return Ok(Valor::Nulo);
+                    }
+                    Ok(Valor::Decimal(
+                        dividendo.a_numero().unwrap_or_default() % divisor,
+                    ))
+                }
+                _ => Err(errores::Errores::InvalidSyntax),
+            }
+        }
+        // LENGTH(texto): la cantidad de caracteres (no de bytes) del texto.
+        "length" => match argumentos.pop() {
+            Some(Valor::Nulo) => Ok(Valor::Nulo),
+            Some(valor) => Ok(Valor::Entero(valor.a_texto().chars().count() as i128)),
+            None => Err(errores::Errores::InvalidSyntax),
+        },
+        _ => Err(errores::Errores::InvalidSyntax),
+    }
+}
+
+/// Interpreta el argumento de una función numérica: los enteros se conservan y el resto de los
+/// números, incluidos los textos numéricos como los campos con decimales, se toman como
+/// decimales.
+///
+/// # Retorno
+/// El número, nulo si el valor es nulo, o `Errores::InvalidSyntax` si no es un número.
+fn numero_de(valor: Valor) -> Result<Valor, errores::Errores> {
+    match valor {
+        Valor::Entero(_) | Valor::Decimal(_) | Valor::Nulo => Ok(valor),
+        Valor::Texto(ref texto) => match texto.parse::<i128>() {
+            Ok(numero) => Ok(Valor::Entero(numero)),
+            Err(_) => valor
+                .a_numero()
+                .map(Valor::Decimal)
+                .ok_or(errores::Errores::InvalidSyntax),
+        },
+        Valor::Booleano(_) => Err(errores::Errores::InvalidSyntax),
+    }
+}
+
+/// Interpreta el argumento de una función de fechas: una fecha `YYYY-MM-DD`, que puede estar
+/// seguida de la hora, como el resultado de `NOW()`.
+///
+/// # Retorno
+/// La tupla `(anio, mes, dia)`, o `Errores::TipoInvalido` si el valor no es una fecha.
+fn fecha_de(valor: &Valor) -> Result<(i64, u32, u32), errores::Errores> {
+    let texto = valor.a_texto();
+    texto
+        .split([' ', 'T'])
+        .next()
+        .and_then(parsear_fecha)
+        .ok_or_else(|| {
+            errores::Errores::TipoInvalido(format!("'{}' no es una fecha YYYY-MM-DD", texto))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consulta::mapear_campos;
+    use crate::lexico::analizar;
+    use crate::patron::LONGITUD_MAXIMA_PATRON;
+
+    fn campos() -> HashMap<String, usize> {
+        mapear_campos(&[
+            "nombre".to_string(),
+            "edad".to_string(),
+            "ciudad".to_string(),
+        ])
+    }
+
+    fn fila(campos: &[&str]) -> Vec<Option<String>> {
+        campos.iter().map(|campo| Some(campo.to_string())).collect()
+    }
+
+    fn registro() -> Vec<Option<String>> {
+        fila(&["Luis", "34", "Madrid"])
+    }
+
+    fn parsear(expresion: &str) -> Expresion {
+        Expresion::parsear(&analizar(expresion)).unwrap()
+    }
+
+    #[test]
+    fn test_parsear_precedencia_de_operadores() {
+        let expresion = parsear("edad > 30 OR edad < 18 AND NOT ciudad = 'Madrid'");
+        match expresion {
+            Expresion::Binaria(_, Operador::Or, derecha) => {
+                assert!(matches!(*derecha, Expresion::Binaria(_, Operador::And, _)))
+            }
+            _ => panic!("Se esperaba un OR en la raíz"),
+        }
+    }
+
+    #[test]
+    fn test_collate_nocase() {
+        let campos = campos();
+        let registro = fila(&["José", "34", "STRASSE"]);
+        let cumple = |expresion: &str| {
+            let expresion = parsear(expresion);
+            let compilada = expresion.compilar(&campos).unwrap();
+            let cumple = compilada.cumple(&registro).unwrap();
+            assert_eq!(expresion.cumple(&campos, &registro).unwrap(), cumple);
+            cumple
+        };
+        assert!(!cumple("nombre = 'JOSÉ'"));
+        assert!(cumple("nombre COLLATE NOCASE = 'JOSÉ'"));
+        assert!(cumple("'JOSÉ' COLLATE NOCASE = nombre"));
+        assert!(cumple("ciudad = 'Straße' COLLATE NOCASE"));
+        assert!(!cumple("nombre COLLATE BINARY = 'JOSÉ'"));
+        assert!(cumple("nombre COLLATE NOCASE LIKE 'JO%'"));
+        assert!(cumple("nombre COLLATE NOCASE > 'ana'"));
+        assert!(!cumple("nombre > 'ana'"));
+
+        assert_eq!(
+            parsear("nombre COLLATE NOCASE = 'JOSÉ'").to_string(),
+            "(nombre COLLATE NOCASE = 'josé')"
+        );
+        assert_eq!(
+            parsear("nombre COLLATE NOCASE")
+                .evaluar(&campos, &registro)
+                .unwrap(),
+            Valor::Texto("josé".to_string())
+        );
+        assert!(Expresion::parsear(&analizar("nombre COLLATE es_AR = 'x'")).is_err());
+        assert!(Expresion::parsear(&analizar("collate nocase(nombre)")).is_err());
+    }
+
+    #[test]
+    fn test_expresion_compilada_evalua_igual_que_la_original() {
+        let campos = campos();
+        let registros = [
+            registro(),
+            fila(&["Ana", "17", "Sevilla"]),
+            vec![Some("Eva".to_string()), None, None],
+        ];
+        for texto in [
+            "edad > 30 AND NOT ciudad = 'Madrid'",
+            "nombre LIKE '%a' OR ciudad LIKE 'M%'",
+            "IIF(edad < 18, 'menor', ciudad)",
+            "ciudad IS NULL",
+            "nombre LIKE ciudad",
+            "ciudad ~ '^[A-Z]' OR nombre REGEXP ciudad",
+        ] {
+            let expresion = parsear(texto);
+            let compilada = expresion.compilar(&campos).unwrap();
+            for registro in &registros {
+                assert_eq!(
+                    compilada.evaluar(registro).unwrap(),
+                    expresion.evaluar(&campos, registro).unwrap()
+                );
+            }
+        }
+        assert_eq!(
+            parsear("sueldo > 3").compilar(&campos).unwrap_err(),
+            errores::Errores::ColumnaDesconocida {
+                columna: "sueldo".to_string(),
+                sugerencias: Vec::new()
+            }
+        );
+        assert!(matches!(
+            parsear(&format!(
+                "nombre LIKE '{}'",
+                "%".repeat(LONGITUD_MAXIMA_PATRON + 1)
+            ))
+            .compilar(&campos),
+            Err(errores::Errores::PatronInvalido(_))
+        ));
+        assert_eq!(
+            parsear("COUNT(*)").compilar(&campos).unwrap_err(),
+            errores::Errores::InvalidSyntax
+        );
+    }
+
+    #[test]
+    fn test_evaluar_condiciones() {
+        let campos = campos();
+        let registro = registro();
+
+        assert!(parsear("edad > 30").cumple(&campos, &registro).unwrap());
+        assert!(parsear("ciudad = 'Madrid' AND NOT edad < 18")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(!parsear("(edad < 30 OR ciudad = 'Sevilla')")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(!parsear("ciudad = 'madrid'")
+            .cumple(&campos, &registro)
+            .unwrap());
+
+        assert!(parsear("edad >= 34 AND edad <= 34")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(!parsear("edad >= 35").cumple(&campos, &registro).unwrap());
+        assert!(parsear("edad != 35 AND ciudad <> 'Sevilla'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(!parsear("nombre != 'Luis'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad <= 'Madrid'")
+            .cumple(&campos, &registro)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_comparar_enteros_de_64_bits() {
+        let campos = campos();
+        let registro = fila(&["Luis", "3000000000", "99999999999999999999"]);
+
+        assert!(parsear("edad > 2147483647 AND edad < 3000000001")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("edad = 3000000000")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad > 9223372036854775807")
+            .cumple(&campos, &registro)
+            .unwrap());
+        let registro = fila(&["Luis", "9223372036854775807", "99999999999999999998"]);
+        assert!(parsear("edad > 9223372036854775806")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad > edad AND ciudad > 1.5")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad = 99999999999999999998")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(
+            parsear("ciudad < 99999999999999999999999 AND edad < ciudad")
+                .cumple(&campos, &registro)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_evaluar_numeros_negativos_y_decimales() {
+        let campos = campos();
+        let registro = fila(&["Luis", "-3", "10.5"]);
+
+        assert!(parsear("edad > -5 AND edad < -2.5")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad > 3.14 AND ciudad > 9")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad = 10.50")
+            .cumple(&campos, &registro)
+            .unwrap());
+        // Un texto que no es un número, o dos textos, se comparan como texto.
+        assert!(parsear("nombre > 3.14 AND ciudad < '9'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert_eq!(parsear("edad > -0.5").to_string(), "(edad > -0.5)");
+    }
+
+    #[test]
+    fn test_evaluar_like() {
+        let campos = campos();
+        let registro = registro();
+
+        assert!(parsear("ciudad LIKE 'Ma%'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("NOT nombre LIKE '_u'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad ILIKE 'MA%' AND NOT ciudad LIKE 'MA%'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        let registro = fila(&["Luis", "34", "Madrid\\"]);
+        assert!(matches!(
+            parsear("nombre LIKE ciudad").cumple(&campos, &registro),
+            Err(errores::Errores::PatronInvalido(_))
+        ));
+    }
+
+    #[test]
+    fn test_evaluar_regexp() {
+        let campos = campos();
+        let registro = registro();
+
+        assert!(parsear("ciudad ~ '^M.d'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("nombre REGEXP 'i(s|z)$' AND NOT ciudad ~ '[0-9]'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(!parsear("nombre ~ 'lu'").cumple(&campos, &registro).unwrap());
+        assert_eq!(parsear("ciudad ~ 'a'").to_string(), "(ciudad REGEXP 'a')");
+    }
+
+    #[test]
+    fn test_comparar_columnas_segun_su_tipo_declarado() {
+        let campos = mapear_campos(&[
+            "codigo".to_string(),
+            "fecha_pedido".to_string(),
+            "fecha_envio".to_string(),
+            "cantidad".to_string(),
+        ]);
+        let tipos = HashMap::from([
+            ("codigo".to_string(), TipoColumna::Texto),
+            ("fecha_pedido".to_string(), TipoColumna::Fecha),
+            ("fecha_envio".to_string(), TipoColumna::Fecha),
+            ("cantidad".to_string(), TipoColumna::Entero),
+        ]);
+        let compilar = |expresion: &str| parsear(expresion).compilar_con_tipos(&campos, &tipos);
+        let registro = fila(&["007", "2024-01-31", "2024-02-01", "12"]);
+
+        assert!(compilar("fecha_envio > fecha_pedido")
+            .unwrap()
+            .cumple(&registro)
+            .unwrap());
+        assert!(compilar("codigo = '007' AND cantidad > 9")
+            .unwrap()
+            .cumple(&registro)
+            .unwrap());
+        assert_eq!(
+            compilar("codigo").unwrap().evaluar(&registro).unwrap(),
+            Valor::Texto("007".to_string())
+        );
+        assert!(matches!(
+            compilar("fecha_envio > cantidad"),
+            Err(errores::Errores::TipoInvalido(_))
+        ));
+        assert!(matches!(
+            compilar("fecha_pedido < 'mañana'"),
+            Err(errores::Errores::TipoInvalido(_))
+        ));
+        let registro = fila(&["007", "31/01/2024", "2024-02-01", "12"]);
+        assert_eq!(
+            compilar("fecha_envio > fecha_pedido")
+                .unwrap()
+                .cumple(&registro),
+            Err(errores::Errores::TipoInvalido(
+                "'31/01/2024' no es un valor de tipo DATE".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_comparar_con_literal_de_otro_tipo_es_invalido() {
+        let campos = campos();
+        let tipos = HashMap::from([
+            ("nombre".to_string(), TipoColumna::Texto),
+            ("edad".to_string(), TipoColumna::Entero),
+        ]);
+        let compilar = |expresion: &str| parsear(expresion).compilar_con_tipos(&campos, &tipos);
+
+        assert_eq!(
+            compilar("nombre = 5").unwrap_err(),
+            errores::Errores::TipoInvalido(
+                "no se puede comparar 'nombre' (TEXT) con 5".to_string()
+            )
+        );
+        assert_eq!(
+            compilar("edad > 'treinta'").unwrap_err(),
+            errores::Errores::TipoInvalido(
+                "no se puede comparar 'edad' (INTEGER) con 'treinta'".to_string()
+            )
+        );
+        assert!(
+            compilar("nombre = 'Luis' AND edad > '30' AND edad < 40.5 AND edad != NULL").is_ok()
+        );
+        // Sin tipo declarado, la columna se compara según el contenido de cada campo.
+        assert!(compilar("ciudad = 5").is_ok());
+    }
+
+    #[test]
+    fn test_evaluar_con_nulos() {
+        let campos = campos();
+        let registro = vec![Some("Ana".to_string()), None, Some(String::new())];
+
+        assert!(parsear("edad IS NULL").cumple(&campos, &registro).unwrap());
+        assert!(parsear("ciudad IS NOT NULL")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear("ciudad = ''").cumple(&campos, &registro).unwrap());
+        assert!(!parsear("edad = NULL").cumple(&campos, &registro).unwrap());
+        assert!(!parsear("edad != 3").cumple(&campos, &registro).unwrap());
+        assert!(!parsear("NOT edad > 3").cumple(&campos, &registro).unwrap());
+        assert!(parsear("edad > 3 OR nombre = 'Ana'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert_eq!(
+            parsear("edad > 3 AND nombre = 'Ana'")
+                .evaluar(&campos, &registro)
+                .unwrap(),
+            Valor::Nulo
+        );
+        assert_eq!(
+            parsear("IIF(edad > 3, 'si', 'no')")
+                .evaluar(&campos, &registro)
+                .unwrap(),
+            Valor::Texto("no".to_string())
+        );
+    }
+
+    #[test]
+    fn test_comparar_con_literal_con_comilla_escapada() {
+        let campos = campos();
+        let registro = fila(&["O'Brien", "40", "Dublin"]);
+
+        assert!(parsear("nombre = 'O''Brien'")
+            .cumple(&campos, &registro)
+            .unwrap());
+        assert!(parsear(r"nombre = 'O\'Brien'")
+            .cumple(&campos, &registro)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_mostrar_expresion_con_su_forma() {
+        assert_eq!(
+            parsear("edad > 30 OR edad < 18 AND NOT ciudad = 'O''Higgins'").to_string(),
+            "((edad > 30) OR ((edad < 18) AND (NOT (ciudad = 'O''Higgins'))))"
+        );
+        assert_eq!(
+            parsear("IIF(ciudad IS NULL, 'sin ciudad', ciudad)").to_string(),
+            "IIF((ciudad IS NULL), 'sin ciudad', ciudad)"
+        );
+        assert_eq!(parsear("COUNT(*)").to_string(), "COUNT(*)");
+        assert_eq!(
+            parsear("dense_rank() over (order by edad desc, nombre)").to_string(),
+            "DENSE_RANK() OVER (ORDER BY edad DESC, nombre ASC)"
+        );
+        assert_eq!(
+            parsear("ROW_NUMBER() OVER ()").to_string(),
+            "ROW_NUMBER() OVER ()"
+        );
+        assert_eq!(
+            parsear("RANK() OVER (PARTITION BY ciudad, IIF(edad > 30, 'a', 'b') ORDER BY edad)")
+                .to_string(),
+            "RANK() OVER (PARTITION BY ciudad, IIF((edad > 30), 'a', 'b') ORDER BY edad ASC)"
+        );
+    }
+
+    #[test]
+    fn test_evaluar_iif() {
+        let campos = campos();
+        let registro = registro();
+
+        let expresion = parsear("IIF(edad > 30, 'mayor', nombre)");
+        assert_eq!(
+            expresion.evaluar(&campos, &registro).unwrap(),
+            Valor::Texto("mayor".to_string())
+        );
+        let expresion = parsear("IF(edad < 30, 'joven', edad)");
+        assert_eq!(
+            expresion.evaluar(&campos, &registro).unwrap(),
+            Valor::Entero(34)
+        );
+    }
+
+    #[test]
+    fn test_evaluar_replace() {
+        let campos = campos();
+        let registro = fila(&["Luis", "34", "555-12-34"]);
+        let evaluar = |expresion: &str| parsear(expresion).evaluar(&campos, &registro).unwrap();
+
+        assert_eq!(
+            evaluar("REPLACE(ciudad, '-', '.')"),
+            Valor::Texto("555.12.34".to_string())
+        );
+        assert_eq!(
+            evaluar("replace(edad, 3, 'tres')"),
+            Valor::Texto("tres4".to_string())
+        );
+        assert_eq!(
+            evaluar("REPLACE(nombre, '', 'x')"),
+            Valor::Texto("Luis".to_string())
+        );
+        assert_eq!(evaluar("REPLACE(nombre, 'u', NULL)"), Valor::Nulo);
+        assert!(Expresion::parsear(&analizar("REPLACE(nombre, 'u')")).is_err());
+    }
+
+    #[test]
+    fn test_evaluar_length() {
+        let campos = campos();
+        let registro = vec![Some("Begoña".to_string()), Some("34".to_string()), None];
+        let evaluar = |expresion: &str| parsear(expresion).evaluar(&campos, &registro).unwrap();
+
+        assert_eq!(evaluar("LENGTH(nombre)"), Valor::Entero(6));
+        assert_eq!(evaluar("length(edad)"), Valor::Entero(2));
+        assert_eq!(evaluar("LENGTH(ciudad)"), Valor::Nulo);
+        assert_eq!(evaluar("LENGTH(nombre) < 32"), Valor::Booleano(true));
+    }
+
+    #[test]
+    fn test_evaluar_fecha_actual() {
+        let campos = campos();
+        let registro = fila(&["Luis", "34", "2000-01-01"]);
+        let evaluar = |expresion: &str| parsear(expresion).evaluar(&campos, &registro).unwrap();
+
+        assert_eq!(evaluar("CURRENT_DATE"), Valor::Texto(fecha_actual()));
+        assert_eq!(evaluar("current_date()"), Valor::Texto(fecha_actual()));
+        assert_eq!(evaluar("ciudad >= CURRENT_DATE"), Valor::Booleano(false));
+        assert_eq!(evaluar("NOW() > ciudad"), Valor::Booleano(true));
+        assert_eq!(parsear("CURRENT_DATE").to_string(), "CURRENT_DATE");
+        assert_eq!(parsear("now()").to_string(), "NOW()");
+        assert!(Expresion::parsear(&analizar("NOW(1)")).is_err());
+    }
+
+    #[test]
+    fn test_evaluar_funciones_de_fechas() {
+        let campos = campos();
+        let registro = vec![
+            Some("Luis".to_string()),
+            Some("30".to_string()),
+            Some("2024-02-15".to_string()),
+        ];
+        let evaluar = |expresion: &str| parsear(expresion).evaluar(&campos, &registro);
+
+        assert_eq!(evaluar("YEAR(ciudad)").unwrap(), Valor::Entero(2024));
+        assert_eq!(evaluar("MONTH(ciudad)").unwrap(), Valor::Entero(2));
+        assert_eq!(
+            evaluar("DAY('2024-02-15 10:30:00')").unwrap(),
+            Valor::Entero(15)
+        );
+        assert_eq!(
+            evaluar("DATE_ADD(ciudad, edad)").unwrap(),
+            Valor::Texto("2024-03-16".to_string())
+        );
+        assert_eq!(
+            evaluar("DATE_SUB(ciudad, 46)").unwrap(),
+            Valor::Texto("2023-12-31".to_string())
+        );
+        assert_eq!(evaluar("DATE_SUB(NULL, 1)").unwrap(), Valor::Nulo);
+        assert_eq!(
+            evaluar("ciudad >= DATE_SUB(CURRENT_DATE, 30)").unwrap(),
+            Valor::Booleano(false)
+        );
+        assert!(matches!(
+            evaluar("YEAR(nombre)"),
+            Err(errores::Errores::TipoInvalido(_))
+        ));
+        assert_eq!(
+            evaluar("DATE_ADD(ciudad, 'x')"),
+            Err(errores::Errores::InvalidSyntax)
+        );
+        assert_eq!(
+            evaluar("DATE_ADD('9999-12-30', 1)").unwrap(),
+            Valor::Texto("9999-12-31".to_string())
+        );
+        assert_eq!(
+            evaluar("DATE_ADD('9999-12-31', 1)"),
+            Err(errores::Errores::ValorInvalido(
+                "el resultado de DATE_ADD('9999-12-31', 1) queda fuera de los años 0000 a 9999"
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            evaluar("DATE_SUB('0000-01-01', 0)").unwrap(),
+            Valor::Texto("0000-01-01".to_string())
+        );
+        for expresion in [
+            "DATE_SUB('0000-01-01', 1)",
+            "DATE_ADD(ciudad, 9223372036854775807)",
+            "DATE_SUB(ciudad, 99999999999999999999999)",
+        ] {
+            assert!(
+                matches!(evaluar(expresion), Err(errores::Errores::ValorInvalido(_))),
+                "{}",
+                expresion
+            );
+        }
+    }
+
+    #[test]
+    fn test_evaluar_funciones_numericas() {
+        let campos = campos();
+        let registro = fila(&["Luis", "-34", "2.675"]);
+        let evaluar = |expresion: &str| parsear(expresion).evaluar(&campos, &registro);
+
+        assert_eq!(evaluar("ABS(edad)").unwrap(), Valor::Entero(34));
+        assert_eq!(evaluar("ABS(-1.5)").unwrap(), Valor::Decimal(1.5));
+        assert_eq!(evaluar("ROUND(ciudad)").unwrap(), Valor::Decimal(3.0));
+        assert_eq!(evaluar("ROUND(ciudad, 1)").unwrap(), Valor::Decimal(2.7));
+        assert_eq!(evaluar("ROUND(1250, -2)").unwrap(), Valor::Entero(1300));
+        assert_eq!(evaluar("ROUND(edad, 2)").unwrap(), Valor::Entero(-34));
+        assert_eq!(evaluar("MOD(edad, 5)").unwrap(), Valor::Entero(-4));
+        assert_eq!(evaluar("MOD(7.5, 2)").unwrap(), Valor::Decimal(1.5));
+        assert_eq!(evaluar("MOD(edad, 0)").unwrap(), Valor::Nulo);
+        assert_eq!(evaluar("ABS(NULL)").unwrap(), Valor::Nulo);
+        assert_eq!(evaluar("ABS(nombre)"), Err(errores::Errores::InvalidSyntax));
+        assert_eq!(
+            evaluar("ROUND(1.5, 0.5)"),
+            Err(errores::Errores::InvalidSyntax)
+        );
+        assert!(Expresion::parsear(&analizar("ROUND(1, 2, 3)")).is_err());
+        assert!(Expresion::parsear(&analizar("MOD(1)")).is_err());
+    }
+
+    #[test]
+    fn test_evaluar_operaciones_aritmeticas() {
+        let campos = campos();
+        let registro = fila(&["Luis", "34", "2.5"]);
+        let evaluar = |expresion: &str| parsear(expresion).evaluar(&campos, &registro);
+
+        assert_eq!(evaluar("edad*2 + 1").unwrap(), Valor::Entero(69));
+        assert_eq!(evaluar("edad - 4 - 10").unwrap(), Valor::Entero(20));
+        assert_eq!(evaluar("edad / 4").unwrap(), Valor::Entero(8));
+        assert_eq!(evaluar("edad * ciudad").unwrap(), Valor::Decimal(85.0));
+        assert_eq!(evaluar("(edad + 2) / 8.0").unwrap(), Valor::Decimal(4.5));
+        assert_eq!(evaluar("edad / 0").unwrap(), Valor::Nulo);
+        assert_eq!(evaluar("edad + NULL").unwrap(), Valor::Nulo);
+        assert_eq!(evaluar("edad * 2 > 60").unwrap(), Valor::Booleano(true));
+        assert_eq!(
+            evaluar("nombre + 1"),
+            Err(errores::Errores::TipoInvalido(
+                "no se puede aplicar '+' a 'Luis', que no es un número".to_string()
+            ))
+        );
+        assert!(matches!(
+            evaluar("170141183460469231731687303715884105727 + edad"),
+            Err(errores::Errores::ValorInvalido(_))
+        ));
+        assert_eq!(
+            parsear("edad * 2 - ciudad / 2 > 0").to_string(),
+            "(((edad * 2) - (ciudad / 2)) > 0)"
+        );
+        assert!(Expresion::parsear(&analizar("edad-1")).is_err());
+        assert!(Expresion::parsear(&analizar("edad *")).is_err());
+    }
+
+    #[test]
+    fn test_iif_con_condicion_no_booleana_es_invalido() {
+        let expresion = parsear("IIF(edad, 'a', 'b')");
+        assert_eq!(
+            expresion.evaluar(&campos(), &registro()),
+            Err(errores::Errores::InvalidSyntax)
+        );
+    }
+
+    #[test]
+    fn test_parsear_expresiones_invalidas() {
+        for expresion in [
+            "IIF(edad > 30, 'a')",
+            "edad >",
+            "(edad > 3",
+            "desconocida(1)",
+            "'abc",
+        ] {
+            assert!(
+                Expresion::parsear(&analizar(expresion))
+                    .unwrap_err()
+                    .es_de_sintaxis(),
+                "{}",
+                expresion
+            );
+        }
+    }
+
+    #[test]
+    fn test_evaluar_agregaciones_sobre_un_grupo() {
+        let campos = campos();
+        let registros = vec![
+            registro(),
+            fila(&["Ana", "29", "Madrid"]),
+            vec![Some("Eva".to_string()), None, Some("Madrid".to_string())],
+        ];
+
+        let evaluar = |expresion: &str| parsear(expresion).evaluar_grupo(&campos, &registros);
+        assert_eq!(evaluar("COUNT(*)").unwrap(), Valor::Entero(3));
+        assert_eq!(evaluar("COUNT(edad)").unwrap(), Valor::Entero(2));
+        assert_eq!(evaluar("SUM(edad)").unwrap(), Valor::Entero(63));
+        assert_eq!(evaluar("MIN(nombre)").unwrap(), Valor::Texto("Ana".into()));
+        assert_eq!(evaluar("MAX(edad)").unwrap(), Valor::Entero(34));
+        assert_eq!(evaluar("AVG(edad)").unwrap(), Valor::Decimal(31.5));
+        assert_eq!(evaluar("SUM(1.5)").unwrap(), Valor::Decimal(4.5));
+        assert_eq!(evaluar("VARIANCE(edad)").unwrap(), Valor::Decimal(12.5));
+        assert_eq!(evaluar("STDDEV(2)").unwrap(), Valor::Decimal(0.0));
+        assert_eq!(
+            evaluar("STDDEV(IIF(edad > 30, edad, NULL))").unwrap(),
+            Valor::Nulo
+        );
+        assert!(matches!(
+            evaluar("VARIANCE(nombre)"),
+            Err(errores::Errores::TipoInvalido(_))
+        ));
+        assert_eq!(
+            evaluar("IIF(COUNT(*) > 2, ciudad, 'pocos')").unwrap(),
+            Valor::Texto("Madrid".into())
+        );
+        assert!(matches!(
+            evaluar("SUM(nombre)"),
+            Err(errores::Errores::TipoInvalido(_))
+        ));
+        assert_eq!(
+            parsear("SUM(edad)").evaluar_grupo(
+                &campos,
+                &vec![fila(&["Ana", "9223372036854775807", "Madrid"]); 2]
+            ),
+            Ok(Valor::Entero(18446744073709551614))
+        );
+        assert_eq!(
+            parsear("SUM(edad)").evaluar_grupo(
+                &campos,
+                &vec![fila(&["Ana", "170141183460469231731687303715884105727", "Madrid"]); 2]
+            ),
+            Err(errores::Errores::ValorInvalido(
+                "el resultado de SUM excede el rango de los enteros".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_agregaciones_invalidas() {
+        for expresion in ["SUM(*)", "COUNT(SUM(edad))", "MAX()"] {
+            assert!(
+                Expresion::parsear(&analizar(expresion))
+                    .unwrap_err()
+                    .es_de_sintaxis(),
+                "{}",
+                expresion
+            );
+        }
+        assert_eq!(
+            parsear("COUNT(*) > 1").evaluar(&campos(), &registro()),
+            Err(errores::Errores::InvalidSyntax)
+        );
+    }
+
+    #[test]
+    fn test_columnas_no_agregadas() {
+        let expresion = parsear("IIF(SUM(edad) > 30, nombre, ciudad)");
+        assert!(expresion.contiene_agregacion());
+        assert_eq!(expresion.columnas_no_agregadas(), vec!["nombre", "ciudad"]);
+    }
+
+    #[test]
+    fn test_columnas_de_la_expresion() {
+        let expresion = parsear("IIF(edad > 30, nombre, 'x') = ciudad");
+        assert_eq!(expresion.columnas(), vec!["edad", "nombre", "ciudad"]);
+    }
+
+    #[test]
+    fn test_comparaciones_cuantificadas() {
+        let mut expresion = parsear("edad > ALL (SELECT edad FROM socios WHERE ciudad = 'Lugo')");
+        assert_eq!(
+            expresion.to_string(),
+            "(edad > ALL (select edad from socios where ciudad = 'Lugo'))"
+        );
+        assert_eq!(expresion.columnas(), vec!["edad"]);
+        assert_eq!(
+            expresion.evaluar(&campos(), &registro()),
+            Err(errores::Errores::InvalidSyntax)
+        );
+
+        let mut ejecutadas = Vec::new();
+        expresion
+            .materializar_subconsultas(&mut |sentencia| {
+                ejecutadas.push(sentencia.tabla.to_string());
+                Ok(vec![Valor::Entero(20), Valor::Entero(30)])
+            })
+            .unwrap();
+        assert_eq!(ejecutadas, vec!["socios"]);
+        assert!(expresion.cumple(&campos(), &registro()).unwrap());
+        let compilada = expresion.compilar(&campos()).unwrap();
+        assert!(!compilada.cumple(&fila(&["Ana", "25", "Lugo"])).unwrap());
+
+        let cuantificar = |operador, cuantificador, valores: &[Valor]| {
+            Expresion::cuantificar(&Valor::Entero(5), &operador, cuantificador, valores).unwrap()
+        };
+        let con_nulo = [Valor::Entero(5), Valor::Nulo];
+        assert_eq!(
+            cuantificar(Operador::Igual, Cuantificador::Alguno, &con_nulo),
+            Valor::Booleano(true)
+        );
+        assert_eq!(
+            cuantificar(Operador::Igual, Cuantificador::Todos, &con_nulo),
+            Valor::Nulo
+        );
+        assert_eq!(
+            cuantificar(Operador::Mayor, Cuantificador::Todos, &[]),
+            Valor::Booleano(true)
+        );
+        assert_eq!(
+            cuantificar(Operador::Mayor, Cuantificador::Alguno, &[]),
+            Valor::Booleano(false)
+        );
+
+        assert!(matches!(
+            parsear("edad = SOME (SELECT edad FROM socios)"),
+            Expresion::Cuantificada(_, Operador::Igual, Cuantificador::Alguno, _)
+        ));
+        assert!(Expresion::parsear(&analizar("edad > ALL (SELECT FROM socios)")).is_err());
+        assert!(
+            Expresion::parsear(&analizar("nombre LIKE ANY (SELECT nombre FROM socios)")).is_err()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SEGUNDOS_POR_DIA: u64 = 86_400;
+
+/// Devuelve la fecha actual (UTC) en formato `YYYY-MM-DD`.
+///
+/// Se calcula a partir de los segundos transcurridos desde la época Unix, sin depender
+/// de la zona horaria del sistema.
+///
+/// # Retorno
+/// Una cadena de texto (`String`) con la fecha actual.
+pub fn fecha_actual() -> String {
+    let (anio, mes, dia) = civil_desde_dias((segundos_actuales() / SEGUNDOS_POR_DIA) as i64);
+    format!("{:04}-{:02}-{:02}", anio, mes, dia)
+}
+
+/// Devuelve la fecha y hora actual (UTC) en formato `YYYY-MM-DD HH:MM:SS` (ver `fecha_hora`).
+pub fn fecha_hora_actual() -> String {
+    fecha_hora(segundos_actuales())
+}
+
+/// Arma la fecha y hora (UTC) en formato `YYYY-MM-DD HH:MM:SS`, que empieza con la fecha y se
+/// ordena cronológicamente al compararla como texto.
+///
+/// # Parámetros
+/// - `segundos`: Segundos transcurridos desde la época Unix.
+pub fn fecha_hora(segundos: u64) -> String {
+    let (anio, mes, dia) = civil_desde_dias((segundos / SEGUNDOS_POR_DIA) as i64);
+    let segundos_del_dia = segundos % SEGUNDOS_POR_DIA;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        anio,
+        mes,
+        dia,
+        segundos_del_dia / 3600,
+        segundos_del_dia / 60 % 60,
+        segundos_del_dia % 60
+    )
+}
+
+/// Devuelve la fecha y hora actual (UTC) en formato `YYYYMMDDHHMMSS`, para usar en nombres de
+/// archivo (ver `marca_de_tiempo`).
+pub fn marca_de_tiempo_actual() -> String {
+    marca_de_tiempo(segundos_actuales())
+}
+
+/// Arma la fecha y hora (UTC) en formato `YYYYMMDDHHMMSS`, que se ordena cronológicamente.
+///
+/// # Parámetros
+/// - `segundos`: Segundos transcurridos desde la época Unix.
+pub fn marca_de_tiempo(segundos: u64) -> String {
+    fecha_hora(segundos).replace(['-', ' ', ':'], "")
+}
+
+fn segundos_actuales() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duracion) => duracion.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+/// Convierte una cantidad de días desde la época Unix (1970-01-01) a una fecha del
+/// calendario gregoriano.
+///
+/// # Parámetros
+/// - `dias`: Días transcurridos desde 1970-01-01 (pueden ser negativos).
+///
+/// # Retorno
+/// Una tupla `(anio, mes, dia)`.
+pub fn civil_desde_dias(dias: i64) -> (i64, u32, u32) {
+    let z = dias + 719_468;
+    let era = z.div_euclid(146_097);
+    let dia_de_era = z.rem_euclid(146_097);
+    let anio_de_era =
+        (dia_de_era - dia_de_era / 1_460 + dia_de_era / 36_524 - dia_de_era / 146_096) / 365;
+    let dia_del_anio = dia_de_era - (365 * anio_de_era + anio_de_era / 4 - anio_de_era / 100);
+    let mes_desplazado = (5 * dia_del_anio + 2) / 153;
+    let dia = (dia_del_anio - (153 * mes_desplazado + 2) / 5 + 1) as u32;
+    let mes = if mes_desplazado < 10 {
+        mes_desplazado + 3
+    } else {
+        mes_desplazado - 9
+    } as u32;
+    let anio = anio_de_era + era * 400 + if mes <= 2 { 1 } else { 0 };
+    (anio, mes, dia)
+}
+
+/// Convierte una fecha del calendario gregoriano en la cantidad de días desde la época Unix
+/// (1970-01-01); es la inversa de `civil_desde_dias`.
+///
+/// # Parámetros
+/// - `anio`, `mes`, `dia`: La fecha, que se supone válida.
+///
+/// # Retorno
+/// Los días transcurridos desde 1970-01-01 (negativos para fechas anteriores).
+pub fn dias_desde_civil(anio: i64, mes: u32, dia: u32) -> i64 {
+    let anio = if mes <= 2 { anio - 1 } else { anio };
+    let era = anio.div_euclid(400);
+    let anio_de_era = anio.rem_euclid(400);
+    let mes_desplazado = if mes > 2 { mes - 3 } else { mes + 9 } as i64;
+    let dia_del_anio = (153 * mes_desplazado + 2) / 5 + dia as i64 - 1;
+    let dia_de_era = anio_de_era * 365 + anio_de_era / 4 - anio_de_era / 100 + dia_del_anio;
+    era * 146_097 + dia_de_era - 719_468
+}
+
+/// Interpreta una fecha en formato `YYYY-MM-DD`.
+///
+/// # Parámetros
+/// - `texto`: El texto de la fecha.
+///
+/// # Retorno
+/// La tupla `(anio, mes, dia)`, o `None` si el texto no tiene el formato o el día no existe en
+/// el calendario.
+pub fn parsear_fecha(texto: &str) -> Option<(i64, u32, u32)> {
+    let partes: Vec<&str> = texto.split('-').collect();
+    if partes.len() != 3
+        || [4, 2, 2] != [partes[0].len(), partes[1].len(), partes[2].len()]
+        || !partes
+            .iter()
+            .all(|parte| parte.bytes().all(|byte| byte.is_ascii_digit()))
+    {
+        return None;
+    }
+    let (Ok(anio), Ok(mes), Ok(dia)) = (
+        partes[0].parse::<i64>(),
+        partes[1].parse::<u32>(),
+        partes[2].parse::<u32>(),
+    ) else {
+        return None;
+    };
+    let bisiesto = anio % 4 == 0 && (anio % 100 != 0 || anio % 400 == 0);
+    let dias_del_mes = match mes {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if bisiesto => 29,
+        2 => 28,
+        _ => return None,
+    };
+    (1..=dias_del_mes)
+        .contains(&dia)
+        .then_some((anio, mes, dia))
+}
+
+/// Indica si el texto es una fecha válida en formato `YYYY-MM-DD`.
+///
+/// # Parámetros
+/// - `texto`: El texto a verificar.
+///
+/// # Retorno
+/// `true` si el texto tiene el formato y el día existe en el calendario.
+pub fn es_fecha_valida(texto: &str) -> bool {
+    parsear_fecha(texto).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_desde_dias() {
+        assert_eq!(civil_desde_dias(0), (1970, 1, 1));
+        assert_eq!(civil_desde_dias(59), (1970, 3, 1));
+        assert_eq!(civil_desde_dias(19_782), (2024, 2, 29));
+        assert_eq!(civil_desde_dias(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_dias_desde_civil() {
+        for dias in [0, 59, 19_782, -1, -719_468, 2_932_896] {
+            let (anio, mes, dia) = civil_desde_dias(dias);
+            assert_eq!(dias_desde_civil(anio, mes, dia), dias);
+        }
+        assert_eq!(parsear_fecha("2024-02-29"), Some((2024, 2, 29)));
+    }
+
+    #[test]
+    fn test_es_fecha_valida() {
+        assert!(es_fecha_valida("2024-02-29"));
+        assert!(es_fecha_valida(&fecha_actual()));
+        assert!(!es_fecha_valida("2023-02-29"));
+        assert!(!es_fecha_valida("2024-13-01"));
+        assert!(!es_fecha_valida("2024-1-05"));
+        assert!(!es_fecha_valida("05/01/2024"));
+    }
+
+    #[test]
+    fn test_marca_de_tiempo() {
+        assert_eq!(marca_de_tiempo(0), "19700101000000");
+        assert_eq!(marca_de_tiempo(1_709_217_045), "20240229143045");
+        assert_eq!(marca_de_tiempo_actual().len(), 14);
+        assert_eq!(fecha_hora(1_709_217_045), "2024-02-29 14:30:45");
+        assert!(fecha_hora_actual().starts_with(&fecha_actual()));
+    }
+
+    #[test]
+    fn test_fecha_actual_tiene_formato_iso() {
+        let fecha = fecha_actual();
+        let partes: Vec<&str> = fecha.split('-').collect();
+        assert_eq!(partes.len(), 3);
+        assert_eq!(partes[0].len(), 4);
+        assert_eq!(partes[1].len(), 2);
+        assert_eq!(partes[2].len(), 2);
+    }
+}
@@ -0,0 +1,575 @@
+use std::io::{self, BufRead, Read};
+
+/// Extensión de los archivos comprimidos con gzip.
+pub const EXTENSION_GZIP: &str = ".gz";
+
+const ID_GZIP: [u8; 2] = [0x1f, 0x8b];
+const METODO_DEFLATE: u8 = 8;
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NOMBRE: u8 = 0x08;
+const FLAG_COMENTARIO: u8 = 0x10;
+const MAXIMO_BLOQUE_ALMACENADO: usize = u16::MAX as usize;
+const MAXIMOS_BITS: usize = 15;
+/// Distancia máxima a la que una referencia de DEFLATE puede copiar datos ya descomprimidos.
+const VENTANA: usize = 32 * 1024;
+/// Cantidad de bytes que `Descompresor` descomprime por vez.
+const PORCION_SALIDA: usize = 64 * 1024;
+
+const BASE_LONGITUD: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const EXTRA_LONGITUD: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const BASE_DISTANCIA: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const EXTRA_DISTANCIA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+const ORDEN_LONGITUDES: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn error_formato(motivo: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("gzip inválido: {}", motivo),
+    )
+}
+
+/// Descomprime el contenido completo de un archivo gzip. Para leer archivos grandes sin
+/// cargarlos en memoria, ver `Descompresor`.
+///
+/// Admite archivos formados por varios miembros gzip concatenados, como los que resultan de
+/// agregar registros con `comprimir`.
+///
+/// # Parámetros
+/// - `datos`: El contenido del archivo comprimido.
+///
+/// # Retorno
+/// Retorna los datos descomprimidos o un error de E/S de tipo `InvalidData` si el archivo no
+/// es un gzip válido o su CRC no coincide.
+pub fn descomprimir(datos: &[u8]) -> io::Result<Vec<u8>> {
+    let mut salida = Vec::new();
+    Descompresor::new(datos).read_to_end(&mut salida)?;
+    Ok(salida)
+}
+
+/// Comprime los datos como un miembro gzip con bloques sin compresión.
+///
+/// El resultado se puede concatenar al final de un archivo gzip existente, por lo que sirve
+/// para agregar registros a una tabla comprimida sin reescribirla.
+///
+/// # Parámetros
+/// - `datos`: Los datos a comprimir.
+///
+/// # Retorno
+/// Los bytes del miembro gzip.
+pub fn comprimir(datos: &[u8]) -> Vec<u8> {
+    let mut salida = vec![
+        ID_GZIP[0],
+        ID_GZIP[1],
+        METODO_DEFLATE,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0xff,
+    ];
+    let mut bloques = datos.chunks(MAXIMO_BLOQUE_ALMACENADO).peekable();
+    if bloques.peek().is_none() {
+        salida.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(bloque) = bloques.next() {
+        let ultimo = bloques.peek().is_none();
+        let longitud = bloque.len() as u16;
+        salida.push(u8::from(ultimo));
+        salida.extend_from_slice(&longitud.to_le_bytes());
+        salida.extend_from_slice(&(!longitud).to_le_bytes());
+        salida.extend_from_slice(bloque);
+    }
+    salida.extend_from_slice(&crc32(datos).to_le_bytes());
+    salida.extend_from_slice(&(datos.len() as u32).to_le_bytes());
+    salida
+}
+
+/// Calcula el CRC-32 (IEEE 802.3) que gzip guarda al final de cada miembro.
+fn crc32(datos: &[u8]) -> u32 {
+    !actualizar_crc(!0, datos)
+}
+
+/// Agrega `datos` a un CRC-32 parcial, sin la inversión inicial ni la final, para calcularlo
+/// a medida que se descomprime un miembro.
+fn actualizar_crc(mut crc: u32, datos: &[u8]) -> u32 {
+    for byte in datos {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mascara = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mascara);
+        }
+    }
+    crc
+}
+
+/// Lector que descomprime un flujo gzip a medida que se lee, sin cargarlo completo en
+/// memoria: solo conserva la ventana de DEFLATE y la última porción descomprimida.
+///
+/// Admite varios miembros gzip concatenados, como los que resultan de agregar registros con
+/// `comprimir`, y verifica el CRC de cada uno al terminar de leerlo. Si el flujo no es un gzip
+/// válido, la lectura falla con un error de E/S de tipo `InvalidData`.
+///
+/// # Campos
+///
+/// - `lector`: Los bits del flujo comprimido.
+/// - `estado`: La parte del flujo que se está leyendo.
+/// - `ultimo_bloque`: Si el bloque actual es el último de su miembro.
+/// - `salida`: La ventana seguida de lo descomprimido que todavía no se entregó.
+/// - `entregado`: La posición en `salida` desde la que falta entregar.
+/// - `crc`: El CRC-32 parcial de lo descomprimido del miembro actual.
+pub struct Descompresor<R: BufRead> {
+    lector: LectorBits<R>,
+    estado: Estado,
+    ultimo_bloque: bool,
+    salida: Vec<u8>,
+    entregado: usize,
+    crc: u32,
+}
+
+/// La parte del flujo gzip que está leyendo un `Descompresor`.
+enum Estado {
+    /// El encabezado del próximo miembro, o el final del flujo.
+    Encabezado,
+    /// El encabezado del próximo bloque DEFLATE.
+    Bloque,
+    /// Un bloque sin compresión, con la cantidad de bytes que le faltan.
+    Almacenado(usize),
+    /// Un bloque comprimido con los códigos de literales y de distancias indicados.
+    Codificado(Huffman, Huffman),
+    /// El CRC y el tamaño al final del miembro.
+    Cola,
+    /// El final del flujo.
+    Terminado,
+}
+
+impl<R: BufRead> Descompresor<R> {
+    /// Crea un descompresor que lee el flujo gzip de `fuente`.
+    pub fn new(fuente: R) -> Descompresor<R> {
+        Descompresor {
+            lector: LectorBits::new(fuente),
+            estado: Estado::Encabezado,
+            ultimo_bloque: false,
+            salida: Vec::new(),
+            entregado: 0,
+            crc: !0,
+        }
+    }
+
+    /// Lee la próxima parte del flujo: un encabezado, la cola de un miembro o hasta
+    /// `PORCION_SALIDA` bytes de un bloque (algunos más si termina en una referencia).
+    fn avanzar(&mut self) -> io::Result<()> {
+        let Descompresor {
+            lector,
+            estado,
+            ultimo_bloque,
+            salida,
+            crc,
+            ..
+        } = self;
+        let inicio = salida.len();
+        let siguiente = match estado {
+            Estado::Encabezado if lector.agotado()? => Some(Estado::Terminado),
+            Estado::Encabezado => {
+                leer_encabezado(lector)?;
+                *crc = !0;
+                Some(Estado::Bloque)
+            }
+            Estado::Bloque => {
+                *ultimo_bloque = lector.leer(1)? == 1;
+                Some(match lector.leer(2)? {
+                    0 => Estado::Almacenado(leer_longitud_almacenada(lector)?),
+                    1 => {
+                        let (literales, distancias) = codigos_fijos();
+                        Estado::Codificado(literales, distancias)
+                    }
+                    2 => {
+                        let (literales, distancias) = codigos_dinamicos(lector)?;
+                        Estado::Codificado(literales, distancias)
+                    }
+                    _ => return Err(error_formato("tipo de bloque inválido")),
+                })
+            }
+            Estado::Almacenado(restantes) => {
+                let cantidad = (*restantes).min(PORCION_SALIDA);
+                for _ in 0..cantidad {
+                    salida.push(lector.leer(8)? as u8);
+                }
+                *restantes -= cantidad;
+                (*restantes == 0).then(|| fin_de_bloque(*ultimo_bloque))
+            }
+            Estado::Codificado(literales, distancias) => {
+                inflar_bloque(lector, salida, literales, distancias)?
+                    .then(|| fin_de_bloque(*ultimo_bloque))
+            }
+            Estado::Cola => {
+                lector.alinear();
+                let esperado = lector.leer(32)?;
+                lector.leer(32)?;
+                if esperado != !*crc {
+                    return Err(error_formato("el CRC no coincide"));
+                }
+                Some(Estado::Encabezado)
+            }
+            Estado::Terminado => None,
+        };
+        *crc = actualizar_crc(*crc, &salida[inicio..]);
+        if let Some(siguiente) = siguiente {
+            *estado = siguiente;
+        }
+        Ok(())
+    }
+
+    /// Descarta lo ya entregado de `salida`, salvo la ventana que pueden necesitar las
+    /// referencias de los próximos bloques.
+    fn descartar_entregado(&mut self) {
+        if self.salida.len() > VENTANA {
+            let sobrante = self.salida.len() - VENTANA;
+            self.salida.drain(..sobrante);
+            self.entregado -= sobrante;
+        }
+    }
+}
+
+impl<R: BufRead> Read for Descompresor<R> {
+    fn read(&mut self, destino: &mut [u8]) -> io::Result<usize> {
+        while self.entregado == self.salida.len() {
+            if let Estado::Terminado = self.estado {
+                return Ok(0);
+            }
+            self.descartar_entregado();
+            self.avanzar()?;
+        }
+        let pendiente = &self.salida[self.entregado..];
+        let cantidad = pendiente.len().min(destino.len());
+        destino[..cantidad].copy_from_slice(&pendiente[..cantidad]);
+        self.entregado += cantidad;
+        Ok(cantidad)
+    }
+}
+
+/// El estado que sigue al final de un bloque: otro bloque o, si era el último, la cola del
+/// miembro.
+fn fin_de_bloque(ultimo_bloque: bool) -> Estado {
+    if ultimo_bloque {
+        Estado::Cola
+    } else {
+        Estado::Bloque
+    }
+}
+
+/// Lee el encabezado de un miembro gzip y descarta sus campos opcionales.
+fn leer_encabezado<R: BufRead>(lector: &mut LectorBits<R>) -> io::Result<()> {
+    let mut encabezado = [0u8; 10];
+    for byte in &mut encabezado {
+        *byte = lector.leer(8)? as u8;
+    }
+    if encabezado[0..2] != ID_GZIP || encabezado[2] != METODO_DEFLATE {
+        return Err(error_formato("encabezado desconocido"));
+    }
+    let flags = encabezado[3];
+    if flags & FLAG_EXTRA != 0 {
+        let longitud = lector.leer(16)?;
+        for _ in 0..longitud {
+            lector.leer(8)?;
+        }
+    }
+    for flag in [FLAG_NOMBRE, FLAG_COMENTARIO] {
+        if flags & flag != 0 {
+            while lector.leer(8)? != 0 {}
+        }
+    }
+    if flags & FLAG_HCRC != 0 {
+        lector.leer(16)?;
+    }
+    Ok(())
+}
+
+/// Lee la longitud de un bloque sin compresión y verifica su complemento.
+fn leer_longitud_almacenada<R: BufRead>(lector: &mut LectorBits<R>) -> io::Result<usize> {
+    lector.alinear();
+    let longitud = lector.leer(16)?;
+    let complemento = lector.leer(16)?;
+    if longitud != !complemento & 0xffff {
+        return Err(error_formato("longitud de bloque inválida"));
+    }
+    Ok(longitud as usize)
+}
+
+/// Lee los bits de un flujo DEFLATE, comenzando por el bit menos significativo de cada byte.
+///
+/// Los bytes se toman de la fuente recién cuando hacen falta, de modo que al alinear no queda
+/// ningún bit pendiente y lo que sigue se puede leer directamente de la fuente.
+struct LectorBits<R: BufRead> {
+    fuente: R,
+    bits: u64,
+    cantidad: u8,
+}
+
+impl<R: BufRead> LectorBits<R> {
+    fn new(fuente: R) -> LectorBits<R> {
+        LectorBits {
+            fuente,
+            bits: 0,
+            cantidad: 0,
+        }
+    }
+
+    fn leer(&mut self, cantidad: u8) -> io::Result<u32> {
+        while self.cantidad < cantidad {
+            let byte = *self
+                .fuente
+                .fill_buf()?
+                .first()
+                .ok_or_else(|| error_formato("datos comprimidos incompletos"))?;
+            self.fuente.consume(1);
+            self.bits |= (byte as u64) << self.cantidad;
+            self.cantidad += 8;
+        }
+        let valor = (self.bits & ((1u64 << cantidad) - 1)) as u32;
+        self.bits >>= cantidad;
+        self.cantidad -= cantidad;
+        Ok(valor)
+    }
+
+    fn alinear(&mut self) {
+        let sobrantes = self.cantidad % 8;
+        self.bits >>= sobrantes;
+        self.cantidad -= sobrantes;
+    }
+
+    /// Indica si no quedan bits pendientes ni datos en la fuente.
+    fn agotado(&mut self) -> io::Result<bool> {
+        Ok(self.cantidad == 0 && self.fuente.fill_buf()?.is_empty())
+    }
+}
+
+/// Código de Huffman canónico: la cantidad de códigos de cada longitud y los símbolos
+/// ordenados por código.
+struct Huffman {
+    cantidades: [u16; MAXIMOS_BITS + 1],
+    simbolos: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(longitudes: &[u8]) -> Huffman {
+        let mut cantidades = [0u16; MAXIMOS_BITS + 1];
+        for longitud in longitudes {
+            cantidades[*longitud as usize] += 1;
+        }
+        cantidades[0] = 0;
+        let mut desplazamientos = [0u16; MAXIMOS_BITS + 2];
+        for longitud in 1..=MAXIMOS_BITS {
+            desplazamientos[longitud + 1] = desplazamientos[longitud] + cantidades[longitud];
+        }
+        let mut simbolos = vec![0; longitudes.len()];
+        for (simbolo, longitud) in longitudes.iter().enumerate() {
+            if *longitud != 0 {
+                simbolos[desplazamientos[*longitud as usize] as usize] = simbolo as u16;
+                desplazamientos[*longitud as usize] += 1;
+            }
+        }
+        Huffman {
+            cantidades,
+            simbolos,
+        }
+    }
+
+    fn decodificar<R: BufRead>(&self, lector: &mut LectorBits<R>) -> io::Result<u16> {
+        let mut codigo: i32 = 0;
+        let mut primero: i32 = 0;
+        let mut indice: i32 = 0;
+        for longitud in 1..=MAXIMOS_BITS {
+            codigo |= lector.leer(1)? as i32;
+            let cantidad = self.cantidades[longitud] as i32;
+            if codigo - cantidad < primero {
+                return Ok(self.simbolos[(indice + codigo - primero) as usize]);
+            }
+            indice += cantidad;
+            primero = (primero + cantidad) << 1;
+            codigo <<= 1;
+        }
+        Err(error_formato("código de Huffman inválido"))
+    }
+}
+
+fn codigos_fijos() -> (Huffman, Huffman) {
+    let mut literales = [0u8; 288];
+    for (simbolo, longitud) in literales.iter_mut().enumerate() {
+        *longitud = match simbolo {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&literales), Huffman::new(&[5; 30]))
+}
+
+fn codigos_dinamicos<R: BufRead>(lector: &mut LectorBits<R>) -> io::Result<(Huffman, Huffman)> {
+    let cantidad_literales = lector.leer(5)? as usize + 257;
+    let cantidad_distancias = lector.leer(5)? as usize + 1;
+    let cantidad_longitudes = lector.leer(4)? as usize + 4;
+
+    let mut longitudes_codigos = [0u8; 19];
+    for indice in ORDEN_LONGITUDES.iter().take(cantidad_longitudes) {
+        longitudes_codigos[*indice] = lector.leer(3)? as u8;
+    }
+    let codigo_longitudes = Huffman::new(&longitudes_codigos);
+
+    let total = cantidad_literales + cantidad_distancias;
+    let mut longitudes = Vec::with_capacity(total);
+    while longitudes.len() < total {
+        let simbolo = codigo_longitudes.decodificar(lector)?;
+        let (valor, repeticiones) = match simbolo {
+            0..=15 => (simbolo as u8, 1),
+            16 => {
+                let anterior = *longitudes
+                    .last()
+                    .ok_or_else(|| error_formato("repetición sin longitud previa"))?;
+                (anterior, 3 + lector.leer(2)? as usize)
+            }
+            17 => (0, 3 + lector.leer(3)? as usize),
+            _ => (0, 11 + lector.leer(7)? as usize),
+        };
+        if longitudes.len() + repeticiones > total {
+            return Err(error_formato("demasiadas longitudes de código"));
+        }
+        longitudes.extend(std::iter::repeat_n(valor, repeticiones));
+    }
+    Ok((
+        Huffman::new(&longitudes[..cantidad_literales]),
+        Huffman::new(&longitudes[cantidad_literales..]),
+    ))
+}
+
+/// Descomprime hasta `PORCION_SALIDA` bytes de un bloque comprimido con los códigos indicados.
+///
+/// # Retorno
+/// Retorna `true` si se llegó al final del bloque.
+fn inflar_bloque<R: BufRead>(
+    lector: &mut LectorBits<R>,
+    salida: &mut Vec<u8>,
+    literales: &Huffman,
+    distancias: &Huffman,
+) -> io::Result<bool> {
+    let inicio_porcion = salida.len();
+    while salida.len() - inicio_porcion < PORCION_SALIDA {
+        let simbolo = literales.decodificar(lector)? as usize;
+        if simbolo < 256 {
+            salida.push(simbolo as u8);
+            continue;
+        }
+        if simbolo == 256 {
+            return Ok(true);
+        }
+        let indice = simbolo - 257;
+        if indice >= BASE_LONGITUD.len() {
+            return Err(error_formato("longitud inválida"));
+        }
+        let longitud =
+            BASE_LONGITUD[indice] as usize + lector.leer(EXTRA_LONGITUD[indice])? as usize;
+        let indice = distancias.decodificar(lector)? as usize;
+        if indice >= BASE_DISTANCIA.len() {
+            return Err(error_formato("distancia inválida"));
+        }
+        let distancia =
+            BASE_DISTANCIA[indice] as usize + lector.leer(EXTRA_DISTANCIA[indice])? as usize;
+        if distancia > salida.len() {
+            return Err(error_formato("distancia fuera de los datos"));
+        }
+        let inicio = salida.len() - distancia;
+        for desplazamiento in 0..longitud {
+            salida.push(salida[inicio + desplazamiento]);
+        }
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descomprimir_codigos_fijos() {
+        // `printf 'hola hola hola\n' | gzip -n`
+        let datos = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0xc8, 0xcf, 0x49,
+            0x54, 0xc8, 0x80, 0x11, 0x5c, 0x00, 0x4e, 0xda, 0x35, 0xda, 0x0f, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(descomprimir(&datos).unwrap(), b"hola hola hola\n");
+    }
+
+    #[test]
+    fn test_descomprimir_codigos_dinamicos() {
+        let original = std::fs::read("tablas/personas").unwrap();
+        let comprimido = std::fs::read("tablas/personas_comprimida.gz").unwrap();
+        assert_eq!(descomprimir(&comprimido).unwrap(), original);
+    }
+
+    #[test]
+    fn test_comprimir_y_concatenar_miembros() {
+        let mut datos = comprimir(b"nombre,edad\n");
+        datos.extend(comprimir(b""));
+        datos.extend(comprimir(&b"Ana,30\n".repeat(20_000)));
+
+        let descomprimido = descomprimir(&datos).unwrap();
+        assert!(descomprimido.starts_with(b"nombre,edad\nAna,30\n"));
+        assert_eq!(descomprimido.len(), 12 + 7 * 20_000);
+    }
+
+    #[test]
+    fn test_descomprimir_datos_corruptos() {
+        let mut datos = comprimir(b"nombre,edad\n");
+        let ultimo = datos.len() - 5;
+        datos[ultimo] ^= 0xff;
+        assert!(descomprimir(&datos).is_err());
+        assert!(descomprimir(b"nombre,edad\n").is_err());
+    }
+
+    #[test]
+    fn test_descomprimir_a_medida_que_se_lee() {
+        let original = std::fs::read("tablas/personas").unwrap();
+        let filas = b"Ana,30\n".repeat(40_000);
+        let mut datos = std::fs::read("tablas/personas_comprimida.gz").unwrap();
+        datos.extend(comprimir(&filas));
+        datos.extend(comprimir(b"Eva,41\n"));
+
+        let mut descompresor = Descompresor::new(datos.as_slice());
+        let mut descomprimido = Vec::new();
+        let mut porcion = [0u8; 1000];
+        loop {
+            let cantidad = descompresor.read(&mut porcion).unwrap();
+            if cantidad == 0 {
+                break;
+            }
+            descomprimido.extend_from_slice(&porcion[..cantidad]);
+            assert!(descompresor.salida.len() <= VENTANA + PORCION_SALIDA + 258);
+        }
+        assert_eq!(descomprimido.len(), original.len() + filas.len() + 7);
+        assert!(descomprimido.starts_with(&original));
+        assert!(descomprimido.ends_with(b"Ana,30\nEva,41\n"));
+
+        let ultimo = datos.len() - 10;
+        datos[ultimo] ^= 0xff;
+        let mut descomprimido = Vec::new();
+        assert!(Descompresor::new(datos.as_slice())
+            .read_to_end(&mut descomprimido)
+            .is_err());
+    }
+}
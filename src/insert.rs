@@ -1,12 +1,18 @@
-use crate::archivo::{leer_archivo, parsear_linea_archivo, procesar_ruta};
-use crate::consulta::{mapear_campos, MetodosConsulta, Parseables, Verificaciones};
-use crate::errores;
-use std::fs::OpenOptions;
-use std::path::Path;
-use std::{
-    collections::HashMap,
-    io::{BufRead, BufWriter, Write},
+use crate::almacenamiento::Almacenamiento;
+use crate::archivo::{formatear_registro, leer_encabezado, OpcionesArchivo};
+use crate::consulta::{
+    columna_invalida, mapear_campos, obtener_campos_consulta_orden_por_defecto, MetodosConsulta,
+    Resumen, Verificaciones,
 };
+use crate::errores;
+use crate::esquema::Esquema;
+use crate::expresion::Valor;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorResultados;
+use crate::sintaxis::{self, Sentencia, SentenciaInsert};
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Representa una consulta SQL de inserción.
 ///
@@ -21,19 +27,23 @@ use std::{
 ///   los campos en los que se van a insertar los datos.
 /// - `campos_posibles`: Un mapa (`HashMap<String, usize>`) que asocia los nombres de los
 ///   campos de la tabla con sus índices. Este mapa permite la validación de campos.
-/// - `valores`: Un vector de vectores de cadenas de texto (`Vec<Vec<String>>`) que contiene
-///   los valores a insertar en los campos especificados.
-/// - `tabla`: Una cadena de texto (`String`) que indica el nombre de la tabla en la
-///   que se van a insertar los datos.
+/// - `valores`: Los valores (`Vec<Vec<Option<String>>>`) de cada fila a insertar, en el orden
+///   de `campos_consulta`, donde `None` representa `NULL`.
 /// - `ruta_tabla`: Una cadena de texto (`String`) que indica la ruta del archivo que
 ///   se actualizará con los datos insertados.
+/// - `almacenamiento`: El `Almacenamiento` en el que está guardada la tabla.
+/// - `esquema`: El `Esquema` de la tabla, con los valores por defecto de las columnas que
+///   la consulta omite.
+/// - `opciones`: Las `OpcionesArchivo` con las que se lee y escribe el archivo de la tabla.
 #[derive(Debug)]
 pub struct ConsultaInsert {
     pub campos_consulta: Vec<String>,
     pub campos_posibles: HashMap<String, usize>,
-    pub valores: Vec<Vec<String>>,
-    pub tabla: String,
+    pub valores: Vec<Vec<Option<String>>>,
     pub ruta_tabla: String,
+    pub almacenamiento: Arc<dyn Almacenamiento>,
+    pub esquema: Esquema,
+    pub opciones: OpcionesArchivo,
 }
 
 impl ConsultaInsert {
@@ -44,198 +54,205 @@ impl ConsultaInsert {
     ///
     /// # Parámetros
     /// - `consulta`: La consulta SQL en formato `String`.
-    /// - `ruta`: La ruta del archivo en el que se van a insertar los datos.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla a modificar.
+    /// - `opciones`: Las opciones con las que se lee y escribe el archivo de la tabla.
     ///
     /// # Retorno
-    /// Una instancia de `ConsultaInsert`
-
-    pub fn crear(consulta: &String, ruta_a_tablas: &String) -> ConsultaInsert {
-        let consulta_parseada = &Self::parsear_consulta_de_comando(&consulta);
-        let mut index = 2; //nos salteamos las palabras:  insert into
-        let tabla = Self::parsear_tabla(consulta_parseada, &mut index);
-        let campos_consulta = Self::parsear_campos(consulta_parseada, &mut index);
-        let valores = Self::parsear_valores(consulta_parseada, &mut index);
-        let campos_posibles: HashMap<String, usize> = HashMap::new();
-        let ruta_tabla = procesar_ruta(&ruta_a_tablas, &tabla);
-
-        ConsultaInsert {
-            campos_consulta,
-            campos_posibles,
-            valores,
-            tabla,
-            ruta_tabla,
+    /// Una instancia de `ConsultaInsert`, `Errores::InvalidSyntax` si la consulta no es un
+    /// `INSERT` válido o `Errores::InvalidTable` si la tabla no se puede resolver.
+    pub fn crear(
+        consulta: &str,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaInsert, errores::Errores> {
+        match sintaxis::parsear(consulta)? {
+            Sentencia::Insert(sentencia) => Self::desde_sentencia(sentencia, resolvedor, opciones),
+            _ => Err(errores::Errores::InvalidSyntax),
         }
     }
 
-    /// Parsea la consulta SQL para obtener los distintos tokens.
-    ///
-    /// Convierte la consulta, eliminando las comas y divide la cadena en palabras.
-    ///
-    /// # Parámetros
-    /// - `consulta`: La consulta SQL en formato `String`.
-    ///
-    /// # Retorno
-    /// Retorna un `Vec<String>` que contiene cada palabra de la consulta SQL.
-
-    fn parsear_consulta_de_comando(consulta: &String) -> Vec<String> {
-        return consulta
-            .replace(",", "")
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
-    }
-}
-
-impl Parseables for ConsultaInsert {
-    // Extrae los campos de la consulta SQL.
-    ///
-    /// A partir de una lista de tokens, extrae los campos entre los paréntesis.
+    /// Crea una nueva instancia de `ConsultaInsert` a partir de una sentencia ya parseada.
     ///
     /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesan los tokens.
+    /// - `sentencia`: La sentencia `INSERT` parseada.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla a modificar.
+    /// - `opciones`: Las opciones con las que se lee y escribe el archivo de la tabla.
     ///
     /// # Retorno
-    /// Un `Vec<String>` que contiene los nombres de los campos a insertar.
+    /// La `ConsultaInsert`, o `Errores::InvalidTable` si la tabla no se puede resolver.
+    pub fn desde_sentencia(
+        sentencia: SentenciaInsert,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaInsert, errores::Errores> {
+        let ruta_tabla = resolvedor.resolver(&sentencia.tabla)?;
+        registro::registrar(registro::DETALLE, || {
+            format!("tabla '{}' en '{}'", sentencia.tabla, ruta_tabla)
+        });
 
-    fn parsear_campos(consulta: &Vec<String>, index: &mut usize) -> Vec<String> {
-        let mut campos: Vec<String> = Vec::new();
-        if consulta[*index] == "(" {
-            *index += 1;
-        }
-
-        while *index < consulta.len() && consulta[*index] != ")" {
-            let campo = &consulta[*index];
-            campos.push(campo.to_string());
-            *index += 1;
-        }
-        campos
+        Ok(ConsultaInsert {
+            campos_consulta: sentencia.columnas,
+            campos_posibles: HashMap::new(),
+            valores: sentencia.filas,
+            ruta_tabla,
+            almacenamiento: resolvedor.almacenamiento(),
+            esquema: Esquema::default(),
+            opciones: opciones.clone(),
+        })
     }
-    /// Extrae el nombre de la tabla a partir de la consulta SQL.
+
+    /// Arma el registro completo a escribir en la tabla a partir de una fila de valores.
     ///
-    /// Busca la palabra clave `INTO` en los tokens de la consulta y toma el siguiente token como el nombre de la tabla.
+    /// Cada columna de la tabla toma el valor indicado en la consulta; las columnas omitidas
+    /// toman su valor por defecto del esquema o quedan nulas si no lo tienen.
     ///
     /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesa la consulta.
+    /// - `valores_fila`: Los valores de la consulta, en el orden de `campos_consulta`.
     ///
     /// # Retorno
-    /// Una cadena de texto (`String`) que contiene el nombre de la tabla.
-
-    fn parsear_tabla(consulta: &Vec<String>, index: &mut usize) -> String {
-        let mut tabla = String::new();
-
-        if *index < consulta.len() {
-            let tabla_consulta = &consulta[*index];
-            tabla = tabla_consulta.to_string();
-            *index += 1;
+    /// Un `Vec<Option<String>>` con un valor por cada columna de la tabla, en el orden del
+    /// archivo, donde `None` representa `NULL`.
+    fn armar_registro(&self, valores_fila: &[Option<String>]) -> Vec<Option<String>> {
+        let campos_tabla = obtener_campos_consulta_orden_por_defecto(&self.campos_posibles);
+        let mut registro = Vec::new();
+        for campo in &campos_tabla {
+            let posicion = self.campos_consulta.iter().position(|c| c == campo);
+            let valor = match posicion.and_then(|posicion| valores_fila.get(posicion)) {
+                Some(valor) => valor.clone(),
+                None => self
+                    .esquema
+                    .columna(campo)
+                    .and_then(|columna| columna.valor_por_defecto.as_ref())
+                    .map(|valor_por_defecto| valor_por_defecto.evaluar()),
+            };
+            registro.push(valor);
         }
-        tabla
+        registro
     }
 
-    /// Extrae los valores a insertar a partir de la consulta SQL.
-    ///
-    /// Busca la palabra clave `VALUES` en los tokens de la consulta y toma los tokens siguientes
-    /// entre paréntesis como los valores a insertar.
-    ///
-    /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesan los tokens.
+    /// Verifica que cada fila de valores tenga un valor por campo de la consulta, que cada
+    /// valor sea del tipo declarado de su columna y que las columnas omitidas tengan valor por
+    /// defecto o admitan valores nulos.
     ///
     /// # Retorno
-    /// Un `Vec<Vec<String>>` que contiene los valores a insertar.
-
-    fn parsear_valores(_consulta: &Vec<String>, _index: &mut usize) -> Vec<Vec<String>> {
-        let mut lista_valores: Vec<Vec<String>> = Vec::new();
-        if _consulta[*_index] == ")" {
-            *_index += 1;
+    /// - `Ok(())`: Si los valores son consistentes con el esquema.
+    /// - `Err(errores::Errores::InvalidSyntax)`: Si no hay valores.
+    /// - `Err(errores::Errores::ValorInvalido)`: Si alguna fila tiene una cantidad de valores
+    ///   distinta de la de campos, con la fila y ambas cantidades.
+    /// - `Err(errores::Errores::InvalidColumn)`: Si se omite una columna `NOT NULL` sin valor por defecto.
+    /// - `Err(errores::Errores::TipoInvalido)`: Si un valor no es del tipo de su columna, como
+    ///   `'abc'` en una columna `INTEGER`.
+    fn verificar_valores(&self) -> Result<(), errores::Errores> {
+        if self.valores.is_empty() {
+            return Err(errores::Errores::InvalidSyntax);
         }
-        if _consulta[*_index] == "values" {
-            *_index += 1;
+        for (numero, fila) in self.valores.iter().enumerate() {
+            if fila.len() != self.campos_consulta.len() {
+                let valores: Vec<String> = fila
+                    .iter()
+                    .map(|valor| match valor {
+                        Some(valor) => format!("'{}'", valor),
+                        None => "NULL".to_string(),
+                    })
+                    .collect();
+                return Err(errores::Errores::ValorInvalido(format!(
+                    "la fila {} ({}) tiene {} valores y se esperaban {}",
+                    numero + 1,
+                    valores.join(", "),
+                    fila.len(),
+                    self.campos_consulta.len()
+                )));
+            }
         }
-
-        while *_index < _consulta.len() {
-            if _consulta[*_index] == "(" {
-                *_index += 1;
+        for fila in &self.valores {
+            for (campo, valor) in self.campos_consulta.iter().zip(fila) {
+                let Some(columna) = self.esquema.columna(campo) else {
+                    continue;
+                };
+                if !columna.nulable && valor.is_none() {
+                    return Err(errores::Errores::InvalidColumn(campo.to_string()));
+                }
+                if let (Some(tipo), Some(valor)) = (columna.tipo, valor) {
+                    if Valor::desde_campo_tipado(Some(valor), tipo).is_err() {
+                        return Err(errores::Errores::TipoInvalido(format!(
+                            "'{}' no es un valor de tipo {} para la columna '{}'",
+                            valor, tipo, campo
+                        )));
+                    }
+                }
             }
-            let mut valores = Vec::new();
-            while *_index < _consulta.len() && _consulta[*_index] != ")" {
-                let valor = &_consulta[*_index];
-
-                valores.push(valor.to_string());
-                *_index += 1;
+        }
+        for campo in obtener_campos_consulta_orden_por_defecto(&self.campos_posibles) {
+            if self.campos_consulta.contains(&campo) {
+                continue;
+            }
+            if let Some(columna) = self.esquema.columna(&campo) {
+                if !columna.nulable && columna.valor_por_defecto.is_none() {
+                    return Err(errores::Errores::InvalidColumn(campo));
+                }
             }
-            lista_valores.push(valores);
-            *_index += 1;
         }
-        lista_valores
+        Ok(())
     }
 }
 
 impl MetodosConsulta for ConsultaInsert {
     /// Verifica la validez de la consulta SQL.
-    ///TODO: verificar la validez de los valores a ingresar
-    /// verifica que la tabla a la que se quiere inserta exista, así como los campos de la consulta no estén vacíos
-    /// y que todos los campos solicitados sean válidos según los campos posibles definidos en la estructura.
+    ///
+    /// Verifica que la tabla a la que se quiere inserta exista y que todos los campos solicitados sean válidos
+    /// según los campos posibles definidos en la estructura. Si la consulta no lista campos, los valores se
+    /// toman en el orden de las columnas de la tabla. Cada fila de valores debe tener exactamente un valor por
+    /// campo, del tipo declarado en el esquema, y las columnas omitidas deben tener valor por
+    /// defecto o admitir valores nulos.
     /// # Retorno
     /// Retorna un `Result` que indica el éxito (`Ok`) o el tipo de error (`Err`).
-
     fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
-        match leer_archivo(&self.ruta_tabla) {
+        match self.almacenamiento.leer(&self.ruta_tabla, &self.opciones) {
             Ok(mut lector) => {
-                let mut nombres_campos = String::new();
-                lector
-                    .read_line(&mut nombres_campos)
-                    .map_err(|_| errores::Errores::Error)?;
-                let (_, campos_validos) = parsear_linea_archivo(&nombres_campos);
+                let campos_validos = leer_encabezado(lector.as_mut(), &mut self.opciones)?;
                 self.campos_posibles = mapear_campos(&campos_validos);
             }
-            Err(_) => return Err(errores::Errores::InvalidTable),
+            Err(error) => return Err(errores::Errores::tabla_ilegible(error, &self.ruta_tabla)),
         };
+        self.esquema = self.almacenamiento.esquema(&self.ruta_tabla)?;
 
         if self.campos_consulta.is_empty() {
-            return Err(errores::Errores::InvalidSyntax);
+            self.campos_consulta = obtener_campos_consulta_orden_por_defecto(&self.campos_posibles);
         }
         let campos_posibles = &self.campos_posibles;
         if !ConsultaInsert::verificar_campos_validos(campos_posibles, &mut self.campos_consulta) {
-            return Err(errores::Errores::InvalidColumn);
+            return Err(columna_invalida(campos_posibles, &self.campos_consulta));
         }
-        Ok(())
+        self.verificar_valores()
     }
 
     /// Procesa el contenido de la consulta y agrega los valores al archivo correspondiente.
     ///
-    /// Abre el archivo en modo append y escribe los valores de la consulta al final del archivo.
-    ///
-    /// # Parámetros
-    /// - `lector`: Un `BufReader<File>` que proporciona acceso al archivo.
+    /// Arma un registro completo por cada fila de valores, completando las columnas omitidas
+    /// con sus valores por defecto, y los agrega al final de la tabla a través del
+    /// almacenamiento (ver `Almacenamiento::agregar`).
     ///
     /// # Retorno
     /// Retorna un `Result` que indica el éxito (`Ok`) o el tipo de error (`Err`).
-
-    fn procesar(&mut self) -> Result<(), errores::Errores> {
-        // Abrir el archivo original en modo append (agregar al final)
-        let ruta_archivo = Path::new(&self.ruta_tabla);
-        let archivo_original = match OpenOptions::new().append(true).open(ruta_archivo) {
-            Ok(file) => file,
-            Err(_) => return Err(errores::Errores::Error),
-        };
-        let mut escritor = BufWriter::new(archivo_original);
-
-        // Agregar valores al final del archivo
+    fn procesar(
+        &mut self,
+        _escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let mut lineas = String::new();
         for valores_fila in &self.valores {
-            let linea = valores_fila.join(",");
-            if let Err(_) = writeln!(escritor, "{}", linea) {
-                return Err(errores::Errores::Error);
-            }
+            lineas.push_str(&formatear_registro(
+                &self.armar_registro(valores_fila),
+                self.opciones.delimitador(),
+                self.opciones.nulo(),
+            ));
+            lineas.push('\n');
         }
-
-        // Asegurarse de escribir en el archivo
-        match escritor.flush() {
-            Ok(_) => {}
-            Err(_) => return Err(errores::Errores::Error), //error al escribir
-        }
-        Ok(())
+        self.almacenamiento
+            .agregar(&self.ruta_tabla, &self.opciones, &lineas)?;
+        registro::registrar(registro::DETALLE, || {
+            format!("{} registros agregados", self.valores.len())
+        });
+        Ok(Resumen::Agregados(self.valores.len()))
     }
 }
 
@@ -249,13 +266,14 @@ impl Verificaciones for ConsultaInsert {
                 return false;
             }
         }
-        return true;
+        true
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::AlmacenamientoArchivos;
     use std::collections::HashMap;
 
     #[test]
@@ -276,4 +294,175 @@ mod tests {
             &mut campos_invalidos
         ));
     }
+
+    fn consulta_personas(
+        campos: &[&str],
+        valores: &[&[Option<&str>]],
+        esquema: &str,
+    ) -> ConsultaInsert {
+        ConsultaInsert {
+            campos_consulta: campos.iter().map(|c| c.to_string()).collect(),
+            campos_posibles: mapear_campos(&[
+                "nombre".to_string(),
+                "edad".to_string(),
+                "ciudad".to_string(),
+            ]),
+            valores: valores
+                .iter()
+                .map(|fila| fila.iter().map(|v| v.map(str::to_string)).collect())
+                .collect(),
+            ruta_tabla: "tablas/personas".to_string(),
+            almacenamiento: Arc::new(AlmacenamientoArchivos::new()),
+            esquema: Esquema::parsear(esquema).unwrap(),
+            opciones: OpcionesArchivo::default(),
+        }
+    }
+
+    #[test]
+    fn test_insert_sin_lista_de_campos_usa_todas_las_columnas() {
+        let mut consulta =
+            consulta_personas(&[], &[&[Some("Ana"), Some("30"), Some("Rosario")]], "");
+        assert!(consulta.verificar_validez_consulta().is_ok());
+        assert_eq!(consulta.campos_consulta, vec!["nombre", "edad", "ciudad"]);
+    }
+
+    #[test]
+    fn test_insert_rechaza_cantidad_de_valores_distinta() {
+        let mut de_mas = consulta_personas(
+            &[],
+            &[&[Some("Ana"), Some("30"), Some("Rosario"), Some("1")]],
+            "",
+        );
+        assert_eq!(
+            de_mas.verificar_validez_consulta(),
+            Err(errores::Errores::ValorInvalido(
+                "la fila 1 ('Ana', '30', 'Rosario', '1') tiene 4 valores y se esperaban 3"
+                    .to_string()
+            ))
+        );
+
+        let de_menos = consulta_personas(
+            &["nombre", "edad"],
+            &[&[Some("Luis"), Some("40")], &[Some("Ana"), None, None]],
+            "",
+        );
+        assert_eq!(
+            de_menos.verificar_valores(),
+            Err(errores::Errores::ValorInvalido(
+                "la fila 2 ('Ana', NULL, NULL) tiene 3 valores y se esperaban 2".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_insert_rechaza_omitir_columna_no_nulable_sin_valor_por_defecto() {
+        let consulta = consulta_personas(&["nombre"], &[&[Some("Ana")]], "edad NOT NULL");
+        assert_eq!(
+            consulta.verificar_valores(),
+            Err(errores::Errores::InvalidColumn("edad".to_string()))
+        );
+
+        let con_defecto =
+            consulta_personas(&["nombre"], &[&[Some("Ana")]], "edad NOT NULL DEFAULT 0");
+        assert!(con_defecto.verificar_valores().is_ok());
+    }
+
+    #[test]
+    fn test_armar_registro_con_valores_por_defecto() {
+        let consulta = ConsultaInsert {
+            campos_consulta: vec!["ciudad".to_string(), "nombre".to_string()],
+            campos_posibles: mapear_campos(&[
+                "nombre".to_string(),
+                "edad".to_string(),
+                "ciudad".to_string(),
+                "alta".to_string(),
+            ]),
+            valores: vec![],
+            ruta_tabla: "tablas/personas".to_string(),
+            almacenamiento: Arc::new(AlmacenamientoArchivos::new()),
+            esquema: Esquema::parsear("edad DEFAULT 18\nalta DEFAULT CURRENT_DATE").unwrap(),
+            opciones: OpcionesArchivo::default(),
+        };
+
+        let registro =
+            consulta.armar_registro(&[Some("Rosario".to_string()), Some("Ana".to_string())]);
+
+        assert_eq!(registro[0].as_deref(), Some("Ana"));
+        assert_eq!(registro[1].as_deref(), Some("18"));
+        assert_eq!(registro[2].as_deref(), Some("Rosario"));
+        assert_eq!(registro[3], Some(crate::fecha::fecha_actual()));
+    }
+
+    #[test]
+    fn test_armar_registro_sin_valor_por_defecto_queda_nulo() {
+        let consulta = ConsultaInsert {
+            campos_consulta: vec!["nombre".to_string()],
+            campos_posibles: mapear_campos(&["nombre".to_string(), "edad".to_string()]),
+            valores: vec![],
+            ruta_tabla: "tablas/personas".to_string(),
+            almacenamiento: Arc::new(AlmacenamientoArchivos::new()),
+            esquema: Esquema::default(),
+            opciones: OpcionesArchivo::default(),
+        };
+
+        let registro = consulta.armar_registro(&[Some("Luis".to_string())]);
+
+        assert_eq!(registro, vec![Some("Luis".to_string()), None]);
+    }
+
+    #[test]
+    fn test_insert_distingue_null_de_cadena_vacia() {
+        let consulta = consulta_personas(&["nombre", "edad", "ciudad"], &[], "");
+        let Ok(Sentencia::Insert(sentencia)) =
+            sintaxis::parsear("INSERT INTO personas VALUES ('', NULL, 'null')")
+        else {
+            panic!("Se esperaba un INSERT");
+        };
+
+        let registro = consulta.armar_registro(&sentencia.filas[0]);
+
+        assert_eq!(
+            registro,
+            vec![Some(String::new()), None, Some("null".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_insert_rechaza_null_en_columna_no_nulable() {
+        let consulta = consulta_personas(
+            &["nombre", "edad"],
+            &[&[Some("Ana"), None]],
+            "edad NOT NULL",
+        );
+        assert_eq!(
+            consulta.verificar_valores(),
+            Err(errores::Errores::InvalidColumn("edad".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_insert_rechaza_valores_de_otro_tipo() {
+        let esquema = "edad INTEGER\nciudad TEXT";
+        let consulta = consulta_personas(
+            &["nombre", "edad", "ciudad"],
+            &[
+                &[Some("Ana"), Some("30"), Some("123")],
+                &[Some("Luis"), None, None],
+            ],
+            esquema,
+        );
+        assert_eq!(consulta.verificar_valores(), Ok(()));
+
+        let consulta = consulta_personas(
+            &["nombre", "edad"],
+            &[&[Some("Ana"), Some("30")], &[Some("Luis"), Some("abc")]],
+            esquema,
+        );
+        assert_eq!(
+            consulta.verificar_valores(),
+            Err(errores::Errores::TipoInvalido(
+                "'abc' no es un valor de tipo INTEGER para la columna 'edad'".to_string()
+            ))
+        );
+    }
 }
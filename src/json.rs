@@ -0,0 +1,534 @@
+use crate::archivo::{formatear_registro, leer_registro, parsear_registro};
+use std::io::{self, Cursor};
+
+/// Extensión de las tablas guardadas como JSON.
+pub const EXTENSION_JSON: &str = ".json";
+
+/// Valor de un documento JSON. Los números se conservan con el texto con el que se escribieron
+/// y los objetos con sus claves en orden.
+#[derive(Debug, Clone, PartialEq)]
+enum ValorJson {
+    Nulo,
+    Booleano(bool),
+    Numero(String),
+    Texto(String),
+    Lista(Vec<ValorJson>),
+    Objeto(Vec<(String, ValorJson)>),
+}
+
+impl ValorJson {
+    /// Devuelve el valor como campo de un registro CSV: `None` para `null`, el texto sin
+    /// comillas para las cadenas y el JSON compacto para las listas y los objetos anidados.
+    fn a_campo(&self) -> Option<String> {
+        match self {
+            ValorJson::Nulo => None,
+            ValorJson::Texto(texto) => Some(texto.to_string()),
+            valor => Some(valor.serializar()),
+        }
+    }
+
+    /// Convierte un campo de un registro CSV en un valor JSON: los números y `true`/`false` se
+    /// escriben sin comillas, y el resto como cadena.
+    fn desde_campo(campo: Option<String>) -> ValorJson {
+        match campo {
+            None => ValorJson::Nulo,
+            Some(campo) if campo == "true" || campo == "false" => {
+                ValorJson::Booleano(campo == "true")
+            }
+            Some(campo) if es_numero(&campo) => ValorJson::Numero(campo),
+            Some(campo) => ValorJson::Texto(campo),
+        }
+    }
+
+    fn serializar(&self) -> String {
+        match self {
+            ValorJson::Nulo => "null".to_string(),
+            ValorJson::Booleano(valor) => valor.to_string(),
+            ValorJson::Numero(numero) => numero.to_string(),
+            ValorJson::Texto(texto) => citar(texto),
+            ValorJson::Lista(valores) => {
+                let valores: Vec<String> = valores.iter().map(ValorJson::serializar).collect();
+                format!("[{}]", valores.join(", "))
+            }
+            ValorJson::Objeto(campos) => {
+                let campos: Vec<String> = campos
+                    .iter()
+                    .map(|(clave, valor)| format!("{}: {}", citar(clave), valor.serializar()))
+                    .collect();
+                format!("{{{}}}", campos.join(", "))
+            }
+        }
+    }
+}
+
+fn error_formato(motivo: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("JSON inválido: {}", motivo),
+    )
+}
+
+/// Convierte una tabla guardada como JSON en su contenido CSV, con el encabezado en la primera
+/// línea.
+///
+/// La tabla es una lista de objetos, uno por registro, o un objeto por línea como en NDJSON.
+/// Las columnas son las claves de los objetos en el orden en que aparecen por primera vez; las
+/// claves que le faltan a un objeto y los `null` son `NULL`.
+///
+/// # Parámetros
+/// - `texto`: El documento JSON.
+/// - `delimitador`: El delimitador de campos del CSV.
+/// - `nulo`: El texto que representa `NULL` en las tablas, si se indicó alguno.
+///
+/// # Retorno
+/// El contenido CSV, o un error de `InvalidData` si el documento no es una lista de objetos
+/// ni una secuencia de objetos.
+pub fn a_csv(texto: &str, delimitador: char, nulo: Option<&str>) -> io::Result<String> {
+    let registros = parsear_registros(texto)?;
+    let columnas = columnas_de(&registros);
+    let encabezado: Vec<Option<String>> = columnas.iter().cloned().map(Some).collect();
+    let mut csv = formatear_registro(&encabezado, delimitador, None);
+    csv.push('\n');
+    for registro in &registros {
+        let campos: Vec<Option<String>> = columnas
+            .iter()
+            .map(|columna| {
+                registro
+                    .iter()
+                    .find(|(clave, _)| clave == columna)
+                    .and_then(|(_, valor)| valor.a_campo())
+            })
+            .collect();
+        csv.push_str(&formatear_registro(&campos, delimitador, nulo));
+        csv.push('\n');
+    }
+    Ok(csv)
+}
+
+/// Agrega registros a una tabla guardada como JSON.
+///
+/// Los campos de los registros nuevos se nombran con las columnas de la tabla; los que son
+/// números o `true`/`false` se guardan sin comillas.
+///
+/// # Parámetros
+/// - `texto`: El documento JSON de la tabla.
+/// - `lineas`: Los registros en formato CSV, cada uno terminado en un salto de línea.
+/// - `delimitador`: El delimitador de campos de los registros.
+/// - `nulo`: El texto que representa `NULL` en los registros, si se indicó alguno.
+///
+/// # Retorno
+/// El nuevo documento JSON, con un objeto por línea, o un error de `InvalidData` si la tabla
+/// no es una lista de objetos.
+pub fn agregar(
+    texto: &str,
+    lineas: &str,
+    delimitador: char,
+    nulo: Option<&str>,
+) -> io::Result<String> {
+    let mut registros = parsear_tabla(texto)?;
+    let columnas = columnas_de(&registros);
+    for linea in lineas.lines() {
+        let campos = parsear_registro(linea, delimitador, nulo);
+        registros.push(
+            columnas
+                .iter()
+                .cloned()
+                .zip(campos.into_iter().map(ValorJson::desde_campo))
+                .collect(),
+        );
+    }
+    Ok(serializar_tabla(registros))
+}
+
+/// Convierte el contenido CSV de una tabla, con el encabezado en la primera línea, en el
+/// documento JSON con el que se guarda; es la inversa de `a_csv`.
+///
+/// # Parámetros
+/// - `texto`: El contenido CSV de la tabla.
+/// - `delimitador`: El delimitador de campos del CSV.
+/// - `nulo`: El texto que representa `NULL` en el CSV, si se indicó alguno.
+///
+/// # Retorno
+/// El documento JSON, con un objeto por línea, o el error de E/S si el texto no se pudo leer.
+pub fn desde_csv(texto: &str, delimitador: char, nulo: Option<&str>) -> io::Result<String> {
+    let mut lector = Cursor::new(texto.as_bytes());
+    let columnas: Vec<String> = match leer_registro(&mut lector)? {
+        Some(encabezado) => parsear_registro(&encabezado, delimitador, None)
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect(),
+        None => Vec::new(),
+    };
+    let mut registros = Vec::new();
+    while let Some(linea) = leer_registro(&mut lector)? {
+        let campos = parsear_registro(&linea, delimitador, nulo);
+        registros.push(
+            columnas
+                .iter()
+                .cloned()
+                .zip(campos.into_iter().map(ValorJson::desde_campo))
+                .collect(),
+        );
+    }
+    Ok(serializar_tabla(registros))
+}
+
+/// Escribe los registros de una tabla como una lista JSON, con un objeto por línea.
+fn serializar_tabla(registros: Vec<Vec<(String, ValorJson)>>) -> String {
+    let objetos: Vec<String> = registros
+        .into_iter()
+        .map(|registro| format!("  {}", ValorJson::Objeto(registro).serializar()))
+        .collect();
+    if objetos.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", objetos.join(",\n"))
+}
+
+/// Devuelve las claves de los registros en el orden en que aparecen por primera vez.
+fn columnas_de(registros: &[Vec<(String, ValorJson)>]) -> Vec<String> {
+    let mut columnas: Vec<String> = Vec::new();
+    for (clave, _) in registros.iter().flatten() {
+        if !columnas.contains(clave) {
+            columnas.push(clave.to_string());
+        }
+    }
+    columnas
+}
+
+/// Parsea los registros de un documento: una lista de objetos, o una secuencia de objetos
+/// separados por espacios o saltos de línea, como en NDJSON.
+fn parsear_registros(texto: &str) -> io::Result<Vec<Vec<(String, ValorJson)>>> {
+    let mut parser = Parser {
+        caracteres: texto.chars().collect(),
+        posicion: 0,
+    };
+    parser.saltar_espacios();
+    if parser.caracteres.get(parser.posicion) != Some(&'{') {
+        return parsear_tabla(texto);
+    }
+    let mut registros = Vec::new();
+    while parser.posicion < parser.caracteres.len() {
+        match parser.valor()? {
+            ValorJson::Objeto(campos) => registros.push(campos),
+            _ => return Err(error_formato("cada línea debe ser un objeto")),
+        }
+        parser.saltar_espacios();
+    }
+    Ok(registros)
+}
+
+/// Parsea el documento de una tabla: una lista de objetos.
+fn parsear_tabla(texto: &str) -> io::Result<Vec<Vec<(String, ValorJson)>>> {
+    let mut parser = Parser {
+        caracteres: texto.chars().collect(),
+        posicion: 0,
+    };
+    let valor = parser.valor()?;
+    parser.saltar_espacios();
+    if parser.posicion < parser.caracteres.len() {
+        return Err(error_formato("contenido después del final del documento"));
+    }
+    let ValorJson::Lista(valores) = valor else {
+        return Err(error_formato("la tabla debe ser una lista de objetos"));
+    };
+    valores
+        .into_iter()
+        .map(|valor| match valor {
+            ValorJson::Objeto(campos) => Ok(campos),
+            _ => Err(error_formato(
+                "cada registro de la tabla debe ser un objeto",
+            )),
+        })
+        .collect()
+}
+
+/// Parser de documentos JSON por descenso recursivo.
+struct Parser {
+    caracteres: Vec<char>,
+    posicion: usize,
+}
+
+impl Parser {
+    fn saltar_espacios(&mut self) {
+        while self
+            .caracteres
+            .get(self.posicion)
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\u{feff}'))
+        {
+            self.posicion += 1;
+        }
+    }
+
+    fn siguiente(&mut self) -> io::Result<char> {
+        let caracter = *self
+            .caracteres
+            .get(self.posicion)
+            .ok_or_else(|| error_formato("fin inesperado del documento"))?;
+        self.posicion += 1;
+        Ok(caracter)
+    }
+
+    fn esperar(&mut self, esperado: char) -> io::Result<()> {
+        self.saltar_espacios();
+        if self.siguiente()? != esperado {
+            return Err(error_formato(&format!(
+                "se esperaba '{}' en la posición {}",
+                esperado,
+                self.posicion - 1
+            )));
+        }
+        Ok(())
+    }
+
+    /// Consume el carácter indicado si es el siguiente, salteando los espacios.
+    fn consumir(&mut self, esperado: char) -> bool {
+        self.saltar_espacios();
+        if self.caracteres.get(self.posicion) == Some(&esperado) {
+            self.posicion += 1;
+            return true;
+        }
+        false
+    }
+
+    fn valor(&mut self) -> io::Result<ValorJson> {
+        self.saltar_espacios();
+        match self.caracteres.get(self.posicion) {
+            Some('{') => self.objeto(),
+            Some('[') => self.lista(),
+            Some('"') => Ok(ValorJson::Texto(self.cadena()?)),
+            Some('t') => self.palabra("true", ValorJson::Booleano(true)),
+            Some('f') => self.palabra("false", ValorJson::Booleano(false)),
+            Some('n') => self.palabra("null", ValorJson::Nulo),
+            Some(_) => self.numero(),
+            None => Err(error_formato("fin inesperado del documento")),
+        }
+    }
+
+    fn palabra(&mut self, palabra: &str, valor: ValorJson) -> io::Result<ValorJson> {
+        for esperado in palabra.chars() {
+            if self.siguiente()? != esperado {
+                return Err(error_formato(&format!("se esperaba '{}'", palabra)));
+            }
+        }
+        Ok(valor)
+    }
+
+    fn numero(&mut self) -> io::Result<ValorJson> {
+        let inicio = self.posicion;
+        while self
+            .caracteres
+            .get(self.posicion)
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.posicion += 1;
+        }
+        let numero: String = self.caracteres[inicio..self.posicion].iter().collect();
+        if !es_numero(&numero) {
+            return Err(error_formato(&format!(
+                "valor inesperado en la posición {}",
+                inicio
+            )));
+        }
+        Ok(ValorJson::Numero(numero))
+    }
+
+    fn cadena(&mut self) -> io::Result<String> {
+        self.esperar('"')?;
+        let mut texto = String::new();
+        loop {
+            match self.siguiente()? {
+                '"' => return Ok(texto),
+                '\\' => match self.siguiente()? {
+                    'n' => texto.push('\n'),
+                    't' => texto.push('\t'),
+                    'r' => texto.push('\r'),
+                    'b' => texto.push('\u{8}'),
+                    'f' => texto.push('\u{c}'),
+                    'u' => texto.push(self.escape_unicode()?),
+                    caracter @ ('"' | '\\' | '/') => texto.push(caracter),
+                    _ => return Err(error_formato("secuencia de escape desconocida")),
+                },
+                caracter => texto.push(caracter),
+            }
+        }
+    }
+
+    /// Lee los cuatro dígitos de un `\uXXXX`, junto con el `\uXXXX` siguiente si es la segunda
+    /// mitad de un par sustituto.
+    fn escape_unicode(&mut self) -> io::Result<char> {
+        let codigo = self.hexadecimal()?;
+        let codigo = if (0xD800..0xDC00).contains(&codigo) {
+            if self.siguiente()? != '\\' || self.siguiente()? != 'u' {
+                return Err(error_formato("par sustituto incompleto"));
+            }
+            let bajo = self.hexadecimal()?;
+            if !(0xDC00..0xE000).contains(&bajo) {
+                return Err(error_formato("par sustituto incompleto"));
+            }
+            0x10000 + ((codigo - 0xD800) << 10) + (bajo - 0xDC00)
+        } else {
+            codigo
+        };
+        char::from_u32(codigo).ok_or_else(|| error_formato("carácter unicode inválido"))
+    }
+
+    fn hexadecimal(&mut self) -> io::Result<u32> {
+        let mut codigo = 0;
+        for _ in 0..4 {
+            let digito = self
+                .siguiente()?
+                .to_digit(16)
+                .ok_or_else(|| error_formato("secuencia \\u inválida"))?;
+            codigo = codigo * 16 + digito;
+        }
+        Ok(codigo)
+    }
+
+    fn lista(&mut self) -> io::Result<ValorJson> {
+        self.esperar('[')?;
+        let mut valores = Vec::new();
+        if self.consumir(']') {
+            return Ok(ValorJson::Lista(valores));
+        }
+        loop {
+            valores.push(self.valor()?);
+            if self.consumir(']') {
+                return Ok(ValorJson::Lista(valores));
+            }
+            self.esperar(',')?;
+        }
+    }
+
+    fn objeto(&mut self) -> io::Result<ValorJson> {
+        self.esperar('{')?;
+        let mut campos: Vec<(String, ValorJson)> = Vec::new();
+        if self.consumir('}') {
+            return Ok(ValorJson::Objeto(campos));
+        }
+        loop {
+            self.saltar_espacios();
+            let clave = self.cadena()?;
+            self.esperar(':')?;
+            let valor = self.valor()?;
+            match campos.iter_mut().find(|(existente, _)| *existente == clave) {
+                Some((_, anterior)) => *anterior = valor,
+                None => campos.push((clave, valor)),
+            }
+            if self.consumir('}') {
+                return Ok(ValorJson::Objeto(campos));
+            }
+            self.esperar(',')?;
+        }
+    }
+}
+
+/// Indica si el texto es un número según la gramática de JSON, como `-12`, `0.5` o `1e10`.
+fn es_numero(texto: &str) -> bool {
+    let texto = texto.strip_prefix('-').unwrap_or(texto);
+    let (mantisa, exponente) = match texto.find(['e', 'E']) {
+        Some(posicion) => (&texto[..posicion], Some(&texto[posicion + 1..])),
+        None => (texto, None),
+    };
+    let (entera, fraccion) = match mantisa.split_once('.') {
+        Some((entera, fraccion)) => (entera, Some(fraccion)),
+        None => (mantisa, None),
+    };
+    let digitos = |parte: &str| !parte.is_empty() && parte.chars().all(|c| c.is_ascii_digit());
+    let exponente_valido = exponente
+        .is_none_or(|exponente| digitos(exponente.strip_prefix(['+', '-']).unwrap_or(exponente)));
+    digitos(entera)
+        && (entera == "0" || !entera.starts_with('0'))
+        && fraccion.is_none_or(digitos)
+        && exponente_valido
+}
+
+/// Escribe el texto como cadena JSON, escapando las comillas, las barras invertidas y los
+/// caracteres de control.
+pub fn citar(texto: &str) -> String {
+    let mut citado = String::with_capacity(texto.len() + 2);
+    citado.push('"');
+    for caracter in texto.chars() {
+        match caracter {
+            '"' => citado.push_str("\\\""),
+            '\\' => citado.push_str("\\\\"),
+            '\n' => citado.push_str("\\n"),
+            '\r' => citado.push_str("\\r"),
+            '\t' => citado.push_str("\\t"),
+            caracter if caracter.is_control() => {
+                citado.push_str(&format!("\\u{:04x}", caracter as u32))
+            }
+            caracter => citado.push(caracter),
+        }
+    }
+    citado.push('"');
+    citado
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_a_csv() {
+        let texto = r#"[
+            {"nombre": "Luis, hijo", "edad": 34, "activo": true},
+            {"nombre": "Ana \"la\" ágil", "edad": null, "notas": [7, 9]},
+            {"nombre": "", "edad": -1.5e3}
+        ]"#;
+        assert_eq!(
+            a_csv(texto, ',', None).unwrap(),
+            "nombre,edad,activo,notas\n\
+             \"Luis, hijo\",34,true,\n\
+             \"Ana \"\"la\"\" ágil\",,,\"[7, 9]\"\n\
+             \"\",-1.5e3,,\n"
+        );
+        assert_eq!(a_csv("[]", ',', None).unwrap(), "\n");
+        assert_eq!(
+            a_csv(
+                "{\"nombre\": \"Ana\"}\n\n{\"edad\": 30, \"nombre\": \"Luis\"}\n",
+                ',',
+                None
+            )
+            .unwrap(),
+            "nombre,edad\nAna,\nLuis,30\n"
+        );
+        assert!(a_csv("{\"nombre\": 1}\n[1]", ',', None).is_err());
+        assert!(a_csv("1", ',', None).is_err());
+        assert!(a_csv("[{\"edad\": 01}]", ',', None).is_err());
+        assert!(a_csv("[{\"edad\": 1}", ',', None).is_err());
+    }
+
+    #[test]
+    fn test_agregar_registros_json() {
+        let texto = "[{\"nombre\": \"Ana\", \"edad\": 30}]";
+        let agregado = agregar(texto, "Luis,45\n\"dijo \"\"hola\"\"\",\n", ',', None).unwrap();
+        assert_eq!(
+            agregado,
+            "[\n  {\"nombre\": \"Ana\", \"edad\": 30},\n  {\"nombre\": \"Luis\", \"edad\": 45},\n  \
+             {\"nombre\": \"dijo \\\"hola\\\"\", \"edad\": null}\n]\n"
+        );
+        assert_eq!(
+            a_csv(&agregado, ',', None).unwrap(),
+            "nombre,edad\nAna,30\nLuis,45\n\"dijo \"\"hola\"\"\",\n"
+        );
+        assert_eq!(
+            agregar("[{\"Nombre\": \"Ana\", \"EDAD\": 30}]", "Luis,45\n", ',', None).unwrap(),
+            "[\n  {\"Nombre\": \"Ana\", \"EDAD\": 30},\n  {\"Nombre\": \"Luis\", \"EDAD\": 45}\n]\n"
+        );
+        assert!(es_numero("0.25") && es_numero("-3E+2"));
+        assert!(!es_numero("007") && !es_numero("1.") && !es_numero("NaN"));
+    }
+
+    #[test]
+    fn test_json_desde_csv() {
+        let csv = "nombre,edad\nAna,30\n\"Luis\nPérez\",\n";
+        let texto = desde_csv(csv, ',', None).unwrap();
+        assert_eq!(
+            texto,
+            "[\n  {\"nombre\": \"Ana\", \"edad\": 30},\n  {\"nombre\": \"Luis\\nPérez\", \"edad\": null}\n]\n"
+        );
+        assert_eq!(a_csv(&texto, ',', None).unwrap(), csv);
+        assert_eq!(desde_csv("nombre\n", ',', None).unwrap(), "[]\n");
+    }
+}
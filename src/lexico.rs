@@ -0,0 +1,378 @@
+use crate::errores;
+
+/// Caracteres que forman un token por sí mismos aunque no estén separados por espacios.
+const SIMBOLOS: &str = "(),;?=<>!~*";
+
+/// Operadores de dos caracteres, que forman un único token.
+const OPERADORES_DOBLES: [&str; 4] = [">=", "<=", "!=", "<>"];
+
+/// Palabras reservadas del lenguaje de consultas, que no pueden usarse como identificadores.
+const PALABRAS_CLAVE: [&str; 26] = [
+    "select", "from", "where", "group", "by", "having", "order", "asc", "desc", "limit", "as",
+    "insert", "into", "values", "update", "set", "delete", "explain", "and", "or", "not", "is",
+    "null", "begin", "commit", "rollback",
+];
+
+/// Tipo de un token de una consulta.
+///
+/// - `PalabraClave`: Una palabra reservada, como `select` o `and` (ver `PALABRAS_CLAVE`).
+/// - `Identificador`: El nombre de una tabla, una columna o una función.
+/// - `Texto`: Un literal de texto entre comillas simples.
+/// - `Numero`: Un literal numérico, entero o decimal y con signo opcional (ver `es_numero`).
+/// - `Operador`: Un operador de comparación (`=`, `<`, `>`, `>=`, `<=`, `!=`, `<>`), el `~` de
+///   las expresiones regulares o el `*` de `SELECT *` y
+///   `COUNT(*)`.
+/// - `Puntuacion`: Un paréntesis, una coma, el `;` que separa las sentencias o el `?` que marca
+///   un parámetro de una sentencia preparada.
+/// - `Parametro`: El valor de texto vinculado a un `?` de una sentencia preparada (ver
+///   `preparada`), que se interpreta como un literal de texto.
+/// - `Desconocido`: Una palabra que no es un identificador ni un número válidos.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TipoToken {
+    PalabraClave,
+    Identificador,
+    Texto,
+    Numero,
+    Operador,
+    Puntuacion,
+    Parametro,
+    Desconocido,
+}
+
+/// Token de una consulta SQL.
+///
+/// - `tipo`: El `TipoToken` del token.
+/// - `texto`: El texto del token; en minúsculas salvo los literales de texto, que conservan
+///   sus comillas, mayúsculas y espacios tal como están en la consulta, y los parámetros, que
+///   contienen el valor vinculado tal cual, sin comillas.
+/// - `posicion`: La posición, en bytes, en la que empieza el token dentro de la consulta.
+/// - `original`: El texto del token tal como está escrito en la consulta, con sus mayúsculas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub tipo: TipoToken,
+    pub texto: String,
+    pub posicion: usize,
+    pub original: String,
+}
+
+impl Token {
+    /// Indica si el token es la palabra clave o el símbolo indicado.
+    ///
+    /// # Parámetros
+    /// - `texto`: La palabra clave, en minúsculas, o el símbolo.
+    pub fn es(&self, texto: &str) -> bool {
+        !self.es_literal() && self.texto == texto
+    }
+
+    /// Indica si el token es un literal de texto, escrito en la consulta o vinculado a un
+    /// parámetro, que nunca coincide con una palabra clave ni con un símbolo.
+    pub fn es_literal(&self) -> bool {
+        matches!(self.tipo, TipoToken::Texto | TipoToken::Parametro)
+    }
+
+    /// Obtiene el valor de un literal de texto (ver `valor_literal`) o de un parámetro.
+    ///
+    /// # Retorno
+    /// El texto del literal, o `None` si el token no es un literal de texto bien cerrado.
+    pub fn valor_texto(&self) -> Option<String> {
+        match self.tipo {
+            TipoToken::Texto => valor_literal(&self.texto),
+            TipoToken::Parametro => Some(self.texto.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Divide una consulta SQL en tokens tipados.
+///
+/// Los literales de texto entre comillas simples se mantienen como un único token (con sus
+/// comillas y respetando mayúsculas y espacios), los paréntesis, comas y operadores forman
+/// tokens propios y el resto de las palabras se convierten a minúsculas. Dentro de un literal,
+/// una comilla se escribe duplicada (`'O''Brien'`) o precedida de una barra (`'O\'Brien'`).
+/// Los comentarios (`-- hasta el fin de la línea` y `/* ... */`) fuera de los literales se
+/// descartan.
+///
+/// # Parámetros
+/// - `consulta`: La consulta SQL a dividir.
+///
+/// # Retorno
+/// Un `Vec<Token>` con los tokens en el orden en que aparecen.
+pub fn analizar(consulta: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut palabra: Option<(usize, String)> = None;
+    let mut caracteres = consulta.char_indices().peekable();
+
+    while let Some((posicion, caracter)) = caracteres.next() {
+        let siguiente = caracteres.peek().map(|(_, siguiente)| *siguiente);
+        let comentario = match (caracter, siguiente) {
+            ('-', Some('-')) => Some("\n"),
+            ('/', Some('*')) => Some("*/"),
+            _ => None,
+        };
+        if let Some(fin) = comentario {
+            if let Some((inicio, texto)) = palabra.take() {
+                tokens.push(token_de_palabra(inicio, &texto));
+            }
+            let resto = &consulta[posicion + 2..];
+            let final_comentario = resto
+                .find(fin)
+                .map_or(consulta.len(), |indice| posicion + 2 + indice + fin.len());
+            while caracteres
+                .peek()
+                .is_some_and(|(siguiente, _)| *siguiente < final_comentario)
+            {
+                caracteres.next();
+            }
+            continue;
+        }
+        let separa = caracter == '\'' || caracter.is_whitespace() || SIMBOLOS.contains(caracter);
+        if separa {
+            if let Some((inicio, texto)) = palabra.take() {
+                tokens.push(token_de_palabra(inicio, &texto));
+            }
+        }
+        if caracter == '\'' {
+            let mut literal = String::from(caracter);
+            while let Some((_, caracter)) = caracteres.next() {
+                literal.push(caracter);
+                let escapa_comilla = (caracter == '\\' || caracter == '\'')
+                    && caracteres.peek().map(|(_, siguiente)| *siguiente) == Some('\'');
+                if escapa_comilla {
+                    literal.extend(caracteres.next().map(|(_, siguiente)| siguiente));
+                } else if caracter == '\'' {
+                    break;
+                }
+            }
+            tokens.push(Token {
+                tipo: TipoToken::Texto,
+                original: literal.to_string(),
+                texto: literal,
+                posicion,
+            });
+        } else if SIMBOLOS.contains(caracter) {
+            let tipo = if "(),;?".contains(caracter) {
+                TipoToken::Puntuacion
+            } else {
+                TipoToken::Operador
+            };
+            let mut texto = caracter.to_string();
+            if let Some(siguiente) = siguiente {
+                texto.push(siguiente);
+                if OPERADORES_DOBLES.contains(&texto.as_str()) {
+                    caracteres.next();
+                } else {
+                    texto.pop();
+                }
+            }
+            tokens.push(Token {
+                tipo,
+                original: texto.to_string(),
+                texto,
+                posicion,
+            });
+        } else if !caracter.is_whitespace() {
+            palabra
+                .get_or_insert_with(|| (posicion, String::new()))
+                .1
+                .push(caracter);
+        }
+    }
+    if let Some((inicio, texto)) = palabra {
+        tokens.push(token_de_palabra(inicio, &texto));
+    }
+    tokens
+}
+
+/// Clasifica una palabra de la consulta, que no es un literal de texto ni un símbolo.
+fn token_de_palabra(posicion: usize, palabra: &str) -> Token {
+    let texto = palabra.to_lowercase();
+    let tipo = if PALABRAS_CLAVE.contains(&texto.as_str()) {
+        TipoToken::PalabraClave
+    } else if es_numero(&texto) {
+        TipoToken::Numero
+    } else if es_identificador(&texto) {
+        TipoToken::Identificador
+    } else {
+        TipoToken::Desconocido
+    };
+    Token {
+        tipo,
+        texto,
+        posicion,
+        original: palabra.to_string(),
+    }
+}
+
+/// Reconstruye el texto de una secuencia de tokens consecutivos tal como está escrito en la
+/// consulta, con un espacio entre dos tokens solo si en la consulta no estaban pegados.
+///
+/// # Parámetros
+/// - `tokens`: Los tokens, tal como los devuelve `analizar`.
+pub fn texto_original(tokens: &[Token]) -> String {
+    let mut texto = String::new();
+    let mut fin_anterior = None;
+    for token in tokens {
+        if fin_anterior.is_some_and(|fin| fin < token.posicion) {
+            texto.push(' ');
+        }
+        texto.push_str(&token.original);
+        fin_anterior = Some(token.posicion + token.original.len());
+    }
+    texto
+}
+
+/// Indica si el texto es un número, entero o decimal, con signo opcional: `42`, `-5`, `3.14`,
+/// `-0.5` o `.5`. No admite exponentes ni valores especiales como `inf`.
+pub fn es_numero(texto: &str) -> bool {
+    let sin_signo = texto.strip_prefix(['-', '+']).unwrap_or(texto);
+    let (entera, decimal) = sin_signo.split_once('.').unwrap_or((sin_signo, ""));
+    !(entera.is_empty() && decimal.is_empty())
+        && entera.bytes().all(|byte| byte.is_ascii_digit())
+        && decimal.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn es_identificador(texto: &str) -> bool {
+    let mut caracteres = texto.chars();
+    match caracteres.next() {
+        Some(primero) if primero.is_alphabetic() || primero == '_' => {
+            caracteres.all(|c| c.is_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+/// Arma el error de sintaxis para el token en la posición indicada.
+///
+/// # Parámetros
+/// - `tokens`: Los tokens de la consulta.
+/// - `index`: La posición, dentro de `tokens`, del token que no se esperaba.
+///
+/// # Retorno
+/// `Errores::TokenInesperado` con el texto y la posición del token, o
+/// `Errores::FinInesperado` con la posición en la que termina el último token si los tokens
+/// se acaban antes de esa posición.
+pub fn error_de_sintaxis(tokens: &[Token], index: usize) -> errores::Errores {
+    match tokens.get(index) {
+        Some(token) => errores::Errores::TokenInesperado {
+            token: token.texto.to_string(),
+            posicion: token.posicion,
+        },
+        None => errores::Errores::FinInesperado {
+            posicion: tokens
+                .last()
+                .map_or(0, |ultimo| ultimo.posicion + ultimo.texto.len()),
+        },
+    }
+}
+
+/// Obtiene el texto de un token literal, quitando las comillas que lo delimitan y resolviendo
+/// las comillas escapadas (`''` o `\'`).
+///
+/// # Parámetros
+/// - `token`: El texto del token tal como lo devuelve `analizar`.
+///
+/// # Retorno
+/// El texto del literal, o `None` si el token no es un literal entre comillas bien cerrado.
+pub fn valor_literal(token: &str) -> Option<String> {
+    let mut caracteres = token.strip_prefix('\'')?.chars().peekable();
+    let mut valor = String::new();
+    while let Some(caracter) = caracteres.next() {
+        if (caracter == '\\' || caracter == '\'') && caracteres.peek() == Some(&'\'') {
+            valor.extend(caracteres.next());
+        } else if caracter == '\'' {
+            return match caracteres.next() {
+                None => Some(valor),
+                Some(_) => None,
+            };
+        } else {
+            valor.push(caracter);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analizar_clasifica_los_tokens() {
+        let tokens = analizar("SELECT IIF(Edad>30,'Sí, Mayor',x.y) FROM Personas");
+        let tipos: Vec<(TipoToken, &str, usize)> = tokens
+            .iter()
+            .map(|token| (token.tipo, token.texto.as_str(), token.posicion))
+            .collect();
+        assert_eq!(
+            tipos,
+            vec![
+                (TipoToken::PalabraClave, "select", 0),
+                (TipoToken::Identificador, "iif", 7),
+                (TipoToken::Puntuacion, "(", 10),
+                (TipoToken::Identificador, "edad", 11),
+                (TipoToken::Operador, ">", 15),
+                (TipoToken::Numero, "30", 16),
+                (TipoToken::Puntuacion, ",", 18),
+                (TipoToken::Texto, "'Sí, Mayor'", 19),
+                (TipoToken::Puntuacion, ",", 31),
+                (TipoToken::Desconocido, "x.y", 32),
+                (TipoToken::Puntuacion, ")", 35),
+                (TipoToken::PalabraClave, "from", 37),
+                (TipoToken::Identificador, "personas", 42),
+            ]
+        );
+        assert_eq!(tokens[7].valor_texto(), Some("Sí, Mayor".to_string()));
+        assert!(tokens[0].es("select"));
+        assert!(!analizar("'select'")[0].es("select"));
+
+        let operadores: Vec<String> = analizar("a>=1 and b <= 2 and c< =3 and d!=4 and e<>5")
+            .into_iter()
+            .filter(|token| token.tipo == TipoToken::Operador)
+            .map(|token| token.texto)
+            .collect();
+        assert_eq!(operadores, vec![">=", "<=", "<", "=", "!=", "<>"]);
+
+        let tipos: Vec<TipoToken> = analizar("-5 3.14 -0.5 .5 +7 1.2.3 1e5 - .")
+            .into_iter()
+            .map(|token| token.tipo)
+            .collect();
+        assert_eq!(
+            tipos,
+            [vec![TipoToken::Numero; 5], vec![TipoToken::Desconocido; 4]].concat()
+        );
+    }
+
+    #[test]
+    fn test_analizar_descarta_comentarios() {
+        let tokens = analizar(
+            "-- migración inicial\nSELECT a--b\nFROM /* tabla */personas WHERE x = '--no'/**/",
+        );
+        let textos: Vec<&str> = tokens.iter().map(|token| token.texto.as_str()).collect();
+        assert_eq!(
+            textos,
+            vec!["select", "a", "from", "personas", "where", "x", "=", "'--no'"]
+        );
+        assert_eq!(tokens[3].posicion, 50);
+        assert_eq!(analizar("SELECT /* sin cerrar").len(), 1);
+    }
+
+    #[test]
+    fn test_texto_original_conserva_mayusculas_y_espacios() {
+        let tokens = analizar("SELECT IIF(Edad>30,  'Sí' , x) ,Count( * ) /* fin */ FROM t");
+        assert_eq!(texto_original(&tokens[1..11]), "IIF(Edad>30, 'Sí' , x)");
+        assert_eq!(texto_original(&tokens[12..16]), "Count( * )");
+        assert_eq!(texto_original(&tokens[16..]), "FROM t");
+        assert_eq!(texto_original(&[]), "");
+    }
+
+    #[test]
+    fn test_valor_literal() {
+        assert_eq!(valor_literal("'O''Brien'"), Some("O'Brien".to_string()));
+        assert_eq!(valor_literal(r"'D\'Amico'"), Some("D'Amico".to_string()));
+        assert_eq!(valor_literal(r"'C:\temp'"), Some(r"C:\temp".to_string()));
+        assert_eq!(valor_literal("''''"), Some("'".to_string()));
+        assert_eq!(valor_literal("''"), Some(String::new()));
+        assert_eq!(valor_literal("'sin cerrar"), None);
+        assert_eq!(valor_literal("'abc' extra"), None);
+        assert_eq!(valor_literal("abc"), None);
+    }
+}
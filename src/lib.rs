@@ -0,0 +1,44 @@
+//! Base de datos que ejecuta consultas SQL sobre tablas guardadas en archivos CSV.
+//!
+//! Además del ejecutable, la biblioteca permite crear y procesar consultas desde otros
+//! programas: `consulta::SQLConsulta` crea las consultas y `select::ConsultaSelect::filas`
+//! recorre el resultado de un `SELECT` fila por fila, `preparada::preparar` prepara sentencias
+//! con parámetros `?` que se ejecutan con distintos valores sin escribirlos en el texto SQL, y
+//! `transaccion::ejecutar_sentencias` ejecuta un lote de sentencias, con transacciones.
+//! `servidor::servir` atiende las consultas por HTTP y responde los resultados en JSON, y
+//! `postgres::servir` atiende conexiones con el protocolo de PostgreSQL, como las de `psql`.
+
+pub mod aleatorio;
+pub mod almacenamiento;
+pub mod archivo;
+pub mod argumentos;
+pub mod bloqueo;
+pub mod cache;
+pub mod comandos;
+pub mod consulta;
+pub mod copia;
+pub mod delete;
+pub mod diario;
+pub mod errores;
+pub mod esquema;
+pub mod estadisticas;
+pub mod expresion;
+pub mod fecha;
+pub mod gzip;
+pub mod insert;
+pub mod json;
+pub mod lexico;
+pub mod patron;
+pub mod planificador;
+pub mod postgres;
+pub mod preparada;
+pub mod registro;
+pub mod resolvedor;
+pub mod revision;
+pub mod salida;
+pub mod select;
+pub mod servidor;
+pub mod sintaxis;
+pub mod terminal;
+pub mod transaccion;
+pub mod update;
@@ -1,48 +1,148 @@
-mod archivo;
-mod consulta;
-mod delete;
-mod errores;
-mod insert;
-mod select;
-mod update;
+use base_de_datos::argumentos::Comando;
+use base_de_datos::{
+    argumentos, comandos, errores, postgres, registro, resolvedor, servidor, terminal, transaccion,
+};
+use std::io::Write;
+use std::process::ExitCode;
 
 /// Función principal que se encarga de manejar la ejecución del programa.
 ///
-/// Esta función llama a `ejecutar` y gestiona cualquier error que ocurra durante la ejecución,
-/// imprimiendo la descripción del error cuando es necesario.
-
-fn main() {
-    match ejecutar() {
-        Ok(_) => {}
-        Err(error) => error.imprimir_desc(),
-    };
+/// Esta función obtiene los argumentos y la consulta, llama a `ejecutar` y gestiona cualquier
+/// error que ocurra durante la ejecución, imprimiendo la descripción del error; con
+/// `--errores-json`, el error se escribe como JSON en la salida de errores (ver
+/// `Errores::escribir_json`). Con `--help` muestra la ayuda, y si los argumentos no son
+/// válidos indica además cómo verla.
+///
+/// # Retorno
+/// El código de salida del proceso: `0` si todo se ejecutó correctamente, o el que corresponde
+/// al tipo de error (ver `Errores::codigo_salida`).
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut consulta = None;
+    let resultado = argumentos::Argumentos::parsear(&args).and_then(|argumentos| {
+        if argumentos.ayuda {
+            print!("{}", argumentos::AYUDA);
+            return Ok(());
+        }
+        registro::fijar_nivel(argumentos.verbosidad);
+        let consulta = consulta.insert(argumentos.leer_consulta()?);
+        ejecutar(&argumentos, consulta)
+    });
+    match resultado {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            let codigo = error.codigo_salida();
+            // Se busca la opción directamente para informar así también los errores en los
+            // argumentos.
+            if args
+                .iter()
+                .any(|arg| argumentos::es_opcion_errores_json(arg))
+            {
+                let _ = error.escribir_json(consulta.as_deref(), &mut std::io::stderr().lock());
+            } else {
+                let argumento_invalido = matches!(error, errores::Errores::ArgumentoInvalido(_));
+                error.imprimir_desc();
+                if argumento_invalido {
+                    eprintln!("Usa --help para ver las opciones disponibles.");
+                }
+            }
+            ExitCode::from(codigo)
+        }
+    }
 }
 
 /// Ejecuta la lógica principal del programa, gestionando la consulta SQL y procesando el archivo correspondiente.
 ///
 /// Este método realiza las siguientes acciones:
-/// 1. Obtiene los argumentos del programa.
-/// 2. Verifica si la cantidad de argumentos es válida.
-/// 3. Parsea la consulta SQL.
-/// 4. Procesa la consulta y genera los resultados.
+/// 1. Crea el resolvedor de las tablas. Con `--serve-http`, en lugar de ejecutar sentencias
+///    atiende consultas por HTTP (ver `servidor::servir`), y con `--serve-pg` atiende
+///    conexiones con el protocolo de PostgreSQL (ver `postgres::servir`). Los subcomandos
+///    `import` y `schema` se ejecutan aparte (ver `comandos`), y `export` y `check` se
+///    traducen a las sentencias equivalentes.
+/// 2. Parsea las sentencias SQL, separadas por `;`, que `main` leyó del argumento, del archivo
+///    indicado con `--file` o de la entrada estándar (ver `Argumentos::leer_consulta`).
+/// 3. Ejecuta las sentencias, con sus transacciones, y escribe los resultados en la salida
+///    estándar o en el archivo indicado con `--output`, a través de un buffer que se vacía al
+///    terminar el lote, aunque una sentencia falle (ver `ejecutar_sentencias`). Con `--dry-run` las tablas no se modifican y en la salida de
+///    errores se informan los registros que se les agregarían. Con `--parallel` los `SELECT`
+///    consecutivos se ejecutan al mismo tiempo. Si la salida estándar es una terminal, los
+///    encabezados se resaltan con colores.
 ///
 /// # Retorno
 /// - `Ok(())`: Si todo se ejecuta correctamente.
 /// - `Err(errores::Errores)`: Si ocurre algún error durante la ejecución.
-
-fn ejecutar() -> Result<(), errores::Errores> {
-    let args: Vec<String> = std::env::args().collect();
-
-    if args.len() != 3 {
-        return Err(errores::Errores::Error);
+fn ejecutar(argumentos: &argumentos::Argumentos, consulta: &str) -> Result<(), errores::Errores> {
+    let resolvedor =
+        resolvedor::crear_resolvedor(&argumentos.ruta_tablas, argumentos.extensiones.as_deref())?;
+    if let Some(direccion) = &argumentos.servir_http {
+        return servidor::servir(
+            direccion,
+            resolvedor.as_ref(),
+            &argumentos.opciones_archivo,
+            argumentos.permitir_escritura,
+        );
+    }
+    if let Some(direccion) = &argumentos.servir_pg {
+        return postgres::servir(
+            direccion,
+            resolvedor.as_ref(),
+            &argumentos.opciones_archivo,
+            argumentos.permitir_escritura,
+        );
+    }
+    if let Comando::Importar { tabla, archivo } = &argumentos.comando {
+        let importados = comandos::importar(
+            archivo,
+            tabla,
+            resolvedor.as_ref(),
+            &argumentos.opciones_archivo,
+        )?;
+        eprintln!("Se importaron {} registros en {}", importados, tabla);
+        return Ok(());
     }
 
-    let ruta_tablas: &String = &args[1];
-    let consulta_sin_parsear = &args[2];
-
-    let mut consulta = consulta::SQLConsulta::crear_consulta(consulta_sin_parsear, ruta_tablas)
-        .map_err(|_| errores::Errores::Error)?;
-
-    consulta.procesar_consulta()?;
-    Ok(())
+    let mut opciones_salida = argumentos.opciones_salida.clone();
+    opciones_salida.color =
+        argumentos.ruta_salida.is_none() && terminal::usar_color(&std::io::stdout());
+    let mut destino: Box<dyn Write> = match &argumentos.ruta_salida {
+        Some(ruta_salida) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(ruta_salida)
+                .map_err(|error| errores::Errores::de_archivo(error, ruta_salida))?,
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+    let consulta = match &argumentos.comando {
+        Comando::Esquema { tablas } => {
+            return comandos::escribir_esquema(
+                tablas,
+                resolvedor.as_ref(),
+                &argumentos.opciones_archivo,
+                &opciones_salida,
+                &mut destino,
+            )
+        }
+        Comando::Exportar { tabla } => comandos::sentencia_de_exportacion(tabla)?,
+        Comando::Revisar { tablas, reparar } => {
+            comandos::sentencias_de_revision(tablas, *reparar, resolvedor.as_ref())?
+        }
+        Comando::Consulta | Comando::Importar { .. } => consulta.to_string(),
+    };
+    let consulta = consulta.as_str();
+    if argumentos.simular {
+        return transaccion::simular_sentencias(
+            consulta,
+            resolvedor.as_ref(),
+            &argumentos.opciones_archivo,
+            &opciones_salida,
+            &mut destino,
+            &mut std::io::stderr().lock(),
+        );
+    }
+    transaccion::ejecutar_sentencias(
+        consulta,
+        resolvedor.as_ref(),
+        &argumentos.opciones_archivo,
+        &opciones_salida,
+        &mut destino,
+    )
 }
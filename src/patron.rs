@@ -0,0 +1,773 @@
+use crate::errores;
+
+/// Longitud máxima, en caracteres, de un patrón de búsqueda.
+pub const LONGITUD_MAXIMA_PATRON: usize = 1024;
+/// Longitud máxima, en caracteres, del texto sobre el que se evalúa un patrón.
+pub const LONGITUD_MAXIMA_TEXTO: usize = 64 * 1024;
+
+/// Elemento de un patrón compilado.
+///
+/// - `Caracter`: Un carácter literal.
+/// - `Cualquiera`: Exactamente un carácter cualquiera (`_` en `LIKE`).
+/// - `Secuencia`: Cero o más caracteres cualesquiera (`%` en `LIKE`).
+#[derive(Debug, Clone, PartialEq)]
+enum Elemento {
+    Caracter(char),
+    Cualquiera,
+    Secuencia,
+}
+
+/// Instrucción del programa en que se compila una expresión regular.
+///
+/// - `Caracter`: Consume el carácter indicado.
+/// - `Cualquiera`: Consume un carácter cualquiera (`.`).
+/// - `Clase`: Consume un carácter de la clase (`[a-z]`, `\d`, ...).
+/// - `Inicio`: Verifica que se está al principio del texto (`^`), sin consumir.
+/// - `Fin`: Verifica que se está al final del texto (`$`), sin consumir.
+/// - `Dividir`: Continúa por las dos instrucciones indicadas a la vez.
+/// - `Saltar`: Continúa por la instrucción indicada.
+/// - `Coincidencia`: El patrón coincide.
+#[derive(Debug, Clone, PartialEq)]
+enum Instruccion {
+    Caracter(char),
+    Cualquiera,
+    Clase(Clase),
+    Inicio,
+    Fin,
+    Dividir(usize, usize),
+    Saltar(usize),
+    Coincidencia,
+}
+
+/// Conjunto de caracteres de una expresión regular, como rangos inclusivos.
+#[derive(Debug, Clone, PartialEq)]
+struct Clase {
+    rangos: Vec<(char, char)>,
+    negada: bool,
+}
+
+impl Clase {
+    fn contiene(&self, caracter: char) -> bool {
+        let incluido = self
+            .rangos
+            .iter()
+            .any(|(desde, hasta)| *desde <= caracter && caracter <= *hasta);
+        incluido != self.negada
+    }
+
+    /// La clase de una secuencia de escape como `\d`, `\w` o `\s` (o su negación en
+    /// mayúsculas), si lo es.
+    fn de_escape(escape: char) -> Option<Clase> {
+        let rangos = match escape.to_ascii_lowercase() {
+            'd' => vec![('0', '9')],
+            'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            's' => vec![(' ', ' '), ('\t', '\r')],
+            _ => return None,
+        };
+        Some(Clase {
+            rangos,
+            negada: escape.is_ascii_uppercase(),
+        })
+    }
+}
+
+/// Forma en que se evalúa un patrón.
+///
+/// - `Like`: Un patrón de `LIKE`, que debe coincidir con el texto completo.
+/// - `Regexp`: Una expresión regular compilada, que puede coincidir con cualquier parte del
+///   texto.
+#[derive(Debug, Clone, PartialEq)]
+enum Forma {
+    Like(Vec<Elemento>),
+    Regexp(Vec<Instruccion>),
+}
+
+/// Patrón de búsqueda compilado, listo para evaluarse sobre los valores de una columna.
+///
+/// La comparación no usa vuelta atrás exponencial: su costo es, en el peor caso, proporcional
+/// al producto de las longitudes del patrón y del texto, y ambas están acotadas, de modo que
+/// ningún patrón puede dejar la evaluación de una consulta bloqueada.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patron {
+    forma: Forma,
+    ignorar_mayusculas: bool,
+}
+
+impl Patron {
+    /// Compila un patrón de `LIKE`, donde `%` representa cualquier secuencia de caracteres y
+    /// `_` un único carácter. Una barra invertida hace literal al carácter siguiente.
+    ///
+    /// # Parámetros
+    /// - `patron`: El texto del patrón.
+    ///
+    /// # Retorno
+    /// Retorna el `Patron` o `Errores::PatronInvalido` si supera la longitud máxima o termina
+    /// con una barra invertida sin carácter a escapar.
+    pub fn like(patron: &str) -> Result<Patron, errores::Errores> {
+        if patron.chars().count() > LONGITUD_MAXIMA_PATRON {
+            return Err(errores::Errores::PatronInvalido(format!(
+                "el patrón supera los {} caracteres",
+                LONGITUD_MAXIMA_PATRON
+            )));
+        }
+        let mut elementos = Vec::new();
+        let mut caracteres = patron.chars();
+        while let Some(caracter) = caracteres.next() {
+            let elemento = match caracter {
+                '%' => Elemento::Secuencia,
+                '_' => Elemento::Cualquiera,
+                '\\' => match caracteres.next() {
+                    Some(escapado) => Elemento::Caracter(escapado),
+                    None => {
+                        return Err(errores::Errores::PatronInvalido(
+                            "el patrón termina con una barra invertida".to_string(),
+                        ))
+                    }
+                },
+                caracter => Elemento::Caracter(caracter),
+            };
+            // Varios `%` seguidos equivalen a uno solo.
+            if elemento == Elemento::Secuencia && elementos.last() == Some(&Elemento::Secuencia) {
+                continue;
+            }
+            elementos.push(elemento);
+        }
+        Ok(Patron {
+            forma: Forma::Like(elementos),
+            ignorar_mayusculas: false,
+        })
+    }
+
+    /// Compila un patrón de `ILIKE`: como `like`, pero sin distinguir mayúsculas de minúsculas
+    /// (ver `plegar_mayusculas`).
+    ///
+    /// # Parámetros
+    /// - `patron`: El texto del patrón.
+    ///
+    /// # Retorno
+    /// Retorna el `Patron` o `Errores::PatronInvalido` en los mismos casos que `like`.
+    pub fn ilike(patron: &str) -> Result<Patron, errores::Errores> {
+        let mut patron = Patron::like(&plegar_mayusculas(patron))?;
+        patron.ignorar_mayusculas = true;
+        Ok(patron)
+    }
+
+    /// Compila una expresión regular, que coincide si encuentra una coincidencia en cualquier
+    /// parte del texto (salvo que se ancle con `^` o `$`).
+    ///
+    /// Se admiten caracteres literales, `.`, clases (`[a-z]`, `[^,]`, `\d`, `\w`, `\s` y sus
+    /// negaciones `\D`, `\W`, `\S`), anclas `^` y `$`, grupos `( )`, alternativas `|` y los
+    /// cuantificadores `*`, `+`, `?`, `{n}`, `{n,}` y `{n,m}`. Una barra invertida hace literal
+    /// al carácter siguiente.
+    ///
+    /// # Parámetros
+    /// - `patron`: El texto de la expresión regular.
+    ///
+    /// # Retorno
+    /// Retorna el `Patron` o `Errores::PatronInvalido` si la expresión está mal formada o
+    /// supera la longitud máxima (también una vez expandidas sus repeticiones).
+    pub fn regexp(patron: &str) -> Result<Patron, errores::Errores> {
+        let caracteres: Vec<char> = patron.chars().collect();
+        if caracteres.len() > LONGITUD_MAXIMA_PATRON {
+            return Err(errores::Errores::PatronInvalido(format!(
+                "el patrón supera los {} caracteres",
+                LONGITUD_MAXIMA_PATRON
+            )));
+        }
+        let mut parser = ParserRegexp {
+            caracteres: &caracteres,
+            posicion: 0,
+        };
+        let nodo = parser.alternativa()?;
+        if parser.posicion < caracteres.len() {
+            return Err(invalido(format!(
+                "'{}' inesperado en la posición {}",
+                caracteres[parser.posicion], parser.posicion
+            )));
+        }
+        let mut programa = Vec::new();
+        compilar_nodo(&nodo, &mut programa)?;
+        programa.push(Instruccion::Coincidencia);
+        Ok(Patron {
+            forma: Forma::Regexp(programa),
+            ignorar_mayusculas: false,
+        })
+    }
+
+    /// Indica si el texto completo coincide con el patrón.
+    ///
+    /// # Parámetros
+    /// - `texto`: El texto a evaluar.
+    ///
+    /// # Retorno
+    /// Retorna si hay coincidencia, o `Errores::PatronInvalido` si el texto supera la longitud
+    /// máxima admitida.
+    pub fn coincide(&self, texto: &str) -> Result<bool, errores::Errores> {
+        let texto: Vec<char> = if self.ignorar_mayusculas {
+            plegar_mayusculas(texto).chars().collect()
+        } else {
+            texto.chars().collect()
+        };
+        if texto.len() > LONGITUD_MAXIMA_TEXTO {
+            return Err(errores::Errores::PatronInvalido(format!(
+                "el texto a comparar supera los {} caracteres",
+                LONGITUD_MAXIMA_TEXTO
+            )));
+        }
+        match &self.forma {
+            Forma::Like(elementos) => Ok(coincide_like(elementos, &texto)),
+            Forma::Regexp(programa) => Ok(coincide_regexp(programa, &texto)),
+        }
+    }
+}
+
+/// Pliega las mayúsculas y minúsculas de un texto para compararlo sin distinguirlas, según
+/// Unicode: cada carácter se lleva a mayúsculas y después a minúsculas, de modo que también
+/// se unen formas que `to_lowercase` deja distintas, como `ß` y `SS` o `ς` y `Σ`.
+///
+/// # Parámetros
+/// - `texto`: El texto a plegar.
+///
+/// # Retorno
+/// El texto plegado, que puede tener más caracteres que el original (`ß` se pliega a `ss`).
+pub fn plegar_mayusculas(texto: &str) -> String {
+    if texto.is_ascii() {
+        return texto.to_ascii_lowercase();
+    }
+    texto
+        .chars()
+        .flat_map(char::to_uppercase)
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Indica si el texto completo coincide con los elementos de un patrón de `LIKE`.
+fn coincide_like(elementos: &[Elemento], texto: &[char]) -> bool {
+    // Recorrido con un único punto de retorno: el último `%` visto. Ante un fallo se
+    // reintenta haciendo que ese `%` abarque un carácter más, sin volver más atrás.
+    let mut posicion_texto = 0;
+    let mut posicion_patron = 0;
+    let mut ultimo_comodin: Option<(usize, usize)> = None;
+    while posicion_texto < texto.len() {
+        match elementos.get(posicion_patron) {
+            Some(Elemento::Secuencia) => {
+                posicion_patron += 1;
+                ultimo_comodin = Some((posicion_patron, posicion_texto));
+                continue;
+            }
+            Some(Elemento::Cualquiera) => {
+                posicion_patron += 1;
+                posicion_texto += 1;
+                continue;
+            }
+            Some(Elemento::Caracter(caracter)) if *caracter == texto[posicion_texto] => {
+                posicion_patron += 1;
+                posicion_texto += 1;
+                continue;
+            }
+            _ => {}
+        }
+        match ultimo_comodin {
+            Some((patron_siguiente, texto_inicio)) => {
+                posicion_patron = patron_siguiente;
+                posicion_texto = texto_inicio + 1;
+                ultimo_comodin = Some((patron_siguiente, texto_inicio + 1));
+            }
+            None => return false,
+        }
+    }
+    elementos[posicion_patron..]
+        .iter()
+        .all(|elemento| *elemento == Elemento::Secuencia)
+}
+
+/// Cantidad máxima de instrucciones de una expresión regular compilada, que acota el costo de
+/// evaluarla aunque sus repeticiones (`{n,m}`) la hagan crecer.
+const INSTRUCCIONES_MAXIMAS: usize = 16 * 1024;
+
+/// Nodo del árbol de una expresión regular.
+///
+/// - `Vacio`: La expresión vacía, que coincide sin consumir caracteres.
+/// - `Instruccion`: Un carácter, una clase o un ancla.
+/// - `Secuencia`: Los nodos uno tras otro.
+/// - `Alternativa`: Cualquiera de los nodos.
+/// - `Repeticion`: El nodo repetido entre `minimo` y `maximo` veces (sin máximo si es `None`).
+#[derive(Debug)]
+enum Nodo {
+    Vacio,
+    Instruccion(Instruccion),
+    Secuencia(Vec<Nodo>),
+    Alternativa(Vec<Nodo>),
+    Repeticion {
+        nodo: Box<Nodo>,
+        minimo: usize,
+        maximo: Option<usize>,
+    },
+}
+
+fn invalido(motivo: String) -> errores::Errores {
+    errores::Errores::PatronInvalido(format!("expresión regular inválida, {}", motivo))
+}
+
+/// Parser descendente recursivo de expresiones regulares.
+struct ParserRegexp<'a> {
+    caracteres: &'a [char],
+    posicion: usize,
+}
+
+impl ParserRegexp<'_> {
+    fn siguiente(&self) -> Option<char> {
+        self.caracteres.get(self.posicion).copied()
+    }
+
+    fn consumir(&mut self, esperado: char) -> bool {
+        let coincide = self.siguiente() == Some(esperado);
+        if coincide {
+            self.posicion += 1;
+        }
+        coincide
+    }
+
+    /// `secuencia ('|' secuencia)*`
+    fn alternativa(&mut self) -> Result<Nodo, errores::Errores> {
+        let mut opciones = vec![self.secuencia()?];
+        while self.consumir('|') {
+            opciones.push(self.secuencia()?);
+        }
+        Ok(if opciones.len() == 1 {
+            opciones.remove(0)
+        } else {
+            Nodo::Alternativa(opciones)
+        })
+    }
+
+    /// Átomos con sus cuantificadores, hasta un `|`, un `)` o el final.
+    fn secuencia(&mut self) -> Result<Nodo, errores::Errores> {
+        let mut nodos = Vec::new();
+        while let Some(caracter) = self.siguiente() {
+            if caracter == '|' || caracter == ')' {
+                break;
+            }
+            let atomo = self.atomo()?;
+            nodos.push(self.cuantificar(atomo)?);
+        }
+        Ok(match nodos.len() {
+            0 => Nodo::Vacio,
+            1 => nodos.remove(0),
+            _ => Nodo::Secuencia(nodos),
+        })
+    }
+
+    fn cuantificar(&mut self, mut nodo: Nodo) -> Result<Nodo, errores::Errores> {
+        loop {
+            let (minimo, maximo) = match self.siguiente() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => {
+                    // Deja la posición en la llave que cierra, que se consume abajo.
+                    self.posicion += 1;
+                    self.limites()?
+                }
+                _ => return Ok(nodo),
+            };
+            self.posicion += 1;
+            nodo = Nodo::Repeticion {
+                nodo: Box::new(nodo),
+                minimo,
+                maximo,
+            };
+        }
+    }
+
+    /// `n}`, `n,}` o `n,m}`, después de la llave que abre.
+    fn limites(&mut self) -> Result<(usize, Option<usize>), errores::Errores> {
+        let minimo = self
+            .numero()
+            .ok_or_else(|| invalido("falta la cantidad de repeticiones".to_string()))?;
+        let maximo = if self.consumir(',') {
+            self.numero()
+        } else {
+            Some(minimo)
+        };
+        if self.siguiente() != Some('}') || maximo.is_some_and(|maximo| maximo < minimo) {
+            return Err(invalido("repetición mal formada".to_string()));
+        }
+        Ok((minimo, maximo))
+    }
+
+    fn numero(&mut self) -> Option<usize> {
+        let inicio = self.posicion;
+        while self
+            .siguiente()
+            .is_some_and(|caracter| caracter.is_ascii_digit())
+        {
+            self.posicion += 1;
+        }
+        let digitos: String = self.caracteres[inicio..self.posicion].iter().collect();
+        digitos.parse().ok()
+    }
+
+    fn atomo(&mut self) -> Result<Nodo, errores::Errores> {
+        let caracter = self
+            .siguiente()
+            .ok_or_else(|| invalido("termina inesperadamente".to_string()))?;
+        self.posicion += 1;
+        let instruccion = match caracter {
+            '(' => {
+                let nodo = self.alternativa()?;
+                if !self.consumir(')') {
+                    return Err(invalido("falta cerrar un paréntesis".to_string()));
+                }
+                return Ok(nodo);
+            }
+            '[' => Instruccion::Clase(self.clase()?),
+            '.' => Instruccion::Cualquiera,
+            '^' => Instruccion::Inicio,
+            '$' => Instruccion::Fin,
+            '\\' => {
+                let escapado = self.escapado()?;
+                match Clase::de_escape(escapado) {
+                    Some(clase) => Instruccion::Clase(clase),
+                    None => Instruccion::Caracter(escapado),
+                }
+            }
+            '*' | '+' | '?' | '{' => {
+                return Err(invalido(format!(
+                    "'{}' sin nada que repetir en la posición {}",
+                    caracter,
+                    self.posicion - 1
+                )))
+            }
+            caracter => Instruccion::Caracter(caracter),
+        };
+        Ok(Nodo::Instruccion(instruccion))
+    }
+
+    fn escapado(&mut self) -> Result<char, errores::Errores> {
+        let escapado = self
+            .siguiente()
+            .ok_or_else(|| invalido("termina con una barra invertida".to_string()))?;
+        self.posicion += 1;
+        Ok(match escapado {
+            'n' => '\n',
+            't' => '\t',
+            escapado => escapado,
+        })
+    }
+
+    /// El contenido de una clase, después del corchete que abre.
+    fn clase(&mut self) -> Result<Clase, errores::Errores> {
+        let negada = self.consumir('^');
+        let mut rangos = Vec::new();
+        let mut primero = true;
+        loop {
+            let caracter = self
+                .siguiente()
+                .ok_or_else(|| invalido("falta cerrar un corchete".to_string()))?;
+            self.posicion += 1;
+            // Un `]` al principio de la clase es literal.
+            if caracter == ']' && !primero {
+                break;
+            }
+            primero = false;
+            let desde = if caracter == '\\' {
+                let escapado = self.escapado()?;
+                if let Some(clase) = Clase::de_escape(escapado) {
+                    if clase.negada {
+                        return Err(invalido(format!(
+                            "'\\{}' no se admite dentro de una clase",
+                            escapado
+                        )));
+                    }
+                    rangos.extend(clase.rangos);
+                    continue;
+                }
+                escapado
+            } else {
+                caracter
+            };
+            let es_rango = self.siguiente() == Some('-')
+                && self
+                    .caracteres
+                    .get(self.posicion + 1)
+                    .is_some_and(|hasta| *hasta != ']');
+            if !es_rango {
+                rangos.push((desde, desde));
+                continue;
+            }
+            self.posicion += 1;
+            let mut hasta = self.siguiente().unwrap_or(desde);
+            self.posicion += 1;
+            if hasta == '\\' {
+                hasta = self.escapado()?;
+            }
+            if hasta < desde {
+                return Err(invalido(format!("rango '{}-{}' invertido", desde, hasta)));
+            }
+            rangos.push((desde, hasta));
+        }
+        Ok(Clase { rangos, negada })
+    }
+}
+
+/// Agrega al programa las instrucciones de un nodo.
+fn compilar_nodo(nodo: &Nodo, programa: &mut Vec<Instruccion>) -> Result<(), errores::Errores> {
+    if programa.len() > INSTRUCCIONES_MAXIMAS {
+        return Err(invalido(format!(
+            "supera las {} instrucciones",
+            INSTRUCCIONES_MAXIMAS
+        )));
+    }
+    match nodo {
+        Nodo::Vacio => {}
+        Nodo::Instruccion(instruccion) => programa.push(instruccion.clone()),
+        Nodo::Secuencia(nodos) => {
+            for nodo in nodos {
+                compilar_nodo(nodo, programa)?;
+            }
+        }
+        Nodo::Alternativa(opciones) => {
+            // Cada opción salvo la última: Dividir(opción, siguiente) ... Saltar(fin).
+            let mut saltos = Vec::new();
+            for (indice, opcion) in opciones.iter().enumerate() {
+                if indice + 1 == opciones.len() {
+                    compilar_nodo(opcion, programa)?;
+                    break;
+                }
+                let dividir = programa.len();
+                programa.push(Instruccion::Dividir(dividir + 1, 0));
+                compilar_nodo(opcion, programa)?;
+                saltos.push(programa.len());
+                programa.push(Instruccion::Saltar(0));
+                programa[dividir] = Instruccion::Dividir(dividir + 1, programa.len());
+            }
+            let fin = programa.len();
+            for salto in saltos {
+                programa[salto] = Instruccion::Saltar(fin);
+            }
+        }
+        Nodo::Repeticion {
+            nodo,
+            minimo,
+            maximo,
+        } => {
+            for _ in 0..*minimo {
+                compilar_nodo(nodo, programa)?;
+            }
+            match maximo {
+                None => {
+                    let dividir = programa.len();
+                    programa.push(Instruccion::Dividir(dividir + 1, 0));
+                    compilar_nodo(nodo, programa)?;
+                    programa.push(Instruccion::Saltar(dividir));
+                    programa[dividir] = Instruccion::Dividir(dividir + 1, programa.len());
+                }
+                Some(maximo) => {
+                    let mut opcionales = Vec::new();
+                    for _ in *minimo..*maximo {
+                        opcionales.push(programa.len());
+                        programa.push(Instruccion::Dividir(0, 0));
+                        compilar_nodo(nodo, programa)?;
+                    }
+                    let fin = programa.len();
+                    for dividir in opcionales {
+                        programa[dividir] = Instruccion::Dividir(dividir + 1, fin);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Indica si el programa de una expresión regular coincide con alguna parte del texto.
+///
+/// Simula todos los caminos posibles a la vez, avanzando un carácter por paso (máquina de
+/// Pike): cada instrucción se visita a lo sumo una vez por posición del texto.
+fn coincide_regexp(programa: &[Instruccion], texto: &[char]) -> bool {
+    let mut actuales = Vec::new();
+    let mut siguientes = Vec::new();
+    // Posición del texto en la que cada instrucción ya se agregó a la lista en construcción.
+    let mut visitadas = vec![usize::MAX; programa.len()];
+    for posicion in 0..=texto.len() {
+        // La búsqueda no está anclada: en cada posición puede empezar una coincidencia.
+        if agregar_hilo(programa, texto, 0, posicion, &mut actuales, &mut visitadas) {
+            return true;
+        }
+        let Some(caracter) = texto.get(posicion) else {
+            break;
+        };
+        siguientes.clear();
+        for &instruccion in &actuales {
+            let consume = match &programa[instruccion] {
+                Instruccion::Caracter(esperado) => esperado == caracter,
+                Instruccion::Cualquiera => true,
+                Instruccion::Clase(clase) => clase.contiene(*caracter),
+                _ => false,
+            };
+            if consume
+                && agregar_hilo(
+                    programa,
+                    texto,
+                    instruccion + 1,
+                    posicion + 1,
+                    &mut siguientes,
+                    &mut visitadas,
+                )
+            {
+                return true;
+            }
+        }
+        std::mem::swap(&mut actuales, &mut siguientes);
+    }
+    false
+}
+
+/// Agrega a la lista las instrucciones que consumen caracteres alcanzables desde `inicio` sin
+/// consumir, siguiendo saltos, divisiones y anclas.
+///
+/// # Retorno
+/// `true` si se alcanza la instrucción de coincidencia.
+fn agregar_hilo(
+    programa: &[Instruccion],
+    texto: &[char],
+    inicio: usize,
+    posicion: usize,
+    lista: &mut Vec<usize>,
+    visitadas: &mut [usize],
+) -> bool {
+    let mut pendientes = vec![inicio];
+    while let Some(instruccion) = pendientes.pop() {
+        if visitadas[instruccion] == posicion {
+            continue;
+        }
+        visitadas[instruccion] = posicion;
+        match &programa[instruccion] {
+            Instruccion::Coincidencia => return true,
+            Instruccion::Saltar(destino) => pendientes.push(*destino),
+            Instruccion::Dividir(primera, segunda) => {
+                pendientes.push(*segunda);
+                pendientes.push(*primera);
+            }
+            Instruccion::Inicio if posicion == 0 => pendientes.push(instruccion + 1),
+            Instruccion::Fin if posicion == texto.len() => pendientes.push(instruccion + 1),
+            Instruccion::Inicio | Instruccion::Fin => {}
+            _ => lista.push(instruccion),
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coincide(patron: &str, texto: &str) -> bool {
+        Patron::like(patron).unwrap().coincide(texto).unwrap()
+    }
+
+    #[test]
+    fn test_like_con_comodines() {
+        assert!(coincide("Ma%", "Madrid"));
+        assert!(coincide("%rid", "Madrid"));
+        assert!(coincide("M_dr%d", "Madrid"));
+        assert!(coincide("%", ""));
+        assert!(coincide("%a%a%", "banana"));
+        assert!(!coincide("Ma_", "Madrid"));
+        assert!(!coincide("madrid", "Madrid"));
+        assert!(coincide(r"100\%", "100%"));
+        assert!(!coincide(r"100\%", "1000"));
+    }
+
+    #[test]
+    fn test_ilike_ignora_mayusculas() {
+        let patron = Patron::ilike("MA%Na").unwrap();
+        assert!(patron.coincide("MAÑANA").unwrap());
+        assert!(patron.coincide("mañana").unwrap());
+        assert!(!patron.coincide("tarde").unwrap());
+
+        assert!(Patron::ilike("josé").unwrap().coincide("JOSÉ").unwrap());
+        assert!(Patron::ilike("STRASSE")
+            .unwrap()
+            .coincide("Straße")
+            .unwrap());
+        assert_eq!(plegar_mayusculas("ΣΟΦΟΣ"), plegar_mayusculas("σοφος"));
+        assert_eq!(plegar_mayusculas("Ärger"), "ärger");
+    }
+
+    #[test]
+    fn test_like_patologico_termina_rapido() {
+        let patron = format!("{}b", "%a".repeat(500));
+        let texto = "a".repeat(10_000);
+        assert!(!coincide(&patron, &texto));
+    }
+
+    fn coincide_regexp(patron: &str, texto: &str) -> bool {
+        Patron::regexp(patron).unwrap().coincide(texto).unwrap()
+    }
+
+    #[test]
+    fn test_regexp() {
+        let correo = r"@(gmail|hotmail)\.com$";
+        assert!(coincide_regexp(correo, "ana@gmail.com"));
+        assert!(coincide_regexp(correo, "luis@hotmail.com"));
+        assert!(!coincide_regexp(correo, "eva@gmail.com.ar"));
+        assert!(!coincide_regexp(correo, "eva@gmailxcom"));
+        assert!(coincide_regexp("ad", "Madrid"));
+        assert!(!coincide_regexp("^ad", "Madrid"));
+        assert!(coincide_regexp(r"^\d{3}-\d{2,}$", "123-4567"));
+        assert!(!coincide_regexp(r"^\d{3}-\d{2,}$", "123-4"));
+        assert!(coincide_regexp("^[A-Z][a-z]+ ?[^0-9]*$", "Buenos Aires"));
+        assert!(!coincide_regexp("^[A-Z][a-z]+ ?[^0-9]*$", "Calle 9"));
+        assert!(coincide_regexp("^(ab)*c?$", "ababab"));
+        assert!(coincide_regexp("", "cualquiera"));
+        assert!(coincide_regexp("x|", "abc"));
+        assert!(coincide_regexp("[]a]", "]"));
+    }
+
+    #[test]
+    fn test_regexp_patologico_termina_rapido() {
+        let patron = format!("^{}{}$", "a?".repeat(100), "a".repeat(100));
+        assert!(coincide_regexp(&patron, &"a".repeat(100)));
+        assert!(!coincide_regexp("^(a+)+b$", &"a".repeat(10_000)));
+    }
+
+    #[test]
+    fn test_regexp_rechaza_patrones_invalidos() {
+        for patron in [
+            "(ab", "ab)", "[a-", "*a", "a{2,1}", "a{x}", "[z-a]", "a\\", r"[\W]",
+        ] {
+            assert!(
+                matches!(
+                    Patron::regexp(patron),
+                    Err(errores::Errores::PatronInvalido(_))
+                ),
+                "{}",
+                patron
+            );
+        }
+        assert!(matches!(
+            Patron::regexp("(a{1000}){1000}"),
+            Err(errores::Errores::PatronInvalido(_))
+        ));
+    }
+
+    #[test]
+    fn test_like_rechaza_patrones_invalidos() {
+        assert!(matches!(
+            Patron::like(&"%".repeat(LONGITUD_MAXIMA_PATRON + 1)),
+            Err(errores::Errores::PatronInvalido(_))
+        ));
+        assert!(matches!(
+            Patron::like("abc\\"),
+            Err(errores::Errores::PatronInvalido(_))
+        ));
+        assert!(matches!(
+            Patron::like("a%")
+                .unwrap()
+                .coincide(&"a".repeat(LONGITUD_MAXIMA_TEXTO + 1)),
+            Err(errores::Errores::PatronInvalido(_))
+        ));
+    }
+}
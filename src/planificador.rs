@@ -0,0 +1,259 @@
+use crate::estadisticas::Estadisticas;
+use crate::expresion::{Expresion, Operador, Valor};
+use crate::sintaxis::CriterioOrden;
+use std::collections::HashMap;
+
+/// Forma en que se obtienen los registros de la tabla.
+///
+/// - `Recorrido`: Se lee la tabla completa, registro por registro.
+/// - `Vacio`: No hace falta leer la tabla, porque ningún registro puede formar parte del
+///   resultado (la condición del `WHERE` es siempre falsa o el `LIMIT` es cero).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Acceso {
+    Recorrido,
+    Vacio,
+}
+
+/// Estrategia con la que se ejecuta una consulta, decidida entre el parseo y la ejecución.
+///
+/// - `restricciones`: La condición del `WHERE` simplificada: sin las condiciones que siempre
+///   se cumplen y con las condiciones unidas por `AND` ordenadas de la más barata a la más
+///   costosa de evaluar, de modo que la evaluación se corta lo antes posible. Entre las de igual
+///   costo, si la tabla tiene estadísticas, primero van las que descartan más registros.
+/// - `acceso`: La forma en que se obtienen los registros.
+/// - `ordenar`: Indica si hace falta ordenar el resultado; no hace falta cuando todos los
+///   criterios de `ORDER BY` son constantes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanConsulta {
+    pub restricciones: Option<Expresion>,
+    pub acceso: Acceso,
+    pub ordenar: bool,
+}
+
+/// Arma el plan de ejecución de una consulta de selección.
+///
+/// # Parámetros
+/// - `restricciones`: La condición del `WHERE`, si la consulta la tiene.
+/// - `criterios_orden`: Los criterios del `ORDER BY`.
+/// - `limite`: La cantidad máxima de filas del resultado, si la consulta la indica.
+/// - `estadisticas`: Las estadísticas de la tabla calculadas con `ANALYZE`, si las tiene.
+///   Pueden estar desactualizadas, así que solo cambian el orden de las condiciones y nunca
+///   el resultado.
+///
+/// # Retorno
+/// El `PlanConsulta` con el que se ejecuta la consulta.
+pub fn planificar(
+    restricciones: Option<&Expresion>,
+    criterios_orden: &[CriterioOrden],
+    limite: Option<usize>,
+    estadisticas: Option<&Estadisticas>,
+) -> PlanConsulta {
+    let mut acceso = if limite == Some(0) {
+        Acceso::Vacio
+    } else {
+        Acceso::Recorrido
+    };
+    let mut condiciones = Vec::new();
+    if let Some(restricciones) = restricciones {
+        separar_conjunciones(restricciones, &mut condiciones);
+    }
+    let mut restantes = Vec::new();
+    for condicion in condiciones {
+        match valor_constante(&condicion) {
+            Some(Valor::Booleano(true)) => {}
+            Some(Valor::Booleano(false)) | Some(Valor::Nulo) => acceso = Acceso::Vacio,
+            _ => restantes.push(condicion),
+        }
+    }
+    restantes.sort_by(|una, otra| {
+        costo(una).cmp(&costo(otra)).then_with(|| {
+            selectividad(una, estadisticas).total_cmp(&selectividad(otra, estadisticas))
+        })
+    });
+    let restricciones = restantes.into_iter().reduce(|izquierda, derecha| {
+        Expresion::Binaria(Box::new(izquierda), Operador::And, Box::new(derecha))
+    });
+
+    PlanConsulta {
+        restricciones,
+        acceso,
+        ordenar: criterios_orden
+            .iter()
+            .any(|criterio| valor_constante(&criterio.expresion).is_none()),
+    }
+}
+
+/// Separa una condición en las condiciones que une con `AND`, en el orden en que aparecen.
+fn separar_conjunciones(expresion: &Expresion, condiciones: &mut Vec<Expresion>) {
+    match expresion {
+        Expresion::Binaria(izquierda, Operador::And, derecha) => {
+            separar_conjunciones(izquierda, condiciones);
+            separar_conjunciones(derecha, condiciones);
+        }
+        _ => condiciones.push(expresion.clone()),
+    }
+}
+
+/// Evalúa una expresión que no depende de ningún registro.
+///
+/// # Retorno
+/// El valor de la expresión, o `None` si usa columnas, funciones de agregación o `RANDOM()`, o
+/// si su evaluación falla (en ese caso el error se informa al ejecutar la consulta).
+fn valor_constante(expresion: &Expresion) -> Option<Valor> {
+    if !expresion.columnas().is_empty()
+        || expresion.contiene_agregacion()
+        || expresion.contiene_aleatoriedad()
+    {
+        return None;
+    }
+    expresion.evaluar(&HashMap::new(), &[]).ok()
+}
+
+/// Estima el costo relativo de evaluar una expresión sobre un registro: las comparaciones
+/// simples son baratas, mientras que los patrones (`LIKE`, `ILIKE`, `REGEXP`) y las funciones son más costosos.
+fn costo(expresion: &Expresion) -> usize {
+    match expresion {
+        Expresion::Literal(_) | Expresion::Columna(_) => 0,
+        Expresion::Binaria(izquierda, operador, derecha) => {
+            let propio = match operador {
+                Operador::Like | Operador::ILike | Operador::Regexp => 10,
+                _ => 1,
+            };
+            propio + costo(izquierda) + costo(derecha)
+        }
+        Expresion::Not(expresion) => 1 + costo(expresion),
+        Expresion::Funcion(_, argumentos) => 5 + argumentos.iter().map(costo).sum::<usize>(),
+        Expresion::Agregada(_, _) | Expresion::Ventana(_) => 5,
+        Expresion::Cuantificada(izquierda, _, _, subconsulta) => {
+            1 + costo(izquierda) + subconsulta.valores.as_ref().map_or(0, Vec::len)
+        }
+    }
+}
+
+/// Estima la fracción de los registros de la tabla que cumplen una condición, a partir de sus
+/// estadísticas: `columna = valor` se cumple en uno de cada `distintos` registros no nulos,
+/// `columna != valor` en el resto, y `columna IS NULL` en los registros nulos.
+///
+/// # Retorno
+/// Un número entre 0 y 1, o 1 si no hay estadísticas o la condición no es de esas formas.
+fn selectividad(expresion: &Expresion, estadisticas: Option<&Estadisticas>) -> f64 {
+    let Some(estadisticas) = estadisticas.filter(|estadisticas| estadisticas.filas > 0) else {
+        return 1.0;
+    };
+    let (negada, expresion) = match expresion {
+        Expresion::Not(negada) => (true, negada.as_ref()),
+        _ => (false, expresion),
+    };
+    let Expresion::Binaria(izquierda, operador, derecha) = expresion else {
+        return 1.0;
+    };
+    let columna = match (izquierda.as_ref(), derecha.as_ref()) {
+        (Expresion::Columna(columna), Expresion::Literal(_))
+        | (Expresion::Literal(_), Expresion::Columna(columna)) => columna,
+        _ => return 1.0,
+    };
+    let Some(columna) = estadisticas.columna(columna) else {
+        return 1.0;
+    };
+    let filas = estadisticas.filas as f64;
+    let nulos = columna.nulos as f64 / filas;
+    let iguales = if columna.distintos == 0 {
+        0.0
+    } else {
+        (1.0 - nulos) / columna.distintos as f64
+    };
+    let (cumplen, complemento) = match operador {
+        Operador::Es => (nulos, 1.0 - nulos),
+        Operador::Igual => (iguales, 1.0 - nulos - iguales),
+        Operador::Distinto => (1.0 - nulos - iguales, iguales),
+        _ => return 1.0,
+    };
+    if negada {
+        complemento
+    } else {
+        cumplen
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexico::analizar;
+
+    fn parsear(expresion: &str) -> Expresion {
+        Expresion::parsear(&analizar(expresion)).unwrap()
+    }
+
+    fn criterio(expresion: &str) -> CriterioOrden {
+        CriterioOrden {
+            expresion: parsear(expresion),
+            descendente: false,
+            natural: false,
+        }
+    }
+
+    #[test]
+    fn test_ordenar_condiciones_por_costo() {
+        let restricciones =
+            parsear("nombre LIKE '%a%' AND 1 = 1 AND edad > 30 AND ciudad = 'Madrid'");
+        let plan = planificar(Some(&restricciones), &[], None, None);
+        assert_eq!(
+            plan.restricciones.unwrap().to_string(),
+            "(((edad > 30) AND (ciudad = 'Madrid')) AND (nombre LIKE '%a%'))"
+        );
+        assert_eq!(plan.acceso, Acceso::Recorrido);
+    }
+
+    #[test]
+    fn test_ordenar_condiciones_por_selectividad() {
+        let estadisticas = Estadisticas::desde_csv(
+            "columna,filas,distintos,nulos,minimo,maximo\n\
+             edad,100,60,0,1,90\n\
+             ciudad,100,4,0,Lugo,Vigo\n\
+             apodo,100,90,95,Al,Zoe\n",
+        )
+        .unwrap();
+        let restricciones =
+            parsear("ciudad = 'Lugo' AND edad = 30 AND NOT apodo IS NULL AND ciudad != 'Vigo'");
+        let plan = planificar(Some(&restricciones), &[], None, Some(&estadisticas));
+        assert_eq!(
+            plan.restricciones.unwrap().to_string(),
+            "((((edad = 30) AND (ciudad = 'Lugo')) AND (ciudad != 'Vigo')) AND \
+             (NOT (apodo IS NULL)))"
+        );
+        let plan = planificar(Some(&restricciones), &[], None, None);
+        assert_eq!(
+            plan.restricciones.unwrap().to_string(),
+            "((((ciudad = 'Lugo') AND (edad = 30)) AND (ciudad != 'Vigo')) AND \
+             (NOT (apodo IS NULL)))"
+        );
+    }
+
+    #[test]
+    fn test_condicion_siempre_falsa_evita_el_recorrido() {
+        let restricciones = parsear("edad > 30 AND 1 = 0");
+        assert_eq!(
+            planificar(Some(&restricciones), &[], None, None).acceso,
+            Acceso::Vacio
+        );
+        let restricciones = parsear("edad > 30 OR 1 = 0");
+        assert_eq!(
+            planificar(Some(&restricciones), &[], None, None).acceso,
+            Acceso::Recorrido
+        );
+        assert_eq!(planificar(None, &[], Some(0), None).acceso, Acceso::Vacio);
+    }
+
+    #[test]
+    fn test_omitir_orden_con_criterios_constantes() {
+        assert!(!planificar(None, &[criterio("1"), criterio("'a'")], None, None).ordenar);
+        assert!(planificar(None, &[criterio("1"), criterio("edad")], None, None).ordenar);
+        assert!(planificar(None, &[criterio("COUNT(*)")], None, None).ordenar);
+        assert!(planificar(None, &[criterio("RANDOM()")], None, None).ordenar);
+
+        let restricciones = parsear("RANDOM() < 0.5");
+        let plan = planificar(Some(&restricciones), &[], None, None);
+        assert_eq!(plan.restricciones, Some(restricciones));
+        assert_eq!(plan.acceso, Acceso::Recorrido);
+    }
+}
@@ -0,0 +1,601 @@
+use crate::archivo::OpcionesArchivo;
+use crate::consulta::{Resumen, SQLConsulta};
+use crate::errores;
+use crate::expresion::Valor;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorResultados;
+use crate::servidor::{admitir_sentencia, LimiteConexiones};
+use crate::sintaxis::{self, Sentencia};
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Versión del protocolo 3.0, la única que se admite, tal como la envía el cliente al conectarse.
+const VERSION_PROTOCOLO: i32 = 196_608;
+/// Códigos con los que el cliente pide cifrar la conexión con SSL o GSSAPI, o cancelar una
+/// consulta en otra conexión, en lugar de la versión del protocolo.
+const PEDIDO_SSL: i32 = 80_877_103;
+const PEDIDO_GSSAPI: i32 = 80_877_104;
+const PEDIDO_CANCELAR: i32 = 80_877_102;
+/// Tamaño máximo de un mensaje del cliente, en bytes.
+const TAMANIO_MAXIMO_MENSAJE: usize = 16 * 1024 * 1024;
+/// Cantidad máxima de conexiones que se atienden al mismo tiempo, para que los mensajes que
+/// se leen a la vez no ocupen más de `CONEXIONES_MAXIMAS * TAMANIO_MAXIMO_MENSAJE` bytes.
+const CONEXIONES_MAXIMAS: usize = 16;
+/// OID de los tipos de PostgreSQL con los que se describen las columnas.
+const TIPO_BOOL: i32 = 16;
+const TIPO_INT8: i32 = 20;
+const TIPO_TEXT: i32 = 25;
+const TIPO_FLOAT8: i32 = 701;
+const TIPO_NUMERIC: i32 = 1700;
+
+/// Atiende conexiones con el protocolo de PostgreSQL en la dirección indicada, para que `psql`
+/// y las herramientas de análisis que se conectan a PostgreSQL consulten las tablas.
+///
+/// Se admite el subconjunto de consultas simples del protocolo 3.0: sin cifrado ni
+/// autenticación, el cliente envía sentencias SQL separadas por `;` y recibe, por cada una, la
+/// descripción de las columnas, las filas en formato de texto y la etiqueta del comando (como
+/// `SELECT 5` o `INSERT 0 2`). Las columnas se describen como `int8`, `float8` o `bool` si
+/// todos sus valores son de ese tipo, y como `text` si no. Las consultas extendidas (con
+/// parámetros), las transacciones, `COPY` y, si no se permite la escritura, las sentencias que
+/// modifican las tablas se rechazan con un error, sin cerrar la conexión (ver
+/// `servidor::admitir_sentencia`). Como en el servidor HTTP, solo se leen las tablas que
+/// resuelve el resolvedor (ver `OpcionesArchivo::solo_tablas`).
+///
+/// Cada conexión se atiende en su propio hilo, hasta `CONEXIONES_MAXIMAS` a la vez (ver
+/// `LimiteConexiones`).
+///
+/// # Parámetros
+/// - `direccion`: La dirección y el puerto en los que se escucha, como `127.0.0.1:5432`.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+/// - `permitir_escritura`: Si se admiten las sentencias que modifican las tablas.
+///
+/// # Retorno
+/// No retorna mientras el servidor esté activo; retorna `Errores::Io` si no se pudo escuchar
+/// en la dirección.
+pub fn servir(
+    direccion: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+) -> Result<(), errores::Errores> {
+    let escucha = TcpListener::bind(direccion)?;
+    eprintln!(
+        "Escuchando conexiones de PostgreSQL en {}",
+        escucha.local_addr()?
+    );
+    let limite = LimiteConexiones::new(CONEXIONES_MAXIMAS);
+    thread::scope(|hilos| {
+        for conexion in escucha.incoming() {
+            let mut conexion = match conexion {
+                Ok(conexion) => conexion,
+                Err(error) => {
+                    registro::advertir(format!("no se pudo aceptar una conexión: {}", error));
+                    continue;
+                }
+            };
+            let lugar = limite.ocupar();
+            hilos.spawn(move || {
+                let _lugar = lugar;
+                if let Err(error) = atender(&mut conexion, resolvedor, opciones, permitir_escritura)
+                {
+                    registro::advertir(format!("se cerró una conexión con error: {}", error));
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Atiende una conexión desde el inicio hasta que el cliente la termina o la cierra.
+///
+/// # Parámetros
+/// - `conexion`: La conexión con el cliente.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+/// - `permitir_escritura`: Si se admiten las sentencias que modifican las tablas.
+///
+/// # Retorno
+/// Retorna `Ok(())` cuando la conexión termina, o el error de E/S de la conexión o de un
+/// mensaje que no respeta el protocolo.
+pub fn atender<C: Read + Write>(
+    conexion: &mut C,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+) -> io::Result<()> {
+    if !iniciar(conexion)? {
+        return Ok(());
+    }
+    let opciones = &OpcionesArchivo {
+        solo_tablas: true,
+        ..opciones.clone()
+    };
+    // Después de un error en una consulta extendida se descartan sus mensajes hasta `Sync`.
+    let mut descartando = false;
+    loop {
+        let mut tipo = [0];
+        if conexion.read(&mut tipo)? == 0 {
+            return Ok(());
+        }
+        let contenido = leer_mensaje(conexion)?;
+        match tipo[0] {
+            b'Q' => {
+                let consulta = texto_de(&contenido);
+                registro::registrar(registro::DETALLE, || format!("consulta: {}", consulta));
+                let mut respuesta = Vec::new();
+                responder(
+                    &consulta,
+                    resolvedor,
+                    opciones,
+                    permitir_escritura,
+                    &mut respuesta,
+                );
+                mensaje(&mut respuesta, b'Z', b"I");
+                conexion.write_all(&respuesta)?;
+            }
+            b'S' => {
+                descartando = false;
+                enviar(conexion, b'Z', b"I")?;
+            }
+            b'X' => return Ok(()),
+            _ if descartando => {}
+            _ => {
+                descartando = true;
+                let error = errores::Errores::ArgumentoInvalido(
+                    "solo se admiten consultas simples".to_string(),
+                );
+                let mut respuesta = Vec::new();
+                mensaje_de_error(&mut respuesta, &error, "");
+                conexion.write_all(&respuesta)?;
+            }
+        }
+        conexion.flush()?;
+    }
+}
+
+/// Recibe el mensaje de inicio, rechazando los pedidos de cifrado, y envía los mensajes con
+/// los que el cliente considera iniciada la sesión.
+///
+/// # Retorno
+/// `true` si la sesión se inició, o `false` si el cliente solo pedía cancelar una consulta.
+fn iniciar<C: Read + Write>(conexion: &mut C) -> io::Result<bool> {
+    loop {
+        let contenido = leer_mensaje(conexion)?;
+        let codigo = match contenido.get(..4) {
+            Some(codigo) => i32::from_be_bytes([codigo[0], codigo[1], codigo[2], codigo[3]]),
+            None => return Err(error_de_protocolo("mensaje de inicio incompleto")),
+        };
+        match codigo {
+            PEDIDO_SSL | PEDIDO_GSSAPI => conexion.write_all(b"N")?,
+            PEDIDO_CANCELAR => return Ok(false),
+            VERSION_PROTOCOLO => break,
+            _ => return Err(error_de_protocolo("versión del protocolo no admitida")),
+        }
+        conexion.flush()?;
+    }
+    let mut respuesta = Vec::new();
+    mensaje(&mut respuesta, b'R', &0i32.to_be_bytes());
+    for (parametro, valor) in [
+        ("server_version", "14.0"),
+        ("server_encoding", "UTF8"),
+        ("client_encoding", "UTF8"),
+        ("DateStyle", "ISO, YMD"),
+        ("integer_datetimes", "on"),
+        ("standard_conforming_strings", "on"),
+    ] {
+        let mut contenido = Vec::new();
+        agregar_texto(&mut contenido, parametro);
+        agregar_texto(&mut contenido, valor);
+        mensaje(&mut respuesta, b'S', &contenido);
+    }
+    let mut clave = std::process::id().to_be_bytes().to_vec();
+    clave.extend_from_slice(&0i32.to_be_bytes());
+    mensaje(&mut respuesta, b'K', &clave);
+    mensaje(&mut respuesta, b'Z', b"I");
+    conexion.write_all(&respuesta)?;
+    conexion.flush()?;
+    Ok(true)
+}
+
+/// Ejecuta las sentencias de una consulta simple y escribe los mensajes de sus resultados. Si
+/// una sentencia falla, se escribe el error y no se ejecutan las siguientes.
+fn responder(
+    consulta: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+    respuesta: &mut Vec<u8>,
+) {
+    let sentencias = match sintaxis::parsear_lote(consulta) {
+        Ok(sentencias) => sentencias,
+        Err(error) => return mensaje_de_error(respuesta, &error, consulta),
+    };
+    if sentencias.is_empty() {
+        return mensaje(respuesta, b'I', &[]);
+    }
+    for sentencia in sentencias {
+        if let Err(error) = ejecutar(
+            sentencia,
+            resolvedor,
+            opciones,
+            permitir_escritura,
+            respuesta,
+        ) {
+            return mensaje_de_error(respuesta, &error, consulta);
+        }
+    }
+}
+
+/// Ejecuta una sentencia y escribe la descripción de sus columnas, sus filas y la etiqueta
+/// del comando.
+fn ejecutar(
+    sentencia: Sentencia,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+    respuesta: &mut Vec<u8>,
+) -> Result<(), errores::Errores> {
+    admitir_sentencia(&sentencia, permitir_escritura)?;
+    let mut resultado = ResultadoPostgres::default();
+    let resumen = SQLConsulta::desde_sentencia(sentencia, resolvedor, opciones)?
+        .procesar_consulta(&mut resultado)?;
+    let etiqueta = match resumen {
+        Resumen::Agregados(registros) => format!("INSERT 0 {}", registros),
+        Resumen::Copiados(filas) => format!("COPY {}", filas),
+        Resumen::Filas(_) => {
+            resultado.escribir(respuesta);
+            format!("SELECT {}", resultado.filas.len())
+        }
+    };
+    let mut contenido = Vec::new();
+    agregar_texto(&mut contenido, &etiqueta);
+    mensaje(respuesta, b'C', &contenido);
+    Ok(())
+}
+
+/// Reúne el resultado de una sentencia, que se envía al terminarla porque el tipo de cada
+/// columna depende de todos sus valores.
+#[derive(Debug, Default)]
+struct ResultadoPostgres {
+    columnas: Vec<String>,
+    filas: Vec<Vec<Valor>>,
+}
+
+impl ResultadoPostgres {
+    /// Devuelve el OID del tipo de una columna: el de sus valores si todos los que no son
+    /// `NULL` son enteros, números o booleanos, y `text` si no. Los enteros que no entran en
+    /// un `int8` son `numeric`.
+    fn tipo(&self, columna: usize) -> i32 {
+        let mut tipo = None;
+        for valor in self.filas.iter().filter_map(|fila| fila.get(columna)) {
+            let tipo_valor = match valor {
+                Valor::Nulo => continue,
+                Valor::Entero(numero) if i64::try_from(*numero).is_err() => TIPO_NUMERIC,
+                Valor::Entero(_) => TIPO_INT8,
+                Valor::Decimal(_) => TIPO_FLOAT8,
+                Valor::Booleano(_) => TIPO_BOOL,
+                Valor::Texto(_) => return TIPO_TEXT,
+            };
+            tipo = match (tipo, tipo_valor) {
+                (None, tipo_valor) => Some(tipo_valor),
+                (Some(anterior), tipo_valor) if anterior == tipo_valor => Some(anterior),
+                (Some(TIPO_INT8 | TIPO_NUMERIC), TIPO_INT8 | TIPO_NUMERIC) => Some(TIPO_NUMERIC),
+                (
+                    Some(TIPO_INT8 | TIPO_NUMERIC | TIPO_FLOAT8),
+                    TIPO_INT8 | TIPO_NUMERIC | TIPO_FLOAT8,
+                ) => Some(TIPO_FLOAT8),
+                _ => return TIPO_TEXT,
+            };
+        }
+        tipo.unwrap_or(TIPO_TEXT)
+    }
+
+    /// Escribe la descripción de las columnas y una fila de datos por registro.
+    fn escribir(&self, respuesta: &mut Vec<u8>) {
+        let mut descripcion = (self.columnas.len() as i16).to_be_bytes().to_vec();
+        for (indice, columna) in self.columnas.iter().enumerate() {
+            let tipo = self.tipo(indice);
+            let largo: i16 = match tipo {
+                TIPO_BOOL => 1,
+                TIPO_TEXT => -1,
+                _ => 8,
+            };
+            agregar_texto(&mut descripcion, columna);
+            descripcion.extend_from_slice(&0i32.to_be_bytes());
+            descripcion.extend_from_slice(&0i16.to_be_bytes());
+            descripcion.extend_from_slice(&tipo.to_be_bytes());
+            descripcion.extend_from_slice(&largo.to_be_bytes());
+            descripcion.extend_from_slice(&(-1i32).to_be_bytes());
+            descripcion.extend_from_slice(&0i16.to_be_bytes());
+        }
+        mensaje(respuesta, b'T', &descripcion);
+        for fila in &self.filas {
+            let mut datos = (fila.len() as i16).to_be_bytes().to_vec();
+            for valor in fila {
+                let texto = match valor {
+                    Valor::Nulo => {
+                        datos.extend_from_slice(&(-1i32).to_be_bytes());
+                        continue;
+                    }
+                    Valor::Booleano(valor) => if *valor { "t" } else { "f" }.to_string(),
+                    valor => valor.a_texto(),
+                };
+                datos.extend_from_slice(&(texto.len() as i32).to_be_bytes());
+                datos.extend_from_slice(texto.as_bytes());
+            }
+            mensaje(respuesta, b'D', &datos);
+        }
+    }
+}
+
+impl EscritorResultados for ResultadoPostgres {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.columnas = columnas.to_vec();
+        Ok(())
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        self.filas.push(valores.to_vec());
+        Ok(())
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        Ok(())
+    }
+}
+
+/// Devuelve el código SQLSTATE de PostgreSQL que corresponde a un error.
+fn codigo_sqlstate(error: &errores::Errores) -> &'static str {
+    match error {
+        errores::Errores::InvalidSyntax
+        | errores::Errores::TokenInesperado { .. }
+        | errores::Errores::FinInesperado { .. } => "42601",
+        errores::Errores::InvalidTable(_) => "42P01",
+        errores::Errores::InvalidColumn(_) | errores::Errores::ColumnaDesconocida { .. } => "42703",
+        errores::Errores::ColumnaNoAgrupada(_) => "42803",
+        errores::Errores::PatronInvalido(_) => "2201B",
+        errores::Errores::TransaccionInvalida(_) => "25000",
+        errores::Errores::TipoInvalido(_) => "42804",
+        errores::Errores::ArgumentoInvalido(_) => "0A000",
+        errores::Errores::TablaBloqueada(_) => "55P03",
+        errores::Errores::RegistroMalformado { .. } => "22P04",
+        errores::Errores::ValorInvalido(_) => "22023",
+        errores::Errores::PermisoDenegado(_) => "42501",
+        errores::Errores::Io(_) | errores::Errores::HiloFallido(_) | errores::Errores::Error => {
+            "XX000"
+        }
+    }
+}
+
+/// Escribe el mensaje de error de PostgreSQL de un error, con su posición en la consulta (en
+/// caracteres, desde 1) si la tiene.
+fn mensaje_de_error(respuesta: &mut Vec<u8>, error: &errores::Errores, consulta: &str) {
+    let mut contenido = Vec::new();
+    for (campo, valor) in [
+        (b'S', "ERROR"),
+        (b'V', "ERROR"),
+        (b'C', codigo_sqlstate(error)),
+    ] {
+        contenido.push(campo);
+        agregar_texto(&mut contenido, valor);
+    }
+    contenido.push(b'M');
+    agregar_texto(&mut contenido, &error.mensaje());
+    if let Some(posicion) = error
+        .posicion()
+        .filter(|&posicion| posicion <= consulta.len())
+    {
+        let caracteres = consulta
+            .get(..posicion)
+            .map_or(posicion, |previo| previo.chars().count());
+        contenido.push(b'P');
+        agregar_texto(&mut contenido, &(caracteres + 1).to_string());
+    }
+    contenido.push(0);
+    mensaje(respuesta, b'E', &contenido);
+}
+
+/// Lee el largo de un mensaje, que se incluye a sí mismo, y su contenido.
+fn leer_mensaje(conexion: &mut dyn Read) -> io::Result<Vec<u8>> {
+    let mut largo = [0; 4];
+    conexion.read_exact(&mut largo)?;
+    let largo = i32::from_be_bytes(largo);
+    if largo < 4 || largo as usize - 4 > TAMANIO_MAXIMO_MENSAJE {
+        return Err(error_de_protocolo("largo de mensaje inválido"));
+    }
+    let mut contenido = vec![0; largo as usize - 4];
+    conexion.read_exact(&mut contenido)?;
+    Ok(contenido)
+}
+
+/// Agrega a la respuesta un mensaje con su tipo, su largo y su contenido.
+fn mensaje(respuesta: &mut Vec<u8>, tipo: u8, contenido: &[u8]) {
+    respuesta.push(tipo);
+    respuesta.extend_from_slice(&(contenido.len() as i32 + 4).to_be_bytes());
+    respuesta.extend_from_slice(contenido);
+}
+
+fn enviar(conexion: &mut dyn Write, tipo: u8, contenido: &[u8]) -> io::Result<()> {
+    let mut respuesta = Vec::new();
+    mensaje(&mut respuesta, tipo, contenido);
+    conexion.write_all(&respuesta)
+}
+
+/// Agrega un texto terminado en `\0`, como los escribe el protocolo.
+fn agregar_texto(contenido: &mut Vec<u8>, texto: &str) {
+    contenido.extend_from_slice(texto.as_bytes());
+    contenido.push(0);
+}
+
+/// Lee un texto terminado en `\0`; los bytes que no son UTF-8 se reemplazan.
+fn texto_de(contenido: &[u8]) -> String {
+    let fin = contenido
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(contenido.len());
+    String::from_utf8_lossy(&contenido[..fin]).to_string()
+}
+
+fn error_de_protocolo(motivo: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, motivo.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use std::net::TcpStream;
+
+    /// Lee los mensajes del servidor hasta el siguiente `ReadyForQuery`.
+    fn leer_hasta_listo(cliente: &mut TcpStream) -> Vec<(u8, Vec<u8>)> {
+        let mut mensajes = Vec::new();
+        loop {
+            let mut tipo = [0];
+            cliente.read_exact(&mut tipo).unwrap();
+            let contenido = leer_mensaje(cliente).unwrap();
+            let listo = tipo[0] == b'Z';
+            mensajes.push((tipo[0], contenido));
+            if listo {
+                return mensajes;
+            }
+        }
+    }
+
+    fn consulta(cliente: &mut TcpStream, consulta: &str) -> Vec<(u8, Vec<u8>)> {
+        let mut contenido = Vec::new();
+        agregar_texto(&mut contenido, consulta);
+        enviar(cliente, b'Q', &contenido).unwrap();
+        leer_hasta_listo(cliente)
+    }
+
+    /// Devuelve los campos de un mensaje `DataRow`.
+    fn campos(contenido: &[u8]) -> Vec<Option<String>> {
+        let cantidad = i16::from_be_bytes([contenido[0], contenido[1]]);
+        let mut posicion = 2;
+        let mut campos = Vec::new();
+        for _ in 0..cantidad {
+            let largo = i32::from_be_bytes(contenido[posicion..posicion + 4].try_into().unwrap());
+            posicion += 4;
+            if largo < 0 {
+                campos.push(None);
+                continue;
+            }
+            let fin = posicion + largo as usize;
+            campos.push(Some(
+                String::from_utf8(contenido[posicion..fin].to_vec()).unwrap(),
+            ));
+            posicion = fin;
+        }
+        campos
+    }
+
+    #[test]
+    fn test_sesion_de_consultas_simples() {
+        let escucha = TcpListener::bind("127.0.0.1:0").unwrap();
+        let direccion = escucha.local_addr().unwrap();
+        let servidor = thread::spawn(move || {
+            let tablas = TablasEnMemoria::new();
+            tablas.agregar_tabla("personas", "nombre,edad\nAna,40\nLuis,25\nEva,\n");
+            let (mut conexion, _) = escucha.accept().unwrap();
+            atender(&mut conexion, &tablas, &OpcionesArchivo::default(), true).unwrap();
+        });
+        let mut cliente = TcpStream::connect(direccion).unwrap();
+
+        let mut pedido_ssl = 8i32.to_be_bytes().to_vec();
+        pedido_ssl.extend_from_slice(&PEDIDO_SSL.to_be_bytes());
+        cliente.write_all(&pedido_ssl).unwrap();
+        let mut respuesta = [0];
+        cliente.read_exact(&mut respuesta).unwrap();
+        assert_eq!(&respuesta, b"N");
+
+        let mut inicio = VERSION_PROTOCOLO.to_be_bytes().to_vec();
+        agregar_texto(&mut inicio, "user");
+        agregar_texto(&mut inicio, "ana");
+        inicio.push(0);
+        let mut mensaje_inicio = (inicio.len() as i32 + 4).to_be_bytes().to_vec();
+        mensaje_inicio.extend_from_slice(&inicio);
+        cliente.write_all(&mensaje_inicio).unwrap();
+        let mensajes = leer_hasta_listo(&mut cliente);
+        assert_eq!(mensajes[0], (b'R', vec![0, 0, 0, 0]));
+        assert_eq!(mensajes.last().unwrap(), &(b'Z', b"I".to_vec()));
+
+        let mensajes = consulta(
+            &mut cliente,
+            "SELECT nombre, edad FROM personas WHERE nombre <> 'Luis'",
+        );
+        let tipos: Vec<u8> = mensajes.iter().map(|(tipo, _)| *tipo).collect();
+        assert_eq!(tipos, b"TDDCZ");
+        let descripcion = &mensajes[0].1;
+        assert!(descripcion.starts_with(&[0, 2, b'n', b'o', b'm', b'b', b'r', b'e', 0]));
+        assert_eq!(
+            campos(&mensajes[1].1),
+            vec![Some("Ana".to_string()), Some("40".to_string())]
+        );
+        assert_eq!(campos(&mensajes[2].1), vec![Some("Eva".to_string()), None]);
+        assert_eq!(mensajes[3].1, b"SELECT 2\0");
+
+        let mensajes = consulta(
+            &mut cliente,
+            "INSERT INTO personas VALUES ('Juan', 30); SELECT COUNT(*) FROM personas",
+        );
+        let tipos: Vec<u8> = mensajes.iter().map(|(tipo, _)| *tipo).collect();
+        assert_eq!(tipos, b"CTDCZ");
+        assert_eq!(mensajes[0].1, b"INSERT 0 1\0");
+        assert_eq!(campos(&mensajes[2].1), vec![Some("4".to_string())]);
+
+        let mensajes = consulta(&mut cliente, "SELECT * FROM clientes");
+        assert_eq!(mensajes[0].0, b'E');
+        assert!(mensajes[0].1.windows(6).any(|campo| campo == b"C42P01"));
+        assert_eq!(mensajes[1], (b'Z', b"I".to_vec()));
+
+        for (texto, codigo) in [
+            ("SELECT * FROM '/etc/passwd'", b"C42501"),
+            (
+                "COPY (SELECT * FROM personas) TO '/tmp/personas.csv'",
+                b"C0A000",
+            ),
+            ("COPY personas FROM '/etc/passwd'", b"C0A000"),
+        ] {
+            let mensajes = consulta(&mut cliente, texto);
+            assert_eq!(mensajes[0].0, b'E');
+            assert!(mensajes[0].1.windows(6).any(|campo| campo == codigo));
+        }
+
+        assert_eq!(consulta(&mut cliente, " ")[0].0, b'I');
+
+        enviar(&mut cliente, b'X', &[]).unwrap();
+        servidor.join().unwrap();
+    }
+
+    #[test]
+    fn test_tipos_de_las_columnas() {
+        let resultado = ResultadoPostgres {
+            columnas: vec!["a".into(), "b".into(), "c".into(), "d".into(), "e".into()],
+            filas: vec![
+                vec![
+                    Valor::Entero(1),
+                    Valor::Entero(1),
+                    Valor::Nulo,
+                    Valor::Booleano(true),
+                    Valor::Entero(1),
+                ],
+                vec![
+                    Valor::Nulo,
+                    Valor::Decimal(0.5),
+                    Valor::Nulo,
+                    Valor::Entero(1),
+                    Valor::Entero(99999999999999999999),
+                ],
+            ],
+        };
+        assert_eq!(
+            (0..5)
+                .map(|columna| resultado.tipo(columna))
+                .collect::<Vec<_>>(),
+            vec![TIPO_INT8, TIPO_FLOAT8, TIPO_TEXT, TIPO_TEXT, TIPO_NUMERIC]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::archivo::OpcionesArchivo;
+use crate::consulta::{Resumen, SQLConsulta};
+use crate::errores;
+use crate::expresion::{Expresion, Valor};
+use crate::lexico::{self, es_numero, TipoToken, Token};
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorResultados;
+use crate::sintaxis::{self, Sentencia};
+
+/// Sentencia SQL con parámetros `?`, que se parsea una vez y se ejecuta con distintos valores.
+///
+/// Los valores se vinculan a los tokens de la sentencia, no a su texto: un texto con comillas,
+/// barras o palabras clave se interpreta siempre como un único literal, sin necesidad de
+/// escaparlo.
+///
+/// - `tokens`: Los tokens de la sentencia, con un `?` en el lugar de cada parámetro.
+/// - `parametros`: La cantidad de parámetros de la sentencia.
+#[derive(Debug, Clone)]
+pub struct SentenciaPreparada {
+    tokens: Vec<Token>,
+    parametros: usize,
+}
+
+/// Prepara una sentencia SQL con parámetros `?` para ejecutarla con `SentenciaPreparada::ejecutar`.
+///
+/// # Parámetros
+/// - `consulta`: El texto de la sentencia. Cada `?` ocupa el lugar de un valor: un literal
+///   de una expresión, un valor de `VALUES` o la cantidad de `LIMIT`.
+///
+/// # Retorno
+/// La `SentenciaPreparada`, o el error de sintaxis de la sentencia.
+pub fn preparar(consulta: &str) -> Result<SentenciaPreparada, errores::Errores> {
+    let tokens = lexico::analizar(consulta);
+    let parametros = tokens.iter().filter(|token| token.es("?")).count();
+    let preparada = SentenciaPreparada { tokens, parametros };
+    // Con todos los parámetros en 0 la sentencia tiene que ser válida, lo que permite
+    // informar los errores de sintaxis al prepararla y no en cada ejecución.
+    preparada.vincular(&vec![Valor::Entero(0); parametros])?;
+    Ok(preparada)
+}
+
+impl SentenciaPreparada {
+    /// Devuelve la cantidad de parámetros `?` de la sentencia.
+    pub fn parametros(&self) -> usize {
+        self.parametros
+    }
+
+    /// Reemplaza cada parámetro por su valor y parsea la sentencia resultante.
+    ///
+    /// # Parámetros
+    /// - `valores`: Los valores de los parámetros, en el orden en que aparecen en la sentencia.
+    ///
+    /// # Retorno
+    /// La `Sentencia` con los valores vinculados, `Errores::ArgumentoInvalido` si la cantidad
+    /// de valores no coincide con la de parámetros o un valor no se puede usar como literal,
+    /// o el error de sintaxis de la sentencia.
+    pub fn vincular(&self, valores: &[Valor]) -> Result<Sentencia, errores::Errores> {
+        if valores.len() != self.parametros {
+            return Err(errores::Errores::ArgumentoInvalido(format!(
+                "la sentencia tiene {} parámetros y se recibieron {} valores",
+                self.parametros,
+                valores.len()
+            )));
+        }
+        let mut valores = valores.iter().peekable();
+        let mut tokens = Vec::with_capacity(self.tokens.len());
+        for token in &self.tokens {
+            match valores.next_if(|_| token.es("?")) {
+                Some(valor) => tokens.push(token_de_valor(valor, token.posicion)?),
+                None => tokens.push(token.clone()),
+            }
+        }
+        sintaxis::parsear_tokens(&tokens)
+    }
+
+    /// Vincula los valores de los parámetros y ejecuta la sentencia (ver `vincular`).
+    ///
+    /// # Parámetros
+    /// - `valores`: Los valores de los parámetros, en el orden en que aparecen en la sentencia.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+    /// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+    /// - `escritor`: El escritor que recibe los resultados de la sentencia.
+    ///
+    /// # Retorno
+    /// Retorna el `Resumen` de la sentencia o el error que impidió ejecutarla.
+    pub fn ejecutar(
+        &self,
+        valores: &[Valor],
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let sentencia = self.vincular(valores)?;
+        SQLConsulta::desde_sentencia(sentencia, resolvedor, opciones)?.procesar_consulta(escritor)
+    }
+}
+
+/// Convierte el valor de un parámetro en el token que ocupa su lugar: un número, un `NULL` o
+/// un `TipoToken::Parametro` con el texto tal cual.
+///
+/// # Retorno
+/// El `Token`, o `Errores::ArgumentoInvalido` si el valor es booleano o un número que no es
+/// finito, que no tienen un literal en el lenguaje de consultas.
+fn token_de_valor(valor: &Valor, posicion: usize) -> Result<Token, errores::Errores> {
+    let (tipo, texto) = match valor {
+        Valor::Texto(texto) => (TipoToken::Parametro, texto.to_string()),
+        Valor::Entero(numero) => (TipoToken::Numero, numero.to_string()),
+        Valor::Decimal(numero) => {
+            let mut texto = numero.to_string();
+            if !texto.contains('.') {
+                texto.push_str(".0");
+            }
+            (TipoToken::Numero, texto)
+        }
+        Valor::Nulo => (TipoToken::PalabraClave, "null".to_string()),
+        Valor::Booleano(_) => {
+            return Err(errores::Errores::ArgumentoInvalido(format!(
+                "el valor {} no se puede usar como parámetro",
+                valor.a_texto()
+            )))
+        }
+    };
+    if tipo == TipoToken::Numero && !es_numero(&texto) {
+        return Err(errores::Errores::ArgumentoInvalido(format!(
+            "el valor {} no se puede usar como parámetro",
+            texto
+        )));
+    }
+    Ok(Token {
+        tipo,
+        texto,
+        posicion,
+        original: Expresion::Literal(valor.clone()).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use crate::salida::OpcionesSalida;
+
+    fn ejecutar(
+        preparada: &SentenciaPreparada,
+        valores: &[Valor],
+        tablas: &TablasEnMemoria,
+    ) -> Result<String, errores::Errores> {
+        let mut destino = Vec::new();
+        {
+            let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+            preparada.ejecutar(
+                valores,
+                tablas,
+                &OpcionesArchivo::default(),
+                escritor.as_mut(),
+            )?;
+        }
+        Ok(String::from_utf8(destino).unwrap())
+    }
+
+    #[test]
+    fn test_ejecutar_sentencia_preparada() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\nLuis,25\n");
+
+        let insertar = preparar("INSERT INTO personas (nombre, edad) VALUES (?, ?)").unwrap();
+        assert_eq!(insertar.parametros(), 2);
+        for nombre in [r"O'Brien \", "select", "' OR 1 = 1 --"] {
+            ejecutar(
+                &insertar,
+                &[Valor::Texto(nombre.to_string()), Valor::Nulo],
+                &tablas,
+            )
+            .unwrap();
+        }
+
+        let buscar = preparar("SELECT edad FROM personas WHERE nombre = ? LIMIT ?").unwrap();
+        let resultado = ejecutar(
+            &buscar,
+            &[Valor::Texto("Ana".to_string()), Valor::Entero(1)],
+            &tablas,
+        );
+        assert_eq!(resultado.unwrap(), "edad\n40\n");
+        let resultado = ejecutar(
+            &buscar,
+            &[Valor::Texto(r"O'Brien \".to_string()), Valor::Entero(5)],
+            &tablas,
+        );
+        assert_eq!(resultado.unwrap(), "edad\n\n");
+        let resultado = ejecutar(
+            &buscar,
+            &[Valor::Texto("' OR 1 = 1 --".to_string()), Valor::Entero(5)],
+            &tablas,
+        );
+        assert_eq!(resultado.unwrap(), "edad\n\n");
+
+        let mayores = preparar("SELECT nombre FROM personas WHERE edad > ?").unwrap();
+        let resultado = ejecutar(&mayores, &[Valor::Decimal(30.0)], &tablas);
+        assert_eq!(resultado.unwrap(), "nombre\nAna\n");
+    }
+
+    #[test]
+    fn test_errores_de_sentencia_preparada() {
+        assert!(matches!(
+            preparar("SELECT nombre FROM personas WHERE edad >"),
+            Err(errores::Errores::FinInesperado { .. })
+        ));
+        let preparada = preparar("SELECT nombre FROM personas WHERE edad > ?").unwrap();
+        for valores in [
+            vec![],
+            vec![Valor::Entero(1), Valor::Entero(2)],
+            vec![Valor::Booleano(true)],
+            vec![Valor::Decimal(f64::NAN)],
+        ] {
+            assert!(matches!(
+                preparada.vincular(&valores),
+                Err(errores::Errores::ArgumentoInvalido(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Nivel de `-v`: la ruta de cada tabla y la cantidad de filas de cada sentencia.
+pub const DETALLE: u8 = 1;
+
+/// Nivel de `-vv`: además, las sentencias parseadas y el árbol de cada `WHERE`.
+pub const DEPURACION: u8 = 2;
+
+/// Nivel de verbosidad del proceso; `0` no registra nada.
+static NIVEL: AtomicU8 = AtomicU8::new(0);
+
+#[cfg(test)]
+thread_local! {
+    /// Nivel y mensajes registrados por el hilo actual mientras corre `capturar`, para que las
+    /// pruebas no dependan del nivel global ni de la salida de errores.
+    static CAPTURA: RefCell<Option<(u8, Vec<String>)>> = const { RefCell::new(None) };
+}
+
+/// Fija el nivel de verbosidad con el que se registran las etapas de la ejecución.
+///
+/// # Parámetros
+/// - `nivel`: `0` para no registrar nada, `DETALLE` o `DEPURACION`.
+pub fn fijar_nivel(nivel: u8) {
+    NIVEL.store(nivel, Ordering::Relaxed);
+}
+
+/// Indica si se registran los mensajes del nivel indicado.
+pub fn activo(nivel: u8) -> bool {
+    #[cfg(test)]
+    if let Some(capturado) =
+        CAPTURA.with_borrow(|captura| captura.as_ref().map(|(nivel, _)| *nivel))
+    {
+        return capturado >= nivel;
+    }
+    NIVEL.load(Ordering::Relaxed) >= nivel
+}
+
+/// Escribe un mensaje en la salida de errores si el nivel de verbosidad lo permite. El mensaje
+/// se arma solo en ese caso.
+///
+/// # Parámetros
+/// - `nivel`: El nivel a partir del cual se registra el mensaje.
+/// - `mensaje`: La función que arma el mensaje.
+pub fn registrar(nivel: u8, mensaje: impl FnOnce() -> String) {
+    if activo(nivel) {
+        let linea = format!("[registro] {}", mensaje());
+        #[cfg(test)]
+        let Some(linea) = CAPTURA.with_borrow_mut(|captura| match captura {
+            Some((_, mensajes)) => {
+                mensajes.push(linea);
+                None
+            }
+            None => Some(linea),
+        }) else {
+            return;
+        };
+        // Si no se puede escribir en la salida de errores, no hay otro lugar donde informarlo.
+        let _ = writeln!(std::io::stderr().lock(), "{}", linea);
+    }
+}
+
+/// Escribe un aviso en la salida de errores, cualquiera sea el nivel de verbosidad.
+///
+/// # Parámetros
+/// - `mensaje`: El aviso, sin el salto de línea final.
+pub fn advertir(mensaje: String) {
+    // Si no se puede escribir en la salida de errores, no hay otro lugar donde informarlo.
+    let _ = writeln!(std::io::stderr().lock(), "[advertencia] {}", mensaje);
+}
+
+/// Escribe en la salida de errores el avance de una operación larga, como la lectura de una
+/// tabla con `--progreso`.
+///
+/// # Parámetros
+/// - `mensaje`: El avance, sin el salto de línea final.
+pub fn progreso(mensaje: String) {
+    // Si no se puede escribir en la salida de errores, no hay otro lugar donde informarlo.
+    let _ = writeln!(std::io::stderr().lock(), "[progreso] {}", mensaje);
+}
+
+/// Ejecuta `funcion` en el hilo actual registrando con el nivel indicado, y devuelve los
+/// mensajes que se registraron en vez de escribirlos en la salida de errores.
+#[cfg(test)]
+pub fn capturar(nivel: u8, funcion: impl FnOnce()) -> Vec<String> {
+    CAPTURA.set(Some((nivel, Vec::new())));
+    funcion();
+    CAPTURA
+        .take()
+        .map(|(_, mensajes)| mensajes)
+        .unwrap_or_default()
+}
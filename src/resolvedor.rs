@@ -0,0 +1,556 @@
+use crate::almacenamiento::{Almacenamiento, AlmacenamientoArchivos};
+use crate::archivo::{procesar_ruta, EXTENSIONES_TABLA};
+use crate::errores;
+use crate::gzip::EXTENSION_GZIP;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Variable de entorno con alias de tablas, con el formato `logica=fisica,otra=otra_fisica`.
+const VARIABLE_ALIAS: &str = "TABLAS_ALIAS";
+
+/// Traduce el nombre lógico de una tabla usado en las consultas a la ubicación del archivo
+/// que la almacena.
+///
+/// Permite ejecutar las mismas consultas contra distintas disposiciones de archivos
+/// (desarrollo, pruebas, producción) cambiando únicamente el resolvedor. Es `Send + Sync`
+/// para que el servidor de PostgreSQL lo comparta entre las conexiones que atiende a la vez.
+pub trait ResolvedorTablas: std::fmt::Debug + Send + Sync {
+    /// Devuelve la ruta del archivo de la tabla.
+    ///
+    /// # Parámetros
+    /// - `tabla`: El nombre de la tabla tal como aparece en la consulta.
+    ///
+    /// # Retorno
+    /// Retorna la ruta del archivo o `Errores::InvalidTable` si la tabla no se puede resolver.
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores>;
+
+    /// Devuelve el almacenamiento en el que están las rutas que resuelve; por defecto, los
+    /// archivos del sistema de archivos.
+    fn almacenamiento(&self) -> Arc<dyn Almacenamiento> {
+        Arc::new(AlmacenamientoArchivos::new())
+    }
+
+    /// Devuelve los nombres de las tablas que se pueden consultar, ordenados y sin repetir; por
+    /// defecto ninguno, para los resolvedores que no pueden enumerarlas.
+    ///
+    /// # Retorno
+    /// Los nombres de las tablas, o `Errores::Io` si no se pudieron enumerar.
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        Ok(Vec::new())
+    }
+}
+
+/// Resuelve las tablas como archivos dentro de un directorio, con el nombre de la tabla en
+/// minúsculas, sin extensión o con alguna de las extensiones indicadas (ver `procesar_ruta`).
+#[derive(Debug)]
+pub struct ResolvedorDirectorio {
+    directorio: String,
+    extensiones: Vec<String>,
+}
+
+impl ResolvedorDirectorio {
+    /// Crea el resolvedor con las extensiones por defecto, `EXTENSIONES_TABLA`.
+    pub fn new(directorio: &str) -> ResolvedorDirectorio {
+        let extensiones = EXTENSIONES_TABLA.iter().map(|e| e.to_string()).collect();
+        ResolvedorDirectorio::con_extensiones(directorio, extensiones)
+    }
+
+    /// Crea el resolvedor con las extensiones con las que se buscan los archivos, sin el punto.
+    pub fn con_extensiones(directorio: &str, extensiones: Vec<String>) -> ResolvedorDirectorio {
+        ResolvedorDirectorio {
+            directorio: directorio.to_string(),
+            extensiones,
+        }
+    }
+}
+
+impl ResolvedorTablas for ResolvedorDirectorio {
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        if tabla.is_empty() {
+            return Err(errores::Errores::InvalidTable(tabla.to_string()));
+        }
+        Ok(procesar_ruta(&self.directorio, tabla, &self.extensiones))
+    }
+
+    /// Las tablas son los archivos del directorio sin extensión o con alguna de las
+    /// extensiones, cuyo nombre está en minúsculas como lo busca `procesar_ruta`. Los archivos
+    /// auxiliares, como los esquemas o los bloqueos, tienen otras extensiones y no se listan.
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        let mut tablas = Vec::new();
+        for entrada in fs::read_dir(&self.directorio)? {
+            let entrada = entrada?;
+            if !entrada.file_type()?.is_file() {
+                continue;
+            }
+            let nombre = entrada.file_name().to_string_lossy().to_string();
+            let tabla = match nombre.split_once('.') {
+                None => Some(nombre.as_str()),
+                Some(_) => self
+                    .extensiones
+                    .iter()
+                    .find_map(|extension| nombre.strip_suffix(&format!(".{}", extension))),
+            };
+            if let Some(tabla) = tabla {
+                if !tabla.is_empty() && tabla == tabla.to_lowercase() {
+                    tablas.push(tabla.to_string());
+                }
+            }
+        }
+        tablas.sort();
+        tablas.dedup();
+        Ok(tablas)
+    }
+}
+
+/// Resuelve todas las tablas al mismo archivo, para consultar un archivo suelto sin moverlo a
+/// un directorio de tablas.
+#[derive(Debug)]
+pub struct ResolvedorArchivo {
+    ruta: String,
+}
+
+impl ResolvedorArchivo {
+    pub fn new(ruta: &str) -> ResolvedorArchivo {
+        ResolvedorArchivo {
+            ruta: ruta.to_string(),
+        }
+    }
+}
+
+impl ResolvedorTablas for ResolvedorArchivo {
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        if tabla.is_empty() {
+            return Err(errores::Errores::InvalidTable(tabla.to_string()));
+        }
+        Ok(self.ruta.to_string())
+    }
+
+    /// Todas las tablas son el mismo archivo, que se lista con su nombre sin extensión.
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        let nombre = Path::new(&self.ruta)
+            .file_name()
+            .map(|nombre| nombre.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let tabla = nombre.split('.').next().unwrap_or_default();
+        Ok(vec![tabla.to_string()])
+    }
+}
+
+/// Resuelve las tablas a partir de un archivo de manifiesto.
+///
+/// Cada línea del manifiesto tiene la forma `tabla = ruta`; las rutas relativas se toman
+/// desde el directorio del manifiesto. Las líneas vacías y las que comienzan con `--` se
+/// ignoran.
+#[derive(Debug)]
+pub struct ResolvedorManifiesto {
+    rutas: HashMap<String, String>,
+}
+
+impl ResolvedorManifiesto {
+    /// Lee el manifiesto ubicado en `ruta_manifiesto`.
+    ///
+    /// # Parámetros
+    /// - `ruta_manifiesto`: La ruta del archivo de manifiesto.
+    ///
+    /// # Retorno
+    /// Retorna el resolvedor, `Errores::Io` si el manifiesto no se puede leer o
+    /// `Errores::ValorInvalido` si tiene líneas mal formadas.
+    pub fn cargar(ruta_manifiesto: &str) -> Result<ResolvedorManifiesto, errores::Errores> {
+        let contenido = fs::read_to_string(ruta_manifiesto)?;
+        let directorio_base = match Path::new(ruta_manifiesto).parent() {
+            Some(directorio) => directorio.to_string_lossy().to_string(),
+            None => String::new(),
+        };
+        Self::parsear(&contenido, &directorio_base)
+    }
+
+    /// Parsea el contenido de un manifiesto.
+    ///
+    /// # Parámetros
+    /// - `contenido`: El texto del manifiesto.
+    /// - `directorio_base`: El directorio desde el que se resuelven las rutas relativas.
+    ///
+    /// # Retorno
+    /// Retorna el resolvedor o `Errores::ValorInvalido` si alguna línea no tiene la forma
+    /// `tabla = ruta`.
+    pub fn parsear(
+        contenido: &str,
+        directorio_base: &str,
+    ) -> Result<ResolvedorManifiesto, errores::Errores> {
+        let mut rutas = HashMap::new();
+        for linea in contenido.lines() {
+            let linea = linea.trim();
+            if linea.is_empty() || linea.starts_with("--") {
+                continue;
+            }
+            let linea_invalida = || {
+                errores::Errores::ValorInvalido(format!(
+                    "la línea '{}' del manifiesto no tiene la forma tabla = ruta",
+                    linea
+                ))
+            };
+            let (tabla, ruta) = match linea.split_once('=') {
+                Some((tabla, ruta)) => (tabla.trim(), ruta.trim()),
+                None => return Err(linea_invalida()),
+            };
+            if tabla.is_empty() || ruta.is_empty() {
+                return Err(linea_invalida());
+            }
+            let ruta = if Path::new(ruta).is_absolute() || directorio_base.is_empty() {
+                ruta.to_string()
+            } else {
+                Path::new(directorio_base)
+                    .join(ruta)
+                    .to_string_lossy()
+                    .to_string()
+            };
+            rutas.insert(tabla.to_lowercase(), ruta);
+        }
+        Ok(ResolvedorManifiesto { rutas })
+    }
+}
+
+impl ResolvedorTablas for ResolvedorManifiesto {
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        match self.rutas.get(&tabla.to_lowercase()) {
+            Some(ruta) => Ok(ruta.to_string()),
+            None => Err(errores::Errores::InvalidTable(tabla.to_string())),
+        }
+    }
+
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        let mut tablas: Vec<String> = self.rutas.keys().cloned().collect();
+        tablas.sort();
+        Ok(tablas)
+    }
+}
+
+/// Traduce nombres de tablas a otros nombres antes de delegar en otro resolvedor.
+///
+/// Las tablas sin alias se delegan sin cambios.
+#[derive(Debug)]
+pub struct ResolvedorAlias {
+    alias: HashMap<String, String>,
+    resolvedor: Box<dyn ResolvedorTablas>,
+}
+
+impl ResolvedorAlias {
+    pub fn new(
+        alias: HashMap<String, String>,
+        resolvedor: Box<dyn ResolvedorTablas>,
+    ) -> ResolvedorAlias {
+        ResolvedorAlias { alias, resolvedor }
+    }
+
+    /// Parsea una lista de alias con el formato `logica=fisica,otra=otra_fisica`.
+    ///
+    /// # Parámetros
+    /// - `lista`: La lista de alias separados por comas.
+    ///
+    /// # Retorno
+    /// Retorna el mapa de alias o `Errores::ValorInvalido` si algún elemento no tiene la forma
+    /// `logica=fisica`.
+    pub fn parsear_alias(lista: &str) -> Result<HashMap<String, String>, errores::Errores> {
+        let mut alias = HashMap::new();
+        for par in lista.split(',').map(|par| par.trim()) {
+            if par.is_empty() {
+                continue;
+            }
+            match par.split_once('=') {
+                Some((logica, fisica))
+                    if !logica.trim().is_empty() && !fisica.trim().is_empty() =>
+                {
+                    alias.insert(logica.trim().to_lowercase(), fisica.trim().to_string());
+                }
+                _ => {
+                    return Err(errores::Errores::ValorInvalido(format!(
+                        "el alias '{}' no tiene la forma logica=fisica",
+                        par
+                    )))
+                }
+            }
+        }
+        Ok(alias)
+    }
+}
+
+impl ResolvedorTablas for ResolvedorAlias {
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        match self.alias.get(&tabla.to_lowercase()) {
+            Some(destino) => self.resolvedor.resolver(destino),
+            None => self.resolvedor.resolver(tabla),
+        }
+    }
+
+    fn almacenamiento(&self) -> Arc<dyn Almacenamiento> {
+        self.resolvedor.almacenamiento()
+    }
+
+    /// Se listan las tablas del resolvedor y además los alias.
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        let mut tablas = self.resolvedor.tablas()?;
+        tablas.extend(self.alias.keys().cloned());
+        tablas.sort();
+        tablas.dedup();
+        Ok(tablas)
+    }
+}
+
+/// Busca las tablas en varios resolvedores, en orden: cada tabla se resuelve con el primero
+/// que la ubica en un archivo existente.
+///
+/// Si ninguno la encuentra, se usa la ruta del primero que la resuelve, de modo que el error
+/// al leerla (o la tabla que crea un `INSERT`) corresponde a esa ruta.
+#[derive(Debug)]
+pub struct ResolvedorBusqueda {
+    resolvedores: Vec<Box<dyn ResolvedorTablas>>,
+}
+
+impl ResolvedorBusqueda {
+    pub fn new(resolvedores: Vec<Box<dyn ResolvedorTablas>>) -> ResolvedorBusqueda {
+        ResolvedorBusqueda { resolvedores }
+    }
+}
+
+impl ResolvedorTablas for ResolvedorBusqueda {
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        let mut primera = None;
+        for resolvedor in &self.resolvedores {
+            let Ok(ruta) = resolvedor.resolver(tabla) else {
+                continue;
+            };
+            if Path::new(&ruta).is_file() {
+                return Ok(ruta);
+            }
+            primera.get_or_insert(ruta);
+        }
+        primera.ok_or_else(|| errores::Errores::InvalidTable(tabla.to_string()))
+    }
+
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        let mut tablas = Vec::new();
+        for resolvedor in &self.resolvedores {
+            tablas.extend(resolvedor.tablas()?);
+        }
+        tablas.sort();
+        tablas.dedup();
+        Ok(tablas)
+    }
+}
+
+/// Construye el resolvedor de tablas correspondiente a la ruta recibida por línea de comandos.
+///
+/// Si la ruta es un archivo con alguna de las extensiones de las tablas (con o sin `.gz`), todas
+/// las tablas de las consultas son ese archivo; si es otro archivo se interpreta como
+/// manifiesto, y si no, como directorio de tablas. Si la ruta no existe y es una lista de rutas
+/// separadas por `:` (`;` en Windows), las tablas se buscan en cada una, en orden (ver
+/// `ResolvedorBusqueda`). Si la variable de entorno `TABLAS_ALIAS` está definida, sus alias se
+/// aplican por encima.
+///
+/// # Parámetros
+/// - `ruta`: La ruta recibida como primer argumento del programa, o la lista de rutas.
+/// - `extensiones`: Las extensiones con las que se buscan las tablas del directorio, o `None`
+///   para usar `EXTENSIONES_TABLA`.
+///
+/// # Retorno
+/// Retorna el resolvedor o `Errores::ValorInvalido` si el manifiesto o los alias no son
+/// válidos.
+pub fn crear_resolvedor(
+    ruta: &str,
+    extensiones: Option<&[String]>,
+) -> Result<Box<dyn ResolvedorTablas>, errores::Errores> {
+    let extensiones: Vec<String> = match extensiones {
+        Some(extensiones) => extensiones.to_vec(),
+        None => EXTENSIONES_TABLA.iter().map(|e| e.to_string()).collect(),
+    };
+    let rutas: Vec<String> = std::env::split_paths(ruta)
+        .map(|ruta| ruta.to_string_lossy().to_string())
+        .filter(|ruta| !ruta.is_empty())
+        .collect();
+    let resolvedor = if Path::new(ruta).exists() || rutas.len() < 2 {
+        resolvedor_de_ruta(ruta, &extensiones)?
+    } else {
+        let mut resolvedores = Vec::new();
+        for ruta in &rutas {
+            resolvedores.push(resolvedor_de_ruta(ruta, &extensiones)?);
+        }
+        Box::new(ResolvedorBusqueda::new(resolvedores))
+    };
+    match std::env::var(VARIABLE_ALIAS) {
+        Ok(lista) => Ok(Box::new(ResolvedorAlias::new(
+            ResolvedorAlias::parsear_alias(&lista)?,
+            resolvedor,
+        ))),
+        Err(_) => Ok(resolvedor),
+    }
+}
+
+/// Construye el resolvedor de una única ruta: un archivo de datos, un manifiesto o un
+/// directorio (ver `crear_resolvedor`).
+fn resolvedor_de_ruta(
+    ruta: &str,
+    extensiones: &[String],
+) -> Result<Box<dyn ResolvedorTablas>, errores::Errores> {
+    if !Path::new(ruta).is_file() {
+        return Ok(Box::new(ResolvedorDirectorio::con_extensiones(
+            ruta,
+            extensiones.to_vec(),
+        )));
+    }
+    let nombre = ruta.to_lowercase();
+    let nombre = nombre.strip_suffix(EXTENSION_GZIP).unwrap_or(&nombre);
+    if extensiones
+        .iter()
+        .any(|extension| nombre.ends_with(&format!(".{}", extension.to_lowercase())))
+    {
+        Ok(Box::new(ResolvedorArchivo::new(ruta)))
+    } else {
+        Ok(Box::new(ResolvedorManifiesto::cargar(ruta)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolvedor_directorio() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        assert_eq!(resolvedor.resolver("Personas").unwrap(), "tablas/personas");
+        assert_eq!(
+            resolvedor.resolver(""),
+            Err(errores::Errores::InvalidTable(String::new()))
+        );
+    }
+
+    #[test]
+    fn test_resolvedor_directorio_prueba_las_extensiones() {
+        let directorio = std::env::temp_dir().join("test_resolvedor_directorio_extensiones");
+        fs::create_dir_all(&directorio).unwrap();
+        fs::write(directorio.join("clientes.csv"), "id\n").unwrap();
+        fs::write(directorio.join("ventas.tsv"), "id\n").unwrap();
+        fs::write(directorio.join("personas"), "id\n").unwrap();
+        fs::write(directorio.join("personas.csv"), "id\n").unwrap();
+        let ruta = directorio.to_string_lossy().to_string();
+
+        let resolvedor = ResolvedorDirectorio::new(&ruta);
+        assert_eq!(
+            resolvedor.resolver("Clientes").unwrap(),
+            format!("{}/clientes.csv", ruta)
+        );
+        // El archivo sin extensión tiene prioridad.
+        assert_eq!(
+            resolvedor.resolver("personas").unwrap(),
+            format!("{}/personas", ruta)
+        );
+        assert_eq!(
+            resolvedor.resolver("ventas").unwrap(),
+            format!("{}/ventas", ruta)
+        );
+
+        let resolvedor =
+            ResolvedorDirectorio::con_extensiones(&ruta, vec!["txt".into(), "tsv".into()]);
+        assert_eq!(
+            resolvedor.resolver("ventas").unwrap(),
+            format!("{}/ventas.tsv", ruta)
+        );
+        assert_eq!(resolvedor.tablas().unwrap(), vec!["personas", "ventas"]);
+
+        fs::write(directorio.join("clientes.csv.esquema"), "id INTEGER\n").unwrap();
+        fs::write(directorio.join("Mayusculas.csv"), "id\n").unwrap();
+        fs::create_dir_all(directorio.join("historico")).unwrap();
+        assert_eq!(
+            ResolvedorDirectorio::new(&ruta).tablas().unwrap(),
+            vec!["clientes", "personas"]
+        );
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_resolvedor_manifiesto() {
+        let contenido =
+            "-- tablas de produccion\nclientes = datos/clientes_2024.csv\nventas=/srv/ventas.csv\n";
+        let resolvedor = ResolvedorManifiesto::parsear(contenido, "config").unwrap();
+
+        assert_eq!(
+            resolvedor.resolver("CLIENTES").unwrap(),
+            "config/datos/clientes_2024.csv"
+        );
+        assert_eq!(resolvedor.resolver("ventas").unwrap(), "/srv/ventas.csv");
+        assert_eq!(resolvedor.tablas().unwrap(), vec!["clientes", "ventas"]);
+        assert_eq!(
+            resolvedor.resolver("personas"),
+            Err(errores::Errores::InvalidTable("personas".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_crear_resolvedor_con_un_archivo_de_datos() {
+        let directorio = std::env::temp_dir().join("test_crear_resolvedor_archivo");
+        fs::create_dir_all(&directorio).unwrap();
+        let datos = directorio.join("export-2024.csv");
+        fs::write(&datos, "id\n").unwrap();
+        let manifiesto = directorio.join("tablas.txt");
+        fs::write(&manifiesto, "clientes = export-2024.csv\n").unwrap();
+        let datos = datos.to_string_lossy().to_string();
+
+        let resolvedor = crear_resolvedor(&datos, None).unwrap();
+        assert_eq!(resolvedor.resolver("export").unwrap(), datos);
+        assert_eq!(resolvedor.resolver("cualquiera").unwrap(), datos);
+        let resolvedor = crear_resolvedor(&manifiesto.to_string_lossy(), None).unwrap();
+        assert_eq!(resolvedor.resolver("clientes").unwrap(), datos);
+        assert!(resolvedor.resolver("export").is_err());
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_resolvedor_busqueda_en_varios_directorios() {
+        let directorio = std::env::temp_dir().join("test_resolvedor_busqueda");
+        let (base, temporales) = (directorio.join("base"), directorio.join("temporales"));
+        fs::create_dir_all(&base).unwrap();
+        fs::create_dir_all(&temporales).unwrap();
+        fs::write(base.join("clientes.csv"), "id\n").unwrap();
+        fs::write(temporales.join("clientes"), "id\n").unwrap();
+        fs::write(temporales.join("borrador.csv"), "id\n").unwrap();
+        let (base, temporales) = (
+            base.to_string_lossy().to_string(),
+            temporales.to_string_lossy().to_string(),
+        );
+        let rutas = std::env::join_paths([&base, &temporales]).unwrap();
+
+        let resolvedor = crear_resolvedor(&rutas.to_string_lossy(), None).unwrap();
+        assert_eq!(
+            resolvedor.resolver("clientes").unwrap(),
+            format!("{}/clientes.csv", base)
+        );
+        assert_eq!(
+            resolvedor.resolver("borrador").unwrap(),
+            format!("{}/borrador.csv", temporales)
+        );
+        assert_eq!(
+            resolvedor.resolver("ventas").unwrap(),
+            format!("{}/ventas", base)
+        );
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_resolvedor_manifiesto_invalido() {
+        assert!(ResolvedorManifiesto::parsear("clientes", "").is_err());
+    }
+
+    #[test]
+    fn test_resolvedor_alias() {
+        let alias = ResolvedorAlias::parsear_alias("clientes=clientes_staging, ventas=v").unwrap();
+        let resolvedor = ResolvedorAlias::new(alias, Box::new(ResolvedorDirectorio::new("t")));
+
+        assert_eq!(
+            resolvedor.resolver("clientes").unwrap(),
+            "t/clientes_staging"
+        );
+        assert_eq!(resolvedor.resolver("personas").unwrap(), "t/personas");
+        assert!(ResolvedorAlias::parsear_alias("clientes").is_err());
+    }
+}
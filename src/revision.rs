@@ -0,0 +1,385 @@
+use crate::almacenamiento::Almacenamiento;
+use crate::archivo::{
+    formatear_registro, leer_registro, leer_registro_en, parsear_linea_archivo, parsear_registro,
+    OpcionesArchivo,
+};
+use crate::consulta::{MetodosConsulta, Resumen};
+use crate::errores;
+use crate::esquema::Esquema;
+use crate::expresion::Valor;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorResultados;
+use crate::sintaxis::SentenciaRevisar;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Columnas del resultado de una revisión.
+const COLUMNAS_RESULTADO: [&str; 3] = ["linea", "problema", "reparacion"];
+
+/// Problema encontrado en un registro de la tabla, junto con lo que hizo la reparación.
+///
+/// - `linea`: La línea del archivo en la que empieza el registro.
+/// - `problema`: La descripción del problema.
+/// - `reparacion`: Lo que se hizo para corregirlo, o `None` si no se corrigió.
+#[derive(Debug, Clone, PartialEq)]
+struct Problema {
+    linea: usize,
+    problema: String,
+    reparacion: Option<&'static str>,
+}
+
+/// Representa una sentencia `CHECK TABLE` o `REPAIR TABLE`.
+///
+/// Recorre todos los registros de la tabla buscando los que no tienen un campo por columna,
+/// los valores que no son del tipo declarado de su columna en el esquema y las claves
+/// primarias repetidas (ver `Esquema::clave_primaria`). El resultado tiene una fila por
+/// problema con la línea del archivo, el problema y la reparación.
+///
+/// `REPAIR TABLE` además corrige la tabla: completa con `NULL` los registros a los que les
+/// faltan campos, descarta los campos sobrantes y elimina los registros cuya clave primaria ya
+/// apareció antes. Los valores de otro tipo no tienen una corrección evidente, así que solo se
+/// informan. La tabla se reescribe completa (ver `Almacenamiento::reescribir`) y solo si hubo
+/// algo que corregir; el encabezado se copia tal como estaba, sin pasar a minúsculas los
+/// nombres de las columnas.
+///
+/// # Campos
+///
+/// - `tabla`: El nombre de la tabla.
+/// - `ruta_tabla`: La ruta de la tabla en el almacenamiento.
+/// - `reparar`: Si además de informar los problemas se corrigen.
+/// - `almacenamiento`: El `Almacenamiento` en el que está guardada la tabla.
+/// - `esquema`: El `Esquema` de la tabla, con los tipos y la clave primaria.
+/// - `opciones`: Las `OpcionesArchivo` con las que se lee y escribe el archivo de la tabla.
+#[derive(Debug)]
+pub struct ConsultaRevision {
+    pub tabla: String,
+    pub ruta_tabla: String,
+    pub reparar: bool,
+    pub almacenamiento: Arc<dyn Almacenamiento>,
+    pub esquema: Esquema,
+    pub opciones: OpcionesArchivo,
+}
+
+impl ConsultaRevision {
+    /// Crea la revisión a partir de una sentencia ya parseada.
+    ///
+    /// # Parámetros
+    /// - `sentencia`: La sentencia `CHECK TABLE` o `REPAIR TABLE` parseada.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla.
+    /// - `opciones`: Las opciones con las que se lee y escribe el archivo de la tabla.
+    ///
+    /// # Retorno
+    /// La `ConsultaRevision`, o `Errores::InvalidTable` si la tabla no se puede resolver.
+    pub fn desde_sentencia(
+        sentencia: SentenciaRevisar,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaRevision, errores::Errores> {
+        let ruta_tabla = resolvedor.resolver(&sentencia.tabla)?;
+        registro::registrar(registro::DETALLE, || {
+            format!("tabla '{}' en '{}'", sentencia.tabla, ruta_tabla)
+        });
+
+        Ok(ConsultaRevision {
+            tabla: sentencia.tabla,
+            ruta_tabla,
+            reparar: sentencia.reparar,
+            almacenamiento: resolvedor.almacenamiento(),
+            esquema: Esquema::default(),
+            opciones: opciones.clone(),
+        })
+    }
+
+    /// Recorre la tabla y reúne sus problemas.
+    ///
+    /// # Retorno
+    /// Los problemas en el orden en que aparecen y, si se repara y hubo algo que corregir, el
+    /// nuevo contenido de la tabla; o el error de E/S si no se pudo leer.
+    fn revisar(&mut self) -> Result<(Vec<Problema>, Option<String>), errores::Errores> {
+        let mut lector = self.almacenamiento.leer(&self.ruta_tabla, &self.opciones)?;
+        let mut contenido = String::new();
+        let mut linea = 0;
+        let columnas: Vec<String> = match &self.opciones.columnas {
+            Some(columnas) => columnas
+                .iter()
+                .map(|columna| columna.to_lowercase())
+                .collect(),
+            None => {
+                let encabezado = leer_registro(lector.as_mut())?.unwrap_or_default();
+                linea += 1 + encabezado.matches('\n').count();
+                if self.reparar {
+                    contenido.push_str(&encabezado);
+                    contenido.push('\n');
+                }
+                let encabezado = encabezado.trim_end();
+                self.opciones.detectar_delimitador(encabezado);
+                parsear_linea_archivo(encabezado, self.opciones.delimitador()).1
+            }
+        };
+        let delimitador = self.opciones.delimitador();
+        let tipos: Vec<_> = columnas
+            .iter()
+            .map(|columna| self.esquema.columna(columna).and_then(|c| c.tipo))
+            .collect();
+        let clave: Option<Vec<usize>> = self
+            .esquema
+            .clave_primaria()
+            .iter()
+            .map(|nombre| columnas.iter().position(|columna| columna == nombre))
+            .collect();
+        let clave = clave.filter(|clave| !clave.is_empty());
+
+        let mut problemas = Vec::new();
+        let mut claves: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+        let mut modificada = false;
+        let mut texto = String::new();
+        while leer_registro_en(lector.as_mut(), &mut texto)? {
+            let inicio = linea + 1;
+            linea = inicio + texto.matches('\n').count();
+            let mut campos = parsear_registro(&texto, delimitador, self.opciones.nulo());
+            let mut corregido = false;
+            if campos.len() != columnas.len() {
+                let reparacion = if campos.len() < columnas.len() {
+                    "se completó con NULL"
+                } else {
+                    "se descartaron los campos sobrantes"
+                };
+                problemas.push(Problema {
+                    linea: inicio,
+                    problema: format!(
+                        "tiene {} campos y la tabla tiene {} columnas",
+                        campos.len(),
+                        columnas.len()
+                    ),
+                    reparacion: self.reparar.then_some(reparacion),
+                });
+                campos.resize(columnas.len(), None);
+                corregido = true;
+            }
+            for ((campo, tipo), columna) in campos.iter().zip(&tipos).zip(&columnas) {
+                let (Some(valor), Some(tipo)) = (campo, tipo) else {
+                    continue;
+                };
+                if Valor::desde_campo_tipado(Some(valor), *tipo).is_err() {
+                    problemas.push(Problema {
+                        linea: inicio,
+                        problema: format!(
+                            "'{}' no es un valor de tipo {} para la columna '{}'",
+                            valor, tipo, columna
+                        ),
+                        reparacion: None,
+                    });
+                }
+            }
+            if let Some(clave) = &clave {
+                let valores: Vec<Option<String>> =
+                    clave.iter().map(|indice| campos[*indice].clone()).collect();
+                if let Some(primera) = claves.get(&valores) {
+                    let texto_clave: Vec<String> = clave
+                        .iter()
+                        .zip(&valores)
+                        .map(|(indice, valor)| {
+                            let valor = valor.as_deref().unwrap_or("NULL");
+                            format!("{} = {}", columnas[*indice], valor)
+                        })
+                        .collect();
+                    problemas.push(Problema {
+                        linea: inicio,
+                        problema: format!(
+                            "la clave primaria ({}) ya aparece en la línea {}",
+                            texto_clave.join(", "),
+                            primera
+                        ),
+                        reparacion: self.reparar.then_some("se eliminó el registro"),
+                    });
+                    modificada = true;
+                    continue;
+                }
+                claves.insert(valores, inicio);
+            }
+            modificada |= corregido;
+            if !self.reparar {
+                continue;
+            }
+            if corregido {
+                contenido.push_str(&formatear_registro(
+                    &campos,
+                    delimitador,
+                    self.opciones.nulo(),
+                ));
+            } else {
+                contenido.push_str(&texto);
+            }
+            contenido.push('\n');
+        }
+        Ok((problemas, (self.reparar && modificada).then_some(contenido)))
+    }
+}
+
+impl MetodosConsulta for ConsultaRevision {
+    /// Verifica que la tabla exista y carga su esquema.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())`, `Errores::InvalidTable` si la tabla no se puede leer o el error del
+    /// esquema si no es válido.
+    fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
+        if let Err(error) = self.almacenamiento.leer(&self.ruta_tabla, &self.opciones) {
+            return Err(errores::Errores::tabla_ilegible(error, &self.ruta_tabla));
+        }
+        self.esquema = self.almacenamiento.esquema(&self.ruta_tabla)?;
+        Ok(())
+    }
+
+    /// Revisa la tabla, escribe una fila por problema y, con `REPAIR TABLE`, reescribe la tabla
+    /// corregida.
+    ///
+    /// # Retorno
+    /// El `Resumen` con la cantidad de problemas encontrados, o el error que impidió leer o
+    /// reescribir la tabla.
+    fn procesar(
+        &mut self,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let (problemas, contenido) = self.revisar()?;
+        escritor.escribir_encabezado(&COLUMNAS_RESULTADO.map(String::from))?;
+        for problema in &problemas {
+            escritor.escribir_fila(&[
+                Valor::Entero(problema.linea as i128),
+                Valor::Texto(problema.problema.to_string()),
+                problema.reparacion.map_or(Valor::Nulo, |reparacion| {
+                    Valor::Texto(reparacion.to_string())
+                }),
+            ])?;
+        }
+        if let Some(contenido) = contenido {
+            self.almacenamiento
+                .reescribir(&self.ruta_tabla, &self.opciones, &contenido)?;
+            registro::registrar(registro::DETALLE, || {
+                format!("tabla '{}' reparada", self.tabla)
+            });
+        }
+        Ok(Resumen::Filas(problemas.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use crate::consulta::SQLConsulta;
+    use crate::resolvedor::ResolvedorDirectorio;
+    use crate::salida::OpcionesSalida;
+    use std::fs;
+
+    fn ejecutar(resolvedor: &dyn ResolvedorTablas, consulta: &str) -> String {
+        let mut destino = Vec::new();
+        let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+        SQLConsulta::crear_consulta(consulta, resolvedor, &OpcionesArchivo::default())
+            .unwrap()
+            .procesar_consulta(escritor.as_mut())
+            .unwrap();
+        drop(escritor);
+        String::from_utf8(destino).unwrap()
+    }
+
+    #[test]
+    fn test_revisar_y_reparar_cantidad_de_campos() {
+        let tablas = TablasEnMemoria::new();
+        let contenido = "nombre,edad\nAna,30\nLuis\n\"Eva\nPérez\",25,extra\n";
+        tablas.agregar_tabla("personas", contenido);
+
+        assert_eq!(
+            ejecutar(&tablas, "CHECK TABLE personas"),
+            "linea,problema,reparacion\n\
+             3,tiene 1 campos y la tabla tiene 2 columnas,\n\
+             4,tiene 3 campos y la tabla tiene 2 columnas,\n"
+        );
+        assert_eq!(tablas.contenido("personas").unwrap(), contenido);
+
+        assert_eq!(
+            ejecutar(&tablas, "REPAIR TABLE personas"),
+            "linea,problema,reparacion\n\
+             3,tiene 1 campos y la tabla tiene 2 columnas,se completó con NULL\n\
+             4,tiene 3 campos y la tabla tiene 2 columnas,se descartaron los campos sobrantes\n"
+        );
+        assert_eq!(
+            tablas.contenido("personas").unwrap(),
+            "nombre,edad\nAna,30\nLuis,\n\"Eva\nPérez\",25\n"
+        );
+        assert_eq!(
+            ejecutar(&tablas, "CHECK TABLE personas"),
+            "linea,problema,reparacion\n"
+        );
+    }
+
+    #[test]
+    fn test_revisar_tipos_y_clave_primaria() {
+        let directorio = std::env::temp_dir().join("test_revisar_tipos_y_clave_primaria");
+        fs::create_dir_all(&directorio).unwrap();
+        let tabla = directorio.join("personas.csv");
+        fs::write(&tabla, "id,nombre,edad\n1,Ana,30\n2,Luis,abc\n1,Eva,25\n").unwrap();
+        fs::write(
+            directorio.join("personas.csv.esquema"),
+            "id INTEGER PRIMARY KEY\nnombre\nedad INTEGER\n",
+        )
+        .unwrap();
+        let resolvedor = ResolvedorDirectorio::new(&directorio.to_string_lossy());
+
+        assert_eq!(
+            ejecutar(&resolvedor, "CHECK TABLE personas"),
+            "linea,problema,reparacion\n\
+             3,'abc' no es un valor de tipo INTEGER para la columna 'edad',\n\
+             4,la clave primaria (id = 1) ya aparece en la línea 2,\n"
+        );
+        assert_eq!(
+            ejecutar(&resolvedor, "REPAIR TABLE personas"),
+            "linea,problema,reparacion\n\
+             3,'abc' no es un valor de tipo INTEGER para la columna 'edad',\n\
+             4,la clave primaria (id = 1) ya aparece en la línea 2,se eliminó el registro\n"
+        );
+        assert_eq!(
+            fs::read_to_string(&tabla).unwrap(),
+            "id,nombre,edad\n1,Ana,30\n2,Luis,abc\n"
+        );
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+
+    #[test]
+    fn test_reparar_conserva_el_encabezado_original() {
+        let directorio = std::env::temp_dir().join("test_reparar_conserva_el_encabezado_original");
+        fs::create_dir_all(&directorio).unwrap();
+        let tabla = directorio.join("personas.csv");
+        let encabezado = "\"Nombre\";EDAD;Ciudad\n";
+        fs::write(&tabla, format!("{encabezado}Ana;30;Rosario\nLuis;45\n")).unwrap();
+        let resolvedor = ResolvedorDirectorio::new(&directorio.to_string_lossy());
+
+        ejecutar(&resolvedor, "REPAIR TABLE personas");
+        ejecutar(
+            &resolvedor,
+            "INSERT INTO personas (nombre, edad, ciudad) VALUES ('Eva', 25, 'Salta')",
+        );
+        assert_eq!(
+            fs::read_to_string(&tabla).unwrap(),
+            format!("{encabezado}Ana;30;Rosario\nLuis;45;\nEva;25;Salta\n")
+        );
+        assert_eq!(
+            ejecutar(&resolvedor, "SELECT nombre FROM personas WHERE edad < 40"),
+            "nombre\nAna\nEva\n"
+        );
+
+        fs::write(&tabla, encabezado).unwrap();
+        ejecutar(
+            &resolvedor,
+            "INSERT INTO personas VALUES ('Ana', 30, 'Rosario')",
+        );
+        ejecutar(&resolvedor, "REPAIR TABLE personas");
+        assert_eq!(
+            fs::read_to_string(&tabla).unwrap(),
+            format!("{encabezado}Ana;30;Rosario\n")
+        );
+
+        fs::remove_dir_all(&directorio).unwrap();
+    }
+}
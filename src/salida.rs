@@ -0,0 +1,974 @@
+use crate::archivo::DELIMITADOR;
+use crate::errores;
+use crate::expresion::Valor;
+use crate::json;
+use crate::terminal;
+use std::io::Write;
+
+/// Formatos en los que se pueden mostrar los resultados de una consulta.
+///
+/// - `Csv`: Una línea CSV con los nombres de las columnas y una por registro, el formato por
+///   defecto.
+/// - `Tabla`: Una tabla alineada con bordes, pensada para uso interactivo.
+/// - `Markdown`: Una tabla de GitHub Flavored Markdown, para pegar en informes e issues.
+/// - `Html`: Un elemento `<table>` de HTML, para incluir en páginas de informes.
+/// - `Xml`: Un documento XML con un elemento `<row>` por registro.
+/// - `Yaml`: Una lista YAML con un mapa por registro.
+/// - `Json`: Una lista JSON con un objeto por registro.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum FormatoSalida {
+    #[default]
+    Csv,
+    Tabla,
+    Markdown,
+    Html,
+    Xml,
+    Yaml,
+    Json,
+}
+
+impl FormatoSalida {
+    /// Devuelve el formato con el nombre indicado por línea de comandos.
+    ///
+    /// # Parámetros
+    /// - `nombre`: El nombre del formato (`csv`, `tabla`, `markdown`, `html`, `xml`, `yaml` o
+    ///   `json`), sin distinguir mayúsculas.
+    ///
+    /// # Retorno
+    /// El `FormatoSalida`, o `None` si el nombre no corresponde a ningún formato.
+    pub fn desde_nombre(nombre: &str) -> Option<FormatoSalida> {
+        match nombre.to_lowercase().as_str() {
+            "csv" => Some(FormatoSalida::Csv),
+            "tabla" | "table" => Some(FormatoSalida::Tabla),
+            "markdown" | "md" => Some(FormatoSalida::Markdown),
+            "html" => Some(FormatoSalida::Html),
+            "xml" => Some(FormatoSalida::Xml),
+            "yaml" | "yml" => Some(FormatoSalida::Yaml),
+            "json" => Some(FormatoSalida::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Cuándo se encierran entre comillas los campos del formato `csv`.
+///
+/// - `Necesario`: Solo los campos que contienen el delimitador, la comilla o un salto de línea,
+///   y los textos vacíos o iguales al texto de `NULL`, para distinguirlos de `NULL`. Es la
+///   política por defecto.
+/// - `Siempre`: Todos los campos, salvo los `NULL` sin texto para mostrarlos.
+/// - `Nunca`: Ningún campo, ni se escapan las comillas; la salida puede no ser CSV válido si
+///   los valores contienen el delimitador o saltos de línea.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PoliticaComillas {
+    #[default]
+    Necesario,
+    Siempre,
+    Nunca,
+}
+
+impl PoliticaComillas {
+    /// Devuelve la política con el nombre indicado por línea de comandos.
+    ///
+    /// # Parámetros
+    /// - `nombre`: `necesario` (o `as-needed`), `siempre` (o `always`) o `nunca` (o `never`),
+    ///   sin distinguir mayúsculas.
+    ///
+    /// # Retorno
+    /// La `PoliticaComillas`, o `None` si el nombre no corresponde a ninguna política.
+    pub fn desde_nombre(nombre: &str) -> Option<PoliticaComillas> {
+        match nombre.to_lowercase().as_str() {
+            "necesario" | "as-needed" => Some(PoliticaComillas::Necesario),
+            "siempre" | "always" => Some(PoliticaComillas::Siempre),
+            "nunca" | "never" => Some(PoliticaComillas::Nunca),
+            _ => None,
+        }
+    }
+}
+
+/// Opciones que indican cómo mostrar los resultados de una consulta.
+///
+/// # Campos
+///
+/// - `formato`: El `FormatoSalida` de los resultados.
+/// - `nulo`: El texto con el que se muestran los valores `NULL`. Si es `None`, se muestran
+///   vacíos.
+/// - `resumen`: Si al terminar cada sentencia se muestra en la salida de errores la cantidad
+///   de filas o de registros agregados y el tiempo que tardó.
+/// - `paralelo`: Si las consultas de selección consecutivas de un lote se ejecutan al mismo
+///   tiempo, marcando cada línea de sus resultados con el número de la sentencia (ver
+///   `transaccion::ejecutar_sentencias`).
+/// - `color`: Si el encabezado de los formatos para leer en la terminal (`tabla` y
+///   `markdown`) se resalta con colores (ver `terminal::usar_color`).
+/// - `sin_encabezado`: Si se omite la fila con los nombres de las columnas en los formatos
+///   `csv`, `tabla`, `markdown` y `html`, para concatenar resultados o pasarlos a otras
+///   herramientas. Los formatos `xml`, `yaml` y `json` la necesitan para nombrar cada valor.
+/// - `comillas`: La `PoliticaComillas` de los campos del formato `csv`.
+/// - `comilla`: El carácter con el que se encierran los campos del formato `csv`. Si es `None`,
+///   se usa la comilla doble.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpcionesSalida {
+    pub formato: FormatoSalida,
+    pub nulo: Option<String>,
+    pub resumen: bool,
+    pub paralelo: bool,
+    pub color: bool,
+    pub sin_encabezado: bool,
+    pub comillas: PoliticaComillas,
+    pub comilla: Option<char>,
+}
+
+impl OpcionesSalida {
+    /// Crea el escritor de resultados correspondiente al formato.
+    ///
+    /// # Parámetros
+    /// - `destino`: Donde se escriben los resultados.
+    ///
+    /// # Retorno
+    /// El escritor de resultados.
+    pub fn crear_escritor<'a, W: Write + 'a>(
+        &self,
+        destino: W,
+    ) -> Box<dyn EscritorResultados + 'a> {
+        let nulo = self.nulo.clone();
+        match self.formato {
+            FormatoSalida::Csv => Box::new(EscritorCsv {
+                destino,
+                nulo,
+                encabezado: !self.sin_encabezado,
+                comillas: self.comillas,
+                comilla: self.comilla.unwrap_or('"'),
+            }),
+            FormatoSalida::Tabla => Box::new(EscritorTabla {
+                color: self.color,
+                encabezado: !self.sin_encabezado,
+                ..EscritorTabla::new(destino, nulo)
+            }),
+            FormatoSalida::Markdown => Box::new(EscritorMarkdown {
+                destino,
+                nulo,
+                color: self.color,
+                encabezado: !self.sin_encabezado,
+            }),
+            FormatoSalida::Html => Box::new(EscritorHtml {
+                encabezado: !self.sin_encabezado,
+                ..EscritorHtml::new(destino, nulo)
+            }),
+            FormatoSalida::Xml => Box::new(EscritorXml::new(destino, nulo)),
+            FormatoSalida::Yaml => Box::new(EscritorYaml::new(destino, nulo)),
+            FormatoSalida::Json => Box::new(EscritorJson::new(destino, nulo)),
+        }
+    }
+}
+
+/// Recibe los resultados de una consulta y los escribe en un formato determinado.
+///
+/// Se llama a `escribir_encabezado` una vez, luego a `escribir_fila` por cada registro del
+/// resultado y finalmente a `finalizar`.
+pub trait EscritorResultados {
+    /// Recibe los nombres de las columnas del resultado.
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores>;
+
+    /// Recibe los valores de un registro del resultado.
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores>;
+
+    /// Termina de escribir los resultados pendientes.
+    fn finalizar(&mut self) -> Result<(), errores::Errores>;
+}
+
+/// Escribe cada registro como una línea CSV separada por comas, precedidas por la línea con
+/// los nombres de las columnas salvo que se omita el encabezado.
+///
+/// Los campos se encierran entre comillas según la `PoliticaComillas`, y las comillas de los
+/// valores se escapan duplicándolas.
+pub struct EscritorCsv<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    encabezado: bool,
+    comillas: PoliticaComillas,
+    comilla: char,
+}
+
+impl<W: Write> EscritorCsv<W> {
+    fn campo(&self, valor: &Valor) -> String {
+        let (texto, es_nulo) = match (valor.a_campo(), &self.nulo) {
+            (Some(texto), _) => (texto, false),
+            (None, Some(nulo)) => (nulo.to_string(), true),
+            (None, None) => return String::new(),
+        };
+        let citar = match self.comillas {
+            PoliticaComillas::Siempre => true,
+            PoliticaComillas::Nunca => false,
+            PoliticaComillas::Necesario => {
+                (!es_nulo && (texto.is_empty() || self.nulo.as_ref() == Some(&texto)))
+                    || texto
+                        .chars()
+                        .any(|c| c == DELIMITADOR || c == self.comilla || c == '\n' || c == '\r')
+            }
+        };
+        if !citar {
+            return texto;
+        }
+        let doble: String = [self.comilla, self.comilla].iter().collect();
+        let escapado = texto.replace(self.comilla, &doble);
+        format!("{}{}{}", self.comilla, escapado, self.comilla)
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorCsv<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        if !self.encabezado {
+            return Ok(());
+        }
+        let nombres: Vec<Valor> = columnas.iter().cloned().map(Valor::Texto).collect();
+        self.escribir_fila(&nombres)
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        let campos: Vec<String> = valores.iter().map(|valor| self.campo(valor)).collect();
+        writeln!(self.destino, "{}", campos.join(&DELIMITADOR.to_string()))
+            .map_err(errores::Errores::Io)
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        self.destino.flush().map_err(errores::Errores::Io)
+    }
+}
+
+/// Escribe los resultados como una tabla con columnas alineadas y bordes, al estilo de `psql`.
+///
+/// Como el ancho de cada columna depende de todos los valores, las filas se acumulan y la
+/// tabla se escribe completa al finalizar. Los números se alinean a la derecha.
+pub struct EscritorTabla<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    color: bool,
+    encabezado: bool,
+    columnas: Vec<String>,
+    filas: Vec<Vec<(String, bool)>>,
+}
+
+impl<W: Write> EscritorTabla<W> {
+    pub fn new(destino: W, nulo: Option<String>) -> EscritorTabla<W> {
+        EscritorTabla {
+            destino,
+            nulo,
+            color: false,
+            encabezado: true,
+            columnas: Vec::new(),
+            filas: Vec::new(),
+        }
+    }
+
+    fn separador(anchos: &[usize]) -> String {
+        let segmentos: Vec<String> = anchos.iter().map(|ancho| "-".repeat(ancho + 2)).collect();
+        format!("+{}+", segmentos.join("+"))
+    }
+
+    fn linea(celdas: &[(String, bool)], anchos: &[usize]) -> String {
+        let celdas: Vec<String> = celdas
+            .iter()
+            .zip(anchos)
+            .map(|((texto, a_derecha), ancho)| {
+                let relleno = " ".repeat(ancho - texto.chars().count());
+                if *a_derecha {
+                    format!(" {}{} ", relleno, texto)
+                } else {
+                    format!(" {}{} ", texto, relleno)
+                }
+            })
+            .collect();
+        format!("|{}|", celdas.join("|"))
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorTabla<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.columnas = columnas.to_vec();
+        Ok(())
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        self.filas.push(
+            valores
+                .iter()
+                .map(|valor| {
+                    (
+                        texto_de_valor(valor, self.nulo.as_deref()),
+                        matches!(valor, Valor::Entero(_) | Valor::Decimal(_)),
+                    )
+                })
+                .collect(),
+        );
+        Ok(())
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        if self.columnas.is_empty() {
+            return Ok(());
+        }
+        let mut anchos: Vec<usize> = self
+            .columnas
+            .iter()
+            .map(|c| {
+                if self.encabezado {
+                    c.chars().count()
+                } else {
+                    0
+                }
+            })
+            .collect();
+        for fila in &self.filas {
+            for (ancho, (texto, _)) in anchos.iter_mut().zip(fila) {
+                *ancho = (*ancho).max(texto.chars().count());
+            }
+        }
+
+        let separador = Self::separador(&anchos);
+        let mut texto = format!("{}\n", separador);
+        if self.encabezado {
+            let encabezado: Vec<(String, bool)> = self
+                .columnas
+                .iter()
+                .map(|columna| (columna.to_string(), false))
+                .collect();
+            let mut encabezado = Self::linea(&encabezado, &anchos);
+            if self.color {
+                encabezado = terminal::resaltar(&encabezado, terminal::ENCABEZADO);
+            }
+            texto.push_str(&format!("{}\n{}\n", encabezado, separador));
+        }
+        for fila in &self.filas {
+            texto.push_str(&Self::linea(fila, &anchos));
+            texto.push('\n');
+        }
+        texto.push_str(&separador);
+        texto.push('\n');
+
+        self.destino
+            .write_all(texto.as_bytes())
+            .and_then(|_| self.destino.flush())
+            .map_err(errores::Errores::Io)
+    }
+}
+
+/// Escribe los resultados como una tabla de GitHub Flavored Markdown.
+///
+/// Las barras verticales de los valores se escapan y los saltos de línea se reemplazan por
+/// `<br>` para que cada registro ocupe una única fila de la tabla.
+pub struct EscritorMarkdown<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    color: bool,
+    encabezado: bool,
+}
+
+impl<W: Write> EscritorMarkdown<W> {
+    fn linea(celdas: &[String]) -> String {
+        let celdas: Vec<String> = celdas
+            .iter()
+            .map(|celda| {
+                celda
+                    .replace('|', "\\|")
+                    .replace("\r\n", "<br>")
+                    .replace('\n', "<br>")
+            })
+            .collect();
+        format!("| {} |", celdas.join(" | "))
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorMarkdown<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        if !self.encabezado {
+            return Ok(());
+        }
+        let separador = vec!["---".to_string(); columnas.len()];
+        let mut encabezado = Self::linea(columnas);
+        if self.color {
+            encabezado = terminal::resaltar(&encabezado, terminal::ENCABEZADO);
+        }
+        writeln!(self.destino, "{}\n|{}|", encabezado, separador.join("|"))
+            .map_err(errores::Errores::Io)
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        let celdas: Vec<String> = valores
+            .iter()
+            .map(|valor| texto_de_valor(valor, self.nulo.as_deref()))
+            .collect();
+        writeln!(self.destino, "{}", Self::linea(&celdas)).map_err(errores::Errores::Io)
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        self.destino.flush().map_err(errores::Errores::Io)
+    }
+}
+
+/// Escribe los resultados como un elemento `<table>` de HTML, con el encabezado en `<thead>` y
+/// un `<tr>` por registro en `<tbody>`. Los valores se escapan para que no se interpreten como
+/// marcado.
+pub struct EscritorHtml<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    encabezado: bool,
+    abierta: bool,
+}
+
+impl<W: Write> EscritorHtml<W> {
+    pub fn new(destino: W, nulo: Option<String>) -> EscritorHtml<W> {
+        EscritorHtml {
+            destino,
+            nulo,
+            encabezado: true,
+            abierta: false,
+        }
+    }
+
+    fn fila(etiqueta: &str, celdas: &[String]) -> String {
+        let celdas: String = celdas
+            .iter()
+            .map(|celda| format!("<{0}>{1}</{0}>", etiqueta, escapar_marcado(celda)))
+            .collect();
+        format!("<tr>{}</tr>", celdas)
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorHtml<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.abierta = true;
+        if !self.encabezado {
+            return writeln!(self.destino, "<table>\n<tbody>").map_err(errores::Errores::Io);
+        }
+        writeln!(
+            self.destino,
+            "<table>\n<thead>\n{}\n</thead>\n<tbody>",
+            Self::fila("th", columnas)
+        )
+        .map_err(errores::Errores::Io)
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        let celdas: Vec<String> = valores
+            .iter()
+            .map(|valor| texto_de_valor(valor, self.nulo.as_deref()))
+            .collect();
+        writeln!(self.destino, "{}", Self::fila("td", &celdas)).map_err(errores::Errores::Io)
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        if self.abierta {
+            writeln!(self.destino, "</tbody>\n</table>").map_err(errores::Errores::Io)?;
+        }
+        self.destino.flush().map_err(errores::Errores::Io)
+    }
+}
+
+/// Escribe los resultados como un documento XML, con un elemento `<row>` por registro y un
+/// elemento `<col name="...">` por columna. Los valores `NULL` se marcan con `null="true"`
+/// salvo que se haya indicado un texto para mostrarlos.
+pub struct EscritorXml<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    columnas: Vec<String>,
+    abierto: bool,
+}
+
+impl<W: Write> EscritorXml<W> {
+    pub fn new(destino: W, nulo: Option<String>) -> EscritorXml<W> {
+        EscritorXml {
+            destino,
+            nulo,
+            columnas: Vec::new(),
+            abierto: false,
+        }
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorXml<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.columnas = columnas.to_vec();
+        self.abierto = true;
+        writeln!(
+            self.destino,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rows>"
+        )
+        .map_err(errores::Errores::Io)
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        let mut fila = String::from("  <row>");
+        for (columna, valor) in self.columnas.iter().zip(valores) {
+            let nombre = escapar_marcado(columna);
+            match (valor, &self.nulo) {
+                (Valor::Nulo, None) => {
+                    fila.push_str(&format!("<col name=\"{}\" null=\"true\"/>", nombre))
+                }
+                _ => fila.push_str(&format!(
+                    "<col name=\"{}\">{}</col>",
+                    nombre,
+                    escapar_marcado(&texto_de_valor(valor, self.nulo.as_deref()))
+                )),
+            }
+        }
+        fila.push_str("</row>");
+        writeln!(self.destino, "{}", fila).map_err(errores::Errores::Io)
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        if self.abierto {
+            writeln!(self.destino, "</rows>").map_err(errores::Errores::Io)?;
+        }
+        self.destino.flush().map_err(errores::Errores::Io)
+    }
+}
+
+/// Escribe los resultados como una lista YAML con un mapa por registro. Los textos se escriben
+/// siempre entre comillas dobles, los números y booleanos sin ellas y `NULL` como `null`,
+/// salvo que se haya indicado un texto para mostrarlo.
+pub struct EscritorYaml<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    columnas: Vec<String>,
+    filas: usize,
+}
+
+impl<W: Write> EscritorYaml<W> {
+    pub fn new(destino: W, nulo: Option<String>) -> EscritorYaml<W> {
+        EscritorYaml {
+            destino,
+            nulo,
+            columnas: Vec::new(),
+            filas: 0,
+        }
+    }
+
+    /// Escribe un texto como escalar YAML entre comillas dobles.
+    fn citar(texto: &str) -> String {
+        let mut citado = String::from("\"");
+        for caracter in texto.chars() {
+            match caracter {
+                '"' => citado.push_str("\\\""),
+                '\\' => citado.push_str("\\\\"),
+                '\n' => citado.push_str("\\n"),
+                '\r' => citado.push_str("\\r"),
+                '\t' => citado.push_str("\\t"),
+                caracter => citado.push(caracter),
+            }
+        }
+        citado.push('"');
+        citado
+    }
+
+    /// Escribe el nombre de una columna como clave, citándolo solo si no es un identificador
+    /// simple.
+    fn clave(columna: &str) -> String {
+        let simple = !columna.is_empty()
+            && columna
+                .chars()
+                .all(|caracter| caracter.is_alphanumeric() || caracter == '_');
+        if simple {
+            columna.to_string()
+        } else {
+            Self::citar(columna)
+        }
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorYaml<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.columnas = columnas.to_vec();
+        Ok(())
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        let mut fila = String::new();
+        for (indice, (columna, valor)) in self.columnas.iter().zip(valores).enumerate() {
+            let escalar = match (valor, &self.nulo) {
+                (Valor::Nulo, None) => "null".to_string(),
+                (Valor::Nulo, Some(nulo)) => Self::citar(nulo),
+                (Valor::Entero(_), _) | (Valor::Decimal(_), _) | (Valor::Booleano(_), _) => {
+                    valor.a_texto()
+                }
+                (Valor::Texto(texto), _) => Self::citar(texto),
+            };
+            let prefijo = if indice == 0 { "- " } else { "  " };
+            fila.push_str(&format!(
+                "{}{}: {}\n",
+                prefijo,
+                Self::clave(columna),
+                escalar
+            ));
+        }
+        self.filas += 1;
+        write!(self.destino, "{}", fila).map_err(errores::Errores::Io)
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        if !self.columnas.is_empty() && self.filas == 0 {
+            writeln!(self.destino, "[]").map_err(errores::Errores::Io)?;
+        }
+        self.destino.flush().map_err(errores::Errores::Io)
+    }
+}
+
+/// Escribe los resultados como una lista JSON con un objeto por registro, uno por línea, como
+/// las tablas JSON (ver `json`). Los textos se escriben como cadenas, los números y booleanos
+/// sin comillas y `NULL` como `null`, salvo que se haya indicado un texto para mostrarlo; los
+/// decimales que no son finitos, que JSON no admite, se escriben como `null`.
+pub struct EscritorJson<W: Write> {
+    destino: W,
+    nulo: Option<String>,
+    columnas: Vec<String>,
+    filas: usize,
+    abierto: bool,
+}
+
+impl<W: Write> EscritorJson<W> {
+    pub fn new(destino: W, nulo: Option<String>) -> EscritorJson<W> {
+        EscritorJson {
+            destino,
+            nulo,
+            columnas: Vec::new(),
+            filas: 0,
+            abierto: false,
+        }
+    }
+}
+
+impl<W: Write> EscritorResultados for EscritorJson<W> {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.columnas = columnas.to_vec();
+        self.abierto = true;
+        Ok(())
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        let campos: Vec<String> = self
+            .columnas
+            .iter()
+            .zip(valores)
+            .map(|(columna, valor)| {
+                let valor = match (valor, &self.nulo) {
+                    (Valor::Nulo, Some(nulo)) => json::citar(nulo),
+                    (Valor::Decimal(numero), _) if !numero.is_finite() => "null".to_string(),
+                    (Valor::Nulo, None) => "null".to_string(),
+                    (Valor::Entero(_), _) | (Valor::Decimal(_), _) | (Valor::Booleano(_), _) => {
+                        valor.a_texto()
+                    }
+                    (Valor::Texto(texto), _) => json::citar(texto),
+                };
+                format!("{}: {}", json::citar(columna), valor)
+            })
+            .collect();
+        let separador = if self.filas == 0 { "[\n" } else { ",\n" };
+        self.filas += 1;
+        write!(self.destino, "{}  {{{}}}", separador, campos.join(", "))
+            .map_err(errores::Errores::Io)
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        match (self.abierto, self.filas) {
+            (false, _) => {}
+            (true, 0) => writeln!(self.destino, "[]").map_err(errores::Errores::Io)?,
+            (true, _) => writeln!(self.destino, "\n]").map_err(errores::Errores::Io)?,
+        }
+        self.destino.flush().map_err(errores::Errores::Io)
+    }
+}
+
+/// Reemplaza los caracteres con significado especial en HTML y XML por sus entidades.
+fn escapar_marcado(texto: &str) -> String {
+    let mut escapado = String::with_capacity(texto.len());
+    for caracter in texto.chars() {
+        match caracter {
+            '&' => escapado.push_str("&amp;"),
+            '<' => escapado.push_str("&lt;"),
+            '>' => escapado.push_str("&gt;"),
+            '"' => escapado.push_str("&quot;"),
+            '\'' => escapado.push_str("&#39;"),
+            caracter => escapado.push(caracter),
+        }
+    }
+    escapado
+}
+
+/// Devuelve el texto con el que se muestra un valor, usando el texto indicado para `NULL` o
+/// vacío si no se indicó ninguno.
+fn texto_de_valor(valor: &Valor, nulo: Option<&str>) -> String {
+    match (valor, nulo) {
+        (Valor::Nulo, Some(nulo)) => nulo.to_string(),
+        _ => valor.a_texto(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escribir(formato: FormatoSalida) -> String {
+        escribir_con_opciones(OpcionesSalida {
+            formato,
+            ..OpcionesSalida::default()
+        })
+    }
+
+    fn escribir_con_opciones(opciones: OpcionesSalida) -> String {
+        let mut destino = Vec::new();
+        {
+            let mut escritor = opciones.crear_escritor(&mut destino);
+            escritor
+                .escribir_encabezado(&["nombre".to_string(), "edad".to_string()])
+                .unwrap();
+            escritor
+                .escribir_fila(&[Valor::Texto("Luis, hijo".to_string()), Valor::Entero(34)])
+                .unwrap();
+            escritor
+                .escribir_fila(&[Valor::Texto("Ana".to_string()), Valor::Nulo])
+                .unwrap();
+            escritor.finalizar().unwrap();
+        }
+        String::from_utf8(destino).unwrap()
+    }
+
+    #[test]
+    fn test_escritor_csv() {
+        assert_eq!(
+            escribir(FormatoSalida::Csv),
+            "nombre,edad\n\"Luis, hijo\",34\nAna,\n"
+        );
+    }
+
+    #[test]
+    fn test_escritor_tabla() {
+        let esperado = "\
++------------+------+
+| nombre     | edad |
++------------+------+
+| Luis, hijo |   34 |
+| Ana        |      |
++------------+------+
+";
+        assert_eq!(escribir(FormatoSalida::Tabla), esperado);
+    }
+
+    #[test]
+    fn test_escritor_markdown() {
+        let mut destino = Vec::new();
+        {
+            let opciones = OpcionesSalida {
+                formato: FormatoSalida::Markdown,
+                ..OpcionesSalida::default()
+            };
+            let mut escritor = opciones.crear_escritor(&mut destino);
+            escritor
+                .escribir_encabezado(&["nombre".to_string(), "nota".to_string()])
+                .unwrap();
+            escritor
+                .escribir_fila(&[Valor::Texto("a|b".to_string()), Valor::Entero(7)])
+                .unwrap();
+            escritor
+                .escribir_fila(&[Valor::Texto("dos\nlíneas".to_string()), Valor::Nulo])
+                .unwrap();
+            escritor.finalizar().unwrap();
+        }
+        let esperado = "\
+| nombre | nota |
+|---|---|
+| a\\|b | 7 |
+| dos<br>líneas |  |
+";
+        assert_eq!(String::from_utf8(destino).unwrap(), esperado);
+    }
+
+    #[test]
+    fn test_escritor_html() {
+        let esperado = "\
+<table>
+<thead>
+<tr><th>nombre</th><th>edad</th></tr>
+</thead>
+<tbody>
+<tr><td>Luis, hijo</td><td>34</td></tr>
+<tr><td>Ana</td><td></td></tr>
+</tbody>
+</table>
+";
+        assert_eq!(escribir(FormatoSalida::Html), esperado);
+        assert_eq!(
+            escapar_marcado("<b>\"R&D\"</b>"),
+            "&lt;b&gt;&quot;R&amp;D&quot;&lt;/b&gt;"
+        );
+    }
+
+    #[test]
+    fn test_escritor_xml() {
+        let esperado = "\
+<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<rows>
+  <row><col name=\"nombre\">Luis, hijo</col><col name=\"edad\">34</col></row>
+  <row><col name=\"nombre\">Ana</col><col name=\"edad\" null=\"true\"/></row>
+</rows>
+";
+        assert_eq!(escribir(FormatoSalida::Xml), esperado);
+    }
+
+    #[test]
+    fn test_escritor_yaml() {
+        let esperado = "\
+- nombre: \"Luis, hijo\"
+  edad: 34
+- nombre: \"Ana\"
+  edad: null
+";
+        assert_eq!(escribir(FormatoSalida::Yaml), esperado);
+        assert_eq!(
+            EscritorYaml::<Vec<u8>>::citar("dijo \"hola\"\n"),
+            "\"dijo \\\"hola\\\"\\n\""
+        );
+        assert_eq!(EscritorYaml::<Vec<u8>>::clave("count(*)"), "\"count(*)\"");
+    }
+
+    #[test]
+    fn test_escritor_json() {
+        let esperado = "\
+[
+  {\"nombre\": \"Luis, hijo\", \"edad\": 34},
+  {\"nombre\": \"Ana\", \"edad\": null}
+]
+";
+        assert_eq!(escribir(FormatoSalida::Json), esperado);
+
+        let mut destino = Vec::new();
+        {
+            let mut escritor = EscritorJson::new(&mut destino, None);
+            escritor.escribir_encabezado(&["x".to_string()]).unwrap();
+            escritor.finalizar().unwrap();
+        }
+        assert_eq!(String::from_utf8(destino).unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn test_encabezado_con_colores() {
+        let tabla = escribir_con_opciones(OpcionesSalida {
+            formato: FormatoSalida::Tabla,
+            color: true,
+            ..OpcionesSalida::default()
+        });
+        assert!(tabla.contains("\n\x1b[1;36m| nombre     | edad |\x1b[0m\n"));
+        assert!(tabla.contains("\n| Ana        |      |\n"));
+
+        let markdown = escribir_con_opciones(OpcionesSalida {
+            formato: FormatoSalida::Markdown,
+            color: true,
+            ..OpcionesSalida::default()
+        });
+        assert!(markdown.starts_with("\x1b[1;36m| nombre | edad |\x1b[0m\n|---|---|\n"));
+        assert!(!escribir(FormatoSalida::Tabla).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_sin_encabezado() {
+        let opciones = |formato| OpcionesSalida {
+            formato,
+            sin_encabezado: true,
+            ..OpcionesSalida::default()
+        };
+        let esperado = "\
++------------+----+
+| Luis, hijo | 34 |
+| Ana        |    |
++------------+----+
+";
+        assert_eq!(
+            escribir_con_opciones(opciones(FormatoSalida::Tabla)),
+            esperado
+        );
+        assert_eq!(
+            escribir_con_opciones(opciones(FormatoSalida::Markdown)),
+            "| Luis, hijo | 34 |\n| Ana |  |\n"
+        );
+        assert!(!escribir_con_opciones(opciones(FormatoSalida::Html)).contains("<thead>"));
+        assert_eq!(
+            escribir_con_opciones(opciones(FormatoSalida::Csv)),
+            "\"Luis, hijo\",34\nAna,\n"
+        );
+    }
+
+    #[test]
+    fn test_politica_de_comillas() {
+        let escribir_csv = |comillas, comilla| {
+            let mut destino = Vec::new();
+            {
+                let opciones = OpcionesSalida {
+                    comillas,
+                    comilla,
+                    ..OpcionesSalida::default()
+                };
+                let mut escritor = opciones.crear_escritor(&mut destino);
+                escritor
+                    .escribir_fila(&[
+                        Valor::Texto("Luis, hijo".to_string()),
+                        Valor::Texto("dijo 'hola'".to_string()),
+                        Valor::Texto(String::new()),
+                        Valor::Entero(34),
+                        Valor::Nulo,
+                    ])
+                    .unwrap();
+                escritor.finalizar().unwrap();
+            }
+            String::from_utf8(destino).unwrap()
+        };
+        assert_eq!(
+            escribir_csv(PoliticaComillas::Necesario, None),
+            "\"Luis, hijo\",dijo 'hola',\"\",34,\n"
+        );
+        assert_eq!(
+            escribir_csv(PoliticaComillas::Necesario, Some('\'')),
+            "'Luis, hijo','dijo ''hola''','',34,\n"
+        );
+        assert_eq!(
+            escribir_csv(PoliticaComillas::Siempre, None),
+            "\"Luis, hijo\",\"dijo 'hola'\",\"\",\"34\",\n"
+        );
+        assert_eq!(
+            escribir_csv(PoliticaComillas::Nunca, None),
+            "Luis, hijo,dijo 'hola',,34,\n"
+        );
+        assert_eq!(
+            PoliticaComillas::desde_nombre("AS-NEEDED"),
+            Some(PoliticaComillas::Necesario)
+        );
+        assert_eq!(PoliticaComillas::desde_nombre("a veces"), None);
+    }
+
+    #[test]
+    fn test_texto_para_nulo() {
+        let csv = escribir_con_opciones(OpcionesSalida {
+            formato: FormatoSalida::Csv,
+            nulo: Some("NULL".to_string()),
+            ..OpcionesSalida::default()
+        });
+        assert_eq!(csv, "nombre,edad\n\"Luis, hijo\",34\nAna,NULL\n");
+
+        let tabla = escribir_con_opciones(OpcionesSalida {
+            formato: FormatoSalida::Tabla,
+            nulo: Some("NULL".to_string()),
+            ..OpcionesSalida::default()
+        });
+        assert!(tabla.contains("| Ana        | NULL |"));
+    }
+
+    #[test]
+    fn test_formato_desde_nombre() {
+        assert_eq!(
+            FormatoSalida::desde_nombre("TABLA"),
+            Some(FormatoSalida::Tabla)
+        );
+        assert_eq!(FormatoSalida::desde_nombre("csv"), Some(FormatoSalida::Csv));
+        assert_eq!(
+            FormatoSalida::desde_nombre("JSON"),
+            Some(FormatoSalida::Json)
+        );
+        assert_eq!(FormatoSalida::desde_nombre("xls"), None);
+    }
+}
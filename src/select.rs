@@ -1,12 +1,327 @@
-use crate::archivo::{self, leer_archivo, procesar_ruta};
+use crate::aleatorio::GeneradorAleatorio;
+use crate::almacenamiento::Almacenamiento;
+use crate::archivo::{
+    dividir_registro, dividir_registro_en, leer_encabezado, leer_registro_en, OpcionesArchivo,
+    VerificadorCampos, RUTA_ENTRADA,
+};
 use crate::consulta::{
-    mapear_campos, obtener_campos_consulta_orden_por_defecto, MetodosConsulta, Parseables,
-    Verificaciones,
+    columna_invalida, mapear_campos, obtener_campos_consulta_orden_por_defecto, MetodosConsulta,
+    Resumen, Verificaciones,
 };
 use crate::errores;
-use archivo::parsear_linea_archivo;
-use std::{collections::HashMap, io::BufRead};
-//TODO: implementar restricciones, ordenamiento y mejorar el parseo
+use crate::estadisticas::Estadisticas;
+use crate::expresion::{Expresion, ExpresionCompilada, FuncionVentana, Valor, Ventana};
+use crate::lexico;
+use crate::planificador::{self, Acceso, PlanConsulta};
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorResultados;
+use crate::sintaxis::{
+    self, Agrupamiento, CriterioOrden, Muestra, Proyeccion, Sentencia, SentenciaSelect,
+};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+use std::thread;
+
+/// Nombre reservado de la tabla que se lee de la entrada estándar, igual que `FROM -`.
+const TABLA_ENTRADA: &str = "stdin";
+/// Cantidad de registros que se leen antes de evaluarlos en paralelo.
+const TAMANIO_LOTE: usize = 16 * 1024;
+/// Cantidad mínima de registros por hilo para que valga la pena repartir un lote.
+const REGISTROS_MINIMOS_POR_HILO: usize = 1024;
+
+/// Expresiones de una consulta compiladas antes de recorrer la tabla, para evaluarlas sobre
+/// cada registro sin resolver columnas por nombre.
+///
+/// Las proyecciones y los criterios de orden solo se compilan en las consultas sin
+/// agrupamiento, ya que en las agrupadas se evalúan una vez por grupo.
+struct PlanEvaluacion {
+    restricciones: Option<ExpresionCompilada>,
+    proyecciones: Vec<ExpresionCompilada>,
+    claves_orden: Vec<ExpresionCompilada>,
+    claves_agrupamiento: Vec<ExpresionCompilada>,
+}
+
+/// Fila del resultado de una consulta junto con los valores de sus criterios de orden, seguidos
+/// de los de las funciones de ventana (ver `ConsultaSelect::claves`).
+type FilaResultado = (Vec<Valor>, Vec<Valor>);
+
+/// Lugar de cada fila del resultado donde se escribe el valor de una función de ventana.
+///
+/// - `Proyeccion`: La posición de la proyección en la fila.
+/// - `Criterio`: La posición del criterio del `ORDER BY` de la consulta en las claves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DestinoVentana {
+    Proyeccion(usize),
+    Criterio(usize),
+}
+
+/// Función de ventana de una consulta, que se calcula sobre todas las filas del resultado
+/// antes de ordenarlas.
+///
+/// - `ventana`: La función de ventana.
+/// - `inicio_claves`: La posición, en las claves de cada fila, del valor de la primera
+///   expresión de la ventana (ver `Ventana::expresiones`).
+/// - `destino`: Dónde se escribe el valor calculado.
+struct CalculoVentana<'a> {
+    ventana: &'a Ventana,
+    inicio_claves: usize,
+    destino: DestinoVentana,
+}
+
+impl CalculoVentana<'_> {
+    /// Calcula la función de ventana para cada fila: las filas se agrupan por partición y,
+    /// dentro de cada una, se ordenan según el `ORDER BY` de la ventana, conservando el orden
+    /// de llegada entre las empatadas; cada fila recibe su número o su posición en la partición.
+    fn calcular(&self, filas: &mut [FilaResultado]) {
+        let inicio_orden = self.inicio_claves + self.ventana.particion.len();
+        let fin_claves = inicio_orden + self.ventana.orden.len();
+        let comparar_particion = |a: usize, b: usize| {
+            let particion = |indice: usize| &filas[indice].0[self.inicio_claves..inicio_orden];
+            particion(a)
+                .iter()
+                .zip(particion(b))
+                .map(|(a, b)| a.comparar_para_ordenar(b))
+                .find(|orden| *orden != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        };
+        let comparar_orden = |a: usize, b: usize| {
+            comparar_claves(
+                &filas[a].0[inicio_orden..fin_claves],
+                &filas[b].0[inicio_orden..fin_claves],
+                &self.ventana.orden,
+            )
+        };
+        let mut indices: Vec<usize> = (0..filas.len()).collect();
+        indices.sort_by(|a, b| comparar_particion(*a, *b).then_with(|| comparar_orden(*a, *b)));
+
+        let mut valores = vec![0; filas.len()];
+        let mut inicio_particion = 0;
+        let mut posicion = 0;
+        let mut posicion_densa = 0;
+        for (numero, indice) in indices.iter().enumerate() {
+            let anterior = numero.checked_sub(1).map(|anterior| indices[anterior]);
+            if anterior.is_none_or(|anterior| comparar_particion(anterior, *indice).is_ne()) {
+                inicio_particion = numero;
+                posicion = 0;
+                posicion_densa = 0;
+            }
+            if posicion == 0
+                || anterior.is_some_and(|anterior| comparar_orden(anterior, *indice).is_ne())
+            {
+                posicion = numero - inicio_particion + 1;
+                posicion_densa += 1;
+            }
+            valores[*indice] = match self.ventana.funcion {
+                FuncionVentana::RowNumber => numero - inicio_particion + 1,
+                FuncionVentana::Rank => posicion,
+                FuncionVentana::DenseRank => posicion_densa,
+            };
+        }
+        for ((claves, fila), valor) in filas.iter_mut().zip(valores) {
+            let valor = Valor::Entero(valor as i128);
+            match self.destino {
+                DestinoVentana::Proyeccion(posicion) => fila[posicion] = valor,
+                DestinoVentana::Criterio(posicion) => claves[posicion] = valor,
+            }
+        }
+    }
+}
+
+/// Compara las claves de dos filas según los criterios de orden, en el sentido de cada uno.
+fn comparar_claves(
+    claves_a: &[Valor],
+    claves_b: &[Valor],
+    criterios: &[CriterioOrden],
+) -> Ordering {
+    for ((a, b), criterio) in claves_a.iter().zip(claves_b).zip(criterios) {
+        let orden = if criterio.natural {
+            a.comparar_natural(b)
+        } else {
+            a.comparar_para_ordenar(b)
+        };
+        let orden = if criterio.descendente {
+            orden.reverse()
+        } else {
+            orden
+        };
+        if orden != Ordering::Equal {
+            return orden;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Recibe las filas del resultado de una consulta y las escribe aplicando las funciones de
+/// ventana, `ORDER BY` y `LIMIT`.
+///
+/// Sin `ORDER BY` ni funciones de ventana, las filas se escriben a medida que llegan hasta
+/// completar el límite; si no, se acumulan y se escriben al finalizar.
+struct Resultado<'a> {
+    escritor: &'a mut dyn EscritorResultados,
+    criterios: &'a [CriterioOrden],
+    ventanas: Vec<CalculoVentana<'a>>,
+    restantes: Option<usize>,
+    filas: Vec<FilaResultado>,
+    escritas: usize,
+}
+
+impl<'a> Resultado<'a> {
+    fn new(
+        escritor: &'a mut dyn EscritorResultados,
+        criterios: &'a [CriterioOrden],
+        ventanas: Vec<CalculoVentana<'a>>,
+        limite: Option<usize>,
+    ) -> Resultado<'a> {
+        Resultado {
+            escritor,
+            criterios,
+            ventanas,
+            restantes: limite,
+            filas: Vec::new(),
+            escritas: 0,
+        }
+    }
+
+    /// Indica si las filas se acumulan hasta finalizar, en lugar de escribirse a medida que
+    /// llegan.
+    fn acumula(&self) -> bool {
+        !self.criterios.is_empty() || !self.ventanas.is_empty()
+    }
+
+    /// Indica si ya se escribieron todas las filas que admite el `LIMIT`, de modo que no hace
+    /// falta seguir leyendo la tabla.
+    fn completo(&self) -> bool {
+        !self.acumula() && self.restantes == Some(0)
+    }
+
+    /// Cantidad de filas que todavía pueden escribirse sin acumular, si hay un límite.
+    fn pendientes(&self) -> Option<usize> {
+        if self.acumula() {
+            None
+        } else {
+            self.restantes
+        }
+    }
+
+    fn agregar(&mut self, (claves, fila): FilaResultado) -> Result<(), errores::Errores> {
+        if self.acumula() {
+            self.filas.push((claves, fila));
+            return Ok(());
+        }
+        if self.restantes == Some(0) {
+            return Ok(());
+        }
+        if let Some(restantes) = &mut self.restantes {
+            *restantes -= 1;
+        }
+        self.escritas += 1;
+        self.escritor.escribir_fila(&fila)
+    }
+
+    /// Calcula las funciones de ventana sobre las filas acumuladas, las ordena según los
+    /// criterios y escribe las que admite el límite. El orden es estable, así que las filas con
+    /// las mismas claves conservan el orden del archivo.
+    ///
+    /// # Retorno
+    /// La cantidad total de filas escritas.
+    fn finalizar(mut self) -> Result<usize, errores::Errores> {
+        for ventana in &self.ventanas {
+            ventana.calcular(&mut self.filas);
+        }
+        let criterios = self.criterios;
+        self.filas
+            .sort_by(|(claves_a, _), (claves_b, _)| comparar_claves(claves_a, claves_b, criterios));
+        let limite = self.restantes.unwrap_or(usize::MAX);
+        for (_, fila) in self.filas.iter().take(limite) {
+            self.escritor.escribir_fila(fila)?;
+            self.escritas += 1;
+        }
+        Ok(self.escritas)
+    }
+}
+
+/// Lote de registros leídos de una tabla que se evalúan juntos.
+///
+/// Las cadenas de los registros se conservan al vaciar el lote y se reutilizan en las lecturas
+/// siguientes, de modo que recorrer la tabla no reserva memoria por cada registro.
+#[derive(Default)]
+struct Lote {
+    registros: Vec<String>,
+    cantidad: usize,
+}
+
+impl Lote {
+    /// Lee el siguiente registro de la tabla al final del lote.
+    ///
+    /// # Retorno
+    /// Retorna `true` si se leyó un registro, `false` si no quedan registros, un error de E/S
+    /// si falla la lectura o el error del `VerificadorCampos`.
+    fn leer(
+        &mut self,
+        lector: &mut dyn BufRead,
+        verificador: &mut VerificadorCampos,
+    ) -> Result<bool, errores::Errores> {
+        if self.cantidad == self.registros.len() {
+            self.registros.push(String::new());
+        }
+        let leido = leer_registro_en(lector, &mut self.registros[self.cantidad])?;
+        if leido {
+            verificador.verificar(&mut self.registros[self.cantidad])?;
+            self.cantidad += 1;
+        }
+        Ok(leido)
+    }
+
+    fn registros(&self) -> &[String] {
+        &self.registros[..self.cantidad]
+    }
+
+    fn len(&self) -> usize {
+        self.cantidad
+    }
+
+    /// Quita del lote el último registro leído.
+    fn descartar_ultimo(&mut self) {
+        self.cantidad -= 1;
+    }
+
+    fn vaciar(&mut self) {
+        self.cantidad = 0;
+    }
+}
+
+/// Decide qué registros de la tabla forman parte de la muestra de `TABLESAMPLE` (ver
+/// `Muestra`). Sin muestra, o con el 100 %, se incluyen todos sin generar números aleatorios.
+struct Muestreo {
+    porcentaje: f64,
+    generador: GeneradorAleatorio,
+}
+
+impl Muestreo {
+    fn new(muestra: Option<&Muestra>) -> Muestreo {
+        let Some(muestra) = muestra else {
+            return Muestreo {
+                porcentaje: 100.0,
+                generador: GeneradorAleatorio::new(0),
+            };
+        };
+        Muestreo {
+            porcentaje: muestra.porcentaje,
+            generador: muestra
+                .semilla
+                .map_or_else(GeneradorAleatorio::desde_entropia, GeneradorAleatorio::new),
+        }
+    }
+
+    /// Indica si el siguiente registro de la tabla forma parte de la muestra.
+    fn incluir(&mut self) -> bool {
+        self.porcentaje >= 100.0 || self.generador.decimal() * 100.0 < self.porcentaje
+    }
+}
 
 /// Representa una consulta SQL de selección.
 ///
@@ -18,191 +333,584 @@ use std::{collections::HashMap, io::BufRead};
 /// # Campos
 ///
 /// - `campos_consulta`: Un vector de cadenas de texto (`Vec<String>`) que especifica
-///   los campos que se desean incluir en los resultados de la consulta.
+///   los campos que se desean incluir en los resultados de la consulta. Cada elemento
+///   puede ser un nombre de columna o una expresión, como `IIF(edad > 30, 'si', 'no')`.
+/// - `proyecciones`: Las expresiones (`Vec<Expresion>`) que se evalúan sobre cada registro
+///   para obtener los valores de `campos_consulta`; vacío mientras no se expanda el `*`.
 /// - `campos_posibles`: Un mapa (`HashMap<String, usize>`) que asocia los nombres de
 ///   los campos de la tabla con sus índices. Este mapa permite la validación de campos
 ///   seleccionados y la referencia a los campos por su índice.
 /// - `tabla`: Una cadena de texto (`String`) que indica el nombre de la tabla en la
 ///   que se realiza la consulta.
-/// - `restricciones`: Un vector de cadenas de texto (`Vec<String>`) que contiene las
-///   restricciones aplicadas a la consulta.
-/// - `ordenamiento`: Un vector de cadenas de texto (`Vec<String>`) que especifica
-///   el criterio de ordenamiento de los resultados. Los valores en este vector pueden
-///   ser nombres de campos seguidos opcionalmente por la palabra clave `ASC` o `DESC`
-///   para indicar el orden ascendente o descendente.
+/// - `muestra`: La cláusula `TABLESAMPLE` (`Option<Muestra>`): los registros que no forman
+///   parte de la muestra se leen, pero no se evalúan.
+/// - `restricciones`: La condición (`Option<Expresion>`) de la cláusula `WHERE` que deben
+///   cumplir los registros para formar parte del resultado.
+/// - `agrupamiento`: La cláusula `GROUP BY` (`Option<Agrupamiento>`), si la consulta la tiene.
+/// - `condicion_grupos`: La condición (`Option<Expresion>`) de la cláusula `HAVING` que deben
+///   cumplir los grupos para formar parte del resultado.
+/// - `criterios_orden`: Los `CriterioOrden` de la cláusula `ORDER BY`.
+/// - `limite`: La cantidad máxima de filas del resultado (`LIMIT`), si la consulta la indica.
+/// - `almacenamiento`: El `Almacenamiento` del que se lee la tabla.
+/// - `opciones`: Las `OpcionesArchivo` con las que se lee el archivo de la tabla.
+/// - `estadisticas`: Las `Estadisticas` de la tabla calculadas con `ANALYZE`, si las tiene,
+///   que se cargan al verificar la consulta y se usan para planificarla.
 #[derive(Debug)]
 pub struct ConsultaSelect {
     pub campos_consulta: Vec<String>,
+    pub proyecciones: Vec<Expresion>,
     pub campos_posibles: HashMap<String, usize>,
     pub tabla: String,
-    pub restricciones: Vec<String>,
-    pub ordenamiento: Vec<String>,
+    pub muestra: Option<Muestra>,
+    pub restricciones: Option<Expresion>,
+    pub agrupamiento: Option<Agrupamiento>,
+    pub condicion_grupos: Option<Expresion>,
+    pub criterios_orden: Vec<CriterioOrden>,
+    pub limite: Option<usize>,
     pub ruta_tabla: String,
+    pub almacenamiento: Arc<dyn Almacenamiento>,
+    pub opciones: OpcionesArchivo,
+    pub estadisticas: Option<Estadisticas>,
 }
 
 impl ConsultaSelect {
     /// Crea una nueva instancia de `ConsultaSelect` a partir de una cadena de consulta SQL.
     ///
-    /// Este método toma una consulta SQL en formato `String` y la procesa para extraer los
-    /// campos de consulta, la tabla, las restricciones, y el ordenamiento.
-    ///
     /// # Parámetros
     /// - `consulta`: La consulta SQL en formato `String`.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla consultada.
+    /// - `opciones`: Las opciones con las que se lee el archivo de la tabla.
     ///
     /// # Retorno
     /// Retorna una instancia de `ConsultaSelect` con los campos, tabla, restricciones y
-    /// ordenamiento extraídos.
-
-    pub fn crear(consulta: &String, ruta_a_tablas: &String) -> ConsultaSelect {
-        let consulta_parseada = &Self::parsear_consulta_de_comando_select(&consulta);
-        let mut index = 1; //nos salteamos la palabra select
-        let campos_consulta = Self::parsear_campos(consulta_parseada, &mut index);
-        let campos_posibles: HashMap<String, usize> = HashMap::new();
-        let tabla = Self::parsear_tabla(consulta_parseada, &mut index);
-        let restricciones = Self::parsear_restricciones(consulta_parseada, &mut index);
-        let ordenamiento = Self::parsear_ordenamiento(consulta_parseada, &mut index);
-        let ruta_tabla = procesar_ruta(&ruta_a_tablas, &tabla);
-
-        ConsultaSelect {
-            campos_consulta,
-            campos_posibles,
-            tabla,
-            restricciones,
-            ordenamiento,
-            ruta_tabla,
+    /// ordenamiento extraídos, `Errores::InvalidSyntax` si la consulta no es un `SELECT` válido
+    /// o `Errores::InvalidTable` si la tabla no se puede resolver.
+    pub fn crear(
+        consulta: &str,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaSelect, errores::Errores> {
+        match sintaxis::parsear(consulta)? {
+            Sentencia::Select(sentencia) => Self::desde_sentencia(sentencia, resolvedor, opciones),
+            _ => Err(errores::Errores::InvalidSyntax),
         }
     }
-    /// Parsea una consulta SQL para obtener los distintos tokens.
+
+    /// Crea una nueva instancia de `ConsultaSelect` a partir de una sentencia ya parseada.
     ///
-    /// Convierte la consulta a minúsculas, reemplaza las comas por espacios y divide la cadena en
-    /// palabras.
+    /// Las tablas `-` y `stdin` no pasan por el resolvedor: se leen de la entrada estándar
+    /// (ver `archivo::RUTA_ENTRADA`). Con `OpcionesArchivo::solo_tablas` no se admiten, como
+    /// tampoco las rutas entre comillas.
     ///
     /// # Parámetros
-    /// - `consulta`: La consulta SQL en formato `String`.
+    /// - `sentencia`: La sentencia `SELECT` parseada.
+    /// - `resolvedor`: El resolvedor que indica dónde se almacena la tabla consultada.
+    /// - `opciones`: Las opciones con las que se lee el archivo de la tabla.
     ///
     /// # Retorno
-    /// Retorna un `Vec<String>` que contiene cada palabra de la consulta SQL.
-
-    fn parsear_consulta_de_comando_select(consulta: &String) -> Vec<String> {
-        return consulta
-            .replace(",", " ")
-            .to_lowercase()
-            .split_whitespace()
-            .map(|s| s.to_string())
-            .collect();
+    /// Retorna la `ConsultaSelect`, `Errores::InvalidTable` si la tabla no se puede resolver, o
+    /// `Errores::PermisoDenegado` si el `FROM` no es una tabla y solo se admiten tablas.
+    pub fn desde_sentencia(
+        mut sentencia: SentenciaSelect,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<ConsultaSelect, errores::Errores> {
+        let entrada = sentencia.tabla == RUTA_ENTRADA
+            || (!sentencia.archivo && sentencia.tabla.eq_ignore_ascii_case(TABLA_ENTRADA));
+        if opciones.solo_tablas && (entrada || sentencia.archivo) {
+            return Err(errores::Errores::PermisoDenegado(sentencia.tabla));
+        }
+        let ruta_tabla = if entrada {
+            RUTA_ENTRADA.to_string()
+        } else if sentencia.archivo {
+            sentencia.tabla.to_string()
+        } else {
+            resolvedor.resolver(&sentencia.tabla)?
+        };
+        registro::registrar(registro::DETALLE, || {
+            format!("tabla '{}' en '{}'", sentencia.tabla, ruta_tabla)
+        });
+        Self::materializar_subconsultas(&mut sentencia, resolvedor, opciones)?;
+        if opciones.mezclar {
+            sentencia.criterios_orden.push(CriterioOrden {
+                expresion: Expresion::Funcion("random".to_string(), Vec::new()),
+                descendente: false,
+                natural: false,
+            });
+        }
+        if let Some(restricciones) = &sentencia.restricciones {
+            registro::registrar(registro::DEPURACION, || format!("WHERE: {}", restricciones));
+        }
+        let mut campos_consulta = Vec::new();
+        let mut proyecciones = Vec::new();
+        for proyeccion in sentencia.proyecciones {
+            match proyeccion {
+                Proyeccion::Todas => campos_consulta.push("*".to_string()),
+                Proyeccion::Expresion { expresion, texto } => {
+                    campos_consulta.push(texto);
+                    proyecciones.push(expresion);
+                }
+            }
+        }
+
+        Ok(ConsultaSelect {
+            campos_consulta,
+            proyecciones,
+            campos_posibles: HashMap::new(),
+            tabla: sentencia.tabla,
+            muestra: sentencia.muestra,
+            restricciones: sentencia.restricciones,
+            agrupamiento: sentencia.agrupamiento,
+            condicion_grupos: sentencia.condicion_grupos,
+            criterios_orden: sentencia.criterios_orden,
+            limite: sentencia.limite,
+            ruta_tabla,
+            almacenamiento: resolvedor.almacenamiento(),
+            opciones: opciones.clone(),
+            estadisticas: None,
+        })
     }
-}
 
-impl Parseables for ConsultaSelect {
-    /// Extrae los campos de consulta a partir de la consulta SQL.
+    /// Ejecuta las subconsultas de las proyecciones, del `WHERE`, del `HAVING` y del `ORDER BY` de la
+    /// sentencia, que no dependen de sus registros, y guarda sus resultados en las
+    /// expresiones (ver `Expresion::materializar_subconsultas`).
     ///
-    /// A partir de una lista de tokens, extrae los campos hasta que encuentre la palabra clave `FROM`.
+    /// # Retorno
+    /// Retorna `Ok(())`, el error de la primera subconsulta que falló o
+    /// `Errores::InvalidSyntax` si una subconsulta no tiene una única columna.
+    fn materializar_subconsultas(
+        sentencia: &mut SentenciaSelect,
+        resolvedor: &dyn ResolvedorTablas,
+        opciones: &OpcionesArchivo,
+    ) -> Result<(), errores::Errores> {
+        // El orden de los valores de una subconsulta no cambia el resultado.
+        let opciones = &OpcionesArchivo {
+            mezclar: false,
+            ..opciones.clone()
+        };
+        let mut ejecutar = |subconsulta: &SentenciaSelect| {
+            let mut consulta =
+                ConsultaSelect::desde_sentencia(subconsulta.clone(), resolvedor, opciones)?;
+            consulta.verificar_validez_consulta()?;
+            let mut recolector = RecolectorValores {
+                columnas: 0,
+                valores: Vec::new(),
+            };
+            consulta.procesar(&mut recolector)?;
+            if recolector.columnas != 1 {
+                return Err(errores::Errores::InvalidSyntax);
+            }
+            Ok(recolector.valores)
+        };
+        let proyecciones =
+            sentencia
+                .proyecciones
+                .iter_mut()
+                .filter_map(|proyeccion| match proyeccion {
+                    Proyeccion::Expresion { expresion, .. } => Some(expresion),
+                    Proyeccion::Todas => None,
+                });
+        let criterios = sentencia
+            .criterios_orden
+            .iter_mut()
+            .map(|criterio| &mut criterio.expresion);
+        for expresion in proyecciones
+            .chain(sentencia.restricciones.as_mut())
+            .chain(sentencia.condicion_grupos.as_mut())
+            .chain(criterios)
+        {
+            expresion.materializar_subconsultas(&mut ejecutar)?;
+        }
+        Ok(())
+    }
+
+    /// Decide la estrategia con la que se ejecuta la consulta (ver `planificador::planificar`).
+    fn planificar(&self) -> PlanConsulta {
+        planificador::planificar(
+            self.restricciones.as_ref(),
+            &self.criterios_orden,
+            self.limite,
+            self.estadisticas.as_ref(),
+        )
+    }
+
+    /// Compila las expresiones que se evalúan sobre cada registro de la tabla.
     ///
     /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesan los tokens.
+    /// - `planificado`: El plan de la consulta, del que se toma la condición simplificada.
     ///
     /// # Retorno
-    /// Un `Vec<String>` que contiene los nombres de los campos a consultar.
+    /// El `PlanEvaluacion` de la consulta, o el error de la primera expresión que no se pueda
+    /// compilar.
+    fn compilar_plan(
+        &self,
+        planificado: &PlanConsulta,
+    ) -> Result<PlanEvaluacion, errores::Errores> {
+        let tipos = self.almacenamiento.esquema(&self.ruta_tabla)?.tipos();
+        let compilar = |expresiones: Vec<&Expresion>| {
+            expresiones
+                .into_iter()
+                .map(|expresion| expresion.compilar_con_tipos(&self.campos_posibles, &tipos))
+                .collect::<Result<Vec<ExpresionCompilada>, errores::Errores>>()
+        };
+        let (proyecciones, claves_orden) = if self.es_agrupada() {
+            (Vec::new(), Vec::new())
+        } else {
+            (
+                compilar(self.proyecciones.iter().collect())?,
+                compilar(self.claves())?,
+            )
+        };
+        Ok(PlanEvaluacion {
+            restricciones: match &planificado.restricciones {
+                Some(restricciones) => {
+                    Some(restricciones.compilar_con_tipos(&self.campos_posibles, &tipos)?)
+                }
+                None => None,
+            },
+            proyecciones,
+            claves_orden,
+            claves_agrupamiento: compilar(self.claves_agrupamiento().iter().collect())?,
+        })
+    }
 
-    fn parsear_campos(consulta: &Vec<String>, index: &mut usize) -> Vec<String> {
-        let mut campos: Vec<String> = Vec::new();
-        while *index < consulta.len() && consulta[*index] != "from" {
-            let campo = &consulta[*index];
-            campos.push(campo.to_string());
-            *index += 1;
+    /// Filtra los registros que cumplen las restricciones y evalúa las proyecciones sobre
+    /// cada uno, junto con sus criterios de orden.
+    ///
+    /// # Parámetros
+    /// - `plan`: Las expresiones compiladas de la consulta.
+    /// - `registros`: El texto de cada registro de la tabla, en el orden del archivo.
+    ///
+    /// # Retorno
+    /// Las filas del resultado, en el mismo orden que los registros, o el primer error de
+    /// evaluación.
+    fn evaluar_registros(
+        &self,
+        plan: &PlanEvaluacion,
+        registros: &[String],
+    ) -> Result<Vec<FilaResultado>, errores::Errores> {
+        let mut filas = Vec::new();
+        let mut registro = Vec::new();
+        for linea in registros {
+            dividir_registro_en(
+                linea,
+                self.opciones.delimitador(),
+                self.opciones.nulo(),
+                &mut registro,
+            );
+            if let Some(restricciones) = &plan.restricciones {
+                if !restricciones.cumple(&registro)? {
+                    continue;
+                }
+            }
+            let mut fila = Vec::new();
+            for proyeccion in &plan.proyecciones {
+                fila.push(proyeccion.evaluar(&registro)?);
+            }
+            let mut claves = Vec::new();
+            for clave in &plan.claves_orden {
+                claves.push(clave.evaluar(&registro)?);
+            }
+            filas.push((claves, fila));
         }
-        campos
+        Ok(filas)
     }
-    /// Extrae el nombre de la tabla a partir de la consulta SQL.
+
+    /// Evalúa un lote de registros repartiéndolo en partes consecutivas entre los hilos
+    /// disponibles, y une los resultados respetando el orden del archivo.
     ///
-    /// Busca la palabra clave `FROM` en los tokens de la consulta y toma el siguiente token como el nombre de la tabla.
+    /// Los lotes chicos, o las máquinas con un único procesador, se evalúan en el hilo actual.
     ///
     /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesa la consulta.
+    /// - `plan`: Las expresiones compiladas de la consulta.
+    /// - `registros`: El texto de cada registro del lote.
     ///
     /// # Retorno
-    /// Una cadena de texto (`String`) que contiene el nombre de la tabla.
-
-    fn parsear_tabla(consulta: &Vec<String>, index: &mut usize) -> String {
-        let mut tabla = String::new();
-        if consulta[*index] == "from" {
-            *index += 1
+    /// Las filas del resultado del lote, o el error del primer registro que falle.
+    fn evaluar_lote(
+        &self,
+        plan: &PlanEvaluacion,
+        registros: &[String],
+    ) -> Result<Vec<FilaResultado>, errores::Errores> {
+        let hilos = thread::available_parallelism()
+            .map(|hilos| hilos.get())
+            .unwrap_or(1)
+            .min(registros.len() / REGISTROS_MINIMOS_POR_HILO);
+        if hilos <= 1 {
+            return self.evaluar_registros(plan, registros);
         }
-        if *index < consulta.len() {
-            let tabla_consulta = &consulta[*index];
-            *index += 1;
-            tabla = tabla_consulta.to_string();
+        let tamanio_parte = registros.len().div_ceil(hilos);
+        thread::scope(|alcance| {
+            let tareas: Vec<_> = registros
+                .chunks(tamanio_parte)
+                .map(|parte| alcance.spawn(move || self.evaluar_registros(plan, parte)))
+                .collect();
+            let mut filas = Vec::new();
+            for tarea in tareas {
+                filas.extend(tarea.join().map_err(errores::Errores::de_panico)??);
+            }
+            Ok(filas)
+        })
+    }
+
+    /// Evalúa el lote pendiente, entrega sus filas al resultado y lo vacía.
+    fn escribir_lote(
+        &self,
+        plan: &PlanEvaluacion,
+        lote: &mut Lote,
+        resultado: &mut Resultado,
+    ) -> Result<(), errores::Errores> {
+        for fila in self.evaluar_lote(plan, lote.registros())? {
+            resultado.agregar(fila)?;
         }
-        tabla
+        lote.vaciar();
+        Ok(())
     }
 
-    /// Extrae las restricciones a partir de la consulta SQL.
-    ///
-    /// Busca la palabra clave `WHERE` en los tokens de la consulta y toma los tokens siguientes como restricciones hasta
-    /// encontrar la palabra clave `ORDER` o `BY`.
+    /// Escribe el plan de la consulta (`EXPLAIN`) en lugar de sus resultados: una fila por
+    /// cada parte de la consulta, tal como quedó después de parsearla, verificarla y
+    /// planificarla.
     ///
     /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesan los tokens.
+    /// - `escritor`: El escritor que recibe las filas del plan, con las columnas `propiedad` y
+    ///   `valor`.
     ///
     /// # Retorno
-    /// Un `Vec<String>` que contiene las restricciones de la consulta.`
-
-    fn parsear_restricciones(consulta: &Vec<String>, index: &mut usize) -> Vec<String> {
-        let mut restricciones = Vec::new();
-
-        while *index < consulta.len() {
-            let palabra = &consulta[*index];
-            if palabra == "where" {
-                *index += 1;
-                while *index < consulta.len()
-                    && consulta[*index] != "order"
-                    && consulta[*index] != "by"
-                {
-                    let palabra = &consulta[*index];
-                    restricciones.push(palabra.to_string());
-                    *index += 1;
-                }
-                break;
+    /// Retorna el `Resumen` con la cantidad de filas del plan, o el error del escritor.
+    pub fn explicar(
+        &self,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let planificado = self.planificar();
+        let listar = |expresiones: Vec<String>| {
+            if expresiones.is_empty() {
+                "ninguno".to_string()
             } else {
-                *index += 1;
+                expresiones.join(", ")
             }
+        };
+        let propiedades = [
+            (
+                "proyecciones",
+                listar(self.proyecciones.iter().map(|p| p.to_string()).collect()),
+            ),
+            ("tabla", self.tabla.to_string()),
+            ("archivo", self.ruta_tabla.to_string()),
+            (
+                "muestra",
+                self.muestra
+                    .as_ref()
+                    .map_or("ninguna".to_string(), |muestra| {
+                        let mut texto = format!("{} % de los registros", muestra.porcentaje);
+                        if let Some(semilla) = muestra.semilla {
+                            texto.push_str(&format!(" (semilla {})", semilla));
+                        }
+                        texto
+                    }),
+            ),
+            (
+                "restricciones",
+                planificado
+                    .restricciones
+                    .as_ref()
+                    .map_or("ninguna".to_string(), |restricciones| {
+                        restricciones.to_string()
+                    }),
+            ),
+            (
+                "agrupamiento",
+                if self.es_agrupada() {
+                    listar(
+                        self.claves_agrupamiento()
+                            .iter()
+                            .map(|c| c.to_string())
+                            .collect(),
+                    )
+                } else {
+                    "ninguno".to_string()
+                },
+            ),
+            (
+                "condicion_grupos",
+                self.condicion_grupos
+                    .as_ref()
+                    .map_or("ninguna".to_string(), |condicion| condicion.to_string()),
+            ),
+            (
+                "orden",
+                listar(
+                    self.criterios_orden
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                ) + if planificado.ordenar || self.criterios_orden.is_empty() {
+                    ""
+                } else {
+                    " (se omite: los criterios son constantes)"
+                },
+            ),
+            (
+                "limite",
+                self.limite
+                    .map_or("ninguno".to_string(), |limite| limite.to_string()),
+            ),
+            ("indice", "ninguno".to_string()),
+            (
+                "acceso",
+                match planificado.acceso {
+                    Acceso::Recorrido => "recorrido completo de la tabla",
+                    Acceso::Vacio => "ninguno (la consulta no puede producir registros)",
+                }
+                .to_string(),
+            ),
+            (
+                "estadisticas",
+                self.estadisticas
+                    .as_ref()
+                    .map_or("ninguna".to_string(), |estadisticas| {
+                        format!("{} filas (ANALYZE)", estadisticas.filas)
+                    }),
+            ),
+        ];
+        escritor.escribir_encabezado(&["propiedad".to_string(), "valor".to_string()])?;
+        let filas = propiedades.len();
+        for (propiedad, valor) in propiedades {
+            escritor.escribir_fila(&[Valor::Texto(propiedad.to_string()), Valor::Texto(valor)])?;
         }
-        restricciones
+        Ok(Resumen::Filas(filas))
     }
 
-    /// Extrae el criterio de ordenamiento a partir de la consulta SQL.
-    ///
-    /// Busca las palabras clave `ORDER` y `BY` en los tokens de la consulta y toma los tokens siguientes como criterios de
-    /// ordenamiento.
+    /// Indica si la consulta combina registros en grupos, ya sea por tener `GROUP BY` o
+    /// `HAVING`, o por proyectar funciones de agregación.
+    fn es_agrupada(&self) -> bool {
+        self.agrupamiento.is_some()
+            || self.condicion_grupos.is_some()
+            || self
+                .proyecciones
+                .iter()
+                .any(|proyeccion| proyeccion.contiene_agregacion())
+    }
+
+    /// Devuelve las funciones de ventana de la consulta, en las proyecciones y en el
+    /// `ORDER BY`, con la posición de sus claves a continuación de las de los criterios de orden.
+    fn ventanas(&self) -> Vec<CalculoVentana<'_>> {
+        let proyecciones = self
+            .proyecciones
+            .iter()
+            .enumerate()
+            .map(|(posicion, expresion)| (expresion, DestinoVentana::Proyeccion(posicion)));
+        let criterios = self
+            .criterios_orden
+            .iter()
+            .enumerate()
+            .map(|(posicion, criterio)| (&criterio.expresion, DestinoVentana::Criterio(posicion)));
+        let mut inicio_claves = self.criterios_orden.len();
+        let mut ventanas = Vec::new();
+        for (expresion, destino) in proyecciones.chain(criterios) {
+            if let Expresion::Ventana(ventana) = expresion {
+                ventanas.push(CalculoVentana {
+                    ventana,
+                    inicio_claves,
+                    destino,
+                });
+                inicio_claves += ventana.expresiones().count();
+            }
+        }
+        ventanas
+    }
+
+    /// Devuelve las expresiones que se evalúan como claves de cada fila del resultado: las de
+    /// los criterios de orden y, a continuación, las de cada función de ventana (ver
+    /// `ventanas`).
+    fn claves(&self) -> Vec<&Expresion> {
+        let criterios = self
+            .criterios_orden
+            .iter()
+            .map(|criterio| &criterio.expresion);
+        let ventanas = self.proyecciones.iter().chain(criterios.clone());
+        criterios
+            .chain(ventanas.flat_map(|expresion| match expresion {
+                Expresion::Ventana(ventana) => ventana.expresiones().collect(),
+                _ => Vec::new(),
+            }))
+            .collect()
+    }
+
+    /// Devuelve las expresiones por las que se agrupan los registros, una vez resuelto el
+    /// `GROUP BY ALL`.
+    fn claves_agrupamiento(&self) -> &[Expresion] {
+        match &self.agrupamiento {
+            Some(Agrupamiento::Expresiones(claves)) => claves,
+            _ => &[],
+        }
+    }
+
+    /// Verifica que las proyecciones de una consulta agrupada sean válidas y resuelve el
+    /// `GROUP BY ALL` a las proyecciones que no son agregaciones.
     ///
-    /// # Parámetros
-    /// - `consulta`: Un vector de cadenas que representa la consulta SQL tokenizada.
-    /// - `index`: Un índice mutable que se actualiza conforme se procesan los tokens.
+    /// Cada proyección, la condición del `HAVING` y cada criterio de orden debe coincidir con una expresión del
+    /// `GROUP BY` o usar, fuera de las funciones de agregación, sólo columnas que están en el
+    /// `GROUP BY`.
     ///
     /// # Retorno
-    /// Un `Vec<String>` que contiene los criterios de ordenamiento de la consulta.
-
-    fn parsear_ordenamiento(consulta: &Vec<String>, index: &mut usize) -> Vec<String> {
-        let mut ordenamiento = Vec::new();
-
-        while *index < consulta.len() {
-            let palabra = &consulta[*index];
-            if palabra == "order" {
-                *index += 1;
-                if *index < consulta.len() && consulta[*index] == "by" {
-                    *index += 1;
-                    while *index < consulta.len() {
-                        let palabra = &consulta[*index];
-                        ordenamiento.push(palabra.to_string());
-                        *index += 1;
-                    }
-                }
+    /// Retorna `Errores::ColumnaDesconocida` si el `GROUP BY` usa columnas inexistentes o
+    /// `Errores::ColumnaNoAgrupada` con la primera columna proyectada que no está agrupada.
+    fn verificar_agrupamiento(&mut self) -> Result<(), errores::Errores> {
+        if !self.es_agrupada() {
+            return Ok(());
+        }
+        let claves: Vec<Expresion> = match &self.agrupamiento {
+            Some(Agrupamiento::Todo) => self
+                .proyecciones
+                .iter()
+                .filter(|proyeccion| {
+                    !proyeccion.contiene_agregacion() && !proyeccion.contiene_ventana()
+                })
+                .cloned()
+                .collect(),
+            _ => self.claves_agrupamiento().to_vec(),
+        };
+        for clave in &claves {
+            if let Some(columna) = clave
+                .columnas()
+                .into_iter()
+                .find(|columna| !self.campos_posibles.contains_key(columna))
+            {
+                return Err(errores::Errores::columna_desconocida(
+                    &columna,
+                    self.campos_posibles.keys(),
+                ));
             }
         }
-        ordenamiento
+
+        let columnas_agrupadas: Vec<&String> = claves
+            .iter()
+            .filter_map(|clave| match clave {
+                Expresion::Columna(columna) => Some(columna),
+                _ => None,
+            })
+            .collect();
+        let criterios = self
+            .criterios_orden
+            .iter()
+            .map(|criterio| &criterio.expresion);
+        let expresiones = self.proyecciones.iter().chain(&self.condicion_grupos);
+        for proyeccion in expresiones.chain(criterios) {
+            if claves.contains(proyeccion) {
+                continue;
+            }
+            if let Some(columna) = proyeccion
+                .columnas_no_agregadas()
+                .into_iter()
+                .find(|columna| !columnas_agrupadas.contains(&columna))
+            {
+                return Err(errores::Errores::ColumnaNoAgrupada(columna));
+            }
+        }
+        if self.agrupamiento.is_some() {
+            self.agrupamiento = Some(Agrupamiento::Expresiones(claves));
+        }
+        Ok(())
     }
 }
 
@@ -214,123 +922,407 @@ impl MetodosConsulta for ConsultaSelect {
     ///
     /// # Retorno
     /// Retorna un `Result` que indica el éxito (`Ok`) o el tipo de error (`Err`).
-
     fn verificar_validez_consulta(&mut self) -> Result<(), errores::Errores> {
-        match leer_archivo(&self.ruta_tabla) {
+        match self.almacenamiento.leer(&self.ruta_tabla, &self.opciones) {
             Ok(mut lector) => {
-                let mut nombres_campos = String::new();
-                lector
-                    .read_line(&mut nombres_campos)
-                    .map_err(|_| errores::Errores::Error)?;
-                let (_, campos_validos) = &parsear_linea_archivo(&nombres_campos);
-                self.campos_posibles = mapear_campos(campos_validos);
+                let campos_validos = leer_encabezado(lector.as_mut(), &mut self.opciones)?;
+                self.campos_posibles = mapear_campos(&campos_validos);
+            }
+            Err(error) => return Err(errores::Errores::tabla_ilegible(error, &self.tabla)),
+        };
+        // Las estadísticas solo mejoran el plan, así que si no se pueden cargar la consulta se
+        // ejecuta igual.
+        self.estadisticas = match self.almacenamiento.estadisticas(&self.ruta_tabla) {
+            Ok(estadisticas) => estadisticas,
+            Err(error) => {
+                registro::advertir(format!(
+                    "no se pudieron cargar las estadísticas de '{}': {}",
+                    self.tabla, error
+                ));
+                None
             }
-            Err(_) => return Err(errores::Errores::InvalidTable),
         };
         if self.campos_consulta.is_empty() {
             return Err(errores::Errores::InvalidSyntax);
         }
         let campos_posibles = &self.campos_posibles;
-        if !ConsultaSelect::verificar_campos_validos(campos_posibles, &mut self.campos_consulta) {
-            return Err(errores::Errores::InvalidColumn);
+        if self.proyecciones.is_empty() {
+            if !ConsultaSelect::verificar_campos_validos(campos_posibles, &mut self.campos_consulta)
+            {
+                return Err(columna_invalida(campos_posibles, &self.campos_consulta));
+            }
+            self.proyecciones = self
+                .campos_consulta
+                .iter()
+                .map(|campo| Expresion::Columna(campo.to_string()))
+                .collect();
         }
-        Ok(())
+        let criterios = self
+            .criterios_orden
+            .iter()
+            .map(|criterio| &criterio.expresion);
+        let expresiones = self
+            .proyecciones
+            .iter()
+            .chain(&self.restricciones)
+            .chain(&self.condicion_grupos);
+        for expresion in expresiones.chain(criterios) {
+            if let Some(columna) = expresion
+                .columnas()
+                .into_iter()
+                .find(|columna| !campos_posibles.contains_key(columna))
+            {
+                return Err(errores::Errores::columna_desconocida(
+                    &columna,
+                    campos_posibles.keys(),
+                ));
+            }
+        }
+        if !self.es_agrupada()
+            && self
+                .criterios_orden
+                .iter()
+                .any(|criterio| criterio.expresion.contiene_agregacion())
+        {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+
+        self.verificar_agrupamiento()
     }
 
     /// Procesa el contenido del archivo tabla y muestra los resultados de la consulta.
     ///
-    /// Lee línea por línea del archivo proporcionado y muestra las líneas que cumplen con los campos seleccionados.
+    /// Lee registro por registro del archivo proporcionado y, para cada registro que cumple las
+    /// restricciones, envía al escritor el valor de cada campo seleccionado. Sin agrupamiento,
+    /// los registros se leen en lotes que se evalúan en paralelo y se escriben en el orden del
+    /// archivo. Si la consulta es agrupada, reúne los registros por grupo y escribe una fila
+    /// por cada grupo que cumple el `HAVING`, en el orden en que aparecen; sin `GROUP BY`, todos los registros forman un
+    /// único grupo. Con `ORDER BY`, las filas se escriben ordenadas al terminar la lectura,
+    /// después de calcular las funciones de ventana; sin ninguno de los dos, la lectura se
+    /// detiene en cuanto se escriben las filas que pide el `LIMIT`.
+    ///
+    /// Una consulta que no selecciona ninguna fila no es un error: el escritor recibe solo el
+    /// encabezado y el `Resumen` indica cero filas.
     ///
     /// # Retorno
-    /// Retorna un `Result` que indica el éxito (`Ok`) o el tipo de error (`Err`).
+    /// Retorna el `Resumen` con la cantidad de filas del resultado, o el tipo de error (`Err`).
+    fn procesar(
+        &mut self,
+        escritor: &mut dyn EscritorResultados,
+    ) -> Result<Resumen, errores::Errores> {
+        let mut lector = self
+            .almacenamiento
+            .leer(&self.ruta_tabla, &self.opciones)
+            .map_err(|error| errores::Errores::tabla_ilegible(error, &self.tabla))?;
+
+        leer_encabezado(lector.as_mut(), &mut self.opciones)?;
+        let mut verificador = VerificadorCampos::new(&self.opciones, self.campos_posibles.len());
 
-    fn procesar(&mut self) -> Result<(), errores::Errores> {
-        //primera version select normal sin condiciones;
-        let mut lector =
-            leer_archivo(&self.ruta_tabla).map_err(|_| errores::Errores::InvalidTable)?;
+        let planificado = self.planificar();
+        let plan = self.compilar_plan(&planificado)?;
+        escritor.escribir_encabezado(&self.campos_consulta)?;
+        let agrupada = self.es_agrupada();
+        let criterios_orden = if planificado.ordenar {
+            &self.criterios_orden[..]
+        } else {
+            &[]
+        };
+        let mut resultado = Resultado::new(escritor, criterios_orden, self.ventanas(), self.limite);
+        let mut grupos: Vec<Vec<Vec<Option<String>>>> = Vec::new();
+        let mut indices_grupos: HashMap<Vec<Option<String>>, usize> = HashMap::new();
+        let mut lote = Lote::default();
+        let mut registro = String::new();
+        let mut muestreo = Muestreo::new(self.muestra.as_ref());
+        while planificado.acceso == Acceso::Recorrido && !resultado.completo() {
+            if !agrupada {
+                if !lote.leer(lector.as_mut(), &mut verificador)? {
+                    break;
+                }
+                if !muestreo.incluir() {
+                    lote.descartar_ultimo();
+                    continue;
+                }
+                let tamanio_lote = resultado
+                    .pendientes()
+                    .map_or(TAMANIO_LOTE, |pendientes| pendientes.min(TAMANIO_LOTE));
+                if lote.len() >= tamanio_lote {
+                    self.escribir_lote(&plan, &mut lote, &mut resultado)?;
+                }
+                continue;
+            }
 
-        let mut nombres_campos = String::new();
-        lector
-            .read_line(&mut nombres_campos)
-            .map_err(|_| errores::Errores::Error)?;
+            if !leer_registro_en(lector.as_mut(), &mut registro)? {
+                break;
+            }
+            verificador.verificar(&mut registro)?;
+            if !muestreo.incluir() {
+                continue;
+            }
+            let registro_parseado =
+                dividir_registro(&registro, self.opciones.delimitador(), self.opciones.nulo());
 
-        for registro in lector.lines() {
-            let (registro_parseado, _) = match registro {
-                Ok(registro) => parsear_linea_archivo(&registro),
-                Err(_) => return Err(errores::Errores::Error),
-            };
+            if let Some(restricciones) = &plan.restricciones {
+                if !restricciones.cumple(&registro_parseado)? {
+                    continue;
+                }
+            }
 
-            let mut campos_seleccionados: Vec<&usize> = Vec::new();
-            for campo in &self.campos_consulta {
-                match self.campos_posibles.get(campo) {
-                    Some(valor) => campos_seleccionados.push(valor),
-                    None => return Err(errores::Errores::Error),
-                };
+            let mut clave = Vec::new();
+            for expresion in &plan.claves_agrupamiento {
+                clave.push(expresion.evaluar(&registro_parseado)?.a_campo());
             }
+            let indice = *indices_grupos.entry(clave).or_insert_with(|| {
+                grupos.push(Vec::new());
+                grupos.len() - 1
+            });
+            grupos[indice].push(
+                registro_parseado
+                    .into_iter()
+                    .map(|campo| campo.map(Cow::into_owned))
+                    .collect(),
+            );
+        }
+        self.escribir_lote(&plan, &mut lote, &mut resultado)?;
 
-            let mut linea: Vec<&str> = Vec::new();
-            for campo in campos_seleccionados {
-                linea.push(&registro_parseado[*campo]);
+        if agrupada && grupos.is_empty() && self.claves_agrupamiento().is_empty() {
+            grupos.push(Vec::new());
+        }
+        for grupo in &grupos {
+            if let Some(condicion) = &self.condicion_grupos {
+                if !condicion.cumple_grupo(&self.campos_posibles, grupo)? {
+                    continue;
+                }
+            }
+            let mut linea = Vec::new();
+            for proyeccion in &self.proyecciones {
+                linea.push(proyeccion.evaluar_grupo(&self.campos_posibles, grupo)?);
+            }
+            let mut claves = Vec::new();
+            for clave in self.claves() {
+                claves.push(clave.evaluar_grupo(&self.campos_posibles, grupo)?);
             }
-            let linea = linea.join(",");
-            println!("{}", linea);
+            resultado.agregar((claves, linea))?;
         }
+        let filas = resultado.finalizar()?;
+        registro::registrar(registro::DETALLE, || {
+            format!("{} filas en el resultado", filas)
+        });
+        Ok(Resumen::Filas(filas))
+    }
+}
+
+impl ConsultaSelect {
+    /// Verifica la consulta y devuelve su resultado como un iterador de filas, para recorrerlo
+    /// desde otros programas sin pasar por un `EscritorResultados`.
+    ///
+    /// Sin `GROUP BY`, `ORDER BY` ni funciones de ventana, cada fila se lee y evalúa recién
+    /// cuando se pide, de modo que la tabla no se carga completa en memoria. El resto de las
+    /// consultas necesitan todos los registros antes de dar la primera fila, así que se
+    /// procesan al crear el iterador.
+    ///
+    /// # Retorno
+    /// Retorna las `FilasSelect` de la consulta, con los valores de cada fila como texto (vacío
+    /// si son nulos), o el error que impide procesar la consulta.
+    pub fn filas(&mut self) -> Result<FilasSelect<'_>, errores::Errores> {
+        self.verificar_validez_consulta()?;
+        let planificado = self.planificar();
+        let mut materializadas = None;
+        if self.es_agrupada() || planificado.ordenar || !self.ventanas().is_empty() {
+            let mut recolector = RecolectorFilas { filas: Vec::new() };
+            self.procesar(&mut recolector)?;
+            materializadas = Some(recolector.filas.into_iter());
+        }
+
+        let mut lector = self
+            .almacenamiento
+            .leer(&self.ruta_tabla, &self.opciones)
+            .map_err(|error| errores::Errores::tabla_ilegible(error, &self.tabla))?;
+        leer_encabezado(lector.as_mut(), &mut self.opciones)?;
+        Ok(FilasSelect {
+            plan: self.compilar_plan(&planificado)?,
+            verificador: VerificadorCampos::new(&self.opciones, self.campos_posibles.len()),
+            muestreo: Muestreo::new(self.muestra.as_ref()),
+            consulta: self,
+            lector,
+            registro: String::new(),
+            restantes: self.limite,
+            terminado: planificado.acceso == Acceso::Vacio,
+            materializadas,
+        })
+    }
+}
+
+/// Escritor que conserva las filas del resultado en memoria, como texto.
+struct RecolectorFilas {
+    filas: Vec<Vec<String>>,
+}
+
+impl EscritorResultados for RecolectorFilas {
+    fn escribir_encabezado(&mut self, _columnas: &[String]) -> Result<(), errores::Errores> {
         Ok(())
     }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        self.filas
+            .push(valores.iter().map(Valor::a_texto).collect());
+        Ok(())
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        Ok(())
+    }
+}
+
+/// Escritor que conserva los valores del resultado de una subconsulta, que debe tener una
+/// única columna.
+struct RecolectorValores {
+    columnas: usize,
+    valores: Vec<Valor>,
+}
+
+impl EscritorResultados for RecolectorValores {
+    fn escribir_encabezado(&mut self, columnas: &[String]) -> Result<(), errores::Errores> {
+        self.columnas = columnas.len();
+        Ok(())
+    }
+
+    fn escribir_fila(&mut self, valores: &[Valor]) -> Result<(), errores::Errores> {
+        self.valores.extend(valores.first().cloned());
+        Ok(())
+    }
+
+    fn finalizar(&mut self) -> Result<(), errores::Errores> {
+        Ok(())
+    }
+}
+
+/// Iterador sobre las filas del resultado de un `SELECT`, creado con `ConsultaSelect::filas`.
+///
+/// Tras el primer error, el iterador no devuelve más filas.
+pub struct FilasSelect<'a> {
+    consulta: &'a ConsultaSelect,
+    plan: PlanEvaluacion,
+    lector: Box<dyn BufRead>,
+    verificador: VerificadorCampos,
+    muestreo: Muestreo,
+    registro: String,
+    restantes: Option<usize>,
+    terminado: bool,
+    materializadas: Option<std::vec::IntoIter<Vec<String>>>,
+}
+
+impl Iterator for FilasSelect<'_> {
+    type Item = Result<Vec<String>, errores::Errores>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(filas) = &mut self.materializadas {
+            return filas.next().map(Ok);
+        }
+        while !self.terminado && self.restantes != Some(0) {
+            let filas = match leer_registro_en(self.lector.as_mut(), &mut self.registro) {
+                Ok(true) => self
+                    .verificador
+                    .verificar(&mut self.registro)
+                    .and_then(|_| {
+                        if !self.muestreo.incluir() {
+                            return Ok(Vec::new());
+                        }
+                        self.consulta
+                            .evaluar_registros(&self.plan, std::slice::from_ref(&self.registro))
+                    }),
+                Ok(false) => break,
+                Err(error) => Err(errores::Errores::Io(error)),
+            };
+            match filas {
+                Ok(filas) => {
+                    if let Some((_, fila)) = filas.into_iter().next() {
+                        self.restantes = self.restantes.map(|restantes| restantes - 1);
+                        return Some(Ok(fila.iter().map(Valor::a_texto).collect()));
+                    }
+                }
+                Err(error) => {
+                    self.terminado = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        self.terminado = true;
+        None
+    }
 }
 
 impl Verificaciones for ConsultaSelect {
     /// verifica si los campos de la consulta son existen en la tabla
     ///
+    /// Un `*` se reemplaza por todas las columnas de la tabla. Los campos que son expresiones
+    /// son válidos si todas las columnas que utilizan existen.
+    ///
     /// # Parámetros
     /// - `campos_validos`: Todos los campos de la tabla que son válidos
     /// - `campos_consulta`: Todos los campos que se quieren seleccionar
     ///
     /// # Retorno
     /// Retorna un `Result` que indica el éxito (`Ok`) o el tipo de error (`Err`).
-
     fn verificar_campos_validos(
         campos_validos: &HashMap<String, usize>,
         campos_consulta: &mut Vec<String>,
     ) -> bool {
-        if campos_consulta.len() == 1 {
-            if campos_consulta[0] == "*".to_string() {
-                campos_consulta.pop(); //Me saco de encima el "*""
-                                       //debo reemplazar ese caracter por todos los campos válidos
-                let campos = &obtener_campos_consulta_orden_por_defecto(campos_validos);
-                for campo in campos {
-                    campos_consulta.push(campo.to_string());
-                }
-                return true;
+        if campos_consulta.len() == 1 && campos_consulta[0] == "*" {
+            campos_consulta.pop(); //Me saco de encima el "*""
+                                   //debo reemplazar ese caracter por todos los campos válidos
+            let campos = &obtener_campos_consulta_orden_por_defecto(campos_validos);
+            for campo in campos {
+                campos_consulta.push(campo.to_string());
             }
+            return true;
         }
 
         for campo in campos_consulta {
-            if !(campos_validos.contains_key(campo)) {
-                return false;
+            if campos_validos.contains_key(campo) {
+                continue;
+            }
+            match Expresion::parsear(&lexico::analizar(campo)) {
+                Ok(expresion) => {
+                    if !expresion
+                        .columnas()
+                        .iter()
+                        .all(|columna| campos_validos.contains_key(columna))
+                    {
+                        return false;
+                    }
+                }
+                Err(_) => return false,
             }
         }
-        return true;
+        true
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::almacenamiento::{AlmacenamientoArchivos, TablasEnMemoria};
+    use crate::expresion::{Operador, Valor};
+    use crate::resolvedor::ResolvedorDirectorio;
+    use crate::salida::{FormatoSalida, OpcionesSalida};
     use std::collections::HashMap;
+    use std::io::Cursor;
 
     #[test]
     fn test_parsear_consulta_select() {
         let consulta = String::from(
             "SELECT campo1, campo2 FROM tabla WHERE campo1 = 'valor1' ORDER BY campo2 DESC",
         );
-        let tokens = ConsultaSelect::parsear_consulta_de_comando_select(&consulta);
+        let tokens = lexico::analizar(&consulta);
 
         assert_eq!(
-            tokens,
+            tokens
+                .iter()
+                .map(|token| token.texto.as_str())
+                .collect::<Vec<_>>(),
             vec![
-                "select", "campo1", "campo2", "from", "tabla", "where", "campo1", "=", "'valor1'",
-                "order", "by", "campo2", "desc"
+                "select", "campo1", ",", "campo2", "from", "tabla", "where", "campo1", "=",
+                "'valor1'", "order", "by", "campo2", "desc"
             ]
         );
     }
@@ -340,18 +1332,41 @@ mod tests {
         let consulta = String::from(
             "SELECT campo1, campo2 FROM tabla WHERE campo1 = 'valor1' ORDER BY campo2 DESC",
         );
-        let ruta_tabla = String::from("/ruta/a/tablas");
+        let resolvedor = ResolvedorDirectorio::new("/ruta/a/tablas");
 
-        let consulta_select = ConsultaSelect::crear(&consulta, &ruta_tabla);
+        let consulta_select =
+            ConsultaSelect::crear(&consulta, &resolvedor, &OpcionesArchivo::default()).unwrap();
 
         assert_eq!(consulta_select.campos_consulta, vec!["campo1", "campo2"]);
         assert_eq!(consulta_select.tabla, "tabla");
         assert_eq!(
             consulta_select.restricciones,
-            vec!["campo1", "=", "'valor1'"]
+            Some(Expresion::Binaria(
+                Box::new(Expresion::Columna("campo1".to_string())),
+                Operador::Igual,
+                Box::new(Expresion::Literal(Valor::Texto("valor1".to_string())))
+            ))
+        );
+        assert_eq!(
+            consulta_select.criterios_orden,
+            vec![CriterioOrden {
+                expresion: Expresion::Columna("campo2".to_string()),
+                descendente: true,
+                natural: false,
+            }]
         );
-        assert_eq!(consulta_select.ordenamiento, vec!["campo2", "desc"]);
         assert_eq!(consulta_select.ruta_tabla, "/ruta/a/tablas/tabla");
+
+        // La entrada estándar no pasa por el resolvedor.
+        for consulta in [
+            "SELECT * FROM -",
+            "SELECT * FROM STDIN",
+            "SELECT * FROM '-'",
+        ] {
+            let consulta_select =
+                ConsultaSelect::crear(consulta, &resolvedor, &OpcionesArchivo::default()).unwrap();
+            assert_eq!(consulta_select.ruta_tabla, RUTA_ENTRADA);
+        }
     }
 
     #[test]
@@ -388,10 +1403,18 @@ mod tests {
                 ("edad".to_string(), 1),
                 ("ciudad".to_string(), 2),
             ]),
+            proyecciones: vec![],
             tabla: "personas".to_string(),
-            restricciones: vec![],
-            ordenamiento: vec![],
+            muestra: None,
+            restricciones: None,
+            agrupamiento: None,
+            condicion_grupos: None,
+            criterios_orden: vec![],
+            limite: None,
             ruta_tabla: "tablas/personas".to_string(),
+            almacenamiento: Arc::new(AlmacenamientoArchivos::new()),
+            opciones: OpcionesArchivo::default(),
+            estadisticas: None,
         };
 
         let resultado = consulta.verificar_validez_consulta();
@@ -403,13 +1426,800 @@ mod tests {
         let mut consulta = ConsultaSelect {
             campos_consulta: vec!["campo_invalido".to_string()],
             campos_posibles: HashMap::new(),
+            proyecciones: vec![],
             tabla: "tabla".to_string(),
-            restricciones: vec![],
-            ordenamiento: vec![],
+            muestra: None,
+            restricciones: None,
+            agrupamiento: None,
+            condicion_grupos: None,
+            criterios_orden: vec![],
+            limite: None,
             ruta_tabla: "/ruta/a/tablas/tabla".to_string(),
+            almacenamiento: Arc::new(AlmacenamientoArchivos::new()),
+            opciones: OpcionesArchivo::default(),
+            estadisticas: None,
         };
 
         let resultado = consulta.verificar_validez_consulta();
         assert!(resultado.is_err());
     }
+
+    #[test]
+    fn test_crear_consulta_con_order_by_y_limit() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let consulta = ConsultaSelect::crear(
+            "SELECT nombre FROM personas WHERE edad > 30 ORDER BY edad DESC, nombre LIMIT 5",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            consulta.criterios_orden,
+            vec![
+                CriterioOrden {
+                    expresion: Expresion::Columna("edad".to_string()),
+                    descendente: true,
+                    natural: false,
+                },
+                CriterioOrden {
+                    expresion: Expresion::Columna("nombre".to_string()),
+                    descendente: false,
+                    natural: false,
+                }
+            ]
+        );
+        assert_eq!(consulta.limite, Some(5));
+        assert!(consulta.restricciones.is_some());
+
+        for invalida in [
+            "SELECT nombre FROM personas LIMIT -1",
+            "SELECT nombre FROM personas LIMIT 5 ORDER BY nombre",
+            "SELECT nombre FROM personas ORDER BY",
+        ] {
+            assert!(
+                ConsultaSelect::crear(invalida, &resolvedor, &OpcionesArchivo::default()).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_ordenar_por_columnas_no_proyectadas() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla(
+            "personas",
+            "nombre,edad,ciudad\nAna,40,Lugo\nLuis,25,Vigo\nEva,33,Lugo\nJuan,,Vigo\n",
+        );
+        let filas = |consulta: &str| -> Vec<Vec<String>> {
+            ConsultaSelect::crear(consulta, &tablas, &OpcionesArchivo::default())
+                .unwrap()
+                .filas()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        assert_eq!(
+            filas("SELECT nombre FROM personas WHERE edad IS NOT NULL ORDER BY edad"),
+            vec![vec!["Luis"], vec!["Eva"], vec!["Ana"]]
+        );
+        assert_eq!(
+            filas("SELECT nombre FROM personas ORDER BY ciudad DESC, edad LIMIT 2"),
+            vec![vec!["Luis"], vec!["Juan"]]
+        );
+        assert_eq!(
+            filas("SELECT SUM(edad) FROM personas GROUP BY ciudad ORDER BY MAX(edad)"),
+            vec![vec!["25"], vec!["73"]]
+        );
+        assert_eq!(
+            filas("SELECT MIN(edad) FROM personas GROUP BY ciudad ORDER BY ciudad DESC"),
+            vec![vec!["25"], vec!["33"]]
+        );
+    }
+
+    #[test]
+    fn test_alias_en_order_by_y_having() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla(
+            "personas",
+            "nombre,edad,ciudad\nAna,40,Lugo\nLuis,25,Vigo\nEva,33,Lugo\nJuan,,Vigo\nRosa,52,Lugo\n",
+        );
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT ciudad AS lugar, COUNT(edad) AS total FROM personas GROUP BY ciudad \
+             HAVING total > 1 ORDER BY total DESC",
+            &tablas,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        assert_eq!(consulta.campos_consulta, vec!["lugar", "total"]);
+        let filas: Vec<Vec<String>> = consulta.filas().unwrap().map(Result::unwrap).collect();
+        assert_eq!(filas, vec![vec!["Lugo", "3"]]);
+
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT nombre, IIF(edad > 35, 'mayor', 'menor') AS ciudad FROM personas \
+             WHERE edad > 30 ORDER BY ciudad DESC, nombre",
+            &tablas,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        let filas: Vec<Vec<String>> = consulta.filas().unwrap().map(Result::unwrap).collect();
+        assert_eq!(
+            filas,
+            vec![
+                vec!["Eva", "menor"],
+                vec!["Ana", "mayor"],
+                vec!["Rosa", "mayor"]
+            ]
+        );
+
+        tablas.agregar_tabla(
+            "ventas",
+            "producto,precio,cantidad\nmesa,120,2\nsilla,45,6\nlampara,30,3\n",
+        );
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT producto, precio*cantidad AS total FROM ventas \
+             WHERE precio * cantidad > 90 ORDER BY total DESC",
+            &tablas,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        assert_eq!(consulta.campos_consulta, vec!["producto", "total"]);
+        let filas: Vec<Vec<String>> = consulta.filas().unwrap().map(Result::unwrap).collect();
+        assert_eq!(filas, vec![vec!["silla", "270"], vec!["mesa", "240"]]);
+
+        let mut sin_agrupar = ConsultaSelect::crear(
+            "SELECT nombre FROM personas HAVING edad > 30",
+            &tablas,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            sin_agrupar.verificar_validez_consulta(),
+            Err(errores::Errores::ColumnaNoAgrupada(_))
+        ));
+    }
+
+    #[test]
+    fn test_explicar_consulta() {
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT nombre, edad FROM personas WHERE edad > 30 AND NOT ciudad = 'Madrid' \
+             ORDER BY edad DESC, nombre LIMIT 5",
+            &ResolvedorDirectorio::new("tablas"),
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        consulta.verificar_validez_consulta().unwrap();
+        let mut destino = Vec::new();
+        {
+            let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+            consulta.explicar(escritor.as_mut()).unwrap();
+            escritor.finalizar().unwrap();
+        }
+        let plan = String::from_utf8(destino).unwrap();
+        assert!(plan.contains("proyecciones,\"nombre, edad\"\n"));
+        assert!(plan.contains("restricciones,((edad > 30) AND (NOT (ciudad = 'Madrid')))\n"));
+        assert!(plan.contains("agrupamiento,ninguno\n"));
+        assert!(plan.contains("orden,\"edad DESC, nombre ASC\"\n"));
+        assert!(plan.contains("limite,5\n"));
+        assert!(plan.contains("acceso,recorrido completo de la tabla\n"));
+        assert!(plan.contains("estadisticas,ninguna\n"));
+    }
+
+    #[test]
+    fn test_filas_select() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let crear = |consulta: &str| {
+            ConsultaSelect::crear(consulta, &resolvedor, &OpcionesArchivo::default()).unwrap()
+        };
+
+        let mut consulta = crear("SELECT nombre, edad FROM personas WHERE ciudad = 'Murcia'");
+        let mut filas = consulta.filas().unwrap();
+        assert_eq!(filas.next().unwrap().unwrap(), vec!["Luis", "36"]);
+        assert_eq!(filas.count(), 4);
+
+        let mut consulta = crear("SELECT nombre FROM personas ORDER BY edad DESC LIMIT 2");
+        let filas: Vec<Vec<String>> = consulta.filas().unwrap().map(Result::unwrap).collect();
+        assert_eq!(filas, vec![vec!["Sofia"], vec!["Lucia"]]);
+
+        let mut consulta = crear("SELECT nombre FROM personas LIMIT 3");
+        assert_eq!(consulta.filas().unwrap().count(), 3);
+
+        let mut consulta = crear("SELECT nombre FROM personas WHERE IIF(edad > 60, 1, 'x')");
+        let mut filas = consulta.filas().unwrap();
+        assert!(filas.next().unwrap().is_err());
+        assert!(filas.next().is_none());
+
+        let mut consulta = crear("SELECT sueldo FROM personas");
+        assert_eq!(
+            consulta.filas().err(),
+            Some(errores::Errores::ColumnaDesconocida {
+                columna: "sueldo".to_string(),
+                sugerencias: Vec::new()
+            })
+        );
+    }
+
+    #[test]
+    fn test_consulta_sin_filas_muestra_solo_el_encabezado() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\nLuis,25\n");
+        let procesar_con = |consulta: &str, opciones_salida: OpcionesSalida| {
+            let mut destino = Vec::new();
+            let resumen = {
+                let mut escritor = opciones_salida.crear_escritor(&mut destino);
+                let mut consulta =
+                    ConsultaSelect::crear(consulta, &tablas, &OpcionesArchivo::default()).unwrap();
+                consulta.verificar_validez_consulta().unwrap();
+                let resumen = consulta.procesar(escritor.as_mut());
+                escritor.finalizar().unwrap();
+                resumen
+            };
+            (resumen, String::from_utf8(destino).unwrap())
+        };
+        let procesar = |consulta: &str, formato: FormatoSalida| {
+            procesar_con(
+                consulta,
+                OpcionesSalida {
+                    formato,
+                    ..OpcionesSalida::default()
+                },
+            )
+        };
+
+        assert_eq!(
+            procesar(
+                "SELECT nombre FROM personas WHERE edad > 100",
+                FormatoSalida::Markdown
+            ),
+            (Ok(Resumen::Filas(0)), "| nombre |\n|---|\n".to_string())
+        );
+        assert_eq!(
+            procesar(
+                "SELECT nombre, COUNT(*) FROM personas WHERE edad > 100 GROUP BY nombre",
+                FormatoSalida::Csv
+            ),
+            (Ok(Resumen::Filas(0)), "nombre,COUNT(*)\n".to_string())
+        );
+        assert_eq!(
+            procesar_con(
+                "SELECT nombre FROM personas WHERE edad > 100",
+                OpcionesSalida {
+                    sin_encabezado: true,
+                    ..OpcionesSalida::default()
+                }
+            ),
+            (Ok(Resumen::Filas(0)), String::new())
+        );
+        assert_eq!(
+            procesar("SELECT * FROM personas LIMIT 0", FormatoSalida::Json),
+            (Ok(Resumen::Filas(0)), "[]\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_muestra_de_la_tabla() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let filas = |consulta: &str| -> Vec<Vec<String>> {
+            ConsultaSelect::crear(consulta, &resolvedor, &OpcionesArchivo::default())
+                .unwrap()
+                .filas()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        let todas = filas("SELECT nombre FROM personas");
+        let muestra = filas("SELECT nombre FROM personas TABLESAMPLE (50 PERCENT) REPEATABLE (7)");
+        assert!(!muestra.is_empty() && muestra.len() < todas.len());
+        assert!(muestra.iter().all(|fila| todas.contains(fila)));
+        assert_eq!(
+            filas("SELECT nombre FROM personas TABLESAMPLE BERNOULLI (50) REPEATABLE (7)"),
+            muestra
+        );
+        let ordenada = filas(
+            "SELECT nombre FROM personas TABLESAMPLE (50 PERCENT) REPEATABLE (7) ORDER BY nombre",
+        );
+        assert_eq!(ordenada.len(), muestra.len());
+        assert_eq!(
+            filas("SELECT COUNT(*) FROM personas TABLESAMPLE (50 PERCENT) REPEATABLE (7)"),
+            vec![vec![muestra.len().to_string()]]
+        );
+
+        assert_eq!(
+            filas("SELECT nombre FROM personas TABLESAMPLE (100 PERCENT)"),
+            todas
+        );
+        assert!(filas("SELECT nombre FROM personas TABLESAMPLE (0 PERCENT)").is_empty());
+    }
+
+    #[test]
+    fn test_orden_aleatorio() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let filas = |consulta: &str, opciones: &OpcionesArchivo| -> Vec<Vec<String>> {
+            ConsultaSelect::crear(consulta, &resolvedor, opciones)
+                .unwrap()
+                .filas()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+        let opciones = OpcionesArchivo::default();
+        let mezclar = OpcionesArchivo {
+            mezclar: true,
+            ..OpcionesArchivo::default()
+        };
+
+        let mut todas = filas("SELECT nombre, edad FROM personas", &opciones);
+        let mut aleatorias = filas(
+            "SELECT nombre, edad FROM personas ORDER BY RANDOM()",
+            &opciones,
+        );
+        let mut mezcladas = filas("SELECT nombre, edad FROM personas", &mezclar);
+        // Con 50 registros, que dos órdenes al azar coincidan con el original es imposible en la
+        // práctica.
+        assert_ne!(aleatorias, todas);
+        assert_ne!(mezcladas, todas);
+        todas.sort();
+        aleatorias.sort();
+        mezcladas.sort();
+        assert_eq!(aleatorias, todas);
+        assert_eq!(mezcladas, todas);
+
+        let ciudades = filas("SELECT ciudad FROM personas ORDER BY ciudad", &mezclar);
+        assert!(ciudades.windows(2).all(|par| par[0] <= par[1]));
+        assert_eq!(
+            filas("SELECT nombre FROM personas LIMIT 3", &mezclar).len(),
+            3
+        );
+        assert_eq!(
+            filas(
+                "SELECT COUNT(*) FROM personas WHERE nombre = ANY (SELECT nombre FROM personas)",
+                &mezclar
+            ),
+            vec![vec!["50"]]
+        );
+    }
+
+    #[test]
+    fn test_orden_natural() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla(
+            "archivos",
+            "nombre,version\nitem10,2\nitem2,1\nitem1,1\nitem02,3\nItem3,\nitem,2\n",
+        );
+        let filas = |consulta: &str| -> Vec<String> {
+            let mut consulta =
+                ConsultaSelect::crear(consulta, &tablas, &OpcionesArchivo::default()).unwrap();
+            consulta
+                .filas()
+                .unwrap()
+                .map(|fila| fila.unwrap().join(","))
+                .collect()
+        };
+
+        assert_eq!(
+            filas("SELECT nombre FROM archivos ORDER BY nombre"),
+            vec!["Item3", "item", "item02", "item1", "item10", "item2"]
+        );
+        assert_eq!(
+            filas("SELECT nombre FROM archivos ORDER BY nombre NATURAL"),
+            vec!["Item3", "item", "item1", "item2", "item02", "item10"]
+        );
+        assert_eq!(
+            filas("SELECT nombre FROM archivos ORDER BY nombre NATURAL DESC"),
+            vec!["item10", "item02", "item2", "item1", "item", "Item3"]
+        );
+        assert_eq!(
+            filas("SELECT nombre, version FROM archivos ORDER BY version NATURAL, nombre NATURAL DESC"),
+            vec!["item2,1", "item1,1", "item10,2", "item,2", "item02,3", "Item3,"]
+        );
+    }
+
+    #[test]
+    fn test_subconsultas_cuantificadas() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("productos", "nombre,precio\nA,10\nB,25\nC,40\n");
+        tablas.agregar_tabla("competencia", "precio,pais\n20,AR\n30,UY\n");
+        let filas = |consulta: &str| {
+            let mut consulta =
+                ConsultaSelect::crear(consulta, &tablas, &OpcionesArchivo::default())?;
+            let filas: Vec<Vec<String>> = consulta.filas()?.map(Result::unwrap).collect();
+            Ok::<_, errores::Errores>(filas)
+        };
+
+        assert_eq!(
+            filas(
+                "SELECT nombre FROM productos WHERE precio > ALL (SELECT precio FROM competencia)"
+            ),
+            Ok(vec![vec!["C".to_string()]])
+        );
+        assert_eq!(
+            filas(
+                "SELECT nombre FROM productos WHERE precio < ANY \
+                 (SELECT precio FROM competencia WHERE pais = 'UY') ORDER BY nombre DESC"
+            ),
+            Ok(vec![vec!["B".to_string()], vec!["A".to_string()]])
+        );
+        assert_eq!(
+            filas(
+                "SELECT nombre FROM productos WHERE precio > ALL \
+                 (SELECT precio FROM competencia WHERE pais = 'CL')"
+            )
+            .unwrap()
+            .len(),
+            3
+        );
+        assert_eq!(
+            filas("SELECT nombre FROM productos WHERE precio > ALL (SELECT * FROM competencia)"),
+            Err(errores::Errores::InvalidSyntax)
+        );
+        assert!(matches!(
+            filas("SELECT nombre FROM productos WHERE precio > ALL (SELECT precio FROM otra)"),
+            Err(errores::Errores::InvalidTable(_))
+        ));
+    }
+
+    #[test]
+    fn test_funciones_de_ventana() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla(
+            "ventas",
+            "vendedor,monto,ciudad\nAna,30,Madrid\nLuis,50,Lima\nEva,30,Lima\nJuan,10,Lima\n",
+        );
+        let filas = |consulta: &str| -> Vec<Vec<String>> {
+            ConsultaSelect::crear(consulta, &tablas, &OpcionesArchivo::default())
+                .unwrap()
+                .filas()
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        assert_eq!(
+            filas(
+                "SELECT vendedor, RANK() OVER (ORDER BY monto DESC), \
+                 DENSE_RANK() OVER (ORDER BY monto DESC) FROM ventas"
+            ),
+            vec![
+                vec!["Ana", "2", "2"],
+                vec!["Luis", "1", "1"],
+                vec!["Eva", "2", "2"],
+                vec!["Juan", "4", "3"],
+            ]
+        );
+        assert_eq!(
+            filas("SELECT vendedor FROM ventas ORDER BY ROW_NUMBER() OVER (ORDER BY monto) DESC LIMIT 2"),
+            vec![vec!["Luis"], vec!["Eva"]]
+        );
+        assert_eq!(
+            filas(
+                "SELECT vendedor, ROW_NUMBER() OVER (PARTITION BY ciudad ORDER BY monto DESC), \
+                 DENSE_RANK() OVER (PARTITION BY ciudad) FROM ventas ORDER BY ciudad, vendedor"
+            ),
+            vec![
+                vec!["Eva", "2", "1"],
+                vec!["Juan", "3", "1"],
+                vec!["Luis", "1", "1"],
+                vec!["Ana", "1", "1"],
+            ]
+        );
+        assert_eq!(
+            filas(
+                "SELECT ciudad, RANK() OVER (ORDER BY SUM(monto) DESC) FROM ventas \
+                 GROUP BY ciudad"
+            ),
+            vec![vec!["Madrid", "2"], vec!["Lima", "1"]]
+        );
+
+        for invalida in [
+            "SELECT vendedor FROM ventas WHERE RANK() OVER () = 1",
+            "SELECT IIF(RANK() OVER () = 1, 'a', 'b') FROM ventas",
+            "SELECT SUM(RANK() OVER ()) FROM ventas",
+            "SELECT vendedor FROM ventas GROUP BY RANK() OVER ()",
+            "SELECT RANK(monto) OVER () FROM ventas",
+            "SELECT RANK() OVER (monto) FROM ventas",
+            "SELECT RANK() OVER (PARTITION BY ORDER BY monto) FROM ventas",
+            "SELECT RANK() OVER (ORDER BY monto PARTITION BY ciudad) FROM ventas",
+            "SELECT RANK() FROM ventas",
+        ] {
+            assert!(
+                ConsultaSelect::crear(invalida, &tablas, &OpcionesArchivo::default())
+                    .unwrap_err()
+                    .es_de_sintaxis(),
+                "{}",
+                invalida
+            );
+        }
+    }
+
+    #[test]
+    fn test_resultado_ordena_y_limita() {
+        let criterios = vec![CriterioOrden {
+            expresion: Expresion::Columna("edad".to_string()),
+            descendente: false,
+            natural: false,
+        }];
+        let fila = |nombre: &str, edad: Valor| (vec![edad], vec![Valor::Texto(nombre.to_string())]);
+        let mut destino = Vec::new();
+        {
+            let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+            let mut resultado = Resultado::new(escritor.as_mut(), &criterios, Vec::new(), Some(2));
+            resultado.agregar(fila("Ana", Valor::Nulo)).unwrap();
+            resultado.agregar(fila("Luis", Valor::Entero(40))).unwrap();
+            resultado.agregar(fila("Eva", Valor::Entero(9))).unwrap();
+            assert!(!resultado.completo());
+            resultado.finalizar().unwrap();
+            escritor.finalizar().unwrap();
+        }
+        assert_eq!(String::from_utf8(destino).unwrap(), "Eva\nLuis\n");
+
+        let mut destino = Vec::new();
+        {
+            let mut escritor = OpcionesSalida::default().crear_escritor(&mut destino);
+            let mut resultado = Resultado::new(escritor.as_mut(), &[], Vec::new(), Some(1));
+            resultado.agregar(fila("Ana", Valor::Nulo)).unwrap();
+            assert!(resultado.completo());
+            resultado.agregar(fila("Luis", Valor::Nulo)).unwrap();
+            resultado.finalizar().unwrap();
+            escritor.finalizar().unwrap();
+        }
+        assert_eq!(String::from_utf8(destino).unwrap(), "Ana\n");
+    }
+
+    #[test]
+    fn test_evaluar_lote_en_paralelo_conserva_el_orden() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT nombre, edad FROM personas WHERE edad > 30",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        consulta.verificar_validez_consulta().unwrap();
+
+        let registros: Vec<String> = (0..10 * REGISTROS_MINIMOS_POR_HILO)
+            .map(|indice| format!("persona{},{},Madrid", indice, indice % 60))
+            .collect();
+        let plan = consulta.compilar_plan(&consulta.planificar()).unwrap();
+        let filas = consulta.evaluar_lote(&plan, &registros).unwrap();
+        assert_eq!(
+            filas,
+            consulta.evaluar_registros(&plan, &registros).unwrap()
+        );
+        assert_eq!(
+            filas[0].1,
+            vec![Valor::Texto("persona31".to_string()), Valor::Entero(31)]
+        );
+        assert_eq!(
+            filas.len(),
+            (0..registros.len())
+                .filter(|indice| indice % 60 > 30)
+                .count()
+        );
+    }
+
+    #[test]
+    fn test_lote_reutiliza_los_registros_de_distinto_ancho() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad,ciudad\n");
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT nombre, ciudad FROM personas WHERE edad > 40",
+            &tablas,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        consulta.verificar_validez_consulta().unwrap();
+        let plan = consulta.compilar_plan(&consulta.planificar()).unwrap();
+        let mut verificador = VerificadorCampos::new(&OpcionesArchivo::default(), 3);
+        let mut lector = Cursor::new(
+            "\"Pérez, Ana María \"\"la jefa\"\"\",45,\"Santiago\nde Compostela\"\n\
+             Luis,34,Lugo\n\
+             \"\",61,\n\
+             Eva,70,\"A Coruña\"\n\
+             \"Juan \"\"el largo\"\" de la Torre y Mendoza\",88,Vigo\n",
+        );
+        let mut lote = Lote::default();
+        let mut leer = |lote: &mut Lote, cantidad: usize| {
+            for _ in 0..cantidad {
+                assert!(lote.leer(&mut lector, &mut verificador).unwrap());
+            }
+            let filas = consulta.evaluar_registros(&plan, lote.registros()).unwrap();
+            lote.vaciar();
+            filas.into_iter().map(|(_, fila)| fila).collect::<Vec<_>>()
+        };
+        let texto = |texto: &str| Valor::Texto(texto.to_string());
+
+        assert_eq!(
+            leer(&mut lote, 3),
+            vec![
+                vec![
+                    texto("Pérez, Ana María \"la jefa\""),
+                    texto("Santiago\nde Compostela")
+                ],
+                vec![texto(""), Valor::Nulo],
+            ]
+        );
+        // Las cadenas ya reservadas por los registros anteriores, más largos o con comillas, no
+        // dejan restos en los registros siguientes.
+        assert_eq!(
+            leer(&mut lote, 2),
+            vec![
+                vec![texto("Eva"), texto("A Coruña")],
+                vec![
+                    texto("Juan \"el largo\" de la Torre y Mendoza"),
+                    texto("Vigo")
+                ],
+            ]
+        );
+        assert_eq!(lote.registros.len(), 3);
+        assert!(!lote.leer(&mut lector, &mut verificador).unwrap());
+    }
+
+    #[test]
+    fn test_crear_consulta_con_iif() {
+        let consulta =
+            String::from("SELECT nombre, IIF(edad > 30, 'Mayor', 'Menor') FROM personas");
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+
+        let consulta_select =
+            ConsultaSelect::crear(&consulta, &resolvedor, &OpcionesArchivo::default()).unwrap();
+
+        assert_eq!(
+            consulta_select.campos_consulta,
+            vec!["nombre", "IIF(edad > 30, 'Mayor', 'Menor')"]
+        );
+    }
+
+    #[test]
+    fn test_crear_consulta_con_expresion_invalida() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        for (consulta, error) in [
+            (
+                "SELECT IIF(edad > 30, 'a') FROM personas",
+                errores::Errores::InvalidSyntax,
+            ),
+            (
+                "SELECT nombre FROM personas WHERE",
+                errores::Errores::FinInesperado { posicion: 33 },
+            ),
+            (
+                "SELECT nombre FROM personas WHERE edad >",
+                errores::Errores::FinInesperado { posicion: 40 },
+            ),
+        ] {
+            assert_eq!(
+                ConsultaSelect::crear(consulta, &resolvedor, &OpcionesArchivo::default())
+                    .unwrap_err(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_verificar_expresion_con_columna_invalida() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT IIF(altura > 2, 'a', 'b') FROM personas",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            consulta.verificar_validez_consulta(),
+            Err(errores::Errores::ColumnaDesconocida {
+                columna: "altura".to_string(),
+                sugerencias: Vec::new()
+            })
+        );
+
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT nombre FROM personas WHERE altura > 2",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            consulta.verificar_validez_consulta(),
+            Err(errores::Errores::ColumnaDesconocida {
+                columna: "altura".to_string(),
+                sugerencias: Vec::new()
+            })
+        );
+    }
+
+    #[test]
+    fn test_crear_consulta_con_group_by() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let opciones = OpcionesArchivo::default();
+
+        let consulta = ConsultaSelect::crear(
+            "SELECT ciudad, COUNT(*) FROM personas WHERE edad > 30 GROUP BY ciudad ORDER BY ciudad",
+            &resolvedor,
+            &opciones,
+        )
+        .unwrap();
+        assert_eq!(
+            consulta.agrupamiento,
+            Some(Agrupamiento::Expresiones(vec![Expresion::Columna(
+                "ciudad".to_string()
+            )]))
+        );
+        assert_eq!(
+            consulta.criterios_orden,
+            vec![CriterioOrden {
+                expresion: Expresion::Columna("ciudad".to_string()),
+                descendente: false,
+                natural: false,
+            }]
+        );
+
+        let consulta = ConsultaSelect::crear(
+            "SELECT ciudad FROM personas GROUP BY ALL",
+            &resolvedor,
+            &opciones,
+        )
+        .unwrap();
+        assert_eq!(consulta.agrupamiento, Some(Agrupamiento::Todo));
+
+        for invalida in [
+            "SELECT ciudad FROM personas GROUP ciudad",
+            "SELECT ciudad FROM personas GROUP BY",
+            "SELECT ciudad FROM personas GROUP BY COUNT(*)",
+            "SELECT ciudad FROM personas WHERE COUNT(*) > 1",
+        ] {
+            assert!(
+                ConsultaSelect::crear(invalida, &resolvedor, &opciones)
+                    .unwrap_err()
+                    .es_de_sintaxis(),
+                "{}",
+                invalida
+            );
+        }
+    }
+
+    #[test]
+    fn test_verificar_columnas_no_agrupadas() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let opciones = OpcionesArchivo::default();
+        let verificar = |consulta: &str| {
+            ConsultaSelect::crear(consulta, &resolvedor, &opciones)
+                .unwrap()
+                .verificar_validez_consulta()
+        };
+
+        assert_eq!(
+            verificar("SELECT ciudad, nombre, COUNT(*) FROM personas GROUP BY ciudad"),
+            Err(errores::Errores::ColumnaNoAgrupada("nombre".to_string()))
+        );
+        assert_eq!(
+            verificar("SELECT edad, MAX(nombre) FROM personas"),
+            Err(errores::Errores::ColumnaNoAgrupada("edad".to_string()))
+        );
+        assert!(verificar("SELECT ciudad, MAX(edad) FROM personas GROUP BY ciudad").is_ok());
+        assert!(verificar("SELECT COUNT(*) FROM personas").is_ok());
+        assert_eq!(
+            verificar("SELECT COUNT(*) FROM personas GROUP BY altura"),
+            Err(errores::Errores::ColumnaDesconocida {
+                columna: "altura".to_string(),
+                sugerencias: Vec::new()
+            })
+        );
+    }
+
+    #[test]
+    fn test_group_by_all_agrupa_por_las_proyecciones_no_agregadas() {
+        let resolvedor = ResolvedorDirectorio::new("tablas");
+        let mut consulta = ConsultaSelect::crear(
+            "SELECT ciudad, IIF(edad > 30, 'si', 'no'), COUNT(*) FROM personas GROUP BY ALL",
+            &resolvedor,
+            &OpcionesArchivo::default(),
+        )
+        .unwrap();
+
+        assert!(consulta.verificar_validez_consulta().is_ok());
+        assert_eq!(consulta.claves_agrupamiento().len(), 2);
+        assert_eq!(
+            consulta.claves_agrupamiento()[0],
+            Expresion::Columna("ciudad".to_string())
+        );
+    }
 }
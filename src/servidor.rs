@@ -0,0 +1,690 @@
+use crate::archivo::OpcionesArchivo;
+use crate::consulta::{Resumen, SQLConsulta};
+use crate::errores;
+use crate::json;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::EscritorJson;
+use crate::sintaxis::{self, Sentencia};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Tamaño máximo de la línea de la solicitud y los encabezados, y del cuerpo, en bytes.
+const TAMANIO_MAXIMO_ENCABEZADOS: u64 = 64 * 1024;
+const TAMANIO_MAXIMO_CUERPO: usize = 1024 * 1024;
+/// Tiempo máximo que se espera a que el cliente envíe la solicitud completa, y a que reciba
+/// cada parte de la respuesta.
+const TIEMPO_MAXIMO_CONEXION: Duration = Duration::from_secs(10);
+/// Cantidad máxima de conexiones que se atienden al mismo tiempo.
+const CONEXIONES_MAXIMAS: usize = 64;
+
+/// Solicitud HTTP recibida, con lo único que el servidor necesita de ella.
+///
+/// - `metodo`: El método, como `GET` o `POST`.
+/// - `ruta`: La ruta, sin los parámetros que siguen al `?`.
+/// - `cuerpo`: El cuerpo, vacío si no tenía.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solicitud {
+    pub metodo: String,
+    pub ruta: String,
+    pub cuerpo: String,
+}
+
+/// Respuesta HTTP, con su código de estado y su cuerpo JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Respuesta {
+    pub estado: u16,
+    pub cuerpo: String,
+}
+
+impl Respuesta {
+    fn error(estado: u16, error: &errores::Errores, consulta: Option<&str>) -> Respuesta {
+        let mut cuerpo = Vec::new();
+        // Escribir en un `Vec` no falla.
+        let _ = error.escribir_json(consulta, &mut cuerpo);
+        Respuesta {
+            estado,
+            cuerpo: String::from_utf8_lossy(&cuerpo).to_string(),
+        }
+    }
+
+    /// Escribe la respuesta completa, con los encabezados, y cierra la conexión.
+    fn escribir(&self, destino: &mut dyn Write) -> io::Result<()> {
+        write!(
+            destino,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json; charset=utf-8\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.estado,
+            razon(self.estado),
+            self.cuerpo.len(),
+            self.cuerpo
+        )?;
+        destino.flush()
+    }
+}
+
+/// Atiende solicitudes HTTP en la dirección indicada, para consultar las tablas desde otros
+/// programas, como tableros:
+///
+/// - `POST /query`: Ejecuta la sentencia SQL del cuerpo y responde sus filas como una lista
+///   JSON con un objeto por fila (ver `EscritorJson`); si se permite la escritura, un
+///   `INSERT` responde `{"inserted": <registros>}`. Se admite una única sentencia por
+///   solicitud, fuera de una transacción (ver `admitir_sentencia`), y solo se leen las tablas
+///   que resuelve el resolvedor.
+/// - `GET /tables`: Responde los nombres de las tablas como una lista JSON (ver
+///   `ResolvedorTablas::tablas`).
+///
+/// Los errores se responden con el objeto de `Errores::escribir_json` y el código de estado
+/// que les corresponde. Cada conexión se atiende en su propio hilo, hasta
+/// `CONEXIONES_MAXIMAS` a la vez (ver `LimiteConexiones`), y se cierra después de responder;
+/// si el cliente tarda más de `TIEMPO_MAXIMO_CONEXION` en enviar la solicitud completa o en
+/// recibir una parte de la respuesta, la conexión se cierra sin responder (ver
+/// `ConexionConPlazo`).
+///
+/// # Parámetros
+/// - `direccion`: La dirección y el puerto en los que se escucha, como `127.0.0.1:8080`.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+/// - `permitir_escritura`: Si se admiten las sentencias que modifican las tablas.
+///
+/// # Retorno
+/// No retorna mientras el servidor esté activo; retorna `Errores::Io` si no se pudo escuchar
+/// en la dirección.
+pub fn servir(
+    direccion: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+) -> Result<(), errores::Errores> {
+    let escucha = TcpListener::bind(direccion)?;
+    eprintln!("Escuchando en http://{}", escucha.local_addr()?);
+    let limite = LimiteConexiones::new(CONEXIONES_MAXIMAS);
+    thread::scope(|hilos| {
+        for conexion in escucha.incoming() {
+            let conexion = match conexion {
+                Ok(conexion) => conexion,
+                Err(error) => {
+                    registro::advertir(format!("no se pudo aceptar una conexión: {}", error));
+                    continue;
+                }
+            };
+            let lugar = limite.ocupar();
+            hilos.spawn(move || {
+                let _lugar = lugar;
+                let resultado = ConexionConPlazo::new(&conexion, TIEMPO_MAXIMO_CONEXION).and_then(
+                    |mut conexion| atender(&mut conexion, resolvedor, opciones, permitir_escritura),
+                );
+                if let Err(error) = resultado {
+                    registro::advertir(format!("no se pudo atender una solicitud: {}", error));
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Cuenta las conexiones que se atienden al mismo tiempo, para no superar un máximo: cuando se
+/// alcanza, no se aceptan más conexiones hasta que termine alguna, y las nuevas esperan en la
+/// cola de la escucha. La usan tanto este servidor como el de PostgreSQL.
+pub struct LimiteConexiones {
+    maximo: usize,
+    activas: Mutex<usize>,
+    liberada: Condvar,
+}
+
+/// Lugar de una conexión activa en un `LimiteConexiones`, que se libera al descartarlo.
+pub struct LugarConexion<'a> {
+    limite: &'a LimiteConexiones,
+}
+
+impl LimiteConexiones {
+    pub fn new(maximo: usize) -> LimiteConexiones {
+        LimiteConexiones {
+            maximo,
+            activas: Mutex::new(0),
+            liberada: Condvar::new(),
+        }
+    }
+
+    /// Espera a que haya lugar para una conexión más y lo ocupa hasta que se descarte el
+    /// `LugarConexion` devuelto.
+    pub fn ocupar(&self) -> LugarConexion<'_> {
+        let mut activas = self.activas.lock().unwrap_or_else(PoisonError::into_inner);
+        while *activas >= self.maximo {
+            activas = self
+                .liberada
+                .wait(activas)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *activas += 1;
+        LugarConexion { limite: self }
+    }
+}
+
+impl Drop for LugarConexion<'_> {
+    fn drop(&mut self) {
+        let mut activas = self
+            .limite
+            .activas
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *activas -= 1;
+        self.limite.liberada.notify_one();
+    }
+}
+
+/// Conexión TCP con un plazo para recibir la solicitud: cada lectura espera como máximo lo que
+/// falta para el plazo, de modo que un cliente que envía la solicitud de a poco tampoco la
+/// retiene más que eso. Cada escritura espera como máximo el tiempo indicado al crearla.
+struct ConexionConPlazo<'a> {
+    conexion: &'a TcpStream,
+    plazo: Instant,
+}
+
+impl<'a> ConexionConPlazo<'a> {
+    fn new(conexion: &'a TcpStream, tiempo: Duration) -> io::Result<ConexionConPlazo<'a>> {
+        conexion.set_write_timeout(Some(tiempo))?;
+        Ok(ConexionConPlazo {
+            conexion,
+            plazo: Instant::now() + tiempo,
+        })
+    }
+}
+
+impl Read for ConexionConPlazo<'_> {
+    fn read(&mut self, destino: &mut [u8]) -> io::Result<usize> {
+        let plazo_vencido = || {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                "se venció el plazo para recibir la solicitud",
+            )
+        };
+        let restante = self.plazo.saturating_duration_since(Instant::now());
+        if restante.is_zero() {
+            return Err(plazo_vencido());
+        }
+        self.conexion.set_read_timeout(Some(restante))?;
+        // Según la plataforma, una lectura que se queda sin tiempo falla con `WouldBlock` o
+        // con `TimedOut`.
+        self.conexion
+            .read(destino)
+            .map_err(|error| match error.kind() {
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => plazo_vencido(),
+                _ => error,
+            })
+    }
+}
+
+impl Write for ConexionConPlazo<'_> {
+    fn write(&mut self, contenido: &[u8]) -> io::Result<usize> {
+        self.conexion.write(contenido)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.conexion.flush()
+    }
+}
+
+/// Lee una solicitud de la conexión, la responde y escribe la respuesta.
+///
+/// # Parámetros
+/// - `conexion`: La conexión con el cliente.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+/// - `permitir_escritura`: Si se admiten las sentencias que modifican las tablas.
+///
+/// # Retorno
+/// Retorna `Ok(())` si se respondió, aunque la respuesta sea un error, o el error de E/S de la
+/// conexión.
+pub fn atender<C: Read + Write>(
+    conexion: &mut C,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+) -> io::Result<()> {
+    let respuesta = match leer_solicitud(&mut *conexion)? {
+        Ok(solicitud) => {
+            let respuesta = responder(&solicitud, resolvedor, opciones, permitir_escritura);
+            registro::registrar(registro::DETALLE, || {
+                format!(
+                    "{} {} -> {}",
+                    solicitud.metodo, solicitud.ruta, respuesta.estado
+                )
+            });
+            respuesta
+        }
+        Err(error) => Respuesta::error(400, &error, None),
+    };
+    respuesta.escribir(conexion)
+}
+
+/// Responde una solicitud según su método y su ruta.
+///
+/// # Parámetros
+/// - `solicitud`: La solicitud recibida.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+/// - `permitir_escritura`: Si se admiten las sentencias que modifican las tablas.
+///
+/// # Retorno
+/// La `Respuesta`: 200 con el resultado, 404 si la ruta o la tabla no existen, 405 si la ruta
+/// no admite el método, o el código de estado del error de la consulta (ver `estado_de_error`).
+pub fn responder(
+    solicitud: &Solicitud,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+) -> Respuesta {
+    let resultado = match (solicitud.metodo.as_str(), solicitud.ruta.as_str()) {
+        ("POST", "/query") => {
+            consultar(&solicitud.cuerpo, resolvedor, opciones, permitir_escritura)
+        }
+        ("GET", "/tables") => resolvedor.tablas().map(|tablas| {
+            let tablas: Vec<String> = tablas.iter().map(|tabla| json::citar(tabla)).collect();
+            format!("[{}]\n", tablas.join(", "))
+        }),
+        (metodo, ruta @ ("/query" | "/tables")) => {
+            let error = errores::Errores::ArgumentoInvalido(format!(
+                "la ruta '{}' no admite el método {}",
+                ruta, metodo
+            ));
+            return Respuesta::error(405, &error, None);
+        }
+        (_, ruta) => {
+            let error =
+                errores::Errores::ArgumentoInvalido(format!("la ruta '{}' no existe", ruta));
+            return Respuesta::error(404, &error, None);
+        }
+    };
+    match resultado {
+        Ok(cuerpo) => Respuesta {
+            estado: 200,
+            cuerpo,
+        },
+        Err(error) => {
+            let consulta = Some(solicitud.cuerpo.as_str()).filter(|_| solicitud.ruta == "/query");
+            Respuesta::error(estado_de_error(&error), &error, consulta)
+        }
+    }
+}
+
+/// Ejecuta la única sentencia de la consulta y devuelve su resultado como JSON. Las tablas
+/// solo se leen con el resolvedor (ver `OpcionesArchivo::solo_tablas`), para que los clientes
+/// no lean otros archivos del servidor.
+fn consultar(
+    consulta: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    permitir_escritura: bool,
+) -> Result<String, errores::Errores> {
+    let mut sentencias = sintaxis::parsear_lote(consulta)?;
+    if sentencias.len() != 1 {
+        return Err(errores::Errores::ArgumentoInvalido(format!(
+            "se esperaba una única sentencia y se recibieron {}",
+            sentencias.len()
+        )));
+    }
+    let sentencia = sentencias.remove(0);
+    admitir_sentencia(&sentencia, permitir_escritura)?;
+    let opciones = &OpcionesArchivo {
+        solo_tablas: true,
+        ..opciones.clone()
+    };
+    let mut cuerpo = Vec::new();
+    let resumen = SQLConsulta::desde_sentencia(sentencia, resolvedor, opciones)?
+        .procesar_consulta(&mut EscritorJson::new(&mut cuerpo, None))?;
+    match resumen {
+        Resumen::Agregados(registros) => Ok(format!("{{\"inserted\": {}}}\n", registros)),
+        Resumen::Copiados(filas) => Ok(format!("{{\"copied\": {}}}\n", filas)),
+        Resumen::Filas(_) => Ok(String::from_utf8_lossy(&cuerpo).to_string()),
+    }
+}
+
+/// Verifica que el servidor admita la sentencia: no se admiten las transacciones ni `COPY`,
+/// que lee o escribe cualquier archivo del servidor, y las sentencias que modifican las tablas
+/// solo si se permite la escritura (ver `Sentencia::es_lectura`). La usan tanto este servidor
+/// como el de PostgreSQL (ver `postgres::servir`).
+///
+/// # Parámetros
+/// - `sentencia`: La sentencia recibida.
+/// - `permitir_escritura`: Si se admiten las sentencias que modifican las tablas.
+///
+/// # Retorno
+/// `Ok(())` si se admite, `Errores::TransaccionInvalida` si es parte de una transacción, o
+/// `Errores::ArgumentoInvalido` si es un `COPY`, o si modifica las tablas y no se permite la
+/// escritura.
+pub fn admitir_sentencia(
+    sentencia: &Sentencia,
+    permitir_escritura: bool,
+) -> Result<(), errores::Errores> {
+    if matches!(
+        sentencia,
+        Sentencia::Comenzar | Sentencia::Confirmar | Sentencia::Deshacer
+    ) {
+        return Err(errores::Errores::TransaccionInvalida(
+            "el servidor no admite transacciones".to_string(),
+        ));
+    }
+    if matches!(sentencia, Sentencia::CopiarA(_) | Sentencia::CopiarDesde(_)) {
+        return Err(errores::Errores::ArgumentoInvalido(
+            "el servidor no admite COPY, que lee o escribe archivos del servidor".to_string(),
+        ));
+    }
+    if !permitir_escritura && !sentencia.es_lectura() {
+        return Err(errores::Errores::ArgumentoInvalido(
+            "el servidor solo admite consultas de lectura; para modificar las tablas se debe \
+             iniciar con --allow-writes"
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Devuelve el código de estado HTTP de un error: 404 si la tabla no existe, 403 si falta
+/// permiso para leer o escribir un archivo, 409 si la tabla está bloqueada, 500 si falló la
+/// lectura o la escritura, y 400 para el resto, que son errores de la consulta.
+fn estado_de_error(error: &errores::Errores) -> u16 {
+    match error {
+        errores::Errores::InvalidTable(_) => 404,
+        errores::Errores::PermisoDenegado(_) => 403,
+        errores::Errores::TablaBloqueada(_) => 409,
+        errores::Errores::Io(_) | errores::Errores::HiloFallido(_) | errores::Errores::Error => 500,
+        _ => 400,
+    }
+}
+
+fn razon(estado: u16) -> &'static str {
+    match estado {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        _ => "Internal Server Error",
+    }
+}
+
+/// Lee la línea de la solicitud, los encabezados y el cuerpo, cuyo tamaño indica
+/// `Content-Length`.
+///
+/// # Retorno
+/// La `Solicitud`, `Errores::ArgumentoInvalido` si no es una solicitud HTTP válida o el cuerpo
+/// es demasiado grande, o el error de E/S de la conexión.
+fn leer_solicitud(conexion: &mut dyn Read) -> io::Result<Result<Solicitud, errores::Errores>> {
+    let invalida = |motivo: &str| Ok(Err(errores::Errores::ArgumentoInvalido(motivo.to_string())));
+    let mut lector = BufReader::new(conexion.take(TAMANIO_MAXIMO_ENCABEZADOS));
+    let mut linea = String::new();
+    lector.read_line(&mut linea)?;
+    let mut partes = linea.split_whitespace();
+    let (Some(metodo), Some(destino), Some(version)) =
+        (partes.next(), partes.next(), partes.next())
+    else {
+        return invalida("la solicitud HTTP no es válida");
+    };
+    if !version.starts_with("HTTP/") {
+        return invalida("la solicitud HTTP no es válida");
+    }
+    let metodo = metodo.to_uppercase();
+    let ruta = destino.split('?').next().unwrap_or_default().to_string();
+
+    let mut largo = 0;
+    loop {
+        let mut encabezado = String::new();
+        if lector.read_line(&mut encabezado)? == 0 {
+            return invalida("la solicitud HTTP terminó antes que sus encabezados");
+        }
+        let encabezado = encabezado.trim_end();
+        if encabezado.is_empty() {
+            break;
+        }
+        if let Some((nombre, valor)) = encabezado.split_once(':') {
+            if nombre.trim().eq_ignore_ascii_case("content-length") {
+                match valor.trim().parse() {
+                    Ok(valor) => largo = valor,
+                    Err(_) => return invalida("el encabezado Content-Length no es válido"),
+                }
+            }
+        }
+    }
+    if largo > TAMANIO_MAXIMO_CUERPO {
+        return invalida("el cuerpo de la solicitud es demasiado grande");
+    }
+    // Lo que el lector ya leyó de más es el comienzo del cuerpo.
+    let mut cuerpo = lector.buffer().to_vec();
+    cuerpo.truncate(largo);
+    let faltante = largo - cuerpo.len();
+    let conexion = lector.into_inner().into_inner();
+    conexion.take(faltante as u64).read_to_end(&mut cuerpo)?;
+    if cuerpo.len() < largo {
+        return invalida("la solicitud HTTP terminó antes que su cuerpo");
+    }
+    match String::from_utf8(cuerpo) {
+        Ok(cuerpo) => Ok(Ok(Solicitud {
+            metodo,
+            ruta,
+            cuerpo,
+        })),
+        Err(_) => invalida("el cuerpo de la solicitud no es UTF-8"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use std::net::TcpStream;
+    use std::thread;
+
+    fn solicitud(metodo: &str, ruta: &str, cuerpo: &str) -> Solicitud {
+        Solicitud {
+            metodo: metodo.to_string(),
+            ruta: ruta.to_string(),
+            cuerpo: cuerpo.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_responder_consultas_y_tablas() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\nLuis,25\n");
+        tablas.agregar_tabla("ventas", "producto\n");
+        let opciones = OpcionesArchivo::default();
+        let responder = |metodo: &str, ruta: &str, cuerpo: &str| {
+            responder(&solicitud(metodo, ruta, cuerpo), &tablas, &opciones, true)
+        };
+
+        assert_eq!(
+            responder(
+                "POST",
+                "/query",
+                "SELECT nombre, edad FROM personas WHERE edad > 30"
+            ),
+            Respuesta {
+                estado: 200,
+                cuerpo: "[\n  {\"nombre\": \"Ana\", \"edad\": 40}\n]\n".to_string()
+            }
+        );
+        assert_eq!(
+            responder("POST", "/query", "INSERT INTO ventas VALUES ('mesa');").cuerpo,
+            "{\"inserted\": 1}\n"
+        );
+        assert_eq!(
+            responder(
+                "POST",
+                "/query",
+                "SELECT producto FROM ventas WHERE producto = 'silla'"
+            )
+            .cuerpo,
+            "[]\n"
+        );
+        assert_eq!(
+            responder("GET", "/tables", "").cuerpo,
+            "[\"personas\", \"ventas\"]\n"
+        );
+
+        let error = responder("POST", "/query", "SELECT * FROM clientes");
+        assert_eq!(error.estado, 404);
+        assert!(error.cuerpo.starts_with("{\"code\": \"INVALID_TABLE\""));
+        assert_eq!(responder("POST", "/query", "SELECT * FROM").estado, 400);
+        assert_eq!(responder("POST", "/query", "BEGIN").estado, 400);
+        assert_eq!(
+            responder(
+                "POST",
+                "/query",
+                "SELECT 1 FROM ventas; SELECT 2 FROM ventas"
+            )
+            .estado,
+            400
+        );
+        for consulta in [
+            "COPY (SELECT * FROM personas) TO '/tmp/personas.csv'",
+            "COPY personas FROM '/etc/passwd'",
+        ] {
+            let error = responder("POST", "/query", consulta);
+            assert_eq!(error.estado, 400);
+            assert!(error.cuerpo.contains("COPY"));
+        }
+        assert_eq!(responder("GET", "/query", "").estado, 405);
+        assert_eq!(responder("GET", "/", "").estado, 404);
+    }
+
+    #[test]
+    fn test_responder_solo_lecturas_de_tablas() {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre,edad\nAna,40\n");
+        let opciones = OpcionesArchivo::default();
+        let responder = |cuerpo: &str| {
+            responder(
+                &solicitud("POST", "/query", cuerpo),
+                &tablas,
+                &opciones,
+                false,
+            )
+        };
+
+        assert_eq!(responder("SELECT nombre FROM personas").estado, 200);
+        assert_eq!(responder("CHECK TABLE personas").estado, 200);
+        for consulta in [
+            "INSERT INTO personas VALUES ('Luis', 25)",
+            "REPAIR TABLE personas",
+            "ANALYZE personas",
+        ] {
+            let error = responder(consulta);
+            assert_eq!(error.estado, 400);
+            assert!(error.cuerpo.contains("--allow-writes"));
+        }
+        assert_eq!(
+            tablas.contenido("personas").as_deref(),
+            Some("nombre,edad\nAna,40\n")
+        );
+
+        for consulta in [
+            "SELECT * FROM '/etc/passwd'",
+            "SELECT * FROM stdin",
+            "SELECT * FROM -",
+            "EXPLAIN SELECT * FROM '/etc/passwd'",
+            "SELECT nombre FROM personas WHERE nombre = ANY (SELECT * FROM '/etc/passwd')",
+        ] {
+            let error = responder(consulta);
+            assert_eq!(error.estado, 403, "{}", consulta);
+            assert!(error.cuerpo.starts_with("{\"code\": \"PERMISSION_DENIED\""));
+        }
+    }
+
+    #[test]
+    fn test_servir_por_tcp() {
+        let escucha = TcpListener::bind("127.0.0.1:0").unwrap();
+        let direccion = escucha.local_addr().unwrap();
+        let servidor = thread::spawn(move || {
+            let tablas = TablasEnMemoria::new();
+            tablas.agregar_tabla("personas", "nombre,edad\nAna,40\n");
+            let (conexion, _) = escucha.accept().unwrap();
+            let mut conexion = ConexionConPlazo::new(&conexion, TIEMPO_MAXIMO_CONEXION).unwrap();
+            atender(&mut conexion, &tablas, &OpcionesArchivo::default(), false).unwrap();
+        });
+
+        let consulta = "SELECT nombre FROM personas";
+        let mut cliente = TcpStream::connect(direccion).unwrap();
+        write!(
+            cliente,
+            "POST /query?formato=json HTTP/1.1\r\nHost: localhost\r\n\
+             content-length: {}\r\n\r\n{}",
+            consulta.len(),
+            consulta
+        )
+        .unwrap();
+        let mut respuesta = String::new();
+        cliente.read_to_string(&mut respuesta).unwrap();
+        servidor.join().unwrap();
+
+        assert!(respuesta.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(respuesta.contains("\r\nContent-Length: 24\r\n"));
+        assert!(respuesta.ends_with("\r\n\r\n[\n  {\"nombre\": \"Ana\"}\n]\n"));
+    }
+
+    #[test]
+    fn test_plazo_para_recibir_la_solicitud() {
+        let escucha = TcpListener::bind("127.0.0.1:0").unwrap();
+        let direccion = escucha.local_addr().unwrap();
+        let servidor = thread::spawn(move || {
+            let (conexion, _) = escucha.accept().unwrap();
+            let mut conexion =
+                ConexionConPlazo::new(&conexion, Duration::from_millis(300)).unwrap();
+            let inicio = Instant::now();
+            let resultado = atender(
+                &mut conexion,
+                &TablasEnMemoria::new(),
+                &OpcionesArchivo::default(),
+                false,
+            );
+            (resultado.map_err(|error| error.kind()), inicio.elapsed())
+        });
+
+        // El cliente envía un byte cada poco tiempo, sin terminar nunca los encabezados.
+        let mut cliente = TcpStream::connect(direccion).unwrap();
+        for byte in b"POST /query HTTP/1.1\r\nHost: localhost\r\n"
+            .iter()
+            .cycle()
+        {
+            if servidor.is_finished() || cliente.write_all(&[*byte]).is_err() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let (resultado, duracion) = servidor.join().unwrap();
+        assert_eq!(resultado, Err(io::ErrorKind::TimedOut));
+        assert!(duracion < Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_limite_de_conexiones() {
+        let limite = LimiteConexiones::new(2);
+        let primero = limite.ocupar();
+        let _segundo = limite.ocupar();
+        thread::scope(|hilos| {
+            let tercero = hilos.spawn(|| {
+                let _tercero = limite.ocupar();
+                Instant::now()
+            });
+            thread::sleep(Duration::from_millis(100));
+            assert!(!tercero.is_finished());
+            let liberado = Instant::now();
+            drop(primero);
+            assert!(tercero.join().unwrap() >= liberado);
+        });
+    }
+
+    #[test]
+    fn test_solicitud_invalida() {
+        let mut entrada: &[u8] = b"hola\r\n\r\n";
+        assert!(leer_solicitud(&mut entrada).unwrap().is_err());
+        let mut entrada: &[u8] = b"POST /query HTTP/1.1\r\nContent-Length: 10\r\n\r\nSELECT";
+        assert!(leer_solicitud(&mut entrada).unwrap().is_err());
+        let mut entrada: &[u8] = b"POST /query HTTP/1.1\r\nContent-Length: x\r\n\r\n";
+        assert!(leer_solicitud(&mut entrada).unwrap().is_err());
+    }
+}
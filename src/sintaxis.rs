@@ -0,0 +1,1504 @@
+use crate::archivo::RUTA_ENTRADA;
+use crate::errores;
+use crate::expresion::Expresion;
+use crate::fecha::{fecha_actual, fecha_hora_actual};
+use crate::lexico::{self, error_de_sintaxis, TipoToken, Token};
+use crate::registro;
+use crate::salida::FormatoSalida;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+/// Cantidad máxima de lotes de sentencias parseadas que se guardan (ver `parsear_lote`).
+const CAPACIDAD_CACHE: usize = 256;
+
+/// Lotes de sentencias ya parseados, por el texto de la consulta.
+static LOTES: OnceLock<Mutex<HashMap<String, Vec<Sentencia>>>> = OnceLock::new();
+
+/// Sentencia SQL reconocida por el parser.
+///
+/// - `Select`: Una consulta de selección.
+/// - `Insert`: Una inserción de registros.
+/// - `Explicar`: `EXPLAIN` seguido de otra sentencia.
+/// - `Revisar`: `CHECK TABLE` o `REPAIR TABLE`, que revisan los registros de una tabla.
+/// - `Analizar`: `ANALYZE [TABLE] tabla`, que calcula las estadísticas de una tabla.
+/// - `CopiarA`: `COPY ... TO`, que escribe el resultado de una consulta en un archivo.
+/// - `CopiarDesde`: `COPY ... FROM`, que agrega a una tabla los registros de un archivo CSV.
+/// - `Comenzar`: `BEGIN [TRANSACTION]`, que inicia una transacción.
+/// - `Confirmar`: `COMMIT`, que aplica los cambios de la transacción.
+/// - `Deshacer`: `ROLLBACK`, que descarta los cambios de la transacción.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sentencia {
+    Select(SentenciaSelect),
+    Insert(SentenciaInsert),
+    Explicar(Box<Sentencia>),
+    Revisar(SentenciaRevisar),
+    Analizar(String),
+    CopiarA(SentenciaCopiarA),
+    CopiarDesde(SentenciaCopiarDesde),
+    Comenzar,
+    Confirmar,
+    Deshacer,
+}
+
+impl Sentencia {
+    /// Indica si la sentencia solo lee las tablas: un `SELECT`, un `EXPLAIN` o un
+    /// `CHECK TABLE`. Las demás modifican las tablas o escriben otros archivos.
+    pub fn es_lectura(&self) -> bool {
+        match self {
+            Sentencia::Select(_) | Sentencia::Explicar(_) => true,
+            Sentencia::Revisar(revision) => !revision.reparar,
+            _ => false,
+        }
+    }
+}
+
+/// Escribe la sentencia en SQL, con las expresiones como en `EXPLAIN` (ver `Expresion`).
+impl fmt::Display for Sentencia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sentencia::Select(consulta) => write!(f, "{consulta}"),
+            Sentencia::Insert(insercion) => {
+                write!(f, "INSERT INTO {}", insercion.tabla)?;
+                if !insercion.columnas.is_empty() {
+                    write!(f, " ({})", insercion.columnas.join(", "))?;
+                }
+                let filas: Vec<String> = insercion
+                    .filas
+                    .iter()
+                    .map(|fila| {
+                        let valores: Vec<String> = fila
+                            .iter()
+                            .map(|valor| valor.as_deref().map_or("NULL".to_string(), literal))
+                            .collect();
+                        format!("({})", valores.join(", "))
+                    })
+                    .collect();
+                write!(f, " VALUES {}", filas.join(", "))
+            }
+            Sentencia::Explicar(explicada) => write!(f, "EXPLAIN {explicada}"),
+            Sentencia::Revisar(revision) if revision.reparar => {
+                write!(f, "REPAIR TABLE {}", revision.tabla)
+            }
+            Sentencia::Revisar(revision) => write!(f, "CHECK TABLE {}", revision.tabla),
+            Sentencia::Analizar(tabla) => write!(f, "ANALYZE TABLE {tabla}"),
+            Sentencia::CopiarA(copia) => {
+                let formato = match copia.formato {
+                    FormatoSalida::Csv => "csv",
+                    FormatoSalida::Tabla => "tabla",
+                    FormatoSalida::Markdown => "markdown",
+                    FormatoSalida::Html => "html",
+                    FormatoSalida::Xml => "xml",
+                    FormatoSalida::Yaml => "yaml",
+                    FormatoSalida::Json => "json",
+                };
+                write!(
+                    f,
+                    "COPY ({}) TO {} (FORMAT {formato}{})",
+                    copia.consulta,
+                    literal(&copia.archivo),
+                    if copia.encabezado { ", HEADER" } else { "" }
+                )
+            }
+            Sentencia::CopiarDesde(copia) => {
+                write!(f, "COPY {}", copia.tabla)?;
+                if !copia.columnas.is_empty() {
+                    write!(f, " ({})", copia.columnas.join(", "))?;
+                }
+                if copia.archivo == RUTA_ENTRADA {
+                    write!(f, " FROM STDIN (FORMAT csv")?;
+                } else {
+                    write!(f, " FROM {} (FORMAT csv", literal(&copia.archivo))?;
+                }
+                if copia.encabezado {
+                    write!(f, ", HEADER")?;
+                }
+                if let Some(delimitador) = copia.delimitador {
+                    write!(f, ", DELIMITER {}", literal(&delimitador.to_string()))?;
+                }
+                write!(f, ")")
+            }
+            Sentencia::Comenzar => write!(f, "BEGIN"),
+            Sentencia::Confirmar => write!(f, "COMMIT"),
+            Sentencia::Deshacer => write!(f, "ROLLBACK"),
+        }
+    }
+}
+
+/// Escribe un texto como literal de SQL entre comillas simples, duplicando las comillas.
+fn literal(texto: &str) -> String {
+    format!("'{}'", texto.replace('\'', "''"))
+}
+
+/// Elemento de la lista de proyecciones de un `SELECT`.
+///
+/// - `Todas`: El `*`, que selecciona todas las columnas de la tabla.
+/// - `Expresion`: Una expresión junto con el texto con el que se la nombra en el resultado:
+///   el alias que sigue a `AS` o, si no lo tiene, la expresión tal como está escrita en la
+///   consulta (ver `lexico::texto_original`). Una columna o un literal sin alias se nombran
+///   con su token, con los nombres en minúsculas.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proyeccion {
+    Todas,
+    Expresion { expresion: Expresion, texto: String },
+}
+
+/// Escribe la proyección como en `EXPLAIN`, seguida del alias si el texto con el que se la
+/// nombra es un nombre distinto de la expresión. El texto de una proyección sin alias es el de
+/// la consulta, que solo es un nombre si la expresión es una columna o un literal.
+impl fmt::Display for Proyeccion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Proyeccion::Todas => write!(f, "*"),
+            Proyeccion::Expresion { expresion, texto } => {
+                let escrita = expresion.to_string();
+                if es_nombre(texto) && !escrita.eq_ignore_ascii_case(texto) {
+                    write!(f, "{escrita} AS {texto}")
+                } else {
+                    write!(f, "{escrita}")
+                }
+            }
+        }
+    }
+}
+
+/// Cláusula `GROUP BY` de una consulta de selección.
+///
+/// - `Expresiones`: Agrupa por el valor de cada una de las expresiones listadas.
+/// - `Todo`: `GROUP BY ALL`, que agrupa por todas las proyecciones que no son agregaciones.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Agrupamiento {
+    Expresiones(Vec<Expresion>),
+    Todo,
+}
+
+/// Criterio de la cláusula `ORDER BY`: una expresión, el sentido en que se ordena y si los
+/// textos se comparan en orden natural (`ORDER BY archivo NATURAL`, con `item2` antes que
+/// `item10`).
+///
+/// Las expresiones se evalúan sobre el registro completo (o sobre el grupo, en las consultas
+/// agrupadas), de modo que se puede ordenar por columnas que no se proyectan.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriterioOrden {
+    pub expresion: Expresion,
+    pub descendente: bool,
+    pub natural: bool,
+}
+
+impl fmt::Display for CriterioOrden {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let natural = if self.natural { " NATURAL" } else { "" };
+        let sentido = if self.descendente { "DESC" } else { "ASC" };
+        write!(f, "{}{natural} {sentido}", self.expresion)
+    }
+}
+
+/// Cláusula `TABLESAMPLE` de una consulta de selección: cada registro de la tabla forma parte
+/// de la muestra con probabilidad `porcentaje / 100`, independientemente de los demás.
+///
+/// - `porcentaje`: El porcentaje de registros que se espera obtener, entre 0 y 100.
+/// - `semilla`: La semilla de `REPEATABLE`, con la que la muestra es siempre la misma; sin ella,
+///   cada ejecución toma una muestra distinta.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Muestra {
+    pub porcentaje: f64,
+    pub semilla: Option<u64>,
+}
+
+/// Sentencia `SELECT` parseada.
+///
+/// - `proyecciones`: Las expresiones que se seleccionan, en orden.
+/// - `tabla`: El nombre de la tabla del `FROM`, o la ruta de su archivo si `archivo` es `true`.
+/// - `archivo`: Si el `FROM` indica la ruta de un archivo entre comillas
+///   (`FROM '/datos/export.csv'`), que se lee sin pasar por el resolvedor de tablas.
+/// - `muestra`: La cláusula `TABLESAMPLE`, si la consulta toma solo una muestra de la tabla.
+/// - `restricciones`: La condición del `WHERE`, si la consulta la tiene.
+/// - `agrupamiento`: La cláusula `GROUP BY`, si la consulta la tiene.
+/// - `condicion_grupos`: La condición del `HAVING` que deben cumplir los grupos, si la consulta
+///   la tiene.
+/// - `criterios_orden`: Los criterios del `ORDER BY`, vacío si la consulta no ordena.
+/// - `limite`: La cantidad del `LIMIT`, si la consulta la tiene.
+///
+/// Los alias de las proyecciones ya están resueltos en `condicion_grupos` y en
+/// `criterios_orden` (ver `resolver_alias`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenciaSelect {
+    pub proyecciones: Vec<Proyeccion>,
+    pub tabla: String,
+    pub archivo: bool,
+    pub muestra: Option<Muestra>,
+    pub restricciones: Option<Expresion>,
+    pub agrupamiento: Option<Agrupamiento>,
+    pub condicion_grupos: Option<Expresion>,
+    pub criterios_orden: Vec<CriterioOrden>,
+    pub limite: Option<usize>,
+}
+
+impl fmt::Display for SentenciaSelect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let proyecciones: Vec<String> = self
+            .proyecciones
+            .iter()
+            .map(|proyeccion| proyeccion.to_string())
+            .collect();
+        write!(f, "SELECT {} FROM ", proyecciones.join(", "))?;
+        if self.archivo {
+            write!(f, "{}", literal(&self.tabla))?;
+        } else {
+            write!(f, "{}", self.tabla)?;
+        }
+        if let Some(muestra) = &self.muestra {
+            write!(f, " TABLESAMPLE BERNOULLI ({})", muestra.porcentaje)?;
+            if let Some(semilla) = muestra.semilla {
+                write!(f, " REPEATABLE ({semilla})")?;
+            }
+        }
+        if let Some(restricciones) = &self.restricciones {
+            write!(f, " WHERE {restricciones}")?;
+        }
+        match &self.agrupamiento {
+            Some(Agrupamiento::Todo) => write!(f, " GROUP BY ALL")?,
+            Some(Agrupamiento::Expresiones(expresiones)) => {
+                let expresiones: Vec<String> = expresiones
+                    .iter()
+                    .map(|expresion| expresion.to_string())
+                    .collect();
+                write!(f, " GROUP BY {}", expresiones.join(", "))?;
+            }
+            None => {}
+        }
+        if let Some(condicion_grupos) = &self.condicion_grupos {
+            write!(f, " HAVING {condicion_grupos}")?;
+        }
+        if !self.criterios_orden.is_empty() {
+            let criterios: Vec<String> = self
+                .criterios_orden
+                .iter()
+                .map(|criterio| criterio.to_string())
+                .collect();
+            write!(f, " ORDER BY {}", criterios.join(", "))?;
+        }
+        if let Some(limite) = self.limite {
+            write!(f, " LIMIT {limite}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sentencia `INSERT INTO` parseada.
+///
+/// - `tabla`: El nombre de la tabla.
+/// - `columnas`: Las columnas listadas entre paréntesis; vacío si la sentencia no las lista.
+/// - `filas`: Los valores de cada fila del `VALUES`, donde `None` representa `NULL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenciaInsert {
+    pub tabla: String,
+    pub columnas: Vec<String>,
+    pub filas: Vec<Vec<Option<String>>>,
+}
+
+/// Sentencia `CHECK TABLE` o `REPAIR TABLE` parseada.
+///
+/// - `tabla`: El nombre de la tabla.
+/// - `reparar`: Si es `REPAIR TABLE`, que además de informar los problemas los corrige.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenciaRevisar {
+    pub tabla: String,
+    pub reparar: bool,
+}
+
+/// Sentencia `COPY (consulta) TO 'archivo'` o `COPY tabla TO 'archivo'` parseada, con las
+/// opciones `[WITH] [(] FORMAT formato [,] HEADER [)]`.
+///
+/// - `consulta`: La consulta cuyo resultado se escribe; con una tabla, `SELECT * FROM tabla`.
+/// - `archivo`: La ruta del archivo en el que se escribe el resultado.
+/// - `formato`: El formato del archivo, de `FORMAT`; por defecto `csv` (ver `FormatoSalida`).
+/// - `encabezado`: Si con `HEADER` el formato `csv` escribe primero la fila con los nombres de
+///   las columnas, que los demás formatos ya escriben.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenciaCopiarA {
+    pub consulta: SentenciaSelect,
+    pub archivo: String,
+    pub formato: FormatoSalida,
+    pub encabezado: bool,
+}
+
+/// Sentencia `COPY tabla [(columnas)] FROM 'archivo'` parseada, con las opciones
+/// `[WITH] [(] FORMAT csv [,] HEADER [,] DELIMITER 'c' [)]`. El archivo puede ser `STDIN`,
+/// que se lee de la entrada estándar.
+///
+/// - `tabla`: El nombre de la tabla a la que se agregan los registros.
+/// - `columnas`: Las columnas listadas entre paréntesis, en el orden de los campos del archivo;
+///   vacío si la sentencia no las lista.
+/// - `archivo`: La ruta del archivo CSV, o `-` para la entrada estándar.
+/// - `encabezado`: Si con `HEADER` la primera línea del archivo es un encabezado.
+/// - `delimitador`: El delimitador de `DELIMITER`, si se indicó.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenciaCopiarDesde {
+    pub tabla: String,
+    pub columnas: Vec<String>,
+    pub archivo: String,
+    pub encabezado: bool,
+    pub delimitador: Option<char>,
+}
+
+/// Parsea una consulta SQL completa.
+///
+/// # Parámetros
+/// - `consulta`: El texto de la consulta.
+///
+/// # Retorno
+/// La `Sentencia` de la consulta, `Errores::TokenInesperado` con el primer token que no respeta
+/// la gramática de ninguna sentencia soportada, o `Errores::FinInesperado` si la consulta
+/// termina antes de completarse. La sentencia puede terminar en `;`.
+pub fn parsear(consulta: &str) -> Result<Sentencia, errores::Errores> {
+    parsear_tokens(&lexico::analizar(consulta))
+}
+
+/// Parsea una sentencia ya dividida en tokens (ver `parsear`).
+///
+/// # Parámetros
+/// - `tokens`: Los tokens de la sentencia, tal como los devuelve `lexico::analizar`.
+///
+/// # Retorno
+/// La `Sentencia`, o el error de sintaxis del primer token que no respeta la gramática.
+pub fn parsear_tokens(tokens: &[Token]) -> Result<Sentencia, errores::Errores> {
+    let mut index = 0;
+    let sentencia = parsear_sentencia(tokens, &mut index)?;
+    consumir(tokens, &mut index, ";");
+    if index != tokens.len() {
+        return Err(error_de_sintaxis(tokens, index));
+    }
+    Ok(sentencia)
+}
+
+/// Parsea un lote de sentencias separadas por `;`.
+///
+/// # Parámetros
+/// - `consulta`: El texto de las sentencias; puede terminar en `;`.
+///
+/// Los lotes parseados se guardan en una caché por el texto de la consulta, de modo que al
+/// ejecutar muchas veces la misma consulta desde la biblioteca no se vuelve a parsear. No se
+/// guardan los que usan `CURRENT_DATE` o `NOW()`, que en los `VALUES` se evalúan al parsear.
+/// Si la caché se llena, se vacía.
+///
+/// # Retorno
+/// Las sentencias en el orden en que aparecen, o el error de sintaxis de la primera sentencia
+/// que no se pudo parsear (ver `parsear`).
+pub fn parsear_lote(consulta: &str) -> Result<Vec<Sentencia>, errores::Errores> {
+    let cache = LOTES.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(sentencias) = cache
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(consulta)
+    {
+        registro::registrar(registro::DEPURACION, || {
+            "sentencias tomadas de la caché".to_string()
+        });
+        return Ok(sentencias.clone());
+    }
+    let tokens = lexico::analizar(consulta);
+    let sentencias = parsear_tokens_lote(&tokens)?;
+    let usa_fecha = tokens.iter().any(|token| {
+        token.tipo == TipoToken::Identificador && ["current_date", "now"].contains(&&*token.texto)
+    });
+    if !usa_fecha {
+        let mut cache = cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if cache.len() >= CAPACIDAD_CACHE {
+            cache.clear();
+        }
+        cache.insert(consulta.to_string(), sentencias.clone());
+    }
+    Ok(sentencias)
+}
+
+/// Parsea los tokens de un lote de sentencias separadas por `;` (ver `parsear_lote`).
+fn parsear_tokens_lote(tokens: &[Token]) -> Result<Vec<Sentencia>, errores::Errores> {
+    let mut index = 0;
+    let mut sentencias = Vec::new();
+    loop {
+        while consumir(tokens, &mut index, ";") {}
+        if index == tokens.len() {
+            return Ok(sentencias);
+        }
+        sentencias.push(parsear_sentencia(tokens, &mut index)?);
+        if index != tokens.len() {
+            esperar(tokens, &mut index, ";")?;
+        }
+    }
+}
+
+/// Parsea la sentencia `SELECT` de una subconsulta, ya sin los paréntesis que la encierran
+/// (ver `expresion::Subconsulta`).
+///
+/// # Parámetros
+/// - `tokens`: Los tokens de la subconsulta.
+///
+/// # Retorno
+/// La `SentenciaSelect`, o el error de sintaxis del primer token que no respeta la gramática.
+pub fn parsear_subconsulta(tokens: &[Token]) -> Result<SentenciaSelect, errores::Errores> {
+    let mut index = 0;
+    let sentencia = parsear_select(tokens, &mut index)?;
+    if index != tokens.len() {
+        return Err(error_de_sintaxis(tokens, index));
+    }
+    Ok(sentencia)
+}
+
+fn parsear_sentencia(tokens: &[Token], index: &mut usize) -> Result<Sentencia, errores::Errores> {
+    if consumir(tokens, index, "explain") {
+        return Ok(Sentencia::Explicar(Box::new(parsear_sentencia(
+            tokens, index,
+        )?)));
+    }
+    if consumir(tokens, index, "begin") {
+        consumir(tokens, index, "transaction");
+        return Ok(Sentencia::Comenzar);
+    }
+    if consumir(tokens, index, "commit") {
+        return Ok(Sentencia::Confirmar);
+    }
+    if consumir(tokens, index, "rollback") {
+        return Ok(Sentencia::Deshacer);
+    }
+    match tokens.get(*index) {
+        Some(token) if token.es("select") => Ok(Sentencia::Select(parsear_select(tokens, index)?)),
+        Some(token) if token.es("insert") => Ok(Sentencia::Insert(parsear_insert(tokens, index)?)),
+        Some(token) if token.es("check") || token.es("repair") => {
+            let reparar = token.es("repair");
+            *index += 1;
+            esperar(tokens, index, "table")?;
+            Ok(Sentencia::Revisar(SentenciaRevisar {
+                tabla: parsear_nombre(tokens, index)?,
+                reparar,
+            }))
+        }
+        Some(token) if token.es("copy") => {
+            *index += 1;
+            parsear_copiar(tokens, index)
+        }
+        Some(token) if token.es("analyze") => {
+            *index += 1;
+            consumir(tokens, index, "table");
+            Ok(Sentencia::Analizar(parsear_nombre(tokens, index)?))
+        }
+        _ => Err(error_de_sintaxis(tokens, *index)),
+    }
+}
+
+/// Parsea el resto de `COPY (consulta) TO 'archivo'`, `COPY tabla TO 'archivo'` o
+/// `COPY tabla [(columnas)] FROM 'archivo'`, con sus opciones (ver `SentenciaCopiarA` y
+/// `SentenciaCopiarDesde`).
+fn parsear_copiar(tokens: &[Token], index: &mut usize) -> Result<Sentencia, errores::Errores> {
+    let consulta = if consumir(tokens, index, "(") {
+        let inicio = *index;
+        let mut profundidad = 1;
+        while profundidad > 0 {
+            let token = tokens
+                .get(*index)
+                .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+            if token.es("(") {
+                profundidad += 1;
+            } else if token.es(")") {
+                profundidad -= 1;
+            }
+            *index += 1;
+        }
+        Some(parsear_subconsulta(&tokens[inicio..*index - 1])?)
+    } else {
+        None
+    };
+    let (tabla, columnas) = match consulta {
+        Some(_) => (String::new(), Vec::new()),
+        None => {
+            let tabla = parsear_nombre(tokens, index)?;
+            let mut columnas = Vec::new();
+            if tokens.get(*index).is_some_and(|token| token.es("(")) {
+                columnas = parsear_lista(tokens, index, parsear_nombre)?;
+            }
+            (tabla, columnas)
+        }
+    };
+    let desde = consulta.is_none() && consumir(tokens, index, "from");
+    if !desde {
+        if !columnas.is_empty() {
+            return Err(error_de_sintaxis(tokens, *index));
+        }
+        esperar(tokens, index, "to")?;
+    }
+    let archivo = if desde && consumir(tokens, index, "stdin") {
+        RUTA_ENTRADA.to_string()
+    } else {
+        let archivo = parsear_texto(tokens, index)?;
+        if archivo.is_empty() {
+            return Err(error_de_sintaxis(tokens, *index - 1));
+        }
+        archivo
+    };
+
+    let mut formato = FormatoSalida::Csv;
+    let mut encabezado = false;
+    let mut delimitador = None;
+    consumir(tokens, index, "with");
+    let entre_parentesis = consumir(tokens, index, "(");
+    loop {
+        if consumir(tokens, index, "format") {
+            formato = tokens
+                .get(*index)
+                .and_then(|token| FormatoSalida::desde_nombre(&token.texto))
+                .filter(|formato| !desde || *formato == FormatoSalida::Csv)
+                .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+            *index += 1;
+        } else if consumir(tokens, index, "header") {
+            encabezado = true;
+        } else if desde && consumir(tokens, index, "delimiter") {
+            let valor = parsear_texto(tokens, index)?;
+            let mut caracteres = valor.chars();
+            match (caracteres.next(), caracteres.next()) {
+                (Some(caracter), None) if !matches!(caracter, '"' | '\n' | '\r') => {
+                    delimitador = Some(caracter)
+                }
+                _ => return Err(error_de_sintaxis(tokens, *index - 1)),
+            }
+        } else {
+            break;
+        }
+        consumir(tokens, index, ",");
+    }
+    if entre_parentesis {
+        esperar(tokens, index, ")")?;
+    }
+    if desde {
+        return Ok(Sentencia::CopiarDesde(SentenciaCopiarDesde {
+            tabla,
+            columnas,
+            archivo,
+            encabezado,
+            delimitador,
+        }));
+    }
+    let consulta = consulta.unwrap_or_else(|| SentenciaSelect {
+        proyecciones: vec![Proyeccion::Todas],
+        tabla,
+        archivo: false,
+        muestra: None,
+        restricciones: None,
+        agrupamiento: None,
+        condicion_grupos: None,
+        criterios_orden: Vec::new(),
+        limite: None,
+    });
+    Ok(Sentencia::CopiarA(SentenciaCopiarA {
+        consulta,
+        archivo,
+        formato,
+        encabezado,
+    }))
+}
+
+/// Parsea un texto entre comillas simples, como la ruta de un archivo.
+fn parsear_texto(tokens: &[Token], index: &mut usize) -> Result<String, errores::Errores> {
+    let texto = tokens
+        .get(*index)
+        .filter(|token| token.tipo == TipoToken::Texto)
+        .and_then(Token::valor_texto)
+        .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+    *index += 1;
+    Ok(texto)
+}
+
+/// Parsea el resto de `TABLESAMPLE [BERNOULLI | SYSTEM] (porcentaje [PERCENT])
+/// [REPEATABLE (semilla)]`. Los dos métodos toman cada registro por separado: las tablas no
+/// se guardan en bloques que se puedan saltear.
+fn parsear_muestra(tokens: &[Token], index: &mut usize) -> Result<Muestra, errores::Errores> {
+    if !consumir(tokens, index, "bernoulli") {
+        consumir(tokens, index, "system");
+    }
+    esperar(tokens, index, "(")?;
+    let porcentaje = tokens
+        .get(*index)
+        .filter(|token| token.tipo == TipoToken::Numero)
+        .and_then(|token| token.texto.parse::<f64>().ok())
+        .filter(|porcentaje| (0.0..=100.0).contains(porcentaje))
+        .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+    *index += 1;
+    consumir(tokens, index, "percent");
+    esperar(tokens, index, ")")?;
+    let mut semilla = None;
+    if consumir(tokens, index, "repeatable") {
+        esperar(tokens, index, "(")?;
+        semilla = Some(
+            tokens
+                .get(*index)
+                .filter(|token| token.tipo == TipoToken::Numero)
+                .and_then(|token| token.texto.parse::<u64>().ok())
+                .ok_or_else(|| error_de_sintaxis(tokens, *index))?,
+        );
+        *index += 1;
+        esperar(tokens, index, ")")?;
+    }
+    Ok(Muestra {
+        porcentaje,
+        semilla,
+    })
+}
+
+/// Parsea `SELECT proyecciones FROM tabla [TABLESAMPLE ...] [WHERE ...] [GROUP BY ...]
+/// [HAVING ...] [ORDER BY ...] [LIMIT n]`, con las cláusulas en ese orden. La tabla puede ser
+/// también la ruta de un archivo entre comillas simples.
+fn parsear_select(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<SentenciaSelect, errores::Errores> {
+    esperar(tokens, index, "select")?;
+    let proyecciones = parsear_clausula(tokens, index, &["from"], parsear_proyecciones)?;
+    esperar(tokens, index, "from")?;
+    let archivo = tokens
+        .get(*index)
+        .is_some_and(|token| token.tipo == TipoToken::Texto);
+    let tabla = if archivo {
+        let ruta = tokens[*index]
+            .valor_texto()
+            .filter(|ruta| !ruta.is_empty())
+            .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+        *index += 1;
+        ruta
+    } else {
+        parsear_nombre(tokens, index)?
+    };
+    let muestra = if consumir(tokens, index, "tablesample") {
+        Some(parsear_muestra(tokens, index)?)
+    } else {
+        None
+    };
+
+    let mut restricciones = None;
+    if consumir(tokens, index, "where") {
+        let condicion = parsear_clausula(
+            tokens,
+            index,
+            &["group", "having", "order", "limit"],
+            Expresion::parsear,
+        )?;
+        if condicion.contiene_agregacion() || condicion.contiene_ventana() {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+        restricciones = Some(condicion);
+    }
+    let mut agrupamiento = None;
+    if consumir(tokens, index, "group") {
+        esperar(tokens, index, "by")?;
+        agrupamiento = Some(parsear_clausula(
+            tokens,
+            index,
+            &["having", "order", "limit"],
+            parsear_agrupamiento,
+        )?);
+    }
+    let mut condicion_grupos = None;
+    if consumir(tokens, index, "having") {
+        condicion_grupos = Some(parsear_clausula(
+            tokens,
+            index,
+            &["order", "limit"],
+            Expresion::parsear,
+        )?);
+    }
+    let mut criterios_orden = Vec::new();
+    if consumir(tokens, index, "order") {
+        esperar(tokens, index, "by")?;
+        criterios_orden = parsear_clausula(tokens, index, &["limit"], parsear_criterios_orden)?;
+    }
+    resolver_alias(
+        &proyecciones,
+        condicion_grupos.as_mut(),
+        &mut criterios_orden,
+    )?;
+    let mut limite = None;
+    if consumir(tokens, index, "limit") {
+        let cantidad = tokens
+            .get(*index)
+            .filter(|token| token.tipo == TipoToken::Numero)
+            .and_then(|token| token.texto.parse::<usize>().ok())
+            .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+        *index += 1;
+        limite = Some(cantidad);
+    }
+
+    Ok(SentenciaSelect {
+        proyecciones,
+        tabla,
+        archivo,
+        muestra,
+        restricciones,
+        agrupamiento,
+        condicion_grupos,
+        criterios_orden,
+        limite,
+    })
+}
+
+/// Interpreta la lista de proyecciones: un `*` solo, o expresiones separadas por comas, cada
+/// una con un alias opcional (`expresion AS alias`).
+fn parsear_proyecciones(tokens: &[Token]) -> Result<Vec<Proyeccion>, errores::Errores> {
+    if let [token] = tokens {
+        if token.es("*") {
+            return Ok(vec![Proyeccion::Todas]);
+        }
+    }
+    let mut proyecciones = Vec::new();
+    for tokens_proyeccion in separar_por_comas(tokens) {
+        let (tokens_expresion, texto) = match tokens_proyeccion {
+            [expresion @ .., palabra, _] if palabra.es("as") => {
+                let mut index = tokens_proyeccion.len() - 1;
+                (expresion, parsear_nombre(tokens_proyeccion, &mut index)?)
+            }
+            [token] => (tokens_proyeccion, token.texto.to_string()),
+            _ => (tokens_proyeccion, lexico::texto_original(tokens_proyeccion)),
+        };
+        proyecciones.push(Proyeccion::Expresion {
+            expresion: parsear_con_ventana(tokens_expresion)?,
+            texto,
+        });
+    }
+    if proyecciones.is_empty() {
+        return Err(error_de_sintaxis(tokens, 0));
+    }
+    Ok(proyecciones)
+}
+
+/// Interpreta la lista que sigue a `GROUP BY`: `ALL` o expresiones separadas por comas, que no
+/// pueden ser agregaciones.
+fn parsear_agrupamiento(tokens: &[Token]) -> Result<Agrupamiento, errores::Errores> {
+    if let [token] = tokens {
+        if token.es("all") {
+            return Ok(Agrupamiento::Todo);
+        }
+    }
+    let mut expresiones = Vec::new();
+    for clave in separar_por_comas(tokens) {
+        let expresion = Expresion::parsear(clave)?;
+        if expresion.contiene_agregacion() || expresion.contiene_ventana() {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+        expresiones.push(expresion);
+    }
+    if expresiones.is_empty() {
+        return Err(error_de_sintaxis(tokens, 0));
+    }
+    Ok(Agrupamiento::Expresiones(expresiones))
+}
+
+/// Reemplaza, en el `HAVING` y en los criterios del `ORDER BY`, las columnas que nombran una
+/// proyección (por su alias o por su texto) por la expresión de esa proyección, de modo que
+/// `SELECT COUNT(*) AS total ... ORDER BY total` ordena por `COUNT(*)`. Si un alias coincide con
+/// una columna de la tabla, prevalece el alias.
+///
+/// # Retorno
+/// Retorna `Ok(())` o `Errores::InvalidSyntax` si el reemplazo deja una función de ventana en
+/// el `HAVING` o dentro de otra expresión de un criterio de orden.
+fn resolver_alias(
+    proyecciones: &[Proyeccion],
+    condicion_grupos: Option<&mut Expresion>,
+    criterios_orden: &mut [CriterioOrden],
+) -> Result<(), errores::Errores> {
+    let mut alias = HashMap::new();
+    for proyeccion in proyecciones {
+        if let Proyeccion::Expresion { expresion, texto } = proyeccion {
+            alias
+                .entry(texto.to_string())
+                .or_insert_with(|| expresion.clone());
+        }
+    }
+    if let Some(condicion) = condicion_grupos {
+        condicion.reemplazar_columnas(&alias);
+        if condicion.contiene_ventana() {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+    }
+    for criterio in criterios_orden {
+        criterio.expresion.reemplazar_columnas(&alias);
+        if criterio.expresion.contiene_ventana()
+            && !matches!(criterio.expresion, Expresion::Ventana(_))
+        {
+            return Err(errores::Errores::InvalidSyntax);
+        }
+    }
+    Ok(())
+}
+
+/// Parsea una proyección o un criterio de orden, que puede ser una función de ventana siempre
+/// que sea la expresión completa (ver `Ventana`).
+fn parsear_con_ventana(tokens: &[Token]) -> Result<Expresion, errores::Errores> {
+    let expresion = Expresion::parsear(tokens)?;
+    if expresion.contiene_ventana() && !matches!(expresion, Expresion::Ventana(_)) {
+        return Err(errores::Errores::InvalidSyntax);
+    }
+    Ok(expresion)
+}
+
+/// Interpreta los criterios de `ORDER BY`, separados por comas, con el modo `NATURAL` y el
+/// sentido opcionales (`expresion [NATURAL] [ASC | DESC]`). Se usa
+/// también para el `ORDER BY` de las funciones de ventana.
+pub fn parsear_criterios_orden(tokens: &[Token]) -> Result<Vec<CriterioOrden>, errores::Errores> {
+    let mut criterios = Vec::new();
+    for mut tokens_criterio in separar_por_comas(tokens) {
+        let descendente = tokens_criterio.last().is_some_and(|token| token.es("desc"));
+        if let Some((ultimo, resto)) = tokens_criterio.split_last() {
+            if ultimo.es("asc") || ultimo.es("desc") {
+                tokens_criterio = resto;
+            }
+        }
+        let natural = tokens_criterio.len() > 1
+            && tokens_criterio
+                .last()
+                .is_some_and(|token| token.es("natural"));
+        if natural {
+            tokens_criterio = &tokens_criterio[..tokens_criterio.len() - 1];
+        }
+        criterios.push(CriterioOrden {
+            expresion: parsear_con_ventana(tokens_criterio)?,
+            descendente,
+            natural,
+        });
+    }
+    if criterios.is_empty() {
+        return Err(error_de_sintaxis(tokens, 0));
+    }
+    Ok(criterios)
+}
+
+/// Parsea `INSERT INTO tabla [(columnas)] VALUES (valores) [, (valores) ...]`.
+fn parsear_insert(
+    tokens: &[Token],
+    index: &mut usize,
+) -> Result<SentenciaInsert, errores::Errores> {
+    esperar(tokens, index, "insert")?;
+    esperar(tokens, index, "into")?;
+    let tabla = parsear_nombre(tokens, index)?;
+    let mut columnas = Vec::new();
+    if tokens.get(*index).is_some_and(|token| token.es("(")) {
+        columnas = parsear_lista(tokens, index, parsear_nombre)?;
+    }
+    esperar(tokens, index, "values")?;
+    let mut filas = vec![parsear_lista(tokens, index, parsear_valor)?];
+    while consumir(tokens, index, ",") {
+        filas.push(parsear_lista(tokens, index, parsear_valor)?);
+    }
+    Ok(SentenciaInsert {
+        tabla,
+        columnas,
+        filas,
+    })
+}
+
+/// Parsea una lista de elementos separados por comas entre paréntesis.
+fn parsear_lista<T>(
+    tokens: &[Token],
+    index: &mut usize,
+    parsear_elemento: fn(&[Token], &mut usize) -> Result<T, errores::Errores>,
+) -> Result<Vec<T>, errores::Errores> {
+    esperar(tokens, index, "(")?;
+    let mut elementos = vec![parsear_elemento(tokens, index)?];
+    while consumir(tokens, index, ",") {
+        elementos.push(parsear_elemento(tokens, index)?);
+    }
+    esperar(tokens, index, ")")?;
+    Ok(elementos)
+}
+
+/// Indica si el texto es exactamente un nombre de tabla o de columna como los que acepta
+/// `parsear_nombre`, de modo que se puede escribir en una sentencia sin agregarle nada más.
+pub fn es_nombre(texto: &str) -> bool {
+    let tokens = lexico::analizar(texto);
+    tokens.len() == 1
+        && tokens[0].texto == texto.to_lowercase()
+        && parsear_nombre(&tokens, &mut 0).is_ok()
+}
+
+/// Parsea el nombre de una tabla o de una columna. Se aceptan también palabras que no son
+/// identificadores válidos (como `ventas-2024`), pero no palabras clave ni símbolos.
+fn parsear_nombre(tokens: &[Token], index: &mut usize) -> Result<String, errores::Errores> {
+    match tokens.get(*index) {
+        Some(token)
+            if matches!(
+                token.tipo,
+                TipoToken::Identificador | TipoToken::Desconocido | TipoToken::Numero
+            ) =>
+        {
+            *index += 1;
+            Ok(token.texto.to_string())
+        }
+        _ => Err(error_de_sintaxis(tokens, *index)),
+    }
+}
+
+/// Parsea un valor a insertar: `NULL` sin comillas es nulo y los literales entre comillas
+/// simples se toman sin ellas; `CURRENT_DATE` y `NOW()` toman la fecha, o la fecha y hora, del
+/// momento en que se parsea la sentencia. El resto de las palabras se toman tal como están.
+fn parsear_valor(tokens: &[Token], index: &mut usize) -> Result<Option<String>, errores::Errores> {
+    let token = tokens
+        .get(*index)
+        .ok_or_else(|| error_de_sintaxis(tokens, *index))?;
+    let con_parentesis = tokens.get(*index + 1).is_some_and(|token| token.es("("))
+        && tokens.get(*index + 2).is_some_and(|token| token.es(")"));
+    let fecha = match token.texto.as_str() {
+        "current_date" if token.tipo == TipoToken::Identificador => Some(fecha_actual()),
+        "now" if token.tipo == TipoToken::Identificador && con_parentesis => {
+            Some(fecha_hora_actual())
+        }
+        _ => None,
+    };
+    if fecha.is_some() {
+        *index += if con_parentesis { 3 } else { 1 };
+        return Ok(fecha);
+    }
+    let valor = match token.tipo {
+        TipoToken::Texto | TipoToken::Parametro => Some(
+            token
+                .valor_texto()
+                .ok_or_else(|| error_de_sintaxis(tokens, *index))?,
+        ),
+        TipoToken::PalabraClave if token.es("null") => None,
+        TipoToken::Numero | TipoToken::Identificador | TipoToken::Desconocido => {
+            Some(token.texto.to_string())
+        }
+        _ => return Err(error_de_sintaxis(tokens, *index)),
+    };
+    *index += 1;
+    Ok(valor)
+}
+
+/// Avanza sobre el token si es la palabra clave o el símbolo indicado.
+///
+/// # Retorno
+/// `true` si el token coincidía y se consumió.
+fn consumir(tokens: &[Token], index: &mut usize, texto: &str) -> bool {
+    let coincide = tokens.get(*index).is_some_and(|token| token.es(texto));
+    if coincide {
+        *index += 1;
+    }
+    coincide
+}
+
+/// Consume la palabra clave o el símbolo indicado, que debe ser el siguiente token.
+fn esperar(tokens: &[Token], index: &mut usize, texto: &str) -> Result<(), errores::Errores> {
+    if consumir(tokens, index, texto) {
+        Ok(())
+    } else {
+        Err(error_de_sintaxis(tokens, *index))
+    }
+}
+
+/// Parsea una cláusula con los tokens que toma `hasta_clausula`.
+///
+/// Si los tokens de la cláusula se acaban antes de completarla, el error indica el token que
+/// la cierra (por ejemplo, el `FROM` de `SELECT nombre, FROM`), o el final de la consulta.
+fn parsear_clausula<T>(
+    tokens: &[Token],
+    index: &mut usize,
+    fin: &[&str],
+    parsear: fn(&[Token]) -> Result<T, errores::Errores>,
+) -> Result<T, errores::Errores> {
+    match parsear(hasta_clausula(tokens, index, fin)) {
+        Err(errores::Errores::FinInesperado { .. }) => Err(error_de_sintaxis(tokens, *index)),
+        resultado => resultado,
+    }
+}
+
+/// Toma los tokens hasta la siguiente palabra clave de `fin` que no esté entre paréntesis, o
+/// hasta el `;` o el final de la sentencia.
+///
+/// # Retorno
+/// Los tokens tomados; `index` queda en la palabra clave que cierra la cláusula.
+fn hasta_clausula<'a>(tokens: &'a [Token], index: &mut usize, fin: &[&str]) -> &'a [Token] {
+    let inicio = *index;
+    let mut profundidad = 0;
+    while let Some(token) = tokens.get(*index) {
+        if token.es("(") {
+            profundidad += 1;
+        } else if token.es(")") {
+            profundidad -= 1;
+        } else if profundidad == 0 && (token.es(";") || fin.iter().any(|palabra| token.es(palabra)))
+        {
+            break;
+        }
+        *index += 1;
+    }
+    &tokens[inicio..*index]
+}
+
+/// Separa una lista de expresiones por las comas que no están entre paréntesis.
+///
+/// # Parámetros
+/// - `tokens`: Los tokens de la lista.
+///
+/// # Retorno
+/// Los tokens de cada expresión, en orden; vacío si la lista no tiene tokens.
+pub fn separar_por_comas(tokens: &[Token]) -> Vec<&[Token]> {
+    let mut partes = Vec::new();
+    if tokens.is_empty() {
+        return partes;
+    }
+    let mut profundidad = 0;
+    let mut inicio = 0;
+    for (posicion, token) in tokens.iter().enumerate() {
+        if token.es("(") {
+            profundidad += 1;
+        } else if token.es(")") {
+            profundidad -= 1;
+        } else if token.es(",") && profundidad == 0 {
+            partes.push(&tokens[inicio..posicion]);
+            inicio = posicion + 1;
+        }
+    }
+    partes.push(&tokens[inicio..]);
+    partes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expresion::{Operador, Valor};
+
+    #[test]
+    fn test_parsear_select_de_un_archivo() {
+        let Sentencia::Select(select) =
+            parsear("SELECT * FROM '/datos/Export 2024.csv' WHERE id = 1").unwrap()
+        else {
+            panic!("Se esperaba un SELECT");
+        };
+        assert_eq!(select.tabla, "/datos/Export 2024.csv");
+        assert!(select.archivo);
+        assert!(matches!(
+            parsear("SELECT * FROM ''"),
+            Err(errores::Errores::TokenInesperado { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parsear_select_completo() {
+        let sentencia = parsear(
+            "SELECT nombre, IIF(edad > 30, 'a', 'b') FROM personas WHERE edad > 30 \
+             GROUP BY ALL ORDER BY nombre DESC LIMIT 5",
+        )
+        .unwrap();
+        let Sentencia::Select(select) = sentencia else {
+            panic!("Se esperaba un SELECT");
+        };
+        assert_eq!(
+            select.proyecciones[0],
+            Proyeccion::Expresion {
+                expresion: Expresion::Columna("nombre".to_string()),
+                texto: "nombre".to_string(),
+            }
+        );
+        assert!(matches!(
+            &select.proyecciones[1],
+            Proyeccion::Expresion { texto, .. } if texto == "IIF(edad > 30, 'a', 'b')"
+        ));
+        assert_eq!(select.tabla, "personas");
+        assert!(!select.archivo);
+        assert_eq!(
+            select.restricciones,
+            Some(Expresion::Binaria(
+                Box::new(Expresion::Columna("edad".to_string())),
+                Operador::Mayor,
+                Box::new(Expresion::Literal(Valor::Entero(30)))
+            ))
+        );
+        assert_eq!(select.agrupamiento, Some(Agrupamiento::Todo));
+        assert_eq!(
+            select.criterios_orden,
+            vec![CriterioOrden {
+                expresion: Expresion::Columna("nombre".to_string()),
+                descendente: true,
+                natural: false,
+            }]
+        );
+        assert_eq!(select.limite, Some(5));
+
+        let Ok(Sentencia::Select(todas)) = parsear("select * from personas") else {
+            panic!("Se esperaba un SELECT");
+        };
+        assert_eq!(todas.proyecciones, vec![Proyeccion::Todas]);
+    }
+
+    #[test]
+    fn test_parsear_alias_en_having_y_order_by() {
+        let Sentencia::Select(select) = parsear(
+            "SELECT ciudad, COUNT(*) AS total FROM personas GROUP BY ciudad \
+             HAVING total > 1 ORDER BY total DESC, ciudad",
+        )
+        .unwrap() else {
+            panic!("Se esperaba un SELECT");
+        };
+        assert!(matches!(
+            &select.proyecciones[1],
+            Proyeccion::Expresion { texto, .. } if texto == "total"
+        ));
+        assert_eq!(
+            select.condicion_grupos.unwrap().to_string(),
+            "(COUNT(*) > 1)"
+        );
+        assert_eq!(select.criterios_orden[0].expresion.to_string(), "COUNT(*)");
+        assert_eq!(
+            select.criterios_orden[1].expresion,
+            Expresion::Columna("ciudad".to_string())
+        );
+
+        for invalida in [
+            "SELECT nombre AS FROM personas",
+            "SELECT nombre AS a b FROM personas",
+            "SELECT ROW_NUMBER() OVER (ORDER BY edad) AS n FROM personas HAVING n > 1",
+        ] {
+            assert!(parsear(invalida).is_err(), "{invalida}");
+        }
+    }
+
+    #[test]
+    fn test_parsear_insert() {
+        let sentencia =
+            parsear("INSERT INTO personas (nombre, edad) VALUES ('O''Brien', 30), (NULL, 'null')")
+                .unwrap();
+        assert_eq!(
+            sentencia,
+            Sentencia::Insert(SentenciaInsert {
+                tabla: "personas".to_string(),
+                columnas: vec!["nombre".to_string(), "edad".to_string()],
+                filas: vec![
+                    vec![Some("O'Brien".to_string()), Some("30".to_string())],
+                    vec![None, Some("null".to_string())],
+                ],
+            })
+        );
+
+        let Sentencia::Insert(sentencia) =
+            parsear("INSERT INTO logs VALUES (NOW(), CURRENT_DATE, 'now')").unwrap()
+        else {
+            panic!("no es un INSERT");
+        };
+        let fila = &sentencia.filas[0];
+        assert_eq!(fila[0].as_ref().unwrap().len(), "2024-02-29 14:30:45".len());
+        assert_eq!(fila[1], Some(fecha_actual()));
+        assert_eq!(fila[2], Some("now".to_string()));
+    }
+
+    #[test]
+    fn test_parsear_check_y_repair() {
+        assert_eq!(
+            parsear("CHECK TABLE personas;"),
+            Ok(Sentencia::Revisar(SentenciaRevisar {
+                tabla: "personas".to_string(),
+                reparar: false,
+            }))
+        );
+        assert_eq!(
+            parsear("repair table Personas"),
+            Ok(Sentencia::Revisar(SentenciaRevisar {
+                tabla: "personas".to_string(),
+                reparar: true,
+            }))
+        );
+        assert_eq!(
+            parsear("CHECK personas"),
+            Err(errores::Errores::TokenInesperado {
+                token: "personas".to_string(),
+                posicion: 6
+            })
+        );
+        assert_eq!(
+            parsear("REPAIR TABLE"),
+            Err(errores::Errores::FinInesperado { posicion: 12 })
+        );
+    }
+
+    #[test]
+    fn test_parsear_orden_natural() {
+        let Ok(Sentencia::Select(select)) =
+            parsear("SELECT * FROM archivos ORDER BY nombre NATURAL DESC, natural, id NATURAL")
+        else {
+            panic!("Se esperaba un SELECT");
+        };
+        let criterio = |columna: &str, descendente, natural| CriterioOrden {
+            expresion: Expresion::Columna(columna.to_string()),
+            descendente,
+            natural,
+        };
+        assert_eq!(
+            select.criterios_orden,
+            vec![
+                criterio("nombre", true, true),
+                criterio("natural", false, false),
+                criterio("id", false, true)
+            ]
+        );
+        assert_eq!(select.criterios_orden[0].to_string(), "nombre NATURAL DESC");
+    }
+
+    #[test]
+    fn test_parsear_tablesample() {
+        let muestra = |consulta: &str| match parsear(consulta) {
+            Ok(Sentencia::Select(select)) => Ok(select.muestra),
+            Ok(_) => panic!("no es un SELECT"),
+            Err(error) => Err(error),
+        };
+        assert_eq!(muestra("SELECT * FROM eventos"), Ok(None));
+        assert_eq!(
+            muestra("SELECT * FROM eventos TABLESAMPLE (1 PERCENT) WHERE id > 3"),
+            Ok(Some(Muestra {
+                porcentaje: 1.0,
+                semilla: None
+            }))
+        );
+        assert_eq!(
+            muestra("SELECT * FROM 'eventos.csv' TABLESAMPLE SYSTEM (0.5) REPEATABLE (42)"),
+            Ok(Some(Muestra {
+                porcentaje: 0.5,
+                semilla: Some(42)
+            }))
+        );
+        assert_eq!(
+            muestra("SELECT * FROM eventos TABLESAMPLE (150 PERCENT)"),
+            Err(errores::Errores::TokenInesperado {
+                token: "150".to_string(),
+                posicion: 35
+            })
+        );
+        assert_eq!(
+            muestra("SELECT * FROM eventos TABLESAMPLE 10 PERCENT"),
+            Err(errores::Errores::TokenInesperado {
+                token: "10".to_string(),
+                posicion: 34
+            })
+        );
+    }
+
+    #[test]
+    fn test_parsear_analyze() {
+        assert_eq!(
+            parsear("ANALYZE personas;"),
+            Ok(Sentencia::Analizar("personas".to_string()))
+        );
+        assert_eq!(
+            parsear("analyze table Personas"),
+            Ok(Sentencia::Analizar("personas".to_string()))
+        );
+        assert_eq!(
+            parsear("ANALYZE TABLE"),
+            Err(errores::Errores::FinInesperado { posicion: 13 })
+        );
+    }
+
+    #[test]
+    fn test_parsear_explain() {
+        assert!(matches!(
+            parsear("EXPLAIN SELECT * FROM personas"),
+            Ok(Sentencia::Explicar(explicada)) if matches!(*explicada, Sentencia::Select(_))
+        ));
+    }
+
+    #[test]
+    fn test_escribir_sentencias_en_sql() {
+        for (consulta, escrita) in [
+            (
+                "SELECT nombre, COUNT(*) AS total FROM personas WHERE edad = 3 \
+                 GROUP BY nombre HAVING total > 1 ORDER BY total DESC LIMIT 5",
+                "SELECT nombre, COUNT(*) AS total FROM personas WHERE (edad = 3) \
+                 GROUP BY nombre HAVING (COUNT(*) > 1) ORDER BY COUNT(*) DESC LIMIT 5",
+            ),
+            (
+                "select nombre, 'x' from personas order by edad",
+                "SELECT nombre, 'x' FROM personas ORDER BY edad ASC",
+            ),
+            (
+                "select nombre from 'datos.csv'",
+                "SELECT nombre FROM 'datos.csv'",
+            ),
+            (
+                "select nombre from personas tablesample (10) repeatable (7)",
+                "SELECT nombre FROM personas TABLESAMPLE BERNOULLI (10) REPEATABLE (7)",
+            ),
+            (
+                "INSERT INTO personas (nombre, edad) VALUES ('O''Brien', NULL), ('Ana', 3)",
+                "INSERT INTO personas (nombre, edad) VALUES ('O''Brien', NULL), ('Ana', '3')",
+            ),
+            (
+                "EXPLAIN SELECT * FROM personas",
+                "EXPLAIN SELECT * FROM personas",
+            ),
+            ("REPAIR TABLE personas", "REPAIR TABLE personas"),
+            ("ANALYZE personas", "ANALYZE TABLE personas"),
+            (
+                "COPY personas TO 'p.json' (FORMAT json)",
+                "COPY (SELECT * FROM personas) TO 'p.json' (FORMAT json)",
+            ),
+            (
+                "COPY personas (nombre) FROM STDIN WITH HEADER DELIMITER ';'",
+                "COPY personas (nombre) FROM STDIN (FORMAT csv, HEADER, DELIMITER ';')",
+            ),
+            ("BEGIN TRANSACTION", "BEGIN"),
+        ] {
+            assert_eq!(parsear(consulta).unwrap().to_string(), escrita);
+        }
+    }
+
+    #[test]
+    fn test_parsear_lote() {
+        let sentencias = parsear_lote(
+            "BEGIN TRANSACTION; INSERT INTO personas VALUES ('Ana'); \
+             SELECT * FROM personas WHERE nombre = 'Ana';; COMMIT; ROLLBACK;",
+        )
+        .unwrap();
+        assert_eq!(sentencias.len(), 5);
+        assert_eq!(sentencias[0], Sentencia::Comenzar);
+        assert!(matches!(&sentencias[1], Sentencia::Insert(insert) if insert.filas.len() == 1));
+        assert!(
+            matches!(&sentencias[2], Sentencia::Select(select) if select.restricciones.is_some())
+        );
+        assert_eq!(sentencias[3..], [Sentencia::Confirmar, Sentencia::Deshacer]);
+
+        assert!(matches!(
+            parsear("SELECT * FROM personas;"),
+            Ok(Sentencia::Select(_))
+        ));
+        assert_eq!(parsear_lote("  ; ").unwrap(), vec![]);
+        assert_eq!(
+            parsear_lote("COMMIT ROLLBACK"),
+            Err(errores::Errores::TokenInesperado {
+                token: "rollback".to_string(),
+                posicion: 7
+            })
+        );
+    }
+
+    #[test]
+    fn test_parsear_rechaza_clausulas_mal_formadas() {
+        for consulta in [
+            "SELECT FROM personas",
+            "SELECT nombre personas",
+            "SELECT nombre FROM",
+            "SELECT nombre FROM personas extra",
+            "SELECT nombre FROM personas WHERE",
+            "SELECT nombre FROM personas ORDER nombre",
+            "SELECT nombre FROM personas ORDER BY nombre WHERE edad > 1",
+            "SELECT nombre FROM personas LIMIT 5 ORDER BY nombre",
+            "INSERT INTO personas VALUES",
+            "INSERT INTO personas (nombre VALUES ('Ana')",
+            "INSERT INTO personas VALUES ('Ana') ('Luis')",
+            "DELETE FROM personas",
+            "SELECT * FROM personas; SELECT * FROM personas",
+        ] {
+            assert!(
+                parsear(consulta).unwrap_err().es_de_sintaxis(),
+                "{}",
+                consulta
+            );
+        }
+    }
+
+    #[test]
+    fn test_parsear_copy_to() {
+        let Ok(Sentencia::CopiarA(copia)) = parsear(
+            "COPY (SELECT nombre FROM personas WHERE (edad > 3)) TO 'salida.json' FORMAT JSON",
+        ) else {
+            panic!("se esperaba un COPY");
+        };
+        assert_eq!(copia.consulta.tabla, "personas");
+        assert!(copia.consulta.restricciones.is_some());
+        assert_eq!(copia.archivo, "salida.json");
+        assert_eq!(copia.formato, FormatoSalida::Json);
+        assert!(!copia.encabezado);
+
+        let Ok(Sentencia::CopiarA(copia)) =
+            parsear("copy Personas to '/tmp/p.csv' with (format csv, header);")
+        else {
+            panic!("se esperaba un COPY");
+        };
+        assert_eq!(copia.consulta.tabla, "personas");
+        assert_eq!(copia.consulta.proyecciones, vec![Proyeccion::Todas]);
+        assert_eq!(copia.formato, FormatoSalida::Csv);
+        assert!(copia.encabezado);
+
+        assert_eq!(
+            parsear("COPY personas TO 'p.txt' FORMAT texto"),
+            Err(errores::Errores::TokenInesperado {
+                token: "texto".to_string(),
+                posicion: 32
+            })
+        );
+        assert!(parsear("COPY (SELECT * FROM personas TO 'p.csv'").is_err());
+        assert!(parsear("COPY personas TO p").is_err());
+    }
+
+    #[test]
+    fn test_parsear_copy_from() {
+        let Ok(Sentencia::CopiarDesde(copia)) =
+            parsear("COPY Personas (nombre, edad) FROM 'nuevas.csv' WITH (FORMAT csv, HEADER, DELIMITER ';')")
+        else {
+            panic!("se esperaba un COPY FROM");
+        };
+        assert_eq!(copia.tabla, "personas");
+        assert_eq!(copia.columnas, vec!["nombre", "edad"]);
+        assert_eq!(copia.archivo, "nuevas.csv");
+        assert!(copia.encabezado);
+        assert_eq!(copia.delimitador, Some(';'));
+
+        let Ok(Sentencia::CopiarDesde(copia)) = parsear("copy personas from stdin") else {
+            panic!("se esperaba un COPY FROM");
+        };
+        assert_eq!(copia.archivo, "-");
+        assert!(copia.columnas.is_empty());
+        assert!(!copia.encabezado);
+        assert_eq!(copia.delimitador, None);
+
+        assert!(parsear("COPY personas FROM 'p.json' FORMAT json").is_err());
+        assert!(parsear("COPY personas FROM 'p.csv' DELIMITER ';;'").is_err());
+        assert!(parsear("COPY personas (nombre) TO 'p.csv'").is_err());
+        assert!(parsear("COPY (SELECT * FROM personas) FROM 'p.csv'").is_err());
+    }
+
+    #[test]
+    fn test_error_de_sintaxis_indica_el_token() {
+        assert_eq!(
+            parsear("SELECT nombre FROM personas extra"),
+            Err(errores::Errores::TokenInesperado {
+                token: "extra".to_string(),
+                posicion: 28
+            })
+        );
+        assert_eq!(
+            parsear("SELECT nombre FROM personas WHERE edad > 'x"),
+            Err(errores::Errores::TokenInesperado {
+                token: "'x".to_string(),
+                posicion: 41
+            })
+        );
+        assert_eq!(
+            parsear("SELECT nombre FROM"),
+            Err(errores::Errores::FinInesperado { posicion: 18 })
+        );
+        assert_eq!(
+            parsear("SELECT nombre, FROM personas"),
+            Err(errores::Errores::TokenInesperado {
+                token: "from".to_string(),
+                posicion: 15
+            })
+        );
+        assert_eq!(
+            parsear("SELECT nombre FROM personas WHERE edad > ORDER BY nombre"),
+            Err(errores::Errores::TokenInesperado {
+                token: "order".to_string(),
+                posicion: 41
+            })
+        );
+        assert_eq!(
+            parsear("SELECT nombre FROM personas WHERE (edad > 3"),
+            Err(errores::Errores::FinInesperado { posicion: 43 })
+        );
+        assert_eq!(
+            parsear("INSERT INTO personas VALUES ('Ana',"),
+            Err(errores::Errores::FinInesperado { posicion: 35 })
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use std::io::IsTerminal;
+
+/// Estilo de los encabezados de los resultados: negrita y cian.
+pub const ENCABEZADO: &str = "\x1b[1;36m";
+
+/// Estilo de los mensajes de error: negrita y rojo.
+pub const ERROR: &str = "\x1b[1;31m";
+
+/// Secuencia que vuelve al estilo normal de la terminal.
+const RESTABLECER: &str = "\x1b[0m";
+
+/// Indica si lo que se escribe en el flujo se puede mostrar con colores: el flujo es una
+/// terminal y no se definió la variable de entorno `NO_COLOR` (ver <https://no-color.org>).
+/// Si la salida se redirige a un archivo o a otro programa, se escribe sin colores.
+///
+/// # Parámetros
+/// - `flujo`: La salida estándar o la de errores.
+pub fn usar_color(flujo: &impl IsTerminal) -> bool {
+    flujo.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|valor| valor.is_empty())
+}
+
+/// Envuelve el texto con las secuencias de escape del estilo indicado.
+///
+/// # Parámetros
+/// - `texto`: El texto a resaltar, que no debería contener saltos de línea.
+/// - `estilo`: `ENCABEZADO` o `ERROR`.
+pub fn resaltar(texto: &str, estilo: &str) -> String {
+    format!("{}{}{}", estilo, texto, RESTABLECER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resaltar_y_detectar_terminal() {
+        assert_eq!(resaltar("nombre", ERROR), "\x1b[1;31mnombre\x1b[0m");
+        // Un archivo nunca es una terminal.
+        let archivo = std::fs::File::open("Cargo.toml").unwrap();
+        assert!(!usar_color(&archivo));
+    }
+}
@@ -0,0 +1,835 @@
+use crate::almacenamiento::Almacenamiento;
+use crate::archivo::{self, OpcionesArchivo};
+use crate::consulta::SQLConsulta;
+use crate::errores;
+use crate::esquema::Esquema;
+use crate::estadisticas::Estadisticas;
+use crate::registro;
+use crate::resolvedor::ResolvedorTablas;
+use crate::salida::OpcionesSalida;
+use crate::sintaxis::{self, Sentencia};
+use std::io::{self, BufRead, Write};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+/// Transacción abierta con `BEGIN`.
+///
+/// Envuelve el almacenamiento de las tablas: la primera vez que una sentencia modifica una
+/// tabla se crea una copia de trabajo (ver `Almacenamiento::copiar`) y desde ese momento la
+/// tabla se lee y se modifica en la copia. `confirmar` reemplaza las tablas por sus copias y
+/// `deshacer` las descarta; si la transacción se descarta sin confirmarse, se deshace.
+///
+/// Dentro de una transacción se puede abrir otra, que modifica copias de las copias de trabajo
+/// y al confirmarse las reemplaza (ver `simular_sentencias`).
+#[derive(Debug)]
+pub struct Transaccion {
+    base: Arc<dyn Almacenamiento>,
+    copias: Mutex<Vec<(String, String)>>,
+    /// Copias de trabajo de las transacciones abiertas dentro de esta, que ya son copias y se
+    /// modifican directamente.
+    ajenas: Mutex<Vec<String>>,
+    /// El texto agregado a cada tabla o copia ajena, si se registra (ver `Transaccion::simular`).
+    agregados: Option<Mutex<Vec<(String, String)>>>,
+}
+
+impl Transaccion {
+    /// Abre una transacción sobre el almacenamiento indicado.
+    pub fn new(base: Arc<dyn Almacenamiento>) -> Transaccion {
+        Transaccion {
+            base,
+            copias: Mutex::new(Vec::new()),
+            ajenas: Mutex::new(Vec::new()),
+            agregados: None,
+        }
+    }
+
+    /// Abre una transacción que además registra los registros agregados a cada tabla, para
+    /// informarlos con `agregados` antes de deshacerla.
+    pub fn simular(base: Arc<dyn Almacenamiento>) -> Transaccion {
+        let mut transaccion = Transaccion::new(base);
+        transaccion.agregados = Some(Mutex::new(Vec::new()));
+        transaccion
+    }
+
+    /// Devuelve el texto agregado a cada tabla, en el orden en que se modificaron; vacío si la
+    /// transacción no se abrió con `simular`.
+    pub fn agregados(&self) -> Vec<(String, String)> {
+        match &self.agregados {
+            Some(agregados) => bloquear(agregados).clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Reemplaza todas las tablas modificadas por sus copias de trabajo (ver
+    /// `Almacenamiento::reemplazar`).
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el error que impidió reemplazar las tablas.
+    pub fn confirmar(&self) -> Result<(), errores::Errores> {
+        let copias = std::mem::take(&mut *self.bloquear());
+        self.base.reemplazar(&copias)
+    }
+
+    /// Guarda un respaldo de cada tabla modificada antes de confirmar la transacción (ver
+    /// `Almacenamiento::respaldar`).
+    ///
+    /// # Retorno
+    /// Las rutas de los respaldos creados, o el error que impidió crear alguno.
+    pub fn respaldar(&self) -> Result<Vec<String>, errores::Errores> {
+        let mut respaldos = Vec::new();
+        for (ruta, _) in self.bloquear().clone() {
+            respaldos.extend(self.base.respaldar(&ruta)?);
+        }
+        Ok(respaldos)
+    }
+
+    /// Descarta las copias de trabajo, dejando las tablas como estaban al comenzar.
+    ///
+    /// # Retorno
+    /// Retorna `Ok(())` o el primer error al descartar una copia; el resto se descartan igual.
+    pub fn deshacer(&self) -> Result<(), errores::Errores> {
+        let copias = std::mem::take(&mut *self.bloquear());
+        let mut resultado = Ok(());
+        for (ruta, ruta_copia) in copias {
+            if let Err(error) = self.base.descartar(&ruta, &ruta_copia) {
+                if resultado.is_ok() {
+                    resultado = Err(error);
+                }
+            }
+        }
+        resultado
+    }
+
+    /// Devuelve la ruta de la copia de trabajo de la tabla, si ya fue modificada.
+    fn ruta_copia(&self, ruta: &str) -> Option<String> {
+        self.bloquear()
+            .iter()
+            .find(|(ruta_tabla, _)| ruta_tabla == ruta)
+            .map(|(_, ruta_copia)| ruta_copia.to_string())
+    }
+
+    /// Devuelve la ruta en la que se modifica la tabla: su copia de trabajo, que se crea la
+    /// primera vez, o la misma ruta si es la copia de una transacción abierta dentro de esta.
+    fn copia_de_trabajo(&self, ruta: &str) -> Result<String, errores::Errores> {
+        if let Some(ruta_copia) = self.ruta_copia(ruta) {
+            return Ok(ruta_copia);
+        }
+        if bloquear(&self.ajenas).iter().any(|ajena| ajena == ruta) {
+            return Ok(ruta.to_string());
+        }
+        let ruta_copia = self.base.copiar(ruta)?;
+        self.bloquear()
+            .push((ruta.to_string(), ruta_copia.to_string()));
+        Ok(ruta_copia)
+    }
+
+    /// Registra el texto agregado a la tabla o copia ajena, si la transacción lo registra.
+    fn registrar_agregado(&self, ruta: &str, lineas: &str) {
+        let Some(agregados) = &self.agregados else {
+            return;
+        };
+        let mut agregados = bloquear(agregados);
+        match agregados.iter_mut().find(|(agregada, _)| agregada == ruta) {
+            Some((_, texto)) => texto.push_str(lineas),
+            None => agregados.push((ruta.to_string(), lineas.to_string())),
+        }
+    }
+
+    /// Quita el texto agregado a una copia ajena: se suma al de la tabla si la transacción
+    /// interna se confirmó, o se olvida si se deshizo.
+    fn resolver_ajena(&self, ruta: &str, ruta_copia: &str, confirmada: bool) {
+        bloquear(&self.ajenas).retain(|ajena| ajena != ruta_copia);
+        let Some(agregados) = &self.agregados else {
+            return;
+        };
+        let mut agregados = bloquear(agregados);
+        let Some(posicion) = agregados
+            .iter()
+            .position(|(agregada, _)| agregada == ruta_copia)
+        else {
+            return;
+        };
+        let (_, lineas) = agregados.remove(posicion);
+        drop(agregados);
+        if confirmada {
+            self.registrar_agregado(ruta, &lineas);
+        }
+    }
+
+    fn bloquear(&self) -> MutexGuard<'_, Vec<(String, String)>> {
+        bloquear(&self.copias)
+    }
+}
+
+fn bloquear<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|envenenado| envenenado.into_inner())
+}
+
+impl Drop for Transaccion {
+    fn drop(&mut self) {
+        // Una transacción que no se confirmó no deja copias de trabajo.
+        let _ = self.deshacer();
+    }
+}
+
+impl Almacenamiento for Transaccion {
+    fn leer(&self, ruta: &str, opciones: &OpcionesArchivo) -> io::Result<Box<dyn BufRead>> {
+        match self.ruta_copia(ruta) {
+            Some(ruta_copia) => self.base.leer(&ruta_copia, opciones),
+            None => self.base.leer(ruta, opciones),
+        }
+    }
+
+    fn agregar(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        lineas: &str,
+    ) -> Result<(), errores::Errores> {
+        let ruta_copia = self.copia_de_trabajo(ruta)?;
+        self.base.agregar(&ruta_copia, opciones, lineas)?;
+        self.registrar_agregado(ruta, lineas);
+        Ok(())
+    }
+
+    fn reescribir(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores> {
+        let ruta_copia = self.copia_de_trabajo(ruta)?;
+        self.base.reescribir(&ruta_copia, opciones, contenido)
+    }
+
+    /// Las tablas nuevas no forman parte de la transacción: se crean enseguida en el
+    /// almacenamiento base, y no se eliminan al deshacerla.
+    fn crear(
+        &self,
+        ruta: &str,
+        opciones: &OpcionesArchivo,
+        contenido: &str,
+    ) -> Result<(), errores::Errores> {
+        self.base.crear(ruta, opciones, contenido)
+    }
+
+    fn esquema(&self, ruta: &str) -> Result<Esquema, errores::Errores> {
+        self.base.esquema(ruta)
+    }
+
+    fn estadisticas(&self, ruta: &str) -> Result<Option<Estadisticas>, errores::Errores> {
+        self.base.estadisticas(ruta)
+    }
+
+    /// Las estadísticas no forman parte de la transacción: son solo una estimación, así que se
+    /// guardan enseguida y no se descartan al deshacerla.
+    fn guardar_estadisticas(
+        &self,
+        ruta: &str,
+        estadisticas: &Estadisticas,
+    ) -> Result<(), errores::Errores> {
+        self.base.guardar_estadisticas(ruta, estadisticas)
+    }
+
+    fn copiar(&self, ruta: &str) -> Result<String, errores::Errores> {
+        let ruta_copia = match self.ruta_copia(ruta) {
+            Some(ruta_copia) => self.base.copiar(&ruta_copia)?,
+            None => self.base.copiar(ruta)?,
+        };
+        bloquear(&self.ajenas).push(ruta_copia.to_string());
+        Ok(ruta_copia)
+    }
+
+    /// Una transacción abierta dentro de esta (un `BEGIN` durante una simulación) se confirma
+    /// sobre las copias de trabajo: reemplaza las que ya existían y las demás pasan a ser las
+    /// copias de esta transacción, sin tocar las tablas.
+    fn reemplazar(&self, copias: &[(String, String)]) -> Result<(), errores::Errores> {
+        let mut propias = Vec::new();
+        for (ruta, ruta_copia) in copias {
+            self.resolver_ajena(ruta, ruta_copia, true);
+            match self.ruta_copia(ruta) {
+                Some(propia) => propias.push((propia, ruta_copia.to_string())),
+                None => self
+                    .bloquear()
+                    .push((ruta.to_string(), ruta_copia.to_string())),
+            }
+        }
+        self.base.reemplazar(&propias)
+    }
+
+    fn descartar(&self, ruta: &str, ruta_copia: &str) -> Result<(), errores::Errores> {
+        self.resolver_ajena(ruta, ruta_copia, false);
+        match self.ruta_copia(ruta) {
+            Some(propia) => self.base.descartar(&propia, ruta_copia),
+            None => self.base.descartar(ruta, ruta_copia),
+        }
+    }
+
+    /// Una transacción no reemplaza las tablas, así que no hace falta respaldarlas.
+    fn respaldar(&self, _ruta: &str) -> Result<Option<String>, errores::Errores> {
+        Ok(None)
+    }
+}
+
+/// Resolvedor de las sentencias de una transacción: resuelve las tablas con el resolvedor
+/// original, pero las lee y modifica a través de la transacción.
+#[derive(Debug)]
+struct ResolvedorTransaccion<'a> {
+    resolvedor: &'a dyn ResolvedorTablas,
+    transaccion: Arc<Transaccion>,
+}
+
+impl ResolvedorTablas for ResolvedorTransaccion<'_> {
+    fn resolver(&self, tabla: &str) -> Result<String, errores::Errores> {
+        self.resolvedor.resolver(tabla)
+    }
+
+    fn almacenamiento(&self) -> Arc<dyn Almacenamiento> {
+        self.transaccion.clone()
+    }
+
+    fn tablas(&self) -> Result<Vec<String>, errores::Errores> {
+        self.resolvedor.tablas()
+    }
+}
+
+/// Ejecuta un lote de sentencias separadas por `;`, en orden.
+///
+/// Las sentencias entre `BEGIN` y `COMMIT` se aplican todas juntas al confirmar; `ROLLBACK`
+/// descarta sus cambios. Con `OpcionesArchivo::respaldo`, antes de confirmar se guarda un
+/// respaldo de cada tabla que se reemplaza. Si una sentencia de la transacción falla, o el lote termina sin
+/// `COMMIT`, la transacción se deshace. Fuera de una transacción, cada sentencia se aplica al
+/// ejecutarse.
+///
+/// Con `OpcionesSalida::resumen`, al terminar cada consulta se escribe en la salida de errores
+/// una línea como `-- 42 filas (0.012 s)`.
+///
+/// Con `OpcionesSalida::paralelo`, las consultas de selección consecutivas del lote se ejecutan
+/// al mismo tiempo (ver `ejecutar_en_paralelo`).
+///
+/// # Parámetros
+/// - `consultas`: El texto de las sentencias.
+/// - `resolvedor`: El resolvedor que indica dónde se almacenan las tablas.
+/// - `opciones`: Las opciones con las que se leen y escriben los archivos de las tablas.
+/// - `opciones_salida`: El formato con el que se escriben los resultados de cada sentencia.
+/// - `destino`: Donde se escriben los resultados. Se vacía al terminar el lote, también si
+///   una sentencia falla, de modo que los resultados anteriores al error se ven completos.
+///
+/// # Retorno
+/// Retorna `Ok(())` si todas las sentencias se ejecutaron, el error de sintaxis del lote (en
+/// ese caso no se ejecuta ninguna), `Errores::TransaccionInvalida` si `BEGIN`, `COMMIT` o
+/// `ROLLBACK` no corresponden, o el error de la primera sentencia que falló.
+pub fn ejecutar_sentencias<W: Write>(
+    consultas: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    opciones_salida: &OpcionesSalida,
+    destino: &mut W,
+) -> Result<(), errores::Errores> {
+    let resultado = ejecutar_lote(consultas, resolvedor, opciones, opciones_salida, destino);
+    let vaciado = destino.flush();
+    resultado?;
+    Ok(vaciado?)
+}
+
+/// Ejecuta las sentencias del lote para `ejecutar_sentencias`, que vacía el destino al
+/// terminar.
+fn ejecutar_lote<W: Write>(
+    consultas: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    opciones_salida: &OpcionesSalida,
+    destino: &mut W,
+) -> Result<(), errores::Errores> {
+    let mut transaccion: Option<Arc<Transaccion>> = None;
+    let mut sentencias = sintaxis::parsear_lote(consultas)?
+        .into_iter()
+        .enumerate()
+        .peekable();
+    while let Some((numero, sentencia)) = sentencias.next() {
+        registro::registrar(registro::DEPURACION, || {
+            format!("sentencia {}: {}", numero + 1, sentencia)
+        });
+        match sentencia {
+            Sentencia::Comenzar => {
+                if transaccion.is_some() {
+                    return Err(errores::Errores::TransaccionInvalida(
+                        "ya hay una transacción en curso".to_string(),
+                    ));
+                }
+                transaccion = Some(Arc::new(Transaccion::new(resolvedor.almacenamiento())));
+            }
+            Sentencia::Confirmar | Sentencia::Deshacer => {
+                let Some(en_curso) = transaccion.take() else {
+                    return Err(errores::Errores::TransaccionInvalida(
+                        "no hay una transacción en curso".to_string(),
+                    ));
+                };
+                if sentencia == Sentencia::Confirmar {
+                    if opciones.respaldo {
+                        for respaldo in en_curso.respaldar()? {
+                            registro::registrar(registro::DETALLE, || {
+                                format!("respaldo guardado en '{}'", respaldo)
+                            });
+                        }
+                    }
+                    en_curso.confirmar()?;
+                } else {
+                    en_curso.deshacer()?;
+                }
+            }
+            sentencia
+                if opciones_salida.paralelo
+                    && es_de_seleccion(&sentencia)
+                    && sentencias
+                        .peek()
+                        .is_some_and(|(_, siguiente)| es_de_seleccion(siguiente)) =>
+            {
+                let mut grupo = vec![(numero, sentencia)];
+                while let Some(siguiente) =
+                    sentencias.next_if(|(_, siguiente)| es_de_seleccion(siguiente))
+                {
+                    grupo.push(siguiente);
+                }
+                match &transaccion {
+                    Some(en_curso) => ejecutar_en_paralelo(
+                        grupo,
+                        &ResolvedorTransaccion {
+                            resolvedor,
+                            transaccion: en_curso.clone(),
+                        },
+                        opciones,
+                        opciones_salida,
+                        destino,
+                    )?,
+                    None => {
+                        ejecutar_en_paralelo(grupo, resolvedor, opciones, opciones_salida, destino)?
+                    }
+                }
+            }
+            sentencia => {
+                let inicio = Instant::now();
+                let mut consulta = match &transaccion {
+                    Some(en_curso) => SQLConsulta::desde_sentencia(
+                        sentencia,
+                        &ResolvedorTransaccion {
+                            resolvedor,
+                            transaccion: en_curso.clone(),
+                        },
+                        opciones,
+                    )?,
+                    None => SQLConsulta::desde_sentencia(sentencia, resolvedor, opciones)?,
+                };
+                let mut escritor = opciones_salida.crear_escritor(&mut *destino);
+                let resumen = consulta.procesar_consulta(escritor.as_mut())?;
+                if opciones_salida.resumen {
+                    // Los resultados se escriben antes que el resumen que los sigue.
+                    drop(escritor);
+                    destino.flush()?;
+                    eprintln!("-- {} ({:.3} s)", resumen, inicio.elapsed().as_secs_f64());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Indica si la sentencia solo lee las tablas y muestra un resultado, de modo que se puede
+/// ejecutar al mismo tiempo que otras como ella.
+fn es_de_seleccion(sentencia: &Sentencia) -> bool {
+    matches!(sentencia, Sentencia::Select(_) | Sentencia::Explicar(_))
+}
+
+/// Ejecuta al mismo tiempo, cada una en su hilo, las consultas de selección consecutivas de un
+/// lote. Cada consulta escribe su resultado aparte y, apenas termina, se escribe completo en el
+/// destino con cada línea precedida por el número de la sentencia en el lote, como
+/// `[2] Ana,40`; así los resultados quedan intercalados en el orden en que terminan, pero cada
+/// uno se puede separar de los demás.
+///
+/// # Parámetros
+/// - `grupo`: Las sentencias, junto con su posición en el lote, empezando en `0`.
+/// - `resolvedor`, `opciones`, `opciones_salida`, `destino`: Los mismos que en
+///   `ejecutar_sentencias`.
+///
+/// # Retorno
+/// Retorna `Ok(())` si todas las consultas se ejecutaron, o el error de la primera del grupo
+/// que falló; las demás se ejecutan igual y muestran sus resultados.
+fn ejecutar_en_paralelo<W: Write>(
+    grupo: Vec<(usize, Sentencia)>,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    opciones_salida: &OpcionesSalida,
+    destino: &mut W,
+) -> Result<(), errores::Errores> {
+    let (emisor, receptor) = mpsc::channel();
+    thread::scope(|ambito| {
+        for (numero, sentencia) in grupo {
+            let emisor = emisor.clone();
+            ambito.spawn(move || {
+                let inicio = Instant::now();
+                let mut resultado = Vec::new();
+                let resumen = SQLConsulta::desde_sentencia(sentencia, resolvedor, opciones)
+                    .and_then(|mut consulta| {
+                        let mut escritor = opciones_salida.crear_escritor(&mut resultado);
+                        consulta.procesar_consulta(escritor.as_mut())
+                    });
+                // El receptor sigue esperando mientras quede algún hilo.
+                let _ = emisor.send((numero, resultado, resumen, inicio.elapsed()));
+            });
+        }
+        drop(emisor);
+
+        let mut primer_error: Option<(usize, errores::Errores)> = None;
+        for (numero, resultado, resumen, duracion) in receptor {
+            for linea in resultado.split_inclusive(|byte| *byte == b'\n') {
+                write!(destino, "[{}] ", numero + 1)?;
+                destino.write_all(linea)?;
+            }
+            destino.flush()?;
+            match resumen {
+                Ok(resumen) if opciones_salida.resumen => eprintln!(
+                    "-- [{}] {} ({:.3} s)",
+                    numero + 1,
+                    resumen,
+                    duracion.as_secs_f64()
+                ),
+                Ok(_) => {}
+                Err(error) => {
+                    if primer_error
+                        .as_ref()
+                        .is_none_or(|(primero, _)| numero < *primero)
+                    {
+                        primer_error = Some((numero, error));
+                    }
+                }
+            }
+        }
+        match primer_error {
+            Some((_, error)) => Err(error),
+            None => Ok(()),
+        }
+    })
+}
+
+/// Ejecuta un lote de sentencias como `ejecutar_sentencias`, pero sin modificar las tablas:
+/// todo el lote se ejecuta dentro de una transacción que se deshace al terminar, aunque el lote
+/// confirme las suyas. Las consultas muestran sus resultados como si los cambios anteriores del
+/// lote se hubieran aplicado.
+///
+/// Al terminar se informa, por cada tabla, cuántos registros se le agregarían y cuáles.
+///
+/// # Parámetros
+/// - `consultas`, `resolvedor`, `opciones`, `opciones_salida`, `destino`: Los mismos que en
+///   `ejecutar_sentencias`.
+/// - `informe`: Donde se escriben los registros que se agregarían a cada tabla.
+///
+/// # Retorno
+/// Retorna `Ok(())` o el error del lote, igual que `ejecutar_sentencias`; si el lote falla no se
+/// informa ningún cambio.
+pub fn simular_sentencias<W: Write>(
+    consultas: &str,
+    resolvedor: &dyn ResolvedorTablas,
+    opciones: &OpcionesArchivo,
+    opciones_salida: &OpcionesSalida,
+    destino: &mut W,
+    informe: &mut dyn Write,
+) -> Result<(), errores::Errores> {
+    let simulacion = Arc::new(Transaccion::simular(resolvedor.almacenamiento()));
+    ejecutar_sentencias(
+        consultas,
+        &ResolvedorTransaccion {
+            resolvedor,
+            transaccion: simulacion.clone(),
+        },
+        opciones,
+        opciones_salida,
+        destino,
+    )?;
+    destino.flush()?;
+
+    let agregados = simulacion.agregados();
+    if agregados.is_empty() {
+        writeln!(informe, "-- simulación: no se modificaría ninguna tabla")?;
+    }
+    for (ruta, lineas) in agregados {
+        let mut lector = io::Cursor::new(lineas);
+        let mut registros = Vec::new();
+        while let Some(registro) = archivo::leer_registro(&mut lector)? {
+            registros.push(registro);
+        }
+        match registros.len() {
+            1 => writeln!(
+                informe,
+                "-- simulación: se agregaría 1 registro a '{}'",
+                ruta
+            )?,
+            cantidad => writeln!(
+                informe,
+                "-- simulación: se agregarían {} registros a '{}'",
+                cantidad, ruta
+            )?,
+        }
+        for registro in registros {
+            writeln!(informe, "{}", registro)?;
+        }
+    }
+    simulacion.deshacer()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::almacenamiento::TablasEnMemoria;
+    use std::io::BufWriter;
+
+    fn ejecutar(tablas: &TablasEnMemoria, consultas: &str) -> Result<String, errores::Errores> {
+        let mut destino = Vec::new();
+        ejecutar_sentencias(
+            consultas,
+            tablas,
+            &OpcionesArchivo::default(),
+            &OpcionesSalida::default(),
+            &mut destino,
+        )?;
+        Ok(String::from_utf8(destino).unwrap())
+    }
+
+    fn crear_tablas() -> TablasEnMemoria {
+        let tablas = TablasEnMemoria::new();
+        tablas.agregar_tabla("personas", "nombre\nAna\n");
+        tablas.agregar_tabla("ventas", "producto\n");
+        tablas
+    }
+
+    #[test]
+    fn test_registro_por_nivel_de_verbosidad() {
+        let consultas = "SELECT nombre FROM personas WHERE nombre = 'Ana' AND nombre <> 'Luis'; \
+                         INSERT INTO ventas VALUES ('mesa')";
+        let registrar = |nivel| {
+            let tablas = crear_tablas();
+            registro::capturar(nivel, || {
+                ejecutar(&tablas, consultas).unwrap();
+            })
+        };
+        assert_eq!(
+            registrar(registro::DEPURACION),
+            vec![
+                "[registro] sentencia 1: SELECT nombre FROM personas \
+                 WHERE ((nombre = 'Ana') AND (nombre != 'Luis'))",
+                "[registro] tabla 'personas' en 'personas'",
+                "[registro] WHERE: ((nombre = 'Ana') AND (nombre != 'Luis'))",
+                "[registro] 1 filas en el resultado",
+                "[registro] sentencia 2: INSERT INTO ventas VALUES ('mesa')",
+                "[registro] tabla 'ventas' en 'ventas'",
+                "[registro] 1 registros agregados",
+            ]
+        );
+        assert_eq!(
+            registrar(registro::DETALLE),
+            vec![
+                "[registro] tabla 'personas' en 'personas'",
+                "[registro] 1 filas en el resultado",
+                "[registro] tabla 'ventas' en 'ventas'",
+                "[registro] 1 registros agregados",
+            ]
+        );
+        assert!(registrar(0).is_empty());
+    }
+
+    #[test]
+    fn test_salida_completa_en_orden_y_vaciada_aunque_falle_una_sentencia() {
+        let tablas = crear_tablas();
+        let numeros: String = (1..=3000).map(|numero| format!("{}\n", numero)).collect();
+        tablas.agregar_tabla("numeros", &format!("numero\n{}", numeros));
+        let mut salida = Vec::new();
+        let mut destino = BufWriter::new(&mut salida);
+        let resultado = ejecutar_sentencias(
+            "SELECT * FROM numeros; SELECT * FROM personas; SELECT sueldo FROM personas; \
+             SELECT * FROM ventas",
+            &tablas,
+            &OpcionesArchivo::default(),
+            &OpcionesSalida::default(),
+            &mut destino,
+        );
+        assert!(matches!(
+            resultado,
+            Err(errores::Errores::ColumnaDesconocida { .. })
+        ));
+        // Todo lo escrito llegó al destino sin esperar a que se descarte el buffer.
+        assert!(destino.buffer().is_empty());
+        assert_eq!(
+            String::from_utf8(destino.get_ref().to_vec()).unwrap(),
+            format!("numero\n{}nombre\nAna\n", numeros)
+        );
+    }
+
+    #[test]
+    fn test_commit_aplica_todos_los_cambios() {
+        let tablas = crear_tablas();
+        let salida = ejecutar(
+            &tablas,
+            "BEGIN; INSERT INTO personas VALUES ('Luis'); INSERT INTO ventas VALUES ('mesa'); \
+             SELECT * FROM personas; COMMIT; SELECT * FROM ventas",
+        )
+        .unwrap();
+        assert_eq!(salida, "nombre\nAna\nLuis\nproducto\nmesa\n");
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\nLuis\n");
+        assert_eq!(tablas.contenido("personas#copia"), None);
+    }
+
+    #[test]
+    fn test_rollback_y_errores_descartan_los_cambios() {
+        let tablas = crear_tablas();
+        ejecutar(
+            &tablas,
+            "BEGIN; INSERT INTO personas VALUES ('Luis'); ROLLBACK; \
+             INSERT INTO ventas VALUES ('mesa')",
+        )
+        .unwrap();
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\n");
+        assert_eq!(tablas.contenido("ventas").unwrap(), "producto\nmesa\n");
+
+        assert_eq!(
+            ejecutar(
+                &tablas,
+                "BEGIN; INSERT INTO personas VALUES ('Luis'); INSERT INTO clientes VALUES ('x'); \
+                 COMMIT"
+            ),
+            Err(errores::Errores::InvalidTable("clientes".to_string()))
+        );
+        ejecutar(&tablas, "BEGIN; INSERT INTO personas VALUES ('Eva')").unwrap();
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\n");
+        assert_eq!(tablas.contenido("personas#copia"), None);
+    }
+
+    #[test]
+    fn test_commit_con_respaldo() {
+        let tablas = crear_tablas();
+        let opciones = OpcionesArchivo {
+            respaldo: true,
+            ..OpcionesArchivo::default()
+        };
+        let antes = crate::fecha::marca_de_tiempo_actual();
+        ejecutar_sentencias(
+            "BEGIN; INSERT INTO personas VALUES ('Luis'); COMMIT",
+            &tablas,
+            &opciones,
+            &OpcionesSalida::default(),
+            &mut Vec::new(),
+        )
+        .unwrap();
+        let despues = crate::fecha::marca_de_tiempo_actual();
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\nLuis\n");
+        // El respaldo tiene el contenido de la tabla antes del COMMIT.
+        let respaldo = [antes, despues]
+            .iter()
+            .find_map(|marca| tablas.contenido(&format!("personas.bak-{}", marca)));
+        assert_eq!(respaldo.as_deref(), Some("nombre\nAna\n"));
+    }
+
+    #[test]
+    fn test_script_se_detiene_en_el_primer_error() {
+        let tablas = crear_tablas();
+        let script = "INSERT INTO personas VALUES ('Luis');\n\
+                      INSERT INTO clientes VALUES ('x');\n\
+                      INSERT INTO personas VALUES ('Eva');\n";
+        assert_eq!(
+            ejecutar(&tablas, script),
+            Err(errores::Errores::InvalidTable("clientes".to_string()))
+        );
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\nLuis\n");
+    }
+
+    #[test]
+    fn test_selects_consecutivos_en_paralelo() {
+        let tablas = crear_tablas();
+        let opciones_salida = OpcionesSalida {
+            paralelo: true,
+            ..OpcionesSalida::default()
+        };
+        let ejecutar_en_paralelo = |consultas: &str| {
+            let mut destino = Vec::new();
+            ejecutar_sentencias(
+                consultas,
+                &tablas,
+                &OpcionesArchivo::default(),
+                &opciones_salida,
+                &mut destino,
+            )
+            .map(|_| String::from_utf8(destino).unwrap())
+        };
+
+        let salida = ejecutar_en_paralelo(
+            "INSERT INTO ventas VALUES ('mesa'); SELECT * FROM personas; \
+             SELECT producto FROM ventas",
+        )
+        .unwrap();
+        // Los resultados llegan en el orden en que terminan las consultas.
+        let mut lineas: Vec<&str> = salida.lines().collect();
+        lineas.sort();
+        assert_eq!(
+            lineas,
+            vec!["[2] Ana", "[2] nombre", "[3] mesa", "[3] producto"]
+        );
+        assert_eq!(
+            ejecutar_en_paralelo("SELECT * FROM personas").unwrap(),
+            "nombre\nAna\n"
+        );
+
+        let resultado = ejecutar_en_paralelo(
+            "SELECT * FROM personas; SELECT * FROM clientes; SELECT * FROM ventas; \
+             INSERT INTO personas VALUES ('Luis')",
+        );
+        assert_eq!(
+            resultado,
+            Err(errores::Errores::InvalidTable("clientes".to_string()))
+        );
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\n");
+    }
+
+    #[test]
+    fn test_simular_no_modifica_las_tablas() {
+        let tablas = crear_tablas();
+        let mut destino = Vec::new();
+        let mut informe = Vec::new();
+        simular_sentencias(
+            "INSERT INTO personas VALUES ('Luis'); BEGIN; INSERT INTO personas VALUES ('Eva'); \
+             INSERT INTO ventas VALUES ('mesa'); COMMIT; SELECT * FROM personas",
+            &tablas,
+            &OpcionesArchivo::default(),
+            &OpcionesSalida::default(),
+            &mut destino,
+            &mut informe,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(destino).unwrap(),
+            "nombre\nAna\nLuis\nEva\n"
+        );
+        let informe = String::from_utf8(informe).unwrap();
+        assert!(
+            informe.contains("-- simulación: se agregarían 2 registros a 'personas'\nLuis\nEva\n")
+        );
+        assert!(informe.contains("-- simulación: se agregaría 1 registro a 'ventas'\nmesa\n"));
+        assert_eq!(tablas.contenido("personas").unwrap(), "nombre\nAna\n");
+        assert_eq!(tablas.contenido("ventas").unwrap(), "producto\n");
+        assert_eq!(tablas.contenido("personas#copia"), None);
+        assert_eq!(tablas.contenido("personas#copia#copia"), None);
+    }
+
+    #[test]
+    fn test_sentencias_de_transaccion_fuera_de_lugar() {
+        let tablas = crear_tablas();
+        assert!(matches!(
+            ejecutar(&tablas, "COMMIT"),
+            Err(errores::Errores::TransaccionInvalida(_))
+        ));
+        assert!(matches!(
+            ejecutar(&tablas, "BEGIN; BEGIN"),
+            Err(errores::Errores::TransaccionInvalida(_))
+        ));
+    }
+}
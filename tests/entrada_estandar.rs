@@ -0,0 +1,67 @@
+//! Pruebas de punta a punta de las tablas leídas de la entrada estándar (`FROM -` y
+//! `FROM stdin`): se ejecuta el programa enviándole el CSV por una tubería, como en
+//! `cat export.csv | base_de_datos tablas "SELECT ... FROM -"`.
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Ejecuta el programa con los argumentos indicados y le envía `entrada` por la entrada
+/// estándar, que se cierra después.
+fn ejecutar(argumentos: &[&str], entrada: &str) -> Output {
+    let mut proceso = Command::new(env!("CARGO_BIN_EXE_base_de_datos"))
+        .args(argumentos)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut tuberia = proceso.stdin.take().unwrap();
+    tuberia.write_all(entrada.as_bytes()).unwrap();
+    drop(tuberia);
+    proceso.wait_with_output().unwrap()
+}
+
+const EXPORTACION: &str = "nombre,edad,ciudad\nAna,40,Lugo\nLuis,25,Vigo\nEva,33,Lugo\n";
+
+#[test]
+fn test_consultar_la_tabla_de_la_entrada_estandar() {
+    let salida = ejecutar(
+        &[
+            "tablas",
+            "SELECT nombre, edad FROM - WHERE ciudad = 'Lugo' ORDER BY edad",
+        ],
+        EXPORTACION,
+    );
+    assert!(salida.status.success(), "{:?}", salida);
+    assert_eq!(
+        String::from_utf8(salida.stdout).unwrap(),
+        "nombre,edad\nEva,33\nAna,40\n"
+    );
+    assert!(salida.stderr.is_empty());
+}
+
+#[test]
+fn test_la_entrada_estandar_se_lee_una_vez_para_todo_el_lote() {
+    let salida = ejecutar(
+        &[
+            "tablas",
+            "SELECT COUNT(*) FROM stdin; SELECT nombre FROM - WHERE edad < 30",
+        ],
+        EXPORTACION,
+    );
+    assert!(salida.status.success(), "{:?}", salida);
+    assert_eq!(
+        String::from_utf8(salida.stdout).unwrap(),
+        "COUNT(*)\n3\nnombre\nLuis\n"
+    );
+}
+
+#[test]
+fn test_detectar_el_delimitador_de_la_entrada_estandar() {
+    let salida = ejecutar(
+        &["tablas", "SELECT ciudad FROM - WHERE nombre = 'Eva'"],
+        &EXPORTACION.replace(',', ";"),
+    );
+    assert!(salida.status.success(), "{:?}", salida);
+    assert_eq!(String::from_utf8(salida.stdout).unwrap(), "ciudad\nLugo\n");
+}